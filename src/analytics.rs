@@ -0,0 +1,168 @@
+//! Local usage patterns for the usage dashboard: which profiles and models are
+//! launched, at which hours and on which weekdays.
+//!
+//! Nothing is collected unless `[analytics] enabled = true` is set in `profiles.toml`,
+//! and `CLAUDE_PROFILER_NO_ANALYTICS=1` turns collection off whatever the config says,
+//! for machines where it must never run. Only aggregate counts are kept, in
+//! `analytics.json` next to the config; there are no timestamps or per-session records,
+//! and nothing is ever sent anywhere.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub use crate::config::ENV_NO_ANALYTICS;
+use crate::config::{AnalyticsPolicy, Config};
+
+pub const ANALYTICS_FILE: &str = "analytics.json";
+
+/// Weekday names, Monday first
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Launch counts, aggregated so no single session can be picked out
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Analytics {
+    pub launches: u64,
+    /// Profile name -> launches
+    pub profiles: BTreeMap<String, u64>,
+    /// Main model -> launches; profiles without a model mapping are not counted here
+    pub models: BTreeMap<String, u64>,
+    /// Launches per UTC hour, 0 to 23
+    pub hours: [u64; 24],
+    /// Launches per weekday (UTC), Monday first
+    pub weekdays: [u64; 7],
+    /// Profile name -> minutes spent in sessions
+    pub minutes: BTreeMap<String, u64>,
+}
+
+fn analytics_file() -> Option<PathBuf> {
+    Config::config_dir().map(|dir| dir.join(ANALYTICS_FILE))
+}
+
+impl Analytics {
+    /// The saved counts; empty when there are none
+    pub fn load() -> Self {
+        analytics_file()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = analytics_file().context("Could not determine config directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Count a session of `profile` with `model` that started at unix time `started`
+    /// and lasted `duration`
+    pub fn add_session(
+        &mut self,
+        profile: &str,
+        model: Option<&str>,
+        started: u64,
+        duration: Duration,
+    ) {
+        self.launches += 1;
+        *self.profiles.entry(profile.to_string()).or_default() += 1;
+        if let Some(model) = model {
+            *self.models.entry(model.to_string()).or_default() += 1;
+        }
+        self.hours[((started % 86_400) / 3_600) as usize] += 1;
+        // 1970-01-01 was a Thursday
+        self.weekdays[((started / 86_400 + 3) % 7) as usize] += 1;
+        *self.minutes.entry(profile.to_string()).or_default() += duration.as_secs() / 60;
+    }
+
+    /// Names by count, most first, at most `limit` of them
+    pub fn top(counts: &BTreeMap<String, u64>, limit: usize) -> Vec<(&str, u64)> {
+        let mut top: Vec<(&str, u64)> = counts
+            .iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(limit);
+        top
+    }
+
+    /// Index of the largest count, if any count is above zero
+    pub fn busiest(counts: &[u64]) -> Option<usize> {
+        let (index, count) = counts
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(&a.0)))?;
+        (*count > 0).then_some(index)
+    }
+}
+
+/// Count a finished session when the policy allows it
+pub fn record_session(
+    policy: &AnalyticsPolicy,
+    profile: &str,
+    model: Option<&str>,
+    started: u64,
+    duration: Duration,
+) -> Result<()> {
+    if !policy.is_active() {
+        return Ok(());
+    }
+    let mut analytics = Analytics::load();
+    analytics.add_session(profile, model, started, duration);
+    analytics.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_are_counted_by_profile_model_hour_and_weekday() {
+        // 2025-03-01 (a Saturday) 14:30 UTC
+        let saturday = 1_740_839_400;
+        let mut analytics = Analytics::default();
+        analytics.add_session(
+            "work",
+            Some("claude-sonnet-4"),
+            saturday,
+            Duration::from_secs(1_800),
+        );
+        analytics.add_session(
+            "work",
+            Some("claude-sonnet-4"),
+            saturday + 86_400,
+            Duration::ZERO,
+        );
+        analytics.add_session("local", None, saturday + 3_600, Duration::from_secs(600));
+
+        assert_eq!(analytics.launches, 3);
+        assert_eq!(Analytics::top(&analytics.profiles, 1), [("work", 2)]);
+        assert_eq!(
+            Analytics::top(&analytics.models, 5),
+            [("claude-sonnet-4", 2)]
+        );
+        assert_eq!((analytics.hours[14], analytics.hours[15]), (2, 1));
+        assert_eq!(Analytics::busiest(&analytics.hours), Some(14));
+        assert_eq!(
+            WEEKDAYS[Analytics::busiest(&analytics.weekdays).unwrap()],
+            "Sat"
+        );
+        assert_eq!(analytics.weekdays[6], 1);
+        assert_eq!(analytics.minutes["work"], 30);
+        assert_eq!(Analytics::busiest(&[0; 7]), None);
+
+        let saved = serde_json::to_string(&analytics).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Analytics>(&saved).unwrap(),
+            analytics
+        );
+
+        assert!(!AnalyticsPolicy::default().is_active());
+    }
+}
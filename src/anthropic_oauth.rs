@@ -0,0 +1,350 @@
+//! Claude.ai subscription sign-in for profiles with `AUTH_PROVIDER = "anthropic-oauth"`.
+//!
+//! The flow mirrors [`crate::openai_oauth`]: PKCE, a browser sign-in redirected to a
+//! localhost callback (or the code pasted back by hand), and refresh-token renewal.
+//! Tokens are kept in the OS keychain under the `claude-profiler` service, or in
+//! `anthropic-oauth.json` next to `profiles.toml` where no keychain is available. The
+//! launcher hands Claude Code a fresh access token as `CLAUDE_CODE_OAUTH_TOKEN`, so the
+//! profile needs no static `ANTHROPIC_AUTH_TOKEN`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::openai_oauth::{
+    generate_pkce, open_browser, parse_authorization_input, random_hex, wait_for_oauth_code,
+};
+use crate::platform;
+use crate::secrets::{Keychain, SecretStore};
+
+/// Shared HTTP client for OAuth requests
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+pub const ANTHROPIC_OAUTH_CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
+pub const ANTHROPIC_OAUTH_AUTHORIZE_URL: &str = "https://claude.ai/oauth/authorize";
+pub const ANTHROPIC_OAUTH_TOKEN_URL: &str = "https://console.anthropic.com/v1/oauth/token";
+pub const ANTHROPIC_OAUTH_REDIRECT_URI: &str = "http://localhost:54545/callback";
+pub const ANTHROPIC_OAUTH_SCOPE: &str = "org:create_api_key user:profile user:inference";
+pub const ANTHROPIC_OAUTH_CALLBACK_PORT: u16 = 54545;
+
+/// Keychain entry the tokens are stored under
+pub const ANTHROPIC_OAUTH_KEYCHAIN_ENTRY: &str = "anthropic-oauth";
+
+/// File the tokens are stored in when the keychain cannot be used
+const TOKEN_FILE: &str = "anthropic-oauth.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnthropicOAuthTokens {
+    pub access: String,
+    pub refresh: String,
+    /// Epoch millis
+    pub expires: u64,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn token_file_path() -> Option<PathBuf> {
+    Config::config_dir().map(|p| p.join(TOKEN_FILE))
+}
+
+/// OAuth token response from Anthropic
+#[derive(Deserialize)]
+struct OAuthTokenResponse {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+fn build_authorize_url(code_challenge: &str, state: &str) -> Result<String> {
+    let mut url = url::Url::parse(ANTHROPIC_OAUTH_AUTHORIZE_URL)?;
+    url.query_pairs_mut()
+        .append_pair("code", "true")
+        .append_pair("client_id", ANTHROPIC_OAUTH_CLIENT_ID)
+        .append_pair("response_type", "code")
+        .append_pair("redirect_uri", ANTHROPIC_OAUTH_REDIRECT_URI)
+        .append_pair("scope", ANTHROPIC_OAUTH_SCOPE)
+        .append_pair("code_challenge", code_challenge)
+        .append_pair("code_challenge_method", "S256")
+        .append_pair("state", state);
+    Ok(url.to_string())
+}
+
+/// POST a JSON grant to the token endpoint; the previous refresh token is kept when
+/// the response does not rotate it
+async fn request_tokens(
+    grant: serde_json::Value,
+    previous_refresh: Option<&str>,
+    what: &str,
+) -> Result<AnthropicOAuthTokens> {
+    let response = HTTP_CLIENT
+        .post(ANTHROPIC_OAUTH_TOKEN_URL)
+        .json(&grant)
+        .send()
+        .await
+        .with_context(|| format!("OAuth {} request failed", what))?;
+
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        anyhow::bail!("OAuth {} failed: {} {}", what, status, body);
+    }
+
+    let parsed: OAuthTokenResponse = serde_json::from_str(&body)
+        .with_context(|| format!("OAuth {} response parse failed", what))?;
+    let access = parsed
+        .access_token
+        .with_context(|| format!("OAuth {} response missing access_token", what))?;
+    let refresh = parsed
+        .refresh_token
+        .or_else(|| previous_refresh.map(str::to_string))
+        .with_context(|| format!("OAuth {} response missing refresh_token", what))?;
+    let expires_in = parsed
+        .expires_in
+        .with_context(|| format!("OAuth {} response missing expires_in", what))?;
+
+    Ok(AnthropicOAuthTokens {
+        access,
+        refresh,
+        expires: now_millis() + expires_in * 1000,
+    })
+}
+
+async fn exchange_authorization_code(
+    code: &str,
+    state: &str,
+    verifier: &str,
+) -> Result<AnthropicOAuthTokens> {
+    let grant = serde_json::json!({
+        "grant_type": "authorization_code",
+        "code": code,
+        "state": state,
+        "client_id": ANTHROPIC_OAUTH_CLIENT_ID,
+        "redirect_uri": ANTHROPIC_OAUTH_REDIRECT_URI,
+        "code_verifier": verifier,
+    });
+    request_tokens(grant, None, "code->token").await
+}
+
+async fn refresh_access_token(refresh_token: &str) -> Result<AnthropicOAuthTokens> {
+    let grant = serde_json::json!({
+        "grant_type": "refresh_token",
+        "refresh_token": refresh_token,
+        "client_id": ANTHROPIC_OAUTH_CLIENT_ID,
+    });
+    request_tokens(grant, Some(refresh_token), "refresh").await
+}
+
+/// Tokens from `store`, else from `file`
+fn load_tokens_from(
+    store: &dyn SecretStore,
+    file: Option<&Path>,
+) -> Result<Option<AnthropicOAuthTokens>> {
+    if let Ok(stored) = store.get(ANTHROPIC_OAUTH_KEYCHAIN_ENTRY) {
+        return serde_json::from_str(&stored)
+            .map(Some)
+            .context("Failed to parse the Anthropic OAuth keychain entry");
+    }
+    let Some(path) = file.filter(|path| path.exists()) else {
+        return Ok(None);
+    };
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let tokens = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse {}", TOKEN_FILE))?;
+    Ok(Some(tokens))
+}
+
+/// Store `tokens` in `store`, falling back to `file` when that fails. Whichever is not
+/// used is cleared so a stale copy is never read back.
+fn save_tokens_to(
+    store: &dyn SecretStore,
+    file: Option<&Path>,
+    tokens: &AnthropicOAuthTokens,
+) -> Result<()> {
+    let contents = serde_json::to_string_pretty(tokens).context("Failed to serialize tokens")?;
+    if store.set(ANTHROPIC_OAUTH_KEYCHAIN_ENTRY, &contents).is_ok() {
+        if let Some(path) = file.filter(|path| path.exists()) {
+            fs::remove_file(path).context("Failed to delete token file")?;
+        }
+        return Ok(());
+    }
+    let Some(path) = file else {
+        anyhow::bail!("Could not determine config directory for saving tokens");
+    };
+    platform::write_private(path, contents.as_bytes())
+}
+
+fn load_tokens() -> Result<Option<AnthropicOAuthTokens>> {
+    load_tokens_from(&Keychain, token_file_path().as_deref())
+}
+
+fn save_tokens(tokens: &AnthropicOAuthTokens) -> Result<()> {
+    save_tokens_to(&Keychain, token_file_path().as_deref(), tokens)
+}
+
+/// Forget the stored sign-in, in the keychain and on disk
+pub fn clear_tokens() -> Result<()> {
+    // Without a keychain the tokens can only have been saved to the file
+    let _ = Keychain.delete(ANTHROPIC_OAUTH_KEYCHAIN_ENTRY);
+    if let Some(path) = token_file_path()
+        && path.exists()
+    {
+        fs::remove_file(path).context("Failed to delete token file")?;
+    }
+    Ok(())
+}
+
+/// Refresh the stored token without user interaction, for use mid-session after the
+/// upstream rejected `stale_access`. If another session already refreshed it, the
+/// newer stored token is returned as-is.
+pub async fn refresh_stored_access_token(stale_access: &str) -> Result<String> {
+    let tokens = load_tokens()?.context("No stored Anthropic OAuth tokens")?;
+    if tokens.access != stale_access && tokens.expires > now_millis() {
+        return Ok(tokens.access);
+    }
+    let refreshed = refresh_access_token(&tokens.refresh).await?;
+    save_tokens(&refreshed)?;
+    Ok(refreshed.access)
+}
+
+pub async fn ensure_access_token_interactive() -> Result<String> {
+    const EXPIRY_SAFETY_WINDOW_MS: u64 = 60_000;
+
+    if let Some(tokens) = load_tokens()? {
+        if tokens.expires.saturating_sub(EXPIRY_SAFETY_WINDOW_MS) > now_millis() {
+            return Ok(tokens.access);
+        }
+
+        if let Ok(refreshed) = refresh_access_token(&tokens.refresh).await {
+            save_tokens(&refreshed)?;
+            return Ok(refreshed.access);
+        }
+    }
+
+    let (verifier, challenge) = generate_pkce();
+    let state = random_hex(16);
+    let authorize_url = build_authorize_url(&challenge, &state)?;
+
+    eprintln!("Claude sign-in required. Opening browser...");
+    eprintln!(
+        "If the browser does not open, visit this URL:\n\n{}\n",
+        authorize_url
+    );
+    let _ = open_browser(&authorize_url);
+
+    // Preferred: localhost callback capture. Fallback: manual paste.
+    let code = wait_for_oauth_code(
+        ANTHROPIC_OAUTH_CALLBACK_PORT,
+        "/callback",
+        state.clone(),
+        Duration::from_secs(300),
+    )
+    .await
+    .ok()
+    .flatten();
+
+    let code = if let Some(code) = code {
+        code
+    } else {
+        // Claude shows `code#state` to copy when the redirect cannot be reached
+        eprint!("Paste the code shown after signing in (or the full redirect URL): ");
+        io::stdout().flush().ok();
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let (code, got_state) = parse_authorization_input(&input);
+        if let Some(got_state) = got_state
+            && got_state != state
+        {
+            anyhow::bail!("OAuth state mismatch");
+        }
+        code.context("No OAuth code provided")?
+    };
+
+    let tokens = exchange_authorization_code(&code, &state, &verifier).await?;
+    save_tokens(&tokens)?;
+    Ok(tokens.access)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    /// A keychain that holds entries in memory, or refuses every write
+    #[derive(Default)]
+    struct MemoryStore {
+        entries: RefCell<HashMap<String, String>>,
+        locked: bool,
+    }
+
+    impl SecretStore for MemoryStore {
+        fn get(&self, entry: &str) -> Result<String> {
+            self.entries
+                .borrow()
+                .get(entry)
+                .cloned()
+                .context("No such entry")
+        }
+
+        fn set(&self, entry: &str, secret: &str) -> Result<()> {
+            if self.locked {
+                anyhow::bail!("Keychain locked");
+            }
+            self.entries
+                .borrow_mut()
+                .insert(entry.to_string(), secret.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tokens_prefer_the_keychain_and_fall_back_to_a_file() {
+        let tokens = AnthropicOAuthTokens {
+            access: "sk-ant-oat01-access".to_string(),
+            refresh: "sk-ant-ort01-refresh".to_string(),
+            expires: 1_800_000_000_000,
+        };
+        let file =
+            std::env::temp_dir().join(format!("anthropic-oauth-{}.json", std::process::id()));
+
+        let locked = MemoryStore {
+            locked: true,
+            ..Default::default()
+        };
+        save_tokens_to(&locked, Some(&file), &tokens).unwrap();
+        assert!(file.exists());
+        assert_eq!(
+            load_tokens_from(&locked, Some(&file)).unwrap(),
+            Some(tokens.clone())
+        );
+
+        let keychain = MemoryStore::default();
+        save_tokens_to(&keychain, Some(&file), &tokens).unwrap();
+        assert!(!file.exists());
+        assert_eq!(
+            load_tokens_from(&keychain, Some(&file)).unwrap(),
+            Some(tokens)
+        );
+        assert_eq!(
+            load_tokens_from(&MemoryStore::default(), Some(&file)).unwrap(),
+            None
+        );
+
+        let url = url::Url::parse(&build_authorize_url("challenge", "state").unwrap()).unwrap();
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert_eq!(query["redirect_uri"], ANTHROPIC_OAUTH_REDIRECT_URI);
+        assert_eq!(query["state"], "state");
+    }
+}
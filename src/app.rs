@@ -1,17 +1,42 @@
 use ratatui::widgets::ListState;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use tui_input::Input;
 
+use crate::analytics::{self, Analytics};
 use crate::config::{
-    Config, ENV_AUTH_TOKEN, ENV_BASE_URL, ENV_DEFAULT_HAIKU_MODEL, ENV_DEFAULT_OPUS_MODEL,
-    ENV_DEFAULT_SONNET_MODEL, ENV_MODEL, ENV_PROXY_TARGET_URL, Profile,
+    Config, Date, ENV_AUTH_TOKEN, ENV_BASE_URL, ENV_DEFAULT_HAIKU_MODEL, ENV_DEFAULT_OPUS_MODEL,
+    ENV_DEFAULT_SONNET_MODEL, ENV_HAIKU_SAMPLING, ENV_MODEL, ENV_OPUS_SAMPLING,
+    ENV_PROXY_TARGET_URL, ENV_SONNET_SAMPLING, EnvFilter, HeaderPolicy, KeyExpiry, Profile,
+    Provider, env_reference, keychain_reference,
 };
-use crate::openai_oauth::is_truthy;
+use crate::config_watch::ConfigWatcher;
+use crate::control::Command;
+use crate::env_editor::EnvEditor;
+use crate::health::HealthChecks;
+use crate::inspector::Inspector;
+use crate::llama_server::{LlamaServerOptions, gguf_models};
+use crate::local_models::{self, LeaseFile, LocalBackend, ModelLease};
+use crate::openai_oauth;
+use crate::pricing;
 use crate::proxy;
+use crate::secrets::{self, SecretStore};
+use crate::textarea::TextArea;
+use crate::usage::UsageCounters;
+use crate::workspace::PinnedWorkspace;
 
 /// URL fragment indicating a Codex profile
 const CODEX_PROXY_INDICATOR: &str = "chatgpt.com/backend-api/codex";
 
+/// Where the model picker's list comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelSource {
+    /// The Codex model list, as last refreshed from the backend
+    Codex,
+    /// Models installed on a local server
+    Local(LocalBackend),
+}
+
 /// Possible application actions from user input
 #[derive(Debug, Clone, PartialEq)]
 pub enum Action {
@@ -23,12 +48,48 @@ pub enum Action {
     HideHelp,
     EditProfile,
     CreateProfile,
+    DuplicateProfile,
     SaveEdit,
     CancelEdit,
     ResetProfile,
     ResetAll,
     ResetOAuth,
+    ConfirmResetOAuth,
+    CancelResetOAuth,
     DeleteProfile,
+    ToggleWorkspace,
+    ToggleListView,
+    OpenConsole,
+    ShowNotes,
+    HideNotes,
+    ShowLogs,
+    HideLogs,
+    CycleLogFilter,
+    ShowInspector,
+    HideInspector,
+    ReplayCapture,
+    StartTagFilter,
+    ApplyTagFilter,
+    ClearTagFilter,
+    StartTutorial,
+    NextTutorialStep,
+    PreviousTutorialStep,
+    EndTutorial,
+    ImportDetected,
+    SkipDetected,
+    TrustWorkspace,
+    SkipWorkspaceTrust,
+    CheckHealth,
+    ShowUsage,
+    HideUsage,
+    StartCompare,
+    CreateFromShellEnv,
+    HideCompare,
+    ToggleCompareDifferences,
+    ConfirmCost,
+    CancelCost,
+    UnloadLeftoverModels,
+    KeepLeftoverModels,
 }
 
 /// Current application mode
@@ -50,8 +111,101 @@ pub enum AppMode {
         /// Whether we are creating a new profile
         is_creating: bool,
     },
+    /// Scrollable popup with the selected profile's notes
+    Notes {
+        /// First visible line
+        scroll: u16,
+    },
+    /// Request log viewer
+    Logs {
+        /// Rows scrolled back from the newest entry; 0 follows new entries
+        back: usize,
+    },
+    /// Captured requests with their bodies, and replays
+    Inspector,
+    /// Typing a tag filter for the profile list
+    TagFilter,
+    /// Guided tour overlay (index into TUTORIAL_STEPS)
+    Tutorial {
+        step: usize,
+    },
+    /// First-run offer to create profiles for API keys found in the environment
+    ImportDetected,
+    /// Asking whether to trust a new or changed `.claude-profiler.toml`
+    TrustWorkspace,
+    /// Token usage and cost dashboard
+    Usage,
+    /// Two profiles' env side by side: `left` against the selected profile
+    Compare {
+        /// Index into `config.profiles`
+        left: usize,
+        /// First visible row
+        scroll: u16,
+        /// Hide variables both profiles set to the same value
+        only_differences: bool,
+    },
+    /// Asking before launching a profile whose last session was expensive
+    ConfirmCost,
+    /// Asking before signing out of ChatGPT for Codex profiles
+    ConfirmResetOAuth,
+    /// Offer to unload local models an earlier run loaded and never unloaded
+    UnloadLeftoverModels,
+}
+
+/// Screen region highlighted by a tutorial step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TutorialRegion {
+    ProfileList,
+    Details,
+    Footer,
 }
 
+/// One page of the guided tour
+pub struct TutorialStep {
+    pub region: TutorialRegion,
+    pub title: &'static str,
+    pub body: &'static str,
+}
+
+pub const TUTORIAL_STEPS: [TutorialStep; 6] = [
+    TutorialStep {
+        region: TutorialRegion::ProfileList,
+        title: "Profiles",
+        body: "Each row is a profile: a named set of environment variables for Claude Code. \
+               Move between profiles with Up/Down or j/k.",
+    },
+    TutorialStep {
+        region: TutorialRegion::ProfileList,
+        title: "Launch",
+        body: "Press Enter to launch Claude Code with the selected profile. \
+               When Claude Code exits you come back to this screen.",
+    },
+    TutorialStep {
+        region: TutorialRegion::Details,
+        title: "Details",
+        body: "The selected profile's settings. API keys are masked, and proxied profiles \
+               show where the built-in proxy forwards requests.",
+    },
+    TutorialStep {
+        region: TutorialRegion::ProfileList,
+        title: "Edit and create",
+        body: "e edits the selected profile, n creates a new one and d deletes it. \
+               In the form, Tab moves between fields and Enter saves.",
+    },
+    TutorialStep {
+        region: TutorialRegion::Details,
+        title: "Proxy",
+        body: "Set a Proxy Target URL to route Claude Code through the local proxy to an \
+               OpenAI-compatible, Codex or Bedrock upstream.",
+    },
+    TutorialStep {
+        region: TutorialRegion::Footer,
+        title: "Keys",
+        body: "The footer lists the main keys. ? opens the full help, t replays this tour \
+               and q quits.",
+    },
+];
+
 pub const EDIT_FIELD_NAME: usize = 0;
 pub const EDIT_FIELD_DESCRIPTION: usize = 1;
 pub const EDIT_FIELD_API_KEY: usize = 2;
@@ -60,7 +214,27 @@ pub const EDIT_FIELD_PROXY_URL: usize = 4;
 pub const EDIT_FIELD_HAIKU: usize = 5;
 pub const EDIT_FIELD_SONNET: usize = 6;
 pub const EDIT_FIELD_OPUS: usize = 7;
-pub const EDIT_FIELD_COUNT: usize = 8;
+/// Free-form editor for the variables without a field of their own
+pub const EDIT_FIELD_ENV: usize = 8;
+/// Fields from here on are in the collapsible advanced section
+pub const EDIT_FIELD_BASIC_COUNT: usize = 9;
+pub const EDIT_FIELD_HAIKU_SAMPLING: usize = 9;
+pub const EDIT_FIELD_SONNET_SAMPLING: usize = 10;
+pub const EDIT_FIELD_OPUS_SAMPLING: usize = 11;
+pub const EDIT_FIELD_COUNT: usize = 12;
+
+/// Variables with a field of their own in the edit form; the env editor holds the rest
+const EDIT_FORM_ENV_KEYS: [&str; 9] = [
+    ENV_AUTH_TOKEN,
+    ENV_BASE_URL,
+    ENV_PROXY_TARGET_URL,
+    ENV_DEFAULT_HAIKU_MODEL,
+    ENV_DEFAULT_SONNET_MODEL,
+    ENV_DEFAULT_OPUS_MODEL,
+    ENV_HAIKU_SAMPLING,
+    ENV_SONNET_SAMPLING,
+    ENV_OPUS_SAMPLING,
+];
 
 /// Main application state
 pub struct App {
@@ -82,11 +256,14 @@ pub struct App {
     /// Status message to display (errors, confirmations)
     pub status_message: Option<String>,
 
+    /// Profile name -> upstream models its last proxied session used
+    pub last_upstream: HashMap<String, String>,
+
     /// Input for Name
     pub name_input: Input,
 
     /// Input for Description
-    pub description_input: Input,
+    pub description_input: TextArea,
 
     /// Input for API Key
     pub api_key_input: Input,
@@ -106,14 +283,85 @@ pub struct App {
     /// Input for Opus model
     pub opus_model_input: Input,
 
+    /// Input for Haiku-tier sampling overrides
+    pub haiku_sampling_input: Input,
+
+    /// Input for Sonnet-tier sampling overrides
+    pub sonnet_sampling_input: Input,
+
+    /// Input for Opus-tier sampling overrides
+    pub opus_sampling_input: Input,
+
+    /// Every other variable of the profile being edited
+    pub env_editor: EnvEditor,
+
     /// Whether to reveal the API key in the edit form
     pub reveal_api_key: bool,
 
-    /// Available Codex models for the model picker
-    pub codex_models: Vec<String>,
+    /// Whether the edit form shows its advanced section
+    pub show_advanced: bool,
 
-    /// Selected index in the model picker
+    /// Models offered by the model picker, and where they were listed from
+    pub picker_models: Vec<String>,
+    pub picker_source: Option<ModelSource>,
+
+    /// Selected index in the model picker (into the filtered list)
     pub model_picker_index: usize,
+
+    /// Type-ahead filter for the model picker
+    pub model_picker_query: String,
+
+    /// Last model picked for each model field (keyed by EDIT_FIELD_* constant)
+    pub model_picker_last: HashMap<usize, String>,
+
+    /// Directory the profiler was launched from (for workspace associations)
+    pub workspace_dir: Option<PathBuf>,
+
+    /// `.claude-profiler.toml` pinning the launch directory's profiles, if any
+    pub workspace_pin: Option<PinnedWorkspace>,
+
+    /// Profiles offered for API keys found in the environment, with their variable
+    pub detected_profiles: Vec<(&'static str, Profile)>,
+
+    /// Local models left loaded by a run that died before unloading them
+    pub leftover_models: Vec<ModelLease>,
+
+    /// Newest request log entries, refreshed while the log viewer is open
+    pub request_log: proxy::LogTail,
+
+    /// Profile whose entries the log viewer shows, or all when `None`
+    pub log_filter: Option<String>,
+
+    /// Captured requests, loaded when the inspector opens
+    pub inspector: Inspector,
+
+    /// Codex usage limits as last reported by the backend
+    pub codex_quota: Option<proxy::QuotaSnapshot>,
+
+    /// Tag filter for the profile list; empty shows every profile
+    pub tag_filter: String,
+
+    /// Reachability of the profiles' upstreams
+    pub health: HealthChecks,
+
+    /// Lifetime usage counters, loaded when the dashboard opens
+    pub usage: UsageCounters,
+
+    /// Launch patterns for the dashboard; `None` while analytics are off
+    pub analytics: Option<Analytics>,
+
+    /// Launch waiting for the cost confirmation, with the estimate shown
+    pub pending_launch: Option<(Profile, pricing::CostEstimate)>,
+
+    /// Index of a duplicated profile open in the edit form and not saved yet;
+    /// cancelling the edit removes it again
+    unsaved_copy: Option<usize>,
+
+    /// Model prices from the cached catalog, read on the first launch
+    price_catalog: Option<pricing::PriceCatalog>,
+
+    /// Changes to `profiles.toml` made outside the TUI
+    config_watcher: Option<ConfigWatcher>,
 }
 
 fn env_value(profile: &Profile, key: &str) -> String {
@@ -133,46 +381,395 @@ impl App {
             should_quit: false,
             selected_profile: None,
             status_message: None,
+            last_upstream: HashMap::new(),
             name_input: Input::default(),
-            description_input: Input::default(),
+            description_input: TextArea::default(),
             api_key_input: Input::default(),
             url_input: Input::default(),
             proxy_url_input: Input::default(),
             haiku_model_input: Input::default(),
             sonnet_model_input: Input::default(),
             opus_model_input: Input::default(),
+            haiku_sampling_input: Input::default(),
+            sonnet_sampling_input: Input::default(),
+            opus_sampling_input: Input::default(),
+            env_editor: EnvEditor::default(),
             reveal_api_key: false,
-            codex_models: Vec::new(),
+            show_advanced: false,
+            picker_models: Vec::new(),
+            picker_source: None,
             model_picker_index: 0,
+            model_picker_query: String::new(),
+            model_picker_last: HashMap::new(),
+            workspace_dir: None,
+            workspace_pin: None,
+            detected_profiles: Vec::new(),
+            leftover_models: Vec::new(),
+            request_log: proxy::LogTail::default(),
+            log_filter: None,
+            inspector: Inspector::default(),
+            codex_quota: None,
+            tag_filter: String::new(),
+            health: HealthChecks::default(),
+            usage: UsageCounters::default(),
+            analytics: None,
+            pending_launch: None,
+            unsaved_copy: None,
+            price_catalog: None,
+            config_watcher: None,
+        }
+    }
+
+    /// Reload `profiles.toml` whenever it is changed outside the TUI
+    pub fn watch_config(&mut self) {
+        self.config_watcher = ConfigWatcher::start();
+    }
+
+    /// Take in external changes to the config, if there were any
+    pub fn poll_config_changes(&mut self) {
+        if !self.config_watcher.as_ref().is_some_and(|w| w.changed()) {
+            return;
+        }
+        // A deleted file would be replaced by the defaults; keep what is loaded
+        if !Config::config_file_path().is_some_and(|path| path.exists()) {
+            return;
+        }
+        match Config::load() {
+            Ok(config) => self.reload_config(config),
+            Err(e) => self.set_status(format!(
+                "profiles.toml changed but could not be loaded; keeping the loaded profiles: {:#}",
+                e
+            )),
+        }
+    }
+
+    /// Switch to a config changed on disk. An open edit form keeps its unsaved
+    /// values and is saved into the new config; an unsaved duplicate is carried over.
+    fn reload_config(&mut self, mut config: Config) {
+        // The TUI's own saves come back as changes too
+        if serde_json::to_value(&config).ok() == serde_json::to_value(&self.config).ok() {
+            return;
+        }
+        let selected = self.current_profile().map(|p| p.name.clone());
+        if let Some(i) = self.unsaved_copy {
+            let copy = self.config.profiles[i].clone();
+            let i = i.min(config.profiles.len());
+            config.profiles.insert(i, copy);
+            self.unsaved_copy = Some(i);
+        }
+        self.config = config;
+
+        let index = selected
+            .as_ref()
+            .and_then(|name| self.config.profiles.iter().position(|p| &p.name == name));
+        let editing = matches!(
+            self.mode,
+            AppMode::EditProfile { .. } | AppMode::ModelPicker { .. }
+        );
+        match &mut self.mode {
+            AppMode::EditProfile { is_creating, .. } | AppMode::ModelPicker { is_creating, .. }
+                if index.is_none() =>
+            {
+                // The profile being edited is gone; saving the form adds it back
+                *is_creating = true;
+            }
+            AppMode::Compare { left, .. } if *left >= self.config.profiles.len() => {
+                self.mode = AppMode::Normal;
+            }
+            _ => {}
+        }
+        let last = self.config.profiles.len().checked_sub(1);
+        let kept = last.map(|last| self.list_state.selected().unwrap_or(0).min(last));
+        self.list_state.select(index.or(kept));
+        self.refresh_health(false);
+        self.set_status(if editing {
+            "profiles.toml changed on disk and was reloaded; your unsaved edits are kept"
+        } else {
+            "profiles.toml changed on disk and was reloaded"
+        });
+    }
+
+    /// Remember the launch directory and preselect its associated profile, if any.
+    /// A pin file there narrows the choice and asks for trust when new or changed.
+    pub fn enter_workspace(&mut self, dir: PathBuf) {
+        let index = self
+            .config
+            .workspace_for(&dir)
+            .and_then(|(_, name)| self.config.profiles.iter().position(|p| p.name == name));
+        if let Some(index) = index {
+            self.list_state.select(Some(index));
+        }
+        match PinnedWorkspace::find(&dir) {
+            Ok(pin) => self.workspace_pin = pin,
+            Err(e) => self.set_status(format!("{:#}", e)),
+        }
+        if let Some(pinned) = &self.workspace_pin {
+            let allowed = self
+                .current_profile()
+                .is_some_and(|p| pinned.allows(&p.name));
+            if !allowed && let Some(index) = pinned.preferred_profile(&self.config.profiles) {
+                self.list_state.select(Some(index));
+            }
+            if !pinned.is_trusted(&self.config) {
+                self.mode = AppMode::TrustWorkspace;
+            }
+        }
+        self.workspace_dir = Some(dir);
+    }
+
+    /// Record the pin file as trusted so its settings apply from now on
+    fn trust_workspace(&mut self) {
+        self.mode = AppMode::Normal;
+        let Some(pinned) = &self.workspace_pin else {
+            return;
+        };
+        pinned.trust(&mut self.config);
+        let path = pinned.path.display().to_string();
+        match self.config.save() {
+            Ok(()) => self.set_status(format!("Trusted {}", path)),
+            Err(e) => self.set_status(format!("Failed to save config: {}", e)),
+        }
+        self.start_tutorial_if_unseen();
+    }
+
+    /// Workspace directory and profile name associated with the launch directory
+    pub fn workspace_profile(&self) -> Option<(&str, &str)> {
+        self.workspace_dir
+            .as_deref()
+            .and_then(|dir| self.config.workspace_for(dir))
+    }
+
+    /// Associate the launch directory with the selected profile, or remove the
+    /// association if it already points there
+    fn toggle_workspace(&mut self) {
+        let Some(dir) = self.workspace_dir.clone() else {
+            return;
+        };
+        let Some(name) = self.current_profile().map(|p| p.name.clone()) else {
+            return;
+        };
+        let key = dir.to_string_lossy().to_string();
+
+        if self.config.workspaces.get(&key) == Some(&name) {
+            self.config.workspaces.remove(&key);
+            self.set_status(format!("Removed workspace profile for {}", key));
+        } else {
+            self.config.workspaces.insert(key.clone(), name.clone());
+            self.set_status(format!("'{}' is now the profile for {}", name, key));
+        }
+
+        if let Err(e) = self.config.save() {
+            self.set_status(format!("Failed to save config: {}", e));
+        }
+    }
+
+    /// Number of edit form fields reachable with Tab
+    pub fn edit_field_count(&self) -> usize {
+        if self.show_advanced {
+            EDIT_FIELD_COUNT
+        } else {
+            EDIT_FIELD_BASIC_COUNT
         }
     }
 
-    /// Check if the current profile being edited is a Codex profile
+    /// Show or hide the advanced section, moving focus out of it when hidden
+    pub fn toggle_advanced(&mut self) {
+        self.show_advanced = !self.show_advanced;
+        if let AppMode::EditProfile {
+            focused_field,
+            is_creating,
+        } = self.mode
+            && focused_field >= self.edit_field_count()
+        {
+            self.mode = AppMode::EditProfile {
+                focused_field: EDIT_FIELD_BASIC_COUNT - 1,
+                is_creating,
+            };
+        }
+    }
+
+    /// Move the API key being edited into `store`, under the profile name, and
+    /// leave a `keychain:` reference in the field
+    pub fn store_api_key(&mut self, store: &dyn SecretStore) {
+        let key = self.api_key_input.value().trim().to_string();
+        if key.is_empty() || keychain_reference(&key).is_some() || env_reference(&key).is_some() {
+            self.set_status("Enter an API key to store in the keychain");
+            return;
+        }
+        let entry = self.name_input.value().trim().to_string();
+        match secrets::store_secret(store, &entry, &key) {
+            Ok(reference) => {
+                self.api_key_input = Input::new(reference);
+                self.set_status(format!(
+                    "API key stored in the keychain as '{}'; save to keep the reference",
+                    entry
+                ));
+            }
+            Err(e) => self.set_status(format!("{:#}", e)),
+        }
+    }
+
+    /// Check if the current profile being edited is a Codex profile: its proxy URL
+    /// points at the Codex backend, or the profile being edited signs in with OAuth
     pub fn is_codex_profile(&self) -> bool {
+        let editing_existing = matches!(
+            self.mode,
+            AppMode::EditProfile {
+                is_creating: false,
+                ..
+            } | AppMode::ModelPicker {
+                is_creating: false,
+                ..
+            }
+        );
         self.proxy_url_input.value().contains(CODEX_PROXY_INDICATOR)
+            || (editing_existing && self.is_selected_profile_codex())
     }
 
-    /// Check if the currently selected profile (in the list) is a Codex profile
+    /// Check if the currently selected profile (in the list) is a Codex profile,
+    /// by its `OPENAI_OAUTH` flag or its URLs
     pub fn is_selected_profile_codex(&self) -> bool {
-        if let Some(profile) = self.current_profile() {
-            if let Some(val) = profile.env.get("OPENAI_OAUTH") {
-                if is_truthy(val) {
-                    return true;
+        self.current_profile()
+            .is_some_and(|profile| profile.provider() == Provider::Codex)
+    }
+
+    /// Where the model picker lists models from for the proxy URL in the form
+    pub fn model_source(&self) -> Option<ModelSource> {
+        if self.is_codex_profile() {
+            return Some(ModelSource::Codex);
+        }
+        LocalBackend::detect(self.proxy_url_input.value().trim()).map(ModelSource::Local)
+    }
+
+    /// List the picker's models if the proxy URL changed to a different source since
+    /// they were loaded (e.g. it was typed into the form after entering edit mode)
+    pub fn ensure_picker_models_loaded(&mut self) {
+        let source = self.model_source();
+        if source.is_none() || source == self.picker_source {
+            return;
+        }
+        match source {
+            Some(ModelSource::Codex) => self.load_codex_models(),
+            Some(ModelSource::Local(backend)) => match self.list_local_models(backend) {
+                Ok(models) => {
+                    self.picker_models = models;
+                    self.picker_source = source;
                 }
+                Err(e) => self.set_status(format!(
+                    "Failed to list {} models: {:#}",
+                    backend.label(),
+                    e
+                )),
+            },
+            None => {}
+        }
+    }
+
+    /// Models a local server lists; llama.cpp profiles add the GGUF files of their
+    /// models directory, so a model can be picked before any server runs
+    fn list_local_models(&self, backend: LocalBackend) -> anyhow::Result<Vec<String>> {
+        let listed = backend.list_models(self.proxy_url_input.value().trim());
+        let editing_existing = !matches!(
+            self.mode,
+            AppMode::EditProfile {
+                is_creating: true,
+                ..
+            } | AppMode::ModelPicker {
+                is_creating: true,
+                ..
             }
-            return env_value(profile, ENV_PROXY_TARGET_URL).contains(CODEX_PROXY_INDICATOR);
+        );
+        let files = match (backend, self.current_profile()) {
+            (LocalBackend::LlamaCpp, Some(profile)) if editing_existing => {
+                LlamaServerOptions::from_env(&profile.env)
+                    .map(|options| gguf_models(&options.models_dir))
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+        match listed {
+            Ok(mut models) => {
+                models.extend(files);
+                models.sort();
+                models.dedup();
+                Ok(models)
+            }
+            Err(_) if !files.is_empty() => Ok(files),
+            Err(e) => Err(e),
         }
-        false
     }
 
     /// Load Codex models (call this when entering edit mode for a Codex profile)
     pub fn load_codex_models(&mut self) {
         use crate::codex_instructions::get_cached_codex_models;
-        self.codex_models = get_cached_codex_models();
+        self.picker_models = get_cached_codex_models();
+        self.picker_source = Some(ModelSource::Codex);
+    }
+
+    /// List the picker's models again from their source, keeping the highlighted
+    /// model if it is still there. Codex models are fetched with the stored sign-in.
+    pub fn refresh_picker_models(&mut self) {
+        let highlighted = self
+            .filtered_models()
+            .get(self.model_picker_index)
+            .map(|model| (*model).clone());
+        let models = match self.picker_source {
+            Some(ModelSource::Codex) => match openai_oauth::stored_access_token() {
+                Ok(Some(token)) => crate::codex_instructions::refresh_codex_models(&token),
+                Ok(None) => {
+                    self.set_status("Not signed in to ChatGPT; launch the profile to sign in");
+                    return;
+                }
+                Err(e) => Err(e),
+            },
+            Some(ModelSource::Local(backend)) => self.list_local_models(backend),
+            None => return,
+        };
+        match models {
+            Ok(models) => {
+                self.set_status(format!("{} models listed", models.len()));
+                self.picker_models = models;
+                self.model_picker_index = highlighted
+                    .and_then(|model| self.filtered_models().iter().position(|m| **m == model))
+                    .unwrap_or(0);
+            }
+            Err(e) => self.set_status(format!("Failed to refresh models: {:#}", e)),
+        }
+    }
+
+    /// Whether Enter on a model field opens the picker instead of saving
+    pub fn has_model_picker(&self) -> bool {
+        self.picker_source.is_some()
+            && self.picker_source == self.model_source()
+            && !self.picker_models.is_empty()
+    }
+
+    /// Heading a model is listed under in the picker
+    pub fn picker_group_label(&self, model: &str) -> String {
+        match self.picker_source {
+            Some(ModelSource::Local(_)) => local_models::model_family(model).to_string(),
+            _ => crate::codex_instructions::model_group_label(model),
+        }
+    }
+
+    /// Models matching the picker's type-ahead query (all whitespace-separated
+    /// terms must appear, case-insensitively)
+    pub fn filtered_models(&self) -> Vec<&String> {
+        let query = self.model_picker_query.to_ascii_lowercase();
+        let terms: Vec<&str> = query.split_whitespace().collect();
+        self.picker_models
+            .iter()
+            .filter(|model| {
+                let lower = model.to_ascii_lowercase();
+                terms.iter().all(|term| lower.contains(term))
+            })
+            .collect()
     }
 
     /// Open the model picker for a specific field
     pub fn open_model_picker(&mut self, field: usize, is_creating: bool) {
+        self.model_picker_query.clear();
+
         // Find current model value and try to select it
         let current_model = match field {
             EDIT_FIELD_HAIKU => self.haiku_model_input.value(),
@@ -180,14 +777,19 @@ impl App {
             EDIT_FIELD_OPUS => self.opus_model_input.value(),
             _ => "",
         };
+        let last_selection = self.model_picker_last.get(&field).map(String::as_str);
 
-        // Find index of current model, or default to gpt-5.2-codex-medium
+        // Find index of current model, then the last pick for this field,
+        // or default to gpt-5.2-codex-medium
         self.model_picker_index = self
-            .codex_models
+            .picker_models
             .iter()
             .position(|m| m == current_model)
             .or_else(|| {
-                self.codex_models
+                last_selection.and_then(|last| self.picker_models.iter().position(|m| m == last))
+            })
+            .or_else(|| {
+                self.picker_models
                     .iter()
                     .position(|m| m == "gpt-5.2-codex-medium")
             })
@@ -199,10 +801,35 @@ impl App {
         };
     }
 
+    /// Append a character to the picker query and jump to the first match
+    pub fn push_model_picker_char(&mut self, c: char) {
+        self.model_picker_query.push(c);
+        self.model_picker_index = 0;
+    }
+
+    /// Remove the last character from the picker query
+    pub fn pop_model_picker_char(&mut self) {
+        if self.model_picker_query.pop().is_some() {
+            self.model_picker_index = 0;
+        }
+    }
+
+    /// Move the picker highlight within the filtered list, wrapping at the ends
+    pub fn move_model_picker(&mut self, delta: isize) {
+        let len = self.filtered_models().len();
+        if len == 0 {
+            self.model_picker_index = 0;
+            return;
+        }
+        let current = self.model_picker_index.min(len - 1) as isize;
+        self.model_picker_index = (current + delta).rem_euclid(len as isize) as usize;
+    }
+
     /// Select a model from the picker and return to edit mode
     pub fn select_model_from_picker(&mut self, target_field: usize, is_creating: bool) {
-        if let Some(model) = self.codex_models.get(self.model_picker_index) {
-            let model = model.clone();
+        if let Some(model) = self.filtered_models().get(self.model_picker_index) {
+            let model = (*model).clone();
+            self.model_picker_last.insert(target_field, model.clone());
             match target_field {
                 EDIT_FIELD_HAIKU => self.haiku_model_input = Input::new(model),
                 EDIT_FIELD_SONNET => self.sonnet_model_input = Input::new(model),
@@ -210,6 +837,7 @@ impl App {
                 _ => {}
             }
         }
+        self.model_picker_query.clear();
         self.mode = AppMode::EditProfile {
             focused_field: target_field,
             is_creating,
@@ -218,6 +846,7 @@ impl App {
 
     /// Cancel the model picker and return to edit mode
     pub fn cancel_model_picker(&mut self, target_field: usize, is_creating: bool) {
+        self.model_picker_query.clear();
         self.mode = AppMode::EditProfile {
             focused_field: target_field,
             is_creating,
@@ -241,6 +870,22 @@ impl App {
             .and_then(|i| self.config.profiles.get(i))
     }
 
+    /// Whether the selected profile comes from an include fragment, saying which;
+    /// `profiles.toml` does not keep it, so changes made here would not last
+    fn selected_is_included(&mut self, change: &str) -> bool {
+        let Some(message) = self.current_profile().and_then(|profile| {
+            let source = self.config.included_source(profile)?;
+            Some(format!(
+                "'{}' comes from {}; {} it there",
+                profile.name, source, change
+            ))
+        }) else {
+            return false;
+        };
+        self.set_status(message);
+        true
+    }
+
     /// Set a status message to display to the user
     fn set_status(&mut self, msg: impl Into<String>) {
         self.status_message = Some(msg.into());
@@ -248,10 +893,205 @@ impl App {
 
     /// Confirm selection and prepare to launch
     pub fn select_current(&mut self) {
-        if let AppMode::Normal = &self.mode {
-            if let Some(profile) = self.current_profile() {
-                self.selected_profile = Some(profile.clone());
+        if let AppMode::Normal = &self.mode
+            && let Some(profile) = self.current_profile()
+        {
+            if let Some(KeyExpiry::Expired { date }) = profile.key_expiry(Date::today()) {
+                let message = format!(
+                    "The key of '{}' expired on {}; rotate it and update key_expires_on",
+                    profile.name, date
+                );
+                self.set_status(message);
+                return;
+            }
+            let mut profile = profile.clone();
+            if let Some(pinned) = &self.workspace_pin {
+                if !pinned.allows(&profile.name) {
+                    let message = format!(
+                        "'{}' is not allowed here ({} pins: {})",
+                        profile.name,
+                        pinned.path.display(),
+                        pinned.pin.profiles.join(", ")
+                    );
+                    self.set_status(message);
+                    return;
+                }
+                if pinned.is_trusted(&self.config) {
+                    pinned.apply(&mut profile);
+                }
+            }
+            if let Some(estimate) = self.cost_estimate(&profile) {
+                self.pending_launch = Some((profile, estimate));
+                self.mode = AppMode::ConfirmCost;
+                return;
+            }
+            self.launch(profile);
+        }
+    }
+
+    fn launch(&mut self, profile: Profile) {
+        self.record_launch(&profile.name);
+        self.selected_profile = Some(profile);
+    }
+
+    /// Estimate for a profile that uses an expensive model and whose last session
+    /// went over the cost ceiling
+    fn cost_estimate(&mut self, profile: &Profile) -> Option<pricing::CostEstimate> {
+        if !self.config.cost_ceiling.enabled {
+            return None;
+        }
+        let last = pricing::read_history()
+            .into_iter()
+            .rev()
+            .find(|entry| entry.profile == profile.name && !entry.models.is_empty())?;
+        let models = [ENV_MODEL, ENV_DEFAULT_OPUS_MODEL, ENV_DEFAULT_SONNET_MODEL]
+            .iter()
+            .filter_map(|key| profile.env.get(*key))
+            .map(|model| model.trim())
+            .filter(|model| !model.is_empty());
+        let catalog = self
+            .price_catalog
+            .get_or_insert_with(pricing::cached_catalog);
+        pricing::CostEstimate::check(models, &last, catalog, &self.config.cost_ceiling)
+    }
+
+    /// Remember when a profile was last launched, for the compact list
+    fn record_launch(&mut self, name: &str) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.config.last_used.insert(name.to_string(), now);
+        if let Err(e) = self.config.save() {
+            self.set_status(format!("Failed to save config: {}", e));
+        }
+    }
+
+    /// Pick up the upstream models a finished session used from the usage history
+    pub fn note_session_upstream(&mut self, name: &str) {
+        let launched = self.config.last_used.get(name).copied().unwrap_or_default();
+        if let Some(entry) = pricing::read_history().into_iter().rev().find(|entry| {
+            entry.profile == name && entry.timestamp >= launched && !entry.models.is_empty()
+        }) {
+            self.last_upstream.insert(
+                name.to_string(),
+                pricing::format_upstream_models(&entry.models),
+            );
+        }
+    }
+
+    /// Count a finished session in the local analytics, when they are on. The session
+    /// started when the launch was recorded in `last_used`.
+    pub fn record_analytics(&mut self, profile: &Profile) {
+        let started = self
+            .config
+            .last_used
+            .get(&profile.name)
+            .copied()
+            .unwrap_or_default();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if let Err(e) = analytics::record_session(
+            &self.config.analytics,
+            &profile.name,
+            profile.primary_model(),
+            started,
+            std::time::Duration::from_secs(now.saturating_sub(started)),
+        ) {
+            self.set_status(format!("Failed to update analytics: {}", e));
+        }
+    }
+
+    /// Check the upstreams without a recent result, or all of them when `force` is set
+    pub fn refresh_health(&mut self, force: bool) {
+        let started = self.health.refresh(&self.config.profiles, force);
+        if force {
+            self.set_status(match started {
+                0 => "Health checks already running".to_string(),
+                n => format!("Checking {} upstreams", n),
+            });
+        }
+    }
+
+    /// Re-read the Codex usage limits saved by the last Codex session
+    pub fn refresh_codex_quota(&mut self) {
+        self.codex_quota = proxy::read_codex_quota();
+    }
+
+    /// Apply a command from the control socket
+    pub fn handle_control(&mut self, command: &Command) -> Result<String, String> {
+        let select = |app: &mut App, name: &str| {
+            let index = app
+                .config
+                .profiles
+                .iter()
+                .position(|p| p.name == name)
+                .ok_or_else(|| format!("no profile named '{}'", name))?;
+            app.list_state.select(Some(index));
+            Ok::<_, String>(())
+        };
+        match command {
+            Command::Status => Ok(format!(
+                "idle {}",
+                self.current_profile().map_or("", |p| p.name.as_str())
+            )),
+            Command::Stop => Err("no session is running".to_string()),
+            _ if self.mode != AppMode::Normal => {
+                Err("the TUI is busy (a dialog or editor is open)".to_string())
             }
+            Command::Reload => {
+                let config = Config::load().map_err(|e| e.to_string())?;
+                let selected = self.current_profile().map(|p| p.name.clone());
+                self.config = config;
+                let index = selected
+                    .and_then(|name| self.config.profiles.iter().position(|p| p.name == name))
+                    .unwrap_or_else(|| self.config.default_profile_index());
+                self.list_state.select(Some(index));
+                Ok(format!("{} profiles", self.config.profiles.len()))
+            }
+            Command::Select(name) => select(self, name).map(|()| String::new()),
+            Command::Launch(name) => {
+                if let Some(name) = name {
+                    select(self, name)?;
+                }
+                self.select_current();
+                if let Some((profile, _)) = &self.pending_launch {
+                    return Ok(format!("waiting for cost confirmation of {}", profile.name));
+                }
+                self.selected_profile
+                    .as_ref()
+                    .map(|p| format!("launching {}", p.name))
+                    .ok_or_else(|| {
+                        self.status_message
+                            .take()
+                            .unwrap_or_else(|| "no profile selected".to_string())
+                    })
+            }
+        }
+    }
+
+    /// Switch between the detailed and compact profile list and remember the choice
+    fn toggle_list_view(&mut self) {
+        self.config.list_view = self.config.list_view.toggled();
+        if let Err(e) = self.config.save() {
+            self.set_status(format!("Failed to save config: {}", e));
+        }
+    }
+
+    /// Open the API key page of the selected profile's provider in the browser
+    fn open_console(&mut self) {
+        let Some(provider) = self.current_profile().map(Profile::provider) else {
+            return;
+        };
+        let Some(url) = provider.console_url() else {
+            self.set_status(format!("No console link for {} profiles", provider.label()));
+            return;
+        };
+        match crate::openai_oauth::open_browser(url) {
+            Ok(()) => self.set_status(format!("Opened the {} console: {}", provider.label(), url)),
+            Err(e) => self.set_status(format!("Failed to open {}: {}", url, e)),
         }
     }
 
@@ -266,17 +1106,410 @@ impl App {
             Action::HideHelp => self.mode = AppMode::Normal,
             Action::EditProfile => self.enter_edit_mode(),
             Action::CreateProfile => self.create_new_profile(),
+            Action::DuplicateProfile => self.duplicate_current_profile(),
             Action::SaveEdit => self.save_profile_edit(),
-            Action::CancelEdit => self.mode = AppMode::Normal,
+            Action::CancelEdit => self.cancel_edit(),
             Action::ResetProfile => self.reset_current_profile(),
             Action::ResetAll => self.reset_all_profiles(),
-            Action::ResetOAuth => self.clear_oauth_tokens(),
+            Action::ResetOAuth => {
+                if self.is_selected_profile_codex() {
+                    self.mode = AppMode::ConfirmResetOAuth;
+                }
+            }
+            Action::ConfirmResetOAuth => {
+                self.mode = AppMode::Normal;
+                self.clear_oauth_tokens();
+            }
+            Action::CancelResetOAuth => self.mode = AppMode::Normal,
             Action::DeleteProfile => self.delete_current_profile(),
+            Action::ToggleWorkspace => self.toggle_workspace(),
+            Action::ToggleListView => self.toggle_list_view(),
+            Action::OpenConsole => self.open_console(),
+            Action::ShowNotes => self.mode = AppMode::Notes { scroll: 0 },
+            Action::HideNotes => self.mode = AppMode::Normal,
+            Action::ShowLogs => {
+                self.refresh_logs();
+                self.mode = AppMode::Logs { back: 0 };
+            }
+            Action::HideLogs => self.mode = AppMode::Normal,
+            Action::CycleLogFilter => self.cycle_log_filter(),
+            Action::ShowInspector => {
+                self.inspector.reload();
+                self.mode = AppMode::Inspector;
+            }
+            Action::HideInspector => self.mode = AppMode::Normal,
+            Action::ReplayCapture => {
+                if let Err(e) = self.inspector.replay(&self.config) {
+                    self.inspector.status = Some(e.to_string());
+                }
+            }
+            Action::ShowUsage => {
+                self.usage = UsageCounters::load();
+                self.analytics = self.config.analytics.is_active().then(Analytics::load);
+                self.mode = AppMode::Usage;
+            }
+            Action::HideUsage => self.mode = AppMode::Normal,
+            Action::StartCompare => self.start_compare(),
+            Action::CreateFromShellEnv => self.create_profile_from_shell_env(),
+            Action::HideCompare => self.mode = AppMode::Normal,
+            Action::ToggleCompareDifferences => {
+                if let AppMode::Compare {
+                    left,
+                    only_differences,
+                    ..
+                } = self.mode
+                {
+                    self.mode = AppMode::Compare {
+                        left,
+                        scroll: 0,
+                        only_differences: !only_differences,
+                    };
+                }
+            }
+            Action::ConfirmCost => {
+                self.mode = AppMode::Normal;
+                if let Some((profile, _)) = self.pending_launch.take() {
+                    self.launch(profile);
+                }
+            }
+            Action::CancelCost => {
+                self.mode = AppMode::Normal;
+                self.pending_launch = None;
+            }
+            Action::StartTagFilter => self.mode = AppMode::TagFilter,
+            Action::ApplyTagFilter => self.mode = AppMode::Normal,
+            Action::ClearTagFilter => {
+                self.tag_filter.clear();
+                self.reselect_visible();
+                self.mode = AppMode::Normal;
+            }
+            Action::StartTutorial => self.mode = AppMode::Tutorial { step: 0 },
+            Action::NextTutorialStep => self.move_tutorial(1),
+            Action::PreviousTutorialStep => self.move_tutorial(-1),
+            Action::EndTutorial => self.end_tutorial(),
+            Action::ImportDetected => self.import_detected_profiles(),
+            Action::SkipDetected => {
+                self.detected_profiles.clear();
+                self.mode = AppMode::Normal;
+                self.start_tutorial_if_unseen();
+            }
+            Action::CheckHealth => self.refresh_health(true),
+            Action::TrustWorkspace => self.trust_workspace(),
+            Action::UnloadLeftoverModels => self.unload_leftover_models(),
+            Action::KeepLeftoverModels => {
+                Self::forget_leases(&std::mem::take(&mut self.leftover_models));
+                self.mode = AppMode::Normal;
+            }
+            Action::SkipWorkspaceTrust => {
+                self.mode = AppMode::Normal;
+                self.set_status("Project settings ignored; its profile pin still applies");
+                self.start_tutorial_if_unseen();
+            }
+        }
+    }
+
+    /// Indices into `config.profiles` of the profiles passing the tag filter
+    pub fn visible_profiles(&self) -> Vec<usize> {
+        self.config
+            .profiles
+            .iter()
+            .enumerate()
+            .filter(|(_, profile)| profile.matches_tags(&self.tag_filter))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn push_tag_filter_char(&mut self, c: char) {
+        self.tag_filter.push(c);
+        self.reselect_visible();
+    }
+
+    pub fn pop_tag_filter_char(&mut self) {
+        self.tag_filter.pop();
+        self.reselect_visible();
+    }
+
+    /// Keep the selection on a visible profile: the current one if it still
+    /// passes the filter, else the first that does
+    fn reselect_visible(&mut self) {
+        let visible = self.visible_profiles();
+        let selected = self.list_state.selected();
+        if selected.is_some_and(|i| visible.contains(&i)) {
+            return;
+        }
+        self.list_state.select(visible.first().copied());
+    }
+
+    /// Scroll the notes popup, never past the last line of the notes
+    pub fn scroll_notes(&mut self, delta: i32) {
+        let AppMode::Notes { scroll } = self.mode else {
+            return;
+        };
+        let max = self
+            .current_profile()
+            .map(|p| p.notes.lines().count().saturating_sub(1))
+            .unwrap_or(0);
+        let next = (i64::from(scroll) + i64::from(delta)).clamp(0, max as i64);
+        self.mode = AppMode::Notes {
+            scroll: u16::try_from(next).unwrap_or(u16::MAX),
+        };
+    }
+
+    /// Compare the selected profile with the next one; moving the selection then
+    /// changes the right-hand side
+    fn start_compare(&mut self) {
+        let Some(left) = self.list_state.selected() else {
+            return;
+        };
+        if self.visible_profiles().len() < 2 {
+            self.set_status("Need at least two profiles to compare");
+            return;
+        }
+        self.next();
+        self.mode = AppMode::Compare {
+            left,
+            scroll: 0,
+            only_differences: false,
+        };
+    }
+
+    /// Profiles being compared, left then right
+    pub fn compared_profiles(&self) -> Option<(&Profile, &Profile)> {
+        let AppMode::Compare { left, .. } = self.mode else {
+            return None;
+        };
+        Some((self.config.profiles.get(left)?, self.current_profile()?))
+    }
+
+    /// Scroll the compare view, never past its last row
+    pub fn scroll_compare(&mut self, delta: i32) {
+        let AppMode::Compare {
+            left,
+            scroll,
+            only_differences,
+        } = self.mode
+        else {
+            return;
+        };
+        let max = self
+            .compared_profiles()
+            .map(|(a, b)| {
+                a.env_compare(b)
+                    .iter()
+                    .filter(|row| !only_differences || row.differs())
+                    .count()
+                    .saturating_sub(1)
+            })
+            .unwrap_or(0);
+        let next = (i64::from(scroll) + i64::from(delta)).clamp(0, max as i64);
+        self.mode = AppMode::Compare {
+            left,
+            scroll: u16::try_from(next).unwrap_or(u16::MAX),
+            only_differences,
+        };
+    }
+
+    /// Pick up entries appended to the request log, e.g. by a session running in
+    /// another terminal
+    pub fn refresh_logs(&mut self) {
+        self.request_log.poll();
+    }
+
+    /// Request log entries that pass the profile filter, oldest first
+    pub fn visible_log_entries(&self) -> Vec<&proxy::RequestLogEntry> {
+        self.request_log
+            .entries()
+            .filter(|entry| {
+                self.log_filter
+                    .as_ref()
+                    .is_none_or(|profile| &entry.profile == profile)
+            })
+            .collect()
+    }
+
+    /// Show all profiles, then each profile in the log in turn
+    fn cycle_log_filter(&mut self) {
+        let mut profiles: Vec<&str> = self
+            .request_log
+            .entries()
+            .map(|entry| entry.profile.as_str())
+            .collect();
+        profiles.sort_unstable();
+        profiles.dedup();
+        let next = match &self.log_filter {
+            None => profiles.first(),
+            Some(current) => profiles.iter().find(|p| **p > current.as_str()),
+        };
+        self.log_filter = next.map(|p| p.to_string());
+        self.mode = AppMode::Logs { back: 0 };
+    }
+
+    /// Scroll the log viewer, never past the oldest entry; `None` jumps back to
+    /// following new entries
+    pub fn scroll_logs(&mut self, delta: Option<i64>) {
+        let AppMode::Logs { back } = self.mode else {
+            return;
+        };
+        let max = self.visible_log_entries().len().saturating_sub(1);
+        let back = match delta {
+            Some(delta) => (back as i64).saturating_sub(delta).clamp(0, max as i64) as usize,
+            None => 0,
+        };
+        self.mode = AppMode::Logs { back };
+    }
+
+    /// On first run, offer profiles for provider API keys set in the environment
+    pub fn offer_detected_profiles(&mut self) {
+        if self.config.tutorial_seen || self.mode != AppMode::Normal {
+            return;
+        }
+        self.detected_profiles = self
+            .config
+            .detect_provider_profiles(|var| std::env::var(var).ok());
+        if !self.detected_profiles.is_empty() {
+            self.mode = AppMode::ImportDetected;
+        }
+    }
+
+    /// Point out profiles whose keys expire soon or already have
+    pub fn warn_expiring_keys(&mut self) {
+        let today = Date::today();
+        let expiring: Vec<String> = self
+            .config
+            .profiles
+            .iter()
+            .filter_map(|profile| {
+                let expiry = profile.key_expiry(today)?;
+                Some(format!("'{}' ({})", profile.name, expiry.label()))
+            })
+            .collect();
+        if !expiring.is_empty() {
+            self.set_status(format!("Keys to rotate: {}", expiring.join(", ")));
+        }
+    }
+
+    /// Offer to unload the models of earlier runs that died with them loaded
+    pub fn offer_leftover_unload(&mut self) {
+        if self.mode != AppMode::Normal {
+            return;
+        }
+        let Some(file) = LeaseFile::default_file() else {
+            return;
+        };
+        self.leftover_models = file.stale(crate::platform::process_running);
+        if !self.leftover_models.is_empty() {
+            self.mode = AppMode::UnloadLeftoverModels;
+        }
+    }
+
+    fn forget_leases(leases: &[ModelLease]) {
+        if let Some(file) = LeaseFile::default_file() {
+            let _ = file.remove(leases);
+        }
+    }
+
+    /// Unload the leftover models; ones that fail stay recorded for the next start
+    fn unload_leftover_models(&mut self) {
+        self.mode = AppMode::Normal;
+        let leftover = std::mem::take(&mut self.leftover_models);
+        let (unloaded, failed): (Vec<_>, Vec<_>) = leftover
+            .into_iter()
+            .map(|lease| {
+                let result = lease
+                    .backend
+                    .unload_blocking(&lease.target_url, &lease.model);
+                (lease, result)
+            })
+            .partition(|(_, result)| result.is_ok());
+        let unloaded: Vec<ModelLease> = unloaded.into_iter().map(|(lease, _)| lease).collect();
+        Self::forget_leases(&unloaded);
+        match failed.first() {
+            Some((lease, Err(e))) => self.set_status(format!(
+                "Failed to unload {} from {}: {}",
+                lease.model,
+                lease.backend.label(),
+                e
+            )),
+            _ => self.set_status(format!("Unloaded {} leftover models", unloaded.len())),
+        }
+    }
+
+    /// Add the offered profiles and select the first of them
+    fn import_detected_profiles(&mut self) {
+        let detected = std::mem::take(&mut self.detected_profiles);
+        let first = self.config.profiles.len();
+        let count = detected.len();
+        self.config
+            .profiles
+            .extend(detected.into_iter().map(|(_, profile)| profile));
+        self.list_state.select(Some(first));
+        self.mode = AppMode::Normal;
+        match self.config.save() {
+            Ok(()) => self.set_status(format!("Created {} profiles from your environment", count)),
+            Err(e) => self.set_status(format!("Failed to save config: {}", e)),
+        }
+        self.start_tutorial_if_unseen();
+    }
+
+    /// Snapshot the Claude Code settings exported in this shell into a new profile
+    fn create_profile_from_shell_env(&mut self) {
+        let Some(profile) = self.config.profile_from_shell_env(std::env::vars()) else {
+            self.set_status(
+                "No ANTHROPIC_*, CLAUDE_* or API_TIMEOUT_MS variables are set in this shell",
+            );
+            return;
+        };
+        let name = profile.name.clone();
+        let count = profile.env.len();
+        self.config.profiles.push(profile);
+        self.list_state.select(Some(self.config.profiles.len() - 1));
+        self.reselect_visible();
+        match self.config.save() {
+            Ok(()) => self.set_status(format!(
+                "Created '{}' from {} shell variables; press e to rename it",
+                name, count
+            )),
+            Err(e) => self.set_status(format!("Failed to save config: {}", e)),
+        }
+    }
+
+    /// Show the guided tour on first run, once nothing else is open
+    pub fn start_tutorial_if_unseen(&mut self) {
+        if !self.config.tutorial_seen && self.mode == AppMode::Normal {
+            self.mode = AppMode::Tutorial { step: 0 };
+        }
+    }
+
+    /// Step through the tour; moving past the last step ends it
+    fn move_tutorial(&mut self, delta: isize) {
+        let AppMode::Tutorial { step } = self.mode else {
+            return;
+        };
+        match step.checked_add_signed(delta) {
+            Some(next) if next < TUTORIAL_STEPS.len() => {
+                self.mode = AppMode::Tutorial { step: next };
+            }
+            Some(_) => self.end_tutorial(),
+            None => {}
+        }
+    }
+
+    fn end_tutorial(&mut self) {
+        self.mode = AppMode::Normal;
+        if self.config.tutorial_seen {
+            return;
+        }
+        self.config.tutorial_seen = true;
+        if let Err(e) = self.config.save() {
+            self.set_status(format!("Failed to save config: {}", e));
         }
     }
 
     /// Enter edit mode for the currently selected profile
     fn enter_edit_mode(&mut self) {
+        if self.selected_is_included("edit") {
+            return;
+        }
         let Some(profile) = self.current_profile() else {
             return;
         };
@@ -286,6 +1519,11 @@ impl App {
         let api_key = env_value(profile, ENV_AUTH_TOKEN);
         let url = env_value(profile, ENV_BASE_URL);
         let proxy_url = env_value(profile, ENV_PROXY_TARGET_URL);
+        let is_codex = profile.provider() == Provider::Codex;
+        let haiku_sampling = env_value(profile, ENV_HAIKU_SAMPLING);
+        let sonnet_sampling = env_value(profile, ENV_SONNET_SAMPLING);
+        let opus_sampling = env_value(profile, ENV_OPUS_SAMPLING);
+        let env_editor = EnvEditor::new(&profile.env, &EDIT_FORM_ENV_KEYS);
 
         let fallback_model = env_value(profile, ENV_MODEL);
         let haiku = profile
@@ -305,16 +1543,25 @@ impl App {
             .unwrap_or(fallback_model);
 
         self.name_input = Input::new(name);
-        self.description_input = Input::new(description);
+        self.description_input = TextArea::new(&description);
         self.api_key_input = Input::new(api_key);
         self.url_input = Input::new(url);
         self.proxy_url_input = Input::new(proxy_url.clone());
         self.haiku_model_input = Input::new(haiku);
         self.sonnet_model_input = Input::new(sonnet);
         self.opus_model_input = Input::new(opus);
+        self.show_advanced = [&haiku_sampling, &sonnet_sampling, &opus_sampling]
+            .iter()
+            .any(|value| !value.is_empty());
+        self.haiku_sampling_input = Input::new(haiku_sampling);
+        self.sonnet_sampling_input = Input::new(sonnet_sampling);
+        self.opus_sampling_input = Input::new(opus_sampling);
+        self.env_editor = env_editor;
         self.reveal_api_key = false;
 
-        if proxy_url.contains(CODEX_PROXY_INDICATOR) {
+        // Local servers are asked for their models when the picker is first opened
+        self.picker_source = None;
+        if is_codex {
             self.load_codex_models();
         }
 
@@ -327,20 +1574,53 @@ impl App {
     /// Initialize the form for creating a new profile
     fn create_new_profile(&mut self) {
         self.name_input = Input::new("new-profile".to_string());
-        self.description_input = Input::new("My custom profile".to_string());
+        self.description_input = TextArea::new("My custom profile");
         self.api_key_input = Input::default();
         self.url_input = Input::new(proxy::PROXY_ANTHROPIC_URL.to_string());
         self.proxy_url_input = Input::default();
         self.haiku_model_input = Input::default();
         self.sonnet_model_input = Input::default();
         self.opus_model_input = Input::default();
+        self.haiku_sampling_input = Input::default();
+        self.sonnet_sampling_input = Input::default();
+        self.opus_sampling_input = Input::default();
+        self.env_editor = EnvEditor::default();
         self.reveal_api_key = false;
+        self.show_advanced = false;
+        self.picker_source = None;
         self.mode = AppMode::EditProfile {
             focused_field: EDIT_FIELD_NAME,
             is_creating: true,
         };
     }
 
+    /// Copy the selected profile, everything included, and open the copy in the
+    /// edit form; it is only kept if the edit is saved
+    fn duplicate_current_profile(&mut self) {
+        let Some(i) = self.list_state.selected() else {
+            return;
+        };
+        let mut copy = self.config.profiles[i].clone();
+        let base = format!("{}-copy", copy.name);
+        copy.name = std::iter::once(base.clone())
+            .chain((2..).map(|n| format!("{}-{}", base, n)))
+            .find(|name| !self.config.profiles.iter().any(|p| &p.name == name))
+            .unwrap_or(base);
+        self.config.profiles.insert(i + 1, copy);
+        self.list_state.select(Some(i + 1));
+        self.enter_edit_mode();
+        self.unsaved_copy = Some(i + 1);
+    }
+
+    /// Leave the edit form without saving, dropping an unsaved duplicate
+    fn cancel_edit(&mut self) {
+        self.mode = AppMode::Normal;
+        if let Some(i) = self.unsaved_copy.take() {
+            self.config.profiles.remove(i);
+            self.list_state.select(Some(i - 1));
+        }
+    }
+
     /// Save the current profile edit
     fn save_profile_edit(&mut self) {
         let AppMode::EditProfile { is_creating, .. } = self.mode else {
@@ -348,18 +1628,36 @@ impl App {
         };
 
         let name = self.name_input.value().to_string();
-        let description = self.description_input.value().to_string();
+        let description = self.description_input.value();
         let updates = [
             (ENV_AUTH_TOKEN, self.api_key_input.value().to_string()),
             (ENV_BASE_URL, self.url_input.value().to_string()),
-            (ENV_PROXY_TARGET_URL, self.proxy_url_input.value().to_string()),
+            (ENV_PROXY_TARGET_URL, self.proxy_url_input.value().trim().to_string()),
             (ENV_DEFAULT_HAIKU_MODEL, self.haiku_model_input.value().to_string()),
             (ENV_DEFAULT_SONNET_MODEL, self.sonnet_model_input.value().to_string()),
             (ENV_DEFAULT_OPUS_MODEL, self.opus_model_input.value().to_string()),
+            (ENV_HAIKU_SAMPLING, self.haiku_sampling_input.value().trim().to_string()),
+            (ENV_SONNET_SAMPLING, self.sonnet_sampling_input.value().trim().to_string()),
+            (ENV_OPUS_SAMPLING, self.opus_sampling_input.value().trim().to_string()),
         ];
 
+        for (key, value) in &updates[updates.len() - 3..] {
+            if let Err(e) = proxy::Sampling::parse(value) {
+                self.set_status(format!("Failed to save: {}: {}", key, e));
+                return;
+            }
+        }
+        let other_vars = match self.env_editor.vars(&EDIT_FORM_ENV_KEYS) {
+            Ok(vars) => vars,
+            Err(e) => {
+                self.set_status(format!("Failed to save: {}", e));
+                return;
+            }
+        };
+
+        let copied = self.unsaved_copy.take().is_some();
         if is_creating {
-            let mut env = HashMap::new();
+            let mut env: HashMap<String, String> = other_vars.into_iter().collect();
             for (key, value) in updates {
                 if !value.is_empty() {
                     env.insert(key.to_string(), value);
@@ -368,6 +1666,18 @@ impl App {
             let new_profile = Profile {
                 name: name.clone(),
                 description,
+                notes: String::new(),
+                tags: Vec::new(),
+                key_expires_on: None,
+                proxy_port: None,
+                proxy_bind: None,
+                headers: BTreeMap::new(),
+                routes: BTreeMap::new(),
+                mcp: BTreeMap::new(),
+                fanout: Vec::new(),
+                model_map: BTreeMap::new(),
+                header_policy: HeaderPolicy::default(),
+                inherit_env: EnvFilter::default(),
                 env,
             };
             self.config.profiles.push(new_profile);
@@ -376,8 +1686,12 @@ impl App {
         } else if let Some(i) = self.list_state.selected()
             && let Some(profile) = self.config.profiles.get_mut(i)
         {
-            profile.name = name;
+            let old_name = std::mem::replace(&mut profile.name, name.clone());
             profile.description = description;
+            profile
+                .env
+                .retain(|key, _| EDIT_FORM_ENV_KEYS.contains(&key.as_str()));
+            profile.env.extend(other_vars);
             for (key, value) in updates {
                 if value.is_empty() {
                     profile.env.remove(key);
@@ -385,7 +1699,15 @@ impl App {
                     profile.env.insert(key.to_string(), value);
                 }
             }
-            self.set_status("Profile updated successfully");
+            if old_name != name {
+                self.config.retarget_workspaces(&old_name, Some(&name));
+                self.config.retarget_last_used(&old_name, Some(&name));
+            }
+            self.set_status(if copied {
+                "Profile copy created"
+            } else {
+                "Profile updated successfully"
+            });
         }
 
         if let Err(e) = self.config.save() {
@@ -399,6 +1721,9 @@ impl App {
         let Some(i) = self.list_state.selected() else {
             return;
         };
+        if self.selected_is_included("change") {
+            return;
+        }
 
         let name = self.config.profiles[i].name.clone();
         let default_config = Config::create_default();
@@ -420,6 +1745,7 @@ impl App {
     /// Reset all profiles to defaults and clear OAuth tokens
     fn reset_all_profiles(&mut self) {
         let _ = crate::openai_oauth::clear_tokens();
+        let _ = crate::anthropic_oauth::clear_tokens();
         self.config = Config::create_default();
 
         if let Err(e) = self.config.save() {
@@ -445,9 +1771,14 @@ impl App {
         let Some(i) = self.list_state.selected() else {
             return;
         };
+        if self.selected_is_included("remove") {
+            return;
+        }
 
         let name = self.config.profiles[i].name.clone();
         self.config.profiles.remove(i);
+        self.config.retarget_workspaces(&name, None);
+        self.config.retarget_last_used(&name, None);
         self.set_status(format!("Profile '{}' deleted", name));
 
         let len = self.config.profiles.len();
@@ -456,6 +1787,7 @@ impl App {
         } else if i >= len {
             self.list_state.select(Some(len - 1));
         }
+        self.reselect_visible();
 
         if let Err(e) = self.config.save() {
             self.set_status(format!("Failed to save config: {}", e));
@@ -463,19 +1795,24 @@ impl App {
     }
 
     fn move_selection(&mut self, delta: isize) {
-        let len = self.config.profiles.len();
-        if len == 0 {
+        let visible = self.visible_profiles();
+        if visible.is_empty() {
             return;
         }
-        let current = self.list_state.selected().unwrap_or(0) as isize;
-        let next = (current + delta).rem_euclid(len as isize) as usize;
-        self.list_state.select(Some(next));
+        let current = self
+            .list_state
+            .selected()
+            .and_then(|i| visible.iter().position(|&v| v == i))
+            .unwrap_or(0) as isize;
+        let next = (current + delta).rem_euclid(visible.len() as isize) as usize;
+        self.list_state.select(Some(visible[next]));
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{IncludedProfile, ListView};
 
     #[test]
     fn move_selection_wraps_profiles() {
@@ -515,6 +1852,18 @@ mod tests {
         let custom_profile = Profile {
             name: "custom".to_string(),
             description: "Custom".to_string(),
+            notes: String::new(),
+            tags: Vec::new(),
+            key_expires_on: None,
+            proxy_port: None,
+            proxy_bind: None,
+            headers: BTreeMap::new(),
+            routes: BTreeMap::new(),
+            mcp: BTreeMap::new(),
+            fanout: Vec::new(),
+            model_map: BTreeMap::new(),
+            header_policy: HeaderPolicy::default(),
+            inherit_env: EnvFilter::default(),
             env: HashMap::from([("KEY".to_string(), "VALUE".to_string())]),
         };
         app.config.profiles.push(custom_profile);
@@ -534,6 +1883,18 @@ mod tests {
         app.config.profiles.push(Profile {
             name: "temporary".to_string(),
             description: String::new(),
+            notes: String::new(),
+            tags: Vec::new(),
+            key_expires_on: None,
+            proxy_port: None,
+            proxy_bind: None,
+            headers: BTreeMap::new(),
+            routes: BTreeMap::new(),
+            mcp: BTreeMap::new(),
+            fanout: Vec::new(),
+            model_map: BTreeMap::new(),
+            header_policy: HeaderPolicy::default(),
+            inherit_env: EnvFilter::default(),
             env: HashMap::new(),
         });
 
@@ -576,12 +1937,54 @@ mod tests {
         assert_eq!(app.list_state.selected(), Some(last_index - 1));
     }
 
+    #[test]
+    fn included_profiles_are_changed_in_their_fragment() {
+        let mut config = Config::create_default();
+        let shared = config.profiles.remove(1);
+        config.profiles.push(shared.clone());
+        config.included.insert(
+            shared.name.clone(),
+            IncludedProfile {
+                source: "work/shared.toml".to_string(),
+                profile: shared.clone(),
+            },
+        );
+        let mut app = App::new(config);
+        let last = app.config.profiles.len() - 1;
+        app.list_state.select(Some(last));
+
+        app.handle_action(Action::DeleteProfile);
+        assert_eq!(app.config.profiles[last], shared);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("'zai' comes from work/shared.toml; remove it there")
+        );
+        app.handle_action(Action::EditProfile);
+        assert_eq!(app.mode, AppMode::Normal);
+
+        // A copy is a local profile of its own
+        app.handle_action(Action::DuplicateProfile);
+        assert!(matches!(app.mode, AppMode::EditProfile { .. }));
+    }
+
     #[test]
     fn edit_profile_falls_back_to_generic_model() {
         let mut app = App::new(Config::create_default());
         let custom_profile = Profile {
             name: "fallback_test".to_string(),
             description: "Test".to_string(),
+            notes: String::new(),
+            tags: Vec::new(),
+            key_expires_on: None,
+            proxy_port: None,
+            proxy_bind: None,
+            headers: BTreeMap::new(),
+            routes: BTreeMap::new(),
+            mcp: BTreeMap::new(),
+            fanout: Vec::new(),
+            model_map: BTreeMap::new(),
+            header_policy: HeaderPolicy::default(),
+            inherit_env: EnvFilter::default(),
             env: HashMap::from([(ENV_MODEL.to_string(), "fallback-model".to_string())]),
         };
         app.config.profiles.push(custom_profile);
@@ -604,6 +2007,18 @@ mod tests {
         let profile = Profile {
             name: "codex-test".to_string(),
             description: "Test".to_string(),
+            notes: String::new(),
+            tags: Vec::new(),
+            key_expires_on: None,
+            proxy_port: None,
+            proxy_bind: None,
+            headers: BTreeMap::new(),
+            routes: BTreeMap::new(),
+            mcp: BTreeMap::new(),
+            fanout: Vec::new(),
+            model_map: BTreeMap::new(),
+            header_policy: HeaderPolicy::default(),
+            inherit_env: EnvFilter::default(),
             env,
         };
         app.config.profiles.push(profile);
@@ -611,4 +2026,415 @@ mod tests {
 
         assert!(app.is_selected_profile_codex());
     }
+
+    #[test]
+    fn reset_oauth_asks_first_and_only_for_codex_profiles() {
+        let mut app = App::new(Config::create_default());
+        let codex = app
+            .config
+            .profiles
+            .iter()
+            .position(|p| p.provider() == Provider::Codex)
+            .unwrap();
+        app.list_state.select(Some(codex));
+        assert!(app.is_selected_profile_codex());
+
+        app.handle_action(Action::ResetOAuth);
+        assert_eq!(app.mode, AppMode::ConfirmResetOAuth);
+        app.handle_action(Action::CancelResetOAuth);
+        assert_eq!(app.mode, AppMode::Normal);
+        assert!(app.status_message.is_none());
+
+        // Editing it lists Codex models even after the URL field is cleared
+        app.handle_action(Action::EditProfile);
+        app.proxy_url_input = Input::default();
+        assert_eq!(app.model_source(), Some(ModelSource::Codex));
+        app.handle_action(Action::CancelEdit);
+
+        app.list_state.select(Some(0));
+        assert!(!app.is_selected_profile_codex());
+        app.handle_action(Action::ResetOAuth);
+        assert_eq!(app.mode, AppMode::Normal);
+    }
+
+    #[test]
+    fn duplicating_copies_everything_until_cancelled() {
+        let mut app = App::new(Config::create_default());
+        app.list_state.select(Some(1));
+        let original = app.config.profiles[1].clone();
+        let count = app.config.profiles.len();
+
+        app.handle_action(Action::DuplicateProfile);
+        assert_eq!(app.config.profiles.len(), count + 1);
+        assert_eq!(app.list_state.selected(), Some(2));
+        assert!(matches!(
+            app.mode,
+            AppMode::EditProfile {
+                is_creating: false,
+                ..
+            }
+        ));
+        assert_eq!(app.name_input.value(), format!("{}-copy", original.name));
+        let copy = &app.config.profiles[2];
+        assert_eq!(copy.env, original.env);
+        assert_eq!(copy.description, original.description);
+
+        app.handle_action(Action::CancelEdit);
+        assert_eq!(app.config.profiles.len(), count);
+        assert_eq!(app.list_state.selected(), Some(1));
+
+        // A second copy of a profile whose copy already exists gets a number
+        let mut copy = original.clone();
+        copy.name = format!("{}-copy", original.name);
+        app.config.profiles.push(copy);
+        app.handle_action(Action::DuplicateProfile);
+        assert_eq!(app.name_input.value(), format!("{}-copy-2", original.name));
+        app.handle_action(Action::CancelEdit);
+    }
+
+    #[test]
+    fn model_picker_filters_by_all_terms() {
+        let mut app = App::new(Config::create_default());
+        app.load_codex_models();
+        app.open_model_picker(EDIT_FIELD_SONNET, false);
+        for c in "5.2 codex HIGH".chars() {
+            app.push_model_picker_char(c);
+        }
+
+        let filtered = app.filtered_models();
+        assert!(!filtered.is_empty());
+        assert!(
+            filtered
+                .iter()
+                .all(|m| m.contains("5.2") && m.contains("codex") && m.contains("high"))
+        );
+
+        app.select_model_from_picker(EDIT_FIELD_SONNET, false);
+        assert_eq!(app.sonnet_model_input.value(), "gpt-5.2-codex-high");
+        assert!(app.model_picker_query.is_empty());
+    }
+
+    #[test]
+    fn model_picker_remembers_last_selection_per_field() {
+        let mut app = App::new(Config::create_default());
+        app.load_codex_models();
+        app.model_picker_last
+            .insert(EDIT_FIELD_HAIKU, "gpt-5.1-codex-mini".to_string());
+        app.haiku_model_input = Input::default();
+
+        app.open_model_picker(EDIT_FIELD_HAIKU, true);
+
+        assert_eq!(
+            app.filtered_models()[app.model_picker_index],
+            "gpt-5.1-codex-mini"
+        );
+    }
+
+    #[test]
+    fn external_config_changes_are_reloaded_around_an_open_edit() {
+        let mut app = App::new(Config::create_default());
+        let unchanged = app.config.clone();
+        app.reload_config(unchanged);
+        assert_eq!(app.status_message, None);
+
+        app.list_state.select(Some(1));
+        let name = app.config.profiles[1].name.clone();
+        app.handle_action(Action::EditProfile);
+        app.url_input = Input::new("http://unsaved.example".to_string());
+
+        let mut external = app.config.clone();
+        external.profiles.remove(0);
+        external.profiles[0].description = "Edited elsewhere".to_string();
+        app.reload_config(external.clone());
+        assert_eq!(app.list_state.selected(), Some(0));
+        assert_eq!(app.config.profiles[0].description, "Edited elsewhere");
+        assert!(matches!(
+            app.mode,
+            AppMode::EditProfile {
+                is_creating: false,
+                ..
+            }
+        ));
+        assert_eq!(app.url_input.value(), "http://unsaved.example");
+        let status = app.status_message.clone().unwrap_or_default();
+        assert!(status.contains("edits are kept"), "{}", status);
+
+        // Saving the form brings back a profile deleted meanwhile
+        external.profiles.retain(|p| p.name != name);
+        app.reload_config(external);
+        assert!(matches!(
+            app.mode,
+            AppMode::EditProfile {
+                is_creating: true,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn save_edit_persists_trimmed_proxy_target_url() {
+        let mut app = App::new(Config::create_default());
+        app.list_state.select(Some(0));
+        app.handle_action(Action::EditProfile);
+        app.proxy_url_input = Input::new("  http://localhost:1234/v1 ".to_string());
+
+        app.handle_action(Action::SaveEdit);
+
+        assert_eq!(
+            app.config.profiles[0].proxy_target_url(),
+            Some("http://localhost:1234/v1")
+        );
+    }
+
+    #[test]
+    fn save_edit_validates_and_stores_sampling_overrides() {
+        let mut app = App::new(Config::create_default());
+        app.list_state.select(Some(0));
+        app.handle_action(Action::EditProfile);
+        assert!(!app.show_advanced);
+        app.mode = AppMode::EditProfile {
+            focused_field: EDIT_FIELD_OPUS_SAMPLING,
+            is_creating: false,
+        };
+        app.toggle_advanced();
+        assert_eq!(app.edit_field_count(), EDIT_FIELD_COUNT);
+
+        app.opus_sampling_input = Input::new("temperature=warm".to_string());
+        app.handle_action(Action::SaveEdit);
+        assert!(matches!(app.mode, AppMode::EditProfile { .. }));
+        assert!(!app.config.profiles[0].env.contains_key(ENV_OPUS_SAMPLING));
+
+        app.opus_sampling_input = Input::new(" temperature=0.6 top_p=0.9 ".to_string());
+        // Other variables come from the env editor, which is checked too
+        app.env_editor.add_row();
+        *app.env_editor.selected_input_mut().unwrap() = Input::new("=1".to_string());
+        app.handle_action(Action::SaveEdit);
+        assert!(matches!(app.mode, AppMode::EditProfile { .. }));
+        *app.env_editor.selected_input_mut().unwrap() =
+            Input::new("HTTP_PROXY=http://proxy:3128".to_string());
+        app.handle_action(Action::SaveEdit);
+        assert_eq!(app.mode, AppMode::Normal);
+        assert_eq!(
+            app.config.profiles[0].env[ENV_OPUS_SAMPLING],
+            "temperature=0.6 top_p=0.9"
+        );
+        assert_eq!(
+            app.config.profiles[0].env["HTTP_PROXY"],
+            "http://proxy:3128"
+        );
+
+        app.handle_action(Action::EditProfile);
+        assert!(app.show_advanced);
+        app.mode = AppMode::EditProfile {
+            focused_field: EDIT_FIELD_OPUS_SAMPLING,
+            is_creating: false,
+        };
+        app.toggle_advanced();
+        assert_eq!(
+            app.mode,
+            AppMode::EditProfile {
+                focused_field: EDIT_FIELD_BASIC_COUNT - 1,
+                is_creating: false,
+            }
+        );
+    }
+
+    #[test]
+    fn tutorial_steps_forward_and_marks_seen() {
+        let mut app = App::new(Config::create_default());
+        app.start_tutorial_if_unseen();
+        assert_eq!(app.mode, AppMode::Tutorial { step: 0 });
+
+        app.handle_action(Action::PreviousTutorialStep);
+        assert_eq!(app.mode, AppMode::Tutorial { step: 0 });
+        app.handle_action(Action::NextTutorialStep);
+        assert_eq!(app.mode, AppMode::Tutorial { step: 1 });
+
+        app.mode = AppMode::Tutorial {
+            step: TUTORIAL_STEPS.len() - 1,
+        };
+        app.handle_action(Action::NextTutorialStep);
+        assert_eq!(app.mode, AppMode::Normal);
+        assert!(app.config.tutorial_seen);
+
+        app.start_tutorial_if_unseen();
+        assert_eq!(app.mode, AppMode::Normal);
+    }
+
+    #[test]
+    fn workspace_preselects_and_toggles_profile() {
+        let mut config = Config::create_default();
+        config
+            .workspaces
+            .insert("/work/repo".to_string(), "minimax".to_string());
+        let mut app = App::new(config);
+
+        app.enter_workspace(PathBuf::from("/work/repo/src"));
+        assert_eq!(
+            app.current_profile().map(|p| p.name.as_str()),
+            Some("minimax")
+        );
+        assert_eq!(app.workspace_profile(), Some(("/work/repo", "minimax")));
+
+        app.enter_workspace(PathBuf::from("/work/other"));
+        app.list_state.select(Some(1));
+        app.handle_action(Action::ToggleWorkspace);
+        assert_eq!(app.config.workspaces["/work/other"], "zai");
+        app.handle_action(Action::ToggleWorkspace);
+        assert!(!app.config.workspaces.contains_key("/work/other"));
+    }
+
+    #[test]
+    fn list_view_toggles_and_launch_is_recorded() {
+        let mut app = App::new(Config::create_default());
+        assert_eq!(app.config.list_view, ListView::Detailed);
+        app.handle_action(Action::ToggleListView);
+        assert_eq!(app.config.list_view, ListView::Compact);
+
+        app.list_state.select(Some(1));
+        app.handle_action(Action::SelectProfile);
+        assert!(app.config.last_used.contains_key("zai"));
+
+        app.handle_action(Action::DeleteProfile);
+        assert!(!app.config.last_used.contains_key("zai"));
+    }
+
+    #[test]
+    fn profiles_with_expired_keys_are_not_launched() {
+        let mut app = App::new(Config::create_default());
+        app.config.profiles[1].key_expires_on = Some(Date::today().add_days(3));
+        app.config.profiles[2].key_expires_on = Some(Date::today());
+        app.warn_expiring_keys();
+        let status = app.status_message.take().unwrap();
+        assert!(status.contains("'zai' (key expires in 3 days)"));
+        assert!(status.contains("'minimax' (key expired on "));
+
+        assert_eq!(
+            app.handle_control(&Command::Launch(Some("zai".to_string()))),
+            Ok("launching zai".to_string())
+        );
+        app.selected_profile = None;
+        let refused = app
+            .handle_control(&Command::Launch(Some("minimax".to_string())))
+            .unwrap_err();
+        assert!(refused.contains("rotate it and update key_expires_on"));
+        assert!(app.selected_profile.is_none());
+    }
+
+    #[test]
+    fn control_commands_select_and_launch_profiles() {
+        let mut app = App::new(Config::create_default());
+        assert_eq!(
+            app.handle_control(&Command::Status),
+            Ok("idle default".to_string())
+        );
+        assert!(
+            app.handle_control(&Command::Select("missing".to_string()))
+                .is_err()
+        );
+
+        assert!(
+            app.handle_control(&Command::Select("zai".to_string()))
+                .is_ok()
+        );
+        assert_eq!(app.current_profile().map(|p| p.name.as_str()), Some("zai"));
+
+        app.mode = AppMode::Help;
+        assert!(app.handle_control(&Command::Launch(None)).is_err());
+        app.mode = AppMode::Normal;
+        assert_eq!(
+            app.handle_control(&Command::Launch(Some("minimax".to_string()))),
+            Ok("launching minimax".to_string())
+        );
+        assert_eq!(
+            app.selected_profile.as_ref().map(|p| p.name.as_str()),
+            Some("minimax")
+        );
+    }
+
+    #[test]
+    fn scroll_notes_stays_within_notes() {
+        let mut app = App::new(Config::create_default());
+        app.list_state.select(Some(0));
+        app.config.profiles[0].notes = "one\ntwo\nthree".to_string();
+
+        app.handle_action(Action::ShowNotes);
+        app.scroll_notes(-1);
+        assert_eq!(app.mode, AppMode::Notes { scroll: 0 });
+        app.scroll_notes(10);
+        assert_eq!(app.mode, AppMode::Notes { scroll: 2 });
+        app.handle_action(Action::HideNotes);
+        assert_eq!(app.mode, AppMode::Normal);
+    }
+
+    #[test]
+    fn log_viewer_filters_by_profile_and_scrolls_back() {
+        let mut app = App::new(Config::create_default());
+        for (profile, status) in [("zai", 200), ("local", 200), ("zai", 429)] {
+            app.request_log.push(proxy::RequestLogEntry {
+                timestamp: 0,
+                profile: profile.to_string(),
+                model: "claude-sonnet-4-5".to_string(),
+                upstream_model: "glm-4.6".to_string(),
+                api: "chat".to_string(),
+                status,
+                latency_ms: 10,
+                stream: true,
+                input_tokens: None,
+                output_tokens: None,
+            });
+        }
+        app.mode = AppMode::Logs { back: 0 };
+        assert_eq!(app.visible_log_entries().len(), 3);
+
+        app.handle_action(Action::CycleLogFilter);
+        assert_eq!(app.log_filter.as_deref(), Some("local"));
+        app.handle_action(Action::CycleLogFilter);
+        let statuses: Vec<u16> = app.visible_log_entries().iter().map(|e| e.status).collect();
+        assert_eq!(statuses, vec![200, 429]);
+        app.handle_action(Action::CycleLogFilter);
+        assert_eq!(app.log_filter, None);
+
+        app.scroll_logs(Some(-10));
+        assert_eq!(app.mode, AppMode::Logs { back: 2 });
+        app.scroll_logs(Some(1));
+        assert_eq!(app.mode, AppMode::Logs { back: 1 });
+        app.scroll_logs(None);
+        assert_eq!(app.mode, AppMode::Logs { back: 0 });
+    }
+
+    #[test]
+    fn tag_filter_narrows_the_list_and_keeps_a_visible_selection() {
+        let mut app = App::new(Config::create_default());
+        let count = app.config.profiles.len();
+        for (i, tags) in [
+            (1, vec!["Work", "cloud"]),
+            (3, vec!["local"]),
+            (4, vec!["work"]),
+        ] {
+            app.config.profiles[i].tags = tags.into_iter().map(String::from).collect();
+        }
+        app.list_state.select(Some(3));
+        assert!(app.config.profiles[1].matches_tags("#wo CL"));
+        assert!(!app.config.profiles[4].matches_tags("work cloud"));
+
+        app.handle_action(Action::StartTagFilter);
+        for c in "wor".chars() {
+            app.push_tag_filter_char(c);
+        }
+        assert_eq!(app.visible_profiles(), vec![1, 4]);
+        assert_eq!(app.list_state.selected(), Some(1));
+        app.handle_action(Action::ApplyTagFilter);
+        app.handle_action(Action::MoveDown);
+        assert_eq!(app.list_state.selected(), Some(4));
+        app.handle_action(Action::MoveDown);
+        assert_eq!(app.list_state.selected(), Some(1));
+
+        app.push_tag_filter_char('x');
+        assert_eq!(app.list_state.selected(), None);
+        app.handle_action(Action::ClearTagFilter);
+        assert_eq!(app.visible_profiles().len(), count);
+        assert_eq!(app.list_state.selected(), Some(0));
+        assert_eq!(app.mode, AppMode::Normal);
+    }
 }
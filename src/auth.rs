@@ -0,0 +1,404 @@
+//! Per-profile credential providers.
+//!
+//! A profile selects how credentials are obtained with `AUTH_PROVIDER`. The launcher
+//! resolves the provider once before starting Claude Code and the proxy, so every
+//! upstream gets its credentials the same way regardless of where they come from.
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{
+    ENV_AUTH_PROVIDER, ENV_AUTH_TOKEN, ENV_AWS_ACCESS_KEY_ID, ENV_AWS_REGION,
+    ENV_AWS_SECRET_ACCESS_KEY, ENV_AWS_SESSION_TOKEN, ENV_GCP_ACCESS_TOKEN, ENV_OPENAI_OAUTH,
+};
+use crate::{anthropic_oauth, openai_oauth};
+
+/// Default AWS region when neither the profile nor the environment sets one
+const DEFAULT_AWS_REGION: &str = "us-east-1";
+
+/// How a profile obtains credentials for its upstream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AuthProvider {
+    /// `ANTHROPIC_AUTH_TOKEN` from the profile, sent as a bearer token
+    #[default]
+    StaticKey,
+    /// ChatGPT OAuth (Codex backend)
+    OpenAiOAuth,
+    /// Claude.ai subscription OAuth
+    AnthropicOAuth,
+    /// AWS Signature Version 4 (Bedrock)
+    AwsSigV4,
+    /// Google Cloud access token (Vertex AI)
+    GcpToken,
+}
+
+impl AuthProvider {
+    /// Parse a provider name as written in `AUTH_PROVIDER`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "static" | "key" | "api-key" => Some(Self::StaticKey),
+            "openai-oauth" | "codex" => Some(Self::OpenAiOAuth),
+            "anthropic-oauth" | "claude-oauth" => Some(Self::AnthropicOAuth),
+            "aws" | "aws-sigv4" | "bedrock" => Some(Self::AwsSigV4),
+            "gcp" | "vertex" => Some(Self::GcpToken),
+            _ => None,
+        }
+    }
+
+    /// Select the provider for a profile's environment.
+    ///
+    /// `AUTH_PROVIDER` wins; otherwise the legacy `OPENAI_OAUTH` flag is honored and
+    /// everything else falls back to a static key.
+    pub fn for_env(env: &HashMap<String, String>) -> Result<Self> {
+        if let Some(value) = env.get(ENV_AUTH_PROVIDER).filter(|v| !v.trim().is_empty()) {
+            return Self::parse(value)
+                .with_context(|| format!("Unknown {}: {}", ENV_AUTH_PROVIDER, value));
+        }
+        if openai_oauth::openai_oauth_enabled(env.get(ENV_OPENAI_OAUTH)) {
+            return Ok(Self::OpenAiOAuth);
+        }
+        Ok(Self::StaticKey)
+    }
+
+    /// Whether obtaining credentials may need an interactive (browser) sign-in
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, Self::OpenAiOAuth | Self::AnthropicOAuth)
+    }
+
+    /// Credentials for the static-key provider (no I/O needed)
+    pub fn resolve_static(&self, env: &HashMap<String, String>) -> Credentials {
+        env.get(ENV_AUTH_TOKEN)
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| Credentials::Bearer(v.clone()))
+            .unwrap_or(Credentials::None)
+    }
+
+    /// Obtain credentials, running interactive sign-in flows when needed
+    pub async fn resolve(&self, env: &HashMap<String, String>) -> Result<Credentials> {
+        match self {
+            Self::StaticKey => Ok(self.resolve_static(env)),
+            Self::OpenAiOAuth => Ok(Credentials::Bearer(
+                openai_oauth::ensure_access_token_interactive().await?,
+            )),
+            Self::AnthropicOAuth => Ok(Credentials::Bearer(
+                anthropic_oauth::ensure_access_token_interactive().await?,
+            )),
+            Self::AwsSigV4 => Ok(Credentials::AwsSigV4(AwsCredentials::from_env(env)?)),
+            Self::GcpToken => Ok(Credentials::Bearer(gcp_access_token(env)?)),
+        }
+    }
+}
+
+/// Resolved credentials for an upstream
+#[derive(Debug, Clone, Default)]
+pub enum Credentials {
+    /// No credentials; requests are forwarded with whatever Claude Code sends
+    #[default]
+    None,
+    /// Bearer token for the `Authorization` header
+    Bearer(String),
+    /// AWS keys used to sign each request
+    AwsSigV4(AwsCredentials),
+}
+
+impl Credentials {
+    /// Token to hand to Claude Code as `ANTHROPIC_AUTH_TOKEN`, if any
+    pub fn bearer_token(&self) -> Option<&str> {
+        match self {
+            Self::Bearer(token) => Some(token),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    pub region: String,
+}
+
+/// Look up a value in the profile first, then the process environment
+fn profile_or_process_env(env: &HashMap<String, String>, key: &str) -> Option<String> {
+    env.get(key)
+        .cloned()
+        .or_else(|| std::env::var(key).ok())
+        .filter(|v| !v.trim().is_empty())
+}
+
+impl AwsCredentials {
+    pub fn from_env(env: &HashMap<String, String>) -> Result<Self> {
+        Ok(Self {
+            access_key_id: profile_or_process_env(env, ENV_AWS_ACCESS_KEY_ID)
+                .with_context(|| format!("{} is not set", ENV_AWS_ACCESS_KEY_ID))?,
+            secret_access_key: profile_or_process_env(env, ENV_AWS_SECRET_ACCESS_KEY)
+                .with_context(|| format!("{} is not set", ENV_AWS_SECRET_ACCESS_KEY))?,
+            session_token: profile_or_process_env(env, ENV_AWS_SESSION_TOKEN),
+            region: profile_or_process_env(env, ENV_AWS_REGION)
+                .unwrap_or_else(|| DEFAULT_AWS_REGION.to_string()),
+        })
+    }
+}
+
+/// Use `GCP_ACCESS_TOKEN` if set, otherwise ask the gcloud CLI for one
+fn gcp_access_token(env: &HashMap<String, String>) -> Result<String> {
+    if let Some(token) = profile_or_process_env(env, ENV_GCP_ACCESS_TOKEN) {
+        return Ok(token);
+    }
+    let output = std::process::Command::new("gcloud")
+        .args(["auth", "print-access-token"])
+        .output()
+        .context("Failed to run `gcloud auth print-access-token`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "gcloud auth print-access-token failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if token.is_empty() {
+        anyhow::bail!("gcloud returned an empty access token");
+    }
+    Ok(token)
+}
+
+// ============================================================================
+// AWS Signature Version 4
+// ============================================================================
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// URI-encode per SigV4 rules (unreserved characters pass through)
+fn sigv4_encode(value: &str, encode_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// (year, month, day) of a count of days since 1970-01-01, using Howard Hinnant's
+/// civil-from-days algorithm
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Count of days since 1970-01-01 of a (year, month, day), the inverse of
+/// [`civil_from_days`]
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Format a unix timestamp as (`YYYYMMDDTHHMMSSZ`, `YYYYMMDD`)
+fn amz_timestamps(unix_secs: u64) -> (String, String) {
+    let secs_of_day = unix_secs % 86_400;
+    let (year, month, day) = civil_from_days((unix_secs / 86_400) as i64);
+
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let datetime = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date,
+        secs_of_day / 3_600,
+        (secs_of_day % 3_600) / 60,
+        secs_of_day % 60
+    );
+    (datetime, date)
+}
+
+impl AwsCredentials {
+    /// Sign a request and return the headers that must be added to it
+    pub fn sign(
+        &self,
+        service: &str,
+        method: &str,
+        url: &url::Url,
+        body: &[u8],
+    ) -> Vec<(String, String)> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.sign_at(service, method, url, body, now)
+    }
+
+    fn sign_at(
+        &self,
+        service: &str,
+        method: &str,
+        url: &url::Url,
+        body: &[u8],
+        unix_secs: u64,
+    ) -> Vec<(String, String)> {
+        let (amz_date, date) = amz_timestamps(unix_secs);
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let canonical_uri = sigv4_encode(url.path(), false);
+        let mut query: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| (sigv4_encode(&k, true), sigv4_encode(&v, true)))
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut headers = vec![
+            ("host".to_string(), host),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            canonical_uri,
+            canonical_query,
+            canonical_headers,
+            signed_headers,
+            sha256_hex(body)
+        );
+
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+
+        let k_date = hmac_sha256(
+            format!("AWS4{}", self.secret_access_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let k_region = hmac_sha256(&k_date, self.region.as_bytes());
+        let k_service = hmac_sha256(&k_region, service.as_bytes());
+        let k_signing = hmac_sha256(&k_service, b"aws4_request");
+        let signature = hex(&hmac_sha256(&k_signing, string_to_sign.as_bytes()));
+
+        let mut out = vec![
+            (
+                "authorization".to_string(),
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key_id, scope, signed_headers, signature
+                ),
+            ),
+            ("x-amz-date".to_string(), amz_date),
+        ];
+        if let Some(token) = &self.session_token {
+            out.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_env_prefers_explicit_provider_then_legacy_flag() {
+        let env = HashMap::from([(ENV_OPENAI_OAUTH.to_string(), "1".to_string())]);
+        assert_eq!(
+            AuthProvider::for_env(&env).unwrap(),
+            AuthProvider::OpenAiOAuth
+        );
+
+        let env = HashMap::from([
+            (ENV_OPENAI_OAUTH.to_string(), "1".to_string()),
+            (ENV_AUTH_PROVIDER.to_string(), "bedrock".to_string()),
+        ]);
+        assert_eq!(AuthProvider::for_env(&env).unwrap(), AuthProvider::AwsSigV4);
+
+        assert_eq!(
+            AuthProvider::for_env(&HashMap::new()).unwrap(),
+            AuthProvider::StaticKey
+        );
+
+        let env = HashMap::from([(ENV_AUTH_PROVIDER.to_string(), "bogus".to_string())]);
+        assert!(AuthProvider::for_env(&env).is_err());
+    }
+
+    #[test]
+    fn amz_timestamps_formats_utc() {
+        // 2015-08-30T12:36:00Z
+        let (datetime, date) = amz_timestamps(1_440_938_160);
+        assert_eq!(datetime, "20150830T123600Z");
+        assert_eq!(date, "20150830");
+    }
+
+    #[test]
+    fn sigv4_matches_aws_get_vanilla_vector() {
+        let creds = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+            region: "us-east-1".to_string(),
+        };
+        let url = url::Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = creds.sign_at("service", "GET", &url, b"", 1_440_938_160);
+
+        let auth = &headers[0].1;
+        assert_eq!(
+            auth,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+}
@@ -0,0 +1,532 @@
+//! Encrypted backup and restore of the config directory for moving machines.
+//!
+//! `claude-profiler backup <file>` bundles `profiles.toml`, its include fragments, the
+//! OAuth token cache, the usage history and `cache/` into one file encrypted with
+//! ChaCha20-Poly1305 under a passphrase-derived key (PBKDF2-HMAC-SHA256). With
+//! `--include-keychain` the keychain secrets the profiles refer to go into the
+//! encrypted payload as well. `claude-profiler restore <file>` unpacks it into the
+//! config directory and keychain of the new machine.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::{RngCore, rngs::OsRng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path};
+
+use crate::analytics::ANALYTICS_FILE;
+use crate::anthropic_oauth::ANTHROPIC_OAUTH_KEYCHAIN_ENTRY;
+use crate::config::{Config, KEYCHAIN_PREFIX, Profile, replace_file};
+use crate::platform::write_private;
+use crate::pricing::USAGE_HISTORY_FILE;
+use crate::secrets::{Keychain, SecretStore};
+use crate::usage::USAGE_FILE;
+
+/// File signature and format version
+const MAGIC: &[u8; 8] = b"CPBAK001";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 4 + SALT_LEN + NONCE_LEN;
+
+/// PBKDF2 rounds for new backups; stored in the header so restores keep working
+/// if this is raised later
+const KDF_ROUNDS: u32 = 600_000;
+
+/// Round counts a backup header may ask for: fewer would make the passphrase cheap
+/// to guess, more would hang the restore
+const MIN_KDF_ROUNDS: u32 = 100_000;
+const MAX_KDF_ROUNDS: u32 = 10_000_000;
+
+/// Passphrase source for non-interactive use
+const ENV_BACKUP_PASSPHRASE: &str = "CLAUDE_PROFILER_BACKUP_PASSPHRASE";
+
+/// Top-level files carried in a backup, relative to the config directory
+const BACKUP_FILES: [&str; 6] = [
+    "profiles.toml",
+    "openai-oauth.json",
+    "anthropic-oauth.json",
+    USAGE_HISTORY_FILE,
+    USAGE_FILE,
+    ANALYTICS_FILE,
+];
+
+/// Directory whose files are carried in a backup
+const BACKUP_CACHE_DIR: &str = "cache";
+
+/// Where include fragments from outside the config directory are restored
+const BACKUP_INCLUDES_DIR: &str = "includes";
+
+/// Flag adding the keychain secrets to a backup
+const INCLUDE_KEYCHAIN_FLAG: &str = "--include-keychain";
+
+/// Decrypted contents of a backup: relative path -> base64 file contents
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Bundle {
+    created: u64,
+    files: BTreeMap<String, String>,
+    /// `include` entries whose fragments were outside the config directory -> the
+    /// files under `includes/` that replace them on restore
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    relocated: BTreeMap<String, Vec<String>>,
+    /// Keychain entry -> secret, with `--include-keychain`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    keychain: BTreeMap<String, String>,
+}
+
+/// Keychain entries the profiles' `keychain:` values refer to
+fn referenced_entries(config: &Config) -> BTreeSet<&str> {
+    config
+        .profiles
+        .iter()
+        .flat_map(Profile::keychain_entries)
+        .collect()
+}
+
+impl Bundle {
+    /// The files worth keeping from `dir` and the include fragments of `config`,
+    /// plus the keychain secrets it uses when `keychain` is given
+    fn collect(dir: &Path, config: &Config, keychain: Option<&dyn SecretStore>) -> Result<Self> {
+        let mut bundle = Self {
+            created: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            ..Default::default()
+        };
+        for name in BACKUP_FILES {
+            if let Ok(bytes) = fs::read(dir.join(name)) {
+                bundle.add(name.to_string(), bytes);
+            }
+        }
+        if let Ok(entries) = fs::read_dir(dir.join(BACKUP_CACHE_DIR)) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if !path.is_file() {
+                    continue;
+                }
+                let bytes = fs::read(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let name = format!(
+                    "{}/{}",
+                    BACKUP_CACHE_DIR,
+                    entry.file_name().to_string_lossy()
+                );
+                bundle.add(name, bytes);
+            }
+        }
+        bundle.collect_includes(dir, config)?;
+
+        if let Some(store) = keychain {
+            for entry in referenced_entries(config) {
+                let secret = store
+                    .get(entry)
+                    .with_context(|| format!("A profile refers to {}{}", KEYCHAIN_PREFIX, entry))?;
+                bundle.keychain.insert(entry.to_string(), secret);
+            }
+            // Not signed in is not an error
+            if let Ok(tokens) = store.get(ANTHROPIC_OAUTH_KEYCHAIN_ENTRY) {
+                bundle
+                    .keychain
+                    .insert(ANTHROPIC_OAUTH_KEYCHAIN_ENTRY.to_string(), tokens);
+            }
+        }
+        Ok(bundle)
+    }
+
+    fn add(&mut self, name: String, bytes: Vec<u8>) {
+        let engine = base64::engine::general_purpose::STANDARD;
+        self.files.insert(name, engine.encode(bytes));
+    }
+
+    /// Fragments inside the config directory keep their place. Those of an entry
+    /// reaching outside it are carried under `includes/`, and the entry is pointed
+    /// at them on restore.
+    fn collect_includes(&mut self, dir: &Path, config: &Config) -> Result<()> {
+        for (pattern, paths) in config.include_files(dir)? {
+            let inside: Option<Vec<String>> = paths
+                .iter()
+                .map(|path| {
+                    let relative = path.strip_prefix(dir).ok()?.to_string_lossy();
+                    is_safe_relative(&relative).then(|| relative.replace('\\', "/"))
+                })
+                .collect();
+            let mut relocated = Vec::new();
+            for (index, path) in paths.iter().enumerate() {
+                let bytes = fs::read(path)
+                    .with_context(|| format!("Failed to read included file: {}", path.display()))?;
+                let name = match &inside {
+                    Some(names) => names[index].clone(),
+                    None => {
+                        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                        let mut name = format!("{}/{}", BACKUP_INCLUDES_DIR, file_name);
+                        let mut copy = 1;
+                        while self.files.contains_key(&name) {
+                            copy += 1;
+                            name = format!("{}/{}-{}", BACKUP_INCLUDES_DIR, copy, file_name);
+                        }
+                        relocated.push(name.clone());
+                        name
+                    }
+                };
+                self.add(name, bytes);
+            }
+            if inside.is_none() {
+                self.relocated.insert(pattern, relocated);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], rounds: u32) -> Key {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, rounds, &mut key);
+    Key::from(key)
+}
+
+fn encrypt(plaintext: &[u8], passphrase: &str, rounds: u32) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt, rounds));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&rounds.to_be_bytes());
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if data.len() < HEADER_LEN || &data[..MAGIC.len()] != MAGIC {
+        anyhow::bail!("Not a claude-profiler backup");
+    }
+    let (rounds, rest) = data[MAGIC.len()..].split_at(4);
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let rounds = u32::from_be_bytes(rounds.try_into()?);
+    if !(MIN_KDF_ROUNDS..=MAX_KDF_ROUNDS).contains(&rounds) {
+        anyhow::bail!(
+            "Backup asks for {} key derivation rounds, outside {}..={}; refusing to open it",
+            rounds,
+            MIN_KDF_ROUNDS,
+            MAX_KDF_ROUNDS
+        );
+    }
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt, rounds));
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Wrong passphrase or corrupted backup"))
+}
+
+/// Only plain relative paths may be restored, so a crafted backup cannot write
+/// outside the config directory
+fn is_safe_relative(path: &str) -> bool {
+    let path = Path::new(path);
+    path.components().count() > 0
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Restore `bundle` into `dir` and `keychain`. Every entry is checked and decoded
+/// before anything is written, so a bad backup leaves the old setup untouched, and
+/// each file is replaced whole.
+fn unpack(bundle: &Bundle, dir: &Path, keychain: &dyn SecretStore) -> Result<usize> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let mut files = Vec::with_capacity(bundle.files.len());
+    for (name, encoded) in &bundle.files {
+        if !is_safe_relative(name) {
+            anyhow::bail!("Refusing to restore unsafe path: {}", name);
+        }
+        let mut bytes = engine
+            .decode(encoded)
+            .with_context(|| format!("Corrupted entry: {}", name))?;
+        if name == "profiles.toml" && !bundle.relocated.is_empty() {
+            bytes = relocate_includes(&bytes, &bundle.relocated)?;
+        }
+        files.push((dir.join(name), bytes));
+    }
+    // Only names a `keychain:` value can refer to
+    if let Some(entry) = bundle
+        .keychain
+        .keys()
+        .find(|entry| entry.is_empty() || entry.trim() != entry.as_str())
+    {
+        anyhow::bail!("Refusing to restore keychain entry '{}'", entry);
+    }
+
+    for (path, bytes) in &files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        replace_file(path, bytes)?;
+    }
+    for (entry, secret) in &bundle.keychain {
+        keychain.set(entry, secret)?;
+    }
+    Ok(files.len())
+}
+
+/// `profiles.toml` with the `include` entries that reached outside the config
+/// directory pointed at the fragments restored under `includes/`
+fn relocate_includes(
+    contents: &[u8],
+    relocated: &BTreeMap<String, Vec<String>>,
+) -> Result<Vec<u8>> {
+    let mut document: toml_edit::DocumentMut = std::str::from_utf8(contents)
+        .context("Failed to read profiles.toml from the backup")?
+        .parse()
+        .context("Failed to parse profiles.toml from the backup")?;
+    let Some(include) = document
+        .get_mut("include")
+        .and_then(|item| item.as_array_mut())
+    else {
+        return Ok(contents.to_vec());
+    };
+    let mut entries = toml_edit::Array::new();
+    for value in include.iter() {
+        match value.as_str().and_then(|pattern| relocated.get(pattern)) {
+            Some(names) => names.iter().for_each(|name| entries.push(name.as_str())),
+            None => entries.push(value.clone()),
+        }
+    }
+    *include = entries;
+    Ok(document.to_string().into_bytes())
+}
+
+fn read_passphrase(confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(ENV_BACKUP_PASSPHRASE)
+        && !passphrase.is_empty()
+    {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("Backup passphrase: ")?;
+    if passphrase.is_empty() {
+        anyhow::bail!("Passphrase must not be empty");
+    }
+    if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+        anyhow::bail!("Passphrases do not match");
+    }
+    Ok(passphrase)
+}
+
+/// `claude-profiler backup <file> [--include-keychain]`
+pub fn run_backup(args: &[String]) -> Result<()> {
+    let include_keychain = args.iter().any(|arg| arg == INCLUDE_KEYCHAIN_FLAG);
+    let [output] = args
+        .iter()
+        .filter(|arg| *arg != INCLUDE_KEYCHAIN_FLAG)
+        .collect::<Vec<_>>()[..]
+    else {
+        anyhow::bail!(
+            "Usage: claude-profiler backup <file> [{}]",
+            INCLUDE_KEYCHAIN_FLAG
+        );
+    };
+    let dir = Config::config_dir().context("Could not determine config directory")?;
+    let config = match Config::config_file_path() {
+        Some(path) if path.exists() => Config::load()?,
+        _ => Config::default(),
+    };
+    let keychain = include_keychain.then_some(&Keychain as &dyn SecretStore);
+    let bundle = Bundle::collect(&dir, &config, keychain)?;
+    if bundle.files.is_empty() {
+        anyhow::bail!("Nothing to back up in {}", dir.display());
+    }
+
+    let passphrase = read_passphrase(true)?;
+    let encrypted = encrypt(&serde_json::to_vec(&bundle)?, &passphrase, KDF_ROUNDS)?;
+    write_private(Path::new(output), &encrypted)?;
+
+    println!("Backed up {} files to {}", bundle.files.len(), output);
+    for name in bundle.files.keys() {
+        println!("  {}", name);
+    }
+    for entry in bundle.keychain.keys() {
+        println!("  {}{}", KEYCHAIN_PREFIX, entry);
+    }
+    let left_out = referenced_entries(&config);
+    if !include_keychain && !left_out.is_empty() {
+        eprintln!(
+            "Not backed up: keychain entries {}. Pass {} to add them, and the \
+             Anthropic sign-in, to the encrypted backup.",
+            left_out.into_iter().collect::<Vec<_>>().join(", "),
+            INCLUDE_KEYCHAIN_FLAG
+        );
+    }
+    Ok(())
+}
+
+/// `claude-profiler restore <file> [--force]`
+pub fn run_restore(args: &[String]) -> Result<()> {
+    let force = args.iter().any(|arg| arg == "--force");
+    let [input] = args
+        .iter()
+        .filter(|arg| *arg != "--force")
+        .collect::<Vec<_>>()[..]
+    else {
+        anyhow::bail!("Usage: claude-profiler restore <file> [--force]");
+    };
+
+    let dir = Config::config_dir().context("Could not determine config directory")?;
+    if !force && Config::config_file_path().is_some_and(|path| path.exists()) {
+        anyhow::bail!(
+            "{} already exists; pass --force to overwrite it",
+            dir.join("profiles.toml").display()
+        );
+    }
+
+    let data = fs::read(input).with_context(|| format!("Failed to read {}", input))?;
+    let passphrase = read_passphrase(false)?;
+    let bundle: Bundle =
+        serde_json::from_slice(&decrypt(&data, &passphrase)?).context("Invalid backup contents")?;
+    let restored = unpack(&bundle, &dir, &Keychain)?;
+
+    println!("Restored {} files to {}", restored, dir.display());
+    if !bundle.keychain.is_empty() {
+        println!("Restored {} keychain entries", bundle.keychain.len());
+    }
+    for (pattern, names) in &bundle.relocated {
+        println!(
+            "include \"{}\" was outside the config directory; now {}",
+            pattern,
+            names.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn encrypt_round_trips_and_rejects_wrong_passphrase() {
+        let encrypted = encrypt(b"profiles", "correct horse", MIN_KDF_ROUNDS).unwrap();
+
+        assert_eq!(&encrypted[..MAGIC.len()], MAGIC);
+        assert_eq!(decrypt(&encrypted, "correct horse").unwrap(), b"profiles");
+        assert!(decrypt(&encrypted, "wrong").is_err());
+        assert!(decrypt(b"not a backup", "correct horse").is_err());
+
+        // The round count in the header is checked before any key is derived
+        for rounds in [10, MAX_KDF_ROUNDS + 1] {
+            let mut tampered = encrypted.clone();
+            tampered[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&rounds.to_be_bytes());
+            let error = decrypt(&tampered, "correct horse").unwrap_err();
+            assert!(error.to_string().contains("key derivation rounds"));
+        }
+    }
+
+    #[test]
+    fn unpack_rejects_paths_outside_config_dir() {
+        assert!(is_safe_relative("profiles.toml"));
+        assert!(is_safe_relative("cache/models-dev.json"));
+        assert!(!is_safe_relative("../.ssh/authorized_keys"));
+        assert!(!is_safe_relative("/etc/passwd"));
+        assert!(!is_safe_relative(""));
+
+        // Nothing is written when any entry is refused, whichever comes first
+        let dir = std::env::temp_dir().join(format!("cp-unpack-{}", std::process::id()));
+        let engine = base64::engine::general_purpose::STANDARD;
+        let keychain = MemoryStore::default();
+        let bundle = Bundle {
+            created: 0,
+            files: BTreeMap::from([
+                ("../escape".to_string(), String::new()),
+                (
+                    "profiles.toml".to_string(),
+                    engine.encode("profiles = []\n"),
+                ),
+            ]),
+            keychain: BTreeMap::from([("zai".to_string(), "sk-zai".to_string())]),
+            ..Default::default()
+        };
+        assert!(unpack(&bundle, &dir, &keychain).is_err());
+        let bundle = Bundle {
+            created: 0,
+            files: BTreeMap::from([(
+                "profiles.toml".to_string(),
+                engine.encode("profiles = []\n"),
+            )]),
+            keychain: BTreeMap::from([(" zai".to_string(), "sk-zai".to_string())]),
+            ..Default::default()
+        };
+        assert!(unpack(&bundle, &dir, &keychain).is_err());
+        assert!(!dir.exists());
+        assert!(keychain.0.borrow().is_empty());
+    }
+
+    #[derive(Default)]
+    struct MemoryStore(RefCell<BTreeMap<String, String>>);
+
+    impl SecretStore for MemoryStore {
+        fn get(&self, entry: &str) -> Result<String> {
+            self.0.borrow().get(entry).cloned().context("No such entry")
+        }
+
+        fn set(&self, entry: &str, secret: &str) -> Result<()> {
+            self.0
+                .borrow_mut()
+                .insert(entry.to_string(), secret.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn fragments_and_opted_in_keychain_secrets_are_restored() {
+        let root =
+            std::env::temp_dir().join(format!("claude-profiler-backup-{}", std::process::id()));
+        let (dir, shared, restored) = (root.join("config"), root.join("shared"), root.join("new"));
+        fs::create_dir_all(dir.join("work")).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        let profiles = "include = [\"work/*.toml\", \"../shared/team.toml\"]\n\n\
+                        [[profiles]]\nname = \"zai\"\n\
+                        [profiles.env]\nANTHROPIC_AUTH_TOKEN = \"keychain:zai\"\n";
+        fs::write(dir.join("profiles.toml"), profiles).unwrap();
+        fs::write(dir.join("work/a.toml"), "profiles = []\n").unwrap();
+        fs::write(shared.join("team.toml"), "profiles = []\n").unwrap();
+
+        let mut config: Config = toml::from_str(profiles).unwrap();
+        config.merge_includes(&dir).unwrap();
+        let keychain = MemoryStore::default();
+        keychain.set("zai", "sk-zai").unwrap();
+        keychain.set(ANTHROPIC_OAUTH_KEYCHAIN_ENTRY, "{}").unwrap();
+
+        let plain = Bundle::collect(&dir, &config, None).unwrap();
+        assert!(plain.keychain.is_empty());
+        let bundle = Bundle::collect(&dir, &config, Some(&keychain)).unwrap();
+        let names: Vec<&str> = bundle.files.keys().map(String::as_str).collect();
+        assert_eq!(
+            names,
+            ["includes/team.toml", "profiles.toml", "work/a.toml"]
+        );
+        assert_eq!(bundle.keychain.len(), 2);
+
+        let new_keychain = MemoryStore::default();
+        unpack(&bundle, &restored, &new_keychain).unwrap();
+        assert_eq!(new_keychain.get("zai").unwrap(), "sk-zai");
+        let restored_config = fs::read_to_string(restored.join("profiles.toml")).unwrap();
+        assert!(restored_config.starts_with("include = [\"work/*.toml\", \"includes/team.toml\"]"));
+        let mut config: Config = toml::from_str(&restored_config).unwrap();
+        assert!(config.merge_includes(&restored).is_ok());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
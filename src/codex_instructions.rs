@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::fs;
 use std::path::PathBuf;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -12,6 +13,19 @@ const CACHE_TTL_SECS: u64 = 15 * 60;
 /// GitHub API for the latest release
 const GITHUB_API_RELEASES: &str = "https://api.github.com/repos/openai/codex/releases/latest";
 
+/// Models endpoint of the ChatGPT Codex backend
+const CODEX_MODELS_URL: &str = "https://chatgpt.com/backend-api/codex/models";
+
+/// Codex CLI version reported to the models endpoint, which filters by it
+const CODEX_CLIENT_VERSION: &str = "0.98.0";
+
+const CODEX_MODELS_CACHE: &str = "codex-models.json";
+
+const MODELS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Efforts the proxy understands as model suffixes (`gpt-5.2-codex-high`)
+const REASONING_EFFORTS: [&str; 5] = ["none", "low", "medium", "high", "xhigh"];
+
 /// Model family for prompt selection
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ModelFamily {
@@ -116,10 +130,10 @@ async fn get_latest_release_tag(client: &reqwest::Client) -> Result<String> {
         .await?;
 
     let final_url = response.url().to_string();
-    if let Some(tag) = final_url.split("/tag/").last() {
-        if !tag.contains('/') {
-            return Ok(tag.to_string());
-        }
+    if let Some(tag) = final_url.split("/tag/").last()
+        && !tag.contains('/')
+    {
+        return Ok(tag.to_string());
     }
 
     anyhow::bail!("Failed to determine latest release tag")
@@ -142,14 +156,12 @@ pub async fn get_codex_instructions(model: &str) -> Result<String> {
     ));
 
     // Check if the cache is still valid (within TTL)
-    if let Ok(meta_content) = fs::read_to_string(&meta_file) {
-        if let Ok(meta) = serde_json::from_str::<CacheMetadata>(&meta_content) {
-            if now_secs().saturating_sub(meta.last_checked) < CACHE_TTL_SECS {
-                if let Ok(instructions) = fs::read_to_string(&cache_file) {
-                    return Ok(instructions);
-                }
-            }
-        }
+    if let Ok(meta_content) = fs::read_to_string(&meta_file)
+        && let Ok(meta) = serde_json::from_str::<CacheMetadata>(&meta_content)
+        && now_secs().saturating_sub(meta.last_checked) < CACHE_TTL_SECS
+        && let Ok(instructions) = fs::read_to_string(&cache_file)
+    {
+        return Ok(instructions);
     }
 
     // Fetch fresh instructions
@@ -285,9 +297,112 @@ fn default_codex_models() -> Vec<String> {
     ]
 }
 
-/// Get available Codex models for UI
+/// Group label used by the model picker (e.g. "GPT-5.2 Codex", "GPT-5.1 General")
+pub fn model_group_label(model: &str) -> String {
+    let normalized = model.to_ascii_lowercase();
+    let version = normalized
+        .strip_prefix("gpt-")
+        .and_then(|rest| rest.split('-').next())
+        .filter(|v| !v.is_empty())
+        .map(|v| format!("GPT-{}", v))
+        .unwrap_or_else(|| "Other".to_string());
+    let kind = if normalized.contains("codex") {
+        "Codex"
+    } else {
+        "General"
+    };
+    format!("{} {}", version, kind)
+}
+
+/// Models the signed-in account can use, as last fetched from the Codex backend
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedModels {
+    fetched_at: u64,
+    models: Vec<String>,
+}
+
+/// Model names with one entry per supported reasoning effort, from a Codex
+/// `/models` body: `{"models": [{"slug": ..., "supported_reasoning_levels": [...]}]}`
+fn parse_codex_models(body: &Value) -> Vec<String> {
+    let mut models = Vec::new();
+    for model in body
+        .get("models")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let Some(slug) = model.get("slug").and_then(Value::as_str) else {
+            continue;
+        };
+        if model.get("visibility").and_then(Value::as_str) == Some("hide") {
+            continue;
+        }
+        models.push(slug.to_string());
+        let efforts = model
+            .get("supported_reasoning_levels")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|level| level.get("effort").or(Some(level)).and_then(Value::as_str))
+            .filter(|effort| REASONING_EFFORTS.contains(effort));
+        models.extend(efforts.map(|effort| format!("{}-{}", slug, effort)));
+    }
+    models
+}
+
+/// Ask the Codex backend which models the signed-in account can use, and cache the
+/// list for the model picker
+pub fn refresh_codex_models(access_token: &str) -> Result<Vec<String>> {
+    let mut request = reqwest::blocking::Client::builder()
+        .timeout(MODELS_TIMEOUT)
+        .build()?
+        .get(format!(
+            "{}?client_version={}",
+            CODEX_MODELS_URL, CODEX_CLIENT_VERSION
+        ))
+        .bearer_auth(access_token)
+        .header("originator", "codex_cli_rs");
+    if let Some(account_id) = crate::openai_oauth::decode_chatgpt_account_id(access_token) {
+        request = request.header("chatgpt-account-id", account_id);
+    }
+    let response = request
+        .send()
+        .context("Failed to reach the Codex backend")?;
+    if !response.status().is_success() {
+        let status = response.status().as_u16();
+        let body = response.text().unwrap_or_default();
+        anyhow::bail!(
+            "Codex backend: {}",
+            crate::provider_errors::describe(status, &body)
+        );
+    }
+    let models = parse_codex_models(&response.json()?);
+    if models.is_empty() {
+        anyhow::bail!("Codex backend listed no models");
+    }
+
+    let cached = CachedModels {
+        fetched_at: now_secs(),
+        models: models.clone(),
+    };
+    if let Some(dir) = cache_dir() {
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(CODEX_MODELS_CACHE),
+            serde_json::to_string_pretty(&cached)?,
+        )?;
+    }
+    Ok(models)
+}
+
+/// Get available Codex models for UI: the last refreshed list, or the defaults
 pub fn get_cached_codex_models() -> Vec<String> {
-    default_codex_models()
+    cache_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(CODEX_MODELS_CACHE)).ok())
+        .and_then(|content| serde_json::from_str::<CachedModels>(&content).ok())
+        .map(|cached| cached.models)
+        .filter(|models| !models.is_empty())
+        .unwrap_or_else(default_codex_models)
 }
 
 /// Claude Code bridge prompt - maps Codex tools to Claude Code tools
@@ -351,3 +466,37 @@ If ANY answer is NO -> STOP and correct before proceeding.
 ## What Remains from Codex
 
 Sandbox policies, approval mechanisms, final answer formatting, git commit protocols, and file reference formats all follow Codex instructions."#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn codex_models_are_listed_with_their_efforts() {
+        let body = json!({
+            "models": [
+                {
+                    "slug": "gpt-5.3-codex",
+                    "supported_reasoning_levels": [
+                        { "effort": "low", "description": "Fast" },
+                        { "effort": "xhigh", "description": "Thorough" },
+                        { "effort": "minimal", "description": "Not a suffix" }
+                    ]
+                },
+                { "slug": "internal-eval", "visibility": "hide" },
+                { "slug": "gpt-5.2" }
+            ]
+        });
+        assert_eq!(
+            parse_codex_models(&body),
+            vec![
+                "gpt-5.3-codex",
+                "gpt-5.3-codex-low",
+                "gpt-5.3-codex-xhigh",
+                "gpt-5.2"
+            ]
+        );
+        assert!(parse_codex_models(&json!({})).is_empty());
+    }
+}
@@ -1,10 +1,17 @@
 use anyhow::{Context, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
+use crate::redact::{BlobRedaction, SecretMask};
+use crate::schema::{self, Diagnostic};
+
+pub const ENV_API_KEY: &str = "ANTHROPIC_API_KEY";
 pub const ENV_AUTH_TOKEN: &str = "ANTHROPIC_AUTH_TOKEN";
+pub const ENV_OAUTH_TOKEN: &str = "CLAUDE_CODE_OAUTH_TOKEN";
 pub const ENV_BASE_URL: &str = "ANTHROPIC_BASE_URL";
 pub const ENV_DEFAULT_HAIKU_MODEL: &str = "ANTHROPIC_DEFAULT_HAIKU_MODEL";
 pub const ENV_DEFAULT_SONNET_MODEL: &str = "ANTHROPIC_DEFAULT_SONNET_MODEL";
@@ -15,9 +22,90 @@ pub const ENV_DISABLE_NONESSENTIAL_TRAFFIC: &str = "CLAUDE_CODE_DISABLE_NONESSEN
 pub const ENV_API_TIMEOUT_MS: &str = "API_TIMEOUT_MS";
 pub const ENV_PROXY_TARGET_URL: &str = "PROXY_TARGET_URL";
 pub const ENV_OPENAI_OAUTH: &str = "OPENAI_OAUTH";
+pub const ENV_AUTH_PROVIDER: &str = "AUTH_PROVIDER";
+pub const ENV_AWS_ACCESS_KEY_ID: &str = "AWS_ACCESS_KEY_ID";
+pub const ENV_AWS_SECRET_ACCESS_KEY: &str = "AWS_SECRET_ACCESS_KEY";
+pub const ENV_AWS_SESSION_TOKEN: &str = "AWS_SESSION_TOKEN";
+pub const ENV_AWS_REGION: &str = "AWS_REGION";
+pub const ENV_GCP_ACCESS_TOKEN: &str = "GCP_ACCESS_TOKEN";
+pub const ENV_CUSTOM_HEADERS: &str = "ANTHROPIC_CUSTOM_HEADERS";
+pub const ENV_MIN_OUTPUT_TOKENS: &str = "PROXY_MIN_OUTPUT_TOKENS";
+pub const ENV_MAX_OUTPUT_TOKENS: &str = "PROXY_MAX_OUTPUT_TOKENS";
+pub const ENV_DEFAULT_OUTPUT_TOKENS: &str = "PROXY_DEFAULT_OUTPUT_TOKENS";
+pub const ENV_IDLE_UNLOAD_SECS: &str = "PROXY_IDLE_UNLOAD_SECS";
+pub const ENV_READY_WAIT_SECS: &str = "PROXY_READY_WAIT_SECS";
+pub const ENV_SSE_BATCH_MS: &str = "PROXY_SSE_BATCH_MS";
+pub const ENV_SSE_DEBUG: &str = "PROXY_SSE_DEBUG";
+pub const ENV_RESPONSES_URL: &str = "PROXY_RESPONSES_URL";
+pub const ENV_CHAT_COMPLETIONS_URL: &str = "PROXY_CHAT_COMPLETIONS_URL";
+pub const ENV_COMPLETIONS_URL: &str = "PROXY_COMPLETIONS_URL";
+pub const ENV_MOCK_SCRIPT: &str = "PROXY_MOCK_SCRIPT";
+pub const ENV_MAX_CONCURRENCY: &str = "PROXY_MAX_CONCURRENCY";
+pub const ENV_HAIKU_SAMPLING: &str = "PROXY_HAIKU_SAMPLING";
+pub const ENV_SONNET_SAMPLING: &str = "PROXY_SONNET_SAMPLING";
+pub const ENV_OPUS_SAMPLING: &str = "PROXY_OPUS_SAMPLING";
+pub const ENV_PREVIOUS_RESPONSE_ID: &str = "PROXY_PREVIOUS_RESPONSE_ID";
+pub const ENV_STRICT_TOOLS: &str = "PROXY_STRICT_TOOLS";
+pub const ENV_CONTEXT_TOKENS: &str = "PROXY_CONTEXT_TOKENS";
+pub const ENV_COMPACT_THRESHOLD: &str = "PROXY_COMPACT_THRESHOLD";
+pub const ENV_TOOL_RESULT_MAX_KB: &str = "PROXY_TOOL_RESULT_MAX_KB";
+pub const ENV_REQUEST_LOG: &str = "PROXY_REQUEST_LOG";
+pub const ENV_REQUEST_CAPTURE: &str = "PROXY_REQUEST_CAPTURE";
+pub const ENV_CODEX_PACING: &str = "PROXY_CODEX_PACING";
+pub const ENV_CONSENSUS_MODEL: &str = "PROXY_CONSENSUS_MODEL";
+pub const ENV_PASSTHROUGH: &str = "PROXY_PASSTHROUGH";
+pub const ENV_THINKING_HINT_BUDGET: &str = "PROXY_THINKING_HINT_BUDGET";
+pub const ENV_RETRY_ATTEMPTS: &str = "PROXY_RETRY_ATTEMPTS";
+pub const ENV_RETRY_BASE_MS: &str = "PROXY_RETRY_BASE_MS";
+pub const ENV_RETRY_MAX_MS: &str = "PROXY_RETRY_MAX_MS";
+pub const ENV_REASONING_SUMMARY: &str = "PROXY_REASONING_SUMMARY";
+pub const ENV_LLAMA_MODELS_DIR: &str = "PROXY_LLAMA_MODELS_DIR";
+pub const ENV_LLAMA_CTX_SIZE: &str = "PROXY_LLAMA_CTX_SIZE";
+pub const ENV_LLAMA_GPU_LAYERS: &str = "PROXY_LLAMA_GPU_LAYERS";
+
+/// Variables consumed by claude-profiler itself and never passed to Claude Code
+pub const PROFILER_ONLY_ENV_KEYS: [&str; 36] = [
+    ENV_PROXY_TARGET_URL,
+    ENV_OPENAI_OAUTH,
+    ENV_AUTH_PROVIDER,
+    ENV_MIN_OUTPUT_TOKENS,
+    ENV_MAX_OUTPUT_TOKENS,
+    ENV_DEFAULT_OUTPUT_TOKENS,
+    ENV_IDLE_UNLOAD_SECS,
+    ENV_READY_WAIT_SECS,
+    ENV_SSE_BATCH_MS,
+    ENV_SSE_DEBUG,
+    ENV_RESPONSES_URL,
+    ENV_CHAT_COMPLETIONS_URL,
+    ENV_COMPLETIONS_URL,
+    ENV_MOCK_SCRIPT,
+    ENV_MAX_CONCURRENCY,
+    ENV_HAIKU_SAMPLING,
+    ENV_SONNET_SAMPLING,
+    ENV_OPUS_SAMPLING,
+    ENV_PREVIOUS_RESPONSE_ID,
+    ENV_STRICT_TOOLS,
+    ENV_CONTEXT_TOKENS,
+    ENV_COMPACT_THRESHOLD,
+    ENV_TOOL_RESULT_MAX_KB,
+    ENV_REQUEST_LOG,
+    ENV_REQUEST_CAPTURE,
+    ENV_CODEX_PACING,
+    ENV_CONSENSUS_MODEL,
+    ENV_PASSTHROUGH,
+    ENV_THINKING_HINT_BUDGET,
+    ENV_RETRY_ATTEMPTS,
+    ENV_RETRY_BASE_MS,
+    ENV_RETRY_MAX_MS,
+    ENV_REASONING_SUMMARY,
+    ENV_LLAMA_MODELS_DIR,
+    ENV_LLAMA_CTX_SIZE,
+    ENV_LLAMA_GPU_LAYERS,
+];
 
 /// A single profile configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Profile {
     /// Unique profile name (used as the identifier)
     pub name: String,
@@ -26,14 +114,607 @@ pub struct Profile {
     #[serde(default)]
     pub description: String,
 
+    /// Long-form notes (purpose, billing owner, caveats), shown in the notes popup
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+
+    /// Free-form labels (e.g. `work`, `local`) the profile list can be filtered by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Day the profile's API key stops working (`YYYY-MM-DD`); the TUI warns as it
+    /// approaches and refuses to launch the profile from then on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub key_expires_on: Option<Date>,
+
+    /// Port the built-in proxy listens on, so several proxied sessions can run
+    /// at once; defaults to 4000
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_port: Option<u16>,
+
+    /// Address the built-in proxy binds to; defaults to `127.0.0.1`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_bind: Option<String>,
+
+    /// Extra request headers (e.g. `anthropic-beta`) sent by Claude Code and
+    /// forwarded upstream by the proxy
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Requested model pattern (`*` and `?` allowed) -> upstream that serves it
+    /// instead of the proxy target
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub routes: BTreeMap<String, ModelRoute>,
+
+    /// More upstreams (local servers or loaded models) that parallel sub-agent
+    /// conversations are spread over, besides the proxy target
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fanout: Vec<ModelRoute>,
+
+    /// MCP servers by name, handed to Claude Code at launch alongside its own
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mcp: BTreeMap<String, McpServer>,
+
+    /// Requested model pattern (`*` and `?` allowed) -> model the proxy sends upstream
+    /// in its place; the longest matching pattern wins
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_map: BTreeMap<String, String>,
+
+    /// Which of Claude Code's request headers the proxy forwards upstream, and which
+    /// upstream response headers it hands back
+    #[serde(default, skip_serializing_if = "HeaderPolicy::is_default")]
+    pub header_policy: HeaderPolicy,
+
+    /// Which of the launcher's own environment variables Claude Code inherits
+    #[serde(default, skip_serializing_if = "EnvFilter::is_empty")]
+    pub inherit_env: EnvFilter,
+
     /// Environment variables to set when launching Claude Code
     #[serde(default)]
     pub env: HashMap<String, String>,
 }
 
+impl Profile {
+    /// Whether every word of `query` starts one of the profile's tags, ignoring
+    /// case; an empty query matches every profile
+    pub fn matches_tags(&self, query: &str) -> bool {
+        query.split_whitespace().all(|term| {
+            let term = term.trim_start_matches('#').to_lowercase();
+            self.tags
+                .iter()
+                .any(|tag| tag.to_lowercase().starts_with(&term))
+        })
+    }
+
+    /// Keychain entries the profile's `keychain:` values refer to: its environment,
+    /// route and fan-out keys and MCP server environments
+    pub fn keychain_entries(&self) -> BTreeSet<&str> {
+        let route_keys = self
+            .routes
+            .values()
+            .chain(&self.fanout)
+            .filter_map(|route| route.api_key.as_deref());
+        let mcp_env = self.mcp.values().flat_map(|server| server.env.values());
+        self.env
+            .values()
+            .chain(mcp_env)
+            .map(String::as_str)
+            .chain(route_keys)
+            .filter_map(keychain_reference)
+            .collect()
+    }
+
+    /// Upstream URL the built-in proxy should target, if this profile uses the proxy
+    pub fn proxy_target_url(&self) -> Option<&str> {
+        self.env
+            .get(ENV_PROXY_TARGET_URL)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Backend this profile ultimately reaches: the proxy target if set, otherwise
+    /// the base URL, otherwise Anthropic itself
+    pub fn provider(&self) -> Provider {
+        if self
+            .env
+            .get(ENV_OPENAI_OAUTH)
+            .is_some_and(|v| crate::openai_oauth::is_truthy(v))
+        {
+            return Provider::Codex;
+        }
+        let base_url = self
+            .env
+            .get(ENV_BASE_URL)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty());
+        match self.proxy_target_url().or(base_url) {
+            Some(url) => Provider::from_url(url),
+            None => Provider::Anthropic,
+        }
+    }
+
+    /// Model Claude Code uses for main turns: `ANTHROPIC_MODEL`, else the
+    /// Sonnet, Opus or Haiku mapping
+    pub fn primary_model(&self) -> Option<&str> {
+        [
+            ENV_MODEL,
+            ENV_DEFAULT_SONNET_MODEL,
+            ENV_DEFAULT_OPUS_MODEL,
+            ENV_DEFAULT_HAIKU_MODEL,
+        ]
+        .iter()
+        .filter_map(|key| self.env.get(*key).map(|v| v.trim()))
+        .find(|v| !v.is_empty())
+    }
+
+    /// `ANTHROPIC_CUSTOM_HEADERS` value (`Name: Value` per line) carrying this
+    /// profile's headers. Lines already in `existing` are kept unless the profile
+    /// sets the same header.
+    pub fn custom_headers_env(&self, existing: Option<&str>) -> Option<String> {
+        if self.headers.is_empty() {
+            return None;
+        }
+        let overridden = |line: &str| {
+            line.split_once(':').is_some_and(|(name, _)| {
+                self.headers
+                    .keys()
+                    .any(|key| key.eq_ignore_ascii_case(name.trim()))
+            })
+        };
+        let lines: Vec<String> = existing
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !overridden(line))
+            .map(str::to_string)
+            .chain(
+                self.headers
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value)),
+            )
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Every variable this profile or `other` sets, sorted by name, with both values
+    pub fn env_compare<'a>(&'a self, other: &'a Profile) -> Vec<EnvComparison<'a>> {
+        let keys: BTreeSet<&str> = self
+            .env
+            .keys()
+            .chain(other.env.keys())
+            .map(String::as_str)
+            .collect();
+        keys.into_iter()
+            .map(|key| EnvComparison {
+                key,
+                left: self.env.get(key).map(String::as_str),
+                right: other.env.get(key).map(String::as_str),
+            })
+            .collect()
+    }
+
+    /// How the profile's key stands on `today`: `None` unless it has an expiry date
+    /// within [`KEY_EXPIRY_WARNING_DAYS`] or behind it
+    pub fn key_expiry(&self, today: Date) -> Option<KeyExpiry> {
+        let date = self.key_expires_on?;
+        match today.days_until(date) {
+            days_left if days_left <= 0 => Some(KeyExpiry::Expired { date }),
+            days_left if days_left <= KEY_EXPIRY_WARNING_DAYS => {
+                Some(KeyExpiry::Soon { date, days_left })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Days before `key_expires_on` that the TUI starts warning about a key
+pub const KEY_EXPIRY_WARNING_DAYS: i64 = 14;
+
+/// A profile key close to or past its `key_expires_on`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyExpiry {
+    /// Still works for `days_left` more days
+    Soon { date: Date, days_left: i64 },
+    /// No longer works; launching the profile is refused
+    Expired { date: Date },
+}
+
+impl KeyExpiry {
+    /// A few words for the profile list and details
+    pub fn label(&self) -> String {
+        match self {
+            KeyExpiry::Soon { days_left: 1, .. } => "key expires tomorrow".to_string(),
+            KeyExpiry::Soon { days_left, .. } => format!("key expires in {} days", days_left),
+            KeyExpiry::Expired { date } => format!("key expired on {}", date),
+        }
+    }
+}
+
+/// A calendar day, written `YYYY-MM-DD`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    /// Days since 1970-01-01
+    days: i64,
+}
+
+impl Date {
+    /// Today in UTC
+    pub fn today() -> Self {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            days: (secs / 86_400) as i64,
+        }
+    }
+
+    /// The date `days` days later, or earlier when negative
+    pub fn add_days(self, days: i64) -> Self {
+        Self {
+            days: self.days + days,
+        }
+    }
+
+    /// Days from this date to `other`, negative when `other` is earlier
+    pub fn days_until(self, other: Date) -> i64 {
+        other.days - self.days
+    }
+}
+
+impl std::str::FromStr for Date {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow::anyhow!("invalid date `{}`, expected YYYY-MM-DD", s);
+        let mut parts = s.trim().splitn(3, '-');
+        let mut part = |len: usize| {
+            parts
+                .next()
+                .filter(|p| p.len() == len && p.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|p| p.parse::<i64>().ok())
+                .ok_or_else(invalid)
+        };
+        let (year, month, day) = (part(4)?, part(2)?, part(2)?);
+        let days = crate::auth::days_from_civil(year, month, day);
+        // Months past 12 or days past the month's end come back as a different date
+        if crate::auth::civil_from_days(days) != (year, month, day) {
+            return Err(invalid());
+        }
+        Ok(Self { days })
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (year, month, day) = crate::auth::civil_from_days(self.days);
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// One environment variable of two profiles, side by side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvComparison<'a> {
+    pub key: &'a str,
+    pub left: Option<&'a str>,
+    pub right: Option<&'a str>,
+}
+
+impl EnvComparison<'_> {
+    /// Set on one side only, or to different values
+    pub fn differs(&self) -> bool {
+        self.left != self.right
+    }
+}
+
+/// Inherited variables kept under an allowlist so Claude Code can still run
+const ESSENTIAL_ENV_KEYS: [&str; 11] = [
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "TERM",
+    "COLORTERM",
+    "LANG",
+    "LC_*",
+    "TMPDIR",
+    "SYSTEMROOT",
+];
+
+/// Allowlist and denylist for variables inherited from the launcher's environment.
+/// Patterns may use `*` and `?`; variables set by the profile itself always apply.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct EnvFilter {
+    /// When non-empty, only matching variables (and PATH, HOME, TERM, locale etc.)
+    /// are inherited
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    /// Matching variables are never inherited; wins over `allow`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl EnvFilter {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether an inherited variable reaches Claude Code
+    pub fn allows(&self, name: &str) -> bool {
+        if matches_any(&self.deny, name) {
+            return false;
+        }
+        self.allow.is_empty()
+            || matches_any(&self.allow, name)
+            || matches_any(&ESSENTIAL_ENV_KEYS, name)
+    }
+}
+
+/// Request headers forwarded upstream unless a profile says otherwise
+pub const DEFAULT_FORWARDED_HEADERS: [&str; 2] = ["anthropic-version", "anthropic-beta"];
+
+/// Response headers handed back to Claude Code unless a profile says otherwise: rate
+/// limits and request ids, which Claude Code shows and retries on
+pub const DEFAULT_RELAYED_HEADERS: [&str; 5] = [
+    "retry-after",
+    "request-id",
+    "x-request-id",
+    "x-ratelimit-*",
+    "anthropic-ratelimit-*",
+];
+
+/// Headers no policy can pass on: credentials, which the proxy sets itself, and
+/// headers describing a connection rather than a message
+const BLOCKED_HEADERS: [&str; 13] = [
+    "authorization",
+    "x-api-key",
+    "cookie",
+    "set-cookie",
+    "host",
+    "connection",
+    "keep-alive",
+    "te",
+    "upgrade",
+    "transfer-encoding",
+    "content-length",
+    "content-encoding",
+    "proxy-*",
+];
+
+/// Header passthrough between Claude Code and the upstream. Patterns may use `*` and
+/// `?` and match header names case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeaderPolicy {
+    /// Claude Code request headers sent on to the upstream (e.g. `x-stainless-*`)
+    pub forward: Vec<String>,
+    /// Upstream response headers returned to Claude Code
+    pub relay: Vec<String>,
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> Self {
+        Self {
+            forward: DEFAULT_FORWARDED_HEADERS.map(String::from).to_vec(),
+            relay: DEFAULT_RELAYED_HEADERS.map(String::from).to_vec(),
+        }
+    }
+}
+
+impl HeaderPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether a Claude Code request header goes upstream
+    pub fn forwards(&self, name: &str) -> bool {
+        Self::passes(&self.forward, name)
+    }
+
+    /// Whether an upstream response header comes back to Claude Code
+    pub fn relays(&self, name: &str) -> bool {
+        Self::passes(&self.relay, name)
+    }
+
+    fn passes(patterns: &[String], name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        !matches_any(&BLOCKED_HEADERS, &name)
+            && patterns
+                .iter()
+                .any(|pattern| wildcard_match(&pattern.to_ascii_lowercase(), &name))
+    }
+}
+
+/// Upstream for requests whose model matches a `[profiles.routes]` pattern, or one
+/// of the `[[profiles.fanout]]` upstreams
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ModelRoute {
+    /// Upstream URL, as for `PROXY_TARGET_URL`
+    pub url: String,
+    /// Model name sent upstream; the requested model when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Key for the upstream; `${VAR}` and `keychain:` references are resolved at launch.
+    /// Claude Code's own key is never sent to a route.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+/// An MCP server under `[profiles.mcp]`: a command for a stdio server, or the URL of
+/// an HTTP or SSE one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct McpServer {
+    /// Program starting a stdio server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Environment of a stdio server; `${VAR}` and `keychain:` references are
+    /// resolved at launch
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Address of an HTTP or SSE server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// `http` (the default for a URL) or `sse`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transport: Option<McpTransport>,
+    /// Request headers for an HTTP or SSE server, resolved like `env`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum McpTransport {
+    Http,
+    Sse,
+}
+
+impl McpServer {
+    /// The server as an entry of Claude Code's `--mcp-config`, with `resolve` applied to
+    /// each env and header value (given a label for errors and the value)
+    pub fn claude_config(
+        &self,
+        name: &str,
+        resolve: impl Fn(&str, &str) -> Result<String>,
+    ) -> Result<serde_json::Value> {
+        let resolved = |values: &BTreeMap<String, String>| {
+            values
+                .iter()
+                .map(|(key, value)| {
+                    let label = format!("MCP server {} {}", name, key);
+                    Ok((key.clone(), resolve(&label, value)?))
+                })
+                .collect::<Result<BTreeMap<_, _>>>()
+        };
+        match (&self.command, &self.url) {
+            (Some(command), None) => Ok(serde_json::json!({
+                "type": "stdio",
+                "command": command,
+                "args": self.args,
+                "env": resolved(&self.env)?,
+            })),
+            (None, Some(url)) => Ok(serde_json::json!({
+                "type": match self.transport {
+                    Some(McpTransport::Sse) => "sse",
+                    _ => "http",
+                },
+                "url": url,
+                "headers": resolved(&self.headers)?,
+            })),
+            _ => anyhow::bail!("MCP server {} needs either a command or a url", name),
+        }
+    }
+}
+
+fn matches_any<S: AsRef<str>>(patterns: &[S], name: &str) -> bool {
+    patterns.iter().any(|p| wildcard_match(p.as_ref(), name))
+}
+
+/// Backend a profile talks to, inferred from its URLs (used for display only)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Anthropic,
+    ZAi,
+    MiniMax,
+    OpenRouter,
+    Codex,
+    OpenAi,
+    Bedrock,
+    Local,
+    Mock,
+    Custom,
+}
+
+impl Provider {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Provider::Anthropic => "Anthropic",
+            Provider::ZAi => "Z.ai",
+            Provider::MiniMax => "MiniMax",
+            Provider::OpenRouter => "OpenRouter",
+            Provider::Codex => "Codex",
+            Provider::OpenAi => "OpenAI",
+            Provider::Bedrock => "Bedrock",
+            Provider::Local => "Local",
+            Provider::Mock => "Mock",
+            Provider::Custom => "Custom",
+        }
+    }
+
+    /// Page where this provider's API keys are managed, for fixing an expired or
+    /// revoked key
+    pub fn console_url(&self) -> Option<&'static str> {
+        match self {
+            Provider::Anthropic => Some("https://console.anthropic.com/settings/keys"),
+            Provider::ZAi => Some("https://z.ai/manage-apikey/apikey-list"),
+            Provider::MiniMax => {
+                Some("https://platform.minimax.io/user-center/basic-information/interface-key")
+            }
+            Provider::OpenRouter => Some("https://openrouter.ai/settings/keys"),
+            Provider::Codex => Some("https://chatgpt.com/codex/settings"),
+            Provider::OpenAi => Some("https://platform.openai.com/api-keys"),
+            Provider::Bedrock => Some("https://console.aws.amazon.com/bedrock/home#/api-keys"),
+            Provider::Local | Provider::Mock | Provider::Custom => None,
+        }
+    }
+
+    fn from_url(url: &str) -> Self {
+        let url = url.to_ascii_lowercase();
+        if url.starts_with("mock:") {
+            Provider::Mock
+        } else if url.contains("chatgpt.com/backend-api/codex") {
+            Provider::Codex
+        } else if url.contains("anthropic.com") {
+            Provider::Anthropic
+        } else if url.contains("z.ai") || url.contains("bigmodel.cn") {
+            Provider::ZAi
+        } else if url.contains("minimax") {
+            Provider::MiniMax
+        } else if url.contains("openrouter.ai") {
+            Provider::OpenRouter
+        } else if url.contains("api.openai.com") {
+            Provider::OpenAi
+        } else if url.contains("bedrock-runtime") {
+            Provider::Bedrock
+        } else if ["localhost", "127.0.0.1", "0.0.0.0", "[::1]"]
+            .iter()
+            .any(|host| url.contains(host))
+        {
+            Provider::Local
+        } else {
+            Provider::Custom
+        }
+    }
+}
+
 /// Root configuration file structure
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Config {
+    /// Fragment files merged in at load time, relative to this file; `*` and `?`
+    /// may be used in the file name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
     /// List of all profiles
     #[serde(default)]
     pub profiles: Vec<Profile>,
@@ -41,17 +722,405 @@ pub struct Config {
     /// Name of the default profile to select on startup
     #[serde(default)]
     pub default_profile: Option<String>,
+
+    /// Whether the first-run guided tour has been completed or skipped
+    #[serde(default)]
+    pub tutorial_seen: bool,
+
+    /// Project directory -> preferred profile name
+    #[serde(default)]
+    pub workspaces: BTreeMap<String, String>,
+
+    /// Limits for the log, usage history and cache files in the config dir
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+
+    /// Blob placeholders for each log sink
+    #[serde(default)]
+    pub logging: LoggingPolicy,
+
+    /// When to ask before launching a profile with an expensive model
+    #[serde(default)]
+    pub cost_ceiling: CostCeiling,
+
+    /// Whether launches are counted for the usage dashboard's patterns
+    #[serde(default)]
+    pub analytics: AnalyticsPolicy,
+
+    /// Layout of the profile list
+    #[serde(default)]
+    pub list_view: ListView,
+
+    /// High contrast, ASCII-only and reduced-motion rendering
+    #[serde(default)]
+    pub display: DisplayOptions,
+
+    /// Profile name -> unix seconds of its last launch
+    #[serde(default)]
+    pub last_used: BTreeMap<String, u64>,
+
+    /// Trusted `.claude-profiler.toml` path -> SHA-256 of the content trusted
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub trusted_workspaces: BTreeMap<String, String>,
+
+    /// Profiles that came from `include` fragments, as loaded
+    #[serde(skip)]
+    pub included: BTreeMap<String, IncludedProfile>,
+}
+
+/// A profile merged in from a fragment file
+#[derive(Debug, Clone)]
+pub struct IncludedProfile {
+    /// Fragment path as shown to the user (relative to the config dir when inside it)
+    pub source: String,
+    /// The profile as the fragment defines it; an edited copy is saved locally
+    pub profile: Profile,
+}
+
+/// Contents of an included fragment file
+#[derive(Debug, Deserialize)]
+struct Fragment {
+    #[serde(default)]
+    profiles: Vec<Profile>,
+}
+
+/// `path:LINE:COLUMN: message`, the way compilers point at a spot in a file
+fn format_diagnostic(path: &Path, diagnostic: &Diagnostic) -> String {
+    format!(
+        "{}:{}:{}: {}",
+        path.display(),
+        diagnostic.line,
+        diagnostic.column,
+        diagnostic.message
+    )
+}
+
+/// A TOML deserialize error pointing at the line and column it was found at
+fn parse_error(path: &Path, contents: &str, error: toml::de::Error) -> anyhow::Error {
+    let diagnostic = Diagnostic::at(contents, error.span(), error.message().trim());
+    anyhow::anyhow!(format_diagnostic(path, &diagnostic))
+}
+
+/// Match a name against a pattern with `*` and `?` wildcards
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Files an `include` entry refers to, sorted by name. A pattern matching nothing
+/// is fine; a plain path that does not exist is an error.
+fn expand_include(base: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = base.join(pattern);
+    let file_pattern = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .with_context(|| format!("Invalid include: {}", pattern))?;
+    let dir = path.parent().unwrap_or(base);
+
+    if dir.to_string_lossy().contains(['*', '?']) {
+        anyhow::bail!(
+            "Invalid include {}: wildcards are only supported in the file name",
+            pattern
+        );
+    }
+    if !file_pattern.contains(['*', '?']) {
+        if !path.is_file() {
+            anyhow::bail!("Included file not found: {}", path.display());
+        }
+        return Ok(vec![path]);
+    }
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .is_some_and(|name| wildcard_match(&file_pattern, &name.to_string_lossy()))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Profile list layout, toggled with `v`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ListView {
+    /// Name and provider with the wrapped description below
+    #[default]
+    Detailed,
+    /// One row per profile: name, provider, model, last used
+    Compact,
+}
+
+impl ListView {
+    pub fn toggled(self) -> Self {
+        match self {
+            ListView::Detailed => ListView::Compact,
+            ListView::Compact => ListView::Detailed,
+        }
+    }
+}
+
+/// Rendering options for screen readers and limited terminals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct DisplayOptions {
+    /// Bright colours on black, with highlighted rows in black on white
+    pub high_contrast: bool,
+    /// Plain ASCII instead of box-drawing, arrow, block and spinner characters
+    pub ascii: bool,
+    /// Draw progress once instead of animating a spinner
+    pub reduced_motion: bool,
+}
+
+/// How each log sink treats base64/hex payloads, and how secrets are masked
+/// wherever they are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct LoggingPolicy {
+    /// `proxy.log` messages, including upstream error bodies
+    pub proxy_log: BlobRedaction,
+    /// The `PROXY_SSE_DEBUG` stream transcript
+    pub sse_debug: BlobRedaction,
+    /// API keys in the profile details and log output
+    pub secrets: SecretMask,
+}
+
+/// How much proxy log, usage history and cached data to keep on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Proxy log and request log size cap, per file; the oldest lines are dropped first
+    pub log_max_bytes: u64,
+    /// Drop proxy log and request log lines older than this many days
+    pub log_max_age_days: u64,
+    /// Usage history size cap; the oldest sessions are dropped first
+    pub history_max_bytes: u64,
+    /// Drop usage history sessions older than this many days
+    pub history_max_age_days: u64,
+    /// Delete cache files not refreshed for this many days
+    pub cache_max_age_days: u64,
+}
+
+/// Confirmation before launching a profile whose model is expensive and whose last
+/// session already cost a lot
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct CostCeiling {
+    pub enabled: bool,
+    /// Output price (USD per million tokens) from which a model counts as expensive
+    pub min_output_price: f64,
+    /// Ask when the profile's last proxied session cost at least this much (USD)
+    pub last_session_usd: f64,
+}
+
+/// Local-only counting of which profiles and models are launched, and when. Off
+/// unless turned on here; `CLAUDE_PROFILER_NO_ANALYTICS=1` keeps it off regardless.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AnalyticsPolicy {
+    pub enabled: bool,
+}
+
+/// Hard off switch for analytics, checked before the config
+pub const ENV_NO_ANALYTICS: &str = "CLAUDE_PROFILER_NO_ANALYTICS";
+
+impl AnalyticsPolicy {
+    /// Whether launches are counted: enabled in the config and not vetoed by the
+    /// environment
+    pub fn is_active(&self) -> bool {
+        self.enabled
+            && !std::env::var(ENV_NO_ANALYTICS).is_ok_and(|v| crate::openai_oauth::is_truthy(&v))
+    }
+}
+
+impl Default for CostCeiling {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_output_price: 25.0,
+            last_session_usd: 5.0,
+        }
+    }
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            log_max_bytes: 5 * 1024 * 1024,
+            log_max_age_days: 14,
+            history_max_bytes: 2 * 1024 * 1024,
+            history_max_age_days: 365,
+            cache_max_age_days: 30,
+        }
+    }
+}
+
+/// Config directory to use instead of the default one
+pub const ENV_CONFIG_DIR: &str = "CLAUDE_PROFILER_CONFIG_DIR";
+
+/// Name of the config directory under the platform's config base
+const CONFIG_DIR_NAME: &str = "claude-profiler";
+
+const CONFIG_FILE_NAME: &str = "profiles.toml";
+
+/// Left in the legacy config directory once it has been copied to the XDG one
+pub const LEGACY_NOTE_FILE: &str = "MOVED.txt";
+
+/// Config directory given with `--config`
+static CONFIG_DIR_FLAG: OnceLock<PathBuf> = OnceLock::new();
+
+/// `$XDG_CONFIG_HOME/claude-profiler`, or `~/.config/claude-profiler` without it;
+/// the platform's config directory on Windows
+fn xdg_config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return dirs::config_dir().map(|p| p.join(CONFIG_DIR_NAME));
+    }
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".config")))
+        .map(|p| p.join(CONFIG_DIR_NAME))
+}
+
+/// Where earlier versions kept the config: the platform's config directory, which
+/// is `~/Library/Application Support` on macOS
+fn legacy_config_dir() -> Option<PathBuf> {
+    let legacy = dirs::config_dir()?.join(CONFIG_DIR_NAME);
+    (Some(&legacy) != xdg_config_dir().as_ref()).then_some(legacy)
+}
+
+/// The config directory: `xdg` unless only `legacy` holds a config
+fn choose_config_dir(xdg: PathBuf, legacy: Option<PathBuf>) -> PathBuf {
+    match legacy {
+        Some(legacy)
+            if !xdg.join(CONFIG_FILE_NAME).exists() && legacy.join(CONFIG_FILE_NAME).exists() =>
+        {
+            legacy
+        }
+        _ => xdg,
+    }
+}
+
+/// Copy a config found only at `legacy` to `xdg`, leaving the original with a note
+/// that it is no longer read; returns whether it was copied. The copy is made beside
+/// `xdg` and renamed into place, so a failed copy leaves the legacy config in use.
+fn migrate_config_dir(legacy: &Path, xdg: &Path) -> Result<bool> {
+    if xdg.exists() || !legacy.join(CONFIG_FILE_NAME).exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = xdg.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let partial = xdg.with_extension("partial");
+    let _ = fs::remove_dir_all(&partial);
+    let copied = copy_dir(legacy, &partial).and_then(|()| {
+        fs::rename(&partial, xdg).with_context(|| format!("Failed to create {}", xdg.display()))
+    });
+    if let Err(e) = copied {
+        let _ = fs::remove_dir_all(&partial);
+        return Err(e)
+            .with_context(|| format!("Failed to copy {} to {}", legacy.display(), xdg.display()));
+    }
+    let note = format!(
+        "claude-profiler now reads its config from {}.\n\
+         This directory was copied there and is no longer used; delete it once you\n\
+         have checked the copy.\n",
+        xdg.display()
+    );
+    // The copy is in use either way; the note only explains the leftovers
+    let _ = fs::write(legacy.join(LEGACY_NOTE_FILE), note);
+    Ok(true)
+}
+
+/// Copy the files and directories below `from` to `to`. Sockets and broken links
+/// are left out.
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("Failed to create {}", to.display()))?;
+    for entry in fs::read_dir(from).with_context(|| format!("Failed to read {}", from.display()))? {
+        let entry = entry?;
+        let (source, target) = (entry.path(), to.join(entry.file_name()));
+        if entry.file_type()?.is_dir() {
+            copy_dir(&source, &target)?;
+        } else if source.is_file() {
+            fs::copy(&source, &target)
+                .with_context(|| format!("Failed to copy {}", source.display()))?;
+        }
+    }
+    Ok(())
 }
 
 impl Config {
-    /// Returns the default config directory path for the current OS.
+    /// Use `dir` as the config directory for the rest of the process (`--config`)
+    pub fn set_config_dir(dir: PathBuf) {
+        let _ = CONFIG_DIR_FLAG.set(dir);
+    }
+
+    /// Config directory chosen with `--config` or `CLAUDE_PROFILER_CONFIG_DIR`
+    fn explicit_config_dir() -> Option<PathBuf> {
+        CONFIG_DIR_FLAG.get().cloned().or_else(|| {
+            std::env::var_os(ENV_CONFIG_DIR)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+        })
+    }
+
+    /// Returns the config directory: `--config`, then `CLAUDE_PROFILER_CONFIG_DIR`,
+    /// then the XDG location. A config still at the legacy location is used from
+    /// there until it is moved.
     pub fn config_dir() -> Option<PathBuf> {
-        dirs::config_dir().map(|p| p.join("claude-profiler"))
+        if let Some(dir) = Self::explicit_config_dir() {
+            return Some(dir);
+        }
+        Some(choose_config_dir(xdg_config_dir()?, legacy_config_dir()))
+    }
+
+    /// Copy a config left at the legacy location (see [`Config::config_dir`]) to the
+    /// XDG one, unless a directory is chosen explicitly or the XDG one already
+    /// exists. Returns the old and new directory when it was copied.
+    pub fn migrate_legacy_config() -> Result<Option<(PathBuf, PathBuf)>> {
+        if Self::explicit_config_dir().is_some() {
+            return Ok(None);
+        }
+        let (Some(legacy), Some(xdg)) = (legacy_config_dir(), xdg_config_dir()) else {
+            return Ok(None);
+        };
+        Ok(migrate_config_dir(&legacy, &xdg)?.then_some((legacy, xdg)))
     }
 
     /// Returns the full path to the config file
     pub fn config_file_path() -> Option<PathBuf> {
-        Self::config_dir().map(|p| p.join("profiles.toml"))
+        Self::config_dir().map(|p| p.join(CONFIG_FILE_NAME))
     }
 
     /// Load config from disk, creating default if not exists
@@ -69,12 +1138,116 @@ impl Config {
         let contents = fs::read_to_string(&config_path)
             .with_context(|| format!("Failed to read config file: {}", config_path.display()))?;
 
-        let config: Config = toml::from_str(&contents)
+        let diagnostics = schema::check_config(&contents);
+        if !diagnostics.is_empty() {
+            anyhow::bail!(
+                "Invalid config file:\n{}",
+                diagnostics
+                    .iter()
+                    .map(|d| format_diagnostic(&config_path, d))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+        let mut config: Config = toml::from_str(&contents)
+            .map_err(|e| parse_error(&config_path, &contents, e))
             .with_context(|| format!("Failed to parse config file: {}", config_path.display()))?;
+        if let Some(dir) = config_path.parent() {
+            config.merge_includes(dir)?;
+        }
 
         Ok(config)
     }
 
+    /// Append the profiles from `include` fragments.
+    ///
+    /// Profiles in `profiles.toml` win over included ones with the same name; between
+    /// fragments, the later file wins (entries in order, files sorted by name).
+    pub fn merge_includes(&mut self, base: &Path) -> Result<()> {
+        let mut included: Vec<(Profile, String)> = Vec::new();
+        for pattern in &self.include {
+            for path in expand_include(base, pattern)? {
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read included file: {}", path.display()))?;
+                let fragment: Fragment = toml::from_str(&contents)
+                    .map_err(|e| parse_error(&path, &contents, e))
+                    .with_context(|| {
+                        format!("Failed to parse included file: {}", path.display())
+                    })?;
+                let source = path
+                    .strip_prefix(base)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                for profile in fragment.profiles {
+                    included.retain(|(existing, _)| existing.name != profile.name);
+                    included.push((profile, source.clone()));
+                }
+            }
+        }
+
+        for (profile, source) in included {
+            if self.profiles.iter().any(|p| p.name == profile.name) {
+                continue;
+            }
+            self.included.insert(
+                profile.name.clone(),
+                IncludedProfile {
+                    source,
+                    profile: profile.clone(),
+                },
+            );
+            self.profiles.push(profile);
+        }
+        Ok(())
+    }
+
+    /// Fragment files of each `include` entry, resolved against `base`
+    pub fn include_files(&self, base: &Path) -> Result<Vec<(String, Vec<PathBuf>)>> {
+        self.include
+            .iter()
+            .map(|pattern| Ok((pattern.clone(), expand_include(base, pattern)?)))
+            .collect()
+    }
+
+    /// Add `profile`, or overwrite the one with the same name when `replace` is set.
+    /// Returns whether an existing profile was replaced. Included profiles are only
+    /// changed in their fragment, as in the TUI.
+    pub fn add_profile(&mut self, profile: Profile, replace: bool) -> Result<bool> {
+        if profile.name.trim().is_empty() {
+            anyhow::bail!("Profile name must not be empty");
+        }
+        match self.profiles.iter().position(|p| p.name == profile.name) {
+            Some(_) if !replace => anyhow::bail!(
+                "Profile '{}' already exists (use --replace to overwrite it)",
+                profile.name
+            ),
+            Some(index) => {
+                if let Some(source) = self.included_source(&self.profiles[index]) {
+                    anyhow::bail!(
+                        "Profile '{}' comes from {}; edit it there",
+                        profile.name,
+                        source
+                    );
+                }
+                self.profiles[index] = profile;
+                Ok(true)
+            }
+            None => {
+                self.profiles.push(profile);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Fragment an unmodified included profile comes from
+    pub fn included_source(&self, profile: &Profile) -> Option<&str> {
+        self.included
+            .get(&profile.name)
+            .filter(|included| included.profile == *profile)
+            .map(|included| included.source.as_str())
+    }
+
     /// Save config to disk
     pub fn save(&self) -> Result<()> {
         let config_dir = Self::config_dir().context("Could not determine config directory")?;
@@ -90,9 +1263,19 @@ impl Config {
         let config_path =
             Self::config_file_path().context("Could not determine config file path")?;
 
-        let contents = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        // Included profiles stay in their fragments unless edited here
+        let mut local = self.clone();
+        local
+            .profiles
+            .retain(|profile| self.included_source(profile).is_none());
+        let contents = toml::to_string_pretty(&local).context("Failed to serialize config")?;
+        // Comments, formatting and key order of a hand-edited file survive the save
+        let contents = match fs::read_to_string(&config_path) {
+            Ok(existing) => merge_toml(&existing, &contents).unwrap_or(contents),
+            Err(_) => contents,
+        };
 
-        fs::write(&config_path, contents)
+        replace_file(&config_path, &contents)
             .with_context(|| format!("Failed to write config file: {}", config_path.display()))?;
 
         Ok(())
@@ -101,17 +1284,53 @@ impl Config {
     /// Create a default config with example profiles
     pub fn create_default() -> Self {
         Config {
+            include: Vec::new(),
             default_profile: Some("default".to_string()),
+            tutorial_seen: false,
+            workspaces: BTreeMap::new(),
+            retention: RetentionPolicy::default(),
+            logging: LoggingPolicy::default(),
+            cost_ceiling: CostCeiling::default(),
+            analytics: AnalyticsPolicy::default(),
+            list_view: ListView::default(),
+            display: DisplayOptions::default(),
+            last_used: BTreeMap::new(),
+            trusted_workspaces: BTreeMap::new(),
+            included: BTreeMap::new(),
             profiles: vec![
                 Profile {
                     name: "default".to_string(),
                     description: "Default profile - uses existing environment".to_string(),
+                    notes: String::new(),
+                    tags: Vec::new(),
+                    key_expires_on: None,
+                    proxy_port: None,
+                    proxy_bind: None,
+                    headers: BTreeMap::new(),
+                    routes: BTreeMap::new(),
+                    mcp: BTreeMap::new(),
+                    fanout: Vec::new(),
+                    model_map: BTreeMap::new(),
+                    header_policy: HeaderPolicy::default(),
+                    inherit_env: EnvFilter::default(),
                     env: HashMap::new(),
                 },
                 Profile {
                     name: "zai".to_string(),
                     description: "Z.ai API proxy (edit profiles.toml to add your API key)"
                         .to_string(),
+                    notes: String::new(),
+                    tags: Vec::new(),
+                    key_expires_on: None,
+                    proxy_port: None,
+                    proxy_bind: None,
+                    headers: BTreeMap::new(),
+                    routes: BTreeMap::new(),
+                    mcp: BTreeMap::new(),
+                    fanout: Vec::new(),
+                    model_map: BTreeMap::new(),
+                    header_policy: HeaderPolicy::default(),
+                    inherit_env: EnvFilter::default(),
                     env: HashMap::from([
                         (
                             ENV_AUTH_TOKEN.to_string(),
@@ -134,6 +1353,18 @@ impl Config {
                     name: "minimax".to_string(),
                     description: "MiniMax API proxy (edit profiles.toml to add your API key)"
                         .to_string(),
+                    notes: String::new(),
+                    tags: Vec::new(),
+                    key_expires_on: None,
+                    proxy_port: None,
+                    proxy_bind: None,
+                    headers: BTreeMap::new(),
+                    routes: BTreeMap::new(),
+                    mcp: BTreeMap::new(),
+                    fanout: Vec::new(),
+                    model_map: BTreeMap::new(),
+                    header_policy: HeaderPolicy::default(),
+                    inherit_env: EnvFilter::default(),
                     env: HashMap::from([
                         (
                             ENV_AUTH_TOKEN.to_string(),
@@ -165,6 +1396,18 @@ impl Config {
                 Profile {
                     name: "OpenRouter".to_string(),
                     description: "OpenRouter API (direct)".to_string(),
+                    notes: String::new(),
+                    tags: Vec::new(),
+                    key_expires_on: None,
+                    proxy_port: None,
+                    proxy_bind: None,
+                    headers: BTreeMap::new(),
+                    routes: BTreeMap::new(),
+                    mcp: BTreeMap::new(),
+                    fanout: Vec::new(),
+                    model_map: BTreeMap::new(),
+                    header_policy: HeaderPolicy::default(),
+                    inherit_env: EnvFilter::default(),
                     env: HashMap::from([
                         (
                             ENV_AUTH_TOKEN.to_string(),
@@ -191,6 +1434,18 @@ impl Config {
                 Profile {
                     name: "OpenAI Codex OAuth".to_string(),
                     description: "OpenAI Codex via ChatGPT OAuth (sign-in on first use)".to_string(),
+                    notes: String::new(),
+                    tags: Vec::new(),
+                    key_expires_on: None,
+                    proxy_port: None,
+                    proxy_bind: None,
+                    headers: BTreeMap::new(),
+                    routes: BTreeMap::new(),
+                    mcp: BTreeMap::new(),
+                    fanout: Vec::new(),
+                    model_map: BTreeMap::new(),
+                    header_policy: HeaderPolicy::default(),
+                    inherit_env: EnvFilter::default(),
                     env: HashMap::from([
                         (ENV_OPENAI_OAUTH.to_string(), "1".to_string()),
                         (
@@ -205,6 +1460,18 @@ impl Config {
                     description:
                         "Custom OpenAI-compatible API (requires PROXY_TARGET_URL and model names; leave ANTHROPIC_BASE_URL blank for Anthropic compatible endpoints)"
                             .to_string(),
+                    notes: String::new(),
+                    tags: Vec::new(),
+                    key_expires_on: None,
+                    proxy_port: None,
+                    proxy_bind: None,
+                    headers: BTreeMap::new(),
+                    routes: BTreeMap::new(),
+                    mcp: BTreeMap::new(),
+                    fanout: Vec::new(),
+                    model_map: BTreeMap::new(),
+                    header_policy: HeaderPolicy::default(),
+                    inherit_env: EnvFilter::default(),
                     env: HashMap::from([
                         (ENV_AUTH_TOKEN.to_string(), "custom".to_string()),
                         (
@@ -233,6 +1500,38 @@ impl Config {
         }
     }
 
+    /// Workspace directory and profile associated with `dir` or its nearest ancestor
+    pub fn workspace_for(&self, dir: &Path) -> Option<(&str, &str)> {
+        dir.ancestors().find_map(|ancestor| {
+            self.workspaces
+                .get_key_value(ancestor.to_string_lossy().as_ref())
+                .map(|(path, profile)| (path.as_str(), profile.as_str()))
+        })
+    }
+
+    /// Point workspace associations at a renamed profile, or drop them when `new_name` is None
+    pub fn retarget_workspaces(&mut self, old_name: &str, new_name: Option<&str>) {
+        match new_name {
+            Some(new_name) => {
+                for profile in self.workspaces.values_mut() {
+                    if profile == old_name {
+                        *profile = new_name.to_string();
+                    }
+                }
+            }
+            None => self.workspaces.retain(|_, profile| profile != old_name),
+        }
+    }
+
+    /// Move a profile's last-launch time to its new name, or drop it when `new_name` is None
+    pub fn retarget_last_used(&mut self, old_name: &str, new_name: Option<&str>) {
+        if let Some(launched) = self.last_used.remove(old_name)
+            && let Some(new_name) = new_name
+        {
+            self.last_used.insert(new_name.to_string(), launched);
+        }
+    }
+
     /// Get the index of the default profile
     pub fn default_profile_index(&self) -> usize {
         if let Some(ref name) = self.default_profile {
@@ -244,6 +1543,340 @@ impl Config {
             0
         }
     }
+
+    /// Ready-to-use profiles for the provider API keys `lookup` finds, as
+    /// `(variable, profile)`. Profiles whose name is already taken are skipped.
+    pub fn detect_provider_profiles(
+        &self,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Vec<(&'static str, Profile)> {
+        DETECTABLE_API_KEYS
+            .iter()
+            .filter(|var| lookup(var).is_some_and(|v| !v.trim().is_empty()))
+            .map(|var| (*var, provider_profile(var)))
+            .filter(|(_, profile)| !self.profiles.iter().any(|p| p.name == profile.name))
+            .collect()
+    }
+
+    /// New profile holding the Claude Code settings exported in the shell
+    /// (`ANTHROPIC_*`, `CLAUDE_*`, `API_TIMEOUT_MS`), named `shell` or `shell-2` and
+    /// so on when taken. `None` when no such variable is set.
+    pub fn profile_from_shell_env(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Option<Profile> {
+        let env: HashMap<String, String> = vars
+            .into_iter()
+            .filter(|(key, value)| is_snapshot_env_key(key) && !value.trim().is_empty())
+            .collect();
+        if env.is_empty() {
+            return None;
+        }
+        let name = std::iter::once("shell".to_string())
+            .chain((2..).map(|n| format!("shell-{}", n)))
+            .find(|name| !self.profiles.iter().any(|p| &p.name == name))?;
+        let mut keys: Vec<&str> = env.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        Some(Profile {
+            name,
+            description: format!("Snapshot of the shell environment ({})", keys.join(", ")),
+            notes: String::new(),
+            tags: Vec::new(),
+            key_expires_on: None,
+            proxy_port: None,
+            proxy_bind: None,
+            headers: BTreeMap::new(),
+            routes: BTreeMap::new(),
+            mcp: BTreeMap::new(),
+            fanout: Vec::new(),
+            model_map: BTreeMap::new(),
+            header_policy: HeaderPolicy::default(),
+            inherit_env: EnvFilter::default(),
+            env,
+        })
+    }
+}
+
+/// Variables set by Claude Code or claude-profiler for the processes they start,
+/// never by the user
+const SNAPSHOT_SKIPPED_ENV_KEYS: [&str; 3] = [
+    "CLAUDE_PROFILER_*",
+    "CLAUDE_CODE_ENTRYPOINT",
+    "CLAUDE_CODE_SSE_PORT",
+];
+
+/// Whether a shell variable configures Claude Code and belongs in a snapshot
+fn is_snapshot_env_key(key: &str) -> bool {
+    (key.starts_with("ANTHROPIC_") || key.starts_with("CLAUDE_") || key == ENV_API_TIMEOUT_MS)
+        && !SNAPSHOT_SKIPPED_ENV_KEYS
+            .iter()
+            .any(|pattern| wildcard_match(pattern, key))
+}
+
+/// Provider API keys offered as profiles on first run
+const DETECTABLE_API_KEYS: [&str; 4] = [
+    "ANTHROPIC_API_KEY",
+    "OPENAI_API_KEY",
+    "OPENROUTER_API_KEY",
+    "GROQ_API_KEY",
+];
+
+/// Profile for one of [`DETECTABLE_API_KEYS`]; the key itself stays in the
+/// environment and is referenced as `${VAR}`
+fn provider_profile(var: &str) -> Profile {
+    let reference = format!("${{{}}}", var);
+    let (name, description, env): (&str, &str, Vec<(&str, &str)>) = match var {
+        "OPENAI_API_KEY" => (
+            "OpenAI API",
+            "OpenAI API through the built-in proxy (key from OPENAI_API_KEY)",
+            vec![
+                (ENV_AUTH_TOKEN, &reference),
+                (ENV_PROXY_TARGET_URL, "https://api.openai.com/v1"),
+                (ENV_DEFAULT_HAIKU_MODEL, "gpt-5-mini"),
+                (ENV_DEFAULT_SONNET_MODEL, "gpt-5.2"),
+                (ENV_DEFAULT_OPUS_MODEL, "gpt-5.2"),
+            ],
+        ),
+        "OPENROUTER_API_KEY" => (
+            "OpenRouter API",
+            "OpenRouter API (key from OPENROUTER_API_KEY)",
+            vec![
+                (ENV_AUTH_TOKEN, &reference),
+                (ENV_BASE_URL, "https://openrouter.ai/api"),
+                (ENV_DEFAULT_HAIKU_MODEL, "google/gemini-3-flash-preview"),
+                (ENV_DEFAULT_SONNET_MODEL, "anthropic/claude-sonnet-4.5"),
+                (ENV_DEFAULT_OPUS_MODEL, "anthropic/claude-opus-4.5"),
+            ],
+        ),
+        "GROQ_API_KEY" => (
+            "Groq API",
+            "Groq API through the built-in proxy (key from GROQ_API_KEY)",
+            vec![
+                (ENV_AUTH_TOKEN, &reference),
+                (ENV_PROXY_TARGET_URL, "https://api.groq.com/openai/v1"),
+                (ENV_DEFAULT_HAIKU_MODEL, "openai/gpt-oss-20b"),
+                (ENV_DEFAULT_SONNET_MODEL, "openai/gpt-oss-120b"),
+                (ENV_DEFAULT_OPUS_MODEL, "openai/gpt-oss-120b"),
+            ],
+        ),
+        _ => (
+            "Anthropic API",
+            "Anthropic API (key from ANTHROPIC_API_KEY)",
+            vec![(ENV_API_KEY, &reference)],
+        ),
+    };
+    Profile {
+        name: name.to_string(),
+        description: description.to_string(),
+        notes: String::new(),
+        tags: Vec::new(),
+        key_expires_on: None,
+        proxy_port: None,
+        proxy_bind: None,
+        headers: BTreeMap::new(),
+        routes: BTreeMap::new(),
+        mcp: BTreeMap::new(),
+        fanout: Vec::new(),
+        model_map: BTreeMap::new(),
+        header_policy: HeaderPolicy::default(),
+        inherit_env: EnvFilter::default(),
+        env: env
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    }
+}
+
+/// Write `contents` beside `path` and rename it over the original, so a crash or a
+/// full disk never leaves half a file and a watcher never reads one. The original's
+/// permissions carry over (a new file is private to the user), and a symlinked file
+/// (say, from a dotfiles repo) is replaced where it points.
+pub fn replace_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.as_path();
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    let existing = fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    if let Err(e) = write_temp_file(&tmp, contents.as_ref(), existing) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e).with_context(|| format!("Failed to write {}", tmp.display()));
+    }
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e).with_context(|| format!("Failed to replace {}", path.display()));
+    }
+    Ok(())
+}
+
+/// A fresh `tmp` holding `contents`, created with the permissions of the file it
+/// replaces (0600 when there is none) so it is never readable by others in between
+fn write_temp_file(
+    tmp: &Path,
+    contents: &[u8],
+    permissions: Option<fs::Permissions>,
+) -> std::io::Result<()> {
+    use std::io::Write;
+    // A leftover from an interrupted save would keep its own permissions
+    let _ = fs::remove_file(tmp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(permissions.as_ref().map_or(0o600, |p| p.mode() & 0o7777));
+    }
+    let mut file = options.open(tmp)?;
+    file.write_all(contents)?;
+    #[cfg(not(unix))]
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)?;
+    }
+    Ok(())
+}
+
+/// `updated` written over the `existing` file so that only what changed is touched:
+/// unchanged values keep their formatting, comments stay with the keys and tables
+/// they precede, and profiles are matched by name. `None` if either does not parse.
+fn merge_toml(existing: &str, updated: &str) -> Option<String> {
+    let mut document: toml_edit::DocumentMut = existing.parse().ok()?;
+    let updated: toml_edit::DocumentMut = updated.parse().ok()?;
+    merge_table(document.as_table_mut(), updated.as_table());
+    Some(document.to_string())
+}
+
+fn merge_table(old: &mut dyn toml_edit::TableLike, new: &dyn toml_edit::TableLike) {
+    let removed: Vec<String> = old
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key))
+        .collect();
+    for key in removed {
+        old.remove(&key);
+    }
+    for (key, item) in new.iter() {
+        match old.get_mut(key) {
+            Some(current) => merge_item(current, item),
+            None => {
+                let mut item = item.clone();
+                clear_positions(&mut item);
+                old.insert(key, item);
+            }
+        }
+    }
+}
+
+fn merge_item(old: &mut toml_edit::Item, new: &toml_edit::Item) {
+    use toml_edit::Item;
+
+    if let (Some(old), Some(new)) = (old.as_table_like_mut(), new.as_table_like()) {
+        return merge_table(old, new);
+    }
+    if let (Item::ArrayOfTables(old), Item::ArrayOfTables(new)) = (&mut *old, new) {
+        return merge_array_of_tables(old, new);
+    }
+    if let (Some(old), Some(new)) = (old.as_value_mut(), new.as_value()) {
+        if !same_value(old, new) {
+            let decor = old.decor().clone();
+            *old = new.clone();
+            *old.decor_mut() = decor;
+        }
+        return;
+    }
+    *old = new.clone();
+    clear_positions(old);
+}
+
+/// Tables matched by their `name` (the profiles), or else in order
+fn merge_array_of_tables(old: &mut toml_edit::ArrayOfTables, new: &toml_edit::ArrayOfTables) {
+    let name =
+        |table: &toml_edit::Table| table.get("name").and_then(|v| v.as_str()).map(String::from);
+    let mut previous: Vec<toml_edit::Table> = old.iter().cloned().collect();
+    let mut merged = toml_edit::ArrayOfTables::new();
+    for table in new.iter() {
+        let wanted = name(table);
+        let matched = previous
+            .iter()
+            .position(|candidate| name(candidate) == wanted);
+        let table = match matched {
+            Some(index) => {
+                let mut current = previous.remove(index);
+                merge_table(&mut current, table);
+                current
+            }
+            None => {
+                let mut item = toml_edit::Item::Table(table.clone());
+                clear_positions(&mut item);
+                item.into_table().unwrap_or_default()
+            }
+        };
+        merged.push(table);
+    }
+    *old = merged;
+}
+
+/// Let added tables be written after the tables preceding them in the document
+/// rather than where they sat in the freshly serialized one
+fn clear_positions(item: &mut toml_edit::Item) {
+    match item {
+        toml_edit::Item::Table(table) => {
+            table.set_position(None);
+            for (_, child) in table.iter_mut() {
+                clear_positions(child);
+            }
+        }
+        toml_edit::Item::ArrayOfTables(tables) => {
+            for table in tables.iter_mut() {
+                let mut item = toml_edit::Item::Table(std::mem::take(table));
+                clear_positions(&mut item);
+                *table = item.into_table().unwrap_or_default();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn same_value(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    use toml_edit::Value;
+
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (Value::Datetime(a), Value::Datetime(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+        }
+        (Value::InlineTable(a), Value::InlineTable(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same_value(a, b)))
+        }
+        _ => false,
+    }
+}
+
+/// Variable name in a `${VAR}` value, which is read from the environment at launch
+pub fn env_reference(value: &str) -> Option<&str> {
+    value
+        .trim()
+        .strip_prefix("${")?
+        .strip_suffix('}')
+        .filter(|name| !name.is_empty())
+}
+
+/// Prefix of values stored in the OS keychain, e.g. `keychain:OpenRouter`
+pub const KEYCHAIN_PREFIX: &str = "keychain:";
+
+/// Entry name in a `keychain:<entry>` value, which is read from the keychain at launch
+pub fn keychain_reference(value: &str) -> Option<&str> {
+    value
+        .trim()
+        .strip_prefix(KEYCHAIN_PREFIX)
+        .filter(|entry| !entry.is_empty())
 }
 
 #[cfg(test)]
@@ -259,13 +1892,489 @@ mod tests {
     #[test]
     fn default_profile_index_falls_back_when_missing() {
         let config = Config {
+            include: Vec::new(),
             profiles: vec![Profile {
                 name: "first".to_string(),
                 description: String::new(),
+                notes: String::new(),
+                tags: Vec::new(),
+                key_expires_on: None,
+                proxy_port: None,
+                proxy_bind: None,
+                headers: BTreeMap::new(),
+                routes: BTreeMap::new(),
+                mcp: BTreeMap::new(),
+                fanout: Vec::new(),
+                model_map: BTreeMap::new(),
+                header_policy: HeaderPolicy::default(),
+                inherit_env: EnvFilter::default(),
                 env: HashMap::new(),
             }],
             default_profile: Some("missing".to_string()),
+            tutorial_seen: false,
+            workspaces: BTreeMap::new(),
+            retention: RetentionPolicy::default(),
+            logging: LoggingPolicy::default(),
+            cost_ceiling: CostCeiling::default(),
+            analytics: AnalyticsPolicy::default(),
+            list_view: ListView::default(),
+            display: DisplayOptions::default(),
+            last_used: BTreeMap::new(),
+            trusted_workspaces: BTreeMap::new(),
+            included: BTreeMap::new(),
         };
         assert_eq!(config.default_profile_index(), 0);
     }
+
+    #[test]
+    fn proxy_target_url_ignores_blank_values() {
+        let mut profile = Profile {
+            name: "proxy".to_string(),
+            description: String::new(),
+            notes: String::new(),
+            tags: Vec::new(),
+            key_expires_on: None,
+            proxy_port: None,
+            proxy_bind: None,
+            headers: BTreeMap::new(),
+            routes: BTreeMap::new(),
+            mcp: BTreeMap::new(),
+            fanout: Vec::new(),
+            model_map: BTreeMap::new(),
+            header_policy: HeaderPolicy::default(),
+            inherit_env: EnvFilter::default(),
+            env: HashMap::from([(ENV_PROXY_TARGET_URL.to_string(), "  ".to_string())]),
+        };
+        assert_eq!(profile.proxy_target_url(), None);
+
+        profile.env.insert(
+            ENV_PROXY_TARGET_URL.to_string(),
+            " http://localhost:1234/v1 ".to_string(),
+        );
+        assert_eq!(profile.proxy_target_url(), Some("http://localhost:1234/v1"));
+    }
+
+    #[test]
+    fn env_compare_lists_every_key_with_both_values() {
+        let config = Config::create_default();
+        let mut left = config.profiles[1].clone();
+        left.env = HashMap::from([
+            ("SHARED".to_string(), "same".to_string()),
+            ("CHANGED".to_string(), "a".to_string()),
+            ("ONLY_LEFT".to_string(), "x".to_string()),
+        ]);
+        let mut right = left.clone();
+        right.env.remove("ONLY_LEFT");
+        right.env.insert("CHANGED".to_string(), "b".to_string());
+        right.env.insert("ONLY_RIGHT".to_string(), "y".to_string());
+
+        let rows = left.env_compare(&right);
+        let keys: Vec<&str> = rows.iter().map(|row| row.key).collect();
+        assert_eq!(keys, ["CHANGED", "ONLY_LEFT", "ONLY_RIGHT", "SHARED"]);
+        assert_eq!((rows[0].left, rows[0].right), (Some("a"), Some("b")));
+        assert_eq!((rows[1].left, rows[1].right), (Some("x"), None));
+        assert_eq!((rows[2].left, rows[2].right), (None, Some("y")));
+        let differing: Vec<bool> = rows.iter().map(EnvComparison::differs).collect();
+        assert_eq!(differing, [true, true, true, false]);
+    }
+
+    #[test]
+    fn a_config_at_the_legacy_location_is_used_until_moved() {
+        let base = std::env::temp_dir().join(format!("cp-config-dir-{}", std::process::id()));
+        let legacy = base.join("Library/Application Support/claude-profiler");
+        let xdg = base.join(".config/claude-profiler");
+        fs::create_dir_all(&legacy).unwrap();
+        assert_eq!(choose_config_dir(xdg.clone(), Some(legacy.clone())), xdg);
+        assert!(!migrate_config_dir(&legacy, &xdg).unwrap());
+
+        fs::write(legacy.join(CONFIG_FILE_NAME), "profiles = []\n").unwrap();
+        fs::write(legacy.join("daemon.json"), "{}").unwrap();
+        fs::create_dir_all(legacy.join("cache")).unwrap();
+        fs::write(legacy.join("cache").join("codex-models.json"), "[]").unwrap();
+        assert_eq!(choose_config_dir(xdg.clone(), Some(legacy.clone())), legacy);
+        assert!(migrate_config_dir(&legacy, &xdg).unwrap());
+        assert!(xdg.join(CONFIG_FILE_NAME).exists());
+        assert!(xdg.join("daemon.json").exists());
+        assert!(xdg.join("cache").join("codex-models.json").exists());
+        assert!(!xdg.with_extension("partial").exists());
+        assert_eq!(choose_config_dir(xdg.clone(), Some(legacy.clone())), xdg);
+        // The original stays, with a note saying where the config went
+        assert!(legacy.join(CONFIG_FILE_NAME).exists());
+        let note = fs::read_to_string(legacy.join(LEGACY_NOTE_FILE)).unwrap();
+        assert!(note.contains(&xdg.display().to_string()));
+
+        // An existing XDG directory is never merged into
+        assert!(!migrate_config_dir(&legacy, &xdg).unwrap());
+        assert_eq!(choose_config_dir(xdg.clone(), Some(legacy.clone())), xdg);
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn key_expiry_warns_ahead_and_marks_the_day_itself_expired() {
+        let date: Date = "2024-02-29".parse().unwrap();
+        assert_eq!(date.to_string(), "2024-02-29");
+        assert_eq!(date.add_days(1).to_string(), "2024-03-01");
+        for invalid in ["2023-02-29", "2024-13-01", "2024-1-01", "24-01-01", "soon"] {
+            assert!(invalid.parse::<Date>().is_err(), "{}", invalid);
+        }
+
+        let config: Config =
+            toml::from_str("[[profiles]]\nname = \"work\"\nkey_expires_on = \"2024-03-15\"\n")
+                .unwrap();
+        let profile = &config.profiles[0];
+        assert_eq!(profile.key_expires_on, "2024-03-15".parse().ok());
+        assert!(
+            toml::to_string(&config)
+                .unwrap()
+                .contains("key_expires_on = \"2024-03-15\"")
+        );
+        assert!(
+            toml::from_str::<Config>("[[profiles]]\nname = \"x\"\nkey_expires_on = \"15/03\"")
+                .is_err()
+        );
+
+        let on = |day: &str| profile.key_expiry(day.parse().unwrap());
+        assert_eq!(on("2024-02-29"), None);
+        assert_eq!(
+            on("2024-03-01").map(|expiry| expiry.label()),
+            Some("key expires in 14 days".to_string())
+        );
+        assert_eq!(
+            on("2024-03-14").map(|expiry| expiry.label()),
+            Some("key expires tomorrow".to_string())
+        );
+        assert!(matches!(on("2024-03-15"), Some(KeyExpiry::Expired { .. })));
+        assert_eq!(
+            on("2024-06-01").map(|expiry| expiry.label()),
+            Some("key expired on 2024-03-15".to_string())
+        );
+        assert_eq!(Config::create_default().profiles[0].key_expiry(date), None);
+    }
+
+    #[test]
+    fn shell_env_snapshot_keeps_claude_code_settings_only() {
+        let mut config = Config::create_default();
+        let vars = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        assert!(
+            config
+                .profile_from_shell_env(vars(&[("PATH", "/bin"), ("ANTHROPIC_MODEL", " ")]))
+                .is_none()
+        );
+
+        let shell = vars(&[
+            ("ANTHROPIC_BASE_URL", "https://api.z.ai/api/anthropic"),
+            ("ANTHROPIC_AUTH_TOKEN", "secret"),
+            ("API_TIMEOUT_MS", "3000000"),
+            ("CLAUDE_CODE_MAX_OUTPUT_TOKENS", "32000"),
+            ("CLAUDE_PROFILER_NO_ANALYTICS", "1"),
+            ("CLAUDE_CODE_SSE_PORT", "1234"),
+            ("HOME", "/home/me"),
+        ]);
+        let profile = config.profile_from_shell_env(shell.clone()).unwrap();
+        assert_eq!(profile.name, "shell");
+        let mut keys: Vec<&String> = profile.env.keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "ANTHROPIC_AUTH_TOKEN",
+                "ANTHROPIC_BASE_URL",
+                "API_TIMEOUT_MS",
+                "CLAUDE_CODE_MAX_OUTPUT_TOKENS"
+            ]
+        );
+        assert_eq!(profile.env[ENV_AUTH_TOKEN], "secret");
+
+        config.profiles.push(profile);
+        let again = config.profile_from_shell_env(shell).unwrap();
+        assert_eq!(again.name, "shell-2");
+    }
+
+    #[test]
+    fn workspace_for_uses_nearest_ancestor() {
+        let mut config = Config::create_default();
+        config
+            .workspaces
+            .insert("/work/repo".to_string(), "zai".to_string());
+        config
+            .workspaces
+            .insert("/work/repo/sub".to_string(), "minimax".to_string());
+
+        assert_eq!(
+            config.workspace_for(Path::new("/work/repo/src")),
+            Some(("/work/repo", "zai"))
+        );
+        assert_eq!(
+            config.workspace_for(Path::new("/work/repo/sub/deep")),
+            Some(("/work/repo/sub", "minimax"))
+        );
+        assert_eq!(config.workspace_for(Path::new("/elsewhere")), None);
+
+        config.retarget_workspaces("zai", Some("zai-renamed"));
+        assert_eq!(config.workspaces["/work/repo"], "zai-renamed");
+        config.retarget_workspaces("minimax", None);
+        assert!(!config.workspaces.contains_key("/work/repo/sub"));
+    }
+
+    #[test]
+    fn custom_headers_merge_with_existing() {
+        let mut profile = Config::create_default().profiles.remove(0);
+        assert_eq!(profile.custom_headers_env(Some("X-Team: core")), None);
+
+        profile.headers.insert(
+            "anthropic-beta".to_string(),
+            "interleaved-thinking-2025-05-14".to_string(),
+        );
+        assert_eq!(
+            profile
+                .custom_headers_env(Some("X-Team: core\nAnthropic-Beta: old"))
+                .as_deref(),
+            Some("X-Team: core\nanthropic-beta: interleaved-thinking-2025-05-14")
+        );
+    }
+
+    #[test]
+    fn provider_detected_from_urls() {
+        let config = Config::create_default();
+        let providers: Vec<Provider> = config.profiles.iter().map(Profile::provider).collect();
+        assert_eq!(
+            providers,
+            vec![
+                Provider::Anthropic,
+                Provider::ZAi,
+                Provider::MiniMax,
+                Provider::OpenRouter,
+                Provider::Codex,
+                Provider::Local,
+            ]
+        );
+        assert_eq!(
+            Provider::OpenRouter.console_url(),
+            Some("https://openrouter.ai/settings/keys")
+        );
+        assert_eq!(Provider::Local.console_url(), None);
+    }
+
+    #[test]
+    fn saves_replace_the_file_in_one_rename() {
+        let dir = std::env::temp_dir().join(format!("cp-replace-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("profiles.toml");
+        replace_file(&path, "default_profile = \"a\"\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        replace_file(&path, "default_profile = \"b\"\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "default_profile = \"b\"\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["profiles.toml"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn saves_keep_comments_and_order_of_unchanged_entries() {
+        let existing = r#"# My profiles
+default_profile = 'work'   # used at startup
+
+# Local model, keep first
+[[profiles]]
+name = "local"
+description = 'Ollama'
+env = { ANTHROPIC_MODEL = "qwen3", PROXY_TARGET_URL = "http://localhost:11434/v1" }
+
+[[profiles]]
+name = "work"
+description = "Work account"
+
+[profiles.env]
+# rotate monthly
+ANTHROPIC_API_KEY = "keychain:work"
+"#;
+        let mut config: Config = toml::from_str(existing).unwrap();
+        config.profiles[1]
+            .env
+            .insert(ENV_MODEL.to_string(), "claude-opus-4-1".to_string());
+        config.profiles[1].description = "Work".to_string();
+        config.profiles.push(Profile {
+            name: "new".to_string(),
+            ..config.profiles[0].clone()
+        });
+        let updated = toml::to_string_pretty(&config).unwrap();
+
+        let merged = merge_toml(existing, &updated).unwrap();
+        let lines: Vec<&str> = merged.lines().collect();
+        assert_eq!(&lines[..2], &existing.lines().collect::<Vec<_>>()[..2]);
+        assert!(merged.contains("# Local model, keep first\n[[profiles]]\nname = \"local\"\ndescription = 'Ollama'\nenv = { ANTHROPIC_MODEL = \"qwen3\""));
+        assert!(merged.contains("description = \"Work\"\n"));
+        assert!(merged.contains("# rotate monthly\nANTHROPIC_API_KEY = \"keychain:work\"\nANTHROPIC_MODEL = \"claude-opus-4-1\""));
+        assert!(merged.find("name = \"new\"") > merged.find("rotate monthly"));
+
+        let reloaded: Config = toml::from_str(&merged).unwrap();
+        assert_eq!(reloaded.profiles, config.profiles);
+        assert_eq!(merge_toml("not toml [", &updated), None);
+    }
+
+    #[test]
+    fn includes_merge_with_local_profiles_winning() {
+        let dir =
+            std::env::temp_dir().join(format!("claude-profiler-include-{}", std::process::id()));
+        fs::create_dir_all(dir.join("work")).unwrap();
+        fs::write(
+            dir.join("work/a.toml"),
+            "[[profiles]]\nname = \"shared\"\ndescription = \"from a\"\n\n\
+             [[profiles]]\nname = \"zai\"\ndescription = \"shadowed\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("work/b.toml"),
+            "[[profiles]]\nname = \"shared\"\ndescription = \"from b\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("work/notes.txt"), "not a fragment").unwrap();
+
+        let mut config = Config::create_default();
+        config.include = vec!["work/*.toml".to_string()];
+        config.merge_includes(&dir).unwrap();
+
+        let zai = config.profiles.iter().find(|p| p.name == "zai").unwrap();
+        assert_ne!(zai.description, "shadowed");
+        assert_eq!(config.included_source(zai), None);
+
+        let shared = config.profiles.last().unwrap().clone();
+        assert_eq!(shared.description, "from b");
+        assert_eq!(
+            config.included_source(&shared),
+            Some(Path::new("work/b.toml").display().to_string().as_str())
+        );
+
+        // An edited copy is no longer the fragment's and gets saved locally
+        config.profiles.last_mut().unwrap().description = "edited".to_string();
+        assert_eq!(
+            config.included_source(config.profiles.last().unwrap()),
+            None
+        );
+
+        config.include = vec!["work/missing.toml".to_string()];
+        assert!(config.merge_includes(&dir).is_err());
+        config.include = vec!["*/a.toml".to_string()];
+        assert!(config.merge_includes(&dir).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(wildcard_match("*.toml", "openai.toml"));
+        assert!(wildcard_match("team-?.toml", "team-1.toml"));
+        assert!(!wildcard_match("*.toml", "notes.txt"));
+    }
+
+    #[test]
+    fn env_filter_denies_before_allowing() {
+        let filter: EnvFilter = toml::from_str(
+            "allow = [\"ANTHROPIC_*\", \"AWS_REGION\"]\ndeny = [\"AWS_*\", \"*_TOKEN\"]",
+        )
+        .unwrap();
+        assert!(filter.allows("ANTHROPIC_MODEL"));
+        assert!(filter.allows("PATH"));
+        assert!(filter.allows("LC_ALL"));
+        assert!(!filter.allows("AWS_REGION"));
+        assert!(!filter.allows("ANTHROPIC_AUTH_TOKEN"));
+        assert!(!filter.allows("OPENAI_API_KEY"));
+
+        assert!(EnvFilter::default().is_empty());
+        assert!(EnvFilter::default().allows("GITHUB_TOKEN"));
+    }
+
+    #[test]
+    fn mcp_servers_become_claude_code_mcp_config_entries() {
+        let profile: Profile = toml::from_str(
+            r#"
+            name = "work"
+            [mcp.github]
+            command = "github-mcp"
+            args = ["stdio"]
+            env = { GITHUB_TOKEN = "${GITHUB_TOKEN}" }
+            [mcp.docs]
+            url = "https://docs.example.com/sse"
+            transport = "sse"
+            [mcp.broken]
+            args = ["--help"]
+            "#,
+        )
+        .unwrap();
+        let resolve = |label: &str, value: &str| match env_reference(value) {
+            Some(name) => Ok(format!("{} from {}", label, name)),
+            None => Ok(value.to_string()),
+        };
+
+        assert_eq!(
+            profile.mcp["github"]
+                .claude_config("github", resolve)
+                .unwrap(),
+            serde_json::json!({
+                "type": "stdio",
+                "command": "github-mcp",
+                "args": ["stdio"],
+                "env": { "GITHUB_TOKEN": "MCP server github GITHUB_TOKEN from GITHUB_TOKEN" }
+            })
+        );
+        assert_eq!(
+            profile.mcp["docs"].claude_config("docs", resolve).unwrap(),
+            serde_json::json!({
+                "type": "sse",
+                "url": "https://docs.example.com/sse",
+                "headers": {}
+            })
+        );
+        assert!(
+            profile.mcp["broken"]
+                .claude_config("broken", resolve)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn detected_api_keys_become_profiles_referencing_the_environment() {
+        let config = Config::create_default();
+        let lookup = |var: &str| match var {
+            "OPENAI_API_KEY" => Some("sk-test".to_string()),
+            "GROQ_API_KEY" => Some(" ".to_string()),
+            "ANTHROPIC_API_KEY" => Some("sk-ant".to_string()),
+            _ => None,
+        };
+        let detected = config.detect_provider_profiles(lookup);
+        let vars: Vec<&str> = detected.iter().map(|(var, _)| *var).collect();
+        assert_eq!(vars, vec!["ANTHROPIC_API_KEY", "OPENAI_API_KEY"]);
+
+        let openai = &detected[1].1;
+        assert_eq!(openai.provider(), Provider::OpenAi);
+        assert_eq!(
+            env_reference(&openai.env[ENV_AUTH_TOKEN]),
+            Some("OPENAI_API_KEY")
+        );
+        assert!(!toml::to_string(openai).unwrap().contains("sk-test"));
+
+        let mut config = config;
+        config.profiles.push(openai.clone());
+        assert_eq!(config.detect_provider_profiles(lookup).len(), 1);
+        assert_eq!(env_reference("$OPENAI_API_KEY"), None);
+    }
 }
@@ -0,0 +1,88 @@
+//! Noticing edits to `profiles.toml` (and included fragments in the config
+//! directory) made outside the TUI, so they can be reloaded while it runs.
+//!
+//! Editors save in several steps (write a temporary file, rename it over the old
+//! one), so the whole config directory is watched and every `.toml` change counts.
+//! Events are only collected here; the TUI reloads between frames.
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{Receiver, channel};
+
+use crate::config::Config;
+
+pub struct ConfigWatcher {
+    /// Dropping the watcher stops it
+    _watcher: RecommendedWatcher,
+    changes: Receiver<()>,
+}
+
+fn is_config_change(event: &Event) -> bool {
+    !event.kind.is_access()
+        && event
+            .paths
+            .iter()
+            .any(|path| path.extension().is_some_and(|ext| ext == "toml"))
+}
+
+impl ConfigWatcher {
+    /// Watch the config directory; `None` where the platform cannot
+    pub fn start() -> Option<Self> {
+        let dir = Config::config_dir()?;
+        Self::watch(&dir).ok()
+    }
+
+    fn watch(dir: &Path) -> notify::Result<Self> {
+        let (sender, changes) = channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if event.is_ok_and(|event| is_config_change(&event)) {
+                let _ = sender.send(());
+            }
+        })?;
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Whether a config file changed since the last call
+    pub fn changed(&self) -> bool {
+        // One save is several events; they are taken together
+        self.changes.try_iter().count() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn toml_changes_in_the_config_directory_are_noticed() {
+        let dir = std::env::temp_dir().join(format!("cp-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let watcher = ConfigWatcher::watch(&dir).unwrap();
+        let wait_for_change = || {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while Instant::now() < deadline {
+                if watcher.changed() {
+                    return true;
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            false
+        };
+
+        std::fs::write(dir.join("proxy.log"), "noise").unwrap();
+        std::fs::write(dir.join("profiles.toml"), "[[profiles]]\nname = \"a\"\n").unwrap();
+        assert!(wait_for_change());
+        std::thread::sleep(Duration::from_millis(100));
+        let _ = watcher.changed();
+
+        std::fs::write(dir.join("proxy.log"), "more noise").unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!watcher.changed());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
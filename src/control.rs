@@ -0,0 +1,282 @@
+//! Local control socket for scripting a running claude-profiler.
+//!
+//! The TUI listens on `control.sock` in the config directory (Unix only, mode 0600).
+//! Each connection sends one command line and gets one reply line back, `ok ...` or
+//! `error: ...`. `claude-profiler ctl <command>` is the bundled client:
+//!
+//! - `select <profile>` highlights a profile in the list
+//! - `launch [<profile>]` launches the given (or highlighted) profile
+//! - `stop` ends the running Claude Code session and its proxy
+//! - `reload` re-reads `profiles.toml`
+//! - `status` reports `idle <profile>` or `running <profile>`
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// Socket file name inside the config directory
+const SOCKET_FILE: &str = "control.sock";
+
+/// How long a connection waits for the TUI to act on its command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Select(String),
+    Launch(Option<String>),
+    Stop,
+    Reload,
+    Status,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (verb, arg) = match line.split_once(char::is_whitespace) {
+            Some((verb, arg)) => (verb, Some(arg.trim().to_string())),
+            None => (line, None),
+        };
+        match (verb, arg) {
+            ("select", Some(name)) => Ok(Command::Select(name)),
+            ("launch", name) => Ok(Command::Launch(name)),
+            ("stop", None) => Ok(Command::Stop),
+            ("reload", None) => Ok(Command::Reload),
+            ("status", None) => Ok(Command::Status),
+            _ => Err(format!(
+                "unknown command '{}' (select <profile>, launch [<profile>], stop, reload, status)",
+                line
+            )),
+        }
+    }
+}
+
+/// A command waiting for the TUI, with the connection's reply channel
+pub struct Request {
+    pub command: Command,
+    reply: mpsc::Sender<Result<String, String>>,
+}
+
+impl Request {
+    pub fn reply(self, result: Result<String, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+/// What the socket thread can answer while the TUI is suspended for a session
+#[derive(Debug, Default)]
+pub struct SessionState {
+    running: Mutex<Option<String>>,
+    stop: AtomicBool,
+}
+
+impl SessionState {
+    pub fn begin(&self, profile: &str) {
+        self.stop.store(false, Ordering::SeqCst);
+        *self.running.lock().unwrap_or_else(|e| e.into_inner()) = Some(profile.to_string());
+    }
+
+    pub fn end(&self) {
+        *self.running.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    fn running(&self) -> Option<String> {
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Whether `stop` was requested since the last call
+    pub fn take_stop(&self) -> bool {
+        self.stop.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// The listening socket; removed again when dropped
+pub struct ControlServer {
+    pub requests: mpsc::Receiver<Request>,
+    pub session: Arc<SessionState>,
+    path: PathBuf,
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn socket_path() -> Option<PathBuf> {
+    Config::config_dir().map(|dir| dir.join(SOCKET_FILE))
+}
+
+/// Answer one command: session commands directly, the rest through the TUI
+fn dispatch(
+    command: Command,
+    requests: &mpsc::Sender<Request>,
+    session: &SessionState,
+) -> Result<String, String> {
+    match (command, session.running()) {
+        (Command::Status, Some(profile)) => Ok(format!("running {}", profile)),
+        (Command::Stop, Some(profile)) => {
+            session.stop.store(true, Ordering::SeqCst);
+            Ok(format!("stopping {}", profile))
+        }
+        (Command::Stop, None) => Err("no session is running".to_string()),
+        (_, Some(profile)) => Err(format!("'{}' is running; stop it first", profile)),
+        (command, None) => {
+            let (reply, result) = mpsc::channel();
+            requests
+                .send(Request { command, reply })
+                .map_err(|_| "claude-profiler is shutting down".to_string())?;
+            result
+                .recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| Err("timed out waiting for the TUI".to_string()))
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    pub fn start() -> Result<Option<ControlServer>> {
+        let path = socket_path().context("Could not determine config directory")?;
+        if UnixStream::connect(&path).is_ok() {
+            // Another instance owns the socket; leave it alone
+            return Ok(None);
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to bind {}", path.display()))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+
+        let (tx, requests) = mpsc::channel();
+        let session = Arc::new(SessionState::default());
+        let thread_session = session.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                let session = thread_session.clone();
+                std::thread::spawn(move || handle_connection(stream, &tx, &session));
+            }
+        });
+        Ok(Some(ControlServer {
+            requests,
+            session,
+            path,
+        }))
+    }
+
+    fn handle_connection(stream: UnixStream, tx: &mpsc::Sender<Request>, session: &SessionState) {
+        let mut line = String::new();
+        let Ok(reader) = stream.try_clone() else {
+            return;
+        };
+        if BufReader::new(reader).read_line(&mut line).is_err() {
+            return;
+        }
+        let result = Command::parse(&line).and_then(|command| dispatch(command, tx, session));
+        let reply = match result {
+            Ok(message) if message.is_empty() => "ok".to_string(),
+            Ok(message) => format!("ok {}", message),
+            Err(message) => format!("error: {}", message),
+        };
+        let mut stream = stream;
+        let _ = writeln!(stream, "{}", reply);
+    }
+
+    pub fn send(command: &str) -> Result<String> {
+        let path = socket_path().context("Could not determine config directory")?;
+        let mut stream = UnixStream::connect(&path).with_context(|| {
+            format!(
+                "No running claude-profiler (could not connect to {})",
+                path.display()
+            )
+        })?;
+        writeln!(stream, "{}", command)?;
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(reply.trim_end().to_string())
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::*;
+
+    pub fn start() -> Result<Option<ControlServer>> {
+        Ok(None)
+    }
+
+    pub fn send(_command: &str) -> Result<String> {
+        anyhow::bail!("The control socket is only available on Unix");
+    }
+}
+
+/// Listen on the control socket, unless another instance already does
+pub fn start() -> Result<Option<ControlServer>> {
+    imp::start()
+}
+
+/// `claude-profiler ctl <command> [<profile>]`
+pub fn run_command(args: &[String]) -> Result<()> {
+    if args.is_empty() {
+        anyhow::bail!("Usage: claude-profiler ctl <select|launch|stop|reload|status> [<profile>]");
+    }
+    let command = args.join(" ");
+    Command::parse(&command).map_err(|e| anyhow::anyhow!(e))?;
+    let reply = imp::send(&command)?;
+    println!("{}", reply);
+    if reply.starts_with("error") {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse_with_optional_profile() {
+        assert_eq!(
+            Command::parse("select Local LLM\n"),
+            Ok(Command::Select("Local LLM".to_string()))
+        );
+        assert_eq!(Command::parse("launch"), Ok(Command::Launch(None)));
+        assert_eq!(Command::parse(" status "), Ok(Command::Status));
+        assert!(Command::parse("select").is_err());
+        assert!(Command::parse("stop now").is_err());
+    }
+
+    #[test]
+    fn running_session_answers_without_the_tui() {
+        let (tx, rx) = mpsc::channel();
+        let session = SessionState::default();
+        assert_eq!(
+            dispatch(Command::Stop, &tx, &session),
+            Err("no session is running".to_string())
+        );
+
+        session.begin("zai");
+        assert_eq!(
+            dispatch(Command::Status, &tx, &session),
+            Ok("running zai".to_string())
+        );
+        assert!(dispatch(Command::Reload, &tx, &session).is_err());
+        assert!(dispatch(Command::Stop, &tx, &session).is_ok());
+        assert!(session.take_stop());
+        assert!(!session.take_stop());
+        assert!(rx.try_recv().is_err());
+    }
+}
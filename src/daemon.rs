@@ -0,0 +1,237 @@
+//! `claude-profiler daemon`: one long-lived proxy for every proxied profile, so
+//! launches reuse it instead of starting and stopping a proxy of their own.
+//!
+//! - `daemon run [--port <port>] [<profile>...]` serves the named profiles (every
+//!   proxied profile by default) until stopped; the first is the active profile
+//! - `daemon status`, `daemon switch <profile>`, `daemon reload` and `daemon stop`
+//!   talk to the running daemon over its control API (see `proxy::daemon`)
+//!
+//! The daemon records its address in `daemon.json` in the config directory, which
+//! is how launches and the commands above find it.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{Config, ENV_PASSTHROUGH, Profile};
+use crate::launcher;
+use crate::openai_oauth::is_truthy;
+use crate::platform;
+use crate::proxy::{self, DaemonLoad, DaemonProfile, ListenAddr};
+
+/// Default daemon port, next to the session proxy's
+pub const DAEMON_PORT: u16 = 4100;
+
+/// Address of the running daemon, in the config directory
+const DAEMON_FILE: &str = "daemon.json";
+
+/// How long a launch waits for the daemon to say whether it serves a profile
+const STATUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Reloading prepares every profile again, which may probe slow upstreams
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(120);
+
+const USAGE: &str = "Usage: claude-profiler daemon run [--port <port>] [<profile>...]\n       \
+                     claude-profiler daemon <status|switch <profile>|reload|stop>";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DaemonInfo {
+    url: String,
+    pid: u32,
+}
+
+fn daemon_file() -> Option<PathBuf> {
+    Config::config_dir().map(|dir| dir.join(DAEMON_FILE))
+}
+
+/// Base URL of the running daemon, if there is one
+fn daemon_url() -> Option<String> {
+    let text = std::fs::read_to_string(daemon_file()?).ok()?;
+    let info: DaemonInfo = serde_json::from_str(&text).ok()?;
+    platform::process_running(info.pid).then_some(info.url)
+}
+
+/// Whether a profile goes through the proxy when launched
+fn uses_proxy(profile: &Profile) -> bool {
+    profile.proxy_target_url().is_some()
+        || profile
+            .env
+            .get(ENV_PASSTHROUGH)
+            .is_some_and(|v| is_truthy(v))
+}
+
+/// Prepare the named profiles, or every proxied one, from the current config
+fn load_profiles(names: &[String]) -> Result<DaemonLoad> {
+    let config = Config::load()?;
+    let mut load = DaemonLoad::default();
+    let profiles: Vec<&Profile> = if names.is_empty() {
+        config.profiles.iter().filter(|p| uses_proxy(p)).collect()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                config
+                    .profiles
+                    .iter()
+                    .find(|p| &p.name == name)
+                    .with_context(|| format!("No profile named '{}'", name))
+            })
+            .collect::<Result<_>>()?
+    };
+    for profile in profiles {
+        match launcher::prepare_profile(profile) {
+            Ok(prepared) => match prepared.proxy {
+                Some((target_url, options)) => load.profiles.push(DaemonProfile {
+                    name: profile.name.clone(),
+                    target_url,
+                    options,
+                }),
+                None => load.skipped.push((
+                    profile.name.clone(),
+                    "does not use the proxy (no PROXY_TARGET_URL)".to_string(),
+                )),
+            },
+            Err(e) => load
+                .skipped
+                .push((profile.name.clone(), format!("{:#}", e))),
+        }
+    }
+    Ok(load)
+}
+
+/// `ANTHROPIC_BASE_URL` for a profile the running daemon serves
+pub fn serving(profile: &str) -> Option<String> {
+    let url = daemon_url()?;
+    let status: Value = reqwest::blocking::Client::builder()
+        .timeout(STATUS_TIMEOUT)
+        .build()
+        .ok()?
+        .get(format!("{}/daemon/status", url))
+        .send()
+        .ok()?
+        .json()
+        .ok()?;
+    status["profiles"]
+        .as_array()?
+        .iter()
+        .find(|served| served["name"] == profile)
+        .map(|_| format!("{}{}/anthropic", url, proxy::daemon_profile_path(profile)))
+}
+
+/// Serve profiles in the foreground until stopped
+fn run(args: &[String]) -> Result<()> {
+    let mut port = DAEMON_PORT;
+    let mut names = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--port" {
+            port = args
+                .next()
+                .and_then(|v| v.parse().ok())
+                .context("--port needs a port number")?;
+        } else {
+            names.push(arg.clone());
+        }
+    }
+    if let Some(url) = daemon_url() {
+        anyhow::bail!("A daemon is already running at {}", url);
+    }
+    proxy::configure_logging(Config::load()?.logging);
+
+    let listen = ListenAddr::new(None, Some(port));
+    let path = daemon_file().context("Could not determine config directory")?;
+    let info = DaemonInfo {
+        url: listen.base_url(),
+        pid: std::process::id(),
+    };
+    platform::write_private(&path, serde_json::to_string_pretty(&info)?.as_bytes())?;
+
+    let active = names.first().cloned();
+    let loader: proxy::DaemonLoader = Arc::new(move || load_profiles(&names));
+    println!("claude-profiler daemon listening on {}", info.url);
+    let result =
+        tokio::runtime::Runtime::new()?.block_on(proxy::serve_daemon(listen, loader, active));
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Send a control request to the running daemon and return its JSON reply
+fn control(method: reqwest::Method, path: &str, body: Option<Value>) -> Result<Value> {
+    let url = daemon_url().context("No claude-profiler daemon is running")?;
+    let mut request = reqwest::blocking::Client::builder()
+        .timeout(CONTROL_TIMEOUT)
+        .build()?
+        .request(method, format!("{}{}", url, path));
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    let response = request
+        .send()
+        .with_context(|| format!("Failed to reach the daemon at {}", url))?;
+    let status = response.status();
+    let reply: Value = response.json()?;
+    if !status.is_success() {
+        anyhow::bail!("{}", reply["error"].as_str().unwrap_or("daemon error"));
+    }
+    Ok(reply)
+}
+
+fn print_status(status: &Value) {
+    let active = status["active"].as_str();
+    println!(
+        "Daemon up {}s, active profile: {}",
+        status["uptime_secs"],
+        active.unwrap_or("(none)")
+    );
+    for profile in status["profiles"].as_array().into_iter().flatten() {
+        let name = profile["name"].as_str().unwrap_or_default();
+        println!(
+            "{} {} -> {} ({})",
+            if Some(name) == active { "*" } else { " " },
+            name,
+            profile["target_url"].as_str().unwrap_or_default(),
+            profile["path"].as_str().unwrap_or_default()
+        );
+    }
+    for skipped in status["skipped"].as_array().into_iter().flatten() {
+        println!(
+            "  skipped {}: {}",
+            skipped["name"].as_str().unwrap_or_default(),
+            skipped["reason"].as_str().unwrap_or_default()
+        );
+    }
+}
+
+/// `claude-profiler daemon <command>`
+pub fn run_command(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("run") => run(&args[1..]),
+        Some("status") if args.len() == 1 => {
+            print_status(&control(reqwest::Method::GET, "/daemon/status", None)?);
+            Ok(())
+        }
+        Some("switch") if args.len() > 1 => {
+            let profile = args[1..].join(" ");
+            let body = serde_json::json!({ "profile": profile });
+            print_status(&control(
+                reqwest::Method::POST,
+                "/daemon/switch",
+                Some(body),
+            )?);
+            Ok(())
+        }
+        Some("reload") if args.len() == 1 => {
+            print_status(&control(reqwest::Method::POST, "/daemon/reload", None)?);
+            Ok(())
+        }
+        Some("stop") if args.len() == 1 => {
+            control(reqwest::Method::POST, "/daemon/stop", None)?;
+            println!("Daemon stopping");
+            Ok(())
+        }
+        _ => anyhow::bail!(USAGE),
+    }
+}
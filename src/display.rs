@@ -0,0 +1,279 @@
+//! Rendering options for screen readers and limited terminals (`[display]` in
+//! profiles.toml): a high-contrast palette, plain ASCII instead of box-drawing,
+//! arrow and block characters, and no spinner animation.
+//!
+//! The TUI draws as usual and `adjust_buffer` rewrites the finished frame, so the
+//! screens need no knowledge of these options. It also brings 24-bit colours down to
+//! the 256-colour palette on terminals that do not advertise truecolor.
+
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ratatui::buffer::Buffer;
+use ratatui::style::{Color, Modifier};
+
+use crate::config::DisplayOptions;
+
+/// Display options, set once from the config at startup
+static DISPLAY: OnceLock<DisplayOptions> = OnceLock::new();
+
+pub fn configure(options: DisplayOptions) {
+    let _ = DISPLAY.set(options);
+}
+
+fn options() -> DisplayOptions {
+    DISPLAY.get().copied().unwrap_or_default()
+}
+
+/// Whether the terminal shows 24-bit colours, set when the TUI starts
+static TRUECOLOR: AtomicBool = AtomicBool::new(true);
+
+pub fn set_truecolor(supported: bool) {
+    TRUECOLOR.store(supported, Ordering::Relaxed);
+}
+
+/// Whether the terminal says it shows 24-bit colours
+pub fn detect_truecolor() -> bool {
+    let var = |name| std::env::var(name).ok();
+    truecolor_from(
+        var("COLORTERM").as_deref(),
+        var("TERM").as_deref(),
+        var("TERM_PROGRAM").as_deref(),
+    ) || var("WT_SESSION").is_some()
+}
+
+fn truecolor_from(colorterm: Option<&str>, term: Option<&str>, program: Option<&str>) -> bool {
+    matches!(colorterm, Some("truecolor" | "24bit"))
+        || term.is_some_and(|term| term.contains("direct") || term.contains("truecolor"))
+        || matches!(
+            program,
+            Some("iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper")
+        )
+}
+
+/// Closest colour of the 256-colour palette: its 6x6x6 cube or its grey ramp
+fn indexed(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| (LEVELS[i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        [(r, r2), (g, g2), (b, b2)]
+            .iter()
+            .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+    // Greys run from 8 to 238 in steps of 10
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let grey_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let grey = 8 + grey_index * 10;
+    if distance((grey, grey, grey)) < distance(cube) {
+        232 + grey_index
+    } else {
+        16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8
+    }
+}
+
+fn reduce_color(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => Color::Indexed(indexed(r, g, b)),
+        _ => color,
+    }
+}
+
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const ASCII_SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Progress indicator for the launcher's waits. With reduced motion it is drawn
+/// once and left alone, so screen readers are not handed a line repainted ten
+/// times a second.
+pub struct Spinner {
+    frames: &'static [char],
+    animated: bool,
+    index: usize,
+    drawn: bool,
+}
+
+impl Default for Spinner {
+    fn default() -> Self {
+        let options = options();
+        Self {
+            frames: match (options.ascii, options.reduced_motion) {
+                (true, true) => &['*'],
+                (false, true) => &['⠿'],
+                (true, false) => &ASCII_SPINNER_FRAMES,
+                (false, false) => &SPINNER_FRAMES,
+            },
+            animated: !options.reduced_motion,
+            index: 0,
+            drawn: false,
+        }
+    }
+}
+
+impl Spinner {
+    /// The frame to draw next, or `None` when the line should stay as it is
+    pub fn tick(&mut self) -> Option<char> {
+        if self.drawn && !self.animated {
+            return None;
+        }
+        if self.drawn {
+            self.index = (self.index + 1) % self.frames.len();
+        }
+        self.drawn = true;
+        Some(self.current())
+    }
+
+    /// The frame last drawn, for the line that replaces the spinner
+    pub fn current(&self) -> char {
+        self.frames[self.index]
+    }
+}
+
+/// ASCII stand-in for a symbol the TUI draws, one cell wide like the original
+fn ascii_symbol(symbol: &str) -> Option<&'static str> {
+    Some(match symbol {
+        "─" | "━" | "═" | "╌" => "-",
+        "│" | "┃" | "║" | "╎" => "|",
+        "┌" | "┐" | "└" | "┘" | "├" | "┤" | "┬" | "┴" | "┼" | "╭" | "╮" | "╯" | "╰" | "╔" | "╗"
+        | "╚" | "╝" => "+",
+        "↑" | "▲" => "^",
+        "↓" | "▼" => "v",
+        "←" | "◀" => "<",
+        "→" | "▶" | "▸" | "►" => ">",
+        "●" | "•" => "*",
+        "○" => "o",
+        "·" => ".",
+        "…" => "~",
+        "×" => "x",
+        "✓" => "+",
+        "✗" => "x",
+        "█" | "▇" | "▆" => "#",
+        "▅" | "▄" | "▃" => "=",
+        "▂" | "▁" => "_",
+        _ => return None,
+    })
+}
+
+/// Bright variant of a colour; greys and custom colours become white
+fn high_contrast(color: Color) -> Color {
+    match color {
+        Color::Reset | Color::Black | Color::White => color,
+        Color::Red => Color::LightRed,
+        Color::Green => Color::LightGreen,
+        Color::Yellow => Color::LightYellow,
+        Color::Blue => Color::LightBlue,
+        Color::Magenta => Color::LightMagenta,
+        Color::Cyan => Color::LightCyan,
+        Color::LightRed
+        | Color::LightGreen
+        | Color::LightYellow
+        | Color::LightBlue
+        | Color::LightMagenta
+        | Color::LightCyan => color,
+        _ => Color::White,
+    }
+}
+
+/// Rewrite a drawn frame for the configured options
+pub fn adjust_buffer(buffer: &mut Buffer) {
+    adjust_buffer_with(buffer, options());
+    if !TRUECOLOR.load(Ordering::Relaxed) {
+        for cell in &mut buffer.content {
+            cell.fg = reduce_color(cell.fg);
+            cell.bg = reduce_color(cell.bg);
+        }
+    }
+}
+
+fn adjust_buffer_with(buffer: &mut Buffer, options: DisplayOptions) {
+    if !options.high_contrast && !options.ascii {
+        return;
+    }
+    for cell in &mut buffer.content {
+        if options.ascii
+            && let Some(ascii) = ascii_symbol(cell.symbol())
+        {
+            cell.set_symbol(ascii);
+        }
+        if options.high_contrast {
+            cell.modifier.remove(Modifier::DIM);
+            if matches!(cell.bg, Color::Reset | Color::Black) {
+                cell.fg = high_contrast(cell.fg);
+            } else {
+                // Highlighted rows become black on white
+                cell.bg = Color::White;
+                cell.fg = Color::Black;
+                cell.modifier.insert(Modifier::BOLD);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+    use ratatui::widgets::{Block, Borders, Widget};
+
+    #[test]
+    fn frames_are_rewritten_for_ascii_and_high_contrast() {
+        let area = Rect::new(0, 0, 6, 3);
+        let mut buffer = Buffer::empty(area);
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::DarkGray))
+            .render(area, &mut buffer);
+        buffer.set_string(1, 1, "↑…", Style::default().bg(Color::DarkGray));
+
+        let unchanged = buffer.clone();
+        adjust_buffer_with(&mut buffer, DisplayOptions::default());
+        assert_eq!(buffer, unchanged);
+
+        adjust_buffer_with(
+            &mut buffer,
+            DisplayOptions {
+                high_contrast: true,
+                ascii: true,
+                reduced_motion: false,
+            },
+        );
+        let rows: Vec<String> = (0..3)
+            .map(|y| (0..6).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+        assert_eq!(rows, ["+----+", "|^~  |", "+----+"]);
+        assert_eq!(buffer[(0, 0)].fg, Color::White);
+        assert_eq!(
+            (buffer[(1, 1)].fg, buffer[(1, 1)].bg),
+            (Color::Black, Color::White)
+        );
+    }
+
+    #[test]
+    fn truecolor_falls_back_to_the_256_colour_palette() {
+        assert!(truecolor_from(
+            Some("truecolor"),
+            Some("xterm-256color"),
+            None
+        ));
+        assert!(truecolor_from(None, Some("xterm-direct"), None));
+        assert!(truecolor_from(None, None, Some("iTerm.app")));
+        assert!(!truecolor_from(
+            None,
+            Some("xterm-256color"),
+            Some("Apple_Terminal")
+        ));
+
+        assert_eq!(reduce_color(Color::Rgb(0, 0, 0)), Color::Indexed(16));
+        assert_eq!(reduce_color(Color::Rgb(255, 255, 255)), Color::Indexed(231));
+        assert_eq!(reduce_color(Color::Rgb(255, 0, 0)), Color::Indexed(196));
+        assert_eq!(reduce_color(Color::Rgb(90, 170, 255)), Color::Indexed(75));
+        assert_eq!(reduce_color(Color::Rgb(128, 128, 128)), Color::Indexed(244));
+        assert_eq!(reduce_color(Color::Blue), Color::Blue);
+    }
+}
@@ -0,0 +1,157 @@
+//! Free-form environment variable editor for the profile edit form.
+//!
+//! Holds the variables the form has no dedicated field for, one `KEY=VALUE` row
+//! each, and checks them when the form is saved.
+
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use tui_input::Input;
+
+#[derive(Debug, Clone, Default)]
+pub struct EnvEditor {
+    rows: Vec<Input>,
+    selected: usize,
+}
+
+impl EnvEditor {
+    /// Rows for the variables of `env` not in `fixed`, sorted by name
+    pub fn new(env: &HashMap<String, String>, fixed: &[&str]) -> Self {
+        let mut vars: Vec<(&String, &String)> = env
+            .iter()
+            .filter(|(key, _)| !fixed.contains(&key.as_str()))
+            .collect();
+        vars.sort();
+        Self {
+            rows: vars
+                .into_iter()
+                .map(|(key, value)| Input::new(format!("{}={}", key, value)))
+                .collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn rows(&self) -> &[Input] {
+        &self.rows
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Input of the selected row, if there are any rows
+    pub fn selected_input_mut(&mut self) -> Option<&mut Input> {
+        self.rows.get_mut(self.selected)
+    }
+
+    /// Move the selection; false when it is already at that end
+    pub fn move_selection(&mut self, delta: isize) -> bool {
+        match self.selected.checked_add_signed(delta) {
+            Some(next) if next < self.rows.len() => {
+                self.selected = next;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Add an empty row below the selected one and select it
+    pub fn add_row(&mut self) {
+        let index = if self.rows.is_empty() {
+            0
+        } else {
+            self.selected + 1
+        };
+        self.rows.insert(index, Input::default());
+        self.selected = index;
+    }
+
+    pub fn remove_row(&mut self) {
+        if self.selected < self.rows.len() {
+            self.rows.remove(self.selected);
+        }
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+    }
+
+    /// The variables as entered; blank rows are skipped. Fails on a row without a
+    /// name, a name that is not a valid variable name, a duplicate, or one of the
+    /// `fixed` variables, which have their own fields.
+    pub fn vars(&self, fixed: &[&str]) -> Result<Vec<(String, String)>> {
+        let mut vars: Vec<(String, String)> = Vec::new();
+        for row in &self.rows {
+            let text = row.value();
+            if text.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = text.split_once('=').unwrap_or((text, ""));
+            let key = key.trim();
+            if key.is_empty() {
+                bail!("'{}' needs a variable name before '='", text.trim());
+            }
+            if key.starts_with(|c: char| c.is_ascii_digit())
+                || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                bail!("'{}' is not a valid variable name", key);
+            }
+            if fixed.contains(&key) {
+                bail!("{} has its own field in the form", key);
+            }
+            if vars.iter().any(|(existing, _)| existing == key) {
+                bail!("{} is set twice", key);
+            }
+            vars.push((key.to_string(), value.to_string()));
+        }
+        Ok(vars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_round_trip_and_are_checked_on_save() {
+        let env = HashMap::from([
+            ("HTTP_PROXY".to_string(), "http://proxy:3128".to_string()),
+            ("ANTHROPIC_AUTH_TOKEN".to_string(), "secret".to_string()),
+            ("API_TIMEOUT_MS".to_string(), "600000".to_string()),
+        ]);
+        let fixed = ["ANTHROPIC_AUTH_TOKEN"];
+        let mut editor = EnvEditor::new(&env, &fixed);
+        let rows: Vec<&str> = editor.rows().iter().map(Input::value).collect();
+        assert_eq!(
+            rows,
+            ["API_TIMEOUT_MS=600000", "HTTP_PROXY=http://proxy:3128"]
+        );
+
+        // Values keep their own `=` signs
+        editor.add_row();
+        assert_eq!(editor.selected(), 1);
+        *editor.selected_input_mut().unwrap() = Input::new("FLAGS=a=b".to_string());
+        editor.add_row();
+        assert_eq!(
+            editor.vars(&fixed).unwrap(),
+            [
+                ("API_TIMEOUT_MS".to_string(), "600000".to_string()),
+                ("FLAGS".to_string(), "a=b".to_string()),
+                ("HTTP_PROXY".to_string(), "http://proxy:3128".to_string()),
+            ]
+        );
+
+        for (text, error) in [
+            ("=1", "needs a variable name"),
+            ("MY VAR=1", "not a valid variable name"),
+            ("ANTHROPIC_AUTH_TOKEN=x", "has its own field"),
+            ("HTTP_PROXY=other", "set twice"),
+        ] {
+            *editor.selected_input_mut().unwrap() = Input::new(text.to_string());
+            let message = editor.vars(&fixed).unwrap_err().to_string();
+            assert!(message.contains(error), "{}: {}", text, message);
+        }
+
+        editor.remove_row();
+        assert_eq!(editor.vars(&fixed).unwrap().len(), 3);
+        assert!(!editor.move_selection(1));
+        assert!(editor.move_selection(-2));
+        assert!(!editor.move_selection(-1));
+    }
+}
@@ -0,0 +1,264 @@
+//! Reachability of each profile's upstream, shown next to the profile in the list.
+//!
+//! Checks run on worker threads, a few at a time, so opening the TUI never waits on
+//! the network. Results are kept for a few minutes and profiles sharing an upstream
+//! share one check; `h` checks everything again.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+use crate::config::{ENV_BASE_URL, Profile, Provider};
+use crate::local_models::LocalBackend;
+
+/// Checks running at once
+const MAX_PARALLEL_CHECKS: usize = 4;
+
+/// How long a result is reused before the upstream is checked again
+const RESULT_TTL: Duration = Duration::from_secs(300);
+
+/// How long one check waits for an answer
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Anthropic's API, reached by profiles without a base URL
+const ANTHROPIC_API_URL: &str = "https://api.anthropic.com";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Health {
+    Checking,
+    /// The upstream answered (any status below 500)
+    Up {
+        latency: Duration,
+    },
+    Down(String),
+}
+
+/// Result of a check and when it finished (or started, while still running)
+#[derive(Debug, Clone)]
+pub struct HealthResult {
+    pub health: Health,
+    pub checked_at: Instant,
+}
+
+type Check = fn(&str) -> Health;
+
+/// Health check results by upstream URL
+pub struct HealthChecks {
+    results: HashMap<String, HealthResult>,
+    sender: mpsc::Sender<(String, Health)>,
+    receiver: mpsc::Receiver<(String, Health)>,
+}
+
+impl Default for HealthChecks {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            results: HashMap::new(),
+            sender,
+            receiver,
+        }
+    }
+}
+
+/// URL checked for a profile: its proxy target, base URL or Anthropic. Bedrock and
+/// the mock upstream have nothing to reach. A local model server is asked for its
+/// models, so one that is still loading (HTTP 503) shows as down.
+pub fn upstream_url(profile: &Profile) -> Option<String> {
+    if matches!(profile.provider(), Provider::Bedrock | Provider::Mock) {
+        return None;
+    }
+    let base_url = profile
+        .env
+        .get(ENV_BASE_URL)
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty());
+    let url = profile
+        .proxy_target_url()
+        .or(base_url)
+        .unwrap_or(ANTHROPIC_API_URL);
+    if let Some(backend) = LocalBackend::detect(url) {
+        return backend.models_url(url).ok().map(String::from);
+    }
+    url::Url::parse(url).is_ok().then(|| url.to_string())
+}
+
+/// Whether the upstream answers at all; credentials are not sent
+fn check_url(url: &str) -> Health {
+    let client = match reqwest::blocking::Client::builder()
+        .timeout(CHECK_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return Health::Down(e.to_string()),
+    };
+    let started = Instant::now();
+    match client.get(url).send() {
+        Ok(response) if response.status().is_server_error() => {
+            Health::Down(format!("HTTP {}", response.status()))
+        }
+        Ok(_) => Health::Up {
+            latency: started.elapsed(),
+        },
+        Err(e) if e.is_timeout() => Health::Down("timed out".to_string()),
+        Err(e) if e.is_connect() => Health::Down("connection failed".to_string()),
+        Err(e) => Health::Down(e.to_string()),
+    }
+}
+
+impl HealthChecks {
+    /// Check the profiles' upstreams in the background: those without a fresh
+    /// result, or all of them when `force` is set. Returns how many were started.
+    pub fn refresh<'a>(
+        &mut self,
+        profiles: impl IntoIterator<Item = &'a Profile>,
+        force: bool,
+    ) -> usize {
+        self.refresh_with(profiles, force, check_url)
+    }
+
+    fn refresh_with<'a>(
+        &mut self,
+        profiles: impl IntoIterator<Item = &'a Profile>,
+        force: bool,
+        check: Check,
+    ) -> usize {
+        let mut urls: Vec<String> = profiles.into_iter().filter_map(upstream_url).collect();
+        urls.sort();
+        urls.dedup();
+        urls.retain(|url| match self.results.get(url) {
+            Some(result) if result.health == Health::Checking => false,
+            Some(result) => force || result.checked_at.elapsed() >= RESULT_TTL,
+            None => true,
+        });
+
+        let now = Instant::now();
+        for url in &urls {
+            self.results.insert(
+                url.clone(),
+                HealthResult {
+                    health: Health::Checking,
+                    checked_at: now,
+                },
+            );
+        }
+        let count = urls.len();
+        let queue = Arc::new(Mutex::new(urls));
+        for _ in 0..count.min(MAX_PARALLEL_CHECKS) {
+            let queue = queue.clone();
+            let sender = self.sender.clone();
+            std::thread::spawn(move || {
+                while let Some(url) = queue.lock().ok().and_then(|mut urls| urls.pop()) {
+                    let health = check(&url);
+                    if sender.send((url, health)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        count
+    }
+
+    /// Take in finished checks; true when any arrived
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok((url, health)) = self.receiver.try_recv() {
+            if let Some(result) = self.results.get_mut(&url) {
+                result.health = health;
+                result.checked_at = Instant::now();
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    pub fn status(&self, profile: &Profile) -> Option<&HealthResult> {
+        self.results.get(&upstream_url(profile)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ENV_PROXY_TARGET_URL, EnvFilter, HeaderPolicy};
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RUNNING: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    fn slow_check(url: &str) -> Health {
+        let running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+        PEAK.fetch_max(running, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(20));
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+        if url.contains("down") {
+            Health::Down("connection failed".to_string())
+        } else {
+            Health::Up {
+                latency: Duration::from_millis(20),
+            }
+        }
+    }
+
+    fn profile(url: &str) -> Profile {
+        Profile {
+            name: url.to_string(),
+            description: String::new(),
+            notes: String::new(),
+            tags: Vec::new(),
+            key_expires_on: None,
+            proxy_port: None,
+            proxy_bind: None,
+            headers: BTreeMap::new(),
+            routes: BTreeMap::new(),
+            mcp: BTreeMap::new(),
+            fanout: Vec::new(),
+            model_map: BTreeMap::new(),
+            header_policy: HeaderPolicy::default(),
+            inherit_env: EnvFilter::default(),
+            env: HashMap::from([(ENV_PROXY_TARGET_URL.to_string(), url.to_string())]),
+        }
+    }
+
+    #[test]
+    fn checks_run_in_parallel_and_are_cached() {
+        let mut profiles: Vec<Profile> = (0..10)
+            .map(|i| profile(&format!("http://up-{}.example", i)))
+            .collect();
+        profiles.push(profile("http://down.example"));
+        // Sharing an upstream shares the check
+        profiles.push(profile("http://up-0.example"));
+
+        let mut checks = HealthChecks::default();
+        assert_eq!(checks.refresh_with(&profiles, false, slow_check), 11);
+        assert_eq!(
+            checks.status(&profiles[0]).unwrap().health,
+            Health::Checking
+        );
+        // Nothing new is started while checks are running
+        assert_eq!(checks.refresh_with(&profiles, true, slow_check), 0);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while checks
+            .results
+            .values()
+            .any(|r| r.health == Health::Checking)
+        {
+            assert!(Instant::now() < deadline, "checks did not finish");
+            checks.poll();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(PEAK.load(Ordering::SeqCst) <= MAX_PARALLEL_CHECKS);
+        assert!(matches!(
+            checks.status(&profiles[11]).unwrap().health,
+            Health::Up { .. }
+        ));
+        assert_eq!(
+            checks.status(&profiles[10]).unwrap().health,
+            Health::Down("connection failed".to_string())
+        );
+
+        assert_eq!(checks.refresh_with(&profiles, false, slow_check), 0);
+        assert_eq!(checks.refresh_with(&profiles[..1], true, slow_check), 1);
+    }
+}
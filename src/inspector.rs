@@ -0,0 +1,134 @@
+//! Request inspector: captured proxy requests with their bodies on both sides of the
+//! translation, and replays of them against the profile's current upstream.
+//!
+//! A replay builds the profile's proxy afresh on a worker thread, so the TUI keeps
+//! drawing while a slow upstream answers; the result arrives over a channel.
+
+use std::sync::mpsc;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+
+use crate::config::{Config, ENV_AUTH_TOKEN};
+use crate::launcher;
+use crate::proxy::{self, Capture};
+
+/// Captures loaded for the inspector and the replay in progress, if any
+pub struct Inspector {
+    /// Newest first
+    pub captures: Vec<Capture>,
+    pub selected: usize,
+    /// First body line shown
+    pub scroll: u16,
+    /// Outcome of the last replay, or what is being replayed
+    pub status: Option<String>,
+    replaying: bool,
+    sender: mpsc::Sender<String>,
+    receiver: mpsc::Receiver<String>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            captures: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            status: None,
+            replaying: false,
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl Inspector {
+    /// Read the captures again, keeping the selection on the same capture
+    pub fn reload(&mut self) {
+        let selected = self.selected().map(|capture| capture.id.clone());
+        self.captures = proxy::load_captures();
+        self.selected = selected
+            .and_then(|id| self.captures.iter().position(|c| c.id == id))
+            .unwrap_or(0);
+    }
+
+    pub fn selected(&self) -> Option<&Capture> {
+        self.captures.get(self.selected)
+    }
+
+    /// Move the selection, never past either end
+    pub fn select(&mut self, delta: i64) {
+        let max = self.captures.len().saturating_sub(1) as i64;
+        self.selected = (self.selected as i64 + delta).clamp(0, max) as usize;
+        self.scroll = 0;
+    }
+
+    pub fn scroll(&mut self, delta: i32) {
+        self.scroll = (i32::from(self.scroll) + delta).clamp(0, i32::from(u16::MAX)) as u16;
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replaying
+    }
+
+    /// Send the selected capture again through its profile's proxy settings
+    pub fn replay(&mut self, config: &Config) -> Result<()> {
+        if self.replaying {
+            anyhow::bail!("A replay is already running");
+        }
+        let capture = self.selected().cloned().context("No capture selected")?;
+        let profile = config
+            .profiles
+            .iter()
+            .find(|p| p.name == capture.profile)
+            .cloned()
+            .with_context(|| format!("Profile '{}' no longer exists", capture.profile))?;
+
+        self.replaying = true;
+        self.status = Some(format!("Replaying through '{}'...", profile.name));
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            let result = (|| -> Result<proxy::Replayed> {
+                let prepared = launcher::prepare_profile(&profile)?;
+                let (target, options) = prepared.proxy.with_context(|| {
+                    format!("Profile '{}' no longer uses the proxy", profile.name)
+                })?;
+                let token = prepared.env.get(ENV_AUTH_TOKEN).cloned();
+                tokio::runtime::Runtime::new()?.block_on(proxy::replay(
+                    target,
+                    options,
+                    &capture,
+                    token.as_deref(),
+                ))
+            })();
+            let status = match result {
+                Ok(replayed) if (200..300).contains(&replayed.status) => format!(
+                    "Replay: HTTP {} in {:.1}s",
+                    replayed.status,
+                    started.elapsed().as_secs_f64()
+                ),
+                Ok(replayed) => format!(
+                    "Replay: HTTP {}: {}",
+                    replayed.status,
+                    replayed.body.lines().next().unwrap_or_default()
+                ),
+                Err(e) => format!("Replay failed: {:#}", e),
+            };
+            let _ = sender.send(status);
+        });
+        Ok(())
+    }
+
+    /// Pick up a finished replay and select its capture, the newest
+    pub fn poll(&mut self) {
+        let Ok(status) = self.receiver.try_recv() else {
+            return;
+        };
+        self.replaying = false;
+        self.status = Some(status);
+        self.captures = proxy::load_captures();
+        self.selected = 0;
+        self.scroll = 0;
+    }
+}
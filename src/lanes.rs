@@ -0,0 +1,197 @@
+//! Concurrency limiter with priority lanes for proxied requests.
+//!
+//! Local models serve only a few requests at a time. When a profile sets
+//! `PROXY_MAX_CONCURRENCY`, requests beyond the limit wait in one of two queues and a
+//! freed slot always goes to the interactive queue first, so the user's own turns are
+//! not stuck behind sub-agents and auxiliary calls.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::oneshot;
+
+/// Which queue a request waits in when the limiter is saturated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// The main conversation the user is typing into
+    Interactive,
+    /// Sub-agents, titles, suggestions and other work nobody is waiting on
+    Background,
+}
+
+impl Lane {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Lane::Interactive => "interactive",
+            Lane::Background => "background",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    active: usize,
+    interactive: VecDeque<oneshot::Sender<()>>,
+    background: VecDeque<oneshot::Sender<()>>,
+}
+
+/// Caps in-flight upstream requests, admitting interactive waiters first
+#[derive(Debug)]
+pub struct PriorityLimiter {
+    max: usize,
+    state: Mutex<LimiterState>,
+}
+
+impl PriorityLimiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    /// Wait for a slot. Returns the permit and whether the request had to queue.
+    pub async fn acquire(self: &Arc<Self>, lane: Lane) -> (LanePermit, bool) {
+        let rx = {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if state.active < self.max {
+                state.active += 1;
+                return (self.permit(), false);
+            }
+            let (tx, rx) = oneshot::channel();
+            match lane {
+                Lane::Interactive => state.interactive.push_back(tx),
+                Lane::Background => state.background.push_back(tx),
+            }
+            rx
+        };
+
+        let mut waiter = Waiter {
+            limiter: self,
+            rx,
+            admitted: false,
+        };
+        // The releasing request hands its slot over rather than freeing it
+        let _ = (&mut waiter.rx).await;
+        waiter.admitted = true;
+        (self.permit(), true)
+    }
+
+    fn permit(self: &Arc<Self>) -> LanePermit {
+        LanePermit {
+            limiter: self.clone(),
+        }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            let next = match state.interactive.pop_front() {
+                Some(tx) => Some(tx),
+                None => state.background.pop_front(),
+            };
+            let Some(tx) = next else {
+                break;
+            };
+            // A cancelled waiter dropped its receiver; try the next one
+            if tx.send(()).is_ok() {
+                return;
+            }
+        }
+        state.active = state.active.saturating_sub(1);
+    }
+
+    #[cfg(test)]
+    fn queued(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        (state.interactive.len(), state.background.len())
+    }
+}
+
+/// A queued request. If it is cancelled after being handed a slot but before
+/// taking it, the slot is passed on instead of leaking.
+struct Waiter<'a> {
+    limiter: &'a PriorityLimiter,
+    rx: oneshot::Receiver<()>,
+    admitted: bool,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if !self.admitted && self.rx.try_recv().is_ok() {
+            self.limiter.release();
+        }
+    }
+}
+
+/// Holds a slot until dropped (after the response body has been fully sent)
+#[derive(Debug)]
+pub struct LanePermit {
+    limiter: Arc<PriorityLimiter>,
+}
+
+impl Drop for LanePermit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn interactive_waiters_are_admitted_first() {
+        let limiter = Arc::new(PriorityLimiter::new(1));
+        let (running, queued) = limiter.acquire(Lane::Background).await;
+        assert!(!queued);
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let spawn = |lane: Lane| {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            tokio::spawn(async move {
+                let (_permit, queued) = limiter.acquire(lane).await;
+                assert!(queued);
+                order.lock().unwrap().push(lane);
+            })
+        };
+        let background = spawn(Lane::Background);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let interactive = spawn(Lane::Interactive);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(limiter.queued(), (1, 1));
+
+        drop(running);
+        interactive.await.unwrap();
+        background.await.unwrap();
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![Lane::Interactive, Lane::Background]
+        );
+        let (_permit, queued) = limiter.acquire(Lane::Background).await;
+        assert!(!queued);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiter_does_not_leak_its_slot() {
+        let limiter = Arc::new(PriorityLimiter::new(1));
+        let (running, _) = limiter.acquire(Lane::Interactive).await;
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move {
+                let _ = limiter.acquire(Lane::Interactive).await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        waiting.abort();
+        let _ = waiting.await;
+
+        drop(running);
+        let acquired =
+            tokio::time::timeout(Duration::from_secs(1), limiter.acquire(Lane::Background));
+        assert!(acquired.await.is_ok());
+    }
+}
@@ -1,128 +1,700 @@
 use std::collections::HashMap;
 use std::io::{self, Write};
 use std::process::Command;
+use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
+use crate::auth::{AuthProvider, Credentials};
 use crate::config::{
-    ENV_AUTH_TOKEN, ENV_BASE_URL, ENV_MODEL, ENV_OPENAI_OAUTH, ENV_PROXY_TARGET_URL,
-    ENV_SMALL_FAST_MODEL, Profile,
+    Config, ENV_AUTH_TOKEN, ENV_BASE_URL, ENV_CODEX_PACING, ENV_CONSENSUS_MODEL,
+    ENV_CUSTOM_HEADERS, ENV_IDLE_UNLOAD_SECS, ENV_MAX_CONCURRENCY, ENV_MOCK_SCRIPT, ENV_MODEL,
+    ENV_OAUTH_TOKEN, ENV_PASSTHROUGH, ENV_PREVIOUS_RESPONSE_ID, ENV_PROXY_TARGET_URL,
+    ENV_READY_WAIT_SECS, ENV_REQUEST_CAPTURE, ENV_REQUEST_LOG, ENV_SMALL_FAST_MODEL,
+    ENV_STRICT_TOOLS, KEYCHAIN_PREFIX, ModelRoute, PROFILER_ONLY_ENV_KEYS, Profile, env_reference,
+    keychain_reference,
 };
-use crate::openai_oauth;
-use crate::proxy;
+use crate::control::SessionState;
+use crate::daemon;
+use crate::display::Spinner;
+use crate::llama_server::{self, LlamaServer, LlamaServerOptions};
+use crate::local_models::{LocalBackend, LocalServer};
+use crate::openai_oauth::is_truthy;
+use crate::platform;
+use crate::pricing::{self, UsageTracker};
+use crate::project::ProjectContext;
+use crate::proxy::{self, Shutdown};
+use crate::redact::is_sensitive_key;
+use crate::secrets::{Keychain, SecretStore, resolve_keychain_references};
+use crate::signals::SessionSignals;
+use crate::usage;
 
-/// Spinner characters for visual feedback
-const SPINNER_CHARS: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+/// Upstream for passthrough profiles that set no base URL
+const ANTHROPIC_API_URL: &str = "https://api.anthropic.com";
 
 /// Timeout for proxy startup
 const PROXY_STARTUP_TIMEOUT_SECS: u64 = 10;
 
+/// How often to check on the Claude Code child and pending signals
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Get non-empty env var value from a map
 fn get_non_empty_env(map: &HashMap<String, String>, key: &str) -> Option<String> {
     map.get(key).cloned().filter(|v| !v.trim().is_empty())
 }
 
-/// Launch Claude Code with the specified profile's environment variables.
-/// We spawn a child process to run Claude, then unload models after it exits.
-pub fn exec_claude(profile: &Profile) -> Result<()> {
+/// A profile's environment with credentials resolved, plus the proxy settings
+/// when it routes through the built-in proxy
+pub struct PreparedProfile {
+    pub env: HashMap<String, String>,
+    /// Upstream target and options for the proxy
+    pub proxy: Option<(String, proxy::ProxyOptions)>,
+    pub usage: Arc<UsageTracker>,
+}
+
+/// Resolve credentials, headers and proxy options for a profile
+pub fn prepare_profile(profile: &Profile) -> Result<PreparedProfile> {
+    prepare_profile_with(profile, None)
+}
+
+/// `prepare_profile`, reusing `signed_in` (a provider and the credentials it gave)
+/// instead of starting another interactive sign-in
+fn prepare_profile_with(
+    profile: &Profile,
+    signed_in: Option<&(AuthProvider, Credentials)>,
+) -> Result<PreparedProfile> {
     let mut resolved_env = profile.env.clone();
+    for (key, value) in resolved_env.iter_mut() {
+        if let Some(name) = env_reference(value).map(str::to_string) {
+            *value = std::env::var(&name)
+                .with_context(|| format!("{} refers to ${{{}}}, which is not set", key, name))?;
+        }
+    }
+    resolve_keychain_references(&mut resolved_env, &Keychain)?;
 
-    if openai_oauth::openai_oauth_enabled(resolved_env.get(ENV_OPENAI_OAUTH)) {
-        let rt = tokio::runtime::Runtime::new()?;
-        let access_token = rt.block_on(openai_oauth::ensure_access_token_interactive())?;
-        resolved_env.insert(ENV_AUTH_TOKEN.to_string(), access_token);
+    let auth_provider = AuthProvider::for_env(&resolved_env)?;
+    let credentials = match signed_in {
+        _ if auth_provider == AuthProvider::StaticKey => {
+            auth_provider.resolve_static(&resolved_env)
+        }
+        Some((provider, credentials)) if auth_provider.is_interactive() => {
+            if *provider != auth_provider {
+                anyhow::bail!(
+                    "The profile now signs in with {:?}, which needs a new session; restart it",
+                    auth_provider
+                );
+            }
+            credentials.clone()
+        }
+        _ => {
+            let rt = tokio::runtime::Runtime::new()?;
+            rt.block_on(auth_provider.resolve(&resolved_env))?
+        }
+    };
+    if let Some(token) = credentials.bearer_token() {
+        // Claude Code sends subscription tokens with the OAuth beta header itself
+        let key = if auth_provider == AuthProvider::AnthropicOAuth {
+            ENV_OAUTH_TOKEN
+        } else {
+            ENV_AUTH_TOKEN
+        };
+        resolved_env.insert(key.to_string(), token.to_string());
     }
 
-    let proxy_target_url = get_non_empty_env(&resolved_env, ENV_PROXY_TARGET_URL);
-    let needs_proxy = proxy_target_url.is_some();
+    let existing_headers = resolved_env
+        .get(ENV_CUSTOM_HEADERS)
+        .cloned()
+        .or_else(|| std::env::var(ENV_CUSTOM_HEADERS).ok());
+    if let Some(headers) = profile.custom_headers_env(existing_headers.as_deref()) {
+        resolved_env.insert(ENV_CUSTOM_HEADERS.to_string(), headers);
+    }
 
-    // Shutdown channel for graceful proxy termination
-    let mut shutdown_tx: Option<tokio::sync::oneshot::Sender<()>> = None;
-
-    if let Some(proxy_target_url) = proxy_target_url {
-        let model_override = get_non_empty_env(&resolved_env, ENV_MODEL);
-        let auxiliary_model = get_non_empty_env(&resolved_env, ENV_SMALL_FAST_MODEL);
-
-        // Create shutdown channel
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        shutdown_tx = Some(tx);
-
-        // Start proxy in a background thread with shutdown support
-        std::thread::spawn(move || {
-            let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-            rt.block_on(async {
-                if let Err(e) =
-                    proxy::start_server(proxy_target_url, model_override, auxiliary_model, Some(rx)).await
-                {
-                    eprintln!("Proxy error: {}", e);
-                }
-            });
+    let usage = Arc::new(UsageTracker::default());
+    // Passthrough without a target routes the profile's own Anthropic endpoint, unless
+    // that already points at the proxy
+    let listen = proxy::ListenAddr::new(profile.proxy_bind.as_deref(), profile.proxy_port);
+    let passthrough =
+        get_non_empty_env(&resolved_env, ENV_PASSTHROUGH).is_some_and(|v| is_truthy(&v));
+    let proxy_target_url = get_non_empty_env(&resolved_env, ENV_PROXY_TARGET_URL).or_else(|| {
+        passthrough.then(|| {
+            get_non_empty_env(&resolved_env, ENV_BASE_URL)
+                .filter(|url| url.trim_end_matches('/') != listen.anthropic_url())
+                .unwrap_or_else(|| ANTHROPIC_API_URL.to_string())
+        })
+    });
+    let Some(proxy_target_url) = proxy_target_url else {
+        return Ok(PreparedProfile {
+            env: resolved_env,
+            proxy: None,
+            usage,
         });
+    };
+
+    // The mock upstream needs no key, but Claude Code still expects one
+    if proxy::is_mock_url(&proxy_target_url) {
+        resolved_env
+            .entry(ENV_AUTH_TOKEN.to_string())
+            .or_insert_with(|| "mock".to_string());
+    }
+    let mock_script = match get_non_empty_env(&resolved_env, ENV_MOCK_SCRIPT) {
+        Some(path) => proxy::load_mock_script(&path)?,
+        None => Vec::new(),
+    };
+
+    let options = proxy::ProxyOptions {
+        listen,
+        model_map: profile
+            .model_map
+            .iter()
+            .filter(|(_, model)| !model.trim().is_empty())
+            .map(|(pattern, model)| (pattern.clone(), model.trim().to_string()))
+            .collect(),
+        model_override: get_non_empty_env(&resolved_env, ENV_MODEL),
+        auxiliary_model: get_non_empty_env(&resolved_env, ENV_SMALL_FAST_MODEL),
+        credentials,
+        auth_provider,
+        usage: usage.clone(),
+        token_limits: proxy::TokenLimits::from_env(&resolved_env),
+        sampling: proxy::SamplingOverrides::from_env(&resolved_env),
+        thinking_hint: proxy::ThinkingHint::from_env(&resolved_env),
+        reasoning_summary: proxy::ReasoningSummary::from_env(&resolved_env),
+        idle_unload: get_non_empty_env(&resolved_env, ENV_IDLE_UNLOAD_SECS)
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs),
+        ready_wait: get_non_empty_env(&resolved_env, ENV_READY_WAIT_SECS)
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs),
+        chain_responses: get_non_empty_env(&resolved_env, ENV_PREVIOUS_RESPONSE_ID)
+            .is_some_and(|v| is_truthy(&v)),
+        strict_tools: get_non_empty_env(&resolved_env, ENV_STRICT_TOOLS)
+            .is_some_and(|v| is_truthy(&v)),
+        tool_result_limit: proxy::tool_result_limit(&resolved_env),
+        compaction: proxy::Compaction::from_env(&resolved_env),
+        sse: proxy::SseOptions::from_env(&resolved_env),
+        retry: proxy::RetryPolicy::from_env(&resolved_env),
+        headers: profile.headers.clone(),
+        header_policy: profile.header_policy.clone(),
+        endpoints: proxy::EndpointOverrides::from_env(&resolved_env),
+        mock_script,
+        max_concurrency: get_non_empty_env(&resolved_env, ENV_MAX_CONCURRENCY)
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .filter(|max| *max > 0),
+        models: proxy::ListedModel::from_env(&resolved_env),
+        request_log: get_non_empty_env(&resolved_env, ENV_REQUEST_LOG)
+            .filter(|v| is_truthy(v))
+            .and_then(|_| proxy::RequestLog::new(&profile.name)),
+        capture: get_non_empty_env(&resolved_env, ENV_REQUEST_CAPTURE)
+            .filter(|v| is_truthy(v))
+            .and_then(|_| proxy::CaptureLog::new(&profile.name)),
+        codex_pacing: get_non_empty_env(&resolved_env, ENV_CODEX_PACING)
+            .is_none_or(|v| is_truthy(&v)),
+        consensus_model: get_non_empty_env(&resolved_env, ENV_CONSENSUS_MODEL),
+        passthrough,
+        routes: profile
+            .routes
+            .iter()
+            .map(|(pattern, route)| resolve_route(pattern, route))
+            .collect::<Result<_>>()?,
+        fanout: profile
+            .fanout
+            .iter()
+            .map(resolve_fanout)
+            .collect::<Result<_>>()?,
+        reload: None,
+        http_backend: None,
+        token_refresher: None,
+    };
+
+    Ok(PreparedProfile {
+        env: resolved_env,
+        proxy: Some((proxy_target_url, options)),
+        usage,
+    })
+}
+
+/// Prepares the profile's proxy settings again from `profiles.toml`, for the session
+/// proxy's `/admin/reload`. An OAuth sign-in is never started again: the session's
+/// is reused, and a profile switched to another OAuth provider is refused.
+fn proxy_reloader(name: &str, signed_in: (AuthProvider, Credentials)) -> proxy::ProxyReloader {
+    let name = name.to_string();
+    Arc::new(move || {
+        let config = Config::load()?;
+        let profile = config
+            .profiles
+            .iter()
+            .find(|p| p.name == name)
+            .with_context(|| format!("No profile named '{}' in profiles.toml", name))?;
+        prepare_profile_with(profile, Some(&signed_in))?
+            .proxy
+            .with_context(|| format!("'{}' no longer uses the proxy", name))
+    })
+}
+
+/// A value with its `${VAR}` or `keychain:` reference resolved; `what` names it in errors
+fn resolve_reference(what: &str, value: &str) -> Result<String> {
+    if let Some(name) = env_reference(value) {
+        std::env::var(name)
+            .with_context(|| format!("{} refers to ${{{}}}, which is not set", what, name))
+    } else if let Some(entry) = keychain_reference(value) {
+        Keychain
+            .get(entry)
+            .with_context(|| format!("{} refers to {}{}", what, KEYCHAIN_PREFIX, entry))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Credentials from a route's optional key, `${VAR}` or `keychain:` references resolved
+fn resolve_route_key(what: &str, route: &ModelRoute) -> Result<Credentials> {
+    Ok(match route.api_key.as_deref().map(str::trim) {
+        None | Some("") => Credentials::None,
+        Some(key) => Credentials::Bearer(resolve_reference(what, key)?),
+    })
+}
+
+/// A profile route with its key resolved
+fn resolve_route(pattern: &str, route: &ModelRoute) -> Result<proxy::RouteOptions> {
+    Ok(proxy::RouteOptions {
+        pattern: pattern.to_string(),
+        target_url: route.url.trim().to_string(),
+        model: route.model.clone().filter(|model| !model.trim().is_empty()),
+        credentials: resolve_route_key(&format!("Route {}", pattern), route)?,
+    })
+}
+
+/// A fan-out upstream with its key resolved
+fn resolve_fanout(upstream: &ModelRoute) -> Result<proxy::FanoutUpstream> {
+    let target_url = upstream.url.trim().to_string();
+    Ok(proxy::FanoutUpstream {
+        credentials: resolve_route_key(&format!("Fan-out {}", target_url), upstream)?,
+        model: upstream
+            .model
+            .clone()
+            .filter(|model| !model.trim().is_empty()),
+        target_url,
+    })
+}
+
+/// The profile's MCP servers, written for Claude Code's `--mcp-config` for the length
+/// of a session. The file holds resolved secrets, so it is private and removed after.
+struct McpConfigFile {
+    path: std::path::PathBuf,
+}
+
+impl McpConfigFile {
+    fn write(profile: &Profile) -> Result<Option<Self>> {
+        if profile.mcp.is_empty() {
+            return Ok(None);
+        }
+        let servers = profile
+            .mcp
+            .iter()
+            .map(|(name, server)| {
+                Ok((name.clone(), server.claude_config(name, resolve_reference)?))
+            })
+            .collect::<Result<serde_json::Map<_, _>>>()?;
+        let path = Config::config_dir()
+            .context("Could not determine config directory")?
+            .join(format!("mcp-{}.json", std::process::id()));
+        let config = serde_json::json!({ "mcpServers": servers });
+        platform::write_private(&path, serde_json::to_string_pretty(&config)?.as_bytes())?;
+        Ok(Some(Self { path }))
+    }
+}
+
+impl Drop for McpConfigFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Inherited variables a profile's `inherit_env` filter keeps from Claude Code
+pub struct EnvScrub {
+    pub withheld: Vec<String>,
+    /// Credential-looking variables the filter still lets through
+    pub exposed: Vec<String>,
+}
+
+/// Apply `profile.inherit_env` to the launcher's environment. Variables the profile
+/// sets itself are not inherited, so they are never withheld or reported.
+pub fn scrub_env(profile: &Profile, inherited: impl Iterator<Item = String>) -> EnvScrub {
+    let mut scrub = EnvScrub {
+        withheld: Vec::new(),
+        exposed: Vec::new(),
+    };
+    for name in inherited.filter(|name| !profile.env.contains_key(name)) {
+        if !profile.inherit_env.allows(&name) {
+            scrub.withheld.push(name);
+        } else if is_sensitive_key(&name) {
+            scrub.exposed.push(name);
+        }
+    }
+    scrub.withheld.sort();
+    scrub.exposed.sort();
+    scrub
+}
+
+/// Pre-launch summary of what the environment filter did
+fn print_env_scrub(scrub: &EnvScrub) {
+    if scrub.withheld.is_empty() {
+        println!("Environment: no inherited variables withheld");
+    } else {
+        println!(
+            "Environment: withholding {} inherited variable(s): {}",
+            scrub.withheld.len(),
+            scrub.withheld.join(", ")
+        );
+    }
+    if !scrub.exposed.is_empty() {
+        println!(
+            "Environment: still passing credential-like {}",
+            scrub.exposed.join(", ")
+        );
+    }
+}
+
+/// Claude Code was stopped by a signal sent to the launcher; the session has been
+/// cleaned up and the launcher should exit instead of returning to the list
+#[derive(Debug)]
+pub struct Terminated(pub &'static str);
+
+impl std::fmt::Display for Terminated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Session terminated by {}", self.0)
+    }
+}
+
+impl std::error::Error for Terminated {}
+
+/// A proxy running on its background thread
+pub struct ProxyHandle {
+    shutdown: tokio::sync::oneshot::Sender<Shutdown>,
+    thread: std::thread::JoinHandle<()>,
+    listen: proxy::ListenAddr,
+}
 
-        // Wait for proxy to be ready
-        print!("Starting proxy ");
-        io::stdout().flush()?;
-
-        let timeout = Duration::from_secs(PROXY_STARTUP_TIMEOUT_SECS);
-        let start = std::time::Instant::now();
-        let mut spinner_idx = 0;
-
-        let client = reqwest::blocking::Client::builder()
-            .timeout(Duration::from_millis(500))
-            .build()
-            .expect("Failed to build HTTP client");
-        let health_url = format!("http://localhost:{}/health", proxy::PROXY_PORT);
-
-        while start.elapsed() < timeout {
-            if let Ok(resp) = client.get(&health_url).send()
-                && resp.status().is_success()
-            {
-                println!("\r{} Proxy started!        ", SPINNER_CHARS[spinner_idx]);
-                break;
+impl ProxyHandle {
+    /// `ANTHROPIC_BASE_URL` pointing Claude Code at this proxy
+    pub fn anthropic_url(&self) -> String {
+        self.listen.anthropic_url()
+    }
+
+    /// Shut the proxy down and wait for its exit cleanup to finish
+    pub fn stop(self, kind: Shutdown) {
+        let _ = self.shutdown.send(kind);
+        let _ = self.thread.join();
+    }
+}
+
+/// Run the proxy on a background thread and wait until it answers health checks
+pub fn start_proxy(proxy_target_url: String, options: proxy::ProxyOptions) -> Result<ProxyHandle> {
+    // Fail early with a useful message instead of health-checking another session's proxy
+    let listen = options.listen.clone();
+    drop(std::net::TcpListener::bind(listen.socket()).with_context(|| {
+        format!(
+            "Cannot listen on {}; is another proxied session using it? Set proxy_port in the profile",
+            listen.socket()
+        )
+    })?);
+
+    // Shutdown channel for graceful proxy termination
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    // Start proxy in a background thread with shutdown support
+    let thread = std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+        rt.block_on(async {
+            if let Err(e) = proxy::start_server(proxy_target_url, options, Some(rx)).await {
+                eprintln!("Proxy error: {}", e);
             }
+        });
+    });
+
+    // Wait for proxy to be ready
+    print!("Starting proxy ");
+    io::stdout().flush()?;
+
+    let timeout = Duration::from_secs(PROXY_STARTUP_TIMEOUT_SECS);
+    let start = std::time::Instant::now();
+    let mut spinner = Spinner::default();
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .build()
+        .expect("Failed to build HTTP client");
+    let health_url = format!("{}/health", listen.base_url());
+
+    while start.elapsed() < timeout {
+        if let Ok(resp) = client.get(&health_url).send()
+            && resp.status().is_success()
+        {
+            println!("\r{} Proxy started!        ", spinner.current());
+            return Ok(ProxyHandle {
+                shutdown: tx,
+                thread,
+                listen,
+            });
+        }
 
-            print!("\r{} Starting proxy...", SPINNER_CHARS[spinner_idx]);
+        if let Some(frame) = spinner.tick() {
+            print!("\r{} Starting proxy...", frame);
             io::stdout().flush()?;
-            spinner_idx = (spinner_idx + 1) % SPINNER_CHARS.len();
-            std::thread::sleep(Duration::from_millis(100));
         }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    println!();
+    // Signal shutdown before bailing
+    let _ = tx.send(Shutdown::Normal);
+    anyhow::bail!(
+        "Proxy did not start within {} seconds",
+        PROXY_STARTUP_TIMEOUT_SECS
+    );
+}
+
+/// Start the Ollama or LM Studio server of `target_url` when nothing answers there,
+/// and pull `model` onto it when it is not installed
+fn prepare_local_server(target_url: &str, model: Option<&str>) -> Result<Option<LocalServer>> {
+    let Some(backend) = LocalBackend::detect(target_url).filter(LocalBackend::can_pull) else {
+        return Ok(None);
+    };
+    let server = backend.start_server(target_url)?;
+    if server.is_some() {
+        println!("Started {}", backend.label());
+    }
+    let Some(model) = model else {
+        return Ok(server);
+    };
+    // Unreachable servers are left for the proxy's own errors to explain
+    let Ok(installed) = backend.list_models(target_url) else {
+        return Ok(server);
+    };
+    // Ollama lists a bare name with its default tag
+    if installed
+        .iter()
+        .any(|name| name == model || name.strip_suffix(":latest") == Some(model))
+    {
+        return Ok(server);
+    }
+
+    let mut spinner = Spinner::default();
+    backend
+        .pull_model(target_url, model, |status| {
+            // Redrawn for every update, so the percentage moves without animation too
+            let frame = spinner.tick().unwrap_or_else(|| spinner.current());
+            let percent = status
+                .percent()
+                .map(|percent| format!(" {}%", percent))
+                .unwrap_or_default();
+            print!(
+                "\r{} Pulling {}: {}{}        ",
+                frame, model, status.status, percent
+            );
+            let _ = io::stdout().flush();
+        })
+        .with_context(|| format!("Failed to pull {} into {}", model, backend.label()))?;
+    println!("\r{} Pulled {}        ", spinner.current(), model);
+    Ok(server)
+}
+
+/// The Claude Code executable: `claude` on the PATH (`claude.cmd` or `claude.exe` on
+/// Windows), else where the native installer or npm puts it
+fn claude_program() -> std::path::PathBuf {
+    let exe = format!("claude{}", std::env::consts::EXE_SUFFIX);
+    let mut fallbacks: Vec<_> = dirs::home_dir()
+        .map(|home| home.join(".local").join("bin").join(&exe))
+        .into_iter()
+        .collect();
+    if cfg!(windows)
+        && let Some(appdata) = std::env::var_os("APPDATA")
+    {
+        fallbacks.push(
+            std::path::PathBuf::from(appdata)
+                .join("npm")
+                .join("claude.cmd"),
+        );
+    }
+    platform::program("claude", &fallbacks)
+}
+
+/// Launch Claude Code with the specified profile's environment variables.
+/// We spawn a child process to run Claude and shut the proxy down after it exits;
+/// SIGTERM/SIGHUP are forwarded to it and end the launcher too (see [`Terminated`]).
+/// A `stop` on the control socket ends the session but keeps the launcher running.
+pub fn exec_claude(profile: &Profile, control: Option<&SessionState>) -> Result<()> {
+    let PreparedProfile {
+        env: resolved_env,
+        proxy: proxy_settings,
+        usage,
+    } = prepare_profile(profile)?;
 
-        if start.elapsed() >= timeout {
-            println!();
-            // Signal shutdown before bailing
-            if let Some(tx) = shutdown_tx.take() {
-                let _ = tx.send(());
+    let mcp_config = McpConfigFile::write(profile)?;
+    if !profile.mcp.is_empty() {
+        let names: Vec<&str> = profile.mcp.keys().map(String::as_str).collect();
+        println!("MCP servers: {}", names.join(", "));
+    }
+
+    let project = ProjectContext::current();
+    if let Some(project) = &project {
+        println!("Project: {}", project);
+    }
+
+    let scrub = (!profile.inherit_env.is_empty()).then(|| {
+        scrub_env(
+            profile,
+            std::env::vars_os().filter_map(|(k, _)| k.into_string().ok()),
+        )
+    });
+    if let Some(scrub) = &scrub {
+        print_env_scrub(scrub);
+    }
+
+    // llama.cpp profiles with a models directory get a server if none is running
+    let llama_server = match &proxy_settings {
+        Some((target, _)) if LocalBackend::detect(target) == Some(LocalBackend::LlamaCpp) => {
+            match (
+                LlamaServerOptions::from_env(&resolved_env),
+                llama_server::served_model(&resolved_env),
+            ) {
+                (Some(options), Some(model)) => LlamaServer::start(&options, model, target)?,
+                _ => None,
             }
-            anyhow::bail!("Proxy did not start within {} seconds", PROXY_STARTUP_TIMEOUT_SECS);
         }
-    }
+        _ => None,
+    };
+    // Ollama and LM Studio profiles get their server started and model pulled if needed
+    let local_server = match &proxy_settings {
+        Some((target, options)) => prepare_local_server(target, options.model_override.as_deref())?,
+        None => None,
+    };
 
-    let mut cmd = Command::new("claude");
+    // A running daemon that serves this profile takes the place of a session proxy
+    let daemon_url = proxy_settings
+        .as_ref()
+        .and_then(|_| daemon::serving(&profile.name));
+    let proxy_handle = match proxy_settings {
+        Some((proxy_target_url, mut options)) if daemon_url.is_none() => {
+            let signed_in = (options.auth_provider, options.credentials.clone());
+            options.reload = Some(proxy_reloader(&profile.name, signed_in));
+            Some(start_proxy(proxy_target_url, options)?)
+        }
+        _ => None,
+    };
+
+    let mut cmd = Command::new(claude_program());
+    if let Some(mcp_config) = &mcp_config {
+        cmd.arg("--mcp-config").arg(&mcp_config.path);
+    }
+    for name in scrub.iter().flat_map(|scrub| &scrub.withheld) {
+        cmd.env_remove(name);
+    }
 
     // Set all environment variables from the profile
     for (key, value) in &resolved_env {
-        if key == ENV_PROXY_TARGET_URL || key == ENV_OPENAI_OAUTH {
+        if PROFILER_ONLY_ENV_KEYS.contains(&key.as_str()) {
             continue;
         }
         cmd.env(key, value);
     }
 
-    if needs_proxy {
-        cmd.env(ENV_BASE_URL, proxy::PROXY_ANTHROPIC_URL);
+    if let Some(handle) = &proxy_handle {
+        cmd.env(ENV_BASE_URL, handle.anthropic_url());
+    } else if let Some(url) = &daemon_url {
+        println!("Using the claude-profiler daemon at {}", url);
+        cmd.env(ENV_BASE_URL, url);
     }
 
-    // Spawn and wait so we can unload after exit.
-    let status = cmd.status()?;
+    // Spawn and wait so we can clean up after exit, whatever ends the session
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            if let Some(handle) = proxy_handle {
+                handle.stop(Shutdown::Normal);
+            }
+            return Err(e.into());
+        }
+    };
+    let signals = SessionSignals::install();
+    if let Some(control) = control {
+        control.begin(&profile.name);
+    }
+    let mut terminated_by = None;
+    let mut stopped = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Some(signal) = signals.forward_pending(&child) {
+            terminated_by = Some(signal);
+        }
+        if control.is_some_and(SessionState::take_stop) {
+            signals.terminate(&child);
+            stopped = true;
+        }
+        std::thread::sleep(CHILD_POLL_INTERVAL);
+    };
+    drop(signals);
+    if let Some(control) = control {
+        control.end();
+    }
 
-    // Signal proxy to shut down gracefully after Claude exits
-    if let Some(tx) = shutdown_tx {
-        let _ = tx.send(());
+    // Shut the proxy down gracefully after Claude exits
+    if let Some(handle) = proxy_handle {
+        handle.stop(match terminated_by {
+            Some(_) => Shutdown::Interrupted,
+            None => Shutdown::Normal,
+        });
     }
+    drop(llama_server);
+    drop(local_server);
+
+    record_session(&profile.name, &usage, project);
 
-    if !status.success() {
+    if let Some(signal) = terminated_by {
+        return Err(Terminated(signal).into());
+    }
+    if !status.success() && !stopped {
         anyhow::bail!("Claude Code exited with status: {}", status);
     }
 
     Ok(())
 }
+
+/// Print estimated session cost per upstream model using the pricing catalog, and
+/// append the session to the usage history
+fn record_session(profile_name: &str, usage: &UsageTracker, project: Option<ProjectContext>) {
+    let rows = usage.snapshot();
+    let catalog = if rows.is_empty() {
+        pricing::PriceCatalog::default()
+    } else {
+        let catalog = tokio::runtime::Runtime::new()
+            .map_err(anyhow::Error::from)
+            .and_then(|rt| rt.block_on(pricing::load_catalog()))
+            .unwrap_or_else(|e| {
+                eprintln!("[pricing] {}", e);
+                pricing::PriceCatalog::default()
+            });
+        println!("Session usage for '{}':", profile_name);
+        for line in pricing::format_summary(&rows, &catalog) {
+            println!("{}", line);
+        }
+        catalog
+    };
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let entry = pricing::HistoryEntry {
+        project,
+        ..pricing::HistoryEntry::new(timestamp, profile_name, &rows, &catalog)
+    };
+    if let Err(e) = usage::record_session(&entry) {
+        eprintln!("[pricing] Failed to update usage counters: {}", e);
+    }
+    if let Err(e) = pricing::append_history(&entry) {
+        eprintln!("[pricing] Failed to record usage history: {}", e);
+    }
+}
@@ -0,0 +1,58 @@
+//! The parts of claude-profiler that work without the TUI: the profile model and
+//! `profiles.toml` handling, and the Anthropic <-> OpenAI translating proxy.
+//!
+//! The `claude-profiler` binary is built on this crate, and other tools can embed
+//! the same pieces:
+//!
+//! - `config`: profiles, their environment and the config file
+//! - `proxy`: the proxy server (`proxy::start_server`) and, for callers with a
+//!   transport of their own, the request and response conversions between the
+//!   Anthropic Messages API and the OpenAI Responses, Chat Completions and legacy
+//!   Completions APIs (`proxy::types` holds the wire types)
+//! - the modules the proxy builds on: credentials (`auth`, `openai_oauth`,
+//!   `anthropic_oauth`, `secrets`), local model servers (`local_models`), usage and
+//!   pricing (`pricing`), and provider error explanations (`provider_errors`)
+//!
+//! Translating one request and its reply without running the server:
+//!
+//! ```
+//! use claude_profiler_core::proxy::{self, types::AnthropicRequest};
+//!
+//! let request: AnthropicRequest = serde_json::from_value(serde_json::json!({
+//!     "model": "claude-sonnet-4-5",
+//!     "max_tokens": 1024,
+//!     "messages": [{ "role": "user", "content": "Hello" }]
+//! }))?;
+//! let chat = proxy::anthropic_to_chat(&request, "gpt-4.1");
+//! assert_eq!(chat.model, "gpt-4.1");
+//! assert_eq!(chat.messages[0].role, "user");
+//!
+//! let reply = serde_json::from_value(serde_json::json!({
+//!     "id": "chatcmpl-1",
+//!     "choices": [{
+//!         "index": 0,
+//!         "message": { "role": "assistant", "content": "Hi there" },
+//!         "finish_reason": "stop"
+//!     }],
+//!     "usage": { "prompt_tokens": 8, "completion_tokens": 3, "total_tokens": 11 }
+//! }))?;
+//! let anthropic = proxy::chat_to_anthropic(&reply, &request.model);
+//! assert_eq!(anthropic.usage.output_tokens, 3);
+//! # Ok::<(), serde_json::Error>(())
+//! ```
+
+pub mod anthropic_oauth;
+pub mod auth;
+pub mod codex_instructions;
+pub mod config;
+pub mod lanes;
+pub mod local_models;
+pub mod openai_oauth;
+pub mod platform;
+pub mod pricing;
+pub mod project;
+pub mod provider_errors;
+pub mod proxy;
+pub mod redact;
+pub mod schema;
+pub mod secrets;
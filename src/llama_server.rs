@@ -0,0 +1,291 @@
+//! Running llama.cpp's `llama-server` for profiles that point at it.
+//!
+//! A profile with `PROXY_LLAMA_MODELS_DIR` names a directory of GGUF files. The model
+//! picker lists them by their path below that directory, without `.gguf`, and when
+//! nothing answers on the profile's llama.cpp URL at launch, the launcher starts
+//! `llama-server` with the profile's model (and its context size and GPU layers, if
+//! set) and stops it again when Claude Code exits. A server that is already running
+//! is used as it is.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::{
+    Config, ENV_DEFAULT_HAIKU_MODEL, ENV_DEFAULT_OPUS_MODEL, ENV_DEFAULT_SONNET_MODEL,
+    ENV_LLAMA_CTX_SIZE, ENV_LLAMA_GPU_LAYERS, ENV_LLAMA_MODELS_DIR, ENV_MODEL,
+};
+use crate::display::Spinner;
+use crate::platform;
+
+/// How long a large model may take to load before the launch is given up
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Directory levels searched below the models directory
+const MAX_DEPTH: usize = 3;
+
+/// Where `llama-server` writes its output, next to `proxy.log`
+const LOG_FILE: &str = "llama-server.log";
+
+/// How to start `llama-server` for a profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlamaServerOptions {
+    pub models_dir: PathBuf,
+    /// Context size (`-c`); the model's own when unset
+    pub ctx_size: Option<u32>,
+    /// Layers offloaded to the GPU (`-ngl`); llama.cpp's default when unset
+    pub gpu_layers: Option<u32>,
+}
+
+impl LlamaServerOptions {
+    /// The profile's settings, if it names a models directory
+    pub fn from_env(env: &HashMap<String, String>) -> Option<Self> {
+        let number = |key: &str| env.get(key).and_then(|v| v.trim().parse::<u32>().ok());
+        let models_dir = env
+            .get(ENV_LLAMA_MODELS_DIR)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())?;
+        Some(Self {
+            models_dir: expand_home(models_dir),
+            ctx_size: number(ENV_LLAMA_CTX_SIZE),
+            gpu_layers: number(ENV_LLAMA_GPU_LAYERS),
+        })
+    }
+
+    /// The GGUF file of a model as the picker lists it
+    pub fn model_path(&self, model: &str) -> Option<PathBuf> {
+        let path = self.models_dir.join(format!("{}.gguf", model));
+        path.is_file().then_some(path)
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().map_or_else(|| PathBuf::from(path), |home| home.join(rest)),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Whether a GGUF file is a model of its own, rather than a vision projector or a
+/// later shard of a split model
+fn is_model_file(name: &str) -> bool {
+    let Some(stem) = name.strip_suffix(".gguf") else {
+        return false;
+    };
+    if stem.to_ascii_lowercase().contains("mmproj") {
+        return false;
+    }
+    // `model-00002-of-00003.gguf` is loaded through the first shard
+    match stem.rsplit_once("-of-") {
+        Some((head, _)) => head.rsplit('-').next() == Some("00001"),
+        None => true,
+    }
+}
+
+/// Models in `dir` and its subdirectories: their paths below `dir` without `.gguf`
+pub fn gguf_models(dir: &Path) -> Vec<String> {
+    fn walk(dir: &Path, prefix: &str, depth: usize, models: &mut Vec<String>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = entry.path();
+            if path.is_dir() && depth < MAX_DEPTH && !name.starts_with('.') {
+                walk(&path, &format!("{}{}/", prefix, name), depth + 1, models);
+            } else if is_model_file(&name) {
+                models.push(format!("{}{}", prefix, name.trim_end_matches(".gguf")));
+            }
+        }
+    }
+
+    let mut models = Vec::new();
+    walk(dir, "", 0, &mut models);
+    models.sort();
+    models
+}
+
+/// The model a server started for the profile loads: its default model, else the
+/// first tier mapping
+pub fn served_model(env: &HashMap<String, String>) -> Option<&str> {
+    [
+        ENV_MODEL,
+        ENV_DEFAULT_SONNET_MODEL,
+        ENV_DEFAULT_OPUS_MODEL,
+        ENV_DEFAULT_HAIKU_MODEL,
+    ]
+    .iter()
+    .filter_map(|key| env.get(*key).map(|v| v.trim()))
+    .find(|v| !v.is_empty())
+}
+
+/// `llama-server` on the PATH, else where Homebrew or a local build install it
+fn llama_server_program() -> PathBuf {
+    let exe = format!("llama-server{}", std::env::consts::EXE_SUFFIX);
+    let mut fallbacks = vec![PathBuf::from("/opt/homebrew/bin").join(&exe)];
+    if let Some(home) = dirs::home_dir() {
+        fallbacks.push(home.join(".local").join("bin").join(&exe));
+        fallbacks.push(home.join("llama.cpp").join("build").join("bin").join(&exe));
+    }
+    platform::program("llama-server", &fallbacks)
+}
+
+/// Whether a llama.cpp server answers its health check at `base_url`
+fn is_healthy(client: &reqwest::blocking::Client, base_url: &url::Url) -> bool {
+    base_url
+        .join("/health")
+        .ok()
+        .and_then(|url| client.get(url).send().ok())
+        .is_some_and(|response| response.status().is_success())
+}
+
+/// A `llama-server` started by the launcher; stopped when dropped
+pub struct LlamaServer {
+    child: Child,
+}
+
+impl LlamaServer {
+    /// Start `llama-server` for `model` on the host and port of `target_url` and
+    /// wait until it has loaded the model. Returns `None` when a server already
+    /// answers there or the URL is not on this machine.
+    pub fn start(
+        options: &LlamaServerOptions,
+        model: &str,
+        target_url: &str,
+    ) -> Result<Option<Self>> {
+        let url = url::Url::parse(target_url)?;
+        let host = url.host_str().unwrap_or_default();
+        if !matches!(host, "localhost" | "127.0.0.1" | "[::1]") {
+            return Ok(None);
+        }
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_millis(500))
+            .build()?;
+        if is_healthy(&client, &url) {
+            return Ok(None);
+        }
+
+        let path = options.model_path(model).with_context(|| {
+            format!(
+                "No {}.gguf in {}; pick a model from the directory in the model picker",
+                model,
+                options.models_dir.display()
+            )
+        })?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let log = Config::config_dir()
+            .map(|dir| dir.join(LOG_FILE))
+            .context("Could not determine config directory")?;
+        if let Some(dir) = log.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let log = fs::File::create(&log)
+            .with_context(|| format!("Failed to create {}", log.display()))?;
+
+        let mut command = Command::new(llama_server_program());
+        command
+            .arg("--model")
+            .arg(&path)
+            .args(["--alias", model, "--host", host.trim_matches(['[', ']'])])
+            .args(["--port", &port.to_string(), "--jinja"])
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log);
+        if let Some(ctx_size) = options.ctx_size {
+            command.args(["--ctx-size", &ctx_size.to_string()]);
+        }
+        if let Some(gpu_layers) = options.gpu_layers {
+            command.args(["--n-gpu-layers", &gpu_layers.to_string()]);
+        }
+        let child = command
+            .spawn()
+            .context("Failed to run `llama-server` (is llama.cpp installed?)")?;
+        let mut server = Self { child };
+
+        let started = Instant::now();
+        let mut spinner = Spinner::default();
+        while started.elapsed() < STARTUP_TIMEOUT {
+            if let Some(status) = server.child.try_wait()? {
+                println!();
+                anyhow::bail!("llama-server exited with {}; see {}", status, LOG_FILE);
+            }
+            if is_healthy(&client, &url) {
+                println!(
+                    "\r{} llama-server loaded {}        ",
+                    spinner.current(),
+                    model
+                );
+                return Ok(Some(server));
+            }
+            if let Some(frame) = spinner.tick() {
+                print!("\r{} Loading {} in llama-server...", frame, model);
+                io::stdout().flush()?;
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
+        println!();
+        anyhow::bail!(
+            "llama-server did not load {} within {} seconds",
+            model,
+            STARTUP_TIMEOUT.as_secs()
+        )
+    }
+}
+
+impl Drop for LlamaServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gguf_models_are_found_below_the_models_dir() {
+        let dir = std::env::temp_dir().join(format!("profiler-gguf-{}", std::process::id()));
+        fs::create_dir_all(dir.join("qwen")).unwrap();
+        for file in [
+            "gemma-3-4b-Q4_K_M.gguf",
+            "qwen/Qwen3-32B-Q4_K_M-00001-of-00002.gguf",
+            "qwen/Qwen3-32B-Q4_K_M-00002-of-00002.gguf",
+            "qwen/mmproj-F16.gguf",
+            "README.md",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            gguf_models(&dir),
+            ["gemma-3-4b-Q4_K_M", "qwen/Qwen3-32B-Q4_K_M-00001-of-00002"]
+        );
+
+        let env = HashMap::from([
+            (
+                ENV_LLAMA_MODELS_DIR.to_string(),
+                dir.to_string_lossy().into_owned(),
+            ),
+            (ENV_LLAMA_CTX_SIZE.to_string(), "32768".to_string()),
+            (
+                ENV_DEFAULT_SONNET_MODEL.to_string(),
+                "gemma-3-4b-Q4_K_M".to_string(),
+            ),
+        ]);
+        let options = LlamaServerOptions::from_env(&env).unwrap();
+        assert_eq!((options.ctx_size, options.gpu_layers), (Some(32768), None));
+        assert_eq!(served_model(&env), Some("gemma-3-4b-Q4_K_M"));
+        assert_eq!(
+            options.model_path("gemma-3-4b-Q4_K_M"),
+            Some(dir.join("gemma-3-4b-Q4_K_M.gguf"))
+        );
+        assert_eq!(options.model_path("missing"), None);
+        assert_eq!(LlamaServerOptions::from_env(&HashMap::new()), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
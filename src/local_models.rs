@@ -0,0 +1,912 @@
+//! Local model servers (Ollama, LM Studio, llama.cpp's `llama-server`, and any
+//! OpenAI-compatible server such as vLLM or text-generation-webui) sitting behind the
+//! proxy.
+//!
+//! The edit form's model picker lists the models installed on the server, and the
+//! health check asks the same endpoint. When a profile sets `PROXY_IDLE_UNLOAD_SECS`,
+//! the proxy unloads the model it last used after that many idle seconds so VRAM is
+//! freed while Claude Code sits idle. Ollama and LM Studio load models on demand, so
+//! the next request reloads it transparently; other servers keep theirs loaded.
+//!
+//! While such a model is loaded it is also recorded in `model-leases.json`, so that
+//! if claude-profiler dies before unloading it the next start can offer to.
+//!
+//! Ollama and LM Studio can also be started by the launcher when nothing answers on
+//! the profile's URL (and stopped again when Claude Code exits), and the profile's
+//! model pulled onto them when it is not installed yet.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::platform;
+
+/// Default Ollama port
+const OLLAMA_PORT: u16 = 11434;
+
+/// Default LM Studio server port
+const LMSTUDIO_PORT: u16 = 1234;
+
+/// Default llama.cpp `llama-server` port
+const LLAMA_CPP_PORT: u16 = 8080;
+
+/// Default ports of vLLM and text-generation-webui's API
+const OPENAI_COMPATIBLE_PORTS: [u16; 2] = [8000, 5000];
+
+/// Host name fragments of OpenAI-compatible local servers
+const OPENAI_COMPATIBLE_HOSTS: [&str; 3] = ["vllm", "textgen", "webui"];
+
+/// How long the model picker waits for the server to list its models
+const LIST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long an unload outside the proxy waits for Ollama
+const UNLOAD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a server started by the launcher may take to answer
+const SERVER_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Models loaded by a proxy and not yet unloaded, in the config directory
+const LEASE_FILE: &str = "model-leases.json";
+
+/// A local model server the model picker can list models from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalBackend {
+    Ollama,
+    LmStudio,
+    /// llama.cpp's `llama-server`, which the launcher can start (see `llama_server`)
+    LlamaCpp,
+    /// Any other server with an OpenAI-style `/v1/models`
+    OpenAiCompatible,
+}
+
+impl LocalBackend {
+    /// Guess the backend from the proxy target URL (host name or default port).
+    /// Any other server on this machine is taken to be OpenAI-compatible.
+    pub fn detect(target_url: &str) -> Option<Self> {
+        let url = url::Url::parse(target_url).ok()?;
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let port = url.port_or_known_default();
+        if host.contains("ollama") || port == Some(OLLAMA_PORT) {
+            return Some(LocalBackend::Ollama);
+        }
+        if host.contains("lmstudio") || port == Some(LMSTUDIO_PORT) {
+            return Some(LocalBackend::LmStudio);
+        }
+        if host.contains("llama") || port == Some(LLAMA_CPP_PORT) {
+            return Some(LocalBackend::LlamaCpp);
+        }
+        if is_loopback(&url)
+            || OPENAI_COMPATIBLE_HOSTS
+                .iter()
+                .any(|name| host.contains(name))
+            || port.is_some_and(|port| OPENAI_COMPATIBLE_PORTS.contains(&port))
+        {
+            return Some(LocalBackend::OpenAiCompatible);
+        }
+        None
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LocalBackend::Ollama => "Ollama",
+            LocalBackend::LmStudio => "LM Studio",
+            LocalBackend::LlamaCpp => "llama.cpp",
+            LocalBackend::OpenAiCompatible => "OpenAI-compatible server",
+        }
+    }
+
+    /// Whether the proxy can unload this server's models when idle
+    pub fn can_unload(&self) -> bool {
+        !matches!(
+            self,
+            LocalBackend::LlamaCpp | LocalBackend::OpenAiCompatible
+        )
+    }
+
+    /// Whether claude-profiler can start this server and pull models onto it
+    pub fn can_pull(&self) -> bool {
+        matches!(self, LocalBackend::Ollama | LocalBackend::LmStudio)
+    }
+
+    /// Endpoint listing the server's models; also what the health check asks
+    pub fn models_url(&self, target_url: &str) -> Result<url::Url> {
+        let path = match self {
+            LocalBackend::Ollama => "/api/tags",
+            LocalBackend::LmStudio | LocalBackend::LlamaCpp | LocalBackend::OpenAiCompatible => {
+                "/v1/models"
+            }
+        };
+        Ok(url::Url::parse(target_url)?.join(path)?)
+    }
+
+    /// Models installed on the server, sorted so families sit together. Blocking;
+    /// called from the TUI when the model picker opens.
+    pub fn list_models(&self, target_url: &str) -> Result<Vec<String>> {
+        let url = self.models_url(target_url)?;
+        let response = reqwest::blocking::Client::builder()
+            .timeout(LIST_TIMEOUT)
+            .build()?
+            .get(url)
+            .send()
+            .with_context(|| format!("Failed to reach {}", self.label()))?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().unwrap_or_default();
+            anyhow::bail!(
+                "{}: {}",
+                self.label(),
+                crate::provider_errors::describe(status, &body)
+            );
+        }
+        Ok(self.parse_models(&response.json()?))
+    }
+
+    /// Model names from an Ollama `/api/tags` or OpenAI-style `/v1/models` body
+    fn parse_models(&self, body: &Value) -> Vec<String> {
+        let (list, key) = match self {
+            LocalBackend::Ollama => ("models", "name"),
+            LocalBackend::LmStudio | LocalBackend::LlamaCpp | LocalBackend::OpenAiCompatible => {
+                ("data", "id")
+            }
+        };
+        let mut models: Vec<String> = body
+            .get(list)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|model| model.get(key).and_then(Value::as_str))
+            .map(str::to_string)
+            .collect();
+        models.sort();
+        models
+    }
+
+    /// Download `model` onto the server, reporting progress as it goes. Blocking;
+    /// Ollama pulls through its API and LM Studio through `lms get`.
+    pub fn pull_model(
+        &self,
+        target_url: &str,
+        model: &str,
+        mut progress: impl FnMut(&PullStatus),
+    ) -> Result<()> {
+        match self {
+            LocalBackend::Ollama => {
+                let url = url::Url::parse(target_url)?.join("/api/pull")?;
+                // A large model takes as long as it takes
+                let response = reqwest::blocking::Client::builder()
+                    .timeout(None)
+                    .build()?
+                    .post(url)
+                    .json(&serde_json::json!({ "model": model, "stream": true }))
+                    .send()
+                    .context("Failed to reach Ollama")?;
+                if !response.status().is_success() {
+                    let status = response.status().as_u16();
+                    let body = response.text().unwrap_or_default();
+                    anyhow::bail!(
+                        "Ollama: {}",
+                        crate::provider_errors::describe(status, &body)
+                    );
+                }
+                for line in BufReader::new(response).lines() {
+                    let line = line.context("Ollama stopped answering mid-pull")?;
+                    if !line.trim().is_empty() {
+                        progress(&PullStatus::parse(&line)?);
+                    }
+                }
+            }
+            LocalBackend::LmStudio => {
+                let mut child = std::process::Command::new(lms_program())
+                    .args(["get", model, "--yes"])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .context("Failed to run `lms` (is the LM Studio CLI installed?)")?;
+                if let Some(stdout) = child.stdout.take() {
+                    // `lms get` redraws its progress bar with carriage returns
+                    let mut line = Vec::new();
+                    for byte in BufReader::new(stdout).bytes() {
+                        match byte? {
+                            b'\r' | b'\n' => {
+                                let status = String::from_utf8_lossy(&line).trim().to_string();
+                                if !status.is_empty() {
+                                    progress(&PullStatus {
+                                        status,
+                                        ..Default::default()
+                                    });
+                                }
+                                line.clear();
+                            }
+                            byte => line.push(byte),
+                        }
+                    }
+                }
+                let status = child.wait()?;
+                if !status.success() {
+                    anyhow::bail!("`lms get {}` exited with {}", model, status);
+                }
+            }
+            LocalBackend::LlamaCpp | LocalBackend::OpenAiCompatible => {
+                anyhow::bail!("{} has no way to pull models", self.label())
+            }
+        }
+        Ok(())
+    }
+
+    /// Start the server on the host and port of `target_url` and wait until it
+    /// answers. Returns `None` when one already answers there or the URL is not on
+    /// this machine.
+    pub fn start_server(&self, target_url: &str) -> Result<Option<LocalServer>> {
+        let url = url::Url::parse(target_url)?;
+        if !is_loopback(&url) || self.list_models(target_url).is_ok() {
+            return Ok(None);
+        }
+        let port = url.port_or_known_default().unwrap_or(80);
+        let mut server = LocalServer {
+            backend: *self,
+            child: None,
+            running: true,
+        };
+        match self {
+            LocalBackend::Ollama => {
+                let host = url.host_str().unwrap_or_default();
+                let child = std::process::Command::new(ollama_program())
+                    .arg("serve")
+                    .env("OLLAMA_HOST", format!("{}:{}", host, port))
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .context("Failed to run `ollama` (is Ollama installed?)")?;
+                server.child = Some(child);
+            }
+            LocalBackend::LmStudio => {
+                let status = std::process::Command::new(lms_program())
+                    .args(["server", "start", "--port", &port.to_string()])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .context("Failed to run `lms` (is the LM Studio CLI installed?)")?;
+                if !status.success() {
+                    server.running = false;
+                    anyhow::bail!("`lms server start` exited with {}", status);
+                }
+            }
+            LocalBackend::LlamaCpp | LocalBackend::OpenAiCompatible => {
+                anyhow::bail!("{} cannot be started by claude-profiler", self.label())
+            }
+        }
+
+        let started = Instant::now();
+        while started.elapsed() < SERVER_START_TIMEOUT {
+            if let Some(child) = &mut server.child
+                && let Some(status) = child.try_wait()?
+            {
+                anyhow::bail!("{} exited with {}", self.label(), status);
+            }
+            if self.list_models(target_url).is_ok() {
+                return Ok(Some(server));
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
+        anyhow::bail!(
+            "{} did not answer within {} seconds",
+            self.label(),
+            SERVER_START_TIMEOUT.as_secs()
+        )
+    }
+
+    /// Unload a model so the server releases its memory
+    pub async fn unload(
+        &self,
+        client: &reqwest::Client,
+        target_url: &str,
+        model: &str,
+    ) -> Result<()> {
+        match self {
+            LocalBackend::Ollama => {
+                // A generate request with keep_alive 0 evicts the model immediately
+                let url = url::Url::parse(target_url)?.join("/api/generate")?;
+                let response = client
+                    .post(url)
+                    .json(&serde_json::json!({ "model": model, "keep_alive": 0 }))
+                    .send()
+                    .await
+                    .context("Failed to reach Ollama")?;
+                if !response.status().is_success() {
+                    anyhow::bail!("Ollama unload failed: {}", response.status());
+                }
+            }
+            LocalBackend::LmStudio => {
+                let status = tokio::process::Command::new(lms_program())
+                    .args(["unload", model])
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .status()
+                    .await
+                    .context("Failed to run `lms` (is the LM Studio CLI installed?)")?;
+                if !status.success() {
+                    anyhow::bail!("`lms unload {}` exited with {}", model, status);
+                }
+            }
+            LocalBackend::LlamaCpp | LocalBackend::OpenAiCompatible => {
+                anyhow::bail!("{} has no way to unload models", self.label())
+            }
+        }
+        Ok(())
+    }
+
+    /// Blocking `unload`, for when no proxy runtime is around (startup, panics)
+    pub fn unload_blocking(&self, target_url: &str, model: &str) -> Result<()> {
+        match self {
+            LocalBackend::Ollama => {
+                let url = url::Url::parse(target_url)?.join("/api/generate")?;
+                let response = reqwest::blocking::Client::builder()
+                    .timeout(UNLOAD_TIMEOUT)
+                    .build()?
+                    .post(url)
+                    .json(&serde_json::json!({ "model": model, "keep_alive": 0 }))
+                    .send()
+                    .context("Failed to reach Ollama")?;
+                if !response.status().is_success() {
+                    anyhow::bail!("Ollama unload failed: {}", response.status());
+                }
+            }
+            LocalBackend::LmStudio => {
+                let status = std::process::Command::new(lms_program())
+                    .args(["unload", model])
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .status()
+                    .context("Failed to run `lms` (is the LM Studio CLI installed?)")?;
+                if !status.success() {
+                    anyhow::bail!("`lms unload {}` exited with {}", model, status);
+                }
+            }
+            LocalBackend::LlamaCpp | LocalBackend::OpenAiCompatible => {
+                anyhow::bail!("{} has no way to unload models", self.label())
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the URL names this machine
+fn is_loopback(url: &url::Url) -> bool {
+    match url.host() {
+        Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    }
+}
+
+/// Progress of a model pull: a line of Ollama's `/api/pull` stream, or of `lms get`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct PullStatus {
+    /// What the server is doing, e.g. `pulling manifest` or `verifying sha256 digest`
+    #[serde(default)]
+    pub status: String,
+    /// Bytes of the current layer downloaded so far
+    pub completed: Option<u64>,
+    /// Size of the current layer
+    pub total: Option<u64>,
+}
+
+impl PullStatus {
+    /// One line of Ollama's pull stream; a line with an `error` fails the pull
+    fn parse(line: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(line)
+            .with_context(|| format!("Unexpected pull progress from Ollama: {}", line))?;
+        if let Some(error) = value.get("error").and_then(Value::as_str) {
+            anyhow::bail!("Ollama: {}", error);
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Share of the current layer downloaded, when the server reports sizes
+    pub fn percent(&self) -> Option<u64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some(completed.min(total) * 100 / total),
+            _ => None,
+        }
+    }
+}
+
+/// An Ollama or LM Studio server started by the launcher; stopped when dropped
+#[derive(Debug)]
+pub struct LocalServer {
+    pub backend: LocalBackend,
+    /// `ollama serve`; LM Studio runs its server in the app and is stopped with `lms`
+    child: Option<Child>,
+    running: bool,
+}
+
+impl LocalServer {
+    /// Stop the server
+    pub fn stop(mut self) -> Result<()> {
+        self.shut_down()
+    }
+
+    fn shut_down(&mut self) -> Result<()> {
+        if !std::mem::take(&mut self.running) {
+            return Ok(());
+        }
+        match self.child.take() {
+            Some(mut child) => {
+                child.kill()?;
+                child.wait()?;
+            }
+            None => {
+                let status = std::process::Command::new(lms_program())
+                    .args(["server", "stop"])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .context("Failed to run `lms` (is the LM Studio CLI installed?)")?;
+                if !status.success() {
+                    anyhow::bail!("`lms server stop` exited with {}", status);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        let _ = self.shut_down();
+    }
+}
+
+/// The Ollama CLI: `ollama` on the PATH, else where its installers put it
+fn ollama_program() -> PathBuf {
+    let exe = format!("ollama{}", std::env::consts::EXE_SUFFIX);
+    let mut fallbacks = vec![
+        PathBuf::from("/usr/local/bin").join(&exe),
+        PathBuf::from("/opt/homebrew/bin").join(&exe),
+    ];
+    if cfg!(windows)
+        && let Some(local) = std::env::var_os("LOCALAPPDATA")
+    {
+        fallbacks.push(
+            PathBuf::from(local)
+                .join("Programs")
+                .join("Ollama")
+                .join(&exe),
+        );
+    }
+    platform::program("ollama", &fallbacks)
+}
+
+/// The LM Studio CLI: `lms` on the PATH, else where LM Studio installs it (the
+/// user's `.lmstudio/bin`, or the copy bundled with the app on Windows)
+fn lms_program() -> PathBuf {
+    let exe = format!("lms{}", std::env::consts::EXE_SUFFIX);
+    let mut fallbacks = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        fallbacks.push(home.join(".lmstudio").join("bin").join(&exe));
+        fallbacks.push(home.join(".cache").join("lm-studio").join("bin").join(&exe));
+    }
+    if cfg!(windows)
+        && let Some(local) = std::env::var_os("LOCALAPPDATA")
+    {
+        fallbacks.push(
+            PathBuf::from(local)
+                .join("Programs")
+                .join("LM Studio")
+                .join("resources")
+                .join("app")
+                .join(".webpack")
+                .join(&exe),
+        );
+    }
+    platform::program("lms", &fallbacks)
+}
+
+/// Family a local model belongs to, for grouping in the picker: the publisher of
+/// `qwen/qwen3-8b` or the name of `qwen3:8b`
+pub fn model_family(model: &str) -> &str {
+    model.split(['/', ':']).next().unwrap_or(model)
+}
+
+/// A model a proxy loaded and has not unloaded yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelLease {
+    /// Process running the proxy
+    pub pid: u32,
+    pub backend: LocalBackend,
+    pub target_url: String,
+    pub model: String,
+}
+
+/// `model-leases.json`: the models proxies loaded, so ones left behind by a process
+/// that died can be unloaded later
+#[derive(Debug, Clone)]
+pub struct LeaseFile {
+    path: PathBuf,
+}
+
+impl LeaseFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The lease file in the config directory
+    pub fn default_file() -> Option<Self> {
+        Config::config_dir().map(|dir| Self::new(dir.join(LEASE_FILE)))
+    }
+
+    /// Recorded leases; a missing or unreadable file has none
+    pub fn read(&self) -> Vec<ModelLease> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, leases: &[ModelLease]) -> Result<()> {
+        if leases.is_empty() {
+            return match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(leases)?)?;
+        Ok(())
+    }
+
+    /// Record `lease`, replacing the one its process held on the same server
+    pub fn hold(&self, lease: ModelLease) -> Result<()> {
+        let mut leases = self.read();
+        leases.retain(|held| !(held.pid == lease.pid && held.target_url == lease.target_url));
+        leases.push(lease);
+        self.write(&leases)
+    }
+
+    /// Forget what process `pid` held on `target_url`
+    pub fn release(&self, pid: u32, target_url: &str) -> Result<()> {
+        let mut leases = self.read();
+        let before = leases.len();
+        leases.retain(|held| !(held.pid == pid && held.target_url == target_url));
+        if leases.len() == before {
+            return Ok(());
+        }
+        self.write(&leases)
+    }
+
+    /// Forget the given leases
+    pub fn remove(&self, removed: &[ModelLease]) -> Result<()> {
+        let mut leases = self.read();
+        leases.retain(|held| !removed.contains(held));
+        self.write(&leases)
+    }
+
+    /// Leases of processes that are no longer running
+    pub fn stale(&self, is_running: impl Fn(u32) -> bool) -> Vec<ModelLease> {
+        self.read()
+            .into_iter()
+            .filter(|lease| lease.pid != std::process::id() && !is_running(lease.pid))
+            .collect()
+    }
+}
+
+/// Unload what this process still holds, from the panic hook: a panic on the main
+/// thread ends the process, and the proxy thread with it, before it can unload
+pub fn unload_after_panic() {
+    if std::thread::current().name() != Some("main") {
+        return;
+    }
+    let Some(file) = LeaseFile::default_file() else {
+        return;
+    };
+    let held: Vec<ModelLease> = file
+        .read()
+        .into_iter()
+        .filter(|lease| lease.pid == std::process::id())
+        .collect();
+    let unloaded: Vec<ModelLease> = held
+        .into_iter()
+        .filter(|lease| {
+            match lease
+                .backend
+                .unload_blocking(&lease.target_url, &lease.model)
+            {
+                Ok(()) => {
+                    eprintln!("Unloaded {} from {}", lease.model, lease.backend.label());
+                    true
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Failed to unload {} from {}: {}",
+                        lease.model,
+                        lease.backend.label(),
+                        e
+                    );
+                    false
+                }
+            }
+        })
+        .collect();
+    // Leases that failed stay behind for the next start to offer
+    let _ = file.remove(&unloaded);
+}
+
+#[derive(Debug)]
+struct IdleState {
+    last_request: Instant,
+    loaded_model: Option<String>,
+    unloaded: bool,
+}
+
+/// Tracks request activity and decides when the loaded model has gone idle
+#[derive(Debug)]
+pub struct IdleUnloader {
+    pub backend: LocalBackend,
+    pub timeout: Duration,
+    state: Mutex<IdleState>,
+    /// Where the loaded model is recorded, and the server it is loaded on
+    lease: Option<(LeaseFile, String)>,
+}
+
+impl IdleUnloader {
+    pub fn new(backend: LocalBackend, timeout: Duration) -> Self {
+        Self {
+            backend,
+            timeout,
+            state: Mutex::new(IdleState {
+                last_request: Instant::now(),
+                loaded_model: None,
+                unloaded: false,
+            }),
+            lease: None,
+        }
+    }
+
+    /// Record the loaded model of `target_url` in `file` until it is unloaded
+    pub fn with_lease(mut self, file: LeaseFile, target_url: &str) -> Self {
+        self.lease = Some((file, target_url.to_string()));
+        self
+    }
+
+    /// Record a request for `model`. Returns true if the model had been unloaded
+    /// and is being reloaded by this request.
+    pub fn touch(&self, model: &str) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        state.last_request = Instant::now();
+        if state.loaded_model.as_deref() != Some(model)
+            && let Some((file, target_url)) = &self.lease
+        {
+            // Best effort: a lease that cannot be written only loses the crash cleanup
+            let _ = file.hold(ModelLease {
+                pid: std::process::id(),
+                backend: self.backend,
+                target_url: target_url.clone(),
+                model: model.to_string(),
+            });
+        }
+        state.loaded_model = Some(model.to_string());
+        std::mem::take(&mut state.unloaded)
+    }
+
+    /// Drop the lease after a successful unload, unless a request loaded a model again
+    pub fn unloaded(&self) {
+        let Ok(state) = self.state.lock() else {
+            return;
+        };
+        if state.loaded_model.is_none() {
+            self.release_lease();
+        }
+    }
+
+    /// Drop the lease while the model stays loaded, when the proxy stops normally
+    pub fn release_lease(&self) {
+        if let Some((file, target_url)) = &self.lease {
+            let _ = file.release(std::process::id(), target_url);
+        }
+    }
+
+    /// Take the loaded model if it has been idle longer than the timeout
+    pub fn take_expired(&self, now: Instant) -> Option<String> {
+        let mut state = self.state.lock().ok()?;
+        if now.duration_since(state.last_request) < self.timeout {
+            return None;
+        }
+        let model = state.loaded_model.take()?;
+        state.unloaded = true;
+        Some(model)
+    }
+
+    /// Take the loaded model regardless of idle time, for cleanup on exit
+    pub fn take_loaded(&self) -> Option<String> {
+        let mut state = self.state.lock().ok()?;
+        let model = state.loaded_model.take()?;
+        state.unloaded = true;
+        Some(model)
+    }
+
+    /// How often the idle check should run
+    pub fn poll_interval(&self) -> Duration {
+        (self.timeout / 4).clamp(Duration::from_secs(1), Duration::from_secs(30))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_by_port_or_host() {
+        assert_eq!(
+            LocalBackend::detect("http://localhost:11434/v1"),
+            Some(LocalBackend::Ollama)
+        );
+        assert_eq!(
+            LocalBackend::detect("http://127.0.0.1:1234/v1/chat/completions"),
+            Some(LocalBackend::LmStudio)
+        );
+        assert_eq!(
+            LocalBackend::detect("http://127.0.0.1:8080/v1"),
+            Some(LocalBackend::LlamaCpp)
+        );
+        assert_eq!(
+            LocalBackend::detect("http://llama-box.lan:9000"),
+            Some(LocalBackend::LlamaCpp)
+        );
+        assert_eq!(LocalBackend::detect("https://api.openai.com/v1"), None);
+    }
+
+    #[test]
+    fn other_local_servers_are_openai_compatible() {
+        for url in [
+            "http://localhost:8000/v1",
+            "http://127.0.0.1:9999/v1",
+            "http://[::1]:9000",
+            "http://gpu-box:5000/v1",
+            "http://vllm.lan/v1",
+        ] {
+            assert_eq!(
+                LocalBackend::detect(url),
+                Some(LocalBackend::OpenAiCompatible),
+                "{}",
+                url
+            );
+        }
+        assert_eq!(LocalBackend::detect("https://openrouter.ai/api/v1"), None);
+        assert!(!LocalBackend::OpenAiCompatible.can_unload());
+
+        let backend = LocalBackend::OpenAiCompatible;
+        assert_eq!(
+            backend
+                .models_url("http://localhost:8000/v1")
+                .unwrap()
+                .as_str(),
+            "http://localhost:8000/v1/models"
+        );
+        let vllm = serde_json::json!({
+            "object": "list",
+            "data": [{ "id": "Qwen/Qwen3-Coder-30B", "owned_by": "vllm" }]
+        });
+        assert_eq!(backend.parse_models(&vllm), ["Qwen/Qwen3-Coder-30B"]);
+    }
+
+    #[test]
+    fn installed_models_are_listed_per_backend() {
+        let ollama = serde_json::json!({
+            "models": [{ "name": "qwen3:8b" }, { "name": "gemma3:4b" }, { "name": "qwen3:32b" }]
+        });
+        assert_eq!(
+            LocalBackend::Ollama.parse_models(&ollama),
+            ["gemma3:4b", "qwen3:32b", "qwen3:8b"]
+        );
+        let lmstudio = serde_json::json!({
+            "object": "list",
+            "data": [{ "id": "qwen/qwen3-8b" }, { "id": "google/gemma-3-4b" }]
+        });
+        assert_eq!(
+            LocalBackend::LmStudio.parse_models(&lmstudio),
+            ["google/gemma-3-4b", "qwen/qwen3-8b"]
+        );
+        assert!(LocalBackend::Ollama.parse_models(&lmstudio).is_empty());
+        assert_eq!(model_family("qwen3:8b"), "qwen3");
+        assert_eq!(model_family("google/gemma-3-4b"), "google");
+    }
+
+    #[test]
+    fn pull_progress_is_read_from_the_ollama_stream() {
+        let status = PullStatus::parse(
+            r#"{"status":"pulling 6a0746a1ec1a","digest":"sha256:6a07","total":4000,"completed":1000}"#,
+        )
+        .unwrap();
+        assert_eq!(status.status, "pulling 6a0746a1ec1a");
+        assert_eq!(status.percent(), Some(25));
+        let manifest = PullStatus::parse(r#"{"status":"pulling manifest"}"#).unwrap();
+        assert_eq!(manifest.percent(), None);
+        let error = PullStatus::parse(r#"{"error":"pull model manifest: file does not exist"}"#)
+            .unwrap_err();
+        assert!(error.to_string().contains("file does not exist"));
+
+        // Servers on other machines are never started, and llama.cpp has no pulls
+        assert!(
+            LocalBackend::Ollama
+                .start_server("http://ollama.lan:11434")
+                .unwrap()
+                .is_none()
+        );
+        assert!(LocalBackend::Ollama.can_pull() && !LocalBackend::LlamaCpp.can_pull());
+        assert!(
+            LocalBackend::LlamaCpp
+                .pull_model("http://localhost:8080", "qwen", |_| {})
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn idle_unloader_expires_once_until_next_request() {
+        let unloader = IdleUnloader::new(LocalBackend::Ollama, Duration::from_secs(60));
+        let later = Instant::now() + Duration::from_secs(61);
+
+        assert_eq!(unloader.take_expired(later), None);
+        assert!(!unloader.touch("qwen3"));
+        assert_eq!(unloader.take_expired(Instant::now()), None);
+        assert_eq!(unloader.take_expired(later), Some("qwen3".to_string()));
+        assert_eq!(unloader.take_expired(later), None);
+        assert!(unloader.touch("qwen3"));
+        assert!(!unloader.touch("qwen3"));
+    }
+
+    #[test]
+    fn loaded_models_are_leased_until_unloaded() {
+        let dir = std::env::temp_dir().join(format!("model-leases-{}", std::process::id()));
+        let file = LeaseFile::new(dir.join(LEASE_FILE));
+        let url = "http://localhost:1234";
+        let unloader = IdleUnloader::new(LocalBackend::LmStudio, Duration::from_secs(60))
+            .with_lease(file.clone(), url);
+
+        unloader.touch("qwen/qwen3-8b");
+        unloader.touch("google/gemma-3-4b");
+        let leases = file.read();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].model, "google/gemma-3-4b");
+        assert_eq!(leases[0].pid, std::process::id());
+
+        // This process is running, so only the dead one's lease is stale
+        let dead = ModelLease {
+            pid: 1,
+            backend: LocalBackend::Ollama,
+            target_url: "http://localhost:11434".to_string(),
+            model: "qwen3:8b".to_string(),
+        };
+        file.hold(dead.clone()).unwrap();
+        assert!(file.stale(|_| true).is_empty());
+        let stale = file.stale(|_| false);
+        assert_eq!(stale, [dead]);
+        file.remove(&stale).unwrap();
+
+        // A request between taking the model and unloading it keeps the lease
+        unloader.take_loaded();
+        unloader.touch("qwen/qwen3-8b");
+        unloader.unloaded();
+        assert_eq!(file.read().len(), 1);
+        unloader.take_loaded();
+        unloader.unloaded();
+        assert!(file.read().is_empty());
+        assert!(!dir.join(LEASE_FILE).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,19 +1,40 @@
+mod analytics;
 mod app;
-mod codex_instructions;
-mod config;
+mod backup;
+mod config_watch;
+mod control;
+mod daemon;
+mod display;
+mod env_editor;
+mod health;
+mod inspector;
 mod launcher;
-mod openai_oauth;
-mod proxy;
+mod llama_server;
+mod maintenance;
+mod provision;
+mod selftest;
+mod signals;
+mod textarea;
 mod tui;
 mod ui;
+mod usage;
+mod workspace;
+
+// The profile model and the proxy live in the library crate; importing them here
+// keeps `crate::config` and friends working throughout the binary
+use claude_profiler_core::{
+    anthropic_oauth, auth, codex_instructions, config, local_models, openai_oauth, platform,
+    pricing, project, provider_errors, proxy, redact, schema, secrets,
+};
 
 use anyhow::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use std::time::Duration;
 
 use crate::app::{
-    Action, App, AppMode, EDIT_FIELD_API_KEY, EDIT_FIELD_COUNT, EDIT_FIELD_DESCRIPTION,
-    EDIT_FIELD_HAIKU, EDIT_FIELD_NAME, EDIT_FIELD_OPUS, EDIT_FIELD_PROXY_URL, EDIT_FIELD_SONNET,
+    Action, App, AppMode, EDIT_FIELD_API_KEY, EDIT_FIELD_DESCRIPTION, EDIT_FIELD_ENV,
+    EDIT_FIELD_HAIKU, EDIT_FIELD_HAIKU_SAMPLING, EDIT_FIELD_NAME, EDIT_FIELD_OPUS,
+    EDIT_FIELD_OPUS_SAMPLING, EDIT_FIELD_PROXY_URL, EDIT_FIELD_SONNET, EDIT_FIELD_SONNET_SAMPLING,
     EDIT_FIELD_URL,
 };
 use crate::config::{Config, Profile};
@@ -23,8 +44,42 @@ fn main() -> Result<()> {
     // Install panic hook for clean terminal restoration
     tui::install_panic_hook();
 
+    // Subcommands run before the config is loaded so `restore` can populate a
+    // fresh machine instead of finding a newly created default config
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(dir) = take_config_flag(&mut args)? {
+        Config::set_config_dir(dir);
+    }
+    match Config::migrate_legacy_config() {
+        Ok(Some((from, to))) => {
+            eprintln!(
+                "Copied the config from {} to {}; the old copy is no longer read (see {})",
+                from.display(),
+                to.display(),
+                from.join(config::LEGACY_NOTE_FILE).display()
+            )
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Config left at its old location: {:#}", e),
+    }
+    match args.first().map(String::as_str) {
+        Some("maintenance") => return maintenance::run_command(&Config::load()?),
+        Some("backup") => return backup::run_backup(&args[1..]),
+        Some("restore") => return backup::run_restore(&args[1..]),
+        Some("proxy-test") => return selftest::run_command(&Config::load()?, &args[1..]),
+        Some("ctl") => return control::run_command(&args[1..]),
+        Some("daemon") => return daemon::run_command(&args[1..]),
+        Some("add") => return provision::run_command(&args[1..]),
+        Some("profile") => return provision::run_profile_command(&args[1..]),
+        Some("schema") => return schema::run_command(&args[1..]),
+        _ => {}
+    }
+
     // Load or create config
     let config = Config::load()?;
+    maintenance::spawn_background_prune(config.retention);
+    proxy::configure_logging(config.logging);
+    display::configure(config.display);
 
     if config.profiles.is_empty() {
         eprintln!("No profiles defined in configuration.");
@@ -39,6 +94,20 @@ fn main() -> Result<()> {
 
     // Initialize app state once (persists across TUI sessions)
     let mut app = App::new(config);
+    app.refresh_codex_quota();
+    if let Ok(dir) = std::env::current_dir() {
+        app.enter_workspace(dir);
+    }
+    app.offer_detected_profiles();
+    app.start_tutorial_if_unseen();
+    app.offer_leftover_unload();
+    app.watch_config();
+    app.warn_expiring_keys();
+
+    let control = control::start().unwrap_or_else(|e| {
+        eprintln!("Control socket disabled: {}", e);
+        None
+    });
 
     // Initialize terminal once (reused across all TUI sessions)
     let mut terminal = tui::init()?;
@@ -46,7 +115,7 @@ fn main() -> Result<()> {
     // Main loop: keep running TUI until user explicitly quits
     loop {
         // Run the TUI event loop
-        let result = run_app(&mut terminal, &mut app);
+        let result = run_app(&mut terminal, &mut app, control.as_ref());
 
         // Handle the result
         match result {
@@ -58,7 +127,19 @@ fn main() -> Result<()> {
                 tui::restore()?;
 
                 // Launch Claude and wait for it to exit
-                let exit_result = launcher::exec_claude(&profile);
+                let exit_result =
+                    launcher::exec_claude(&profile, control.as_ref().map(|c| c.session.as_ref()));
+                app.note_session_upstream(&profile.name);
+                app.record_analytics(&profile);
+                app.refresh_codex_quota();
+
+                // A forwarded SIGTERM/SIGHUP ends the launcher as well
+                if let Err(e) = &exit_result
+                    && let Some(terminated) = e.downcast_ref::<launcher::Terminated>()
+                {
+                    eprintln!("{}", terminated);
+                    return Ok(());
+                }
 
                 // Reinitialize terminal for TUI
                 terminal = tui::init()?;
@@ -95,10 +176,61 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Remove `--config <dir>` (or `--config=<dir>`) from the arguments, before or
+/// after the subcommand but not past a `--`, and return the directory
+fn take_config_flag(args: &mut Vec<String>) -> Result<Option<std::path::PathBuf>> {
+    let end = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    let Some(index) = args[..end]
+        .iter()
+        .position(|arg| arg == "--config" || arg.starts_with("--config="))
+    else {
+        return Ok(None);
+    };
+    let dir = match args[index].strip_prefix("--config=") {
+        Some(dir) => {
+            let dir = dir.to_string();
+            args.remove(index);
+            dir
+        }
+        None if index + 1 < end => args.drain(index..index + 2).nth(1).unwrap_or_default(),
+        None => anyhow::bail!("--config needs a directory"),
+    };
+    Ok(Some(dir).filter(|dir| !dir.is_empty()).map(Into::into))
+}
+
 const UI_POLL_GRANULARITY: Duration = Duration::from_millis(50);
 
-fn run_app(terminal: &mut tui::Tui, app: &mut App) -> Result<Option<Profile>> {
+fn run_app(
+    terminal: &mut tui::Tui,
+    app: &mut App,
+    control: Option<&control::ControlServer>,
+) -> Result<Option<Profile>> {
+    // Re-check upstreams whose results have gone stale (all of them on startup)
+    app.refresh_health(false);
     loop {
+        // Commands from the control socket
+        if let Some(control) = control {
+            while let Ok(request) = control.requests.try_recv() {
+                let result = app.handle_control(&request.command);
+                request.reply(result);
+            }
+        }
+        if let Some(profile) = app.selected_profile.take() {
+            return Ok(Some(profile));
+        }
+
+        app.health.poll();
+        app.poll_config_changes();
+
+        // Follow the request log while its viewer is open
+        if matches!(app.mode, AppMode::Logs { .. }) {
+            app.refresh_logs();
+        }
+        app.inspector.poll();
+
         // Render
         terminal.draw(|frame| ui::render(frame, app))?;
 
@@ -108,9 +240,17 @@ fn run_app(terminal: &mut tui::Tui, app: &mut App) -> Result<Option<Profile>> {
         }
 
         // Handle input
-        if let Event::Key(key) = event::read()?
-            && key.kind == KeyEventKind::Press
-        {
+        let key = match event::read()? {
+            Event::Paste(text) => {
+                if let AppMode::EditProfile { focused_field, .. } = app.mode {
+                    handle_edit_paste(app, focused_field, &text);
+                }
+                continue;
+            }
+            Event::Key(key) => key,
+            _ => continue,
+        };
+        if key.kind == KeyEventKind::Press {
             // Clear status message on any key press in Normal mode
             if app.mode == AppMode::Normal && app.status_message.is_some() {
                 app.status_message = None;
@@ -135,22 +275,229 @@ fn run_app(terminal: &mut tui::Tui, app: &mut App) -> Result<Option<Profile>> {
                     }
                     KeyCode::Char('R') => Some(Action::ResetAll),
                     KeyCode::Char('d') => Some(Action::DeleteProfile),
+                    KeyCode::Char('t') => Some(Action::StartTutorial),
+                    KeyCode::Char('w') => Some(Action::ToggleWorkspace),
+                    KeyCode::Char('v') => Some(Action::ToggleListView),
+                    KeyCode::Char('i') => Some(Action::ShowNotes),
+                    KeyCode::Char('o') => Some(Action::OpenConsole),
+                    KeyCode::Char('l') => Some(Action::ShowLogs),
+                    KeyCode::Char('/') => Some(Action::StartTagFilter),
+                    KeyCode::Char('h') => Some(Action::CheckHealth),
+                    KeyCode::Char('u') => Some(Action::ShowUsage),
+                    KeyCode::Char('I') => Some(Action::ShowInspector),
+                    KeyCode::Char('c') => Some(Action::DuplicateProfile),
+                    KeyCode::Char('C') => Some(Action::StartCompare),
+                    KeyCode::Char('N') => Some(Action::CreateFromShellEnv),
+                    _ => None,
+                },
+                AppMode::TagFilter => match key.code {
+                    KeyCode::Enter => Some(Action::ApplyTagFilter),
+                    KeyCode::Up => Some(Action::MoveUp),
+                    KeyCode::Down => Some(Action::MoveDown),
+                    KeyCode::Esc => Some(Action::ClearTagFilter),
+                    KeyCode::Backspace => {
+                        app.pop_tag_filter_char();
+                        None
+                    }
+                    KeyCode::Char(c) => {
+                        app.push_tag_filter_char(c);
+                        None
+                    }
                     _ => None,
                 },
                 AppMode::Help => Some(Action::HideHelp),
+                AppMode::Notes { .. } => match key.code {
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.scroll_notes(-1);
+                        None
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.scroll_notes(1);
+                        None
+                    }
+                    KeyCode::PageUp => {
+                        app.scroll_notes(-10);
+                        None
+                    }
+                    KeyCode::PageDown => {
+                        app.scroll_notes(10);
+                        None
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('i') | KeyCode::Enter => {
+                        Some(Action::HideNotes)
+                    }
+                    _ => None,
+                },
+                AppMode::Logs { .. } => {
+                    let scroll = match key.code {
+                        KeyCode::Up | KeyCode::Char('k') => Some(Some(-1)),
+                        KeyCode::Down | KeyCode::Char('j') => Some(Some(1)),
+                        KeyCode::PageUp => Some(Some(-10)),
+                        KeyCode::PageDown => Some(Some(10)),
+                        KeyCode::Home | KeyCode::Char('g') => Some(Some(i64::MIN)),
+                        KeyCode::End | KeyCode::Char('G') => Some(None),
+                        _ => None,
+                    };
+                    match (scroll, key.code) {
+                        (Some(delta), _) => {
+                            app.scroll_logs(delta);
+                            None
+                        }
+                        (None, KeyCode::Char('f') | KeyCode::Tab) => Some(Action::CycleLogFilter),
+                        (None, KeyCode::Char('i') | KeyCode::Enter) => Some(Action::ShowInspector),
+                        (None, KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('l')) => {
+                            Some(Action::HideLogs)
+                        }
+                        _ => None,
+                    }
+                }
+                AppMode::Inspector => match key.code {
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        app.inspector.select(-1);
+                        None
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        app.inspector.select(1);
+                        None
+                    }
+                    KeyCode::PageUp => {
+                        app.inspector.scroll(-10);
+                        None
+                    }
+                    KeyCode::PageDown => {
+                        app.inspector.scroll(10);
+                        None
+                    }
+                    KeyCode::Char('r') => Some(Action::ReplayCapture),
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('I') => {
+                        Some(Action::HideInspector)
+                    }
+                    _ => None,
+                },
+                AppMode::Compare { .. } => match key.code {
+                    KeyCode::Up | KeyCode::Char('k') => Some(Action::MoveUp),
+                    KeyCode::Down | KeyCode::Char('j') => Some(Action::MoveDown),
+                    KeyCode::PageUp => {
+                        app.scroll_compare(-10);
+                        None
+                    }
+                    KeyCode::PageDown => {
+                        app.scroll_compare(10);
+                        None
+                    }
+                    KeyCode::Char('d') => Some(Action::ToggleCompareDifferences),
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('C') => {
+                        Some(Action::HideCompare)
+                    }
+                    _ => None,
+                },
+                AppMode::Usage => match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('u') => {
+                        Some(Action::HideUsage)
+                    }
+                    _ => None,
+                },
+                AppMode::ConfirmCost => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => Some(Action::ConfirmCost),
+                    KeyCode::Char('n') | KeyCode::Esc => Some(Action::CancelCost),
+                    _ => None,
+                },
+                AppMode::ConfirmResetOAuth => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => Some(Action::ConfirmResetOAuth),
+                    KeyCode::Char('n') | KeyCode::Esc => Some(Action::CancelResetOAuth),
+                    _ => None,
+                },
+                AppMode::UnloadLeftoverModels => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => Some(Action::UnloadLeftoverModels),
+                    KeyCode::Char('n') | KeyCode::Esc => Some(Action::KeepLeftoverModels),
+                    _ => None,
+                },
+                AppMode::ImportDetected => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => Some(Action::ImportDetected),
+                    KeyCode::Char('n') | KeyCode::Esc => Some(Action::SkipDetected),
+                    _ => None,
+                },
+                AppMode::TrustWorkspace => match key.code {
+                    KeyCode::Char('y') | KeyCode::Enter => Some(Action::TrustWorkspace),
+                    KeyCode::Char('n') | KeyCode::Esc => Some(Action::SkipWorkspaceTrust),
+                    _ => None,
+                },
+                AppMode::Tutorial { .. } => match key.code {
+                    KeyCode::Enter | KeyCode::Right | KeyCode::Char(' ') | KeyCode::Char('l') => {
+                        Some(Action::NextTutorialStep)
+                    }
+                    KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => {
+                        Some(Action::PreviousTutorialStep)
+                    }
+                    KeyCode::Esc | KeyCode::Char('q') => Some(Action::EndTutorial),
+                    _ => None,
+                },
                 AppMode::EditProfile {
                     focused_field,
                     is_creating,
                 } => match key.code {
                     KeyCode::Esc => Some(Action::CancelEdit),
+                    // Newline in the description: Alt/Shift+Enter, or Ctrl+J where
+                    // the terminal cannot tell those apart from Enter
+                    KeyCode::Enter
+                        if focused_field == EDIT_FIELD_DESCRIPTION
+                            && key.modifiers.intersects(
+                                event::KeyModifiers::ALT | event::KeyModifiers::SHIFT,
+                            ) =>
+                    {
+                        app.description_input.insert_newline();
+                        None
+                    }
+                    KeyCode::Char('j')
+                        if focused_field == EDIT_FIELD_DESCRIPTION
+                            && key.modifiers.contains(event::KeyModifiers::CONTROL) =>
+                    {
+                        app.description_input.insert_newline();
+                        None
+                    }
+                    KeyCode::Up | KeyCode::Down
+                        if focused_field == EDIT_FIELD_DESCRIPTION
+                            && app
+                                .description_input
+                                .move_vertical(if key.code == KeyCode::Up { -1 } else { 1 }) =>
+                    {
+                        None
+                    }
+                    // Up/Down move between the env editor's rows before leaving it
+                    KeyCode::Up | KeyCode::Down
+                        if focused_field == EDIT_FIELD_ENV
+                            && app.env_editor.move_selection(if key.code == KeyCode::Up {
+                                -1
+                            } else {
+                                1
+                            }) =>
+                    {
+                        None
+                    }
+                    KeyCode::Char('n')
+                        if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                            && focused_field == EDIT_FIELD_ENV =>
+                    {
+                        app.env_editor.add_row();
+                        None
+                    }
+                    KeyCode::Char('d')
+                        if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                            && focused_field == EDIT_FIELD_ENV =>
+                    {
+                        app.env_editor.remove_row();
+                        None
+                    }
                     KeyCode::Enter => {
-                        // For Codex profiles, open model picker on model fields
+                        // For Codex and local-server profiles, open model picker on model fields
                         let is_model_field = matches!(
                             focused_field,
                             EDIT_FIELD_HAIKU | EDIT_FIELD_SONNET | EDIT_FIELD_OPUS
                         );
-                        if is_model_field && app.is_codex_profile() && !app.codex_models.is_empty()
-                        {
+                        if is_model_field {
+                            app.ensure_picker_models_loaded();
+                        }
+                        if is_model_field && app.has_model_picker() {
                             app.open_model_picker(focused_field, is_creating);
                             None
                         } else {
@@ -159,7 +506,7 @@ fn run_app(terminal: &mut tui::Tui, app: &mut App) -> Result<Option<Profile>> {
                     }
                     KeyCode::Tab | KeyCode::Down => {
                         app.mode = AppMode::EditProfile {
-                            focused_field: (focused_field + 1) % EDIT_FIELD_COUNT,
+                            focused_field: (focused_field + 1) % app.edit_field_count(),
                             is_creating,
                         };
                         None
@@ -168,7 +515,7 @@ fn run_app(terminal: &mut tui::Tui, app: &mut App) -> Result<Option<Profile>> {
                         app.mode = AppMode::EditProfile {
                             focused_field: focused_field
                                 .checked_sub(1)
-                                .unwrap_or(EDIT_FIELD_COUNT - 1),
+                                .unwrap_or(app.edit_field_count() - 1),
                             is_creating,
                         };
                         None
@@ -180,6 +527,17 @@ fn run_app(terminal: &mut tui::Tui, app: &mut App) -> Result<Option<Profile>> {
                         app.reveal_api_key = !app.reveal_api_key;
                         None
                     }
+                    KeyCode::Char('k')
+                        if key.modifiers.contains(event::KeyModifiers::CONTROL)
+                            && focused_field == EDIT_FIELD_API_KEY =>
+                    {
+                        app.store_api_key(&secrets::Keychain);
+                        None
+                    }
+                    KeyCode::Char('a') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        app.toggle_advanced();
+                        None
+                    }
                     _ => {
                         handle_edit_input(app, focused_field, key);
                         None
@@ -197,20 +555,24 @@ fn run_app(terminal: &mut tui::Tui, app: &mut App) -> Result<Option<Profile>> {
                         app.select_model_from_picker(target_field, is_creating);
                         None
                     }
-                    KeyCode::Up | KeyCode::Char('k') => {
-                        if app.model_picker_index > 0 {
-                            app.model_picker_index -= 1;
-                        } else {
-                            app.model_picker_index = app.codex_models.len().saturating_sub(1);
-                        }
+                    KeyCode::Up => {
+                        app.move_model_picker(-1);
                         None
                     }
-                    KeyCode::Down | KeyCode::Char('j') => {
-                        if app.model_picker_index < app.codex_models.len().saturating_sub(1) {
-                            app.model_picker_index += 1;
-                        } else {
-                            app.model_picker_index = 0;
-                        }
+                    KeyCode::Down => {
+                        app.move_model_picker(1);
+                        None
+                    }
+                    KeyCode::Backspace => {
+                        app.pop_model_picker_char();
+                        None
+                    }
+                    KeyCode::Char('r') if key.modifiers.contains(event::KeyModifiers::CONTROL) => {
+                        app.refresh_picker_models();
+                        None
+                    }
+                    KeyCode::Char(c) => {
+                        app.push_model_picker_char(c);
                         None
                     }
                     _ => None,
@@ -236,13 +598,43 @@ fn handle_edit_input(app: &mut App, focused_field: usize, key: event::KeyEvent)
     let event = Event::Key(key);
     match focused_field {
         EDIT_FIELD_NAME => { app.name_input.handle_event(&event); }
-        EDIT_FIELD_DESCRIPTION => { app.description_input.handle_event(&event); }
+        EDIT_FIELD_DESCRIPTION => { app.description_input.handle_key(key); }
         EDIT_FIELD_API_KEY => { app.api_key_input.handle_event(&event); }
         EDIT_FIELD_URL => { app.url_input.handle_event(&event); }
         EDIT_FIELD_PROXY_URL => { app.proxy_url_input.handle_event(&event); }
         EDIT_FIELD_HAIKU => { app.haiku_model_input.handle_event(&event); }
         EDIT_FIELD_SONNET => { app.sonnet_model_input.handle_event(&event); }
         EDIT_FIELD_OPUS => { app.opus_model_input.handle_event(&event); }
+        EDIT_FIELD_ENV => {
+            // Typing into an empty editor starts the first variable
+            if app.env_editor.rows().is_empty() && matches!(key.code, KeyCode::Char(_)) {
+                app.env_editor.add_row();
+            }
+            if let Some(input) = app.env_editor.selected_input_mut() {
+                input.handle_event(&event);
+            }
+        }
+        EDIT_FIELD_HAIKU_SAMPLING => {
+            app.haiku_sampling_input.handle_event(&event);
+        }
+        EDIT_FIELD_SONNET_SAMPLING => {
+            app.sonnet_sampling_input.handle_event(&event);
+        }
+        EDIT_FIELD_OPUS_SAMPLING => {
+            app.opus_sampling_input.handle_event(&event);
+        }
         _ => {}
     }
 }
+
+/// Bracketed paste (and IME commits some terminals deliver the same way): the
+/// description keeps newlines, single-line fields drop them
+fn handle_edit_paste(app: &mut App, focused_field: usize, text: &str) {
+    if focused_field == EDIT_FIELD_DESCRIPTION {
+        app.description_input.insert_str(text);
+        return;
+    }
+    for c in text.chars().filter(|c| !c.is_control()) {
+        handle_edit_input(app, focused_field, event::KeyEvent::from(KeyCode::Char(c)));
+    }
+}
@@ -0,0 +1,261 @@
+//! Retention for the files that grow in the config directory.
+//!
+//! The proxy log, the request log and the usage history are append-only, and the `cache/` directory
+//! collects catalog snapshots. A background pass runs on every startup and
+//! `claude-profiler maintenance` runs the same pass on demand, printing what it did.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::{Config, RetentionPolicy};
+use crate::pricing::{self, HistoryEntry, USAGE_HISTORY_FILE};
+use crate::proxy::{PROXY_LOG_FILE, REQUEST_LOG_FILE, RequestLogEntry};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// What a pruning pass did to one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pruned {
+    pub path: PathBuf,
+    pub bytes_before: u64,
+    /// `None` when the file was deleted
+    pub bytes_after: Option<u64>,
+}
+
+/// Unix seconds of a proxy log line (`[1700000000] message`)
+fn log_line_timestamp(line: &str) -> Option<u64> {
+    line.strip_prefix('[')?.split_once(']')?.0.parse().ok()
+}
+
+/// Unix seconds of a usage history line
+fn history_line_timestamp(line: &str) -> Option<u64> {
+    serde_json::from_str::<HistoryEntry>(line)
+        .ok()
+        .map(|entry| entry.timestamp)
+}
+
+/// Unix seconds of a request log line
+fn request_line_timestamp(line: &str) -> Option<u64> {
+    serde_json::from_str::<RequestLogEntry>(line)
+        .ok()
+        .map(|entry| entry.timestamp)
+}
+
+/// Keep the newest lines that are younger than `cutoff` and fit in `max_bytes`.
+///
+/// Lines without a readable timestamp are kept as long as they fit, so a
+/// hand-edited or truncated line never blocks pruning of the rest.
+fn prune_lines(
+    content: &str,
+    timestamp: impl Fn(&str) -> Option<u64>,
+    cutoff: u64,
+    max_bytes: u64,
+) -> String {
+    let mut kept = Vec::new();
+    let mut size = 0u64;
+    for line in content.lines().rev() {
+        if timestamp(line).is_some_and(|ts| ts < cutoff) {
+            continue;
+        }
+        let line_size = line.len() as u64 + 1;
+        if size + line_size > max_bytes {
+            break;
+        }
+        size += line_size;
+        kept.push(line);
+    }
+    kept.reverse();
+    kept.into_iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Rewrite a line-oriented file in place, returning what changed
+fn prune_file(
+    path: &Path,
+    timestamp: impl Fn(&str) -> Option<u64>,
+    max_age_days: u64,
+    max_bytes: u64,
+    now: u64,
+) -> Result<Option<Pruned>> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+    let cutoff = now.saturating_sub(max_age_days * SECS_PER_DAY);
+    let pruned = prune_lines(&content, timestamp, cutoff, max_bytes);
+    if pruned.len() == content.len() {
+        return Ok(None);
+    }
+
+    // Write beside the original and rename so a crash never leaves half a file
+    let tmp = path.with_extension("prune.tmp");
+    fs::write(&tmp, &pruned).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(Some(Pruned {
+        path: path.to_path_buf(),
+        bytes_before: content.len() as u64,
+        bytes_after: Some(pruned.len() as u64),
+    }))
+}
+
+/// Delete cache files whose last refresh is older than `max_age_days`
+fn prune_cache(dir: &Path, max_age_days: u64, now: SystemTime) -> Result<Vec<Pruned>> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let max_age = Duration::from_secs(max_age_days * SECS_PER_DAY);
+    let mut removed = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let is_stale = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > max_age);
+        if !metadata.is_file() || !is_stale {
+            continue;
+        }
+        let path = entry.path();
+        fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        removed.push(Pruned {
+            path,
+            bytes_before: metadata.len(),
+            bytes_after: None,
+        });
+    }
+    Ok(removed)
+}
+
+/// Apply the retention policy to everything under `dir`
+pub fn prune(dir: &Path, policy: &RetentionPolicy, now: SystemTime) -> Result<Vec<Pruned>> {
+    let now_secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut report = Vec::new();
+
+    report.extend(prune_file(
+        &dir.join(PROXY_LOG_FILE),
+        log_line_timestamp,
+        policy.log_max_age_days,
+        policy.log_max_bytes,
+        now_secs,
+    )?);
+    report.extend(prune_file(
+        &dir.join(REQUEST_LOG_FILE),
+        request_line_timestamp,
+        policy.log_max_age_days,
+        policy.log_max_bytes,
+        now_secs,
+    )?);
+    report.extend(prune_file(
+        &dir.join(USAGE_HISTORY_FILE),
+        history_line_timestamp,
+        policy.history_max_age_days,
+        policy.history_max_bytes,
+        now_secs,
+    )?);
+    report.extend(prune_cache(
+        &dir.join("cache"),
+        policy.cache_max_age_days,
+        now,
+    )?);
+
+    Ok(report)
+}
+
+/// Prune on a background thread; failures are ignored since the TUI owns the terminal
+pub fn spawn_background_prune(policy: RetentionPolicy) {
+    let Some(dir) = Config::config_dir() else {
+        return;
+    };
+    std::thread::spawn(move || {
+        let _ = prune(&dir, &policy, SystemTime::now());
+    });
+}
+
+/// `claude-profiler maintenance`: prune now and print lifetime usage totals
+pub fn run_command(config: &Config) -> Result<()> {
+    let dir = Config::config_dir().context("Could not determine config directory")?;
+    let report = prune(&dir, &config.retention, SystemTime::now())?;
+
+    if report.is_empty() {
+        println!("Nothing to prune in {}", dir.display());
+    }
+    for pruned in &report {
+        match pruned.bytes_after {
+            Some(after) => println!(
+                "Pruned {}: {} -> {} bytes",
+                pruned.path.display(),
+                pruned.bytes_before,
+                after
+            ),
+            None => println!(
+                "Removed {} ({} bytes)",
+                pruned.path.display(),
+                pruned.bytes_before
+            ),
+        }
+    }
+
+    let history = pricing::read_history();
+    if !history.is_empty() {
+        println!(
+            "Usage history: {}",
+            pricing::format_history_totals(&history)
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_lines_drops_old_then_oversized() {
+        let log = "[100] old\n[200] kept-but-too-big\nno timestamp\n[300] newest\n";
+
+        assert_eq!(
+            prune_lines(log, log_line_timestamp, 150, 1024),
+            "[200] kept-but-too-big\nno timestamp\n[300] newest\n"
+        );
+        assert_eq!(
+            prune_lines(log, log_line_timestamp, 150, 30),
+            "no timestamp\n[300] newest\n"
+        );
+        assert_eq!(prune_lines(log, log_line_timestamp, 0, 0), "");
+    }
+
+    #[test]
+    fn prune_rewrites_log_and_history() {
+        let dir =
+            std::env::temp_dir().join(format!("claude-profiler-prune-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let day = SECS_PER_DAY;
+        let now = UNIX_EPOCH + Duration::from_secs(400 * day);
+
+        fs::write(
+            dir.join(PROXY_LOG_FILE),
+            format!("[{}] stale\n[{}] fresh\n", 10 * day, 399 * day),
+        )
+        .unwrap();
+        fs::write(
+            dir.join(USAGE_HISTORY_FILE),
+            format!(
+                "{{\"timestamp\":{},\"profile\":\"a\",\"models\":[]}}\n",
+                399 * day
+            ),
+        )
+        .unwrap();
+
+        let report = prune(&dir, &RetentionPolicy::default(), now).unwrap();
+        let log = fs::read_to_string(dir.join(PROXY_LOG_FILE)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].path, dir.join(PROXY_LOG_FILE));
+        assert_eq!(log, format!("[{}] fresh\n", 399 * day));
+    }
+}
@@ -22,6 +22,7 @@ use tokio::sync::oneshot;
 static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
 
 use crate::config::Config;
+use crate::platform;
 
 pub const OPENAI_OAUTH_CLIENT_ID: &str = "app_EMoamEEZ73f0CkXaXp7hrann";
 pub const OPENAI_OAUTH_AUTHORIZE_URL: &str = "https://auth.openai.com/oauth/authorize";
@@ -87,7 +88,7 @@ pub fn decode_chatgpt_account_id(access_token: &str) -> Option<String> {
         .map(|s| s.to_string())
 }
 
-fn parse_authorization_input(input: &str) -> (Option<String>, Option<String>) {
+pub(crate) fn parse_authorization_input(input: &str) -> (Option<String>, Option<String>) {
     let value = input.trim();
     if value.is_empty() {
         return (None, None);
@@ -126,13 +127,13 @@ fn parse_authorization_input(input: &str) -> (Option<String>, Option<String>) {
     (Some(value.to_string()), None)
 }
 
-fn random_hex(bytes: usize) -> String {
+pub(crate) fn random_hex(bytes: usize) -> String {
     let mut buf = vec![0u8; bytes];
     OsRng.fill_bytes(&mut buf);
     buf.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
-fn generate_pkce() -> (String, String) {
+pub(crate) fn generate_pkce() -> (String, String) {
     // RFC 7636 recommends 43-128 chars; we use 32 random bytes base64url.
     let mut verifier_bytes = [0u8; 32];
     OsRng.fill_bytes(&mut verifier_bytes);
@@ -257,42 +258,32 @@ fn load_tokens() -> Result<Option<OpenAiOAuthTokens>> {
 }
 
 pub fn clear_tokens() -> Result<()> {
-    if let Some(path) = token_file_path() {
-        if path.exists() {
-            fs::remove_file(path).context("Failed to delete token file")?;
-        }
+    if let Some(path) = token_file_path()
+        && path.exists()
+    {
+        fs::remove_file(path).context("Failed to delete token file")?;
     }
     Ok(())
 }
 
+/// Whether a ChatGPT sign-in is stored
+pub fn has_stored_tokens() -> bool {
+    token_file_path().is_some_and(|path| path.exists())
+}
+
+/// The stored access token while it is still valid, without signing in or refreshing
+pub fn stored_access_token() -> Result<Option<String>> {
+    Ok(load_tokens()?
+        .filter(|tokens| tokens.expires > now_millis())
+        .map(|tokens| tokens.access))
+}
+
 fn save_tokens(tokens: &OpenAiOAuthTokens) -> Result<()> {
     let Some(path) = token_file_path() else {
         anyhow::bail!("Could not determine config directory for saving tokens");
     };
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("Failed to create {}", parent.display()))?;
-    }
     let contents = serde_json::to_string_pretty(tokens).context("Failed to serialize tokens")?;
-
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::OpenOptionsExt;
-        let mut opts = fs::OpenOptions::new();
-        opts.create(true).truncate(true).write(true).mode(0o600);
-        let mut f = opts
-            .open(&path)
-            .with_context(|| format!("Failed to write {}", path.display()))?;
-        f.write_all(contents.as_bytes())?;
-        return Ok(());
-    }
-
-    #[cfg(not(unix))]
-    {
-        fs::write(&path, contents)
-            .with_context(|| format!("Failed to write {}", path.display()))?;
-        Ok(())
-    }
+    platform::write_private(&path, contents.as_bytes())
 }
 
 #[derive(Clone)]
@@ -340,7 +331,14 @@ async fn callback_handler(
     .into_response()
 }
 
-async fn wait_for_oauth_code(expected_state: String, timeout: Duration) -> Result<Option<String>> {
+/// Serve `path` on localhost `port` until the browser is redirected there with a code
+/// for `expected_state`, or `timeout` passes
+pub(crate) async fn wait_for_oauth_code(
+    port: u16,
+    path: &str,
+    expected_state: String,
+    timeout: Duration,
+) -> Result<Option<String>> {
     let (code_tx, code_rx) = oneshot::channel::<String>();
 
     let state = CallbackState {
@@ -349,10 +347,10 @@ async fn wait_for_oauth_code(expected_state: String, timeout: Duration) -> Resul
     };
 
     let app = Router::new()
-        .route("/auth/callback", get(callback_handler))
+        .route(path, get(callback_handler))
         .with_state(state);
 
-    let addr = format!("127.0.0.1:{}", OPENAI_OAUTH_CALLBACK_PORT);
+    let addr = format!("127.0.0.1:{}", port);
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .with_context(|| format!("Failed to bind OAuth callback server on {}", addr))?;
@@ -377,21 +375,43 @@ async fn wait_for_oauth_code(expected_state: String, timeout: Duration) -> Resul
     }
 }
 
-fn try_open_browser(url: &str) {
-    if cfg!(target_os = "windows") {
-        let _ = std::process::Command::new("cmd")
-            .args(["/C", "start", "", url])
-            .spawn();
-        return;
-    }
+/// Open `url` in the default browser without waiting for it. The opener's output is
+/// discarded so it cannot draw over the TUI.
+pub fn open_browser(url: &str) -> std::io::Result<()> {
+    use std::process::{Command, Stdio};
 
-    let opener = if cfg!(target_os = "macos") {
-        "open"
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", "", url]);
+        command
     } else {
-        "xdg-open"
+        let mut command = Command::new(if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        });
+        command.arg(url);
+        command
     };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
 
-    let _ = std::process::Command::new(opener).arg(url).spawn();
+/// Refresh the stored token without user interaction, for use mid-session after the
+/// upstream rejected `stale_access`. If another session already refreshed it, the
+/// newer stored token is returned as-is.
+pub async fn refresh_stored_access_token(stale_access: &str) -> Result<String> {
+    let tokens = load_tokens()?.context("No stored OpenAI OAuth tokens")?;
+    if tokens.access != stale_access && tokens.expires > now_millis() {
+        return Ok(tokens.access);
+    }
+    let refreshed = refresh_access_token(&tokens.refresh).await?;
+    save_tokens(&refreshed)?;
+    Ok(refreshed.access)
 }
 
 pub async fn ensure_access_token_interactive() -> Result<String> {
@@ -414,13 +434,18 @@ pub async fn ensure_access_token_interactive() -> Result<String> {
 
     eprintln!("OpenAI OAuth required. Opening browser for sign-in...");
     eprintln!("If the browser does not open, visit this URL:\n\n{}\n", authorize_url);
-    try_open_browser(&authorize_url);
+    let _ = open_browser(&authorize_url);
 
     // Preferred: localhost callback capture. Fallback: manual paste.
-    let code = wait_for_oauth_code(state.clone(), Duration::from_secs(300))
-        .await
-        .ok()
-        .flatten();
+    let code = wait_for_oauth_code(
+        OPENAI_OAUTH_CALLBACK_PORT,
+        "/auth/callback",
+        state.clone(),
+        Duration::from_secs(300),
+    )
+    .await
+    .ok()
+    .flatten();
 
     let code = if let Some(code) = code {
         code
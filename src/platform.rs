@@ -0,0 +1,182 @@
+//! Differences between Unix and Windows that the launcher has to paper over.
+//!
+//! Windows finds programs by `PATHEXT` as well as by name, and npm installs Claude
+//! Code as `claude.cmd`, which `Command::new("claude")` alone does not find. Files
+//! holding secrets are created with mode 0600 on Unix; on Windows the file's ACL is
+//! reduced to the current user with `icacls`.
+
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions tried for a bare program name, in order
+fn program_extensions() -> Vec<String> {
+    if !cfg!(windows) {
+        return vec![String::new()];
+    }
+    let pathext = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+    pathext
+        .split(';')
+        .map(str::trim)
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
+
+/// First `name` (with any `PATHEXT` extension on Windows) in the directories of `path`
+pub fn find_in_path(name: &str, path: Option<OsString>) -> Option<PathBuf> {
+    let extensions = program_extensions();
+    std::env::split_paths(&path?)
+        .flat_map(|dir| {
+            extensions
+                .iter()
+                .map(move |ext| dir.join(format!("{}{}", name, ext)))
+        })
+        .find(|candidate| candidate.is_file())
+}
+
+/// A program on the `PATH`, or else the first of `fallbacks` that exists; the bare
+/// name when neither does, so the spawn error names the program
+pub fn program(name: &str, fallbacks: &[PathBuf]) -> PathBuf {
+    find_in_path(name, std::env::var_os("PATH"))
+        .or_else(|| fallbacks.iter().find(|path| path.is_file()).cloned())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Write a file only the current user can read
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        file.write_all(contents)?;
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    {
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        restrict_to_owner(path)
+    }
+}
+
+/// Create a directory (and any missing parents) only the current user can open. On
+/// Unix an existing directory is narrowed to mode 0700 as well; elsewhere the files
+/// in it are restricted one by one by [`write_private`].
+pub fn create_private_dir(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700))
+            .with_context(|| format!("Failed to restrict access to {}", path.display()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        fs::create_dir_all(path).with_context(|| format!("Failed to create {}", path.display()))
+    }
+}
+
+/// Whether a process with this id is running
+#[cfg(unix)]
+pub fn process_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks; EPERM means it exists but belongs to someone else
+    let signalled = unsafe { libc::kill(pid, 0) == 0 };
+    signalled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn process_running(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/NH", "/FO", "CSV", "/FI"])
+        .arg(format!("PID eq {}", pid))
+        .output()
+        .is_ok_and(|output| {
+            String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid))
+        })
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn process_running(_pid: u32) -> bool {
+    false
+}
+
+/// Replace a file's inherited ACL with full control for the current user alone
+#[cfg(windows)]
+fn restrict_to_owner(path: &Path) -> Result<()> {
+    let user = std::env::var("USERNAME").context("USERNAME is not set")?;
+    let status = std::process::Command::new("icacls")
+        .arg(path)
+        .args(["/inheritance:r", "/grant:r"])
+        .arg(format!("{}:F", user))
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .context("Failed to run icacls")?;
+    if !status.success() {
+        anyhow::bail!(
+            "Failed to restrict access to {}: icacls exited with {}",
+            path.display(),
+            status
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn restrict_to_owner(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn programs_are_found_on_the_path() {
+        let dir = std::env::temp_dir().join(format!("profiler-path-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let name = if cfg!(windows) { "tool.cmd" } else { "tool" };
+        fs::write(dir.join(name), "").unwrap();
+        let path = std::env::join_paths([Path::new("/nonexistent"), &dir]).unwrap();
+
+        assert_eq!(
+            find_in_path("tool", Some(path.clone())),
+            Some(dir.join(name))
+        );
+        assert_eq!(find_in_path("missing", Some(path)), None);
+        assert_eq!(find_in_path("tool", None), None);
+
+        let secret = dir.join("secret.json");
+        write_private(&secret, b"{}").unwrap();
+        assert_eq!(fs::read(&secret).unwrap(), b"{}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&secret).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,533 @@
+//! Catalog-driven cost estimates for proxied sessions.
+//!
+//! Prices come from the community models.dev catalog, cached under `cache/` so a
+//! launch only hits the network about once a day. The proxy records token usage
+//! per upstream model in a [`UsageTracker`]; the launcher prices it on exit and
+//! appends the session to `usage-history.jsonl` for cross-session totals.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::config::{Config, CostCeiling};
+use crate::project::ProjectContext;
+
+/// Community-maintained model catalog with per-token pricing
+const CATALOG_URL: &str = "https://models.dev/api.json";
+
+/// Cache file name under the config `cache/` directory
+const CATALOG_CACHE_FILE: &str = "models-dev.json";
+
+/// Per-session usage history under the config directory, one JSON object per line
+pub const USAGE_HISTORY_FILE: &str = "usage-history.jsonl";
+
+/// Cache TTL: 24 hours
+const CATALOG_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Reasoning-effort suffixes appended to model names (e.g. `gpt-5.2-codex-high`)
+const EFFORT_SUFFIXES: [&str; 6] = ["-none", "-minimal", "-low", "-medium", "-high", "-xhigh"];
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub input: f64,
+    #[serde(default)]
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &ModelUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input + usage.output_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Prices keyed by lowercase model id (and `provider/model`)
+#[derive(Debug, Default)]
+pub struct PriceCatalog {
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl PriceCatalog {
+    /// Build from the models.dev `api.json` shape:
+    /// `{ provider: { models: { id: { cost: { input, output } } } } }`
+    pub fn from_models_dev(value: &Value) -> Self {
+        let mut prices = HashMap::new();
+        let Some(providers) = value.as_object() else {
+            return Self { prices };
+        };
+        for (provider_id, provider) in providers {
+            let Some(models) = provider.get("models").and_then(|m| m.as_object()) else {
+                continue;
+            };
+            for (model_id, model) in models {
+                let Some(price) = model
+                    .get("cost")
+                    .and_then(|c| serde_json::from_value::<ModelPrice>(c.clone()).ok())
+                else {
+                    continue;
+                };
+                let model_id = model_id.to_ascii_lowercase();
+                prices.insert(
+                    format!("{}/{}", provider_id.to_ascii_lowercase(), model_id),
+                    price,
+                );
+                // Providers are visited in sorted order; the first listing wins
+                prices.entry(model_id).or_insert(price);
+            }
+        }
+        Self { prices }
+    }
+
+    /// Find a price for a model name as sent upstream
+    pub fn lookup(&self, model: &str) -> Option<ModelPrice> {
+        let lower = model.trim().to_ascii_lowercase();
+        let bare = lower.rsplit('/').next().unwrap_or(&lower);
+        let without_effort = EFFORT_SUFFIXES
+            .iter()
+            .find_map(|suffix| bare.strip_suffix(suffix))
+            .unwrap_or(bare);
+
+        [lower.as_str(), bare, without_effort]
+            .iter()
+            .find_map(|key| self.prices.get(*key).copied())
+    }
+}
+
+fn cache_file() -> Option<PathBuf> {
+    Config::config_dir().map(|p| p.join("cache").join(CATALOG_CACHE_FILE))
+}
+
+fn is_fresh(path: &PathBuf) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age.as_secs() < CATALOG_TTL_SECS)
+}
+
+fn read_cached(path: &PathBuf) -> Option<PriceCatalog> {
+    let content = fs::read_to_string(path).ok()?;
+    let value = serde_json::from_str::<Value>(&content).ok()?;
+    Some(PriceCatalog::from_models_dev(&value))
+}
+
+/// The cached catalog however old it is, for the TUI, which never waits on the network
+pub fn cached_catalog() -> PriceCatalog {
+    cache_file()
+        .and_then(|path| read_cached(&path))
+        .unwrap_or_default()
+}
+
+async fn fetch_catalog() -> Result<String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+    let response = client
+        .get(CATALOG_URL)
+        .header("User-Agent", "claude-profiler")
+        .send()
+        .await
+        .context("Failed to fetch pricing catalog")?;
+    if !response.status().is_success() {
+        anyhow::bail!("Failed to fetch pricing catalog: {}", response.status());
+    }
+    Ok(response.text().await?)
+}
+
+/// Load the pricing catalog, refreshing the local cache when it is stale
+pub async fn load_catalog() -> Result<PriceCatalog> {
+    let cache_path = cache_file();
+
+    if let Some(path) = &cache_path
+        && is_fresh(path)
+        && let Some(catalog) = read_cached(path)
+    {
+        return Ok(catalog);
+    }
+
+    let body = match fetch_catalog().await {
+        Ok(body) => body,
+        Err(e) => {
+            // Stale prices are better than none
+            if let Some(catalog) = cache_path.as_ref().and_then(read_cached) {
+                eprintln!("[pricing] Using cached catalog ({})", e);
+                return Ok(catalog);
+            }
+            return Err(e);
+        }
+    };
+
+    let value: Value = serde_json::from_str(&body).context("Invalid pricing catalog")?;
+
+    if let Some(path) = &cache_path {
+        if let Some(dir) = path.parent()
+            && let Err(e) = fs::create_dir_all(dir)
+        {
+            eprintln!("[pricing] Failed to create cache dir: {}", e);
+        } else if let Err(e) = fs::write(path, &body) {
+            eprintln!("[pricing] Failed to write cache: {}", e);
+        }
+    }
+
+    Ok(PriceCatalog::from_models_dev(&value))
+}
+
+/// Token usage accumulated for one upstream model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModelUsage {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Per-model usage recorded by the proxy over a session
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    totals: Mutex<HashMap<String, ModelUsage>>,
+}
+
+impl UsageTracker {
+    pub fn record(&self, model: &str, input_tokens: u32, output_tokens: u32) {
+        let Ok(mut totals) = self.totals.lock() else {
+            return;
+        };
+        let entry = totals.entry(model.to_string()).or_default();
+        entry.requests += 1;
+        entry.input_tokens += u64::from(input_tokens);
+        entry.output_tokens += u64::from(output_tokens);
+    }
+
+    /// Usage sorted by model name
+    pub fn snapshot(&self) -> Vec<(String, ModelUsage)> {
+        let mut rows: Vec<(String, ModelUsage)> = self
+            .totals
+            .lock()
+            .map(|totals| totals.iter().map(|(k, v)| (k.clone(), *v)).collect())
+            .unwrap_or_default();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        rows
+    }
+}
+
+/// Human-readable session summary, one line per model
+pub fn format_summary(rows: &[(String, ModelUsage)], catalog: &PriceCatalog) -> Vec<String> {
+    let mut total = 0.0;
+    let mut lines: Vec<String> = rows
+        .iter()
+        .map(|(model, usage)| {
+            let cost = match catalog.lookup(model) {
+                Some(price) => {
+                    let cost = price.cost(usage);
+                    total += cost;
+                    format!("~${:.4}", cost)
+                }
+                None => "no pricing".to_string(),
+            };
+            format!(
+                "  {}: {} requests, {} in / {} out tokens, {}",
+                model, usage.requests, usage.input_tokens, usage.output_tokens, cost
+            )
+        })
+        .collect();
+    if rows.len() > 1 {
+        lines.push(format!("  Total: ~${:.4}", total));
+    }
+    lines
+}
+
+/// One model's usage within a recorded session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryModel {
+    pub model: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+/// A finished session as stored in the usage history; `models` is empty when the
+/// session did not go through the proxy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix seconds when the session ended
+    pub timestamp: u64,
+    pub profile: String,
+    pub models: Vec<HistoryModel>,
+    /// Directory and git branch the session was launched from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<ProjectContext>,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        timestamp: u64,
+        profile: &str,
+        rows: &[(String, ModelUsage)],
+        catalog: &PriceCatalog,
+    ) -> Self {
+        let models = rows
+            .iter()
+            .map(|(model, usage)| HistoryModel {
+                model: model.clone(),
+                requests: usage.requests,
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+                cost_usd: catalog.lookup(model).map(|price| price.cost(usage)),
+            })
+            .collect();
+        Self {
+            timestamp,
+            profile: profile.to_string(),
+            models,
+            project: None,
+        }
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    Config::config_dir().map(|p| p.join(USAGE_HISTORY_FILE))
+}
+
+/// Append a finished session to the usage history
+pub fn append_history(entry: &HistoryEntry) -> Result<()> {
+    let path = history_file().context("Could not determine config directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// Read every session in the usage history, skipping malformed lines
+pub fn read_history() -> Vec<HistoryEntry> {
+    let Some(content) = history_file().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Why a launch deserves a second look: the profile's priciest configured model and
+/// what its last session cost
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostEstimate {
+    pub model: String,
+    pub price: ModelPrice,
+    /// Tokens of the profile's last proxied session, over all its models
+    pub last_session: ModelUsage,
+    /// Recorded cost of that session
+    pub last_session_usd: f64,
+    /// The same tokens priced at `model`
+    pub projected_usd: f64,
+}
+
+impl CostEstimate {
+    /// An estimate when one of `models` is expensive and `last` cost more than the
+    /// ceiling allows; `None` means the launch can go ahead
+    pub fn check<'a>(
+        models: impl IntoIterator<Item = &'a str>,
+        last: &HistoryEntry,
+        catalog: &PriceCatalog,
+        ceiling: &CostCeiling,
+    ) -> Option<Self> {
+        if !ceiling.enabled {
+            return None;
+        }
+        let (model, price) = models
+            .into_iter()
+            .filter_map(|model| Some((model, catalog.lookup(model)?)))
+            .max_by(|a, b| a.1.output.total_cmp(&b.1.output))?;
+        let last_session_usd: f64 = last.models.iter().filter_map(|m| m.cost_usd).sum();
+        if price.output < ceiling.min_output_price || last_session_usd < ceiling.last_session_usd {
+            return None;
+        }
+        let mut last_session = ModelUsage::default();
+        for model in &last.models {
+            last_session.requests += model.requests;
+            last_session.input_tokens += model.input_tokens;
+            last_session.output_tokens += model.output_tokens;
+        }
+        Some(Self {
+            model: model.to_string(),
+            price,
+            last_session,
+            last_session_usd,
+            projected_usd: price.cost(&last_session),
+        })
+    }
+}
+
+/// Upstream models that served a session, busiest first, e.g. `qwen3-coder ×12`
+pub fn format_upstream_models(models: &[HistoryModel]) -> String {
+    let mut models: Vec<&HistoryModel> = models.iter().collect();
+    models.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.model.cmp(&b.model)));
+    models
+        .iter()
+        .map(|model| format!("{} ×{}", model.model, model.requests))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Lifetime totals across the usage history
+pub fn format_history_totals(entries: &[HistoryEntry]) -> String {
+    let (mut requests, mut input, mut output, mut cost) = (0u64, 0u64, 0u64, 0.0);
+    for model in entries.iter().flat_map(|entry| &entry.models) {
+        requests += model.requests;
+        input += model.input_tokens;
+        output += model.output_tokens;
+        cost += model.cost_usd.unwrap_or_default();
+    }
+    format!(
+        "{} sessions, {} requests, {} in / {} out tokens, ~${:.4}",
+        entries.len(),
+        requests,
+        input,
+        output,
+        cost
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_catalog() -> PriceCatalog {
+        PriceCatalog::from_models_dev(&json!({
+            "openai": {
+                "models": {
+                    "gpt-5.2-codex": { "cost": { "input": 1.25, "output": 10.0 } },
+                    "free-model": { "name": "no cost field" }
+                }
+            },
+            "openrouter": {
+                "models": {
+                    "gpt-5.2-codex": { "cost": { "input": 2.0, "output": 20.0 } }
+                }
+            }
+        }))
+    }
+
+    #[test]
+    fn lookup_strips_provider_and_effort_suffix() {
+        let catalog = sample_catalog();
+        let expected = ModelPrice {
+            input: 1.25,
+            output: 10.0,
+        };
+
+        assert_eq!(catalog.lookup("gpt-5.2-codex"), Some(expected));
+        assert_eq!(catalog.lookup("GPT-5.2-Codex-high"), Some(expected));
+        assert_eq!(catalog.lookup("openai/gpt-5.2-codex"), Some(expected));
+        assert_eq!(
+            catalog.lookup("openrouter/gpt-5.2-codex").map(|p| p.input),
+            Some(2.0)
+        );
+        assert_eq!(catalog.lookup("free-model"), None);
+    }
+
+    #[test]
+    fn summary_prices_known_models() {
+        let tracker = UsageTracker::default();
+        tracker.record("gpt-5.2-codex-medium", 1_000_000, 100_000);
+        tracker.record("gpt-5.2-codex-medium", 0, 0);
+        tracker.record("local-model", 10, 5);
+
+        let lines = format_summary(&tracker.snapshot(), &sample_catalog());
+
+        assert_eq!(
+            lines[0],
+            "  gpt-5.2-codex-medium: 2 requests, 1000000 in / 100000 out tokens, ~$2.2500"
+        );
+        assert!(lines[1].ends_with("no pricing"));
+        assert_eq!(lines[2], "  Total: ~$2.2500");
+    }
+
+    #[test]
+    fn history_totals_sum_sessions() {
+        let tracker = UsageTracker::default();
+        tracker.record("gpt-5.2-codex", 1_000_000, 100_000);
+        tracker.record("local-model", 10, 5);
+        let entry = HistoryEntry::new(
+            1_700_000_000,
+            "codex",
+            &tracker.snapshot(),
+            &sample_catalog(),
+        );
+
+        let line = serde_json::to_string(&entry).unwrap();
+        let parsed: HistoryEntry = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed, entry);
+        assert_eq!(parsed.models[1].cost_usd, None);
+
+        assert_eq!(
+            format_history_totals(&[entry.clone(), entry]),
+            "2 sessions, 4 requests, 2000020 in / 200010 out tokens, ~$4.5000"
+        );
+    }
+    #[test]
+    fn upstream_models_list_busiest_first() {
+        let tracker = UsageTracker::default();
+        tracker.record("qwen3-coder", 10, 5);
+        tracker.record("gpt-4o-mini", 10, 5);
+        tracker.record("gpt-4o-mini", 10, 5);
+        let entry = HistoryEntry::new(0, "local", &tracker.snapshot(), &sample_catalog());
+
+        assert_eq!(
+            format_upstream_models(&entry.models),
+            "gpt-4o-mini ×2, qwen3-coder ×1"
+        );
+    }
+
+    #[test]
+    fn cost_ceiling_flags_expensive_models_after_costly_sessions() {
+        let tracker = UsageTracker::default();
+        tracker.record("gpt-5.2-codex", 2_000_000, 200_000);
+        tracker.record("local-model", 1_000, 100);
+        let last = HistoryEntry::new(0, "codex", &tracker.snapshot(), &sample_catalog());
+        let ceiling = CostCeiling {
+            enabled: true,
+            min_output_price: 10.0,
+            last_session_usd: 4.0,
+        };
+        let catalog = sample_catalog();
+
+        let estimate =
+            CostEstimate::check(["local-model", "gpt-5.2-codex"], &last, &catalog, &ceiling)
+                .unwrap();
+        assert_eq!(estimate.model, "gpt-5.2-codex");
+        assert_eq!(estimate.last_session_usd, 4.5);
+        assert_eq!(estimate.last_session.requests, 2);
+        // The local model's tokens are priced at the expensive model too
+        assert!(estimate.projected_usd > 4.5);
+
+        let cheap_session = CostCeiling {
+            last_session_usd: 5.0,
+            ..ceiling
+        };
+        assert_eq!(
+            CostEstimate::check(["gpt-5.2-codex"], &last, &catalog, &cheap_session),
+            None
+        );
+        assert_eq!(
+            CostEstimate::check(["local-model"], &last, &catalog, &ceiling),
+            None
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Where a session was started: the working directory and its git state, shown in the
+//! launch banner and kept with the session in the usage history.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectContext {
+    pub dir: String,
+    /// Checked-out branch, `detached@<sha>` for a detached HEAD, or `None` outside git
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Uncommitted changes (including untracked files) when the session started
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dirty: bool,
+}
+
+/// Trimmed stdout of `git -C <dir> <args>`, if git ran and succeeded
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl ProjectContext {
+    pub fn detect(dir: &Path) -> Self {
+        let branch = git(dir, &["symbolic-ref", "--short", "-q", "HEAD"]).or_else(|| {
+            git(dir, &["rev-parse", "--short", "HEAD"]).map(|sha| format!("detached@{}", sha))
+        });
+        let dirty = branch.is_some()
+            && git(dir, &["status", "--porcelain"]).is_some_and(|status| !status.is_empty());
+        Self {
+            dir: dir.display().to_string(),
+            branch,
+            dirty,
+        }
+    }
+
+    /// The current directory's context, when it can be determined
+    pub fn current() -> Option<Self> {
+        std::env::current_dir().ok().map(|dir| Self::detect(&dir))
+    }
+}
+
+impl std::fmt::Display for ProjectContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.dir)?;
+        match (&self.branch, self.dirty) {
+            (Some(branch), true) => write!(f, " ({}, uncommitted changes)", branch),
+            (Some(branch), false) => write!(f, " ({})", branch),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_reads_branch_and_dirty_state() {
+        let dir =
+            std::env::temp_dir().join(format!("claude-profiler-project-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(ProjectContext::detect(&dir).branch, None);
+
+        git(&dir, &["init", "-q", "-b", "feature/x"]).unwrap();
+        let clean = ProjectContext::detect(&dir);
+        assert_eq!(clean.branch.as_deref(), Some("feature/x"));
+        assert!(!clean.dirty);
+        assert_eq!(clean.to_string(), format!("{} (feature/x)", dir.display()));
+
+        std::fs::write(dir.join("notes.txt"), "wip").unwrap();
+        let dirty = ProjectContext::detect(&dir);
+        assert!(dirty.dirty);
+        assert!(
+            dirty
+                .to_string()
+                .ends_with("(feature/x, uncommitted changes)")
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,177 @@
+//! What common upstream failures mean and how to fix them.
+//!
+//! Providers word the same problem differently and wrap it in JSON of their own.
+//! `diagnose` recognises the failures users can fix themselves (quota, unknown
+//! model, bad key, unsupported region) so the proxy log, the status bar and the
+//! replies Claude Code shows can say what to do instead of repeating the raw body.
+
+use serde_json::Value;
+
+/// A recognised upstream failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnosis {
+    pub problem: &'static str,
+    pub fix: &'static str,
+}
+
+const QUOTA_EXCEEDED: Diagnosis = Diagnosis {
+    problem: "The provider account is out of quota or credits",
+    fix: "Add credits or raise the spending limit in the provider's billing console \
+          (o in the profile list opens it)",
+};
+
+const MODEL_NOT_FOUND: Diagnosis = Diagnosis {
+    problem: "The provider does not know the requested model",
+    fix: "Check the profile's model names against the provider's model list; local \
+          servers may need the model pulled or loaded first",
+};
+
+const INVALID_KEY: Diagnosis = Diagnosis {
+    problem: "The provider rejected the API key",
+    fix: "Check the profile's ANTHROPIC_AUTH_TOKEN (or its keychain entry) and that \
+          the key belongs to this provider; sign-in profiles need signing in again",
+};
+
+const REGION_BLOCKED: Diagnosis = Diagnosis {
+    problem: "The provider does not serve requests from this country or region",
+    fix: "Use a profile whose provider or endpoint region is available where you are",
+};
+
+const REGION_HINTS: &[&str] = &[
+    "unsupported_country",
+    "country, region, or territory",
+    "user location is not supported",
+    "not available in your region",
+    "unsupported region",
+];
+
+const QUOTA_HINTS: &[&str] = &[
+    "insufficient_quota",
+    "exceeded your current quota",
+    "credit balance",
+    "insufficient credits",
+    "insufficient balance",
+    "billing",
+];
+
+const KEY_HINTS: &[&str] = &[
+    "api key",
+    "api_key",
+    "x-api-key",
+    "unauthorized",
+    "authentication",
+    "auth credentials",
+];
+
+const NOT_FOUND_HINTS: &[&str] = &[
+    "not_found",
+    "not found",
+    "does not exist",
+    "not a valid model",
+    "unknown model",
+    "invalid model",
+];
+
+/// Recognise an upstream failure from its status and body
+pub fn diagnose(status: u16, body: &str) -> Option<Diagnosis> {
+    let body = body.to_ascii_lowercase();
+    let mentions = |hints: &[&str]| hints.iter().any(|hint| body.contains(hint));
+    if matches!(status, 400 | 403 | 451) && mentions(REGION_HINTS) {
+        Some(REGION_BLOCKED)
+    } else if status == 402 || (matches!(status, 400 | 403 | 429) && mentions(QUOTA_HINTS)) {
+        Some(QUOTA_EXCEEDED)
+    } else if status == 401 || (status == 403 && mentions(KEY_HINTS)) {
+        Some(INVALID_KEY)
+    } else if matches!(status, 400 | 404) && body.contains("model") && mentions(NOT_FOUND_HINTS) {
+        Some(MODEL_NOT_FOUND)
+    } else {
+        None
+    }
+}
+
+/// The human part of an error body: the message of the usual JSON error shapes, or
+/// the start of the body when it is not JSON
+pub fn upstream_message(body: &str) -> String {
+    let message = serde_json::from_str::<Value>(body).ok().and_then(|value| {
+        // Gemini wraps its error in an array
+        let value = value.get(0).unwrap_or(&value);
+        let error = value.get("error").unwrap_or(value);
+        error
+            .as_str()
+            .or_else(|| error.get("message").and_then(Value::as_str))
+            .or_else(|| value.get("message").and_then(Value::as_str))
+            .or_else(|| value.get("detail").and_then(Value::as_str))
+            .map(str::to_string)
+    });
+    let message = message.unwrap_or_else(|| body.split_whitespace().collect::<Vec<_>>().join(" "));
+    match message.char_indices().nth(200) {
+        Some((end, _)) => format!("{}...", &message[..end]),
+        None => message,
+    }
+}
+
+/// One line for the user: the problem and its fix when recognised, followed by what
+/// the upstream said
+pub fn describe(status: u16, body: &str) -> String {
+    let message = upstream_message(body);
+    let said = if message.is_empty() {
+        format!("HTTP {}", status)
+    } else {
+        format!("HTTP {}: {}", status, message)
+    };
+    match diagnose(status, body) {
+        Some(diagnosis) => format!("{}. {} ({})", diagnosis.problem, diagnosis.fix, said),
+        None => said,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provider_errors_are_recognised_and_explained() {
+        let openai_quota = r#"{"error":{"message":"You exceeded your current quota, please check your plan and billing details.","type":"insufficient_quota","code":"insufficient_quota"}}"#;
+        assert_eq!(diagnose(429, openai_quota), Some(QUOTA_EXCEEDED));
+        assert_eq!(
+            diagnose(402, r#"{"error":{"message":"Insufficient credits"}}"#),
+            Some(QUOTA_EXCEEDED)
+        );
+        assert_eq!(
+            diagnose(
+                404,
+                r#"{"error":"model \"qwen3:8b\" not found, try pulling it first"}"#
+            ),
+            Some(MODEL_NOT_FOUND)
+        );
+        assert_eq!(
+            diagnose(
+                401,
+                r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#
+            ),
+            Some(INVALID_KEY)
+        );
+        assert_eq!(
+            diagnose(
+                403,
+                r#"{"error":{"code":"unsupported_country_region_territory","message":"Country, region, or territory not supported"}}"#
+            ),
+            Some(REGION_BLOCKED)
+        );
+        // Rate limits and unknown endpoints are not the user's to fix
+        assert_eq!(diagnose(429, r#"{"error":{"message":"slow down"}}"#), None);
+        assert_eq!(diagnose(404, "404 page not found"), None);
+
+        assert_eq!(
+            describe(429, openai_quota),
+            "The provider account is out of quota or credits. Add credits or raise the \
+             spending limit in the provider's billing console (o in the profile list \
+             opens it) (HTTP 429: You exceeded your current quota, please check your \
+             plan and billing details.)"
+        );
+        assert_eq!(
+            describe(500, "<html>\n  Internal error\n</html>"),
+            "HTTP 500: <html> Internal error </html>"
+        );
+    }
+}
@@ -0,0 +1,441 @@
+//! Managing profiles from scripts and dotfile installers, without the TUI.
+//!
+//! `claude-profiler add --from-json <file|->` takes one profile as JSON, or an array
+//! of them, with the same fields as a `[[profiles]]` table in `profiles.toml`:
+//!
+//! ```json
+//! {"name": "work", "description": "Team gateway", "notes": "Billed to infra",
+//!  "env": {"ANTHROPIC_BASE_URL": "https://gateway.example.com"}}
+//! ```
+//!
+//! `claude-profiler profile <list|show|add|edit|remove>` works on one profile at a
+//! time with flags, and prints profiles as text or `--json`.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+
+use crate::config::{
+    Config, ENV_HAIKU_SAMPLING, ENV_OPUS_SAMPLING, ENV_SONNET_SAMPLING, Profile, env_reference,
+    keychain_reference,
+};
+use crate::proxy;
+use crate::redact::{SecretMask, is_sensitive_key};
+
+const USAGE: &str = "Usage: claude-profiler add --from-json <file|-> [--replace]";
+
+const PROFILE_USAGE: &str = "Usage: claude-profiler profile list [--json] [--reveal]
+       claude-profiler profile show <name> [--json] [--reveal]
+       claude-profiler profile add <name> [<fields>] [--replace]
+       claude-profiler profile edit <name> [<fields>] [--rename <name>] [--unset <KEY>]...
+                                    [--untag <tag>]...
+       claude-profiler profile remove <name>
+Fields: --description <text> --notes <text> --tag <tag>... --env KEY=VALUE...";
+
+/// Check a profile the way the editor checks it
+fn check_profile(profile: &Profile) -> Result<()> {
+    for key in [ENV_HAIKU_SAMPLING, ENV_SONNET_SAMPLING, ENV_OPUS_SAMPLING] {
+        if let Some(spec) = profile.env.get(key) {
+            proxy::Sampling::parse(spec)
+                .map_err(|e| anyhow::anyhow!("Profile '{}': {}: {}", profile.name, key, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Profiles in a JSON document, checked the way the editor checks them
+fn parse_profiles(json: &str) -> Result<Vec<Profile>> {
+    let value: serde_json::Value = serde_json::from_str(json).context("Invalid JSON")?;
+    let profiles: Vec<Profile> = if value.is_array() {
+        serde_json::from_value(value)
+    } else {
+        serde_json::from_value(value).map(|profile| vec![profile])
+    }
+    .context("Not a profile (expected an object with at least a \"name\")")?;
+
+    for profile in &profiles {
+        check_profile(profile)?;
+    }
+    Ok(profiles)
+}
+
+pub fn run_command(args: &[String]) -> Result<()> {
+    let mut source = None;
+    let mut replace = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from-json" => source = args.next(),
+            "--replace" => replace = true,
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+    let source = source.context(USAGE)?;
+
+    let json = if source == "-" {
+        let mut json = String::new();
+        std::io::stdin()
+            .read_to_string(&mut json)
+            .context("Failed to read stdin")?;
+        json
+    } else {
+        std::fs::read_to_string(source).with_context(|| format!("Failed to read {}", source))?
+    };
+    let profiles = parse_profiles(&json)?;
+
+    let mut config = Config::load()?;
+    let mut report = Vec::new();
+    for profile in profiles {
+        let name = profile.name.clone();
+        let verb = if config.add_profile(profile, replace)? {
+            "Replaced"
+        } else {
+            "Added"
+        };
+        report.push(format!("{} profile '{}'", verb, name));
+    }
+    config.save()?;
+    for line in report {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Changes to a profile given as `profile add` and `profile edit` flags
+#[derive(Debug, Default)]
+struct ProfileEdits {
+    description: Option<String>,
+    notes: Option<String>,
+    tags: Vec<String>,
+    untag: Vec<String>,
+    env: Vec<(String, String)>,
+    unset: Vec<String>,
+    rename: Option<String>,
+    replace: bool,
+}
+
+impl ProfileEdits {
+    /// Parse the flags; `editing` allows the ones that only make sense on an
+    /// existing profile, and `--replace` otherwise
+    fn parse(args: &[String], editing: bool) -> Result<Self> {
+        let mut edits = Self::default();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .with_context(|| format!("{} needs a value", flag))
+            };
+            match flag.as_str() {
+                "--description" => edits.description = Some(value()?),
+                "--notes" => edits.notes = Some(value()?),
+                "--tag" => edits.tags.push(value()?),
+                "--env" => {
+                    let pair = value()?;
+                    let (key, value) = pair
+                        .split_once('=')
+                        .filter(|(key, _)| !key.trim().is_empty())
+                        .with_context(|| format!("--env expects KEY=VALUE, got '{}'", pair))?;
+                    edits.env.push((key.trim().to_string(), value.to_string()));
+                }
+                "--untag" if editing => edits.untag.push(value()?),
+                "--unset" if editing => edits.unset.push(value()?),
+                "--rename" if editing => edits.rename = Some(value()?),
+                "--replace" if !editing => edits.replace = true,
+                _ => anyhow::bail!("Unknown option '{}'\n{}", flag, PROFILE_USAGE),
+            }
+        }
+        Ok(edits)
+    }
+
+    fn apply(&self, profile: &mut Profile) {
+        if let Some(name) = &self.rename {
+            profile.name = name.clone();
+        }
+        if let Some(description) = &self.description {
+            profile.description = description.clone();
+        }
+        if let Some(notes) = &self.notes {
+            profile.notes = notes.clone();
+        }
+        profile.tags.retain(|tag| !self.untag.contains(tag));
+        for tag in &self.tags {
+            if !profile.tags.contains(tag) {
+                profile.tags.push(tag.clone());
+            }
+        }
+        for key in &self.unset {
+            profile.env.remove(key);
+        }
+        for (key, value) in &self.env {
+            profile.env.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+/// A value as `list` and `show` print it: secrets masked (as `[logging] secrets` says)
+/// unless revealed, references to the environment or the keychain as they are
+fn printed_value(key: &str, value: &str, mask: &SecretMask, reveal: bool) -> String {
+    if reveal
+        || !is_sensitive_key(key)
+        || env_reference(value).is_some()
+        || keychain_reference(value).is_some()
+    {
+        value.to_string()
+    } else {
+        mask.mask(value)
+    }
+}
+
+/// The profile with its secrets masked for printing
+fn printable(profile: &Profile, mask: &SecretMask, reveal: bool) -> Profile {
+    let mut profile = profile.clone();
+    for (key, value) in profile.env.iter_mut().chain(profile.headers.iter_mut()) {
+        *value = printed_value(key, value, mask, reveal);
+    }
+    profile
+}
+
+fn describe_profile(profile: &Profile) -> String {
+    let mut lines = vec![format!("Name: {}", profile.name)];
+    if !profile.description.is_empty() {
+        lines.push(format!("Description: {}", profile.description));
+    }
+    if !profile.tags.is_empty() {
+        lines.push(format!("Tags: {}", profile.tags.join(", ")));
+    }
+    if let Some(date) = profile.key_expires_on {
+        lines.push(format!("Key expires: {}", date));
+    }
+    if !profile.notes.is_empty() {
+        lines.push(format!("Notes: {}", profile.notes));
+    }
+    let mut env: Vec<_> = profile.env.iter().collect();
+    env.sort();
+    for (key, value) in env {
+        lines.push(format!("  {}={}", key, value));
+    }
+    lines.join("\n")
+}
+
+fn find_profile<'a>(config: &'a Config, name: &str) -> Result<&'a Profile> {
+    config
+        .profiles
+        .iter()
+        .find(|p| p.name == name)
+        .with_context(|| format!("No profile named '{}'", name))
+}
+
+/// Run a `profile` subcommand against `config`. Returns what to print and whether
+/// the config changed and needs saving.
+fn profile_command(config: &mut Config, args: &[String]) -> Result<(String, bool)> {
+    let flag = |name: &str| args.iter().skip(1).any(|arg| arg == name);
+    let name = || {
+        args.get(1)
+            .filter(|name| !name.starts_with("--"))
+            .cloned()
+            .context(PROFILE_USAGE)
+    };
+    match args.first().map(String::as_str) {
+        Some("list") => {
+            let profiles = config
+                .profiles
+                .iter()
+                .map(|profile| printable(profile, &config.logging.secrets, flag("--reveal")));
+            if flag("--json") {
+                let profiles: Vec<Profile> = profiles.collect();
+                return Ok((serde_json::to_string_pretty(&profiles)?, false));
+            }
+            let lines: Vec<String> = profiles
+                .map(|profile| match profile.description.as_str() {
+                    "" => profile.name,
+                    description => format!("{}\t{}", profile.name, description),
+                })
+                .collect();
+            Ok((lines.join("\n"), false))
+        }
+        Some("show") => {
+            let profile = printable(
+                find_profile(config, &name()?)?,
+                &config.logging.secrets,
+                flag("--reveal"),
+            );
+            if flag("--json") {
+                return Ok((serde_json::to_string_pretty(&profile)?, false));
+            }
+            Ok((describe_profile(&profile), false))
+        }
+        Some("add") => {
+            let name = name()?;
+            let edits = ProfileEdits::parse(&args[2..], false)?;
+            let mut profile = Profile {
+                name: name.clone(),
+                ..Default::default()
+            };
+            edits.apply(&mut profile);
+            check_profile(&profile)?;
+            let verb = if config.add_profile(profile, edits.replace)? {
+                "Replaced"
+            } else {
+                "Added"
+            };
+            Ok((format!("{} profile '{}'", verb, name), true))
+        }
+        Some("edit") => {
+            let name = name()?;
+            let edits = ProfileEdits::parse(&args[2..], true)?;
+            let mut profile = find_profile(config, &name)?.clone();
+            if let Some(source) = config.included_source(&profile) {
+                anyhow::bail!("Profile '{}' comes from {}; edit it there", name, source);
+            }
+            edits.apply(&mut profile);
+            check_profile(&profile)?;
+            if profile.name.trim().is_empty() {
+                anyhow::bail!("Profile name must not be empty");
+            }
+            if profile.name != name && find_profile(config, &profile.name).is_ok() {
+                anyhow::bail!("Profile '{}' already exists", profile.name);
+            }
+            let new_name = profile.name.clone();
+            if let Some(existing) = config.profiles.iter_mut().find(|p| p.name == name) {
+                *existing = profile;
+            }
+            if new_name != name {
+                config.retarget_workspaces(&name, Some(&new_name));
+                config.retarget_last_used(&name, Some(&new_name));
+            }
+            Ok((format!("Updated profile '{}'", new_name), true))
+        }
+        Some("remove") if args.len() == 2 => {
+            let name = name()?;
+            let profile = find_profile(config, &name)?;
+            if let Some(source) = config.included_source(profile) {
+                anyhow::bail!("Profile '{}' comes from {}; remove it there", name, source);
+            }
+            config.profiles.retain(|p| p.name != name);
+            config.retarget_workspaces(&name, None);
+            config.retarget_last_used(&name, None);
+            Ok((format!("Removed profile '{}'", name), true))
+        }
+        _ => anyhow::bail!(PROFILE_USAGE),
+    }
+}
+
+/// `claude-profiler profile <command>`
+pub fn run_profile_command(args: &[String]) -> Result<()> {
+    let mut config = Config::load()?;
+    let (output, changed) = profile_command(&mut config, args)?;
+    if changed {
+        config.save()?;
+    }
+    if !output.is_empty() {
+        println!("{}", output);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IncludedProfile;
+
+    #[test]
+    fn json_profiles_are_added_once_unless_replaced() {
+        let profiles = parse_profiles(
+            r#"[{"name": "work", "notes": "Billed to infra",
+                 "env": {"ANTHROPIC_BASE_URL": "https://gateway.example.com"}},
+                {"name": "local", "env": {"PROXY_SONNET_SAMPLING": "temperature=0.7"}}]"#,
+        )
+        .unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].notes, "Billed to infra");
+        assert!(parse_profiles(r#"{"description": "no name"}"#).is_err());
+        assert!(
+            parse_profiles(r#"{"name": "x", "env": {"PROXY_OPUS_SAMPLING": "temp=hot"}}"#).is_err()
+        );
+
+        let mut config = Config::create_default();
+        let work = profiles[0].clone();
+        assert!(!config.add_profile(work.clone(), false).unwrap());
+        assert!(config.add_profile(work.clone(), false).is_err());
+        assert!(config.add_profile(work, true).unwrap());
+        assert_eq!(
+            config.profiles.iter().filter(|p| p.name == "work").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn profiles_are_managed_with_flags_and_printed_without_secrets() {
+        let args = |line: &str| line.split(' ').map(str::to_string).collect::<Vec<_>>();
+        let mut config = Config::create_default();
+        config.profiles.clear();
+        config.last_used.insert("work".to_string(), 1);
+
+        let (output, changed) = profile_command(
+            &mut config,
+            &args(
+                "add work --description Gateway --tag team \
+                 --env ANTHROPIC_AUTH_TOKEN=sk-ant-0123456789abcdef --env ANTHROPIC_MODEL=opus",
+            ),
+        )
+        .unwrap();
+        assert_eq!((output.as_str(), changed), ("Added profile 'work'", true));
+        assert!(profile_command(&mut config, &args("add work")).is_err());
+        assert!(profile_command(&mut config, &args("add x --env =1")).is_err());
+        assert!(profile_command(&mut config, &args("add x --rename y")).is_err());
+
+        profile_command(
+            &mut config,
+            &args(
+                "edit work --rename team-work --unset ANTHROPIC_MODEL --tag gateway --untag team",
+            ),
+        )
+        .unwrap();
+        let profile = &config.profiles[0];
+        assert_eq!(profile.name, "team-work");
+        assert_eq!(profile.tags, ["gateway"]);
+        assert!(!profile.env.contains_key("ANTHROPIC_MODEL"));
+        assert_eq!(config.last_used.get("team-work"), Some(&1));
+
+        let (json, changed) = profile_command(&mut config, &args("show team-work --json")).unwrap();
+        assert!(!changed);
+        let shown: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(shown["description"], "Gateway");
+        assert_eq!(shown["env"]["ANTHROPIC_AUTH_TOKEN"], "sk-a...cdef");
+        config.logging.secrets.reveal = 0;
+        let (json, _) = profile_command(&mut config, &args("show team-work --json")).unwrap();
+        assert!(!json.contains("sk-a"));
+        let (json, _) = profile_command(&mut config, &args("list --json --reveal")).unwrap();
+        assert!(json.contains("sk-ant-0123456789abcdef"));
+        let (list, _) = profile_command(&mut config, &args("list")).unwrap();
+        assert_eq!(list, "team-work\tGateway");
+
+        profile_command(&mut config, &args("remove team-work")).unwrap();
+        assert!(config.profiles.is_empty());
+        assert!(config.last_used.is_empty());
+    }
+
+    #[test]
+    fn included_profiles_are_changed_in_their_fragment() {
+        let args = |line: &str| line.split(' ').map(str::to_string).collect::<Vec<_>>();
+        let mut config = Config::create_default();
+        let shared = config.profiles[1].clone();
+        config.included.insert(
+            shared.name.clone(),
+            IncludedProfile {
+                source: "work/shared.toml".to_string(),
+                profile: shared.clone(),
+            },
+        );
+        let refused = "Profile 'zai' comes from work/shared.toml; edit it there";
+
+        for line in ["edit zai --description Mine", "add zai --replace"] {
+            let error = profile_command(&mut config, &args(line)).unwrap_err();
+            assert_eq!(error.to_string(), refused);
+        }
+        let error = config.add_profile(shared.clone(), true).unwrap_err();
+        assert_eq!(error.to_string(), refused);
+        assert!(profile_command(&mut config, &args("remove zai")).is_err());
+        assert_eq!(config.profiles[1], shared);
+    }
+}
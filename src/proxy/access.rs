@@ -0,0 +1,42 @@
+//! Who may use the control endpoints (`/admin/reload` and the daemon's `/daemon/*`).
+//!
+//! The proxy can listen on other interfaces, and a browser may be pointed at it, so
+//! control requests are only accepted from this machine and never from a web page.
+//! Browsers send `Origin` with every cross-site POST (and with cross-origin fetches);
+//! curl, `claude-profiler ctl` and scripts do not.
+
+use super::*;
+use axum::extract::ConnectInfo;
+use axum::middleware::Next;
+use std::net::SocketAddr;
+
+/// Why a control request is refused, if it is: it comes from another machine (or a
+/// server that does not say where from), or from a web page
+pub(crate) fn control_refusal(request: &axum::extract::Request) -> Option<&'static str> {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>();
+    if !peer.is_some_and(|peer| peer.0.ip().to_canonical().is_loopback()) {
+        return Some("Control requests are only accepted from this machine");
+    }
+    if request.headers().contains_key(header::ORIGIN) {
+        return Some("Control requests are not accepted from web pages");
+    }
+    None
+}
+
+/// Route layer answering refused control requests with 403
+pub(crate) async fn local_control_only(request: axum::extract::Request, next: Next) -> Response {
+    if let Some(refusal) = control_refusal(&request) {
+        proxy_log(&format!(
+            "Refused {} {}: {}",
+            request.method(),
+            request.uri().path(),
+            refusal
+        ));
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "error": refusal })),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
@@ -0,0 +1,343 @@
+//! Auxiliary requests: token counts, prompt suggestions and structured (JSON
+//! prefilled) requests, which go to the profile's auxiliary model when it has one.
+//!
+//! A small auxiliary model can fail at these quietly: errors, or JSON Claude Code
+//! cannot parse, show up only as missing suggestions or titles. Each reply of the
+//! auxiliary model is checked, and a kind of request it fails repeatedly is sent
+//! to the main model for the rest of the session instead.
+
+use super::*;
+use std::sync::Mutex;
+
+/// Failures in a row after which a kind of request leaves the auxiliary model
+const PROMOTE_AFTER_FAILURES: u32 = 3;
+
+/// Kinds of auxiliary request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum AuxClass {
+    /// `max_tokens: 1`, sent to count tokens
+    TokenCount,
+    /// `[SUGGESTION MODE:` prompts for the next thing to type
+    Suggestion,
+    /// Replies continuing an assistant message prefilled with `{`
+    Structured,
+}
+
+impl AuxClass {
+    fn label(self) -> &'static str {
+        match self {
+            AuxClass::TokenCount => "token count",
+            AuxClass::Suggestion => "suggestion",
+            AuxClass::Structured => "structured",
+        }
+    }
+}
+
+/// Text of the last message when it is an assistant turn starting a JSON object
+/// and no tools are offered
+fn json_prefill(request: &AnthropicRequest) -> Option<String> {
+    if request
+        .tools
+        .as_ref()
+        .is_some_and(|tools| !tools.is_empty())
+    {
+        return None;
+    }
+    let last = request.messages.last().filter(|m| m.role == "assistant")?;
+    let (starts_with_brace, text) = match &last.content {
+        AnthropicContent::Text(text) => (text.trim_start().starts_with('{'), text.clone()),
+        AnthropicContent::Blocks(blocks) => {
+            let texts: Vec<&str> = blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text, .. } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect();
+            let starts = texts.iter().any(|text| text.trim_start().starts_with('{'));
+            (starts, texts.concat())
+        }
+    };
+    starts_with_brace.then_some(text)
+}
+
+fn mentions_suggestion_mode(request: &AnthropicRequest) -> bool {
+    let marked = |text: &str| text.contains("[SUGGESTION MODE:");
+    request.messages.iter().any(|msg| match &msg.content {
+        AnthropicContent::Text(text) => marked(text),
+        AnthropicContent::Blocks(blocks) => blocks
+            .iter()
+            .any(|block| matches!(block, ContentBlock::Text { text, .. } if marked(text))),
+    })
+}
+
+/// The kind of auxiliary request, or `None` for the main conversation
+pub(crate) fn auxiliary_class(request: &AnthropicRequest) -> Option<AuxClass> {
+    if request.max_tokens == Some(1) {
+        Some(AuxClass::TokenCount)
+    } else if mentions_suggestion_mode(request) {
+        Some(AuxClass::Suggestion)
+    } else if json_prefill(request).is_some() {
+        Some(AuxClass::Structured)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClassRecord {
+    failures_in_a_row: u32,
+    promoted: bool,
+}
+
+/// Kinds of auxiliary request moved to the main model this session
+#[derive(Debug, Default)]
+pub(crate) struct AuxPromotion {
+    classes: Mutex<HashMap<AuxClass, ClassRecord>>,
+}
+
+impl AuxPromotion {
+    pub(crate) fn is_promoted(&self, class: AuxClass) -> bool {
+        let classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+        classes.get(&class).is_some_and(|record| record.promoted)
+    }
+
+    /// Count a reply of the auxiliary `model`; returns whether this failure promoted
+    /// the class
+    fn record(&self, class: AuxClass, model: &str, failure: Option<&str>) -> bool {
+        let mut classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+        let record = classes.entry(class).or_default();
+        let Some(failure) = failure else {
+            record.failures_in_a_row = 0;
+            return false;
+        };
+        if record.promoted {
+            return false;
+        }
+        record.failures_in_a_row += 1;
+        proxy_log(&format!(
+            "Auxiliary model {} failed a {} request: {} ({} in a row)",
+            model,
+            class.label(),
+            failure,
+            record.failures_in_a_row
+        ));
+        if record.failures_in_a_row < PROMOTE_AFTER_FAILURES {
+            return false;
+        }
+        record.promoted = true;
+        proxy_log(&format!(
+            "Sending {} requests to the main model for the rest of the session \
+             instead of {}",
+            class.label(),
+            model
+        ));
+        true
+    }
+}
+
+/// What a reply of the auxiliary model is checked for
+pub(crate) struct AuxCheck {
+    class: AuxClass,
+    /// The prefilled start of the JSON a structured reply continues
+    prefill: Option<String>,
+}
+
+impl AuxCheck {
+    pub(crate) fn new(request: &AnthropicRequest) -> Option<Self> {
+        let class = auxiliary_class(request)?;
+        Some(Self {
+            class,
+            prefill: (class == AuxClass::Structured)
+                .then(|| json_prefill(request))
+                .flatten(),
+        })
+    }
+
+    /// Why the reply text is unusable, if it is
+    fn failure(&self, text: &str) -> Option<String> {
+        let prefill = self.prefill.as_deref()?;
+        serde_json::from_str::<Value>(&format!("{}{}", prefill, text))
+            .err()
+            .map(|e| format!("invalid JSON ({})", e))
+    }
+}
+
+/// Text of a non-streamed Anthropic message
+fn message_text(message: &Value) -> String {
+    message["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|block| block["text"].as_str())
+        .collect()
+}
+
+/// The next complete event of an event stream. Bytes are only decoded once the
+/// event is whole, so a character split across chunks arrives intact.
+fn take_event(buffer: &mut Vec<u8>) -> Option<String> {
+    let end = buffer.windows(2).position(|pair| pair == b"\n\n")?;
+    let event: Vec<u8> = buffer.drain(..end + 2).collect();
+    Some(String::from_utf8_lossy(&event).into_owned())
+}
+
+/// Watch a reply of the auxiliary `model` and record whether it was usable. A
+/// stream is passed on as it arrives and judged once it ends.
+pub(crate) async fn check_auxiliary_reply(
+    state: Arc<ProxyState>,
+    check: AuxCheck,
+    model: String,
+    response: Response,
+) -> Response {
+    use futures::StreamExt;
+
+    if !response.status().is_success() {
+        let failure = format!("HTTP {}", response.status().as_u16());
+        state
+            .aux_promotion
+            .record(check.class, &model, Some(&failure));
+        return response;
+    }
+    let is_sse = is_sse_response(&response);
+    let (parts, body) = response.into_parts();
+
+    if is_sse {
+        let data = body.into_data_stream();
+        let stream = async_stream::stream! {
+            futures::pin_mut!(data);
+            let mut buffer = Vec::new();
+            let mut text = String::new();
+            let mut failure = None;
+            while let Some(chunk) = data.next().await {
+                match &chunk {
+                    Ok(bytes) => buffer.extend_from_slice(bytes),
+                    Err(_) => failure = Some("stream cut off".to_string()),
+                }
+                while let Some(event) = take_event(&mut buffer) {
+                    for data in event.lines().filter_map(|line| line.strip_prefix("data:")) {
+                        let Ok(event) = serde_json::from_str::<Value>(data.trim()) else {
+                            continue;
+                        };
+                        match event["type"].as_str() {
+                            Some("content_block_delta") => {
+                                if let Some(delta) = event["delta"]["text"].as_str() {
+                                    text.push_str(delta);
+                                }
+                            }
+                            Some("error") => failure = Some("error event".to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                let failed = chunk.is_err();
+                yield chunk;
+                if failed {
+                    break;
+                }
+            }
+            let failure = failure.or_else(|| check.failure(&text));
+            state.aux_promotion.record(check.class, &model, failure.as_deref());
+        };
+        return Response::from_parts(parts, Body::from_stream(stream));
+    }
+
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        state
+            .aux_promotion
+            .record(check.class, &model, Some("unreadable reply"));
+        return StatusCode::BAD_GATEWAY.into_response();
+    };
+    let failure = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(message) => check.failure(&message_text(&message)),
+        Err(e) => Some(format!("unreadable reply ({})", e)),
+    };
+    state
+        .aux_promotion
+        .record(check.class, &model, failure.as_deref());
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn failing_auxiliary_requests_move_to_the_main_model() {
+        let options = ProxyOptions {
+            model_override: Some("main-model".to_string()),
+            auxiliary_model: Some("small-model".to_string()),
+            ..Default::default()
+        };
+        let state = build_state("mock:".to_string(), options).await.unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+
+        let client = reqwest::Client::new();
+        let upstream_model = |body: Value| {
+            let request = client.post(format!("{}/v1/messages", url)).json(&body);
+            async move {
+                let response = request.send().await.unwrap();
+                let model = response.headers()[UPSTREAM_MODEL_HEADER]
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                // A stream is judged once it has been read to the end
+                response.bytes().await.unwrap();
+                model
+            }
+        };
+        // The mock echoes the user, which does not continue the prefilled JSON
+        let structured = |stream: bool| {
+            json!({
+                "model": "claude-haiku-4-5",
+                "max_tokens": 64,
+                "stream": stream,
+                "messages": [
+                    { "role": "user", "content": "Title this conversation" },
+                    { "role": "assistant", "content": "{" }
+                ]
+            })
+        };
+        let token_count = json!({
+            "model": "claude-haiku-4-5",
+            "max_tokens": 1,
+            "messages": [{ "role": "user", "content": "count" }]
+        });
+
+        for stream in [false, true, false] {
+            assert_eq!(upstream_model(structured(stream)).await, "small-model");
+        }
+        assert_eq!(upstream_model(structured(true)).await, "main-model");
+        assert_eq!(upstream_model(token_count).await, "small-model");
+
+        // A usable reply resets the count
+        let promotion = AuxPromotion::default();
+        let class = AuxClass::Suggestion;
+        assert!(!promotion.record(class, "small", Some("HTTP 500")));
+        assert!(!promotion.record(class, "small", Some("HTTP 500")));
+        assert!(!promotion.record(class, "small", None));
+        assert!(!promotion.record(class, "small", Some("HTTP 500")));
+        assert!(!promotion.is_promoted(class));
+        assert!(!promotion.record(class, "small", Some("HTTP 500")));
+        assert!(promotion.record(class, "small", Some("HTTP 500")));
+        assert!(promotion.is_promoted(class));
+    }
+
+    #[test]
+    fn events_are_decoded_once_whole() {
+        let event = "data: {\"delta\":{\"text\":\"café\"}}\n\n".as_bytes();
+        // The chunk boundary falls inside `é`
+        let split = event.len() - 6;
+        let mut buffer = event[..split].to_vec();
+        assert_eq!(take_event(&mut buffer), None);
+        buffer.extend_from_slice(&event[split..]);
+        buffer.extend_from_slice(b"data: [DONE]");
+        assert_eq!(
+            take_event(&mut buffer).as_deref(),
+            Some("data: {\"delta\":{\"text\":\"café\"}}\n\n")
+        );
+        assert_eq!(buffer, b"data: [DONE]");
+    }
+}
@@ -0,0 +1,394 @@
+//! Optional request capture for the TUI's inspector: the body Claude Code sent, the
+//! translated request and the upstream's raw reply, and what went back to Claude Code,
+//! one JSON file per request under `captures/`.
+//!
+//! Like the forwarded headers, the capture travels in a task-local scope around the
+//! request, so `send_json_request` can record the upstream side of whichever handler
+//! sent it. Bodies are kept up to a size limit and only the newest captures are kept.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::*;
+
+/// Capture directory under the config directory
+pub const CAPTURE_DIR: &str = "captures";
+
+/// Captures kept on disk; older ones are deleted as new ones are written
+const CAPTURE_LIMIT: usize = 50;
+
+/// Longest body kept per field; the rest is cut off
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Marks a request as the replay of a capture, so the new capture can say so
+const REPLAY_HEADER: &str = "x-profiler-replay-of";
+
+tokio::task_local! {
+    static CAPTURE: Arc<PendingCapture>;
+}
+
+/// One proxied request with its bodies on both sides of the translation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Capture {
+    pub id: String,
+    /// Unix seconds when the request arrived
+    pub timestamp: u64,
+    pub profile: String,
+    /// Body Claude Code sent
+    pub request: String,
+    pub upstream_url: Option<String>,
+    /// Body sent upstream, after translation; of the last attempt when retried
+    pub upstream_request: Option<String>,
+    pub upstream_status: Option<u16>,
+    /// Upstream reply as received, events and all for streams
+    pub upstream_response: String,
+    /// Status and body returned to Claude Code
+    pub status: u16,
+    pub response: String,
+    /// Capture this request replayed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay_of: Option<String>,
+}
+
+impl Capture {
+    /// Model Claude Code asked for
+    pub fn model(&self) -> Option<String> {
+        body_model(&self.request)
+    }
+
+    /// Model sent upstream
+    pub fn upstream_model(&self) -> Option<String> {
+        self.upstream_request.as_deref().and_then(body_model)
+    }
+}
+
+fn body_model(body: &str) -> Option<String> {
+    serde_json::from_str::<Value>(body)
+        .ok()?
+        .get("model")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Add `bytes` to a captured body, up to the size limit
+fn append_body(body: &mut String, bytes: &[u8]) {
+    if body.len() >= MAX_BODY_BYTES {
+        return;
+    }
+    body.push_str(&String::from_utf8_lossy(bytes));
+    if body.len() >= MAX_BODY_BYTES {
+        let mut end = MAX_BODY_BYTES;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+        body.push_str("\n... (truncated)");
+    }
+}
+
+/// Writes captures for one profile's proxy session
+#[derive(Debug, Clone)]
+pub struct CaptureLog {
+    profile: String,
+    dir: PathBuf,
+}
+
+impl CaptureLog {
+    /// `None` when the config directory cannot be determined
+    pub fn new(profile: &str) -> Option<Self> {
+        Some(Self {
+            profile: profile.to_string(),
+            dir: Config::config_dir()?.join(CAPTURE_DIR),
+        })
+    }
+
+    /// A capture for a request that just arrived
+    pub(crate) fn start(&self, headers: &HeaderMap, body: &[u8]) -> Arc<PendingCapture> {
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut capture = Capture {
+            // Sorts by time, then by arrival within the same millisecond
+            id: format!(
+                "{:013}-{:04}",
+                now.as_millis(),
+                SEQUENCE.fetch_add(1, Ordering::Relaxed) % 10_000
+            ),
+            timestamp: now.as_secs(),
+            profile: self.profile.clone(),
+            replay_of: headers
+                .get(REPLAY_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            ..Default::default()
+        };
+        append_body(&mut capture.request, body);
+        Arc::new(PendingCapture {
+            dir: self.dir.clone(),
+            capture: Mutex::new(capture),
+        })
+    }
+}
+
+/// A capture still being filled in; written when the last reference is dropped,
+/// which is once both the upstream reply and Claude Code's response have been read
+pub(crate) struct PendingCapture {
+    dir: PathBuf,
+    capture: Mutex<Capture>,
+}
+
+impl PendingCapture {
+    fn update(&self, change: impl FnOnce(&mut Capture)) {
+        if let Ok(mut capture) = self.capture.lock() {
+            change(&mut capture);
+        }
+    }
+}
+
+impl Drop for PendingCapture {
+    fn drop(&mut self) {
+        let Ok(capture) = self.capture.get_mut() else {
+            return;
+        };
+        // Bodies hold the conversation, and at times keys pasted into it
+        if platform::create_private_dir(&self.dir).is_err() {
+            return;
+        }
+        if let Ok(json) = serde_json::to_string_pretty(capture) {
+            let path = self.dir.join(format!("{}.json", capture.id));
+            let _ = platform::write_private(&path, json.as_bytes());
+        }
+        prune_captures(&self.dir);
+    }
+}
+
+/// Delete all but the newest captures
+fn prune_captures(dir: &Path) {
+    let mut files = capture_files(dir);
+    if files.len() <= CAPTURE_LIMIT {
+        return;
+    }
+    files.sort();
+    for path in &files[..files.len() - CAPTURE_LIMIT] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+fn capture_files(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Saved captures, newest first
+pub fn load_captures() -> Vec<Capture> {
+    Config::config_dir()
+        .map(|dir| load_captures_from(&dir.join(CAPTURE_DIR)))
+        .unwrap_or_default()
+}
+
+fn load_captures_from(dir: &Path) -> Vec<Capture> {
+    let mut captures: Vec<Capture> = capture_files(dir)
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter_map(|json| serde_json::from_str(&json).ok())
+        .collect();
+    captures.sort_by(|a, b| b.id.cmp(&a.id));
+    captures
+}
+
+/// Record the translated request about to be sent upstream, if a capture is in scope
+pub(crate) fn note_upstream_request(url: &str, payload: &[u8]) {
+    let _ = CAPTURE.try_with(|pending| {
+        pending.update(|capture| {
+            let mut body = String::new();
+            append_body(&mut body, payload);
+            capture.upstream_url = Some(url.to_string());
+            capture.upstream_request = Some(body);
+            capture.upstream_status = None;
+            capture.upstream_response.clear();
+        })
+    });
+}
+
+/// Copy the upstream reply into the capture in scope as it is read
+pub(crate) fn tap_upstream_response(response: reqwest::Response) -> reqwest::Response {
+    use futures::StreamExt;
+
+    let Ok(pending) = CAPTURE.try_with(Arc::clone) else {
+        return response;
+    };
+    pending.update(|capture| capture.upstream_status = Some(response.status().as_u16()));
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes_stream().map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            pending.update(|capture| append_body(&mut capture.upstream_response, bytes));
+        }
+        chunk
+    });
+    let mut tapped = axum::http::Response::new(reqwest::Body::wrap_stream(body));
+    *tapped.status_mut() = status;
+    *tapped.headers_mut() = headers;
+    reqwest::Response::from(tapped)
+}
+
+/// Run a messages request with its capture in scope, if it has one, recording the
+/// response Claude Code gets as it is sent
+pub(crate) async fn with_capture(
+    pending: Option<Arc<PendingCapture>>,
+    request: impl Future<Output = Response>,
+) -> Response {
+    use futures::StreamExt;
+
+    let Some(pending) = pending else {
+        return request.await;
+    };
+    let response = CAPTURE.scope(pending.clone(), request).await;
+    pending.update(|capture| capture.status = response.status().as_u16());
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            pending.update(|capture| append_body(&mut capture.response, bytes));
+        }
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
+
+/// A replayed request's outcome
+#[derive(Debug, Clone)]
+pub struct Replayed {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Send a captured request again, through a proxy built from the profile's current
+/// settings and without listening on its port, so a running session is unaffected
+pub async fn replay(
+    proxy_target_url: String,
+    options: ProxyOptions,
+    capture: &Capture,
+    auth_token: Option<&str>,
+) -> anyhow::Result<Replayed> {
+    let state = build_state(proxy_target_url, options).await?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "anthropic-version",
+        header::HeaderValue::from_static("2023-06-01"),
+    );
+    if let Ok(id) = header::HeaderValue::from_str(&capture.id) {
+        headers.insert(REPLAY_HEADER, id);
+    }
+    if let Some(token) = auth_token
+        && let Ok(value) = header::HeaderValue::from_str(&format!("Bearer {}", token))
+    {
+        headers.insert(header::AUTHORIZATION, value);
+    }
+    let response = messages_handler(
+        State(state),
+        headers,
+        bytes::Bytes::from(capture.request.clone()),
+    )
+    .await;
+    let status = response.status().as_u16();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+    Ok(Replayed {
+        status,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn requests_are_captured_on_both_sides_and_replayed() {
+        let app = Router::new().route(
+            "/v1/chat/completions",
+            post(|| async {
+                r#"{"id":"c1","choices":[{"message":{"role":"assistant","content":"Hello"},"finish_reason":"stop"}],"usage":{"prompt_tokens":5,"completion_tokens":1}}"#
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let dir = std::env::temp_dir().join(format!("captures-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let options = || ProxyOptions {
+            model_override: Some("local-model".to_string()),
+            capture: Some(CaptureLog {
+                profile: "local".to_string(),
+                dir: dir.clone(),
+            }),
+            ..Default::default()
+        };
+        let request = json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "messages": [{ "role": "user", "content": "Hi" }],
+        });
+        let state = build_state(target.clone(), options()).await.unwrap();
+        let response = messages_handler(
+            State(state),
+            HeaderMap::new(),
+            bytes::Bytes::from(request.to_string()),
+        )
+        .await;
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        let captures = load_captures_from(&dir);
+        assert_eq!(captures.len(), 1);
+        let capture = &captures[0];
+        assert_eq!(capture.profile, "local");
+        assert_eq!(capture.model().as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(capture.upstream_model().as_deref(), Some("local-model"));
+        assert_eq!(capture.upstream_url.as_deref(), Some(target.as_str()));
+        assert_eq!(capture.upstream_status, Some(200));
+        assert!(
+            capture
+                .upstream_response
+                .contains("\"finish_reason\":\"stop\"")
+        );
+        assert_eq!(capture.status, 200);
+        let returned: Value = serde_json::from_str(&capture.response).unwrap();
+        assert_eq!(returned["content"][0]["text"], "Hello");
+
+        let replayed = replay(target, options(), capture, None).await.unwrap();
+        assert_eq!(replayed.status, 200);
+        assert!(replayed.body.contains("Hello"));
+        let captures = load_captures_from(&dir);
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[0].replay_of.as_ref(), Some(&capture.id));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&dir), 0o700);
+            for path in capture_files(&dir) {
+                assert_eq!(mode(&path), 0o600);
+            }
+        }
+
+        let mut body = String::new();
+        append_body(&mut body, &vec![b'x'; MAX_BODY_BYTES + 10]);
+        assert!(body.ends_with("(truncated)"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
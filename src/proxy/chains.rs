@@ -0,0 +1,208 @@
+//! Response chaining: follow-up Responses requests send only the new items along
+//! with `previous_response_id`.
+
+use super::*;
+
+/// Conversations remembered at once; the least recently used is dropped
+const MAX_RESPONSE_CHAINS: usize = 256;
+
+/// The last stored response of one conversation and the input that produced it
+#[derive(Debug, Clone)]
+struct ResponseChain {
+    response_id: String,
+    input_len: usize,
+    input_fingerprint: u64,
+    last_used: std::time::Instant,
+}
+
+/// Where to continue a conversation from instead of replaying it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChainPoint {
+    pub(crate) response_id: String,
+    /// First input item the upstream has not seen
+    pub(crate) new_items_from: usize,
+}
+
+/// Response ids per conversation, for Responses upstreams that keep state.
+///
+/// Claude Code resends the whole history every turn. When the start of a request
+/// matches the input of a response we already got, only the items after that
+/// response's own output are sent, with `previous_response_id` pointing at it.
+#[derive(Debug, Default)]
+pub struct ResponseChains {
+    chains: std::sync::Mutex<HashMap<u64, ResponseChain>>,
+}
+
+fn input_fingerprint(items: &[ResponseInputItem]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for item in items {
+        serde_json::to_string(item)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Items the upstream produced itself (and so already holds with the response)
+fn is_response_output(item: &ResponseInputItem) -> bool {
+    match item {
+        ResponseInputItem::Message { role, .. } => role == "assistant",
+        ResponseInputItem::FunctionCall { .. } => true,
+        ResponseInputItem::FunctionCallOutput { .. } => false,
+    }
+}
+
+/// Claude Code's session id (inside `metadata.user_id`), when present
+pub(crate) fn session_hint(request: &AnthropicRequest) -> Option<String> {
+    request
+        .metadata
+        .as_ref()?
+        .get("user_id")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Identify a conversation: sub-agents share the session but differ in
+/// instructions and opening message
+pub(crate) fn conversation_key(session: Option<&str>, request: &ResponsesRequest) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    session.hash(&mut hasher);
+    request.model.hash(&mut hasher);
+    request.instructions.hash(&mut hasher);
+    input_fingerprint(&request.input[..request.input.len().min(1)]).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl ResponseChains {
+    pub(crate) fn continue_from(
+        &self,
+        key: u64,
+        input: &[ResponseInputItem],
+    ) -> Option<ChainPoint> {
+        let chains = self.chains.lock().unwrap_or_else(|e| e.into_inner());
+        let chain = chains.get(&key)?;
+        if chain.input_len >= input.len()
+            || input_fingerprint(&input[..chain.input_len]) != chain.input_fingerprint
+        {
+            return None;
+        }
+        let new_items_from = chain.input_len
+            + input[chain.input_len..]
+                .iter()
+                .take_while(|item| is_response_output(item))
+                .count();
+        (new_items_from < input.len()).then(|| ChainPoint {
+            response_id: chain.response_id.clone(),
+            new_items_from,
+        })
+    }
+
+    pub(crate) fn record(&self, key: u64, input: &[ResponseInputItem], response_id: String) {
+        let mut chains = self.chains.lock().unwrap_or_else(|e| e.into_inner());
+        if chains.len() >= MAX_RESPONSE_CHAINS
+            && !chains.contains_key(&key)
+            && let Some(oldest) = chains
+                .iter()
+                .min_by_key(|(_, chain)| chain.last_used)
+                .map(|(key, _)| *key)
+        {
+            chains.remove(&oldest);
+        }
+        chains.insert(
+            key,
+            ResponseChain {
+                response_id,
+                input_len: input.len(),
+                input_fingerprint: input_fingerprint(input),
+                last_used: std::time::Instant::now(),
+            },
+        );
+    }
+
+    pub(crate) fn forget(&self, key: u64) {
+        self.chains
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&key);
+    }
+}
+
+/// Pass an upstream Responses stream through, recording the response id for the
+/// chain (if any) once the response completes
+pub(crate) fn record_stream_response_id<S, E>(
+    byte_stream: S,
+    mut chain: Option<(Arc<ResponseChains>, u64, Vec<ResponseInputItem>)>,
+) -> impl Stream<Item = Result<bytes::Bytes, E>> + Send + 'static
+where
+    S: Stream<Item = Result<bytes::Bytes, E>> + Send + 'static,
+    E: Send + 'static,
+{
+    use futures::StreamExt;
+
+    let mut buffer = String::new();
+    byte_stream.inspect(move |chunk| {
+        let (Ok(bytes), Some(_)) = (chunk, &chain) else {
+            return;
+        };
+        buffer.push_str(&String::from_utf8_lossy(bytes));
+        while let Some(line) = drain_sse_line(&mut buffer) {
+            let Some(data) = line.strip_prefix("data:") else {
+                continue;
+            };
+            let Ok(event) = serde_json::from_str::<Value>(data.trim()) else {
+                continue;
+            };
+            if event.get("type").and_then(|t| t.as_str()) != Some("response.completed") {
+                continue;
+            }
+            if let Some(id) = event.pointer("/response/id").and_then(|id| id.as_str())
+                && let Some((chains, key, input)) = chain.take()
+            {
+                chains.record(key, &input, id.to_string());
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_chains_send_only_new_items() {
+        let user = |text: &str| ResponseInputItem::Message {
+            role: "user".to_string(),
+            content: vec![ResponseInputContentPart::InputText {
+                text: text.to_string(),
+            }],
+        };
+        let assistant = ResponseInputItem::Message {
+            role: "assistant".to_string(),
+            content: vec![ResponseInputContentPart::OutputText {
+                text: "hi".to_string(),
+            }],
+        };
+        let chains = ResponseChains::default();
+        let first = vec![user("hello")];
+        chains.record(1, &first, "resp_1".to_string());
+
+        let second = vec![user("hello"), assistant.clone(), user("again")];
+        assert_eq!(
+            chains.continue_from(1, &second),
+            Some(ChainPoint {
+                response_id: "resp_1".to_string(),
+                new_items_from: 2,
+            })
+        );
+        assert_eq!(chains.continue_from(2, &second), None);
+
+        // An edited history no longer matches the stored input
+        let edited = vec![user("hullo"), assistant, user("again")];
+        assert_eq!(chains.continue_from(1, &edited), None);
+
+        chains.forget(1);
+        assert_eq!(chains.continue_from(1, &second), None);
+    }
+}
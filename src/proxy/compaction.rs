@@ -0,0 +1,438 @@
+//! Context compaction: old turns are summarized by the auxiliary model once a
+//! conversation nears the context window.
+
+use super::*;
+
+/// Newest messages that are never folded into a summary
+const COMPACT_KEEP_RECENT: usize = 4;
+
+/// Summaries remembered at once; the oldest is dropped first
+const MAX_COMPACT_SUMMARIES: usize = 64;
+
+/// Output budget for one summary
+const COMPACT_SUMMARY_MAX_TOKENS: u32 = 1024;
+
+/// Longest text kept from a single block when writing the transcript to summarize
+const COMPACT_BLOCK_CHARS: usize = 2000;
+
+/// Default share of the context window a request may fill before compaction
+const DEFAULT_COMPACT_THRESHOLD: f64 = 0.75;
+
+/// Heads the summary that replaces the folded turns
+const COMPACT_SUMMARY_HEADER: &str = "[Summary of the earlier conversation]";
+
+const COMPACT_INSTRUCTIONS: &str = "Summarize the conversation below for an assistant \
+that will continue it without seeing it. Keep the user's goals and constraints, decisions \
+made, file names and identifiers, tool results that still matter, and unfinished tasks. \
+Reply with the summary only, as plain text.";
+
+/// What to do with a request's history
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CompactionPlan {
+    /// Fits in the budget as is
+    Fits,
+    /// Replace messages before `from` with a summary we already have
+    Reuse { from: usize, summary: String },
+    /// Summarize `from..cut` (on top of `previous`, the summary of `..from`) and
+    /// replace everything before `cut` with it
+    Summarize {
+        from: usize,
+        cut: usize,
+        previous: Option<String>,
+    },
+    /// Over budget, but nothing can be folded without splitting a tool call
+    Stuck,
+}
+
+/// Folds older turns into a summary, written by the auxiliary model, once a
+/// request nears the upstream's context window.
+///
+/// Claude Code resends the whole history every turn, so summaries are kept per
+/// history prefix and reused until the rest of the conversation outgrows the
+/// budget again; the next summary then builds on the previous one.
+#[derive(Debug)]
+pub struct Compaction {
+    /// Estimated input tokens a request may use before it is compacted
+    budget: u32,
+    /// Prefix fingerprint -> (messages summarized, summary)
+    summaries: std::sync::Mutex<HashMap<u64, (usize, String)>>,
+}
+
+impl Compaction {
+    /// Enabled by `PROXY_CONTEXT_TOKENS`; `PROXY_COMPACT_THRESHOLD` is the share of
+    /// it (0.1 to 0.95, default 0.75) a request may fill
+    pub fn from_env(env: &HashMap<String, String>) -> Option<Self> {
+        let context = env
+            .get(ENV_CONTEXT_TOKENS)
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .filter(|tokens| *tokens > 0)?;
+        let threshold = env
+            .get(ENV_COMPACT_THRESHOLD)
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map_or(DEFAULT_COMPACT_THRESHOLD, |v| v.clamp(0.1, 0.95));
+        Some(Self::new((f64::from(context) * threshold) as u32))
+    }
+
+    fn new(budget: u32) -> Self {
+        Self {
+            budget,
+            summaries: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn plan(&self, messages: &[AnthropicMessage], fixed: u32) -> CompactionPlan {
+        let sizes: Vec<u32> = messages.iter().map(message_tokens).collect();
+        let rest = |from: usize| fixed + sizes[from..].iter().sum::<u32>();
+        if rest(0) <= self.budget {
+            return CompactionPlan::Fits;
+        }
+
+        let prefixes = prefix_fingerprints(messages);
+        let cached = {
+            let summaries = self.summaries.lock().unwrap_or_else(|e| e.into_inner());
+            prefixes
+                .iter()
+                .rev()
+                .find_map(|key| summaries.get(key).cloned())
+        };
+        let (from, previous) = match cached {
+            Some((from, summary)) if rest(from) + text_tokens(&summary) <= self.budget => {
+                return CompactionPlan::Reuse { from, summary };
+            }
+            Some((from, summary)) => (from, Some(summary)),
+            None => (0, None),
+        };
+
+        // Keep the newest messages that fit in half the budget, then move the cut
+        // forward until it does not separate a tool result from its call
+        let mut cut = messages.len();
+        let mut tail = 0;
+        while cut > 0 && tail + sizes[cut - 1] <= self.budget / 2 {
+            cut -= 1;
+            tail += sizes[cut];
+        }
+        let cut = (cut.min(messages.len().saturating_sub(COMPACT_KEEP_RECENT))..messages.len())
+            .find(|&i| i > from && !starts_with_tool_result(&messages[i]));
+        match cut {
+            Some(cut) => CompactionPlan::Summarize {
+                from,
+                cut,
+                previous,
+            },
+            None => CompactionPlan::Stuck,
+        }
+    }
+
+    fn remember(&self, key: u64, len: usize, summary: String) {
+        let mut summaries = self.summaries.lock().unwrap_or_else(|e| e.into_inner());
+        if summaries.len() >= MAX_COMPACT_SUMMARIES {
+            summaries.clear();
+        }
+        summaries.insert(key, (len, summary));
+    }
+}
+
+/// Roughly four characters per token, as the mock upstream counts
+fn text_tokens(text: &str) -> u32 {
+    (text.len() / 4) as u32
+}
+
+fn message_tokens(message: &AnthropicMessage) -> u32 {
+    serde_json::to_string(message).map_or(0, |s| text_tokens(&s))
+}
+
+/// Tokens a request uses besides its messages (system prompt and tools)
+fn fixed_tokens(request: &AnthropicRequest) -> u32 {
+    let system = request.system.as_ref().map_or(0, |s| {
+        serde_json::to_string(s).map_or(0, |s| text_tokens(&s))
+    });
+    let tools = request.tools.as_ref().map_or(0, |t| {
+        serde_json::to_string(t).map_or(0, |s| text_tokens(&s))
+    });
+    system + tools
+}
+
+/// Fingerprint of `messages[..i]` for every `i` in `0..=messages.len()`
+fn prefix_fingerprints(messages: &[AnthropicMessage]) -> Vec<u64> {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    let mut prefixes = vec![hasher.clone().finish()];
+    for message in messages {
+        serde_json::to_string(message)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        prefixes.push(hasher.clone().finish());
+    }
+    prefixes
+}
+
+fn starts_with_tool_result(message: &AnthropicMessage) -> bool {
+    matches!(
+        &message.content,
+        AnthropicContent::Blocks(blocks)
+            if blocks.iter().any(|b| matches!(b, ContentBlock::ToolResult { .. }))
+    )
+}
+
+fn clip(text: &str) -> String {
+    if text.len() <= COMPACT_BLOCK_CHARS {
+        return text.to_string();
+    }
+    let end = text.floor_char_boundary(COMPACT_BLOCK_CHARS);
+    format!("{} [...]", &text[..end])
+}
+
+/// Plain-text transcript of `messages` for the summarizer
+fn compaction_transcript(messages: &[AnthropicMessage]) -> String {
+    let mut out = String::new();
+    for message in messages {
+        let speaker = if message.role == "assistant" {
+            "Assistant"
+        } else {
+            "User"
+        };
+        let blocks = match &message.content {
+            AnthropicContent::Text(text) => vec![clip(text)],
+            AnthropicContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text, .. } => Some(clip(text)),
+                    ContentBlock::ToolUse { name, input, .. } => Some(format!(
+                        "[called {} with {}]",
+                        name,
+                        clip(&input.to_string())
+                    )),
+                    ContentBlock::ToolResult { content, .. } => Some(format!(
+                        "[tool result: {}]",
+                        clip(&stringify_value(content))
+                    )),
+                    ContentBlock::Image { .. } => Some("[image]".to_string()),
+                    _ => None,
+                })
+                .collect(),
+        };
+        for text in blocks {
+            out.push_str(&format!("{}: {}\n\n", speaker, text));
+        }
+    }
+    out
+}
+
+/// Replace `messages[..from]` with `summary`, as a leading block of the first kept
+/// user message or as a user message of its own before an assistant turn
+fn apply_compaction(request: &mut AnthropicRequest, from: usize, summary: &str) {
+    let text = format!("{}\n{}", COMPACT_SUMMARY_HEADER, summary);
+    let mut kept = request.messages.split_off(from);
+    match kept.first_mut() {
+        Some(first) if first.role == "user" => {
+            let mut blocks = vec![ContentBlock::Text {
+                text,
+                cache_control: None,
+            }];
+            match std::mem::replace(&mut first.content, AnthropicContent::Text(String::new())) {
+                AnthropicContent::Text(original) => blocks.push(ContentBlock::Text {
+                    text: original,
+                    cache_control: None,
+                }),
+                AnthropicContent::Blocks(original) => blocks.extend(original),
+            }
+            first.content = AnthropicContent::Blocks(blocks);
+        }
+        _ => kept.insert(
+            0,
+            AnthropicMessage {
+                role: "user".to_string(),
+                content: AnthropicContent::Text(text),
+            },
+        ),
+    }
+    request.messages = kept;
+}
+
+/// Ask the auxiliary model (or `model`) to summarize `messages`
+async fn summarize_history(
+    state: &Arc<ProxyState>,
+    mode: UpstreamMode,
+    model: &str,
+    previous: Option<&str>,
+    messages: &[AnthropicMessage],
+    auth_header: Option<String>,
+) -> Result<String, String> {
+    let model = state.auxiliary_model.clone().unwrap_or(model.to_string());
+    let mut prompt = format!("{}\n\n", COMPACT_INSTRUCTIONS);
+    if let Some(previous) = previous {
+        prompt.push_str(&format!(
+            "Summary of the conversation before this part:\n{}\n\n",
+            previous
+        ));
+    }
+    prompt.push_str(&compaction_transcript(messages));
+    let request = AnthropicRequest {
+        model: model.clone(),
+        messages: vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Text(prompt),
+        }],
+        max_tokens: Some(COMPACT_SUMMARY_MAX_TOKENS),
+        temperature: None,
+        top_p: None,
+        top_k: None,
+        stop_sequences: None,
+        stream: Some(false),
+        system: None,
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: None,
+    };
+
+    let response = forward_request(state.clone(), mode, request, model.clone(), auth_header).await;
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("{} {}", status, String::from_utf8_lossy(&bytes)));
+    }
+    let response: AnthropicResponse = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    state.usage.record(
+        &model,
+        response.usage.input_tokens,
+        response.usage.output_tokens,
+    );
+    let summary: String = response
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            ResponseContent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if summary.trim().is_empty() {
+        return Err("the summary came back empty".to_string());
+    }
+    Ok(summary.trim().to_string())
+}
+
+/// Shrink a request's history to the compaction budget, if the profile sets one.
+/// Failures are logged and leave the request as it was.
+pub(crate) async fn compact_history(
+    state: &Arc<ProxyState>,
+    mode: UpstreamMode,
+    request: &mut AnthropicRequest,
+    model: &str,
+    auth_header: Option<String>,
+) {
+    let Some(compaction) = &state.compaction else {
+        return;
+    };
+    let fixed = fixed_tokens(request);
+    match compaction.plan(&request.messages, fixed) {
+        CompactionPlan::Fits => {}
+        CompactionPlan::Reuse { from, summary } => apply_compaction(request, from, &summary),
+        CompactionPlan::Stuck => proxy_log(&format!(
+            "Compaction: history for {} is over {} tokens but has no turn boundary to cut at",
+            model, compaction.budget
+        )),
+        CompactionPlan::Summarize {
+            from,
+            cut,
+            previous,
+        } => {
+            let summary = match summarize_history(
+                state,
+                mode,
+                model,
+                previous.as_deref(),
+                &request.messages[from..cut],
+                auth_header,
+            )
+            .await
+            {
+                Ok(summary) => summary,
+                Err(e) => {
+                    proxy_log(&format!(
+                        "Compaction: summary failed, sending full history: {}",
+                        e
+                    ));
+                    return;
+                }
+            };
+            let before: u32 = request.messages[..cut].iter().map(message_tokens).sum();
+            proxy_log(&format!(
+                "Compaction: folded {} messages (~{} tokens) into a ~{}-token summary for {}",
+                cut,
+                before,
+                text_tokens(&summary),
+                model
+            ));
+            let key = prefix_fingerprints(&request.messages[..cut])[cut];
+            compaction.remember(key, cut, summary.clone());
+            apply_compaction(request, cut, &summary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn compaction_summarizes_old_turns_and_reuses_the_summary() {
+        let text = |role: &str, i: usize| AnthropicMessage {
+            role: role.to_string(),
+            content: AnthropicContent::Text(format!("turn {} {}", i, "x".repeat(150))),
+        };
+        let mut messages: Vec<AnthropicMessage> = (0..10)
+            .map(|i| text(if i % 2 == 0 { "user" } else { "assistant" }, i))
+            .collect();
+        messages[6] = AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                content: json!("ok"),
+                is_error: None,
+                cache_control: None,
+            }]),
+        };
+        let compaction = Compaction::new(300);
+        assert_eq!(compaction.plan(&messages[..4], 0), CompactionPlan::Fits);
+
+        // The cut never leaves a tool result without its call
+        assert_eq!(
+            compaction.plan(&messages, 0),
+            CompactionPlan::Summarize {
+                from: 0,
+                cut: 7,
+                previous: None,
+            }
+        );
+        compaction.remember(prefix_fingerprints(&messages)[7], 7, "summary".to_string());
+
+        messages.push(text("user", 10));
+        let plan = compaction.plan(&messages, 0);
+        assert_eq!(
+            plan,
+            CompactionPlan::Reuse {
+                from: 7,
+                summary: "summary".to_string(),
+            }
+        );
+
+        let mut request: AnthropicRequest = serde_json::from_value(json!({
+            "model": "local",
+            "messages": messages,
+        }))
+        .unwrap();
+        apply_compaction(&mut request, 7, "summary");
+        assert_eq!(request.messages.len(), 5);
+        assert_eq!(request.messages[0].role, "user");
+        assert!(matches!(
+            &request.messages[0].content,
+            AnthropicContent::Text(text) if text.starts_with(COMPACT_SUMMARY_HEADER)
+        ));
+        assert_eq!(request.messages[1].role, "assistant");
+    }
+}
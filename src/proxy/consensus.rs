@@ -0,0 +1,179 @@
+//! Experimental consensus mode: main-conversation turns are answered by two models
+//! and the auxiliary model merges the answers into one.
+
+use super::*;
+
+const CONSENSUS_INSTRUCTIONS: &str = "Two assistants answered the same request. Compare \
+their answers, settle any disagreement on the merits, and write the single best answer, \
+keeping what each got right. Reply with that answer only, addressed to the user, without \
+mentioning that there were two answers.";
+
+/// Send `request` upstream without streaming and parse the complete reply. An error
+/// response is handed back as is.
+async fn fetch_answer(
+    state: &Arc<ProxyState>,
+    mode: UpstreamMode,
+    request: AnthropicRequest,
+    model: &str,
+    auth_header: Option<String>,
+) -> Result<AnthropicResponse, Response> {
+    let response =
+        forward_request(state.clone(), mode, request, model.to_string(), auth_header).await;
+    if !response.status().is_success() {
+        return Err(response);
+    }
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()).into_response())?;
+    serde_json::from_slice(&bytes)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()).into_response())
+}
+
+fn answer_text(response: &AnthropicResponse) -> String {
+    response
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            ResponseContent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn calls_tools(response: &AnthropicResponse) -> bool {
+    response
+        .content
+        .iter()
+        .any(|block| matches!(block, ResponseContent::ToolUse { .. }))
+}
+
+/// Prompt asking the adjudicator to merge `answers` to the last user message
+fn consensus_prompt(request: &AnthropicRequest, answers: [(&str, &str); 2]) -> String {
+    let question = request
+        .messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map(flatten_anthropic_message_text)
+        .unwrap_or_default();
+    let mut prompt = format!(
+        "{}\n\nRequest:\n{}\n\n",
+        CONSENSUS_INSTRUCTIONS,
+        question.trim()
+    );
+    for ((model, text), label) in answers.into_iter().zip(["A", "B"]) {
+        prompt.push_str(&format!(
+            "Answer {} ({}):\n{}\n\n",
+            label,
+            model,
+            text.trim()
+        ));
+    }
+    prompt
+}
+
+/// Answer a main-conversation request with both `model` and `second_model`, then
+/// have the auxiliary model (or `model`) merge the two. Turns that call tools, and
+/// any failure of the second model or the merge, get `model`'s answer unchanged.
+pub(crate) async fn consensus_request(
+    state: Arc<ProxyState>,
+    mode: UpstreamMode,
+    request: AnthropicRequest,
+    model: String,
+    second_model: &str,
+    auth_header: Option<String>,
+) -> Response {
+    let is_streaming = request.stream.unwrap_or(false);
+    let mut buffered = request.clone();
+    buffered.stream = Some(false);
+    let (first, second) = tokio::join!(
+        fetch_answer(&state, mode, buffered.clone(), &model, auth_header.clone()),
+        fetch_answer(&state, mode, buffered, second_model, auth_header.clone()),
+    );
+    let mut first = match first {
+        Ok(first) => first,
+        Err(response) => return response,
+    };
+    let second = match second {
+        Ok(second) => second,
+        Err(response) => {
+            proxy_log(&format!(
+                "Consensus: {} failed with {}; using {} alone",
+                second_model,
+                response.status(),
+                model
+            ));
+            return replay_response(first, is_streaming);
+        }
+    };
+    state.usage.record(
+        second_model,
+        second.usage.input_tokens,
+        second.usage.output_tokens,
+    );
+    if calls_tools(&first) || calls_tools(&second) {
+        return replay_response(first, is_streaming);
+    }
+
+    let judge = state.auxiliary_model.clone().unwrap_or(model.clone());
+    let prompt = consensus_prompt(
+        &request,
+        [
+            (&model, &answer_text(&first)),
+            (second_model, &answer_text(&second)),
+        ],
+    );
+    let judge_request = AnthropicRequest {
+        model: judge.clone(),
+        messages: vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Text(prompt),
+        }],
+        max_tokens: request.max_tokens,
+        temperature: None,
+        top_p: None,
+        top_k: None,
+        stop_sequences: None,
+        stream: Some(false),
+        system: None,
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: None,
+    };
+    let merged = match fetch_answer(&state, mode, judge_request, &judge, auth_header).await {
+        Ok(merged) => merged,
+        Err(response) => {
+            proxy_log(&format!(
+                "Consensus: merge by {} failed with {}; using {}",
+                judge,
+                response.status(),
+                model
+            ));
+            return replay_response(first, is_streaming);
+        }
+    };
+    state.usage.record(
+        &judge,
+        merged.usage.input_tokens,
+        merged.usage.output_tokens,
+    );
+    let text = answer_text(&merged);
+    if text.trim().is_empty() {
+        proxy_log(&format!("Consensus: {} returned an empty merge", judge));
+        return replay_response(first, is_streaming);
+    }
+    proxy_log(&format!(
+        "Consensus: merged answers from {} and {} with {}",
+        model, second_model, judge
+    ));
+
+    // Keep the first model's reasoning, but answer with the merged text
+    first
+        .content
+        .retain(|block| !matches!(block, ResponseContent::Text { .. }));
+    first.content.push(ResponseContent::Text { text });
+    first.stop_reason = Some("end_turn".to_string());
+    replay_response(first, is_streaming)
+}
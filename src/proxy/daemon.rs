@@ -0,0 +1,426 @@
+//! Daemon mode: one long-lived proxy serving several profiles on one port.
+//!
+//! Each profile is served below `/profiles/<name>/`, where every request is answered
+//! exactly as that profile's own session proxy would; requests without the prefix go
+//! to the active profile. `/daemon/*` is the control API, all JSON:
+//!
+//! - `GET /daemon/status`: the profiles served, the active one and the ones skipped
+//! - `POST /daemon/switch` with `{"profile": "<name>"}`: change the active profile
+//! - `POST /daemon/reload`: prepare the profiles again from `profiles.toml`
+//! - `POST /daemon/stop`: shut the daemon down
+//!
+//! The control API only answers this machine, and not web pages (see `access`).
+
+use super::*;
+use std::time::Instant;
+use tokio::sync::{Notify, RwLock};
+use tower::ServiceExt;
+
+/// A profile prepared for the daemon, as a launch would prepare it
+pub struct DaemonProfile {
+    pub name: String,
+    pub target_url: String,
+    pub options: ProxyOptions,
+}
+
+/// The profiles to serve, and why the others could not be prepared
+#[derive(Default)]
+pub struct DaemonLoad {
+    pub profiles: Vec<DaemonProfile>,
+    /// Profile name and reason
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Prepares the profiles to serve, on start and on every reload. Blocking.
+pub type DaemonLoader = Arc<dyn Fn() -> Result<DaemonLoad> + Send + Sync>;
+
+/// Path prefix a profile is served under
+pub fn daemon_profile_path(name: &str) -> String {
+    format!(
+        "/profiles/{}",
+        url::form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>()
+    )
+}
+
+struct ServedProfile {
+    name: String,
+    target_url: String,
+    state: Arc<ProxyState>,
+    router: Router,
+}
+
+#[derive(Default)]
+struct Served {
+    profiles: Vec<ServedProfile>,
+    active: Option<String>,
+    skipped: Vec<(String, String)>,
+}
+
+impl Served {
+    fn find(&self, name: &str) -> Option<&ServedProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Let go of the loaded local models without unloading them, as a session proxy
+    /// does when it stops normally
+    fn release_leases(&self) {
+        for profile in &self.profiles {
+            if let Some(unloader) = &profile.state.idle_unloader {
+                unloader.release_lease();
+            }
+        }
+    }
+}
+
+pub(crate) struct DaemonState {
+    served: RwLock<Served>,
+    loader: DaemonLoader,
+    started: Instant,
+    stop: Notify,
+}
+
+impl DaemonState {
+    /// Prepare the profiles and swap them in; the active profile stays active if it
+    /// is still served, else the first profile becomes active
+    async fn load(&self) -> Result<()> {
+        let loader = self.loader.clone();
+        let load = tokio::task::spawn_blocking(move || loader()).await??;
+        let mut profiles = Vec::new();
+        let mut skipped = load.skipped;
+        for profile in load.profiles {
+            match build_state(profile.target_url.clone(), profile.options).await {
+                Ok(state) => profiles.push(ServedProfile {
+                    name: profile.name,
+                    target_url: profile.target_url,
+                    router: router(state.clone()),
+                    state,
+                }),
+                Err(e) => skipped.push((profile.name, format!("{:#}", e))),
+            }
+        }
+        for (name, reason) in &skipped {
+            proxy_log(&format!("Daemon skipped {}: {}", name, reason));
+        }
+
+        let mut served = self.served.write().await;
+        served.release_leases();
+        let active = served
+            .active
+            .take()
+            .filter(|name| profiles.iter().any(|profile| &profile.name == name))
+            .or_else(|| profiles.first().map(|profile| profile.name.clone()));
+        *served = Served {
+            profiles,
+            active,
+            skipped,
+        };
+        Ok(())
+    }
+
+    async fn status(&self) -> Value {
+        let served = self.served.read().await;
+        serde_json::json!({
+            "active": served.active,
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "profiles": served.profiles.iter().map(|profile| serde_json::json!({
+                "name": profile.name,
+                "target_url": profile.target_url,
+                "path": daemon_profile_path(&profile.name),
+            })).collect::<Vec<_>>(),
+            "skipped": served.skipped.iter().map(|(name, reason)| serde_json::json!({
+                "name": name,
+                "reason": reason,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn control_error(status: StatusCode, message: String) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+async fn status_handler(State(daemon): State<Arc<DaemonState>>) -> Response {
+    Json(daemon.status().await).into_response()
+}
+
+#[derive(Deserialize)]
+struct SwitchRequest {
+    profile: String,
+}
+
+async fn switch_handler(
+    State(daemon): State<Arc<DaemonState>>,
+    Json(request): Json<SwitchRequest>,
+) -> Response {
+    let mut served = daemon.served.write().await;
+    if served.find(&request.profile).is_none() {
+        return control_error(
+            StatusCode::NOT_FOUND,
+            format!("'{}' is not served by the daemon", request.profile),
+        );
+    }
+    proxy_log(&format!("Daemon switched to {}", request.profile));
+    served.active = Some(request.profile);
+    drop(served);
+    Json(daemon.status().await).into_response()
+}
+
+async fn reload_handler(State(daemon): State<Arc<DaemonState>>) -> Response {
+    match daemon.load().await {
+        Ok(()) => Json(daemon.status().await).into_response(),
+        Err(e) => control_error(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)),
+    }
+}
+
+async fn stop_handler(State(daemon): State<Arc<DaemonState>>) -> Response {
+    daemon.stop.notify_one();
+    Json(serde_json::json!({ "stopping": true })).into_response()
+}
+
+/// Hand a request to the profile named by its path prefix, or to the active profile
+async fn dispatch(
+    State(daemon): State<Arc<DaemonState>>,
+    mut request: axum::extract::Request,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let served = daemon.served.read().await;
+    let (profile, rest) = match path.strip_prefix("/profiles/") {
+        Some(rest) => {
+            let (name, rest) = rest.split_once('/').unwrap_or((rest, ""));
+            let prefix = format!("/profiles/{}", name);
+            let profile = served
+                .profiles
+                .iter()
+                .find(|profile| daemon_profile_path(&profile.name) == prefix);
+            (profile, format!("/{}", rest))
+        }
+        None => (
+            served.active.as_deref().and_then(|name| served.find(name)),
+            path,
+        ),
+    };
+    let Some(profile) = profile else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "type": "error",
+                "error": {
+                    "type": "not_found_error",
+                    "message": "No such profile in the claude-profiler daemon"
+                }
+            })),
+        )
+            .into_response();
+    };
+    let router = profile.router.clone();
+    drop(served);
+
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{}?{}", rest, query),
+        None => rest,
+    };
+    match path_and_query.parse() {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    }
+    match router.oneshot(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
+
+/// Load the profiles and build the daemon's state
+pub(crate) async fn start_daemon(
+    loader: DaemonLoader,
+    active: Option<String>,
+) -> Result<Arc<DaemonState>> {
+    let daemon = Arc::new(DaemonState {
+        served: RwLock::new(Served {
+            active,
+            ..Default::default()
+        }),
+        loader,
+        started: Instant::now(),
+        stop: Notify::new(),
+    });
+    daemon.load().await?;
+    Ok(daemon)
+}
+
+pub(crate) fn daemon_router(daemon: Arc<DaemonState>) -> Router {
+    Router::new()
+        .route("/daemon/status", get(status_handler))
+        .route("/daemon/switch", post(switch_handler))
+        .route("/daemon/reload", post(reload_handler))
+        .route("/daemon/stop", post(stop_handler))
+        .route_layer(axum::middleware::from_fn(local_control_only))
+        .fallback(dispatch)
+        .with_state(daemon)
+}
+
+/// Serve the loader's profiles on `listen` until `/daemon/stop` or Ctrl+C.
+/// `active` is the profile served without a path prefix (the first one if unset).
+pub async fn serve_daemon(
+    listen: ListenAddr,
+    loader: DaemonLoader,
+    active: Option<String>,
+) -> Result<()> {
+    let daemon = start_daemon(loader, active).await?;
+    let listener = tokio::net::TcpListener::bind(listen.socket())
+        .await
+        .with_context(|| format!("Cannot listen on {}", listen.socket()))?
+        .tap_io(|tcp| {
+            let _ = tcp.set_nodelay(true);
+        });
+    let stopping = daemon.clone();
+    // The control API checks where requests come from
+    let app =
+        daemon_router(daemon.clone()).into_make_service_with_connect_info::<std::net::SocketAddr>();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            tokio::select! {
+                _ = stopping.stop.notified() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        })
+        .await?;
+    daemon.served.read().await.release_leases();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::ConnectInfo;
+    use serde_json::json;
+    use std::net::SocketAddr;
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = app.into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    fn mock_profile(name: &str, model: &str) -> DaemonProfile {
+        DaemonProfile {
+            name: name.to_string(),
+            target_url: "mock:".to_string(),
+            options: ProxyOptions {
+                model_override: Some(model.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn profiles_are_served_by_prefix_and_the_active_one_at_the_root() {
+        let loader: DaemonLoader = Arc::new(|| {
+            Ok(DaemonLoad {
+                profiles: vec![
+                    mock_profile("local", "qwen3"),
+                    mock_profile("Work Mock", "gpt-5"),
+                ],
+                skipped: vec![("codex".to_string(), "not signed in".to_string())],
+            })
+        });
+        let daemon = start_daemon(loader, None).await.unwrap();
+        let url = serve(daemon_router(daemon)).await;
+        let client = reqwest::Client::new();
+        let upstream_model = |path: String| {
+            let client = client.clone();
+            async move {
+                let response = client
+                    .post(path)
+                    .json(&json!({
+                        "model": "claude-sonnet-4-5",
+                        "max_tokens": 16,
+                        "messages": [{ "role": "user", "content": "hi" }]
+                    }))
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                response.headers()[UPSTREAM_MODEL_HEADER]
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            }
+        };
+
+        assert_eq!(
+            upstream_model(format!("{}/v1/messages", url)).await,
+            "qwen3"
+        );
+        let work = format!("{}{}", url, daemon_profile_path("Work Mock"));
+        assert_eq!(
+            upstream_model(format!("{}/anthropic/v1/messages", work)).await,
+            "gpt-5"
+        );
+
+        let switched: Value = client
+            .post(format!("{}/daemon/switch", url))
+            .json(&json!({ "profile": "Work Mock" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(switched["active"], "Work Mock");
+        assert_eq!(switched["profiles"].as_array().unwrap().len(), 2);
+        assert_eq!(switched["skipped"][0]["name"], "codex");
+        assert_eq!(
+            upstream_model(format!("{}/v1/messages", url)).await,
+            "gpt-5"
+        );
+
+        let missing = client
+            .post(format!("{}/profiles/nope/v1/messages", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        let unknown = client
+            .post(format!("{}/daemon/switch", url))
+            .json(&json!({ "profile": "nope" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn web_pages_and_other_machines_cannot_stop_the_daemon() {
+        let loader: DaemonLoader = Arc::new(|| {
+            Ok(DaemonLoad {
+                profiles: vec![mock_profile("local", "qwen3")],
+                skipped: Vec::new(),
+            })
+        });
+        let daemon = start_daemon(loader, None).await.unwrap();
+        let url = serve(daemon_router(daemon.clone())).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/daemon/stop", url))
+            .header(header::ORIGIN, "https://example.com")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let mut remote = axum::extract::Request::post("/daemon/stop")
+            .body(Body::empty())
+            .unwrap();
+        remote
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 168, 1, 20], 50000))));
+        let response = daemon_router(daemon.clone()).oneshot(remote).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // Neither reached the stop handler, which would have left a permit behind
+        let stopped = tokio::time::timeout(Duration::from_millis(50), daemon.stop.notified());
+        assert!(stopped.await.is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Chat Completions streams from local servers deviate from OpenAI's in small ways:
+//! reasoning in `reasoning_content` or `reasoning` deltas, `role` repeated in every
+//! delta, tool calls without an `index`, or no closing `[DONE]`. Each chunk is
+//! rewritten into the OpenAI shape before the chat stream translator reads it.
+
+use super::*;
+
+/// Server family behind a Chat Completions endpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ChatDialect {
+    #[default]
+    OpenAi,
+    Vllm,
+    LlamaCpp,
+    Ollama,
+}
+
+/// Default vLLM port
+const VLLM_PORT: u16 = 8000;
+
+/// Default llama.cpp server port
+const LLAMA_CPP_PORT: u16 = 8080;
+
+impl ChatDialect {
+    /// Guess the server from the endpoint URL (host name or default port)
+    pub(crate) fn detect(url: &str) -> Self {
+        if LocalBackend::detect(url) == Some(LocalBackend::Ollama) {
+            return ChatDialect::Ollama;
+        }
+        let Ok(url) = url::Url::parse(url) else {
+            return ChatDialect::OpenAi;
+        };
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let port = url.port();
+        if host.contains("vllm") || port == Some(VLLM_PORT) {
+            ChatDialect::Vllm
+        } else if host.contains("llama") || port == Some(LLAMA_CPP_PORT) {
+            ChatDialect::LlamaCpp
+        } else {
+            ChatDialect::OpenAi
+        }
+    }
+
+    /// Recognize the server from a chunk when the URL gave nothing away:
+    /// llama.cpp reports `timings`, Ollama a `fp_ollama` fingerprint
+    fn sniff(chunk: &Value) -> Option<Self> {
+        if chunk.get("timings").is_some() {
+            Some(ChatDialect::LlamaCpp)
+        } else if chunk["system_fingerprint"] == "fp_ollama" {
+            Some(ChatDialect::Ollama)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            ChatDialect::OpenAi => "OpenAI",
+            ChatDialect::Vllm => "vLLM",
+            ChatDialect::LlamaCpp => "llama.cpp",
+            ChatDialect::Ollama => "Ollama",
+        }
+    }
+
+    /// Delta fields carrying reasoning text, in order of preference
+    fn reasoning_fields(self) -> &'static [&'static str] {
+        match self {
+            ChatDialect::Ollama => &["reasoning"],
+            ChatDialect::LlamaCpp => &["reasoning_content"],
+            // DeepSeek and older vLLM use `reasoning_content`, gateways and newer vLLM `reasoning`
+            ChatDialect::OpenAi | ChatDialect::Vllm => &["reasoning_content", "reasoning"],
+        }
+    }
+}
+
+/// Rewrites the chunks of one stream. Reasoning always ends up in
+/// `reasoning_content`.
+#[derive(Debug)]
+pub(crate) struct DialectAdapter {
+    dialect: ChatDialect,
+    sniffed: bool,
+    /// Ollama sends each tool call whole, in its own chunk and without an `index`
+    next_tool_index: u64,
+}
+
+impl DialectAdapter {
+    pub(crate) fn new(dialect: ChatDialect) -> Self {
+        Self {
+            dialect,
+            sniffed: dialect != ChatDialect::OpenAi,
+            next_tool_index: 0,
+        }
+    }
+
+    pub(crate) fn dialect(&self) -> ChatDialect {
+        self.dialect
+    }
+
+    pub(crate) fn adapt(&mut self, chunk: &mut Value) {
+        if !self.sniffed {
+            self.sniffed = true;
+            if let Some(dialect) = ChatDialect::sniff(chunk) {
+                proxy_log(&format!("Chat stream looks like {}", dialect.label()));
+                self.dialect = dialect;
+            }
+        }
+        let Some(choices) = chunk.get_mut("choices").and_then(Value::as_array_mut) else {
+            return;
+        };
+        for choice in choices {
+            let Some(delta) = choice.get_mut("delta").and_then(Value::as_object_mut) else {
+                continue;
+            };
+            delta.remove("role");
+            if delta.get("content").is_some_and(Value::is_null) {
+                delta.remove("content");
+            }
+
+            let reasoning = self
+                .dialect
+                .reasoning_fields()
+                .iter()
+                .filter_map(|field| delta.remove(*field))
+                .find(|value| value.as_str().is_some_and(|text| !text.is_empty()));
+            if let Some(reasoning) = reasoning {
+                delta.insert("reasoning_content".to_string(), reasoning);
+            }
+
+            if self.dialect == ChatDialect::Ollama
+                && let Some(calls) = delta.get_mut("tool_calls").and_then(Value::as_array_mut)
+            {
+                for call in calls.iter_mut().filter(|call| call.get("index").is_none()) {
+                    call["index"] = self.next_tool_index.into();
+                    self.next_tool_index += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn dialects_are_detected_and_normalized() {
+        assert_eq!(
+            ChatDialect::detect("http://localhost:11434/v1"),
+            ChatDialect::Ollama
+        );
+        assert_eq!(
+            ChatDialect::detect("http://gpu-box:8000/v1"),
+            ChatDialect::Vllm
+        );
+        assert_eq!(
+            ChatDialect::detect("http://llama-server.lan/v1"),
+            ChatDialect::LlamaCpp
+        );
+        assert_eq!(
+            ChatDialect::detect("https://api.deepseek.com"),
+            ChatDialect::OpenAi
+        );
+
+        // An unrecognized URL is refined by the first chunk
+        let mut adapter = DialectAdapter::new(ChatDialect::OpenAi);
+        let mut chunk = json!({
+            "system_fingerprint": "fp_ollama",
+            "choices": [{ "delta": {
+                "role": "assistant",
+                "content": null,
+                "reasoning": "Let me think",
+                "tool_calls": [{ "id": "call_a", "function": { "name": "Read" } }]
+            }}]
+        });
+        adapter.adapt(&mut chunk);
+        assert_eq!(adapter.dialect(), ChatDialect::Ollama);
+        assert_eq!(
+            chunk["choices"][0]["delta"],
+            json!({
+                "reasoning_content": "Let me think",
+                "tool_calls": [{ "id": "call_a", "function": { "name": "Read" }, "index": 0 }]
+            })
+        );
+        let mut next = json!({ "choices": [{ "delta": {
+            "tool_calls": [{ "id": "call_b", "function": { "name": "Bash" } }]
+        }}]});
+        adapter.adapt(&mut next);
+        assert_eq!(next["choices"][0]["delta"]["tool_calls"][0]["index"], 1);
+    }
+}
@@ -0,0 +1,246 @@
+//! Empty replies: some local models now and then answer with no content at all,
+//! which leaves Claude Code asking again in a loop. Such a reply is retried once
+//! with a nudge, then replaced by a short explanation.
+
+use super::*;
+
+/// Added to the last user message when a request is retried
+const EMPTY_REPLY_NUDGE: &str =
+    "Your previous reply was empty. Please answer the request above or call a tool.";
+
+/// Text of the reply sent when the retry comes back empty as well
+const EMPTY_REPLY_TEXT: &str = "[claude-profiler: the model returned an empty response twice. \
+Try again, rephrase the request, or switch to another model.]";
+
+/// Stop reason of that reply, so it cannot be mistaken for a finished answer
+const EMPTY_REPLY_STOP_REASON: &str = "empty_response";
+
+/// A successful upstream reply, with whether it carried any content
+enum Reply {
+    Content(Response),
+    Empty,
+}
+
+/// Whether an Anthropic SSE event carries something Claude Code can act on. Errors
+/// count, so they reach the client instead of being retried.
+fn event_has_content(event: &Value) -> bool {
+    match event["type"].as_str() {
+        Some("content_block_start") => event["content_block"]["type"] == "tool_use",
+        Some("content_block_delta") => {
+            let delta = &event["delta"];
+            match delta["type"].as_str() {
+                Some("text_delta") => delta["text"].as_str().is_some_and(|t| !t.trim().is_empty()),
+                Some("thinking_delta") => delta["thinking"]
+                    .as_str()
+                    .is_some_and(|t| !t.trim().is_empty()),
+                Some("input_json_delta") => true,
+                _ => false,
+            }
+        }
+        Some("error") => true,
+        _ => false,
+    }
+}
+
+fn sse_has_content(text: &str) -> bool {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+        .any(|event| event_has_content(&event))
+}
+
+fn message_has_content(message: &Value) -> bool {
+    message["content"].as_array().is_none_or(|blocks| {
+        blocks.iter().any(|block| match block["type"].as_str() {
+            Some("text") => block["text"].as_str().is_some_and(|t| !t.trim().is_empty()),
+            Some("thinking") => block["thinking"]
+                .as_str()
+                .is_some_and(|t| !t.trim().is_empty()),
+            _ => true,
+        })
+    })
+}
+
+/// Read a successful reply until it shows content. A stream is only held back
+/// until its first content event; everything read so far is then sent on ahead
+/// of the rest.
+async fn check_reply(response: Response) -> Reply {
+    use futures::StreamExt;
+
+    if !response.status().is_success() {
+        return Reply::Content(response);
+    }
+    let is_sse = is_sse_response(&response);
+    let (parts, body) = response.into_parts();
+
+    if is_sse {
+        let mut data = body.into_data_stream();
+        let mut read = Vec::new();
+        let mut text = String::new();
+        while let Some(chunk) = data.next().await {
+            let failed = chunk.is_err();
+            if let Ok(bytes) = &chunk {
+                text.push_str(&String::from_utf8_lossy(bytes));
+            }
+            read.push(chunk);
+            if failed || sse_has_content(&text) {
+                let stream = futures::stream::iter(read).chain(data);
+                return Reply::Content(Response::from_parts(parts, Body::from_stream(stream)));
+            }
+        }
+        return Reply::Empty;
+    }
+
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return Reply::Content(StatusCode::BAD_GATEWAY.into_response());
+    };
+    match serde_json::from_slice::<Value>(&bytes) {
+        Ok(message) if !message_has_content(&message) => Reply::Empty,
+        _ => Reply::Content(Response::from_parts(parts, Body::from(bytes))),
+    }
+}
+
+/// Append the nudge to the last user message
+fn nudge(request: &mut AnthropicRequest) {
+    let nudge = ContentBlock::Text {
+        text: EMPTY_REPLY_NUDGE.to_string(),
+        cache_control: None,
+    };
+    match request.messages.last_mut() {
+        Some(message) if message.role == "user" => match &mut message.content {
+            AnthropicContent::Text(text) => {
+                message.content = AnthropicContent::Blocks(vec![
+                    ContentBlock::Text {
+                        text: std::mem::take(text),
+                        cache_control: None,
+                    },
+                    nudge,
+                ]);
+            }
+            AnthropicContent::Blocks(blocks) => blocks.push(nudge),
+        },
+        _ => request.messages.push(AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Blocks(vec![nudge]),
+        }),
+    }
+}
+
+fn empty_reply(model: &str) -> AnthropicResponse {
+    AnthropicResponse {
+        id: format!("msg_empty_{}", uuid_simple()),
+        response_type: "message".to_string(),
+        role: "assistant".to_string(),
+        content: vec![ResponseContent::Text {
+            text: EMPTY_REPLY_TEXT.to_string(),
+        }],
+        model: model.to_string(),
+        stop_reason: Some(EMPTY_REPLY_STOP_REASON.to_string()),
+        stop_sequence: None,
+        usage: AnthropicUsage::default(),
+    }
+}
+
+/// [`forward_request`], retrying an empty reply once with a nudge and answering
+/// a second one with [`EMPTY_REPLY_TEXT`]
+pub(crate) async fn forward_with_empty_retry(
+    state: Arc<ProxyState>,
+    mode: UpstreamMode,
+    mut request: AnthropicRequest,
+    target_model: String,
+    auth_header: Option<String>,
+) -> Response {
+    let first = forward_request(
+        state.clone(),
+        mode,
+        request.clone(),
+        target_model.clone(),
+        auth_header.clone(),
+    )
+    .await;
+    if let Reply::Content(response) = check_reply(first).await {
+        return response;
+    }
+    proxy_log(&format!(
+        "Empty reply from {}; retrying with a nudge",
+        target_model
+    ));
+
+    nudge(&mut request);
+    let original_model = request.model.clone();
+    let is_streaming = request.stream.unwrap_or(false);
+    let retry = forward_request(state, mode, request, target_model.clone(), auth_header).await;
+    match check_reply(retry).await {
+        Reply::Content(response) => response,
+        Reply::Empty => {
+            proxy_log(&format!(
+                "Empty reply from {} again; answering with a placeholder",
+                target_model
+            ));
+            replay_response(empty_reply(&original_model), is_streaming)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn empty_replies_are_detected_in_streams_and_messages() {
+        let empty = concat!(
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":3}}}\n\n",
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"\\n \"}}\n\n",
+            "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+        );
+        let stream = |chunks: Vec<&'static str>| {
+            sse_response(futures::stream::iter(
+                chunks
+                    .into_iter()
+                    .map(|c| Ok::<_, Infallible>(c.to_string())),
+            ))
+        };
+        assert!(matches!(
+            check_reply(stream(vec![empty])).await,
+            Reply::Empty
+        ));
+
+        // Content split across chunks is found, and nothing read is lost
+        let answer = "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n";
+        let (head, tail) = answer.split_at(60);
+        let Reply::Content(response) = check_reply(stream(vec![empty, head, tail])).await else {
+            panic!("content was not found");
+        };
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, format!("{}{}", empty, answer));
+
+        let message = |content: Value| Json(json!({ "content": content })).into_response();
+        assert!(matches!(
+            check_reply(message(json!([{ "type": "text", "text": "  " }]))).await,
+            Reply::Empty
+        ));
+        assert!(matches!(
+            check_reply(message(
+                json!([{ "type": "tool_use", "id": "t", "name": "Bash", "input": {} }])
+            ))
+            .await,
+            Reply::Content(_)
+        ));
+
+        let mut request = base_request(vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Text("list files".to_string()),
+        }]);
+        nudge(&mut request);
+        assert!(matches!(
+            &request.messages[0].content,
+            AnthropicContent::Blocks(blocks) if blocks.len() == 2
+        ));
+        let events = replay_sse_events(&empty_reply("claude-sonnet-4-5")).concat();
+        assert!(events.contains(EMPTY_REPLY_STOP_REASON));
+    }
+}
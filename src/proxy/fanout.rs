@@ -0,0 +1,239 @@
+//! Sub-agent fan-out: the conversations of Claude Code's parallel Task sub-agents are
+//! spread over the proxy target and the profile's `[[profiles.fanout]]` upstreams
+//! (more local servers, or more models loaded in one), so they run side by side
+//! instead of queueing on one GPU.
+//!
+//! Each sub-agent conversation stays on the upstream it started on, keeping that
+//! server's prompt cache warm. A new one goes to the upstream with the fewest
+//! sub-agent streams in flight, taking turns when several are equally busy. The main
+//! conversation and auxiliary requests always go to the target.
+
+use super::*;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// A conversation not heard from for this long is forgotten and may start anywhere
+const CONVERSATION_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// A fan-out upstream with its key resolved by the launcher
+#[derive(Debug, Clone)]
+pub struct FanoutUpstream {
+    pub target_url: String,
+    /// Model sent upstream; the target's model choice when unset
+    pub model: Option<String>,
+    pub credentials: Credentials,
+}
+
+impl ProxyOptions {
+    /// Options for a fan-out upstream: a route's, keeping the target's model choice
+    /// unless the upstream names a model of its own
+    pub(crate) fn for_fanout(&self, upstream: &FanoutUpstream) -> ProxyOptions {
+        let mut options = self.for_route(&RouteOptions {
+            pattern: String::new(),
+            target_url: upstream.target_url.clone(),
+            model: upstream.model.clone(),
+            credentials: upstream.credentials.clone(),
+        });
+        if upstream.model.is_none() {
+            options.model_map = self.model_map.clone();
+            options.model_override = self.model_override.clone();
+            options.auxiliary_model = self.auxiliary_model.clone();
+        }
+        options
+    }
+}
+
+#[derive(Default)]
+struct Slots {
+    /// Sub-agent requests in flight per upstream, the target first
+    in_flight: Vec<usize>,
+    /// Where the search for the least busy upstream starts, so ties take turns
+    next: usize,
+    /// Upstream of each sub-agent conversation, and when it was last used
+    conversations: HashMap<u64, (usize, Instant)>,
+}
+
+impl Slots {
+    fn assign(&mut self, conversation: u64, now: Instant) -> (usize, bool) {
+        if let Some((index, used)) = self.conversations.get_mut(&conversation)
+            && now.duration_since(*used) < CONVERSATION_TTL
+        {
+            *used = now;
+            return (*index, false);
+        }
+        self.conversations
+            .retain(|_, (_, used)| now.duration_since(*used) < CONVERSATION_TTL);
+        let count = self.in_flight.len();
+        let index = (0..count)
+            .map(|offset| (self.next + offset) % count)
+            .min_by_key(|&index| self.in_flight[index])
+            .unwrap_or(0);
+        self.next = (index + 1) % count;
+        self.conversations.insert(conversation, (index, now));
+        (index, true)
+    }
+}
+
+/// The fan-out upstreams of a target and the conversations placed on them
+pub(crate) struct Fanout {
+    upstreams: Vec<Arc<ProxyState>>,
+    slots: Arc<Mutex<Slots>>,
+}
+
+/// An upstream's share of the sub-agent requests in flight, held until the reply
+/// has been sent
+pub(crate) struct FanoutSlot {
+    slots: Arc<Mutex<Slots>>,
+    index: usize,
+}
+
+impl Drop for FanoutSlot {
+    fn drop(&mut self) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.in_flight[self.index] = slots.in_flight[self.index].saturating_sub(1);
+    }
+}
+
+impl Fanout {
+    pub(crate) fn new(upstreams: Vec<Arc<ProxyState>>) -> Option<Self> {
+        (!upstreams.is_empty()).then(|| Self {
+            slots: Arc::new(Mutex::new(Slots {
+                in_flight: vec![0; upstreams.len() + 1],
+                ..Default::default()
+            })),
+            upstreams,
+        })
+    }
+}
+
+/// Only the system prompt and the opening message are read to place a request
+#[derive(Deserialize)]
+struct ConversationStart {
+    #[serde(default)]
+    system: Option<SystemPrompt>,
+    #[serde(default)]
+    messages: Vec<Value>,
+}
+
+/// Identify a sub-agent conversation by its instructions and task, or `None` for
+/// requests that are not a sub-agent's
+fn subagent_conversation(body: &[u8]) -> Option<u64> {
+    use std::hash::{Hash, Hasher};
+    let start: ConversationStart = serde_json::from_slice(body).ok()?;
+    let system = system_prompt_text_opt(start.system.as_ref())?;
+    if !system.contains(SUBAGENT_PROMPT_MARKER) {
+        return None;
+    }
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    system.hash(&mut hasher);
+    start
+        .messages
+        .first()
+        .map(Value::to_string)
+        .hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// State to serve a request body with: for a sub-agent, the upstream its
+/// conversation is placed on, along with the slot to hold while it is served
+pub(crate) fn fan_out_request(
+    state: Arc<ProxyState>,
+    headers: &mut HeaderMap,
+    body: &[u8],
+) -> (Arc<ProxyState>, Option<FanoutSlot>) {
+    let Some(fanout) = &state.fanout else {
+        return (state, None);
+    };
+    let Some(conversation) = subagent_conversation(body) else {
+        return (state, None);
+    };
+    let mut slots = fanout.slots.lock().unwrap_or_else(|e| e.into_inner());
+    let (index, new) = slots.assign(conversation, Instant::now());
+    slots.in_flight[index] += 1;
+    drop(slots);
+    let slot = FanoutSlot {
+        slots: fanout.slots.clone(),
+        index,
+    };
+    let upstream = match index {
+        0 => state.clone(),
+        _ => fanout.upstreams[index - 1].clone(),
+    };
+    if new {
+        proxy_log(&format!(
+            "Sub-agent conversation -> {}",
+            upstream.target_url
+        ));
+    }
+    if index > 0 {
+        // Claude Code's key belongs to the proxy target, as for routes
+        headers.remove(header::AUTHORIZATION);
+        headers.remove("x-api-key");
+    }
+    (upstream, Some(slot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn subagent_conversations_are_spread_over_the_upstreams_and_stay_put() {
+        let mock = |model: &str| FanoutUpstream {
+            target_url: "mock:".to_string(),
+            model: Some(model.to_string()),
+            credentials: Credentials::None,
+        };
+        let options = ProxyOptions {
+            model_override: Some("gpu-0".to_string()),
+            fanout: vec![mock("gpu-1"), mock("gpu-2")],
+            ..Default::default()
+        };
+        let state = build_state("mock:".to_string(), options).await.unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(state)).await });
+
+        let client = reqwest::Client::new();
+        let upstream_model = |system: &str, task: &str| {
+            let request = client.post(format!("{}/v1/messages", url)).json(&json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 16,
+                "system": system,
+                "messages": [{ "role": "user", "content": task }]
+            }));
+            async move {
+                let response = request.send().await.unwrap();
+                response.headers()[UPSTREAM_MODEL_HEADER]
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            }
+        };
+        let agent = "You are an agent for Claude Code, Anthropic's official CLI for Claude.";
+
+        assert_eq!(
+            upstream_model("You are Claude Code.", "main").await,
+            "gpu-0"
+        );
+        assert_eq!(upstream_model(agent, "search the docs").await, "gpu-0");
+        assert_eq!(upstream_model(agent, "read the tests").await, "gpu-1");
+        assert_eq!(upstream_model(agent, "check the build").await, "gpu-2");
+        assert_eq!(upstream_model(agent, "read the tests").await, "gpu-1");
+        assert_eq!(
+            upstream_model("You are Claude Code.", "main").await,
+            "gpu-0"
+        );
+
+        // A busy upstream is passed over for new conversations
+        let mut slots = Slots {
+            in_flight: vec![1, 0, 1],
+            ..Default::default()
+        };
+        assert_eq!(slots.assign(1, Instant::now()), (1, true));
+        slots.in_flight[1] += 1;
+        assert_eq!(slots.assign(2, Instant::now()), (2, true));
+        assert_eq!(slots.assign(1, Instant::now()), (1, false));
+    }
+}
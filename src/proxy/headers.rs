@@ -0,0 +1,130 @@
+//! Header passthrough: which of Claude Code's request headers reach the upstream and
+//! which of the upstream's response headers come back, per the profile's
+//! `[profiles.header_policy]`.
+//!
+//! A translated request passes through several handlers before it is sent, so the
+//! headers travel in a task-local scope around the request rather than as yet
+//! another argument to each of them.
+
+use super::*;
+
+tokio::task_local! {
+    static CLIENT_HEADERS: ClientHeaders;
+}
+
+/// Headers of the request being translated, and those of its latest upstream reply
+struct ClientHeaders {
+    policy: HeaderPolicy,
+    forwarded: HeaderMap,
+    relayed: std::sync::Mutex<HeaderMap>,
+}
+
+fn filter_headers(headers: &HeaderMap, passes: impl Fn(&str) -> bool) -> HeaderMap {
+    headers
+        .iter()
+        .filter(|(name, _)| passes(name.as_str()))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Claude Code's request headers that the policy sends upstream
+pub(crate) fn forwarded_headers(policy: &HeaderPolicy, headers: &HeaderMap) -> HeaderMap {
+    filter_headers(headers, |name| policy.forwards(name))
+}
+
+/// Upstream response headers that the policy hands back to Claude Code
+pub(crate) fn relayed_headers(policy: &HeaderPolicy, headers: &HeaderMap) -> HeaderMap {
+    filter_headers(headers, |name| policy.relays(name))
+}
+
+/// Headers forwarded with the translated request in progress, if any
+pub(crate) fn client_forwarded_headers() -> HeaderMap {
+    CLIENT_HEADERS
+        .try_with(|client| client.forwarded.clone())
+        .unwrap_or_default()
+}
+
+/// Keep the relayed headers of an upstream reply for the translated response; a
+/// retried request replaces those of the earlier attempt
+pub(crate) fn note_upstream_headers(headers: &HeaderMap) {
+    let _ = CLIENT_HEADERS.try_with(|client| {
+        if let Ok(mut relayed) = client.relayed.lock() {
+            *relayed = relayed_headers(&client.policy, headers);
+        }
+    });
+}
+
+/// Run a translated request with Claude Code's forwardable headers in scope, then
+/// add the relayed upstream headers its reply does not already set
+pub(crate) async fn with_client_headers(
+    policy: &HeaderPolicy,
+    headers: &HeaderMap,
+    request: impl Future<Output = Response>,
+) -> Response {
+    let client = ClientHeaders {
+        policy: policy.clone(),
+        forwarded: forwarded_headers(policy, headers),
+        relayed: std::sync::Mutex::new(HeaderMap::new()),
+    };
+    let (mut response, relayed) = CLIENT_HEADERS
+        .scope(client, async {
+            let response = request.await;
+            let relayed = CLIENT_HEADERS.with(|client| {
+                client
+                    .relayed
+                    .lock()
+                    .map(|mut relayed| std::mem::take(&mut *relayed))
+                    .unwrap_or_default()
+            });
+            (response, relayed)
+        })
+        .await;
+    for (name, value) in &relayed {
+        if !response.headers().contains_key(name) {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn header_policy_filters_both_directions() {
+        let policy = HeaderPolicy {
+            forward: vec!["anthropic-beta".to_string(), "X-Stainless-*".to_string()],
+            ..HeaderPolicy::default()
+        };
+        let mut incoming = HeaderMap::new();
+        incoming.insert("anthropic-beta", "context-1m".parse().unwrap());
+        incoming.insert("x-stainless-os", "Linux".parse().unwrap());
+        incoming.insert("x-api-key", "sk-secret".parse().unwrap());
+        incoming.insert("user-agent", "claude-cli".parse().unwrap());
+
+        let mut upstream = HeaderMap::new();
+        upstream.insert("retry-after", "30".parse().unwrap());
+        upstream.insert("x-ratelimit-remaining-tokens", "100".parse().unwrap());
+        upstream.insert("set-cookie", "session=1".parse().unwrap());
+        upstream.insert("server", "nginx".parse().unwrap());
+
+        let response = with_client_headers(&policy, &incoming, async {
+            let forwarded = client_forwarded_headers();
+            let mut names: Vec<&str> = forwarded.keys().map(|name| name.as_str()).collect();
+            names.sort();
+            assert_eq!(names, ["anthropic-beta", "x-stainless-os"]);
+            note_upstream_headers(&upstream);
+            (StatusCode::OK, [("retry-after", "5")]).into_response()
+        })
+        .await;
+
+        let headers = response.headers();
+        assert_eq!(headers["retry-after"], "5");
+        assert_eq!(headers["x-ratelimit-remaining-tokens"], "100");
+        assert!(!headers.contains_key("set-cookie"));
+        assert!(!headers.contains_key("server"));
+        // Outside a translated request nothing is forwarded
+        assert!(client_forwarded_headers().is_empty());
+    }
+}
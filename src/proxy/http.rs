@@ -0,0 +1,78 @@
+//! The seam between the proxy and the network. Upstream requests are built with a
+//! `reqwest::Client` and handed to an [`HttpBackend`] to send; `reqwest::Client` is
+//! the backend in a real session, and a test (or a program embedding the proxy) can
+//! pass one of its own in [`ProxyOptions::http_backend`] to answer in-process.
+//!
+//! OAuth tokens refreshed after a 401 come from a [`TokenRefresher`] in the same way:
+//! the stored sign-in by default, or [`ProxyOptions::token_refresher`].
+
+use super::*;
+use futures::future::BoxFuture;
+
+/// Sends the proxy's upstream requests: the endpoint probe, model requests in every
+/// API, token counts and passthrough forwarding
+pub trait HttpBackend: Send + Sync {
+    fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, reqwest::Result<reqwest::Response>>;
+}
+
+impl HttpBackend for reqwest::Client {
+    fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> BoxFuture<'static, reqwest::Result<reqwest::Response>> {
+        Box::pin(reqwest::Client::execute(self, request))
+    }
+}
+
+/// Upstream HTTP of a proxy state: the client requests are built with, and the
+/// backend that sends them
+#[derive(Clone)]
+pub struct UpstreamHttp {
+    /// Also used directly for local server housekeeping (readiness, unloading)
+    pub client: reqwest::Client,
+    backend: Arc<dyn HttpBackend>,
+}
+
+impl UpstreamHttp {
+    /// `backend`, or the client itself when none is given
+    pub(crate) fn new(client: reqwest::Client, backend: Option<Arc<dyn HttpBackend>>) -> Self {
+        let backend = backend.unwrap_or_else(|| Arc::new(client.clone()));
+        Self { client, backend }
+    }
+
+    pub(crate) fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        self.client.post(url)
+    }
+
+    pub(crate) async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> reqwest::Result<reqwest::Response> {
+        let request = request.build()?;
+        self.backend.execute(request).await
+    }
+}
+
+/// Obtains a new access token for an OAuth profile whose token the upstream refused
+pub trait TokenRefresher: Send + Sync {
+    fn refresh(&self, provider: AuthProvider, stale: &str) -> BoxFuture<'static, Result<String>>;
+}
+
+/// Refreshes the sign-in stored when an OAuth profile was launched
+pub(crate) struct StoredSignIn;
+
+impl TokenRefresher for StoredSignIn {
+    fn refresh(&self, provider: AuthProvider, stale: &str) -> BoxFuture<'static, Result<String>> {
+        let stale = stale.to_string();
+        Box::pin(async move {
+            if provider == AuthProvider::AnthropicOAuth {
+                anthropic_oauth::refresh_stored_access_token(&stale).await
+            } else {
+                openai_oauth::refresh_stored_access_token(&stale).await
+            }
+        })
+    }
+}
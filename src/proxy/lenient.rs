@@ -0,0 +1,198 @@
+//! Tolerant reading of upstream responses.
+//!
+//! Some OpenAI-compatible servers send slightly off-spec JSON: token counts as strings
+//! or floats, `null` where a list belongs, tool arguments as an object instead of a
+//! string, missing ids. The response types read such fields through these helpers,
+//! which coerce what they can and fall back to defaults otherwise, so a usable reply
+//! is never turned into an error. Each kind of coercion is logged once per session.
+
+use std::sync::Mutex;
+
+use serde::Deserializer;
+
+use super::*;
+
+/// Log an off-spec upstream value, once per distinct message
+fn warn_once(message: String) {
+    static WARNED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+    let Ok(mut warned) = WARNED.lock() else {
+        return;
+    };
+    if warned
+        .get_or_insert_with(HashSet::new)
+        .insert(message.clone())
+    {
+        proxy_log(&format!("Upstream sent off-spec JSON: {}", message));
+    }
+}
+
+/// Short description of an unexpected value for the log
+fn describe(value: &Value) -> String {
+    let text = value.to_string();
+    match text.char_indices().nth(40) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+/// A token count from a number, a float or a numeric string
+pub(crate) fn count(value: &Value) -> Option<u32> {
+    match value {
+        Value::Number(number) => number
+            .as_u64()
+            .or_else(|| number.as_f64().filter(|v| *v >= 0.0).map(|v| v as u64))
+            .map(|v| v.min(u64::from(u32::MAX)) as u32),
+        Value::String(text) => {
+            let parsed = text.trim().parse::<f64>().ok().filter(|v| *v >= 0.0)?;
+            warn_once(format!("token count as a string ({})", describe(value)));
+            Some(parsed.min(f64::from(u32::MAX)) as u32)
+        }
+        _ => None,
+    }
+}
+
+/// A count, or zero when missing or unreadable
+pub(crate) fn u32<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    Ok(opt_u32(deserializer)?.unwrap_or(0))
+}
+
+pub(crate) fn opt_u32<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    let parsed = count(&value);
+    if parsed.is_none() && !value.is_null() {
+        warn_once(format!("unreadable token count {}", describe(&value)));
+    }
+    Ok(parsed)
+}
+
+/// Text from a string, a number or a boolean; empty when missing
+pub(crate) fn string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    Ok(match value {
+        Value::String(text) => text,
+        Value::Null => String::new(),
+        Value::Number(_) | Value::Bool(_) => {
+            warn_once(format!("{} where text was expected", describe(&value)));
+            value.to_string()
+        }
+        _ => {
+            warn_once(format!("{} where text was expected", describe(&value)));
+            String::new()
+        }
+    })
+}
+
+/// Tool arguments: a JSON string per the spec, or the object itself as some servers
+/// send it
+pub(crate) fn arguments<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    Ok(match value {
+        Value::String(text) => text,
+        Value::Null => String::new(),
+        other => {
+            warn_once("tool arguments as JSON rather than a string".to_string());
+            other.to_string()
+        }
+    })
+}
+
+/// The readable items of a list; `null` or a non-list is empty, and items that
+/// cannot be read are skipped
+pub(crate) fn list<'de, D: Deserializer<'de>, T: DeserializeOwned>(
+    deserializer: D,
+) -> Result<Vec<T>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Null => return Ok(Vec::new()),
+        other => {
+            warn_once(format!("{} where a list was expected", describe(&other)));
+            return Ok(Vec::new());
+        }
+    };
+    Ok(items
+        .into_iter()
+        .filter_map(|item| match serde_json::from_value(item.clone()) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                warn_once(format!(
+                    "skipped unreadable item {} ({})",
+                    describe(&item),
+                    e
+                ));
+                None
+            }
+        })
+        .collect())
+}
+
+pub(crate) fn opt_list<'de, D: Deserializer<'de>, T: DeserializeOwned>(
+    deserializer: D,
+) -> Result<Option<Vec<T>>, D::Error> {
+    let items = list(deserializer)?;
+    Ok((!items.is_empty()).then_some(items))
+}
+
+/// An optional object, `None` when it cannot be read
+pub(crate) fn opt<'de, D: Deserializer<'de>, T: DeserializeOwned>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    if value.is_null() {
+        return Ok(None);
+    }
+    match serde_json::from_value(value.clone()) {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(e) => {
+            warn_once(format!("ignored unreadable {} ({})", describe(&value), e));
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn off_spec_chat_responses_are_read_with_defaults() {
+        let response: ChatCompletionResponse = serde_json::from_value(json!({
+            "choices": [
+                "garbage",
+                {
+                    "message": {
+                        "content": "Hello",
+                        "tool_calls": [{
+                            "function": { "name": "get_weather", "arguments": { "city": "Paris" } },
+                            "extra": true
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }
+            ],
+            "usage": { "prompt_tokens": "12", "completion_tokens": 3.0, "prompt_tokens_details": null },
+            "system_fingerprint": 7
+        }))
+        .unwrap();
+        assert_eq!(response.id, "");
+        assert_eq!(response.choices.len(), 1);
+        let usage = response.usage.as_ref().unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (12, 3));
+
+        let anthropic = chat_to_anthropic(&response, "claude-sonnet-4-5");
+        assert!(anthropic.id.len() > "msg_".len());
+        let ResponseContent::ToolUse { id, input, .. } = &anthropic.content[1] else {
+            panic!("expected a tool call: {:?}", anthropic.content);
+        };
+        assert!(id.starts_with("toolu_"));
+        assert_eq!(input["city"], "Paris");
+
+        let usage: ChatCompletionResponse =
+            serde_json::from_value(json!({ "id": 5, "choices": null, "usage": "n/a" })).unwrap();
+        assert_eq!(usage.id, "5");
+        assert!(usage.choices.is_empty() && usage.usage.is_none());
+        assert_eq!(count(&json!("1e3")), Some(1000));
+        assert_eq!(count(&json!(-1)), None);
+    }
+}
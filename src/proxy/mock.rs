@@ -0,0 +1,192 @@
+//! Mock upstream for offline demos and tests.
+
+use super::*;
+
+/// `PROXY_TARGET_URL` scheme that serves canned responses instead of calling out
+const MOCK_URL_SCHEME: &str = "mock:";
+
+/// Longest slice of the user's message echoed back by the default mock reply
+const MOCK_ECHO_CHARS: usize = 200;
+
+pub fn is_mock_url(url: &str) -> bool {
+    url.trim().to_ascii_lowercase().starts_with(MOCK_URL_SCHEME)
+}
+
+/// One scripted assistant turn: optional text, optionally followed by a tool call
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MockTurn {
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub tool_use: Option<MockToolCall>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MockToolCall {
+    pub name: String,
+    #[serde(default)]
+    pub input: Value,
+}
+
+/// Load a mock script: a JSON array of [`MockTurn`]s played in order
+pub fn load_mock_script(path: &str) -> Result<Vec<MockTurn>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read mock script: {}", path))?;
+    serde_json::from_str(&content).with_context(|| format!("Invalid mock script: {}", path))
+}
+
+/// Replays scripted turns, then echoes the user once the script runs out
+#[derive(Debug, Default)]
+pub struct MockUpstream {
+    turns: Vec<MockTurn>,
+    next_turn: std::sync::atomic::AtomicUsize,
+}
+
+impl MockUpstream {
+    pub fn new(turns: Vec<MockTurn>) -> Self {
+        Self {
+            turns,
+            next_turn: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    fn respond(&self, request: &AnthropicRequest, model: &str) -> AnthropicResponse {
+        // Titles, summaries and token counts must not consume scripted turns
+        let turn = if is_auxiliary_request(request) {
+            None
+        } else {
+            let index = self
+                .next_turn
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.turns.get(index).cloned()
+        };
+        let turn = turn.unwrap_or_else(|| MockTurn {
+            text: Some(mock_echo(request)),
+            tool_use: None,
+        });
+
+        let mut content = Vec::new();
+        if let Some(text) = turn.text {
+            content.push(ResponseContent::Text { text });
+        }
+        let stop_reason = match turn.tool_use {
+            Some(call) => {
+                content.push(ResponseContent::ToolUse {
+                    id: format!("toolu_mock_{}", uuid_simple()),
+                    name: call.name,
+                    input: call.input,
+                });
+                "tool_use"
+            }
+            None => "end_turn",
+        };
+
+        let output_chars: usize = content
+            .iter()
+            .map(|block| serde_json::to_string(block).map_or(0, |s| s.len()))
+            .sum();
+        let input_chars = serde_json::to_string(&request.messages).map_or(0, |s| s.len());
+        AnthropicResponse {
+            id: format!("msg_mock_{}", uuid_simple()),
+            response_type: "message".to_string(),
+            role: "assistant".to_string(),
+            content,
+            model: model.to_string(),
+            stop_reason: Some(stop_reason.to_string()),
+            stop_sequence: None,
+            // Roughly four characters per token
+            usage: AnthropicUsage {
+                input_tokens: (input_chars / 4) as u32,
+                output_tokens: (output_chars / 4).max(1) as u32,
+                ..AnthropicUsage::default()
+            },
+        }
+    }
+}
+
+/// Default reply: quote the latest user text, or acknowledge tool results
+fn mock_echo(request: &AnthropicRequest) -> String {
+    let Some(message) = request.messages.iter().rev().find(|m| m.role == "user") else {
+        return "Mock response.".to_string();
+    };
+    let text = match &message.content {
+        AnthropicContent::Text(text) => text.clone(),
+        AnthropicContent::Blocks(blocks) => {
+            if blocks
+                .iter()
+                .any(|block| matches!(block, ContentBlock::ToolResult { .. }))
+            {
+                return "Mock response: tool result received.".to_string();
+            }
+            blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text, .. } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    };
+    let quoted: String = text.trim().chars().take(MOCK_ECHO_CHARS).collect();
+    format!("Mock response to: {}", quoted)
+}
+
+pub(crate) fn handle_mock_request(
+    state: &ProxyState,
+    request: &AnthropicRequest,
+    original_model: &str,
+    is_streaming: bool,
+) -> Response {
+    replay_response(state.mock.respond(request, original_model), is_streaming)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn mock_upstream_plays_script_then_echoes() {
+        let mock = MockUpstream::new(vec![MockTurn {
+            text: Some("Listing files".to_string()),
+            tool_use: Some(MockToolCall {
+                name: "Bash".to_string(),
+                input: json!({ "command": "ls" }),
+            }),
+        }]);
+        let mut req = base_request(vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Text("what is here?".to_string()),
+        }]);
+        req.max_tokens = Some(1024);
+
+        let first = mock.respond(&req, "claude-sonnet");
+        assert_eq!(first.stop_reason.as_deref(), Some("tool_use"));
+        assert!(matches!(
+            &first.content[1],
+            ResponseContent::ToolUse { name, input, .. } if name == "Bash" && input["command"] == "ls"
+        ));
+
+        let events = replay_sse_events(&first);
+        assert!(events[0].starts_with("event: message_start"));
+        assert!(
+            events
+                .iter()
+                .any(|e| e.contains("\"partial_json\":\"{\\\"command\\\":\\\"ls\\\"}\""))
+        );
+        assert!(
+            events
+                .iter()
+                .any(|e| e.contains("\"stop_reason\":\"tool_use\""))
+        );
+
+        let second = mock.respond(&req, "claude-sonnet");
+        assert_eq!(second.stop_reason.as_deref(), Some("end_turn"));
+        assert!(matches!(
+            &second.content[0],
+            ResponseContent::Text { text } if text == "Mock response to: what is here?"
+        ));
+        assert_eq!(build_upstream_urls("mock://demo").3, UpstreamMode::Mock);
+    }
+}
@@ -0,0 +1,212 @@
+//! Rust-native proxy for translating between Anthropic and OpenAI API formats.
+//!
+//! This proxy allows Claude Code (which expects Anthropic API) to communicate with
+//! OpenAI-compatible endpoints (Responses or Completions) without requiring Python/LiteLLM.
+//!
+//! - `types`: wire types for the Anthropic and OpenAI APIs
+//! - `lenient`: tolerant reading of off-spec upstream responses
+//! - `translate`: request/response conversion per upstream API, Bedrock included
+//! - `stream`: SSE conversion into Anthropic events, batching and usage tracking
+//! - `dialect`: normalizing the Chat Completions streams of vLLM, llama.cpp and Ollama
+//! - `state`: proxy state and the options it is started with
+//! - `server`: the axum router, endpoint probing and the messages handler
+//! - `upstream`: sending requests upstream and the per-API request handlers
+//! - `http`: the HTTP backend upstream requests are sent through and the OAuth token
+//!   refresher, both replaceable in tests
+//! - `mock`, `strict_tools`, `compaction`, `chains`: optional request shaping
+//! - `consensus`: the experimental two-model consensus mode
+//! - `passthrough`: forwarding requests untranslated to Anthropic upstreams
+//! - `empty_reply`: retrying and replacing replies without content
+//! - `auxiliary`: telling auxiliary requests apart, and moving the kinds the auxiliary
+//!   model keeps failing to the main model
+//! - `retry`: retrying rate limits, server errors and failed connections
+//! - `readiness`: holding requests until a local server has loaded its model
+//! - `request_log`: the optional per-request log and its reader
+//! - `capture`: optional full request and response bodies for the inspector, and replay
+//! - `tokens`: local token counting for `/v1/messages/count_tokens`
+//! - `tool_trim`: cutting oversized tool results down to their start and end
+//! - `quota`: Codex usage limits and request pacing
+//! - `routes`: sending requests for some models to upstreams of their own
+//! - `fanout`: spreading parallel sub-agent conversations over several upstreams
+//! - `headers`: which request and response headers pass through the proxy
+//! - `daemon`: one long-lived proxy serving several profiles, with a control API
+//! - `reload`: `/admin/reload`, swapping in a session proxy's settings from disk
+//! - `access`: keeping the control endpoints to this machine
+
+mod access;
+mod auxiliary;
+mod capture;
+mod chains;
+mod compaction;
+mod consensus;
+mod daemon;
+mod dialect;
+mod empty_reply;
+mod fanout;
+mod headers;
+mod http;
+mod lenient;
+mod mock;
+mod passthrough;
+mod quota;
+mod readiness;
+mod reload;
+mod request_log;
+mod retry;
+mod routes;
+mod server;
+mod state;
+mod stream;
+mod strict_tools;
+mod tokens;
+mod tool_trim;
+mod translate;
+pub mod types;
+mod upstream;
+
+pub use capture::{Capture, CaptureLog, Replayed, load_captures, replay};
+pub use compaction::Compaction;
+pub use daemon::{DaemonLoad, DaemonLoader, DaemonProfile, daemon_profile_path, serve_daemon};
+pub use fanout::FanoutUpstream;
+pub use http::{HttpBackend, TokenRefresher, UpstreamHttp};
+pub use mock::{MockTurn, is_mock_url, load_mock_script};
+pub use quota::{QuotaSnapshot, read_codex_quota};
+pub use reload::ProxyReloader;
+pub use request_log::{LogTail, REQUEST_LOG_FILE, RequestLog, RequestLogEntry};
+pub use retry::RetryPolicy;
+pub use routes::RouteOptions;
+pub use server::{Shutdown, start_server};
+pub use state::{
+    EndpointOverrides, ListedModel, ListenAddr, ProxyOptions, ReasoningSummary, Sampling,
+    SamplingOverrides, SseOptions, ThinkingHint, TokenLimits,
+};
+pub use tool_trim::tool_result_limit;
+pub use translate::{
+    anthropic_to_chat, anthropic_to_completions, anthropic_to_responses, chat_to_anthropic,
+    completions_to_anthropic, responses_to_anthropic,
+};
+
+use access::*;
+use auxiliary::*;
+use capture::*;
+use chains::*;
+use compaction::*;
+use consensus::*;
+use dialect::*;
+use empty_reply::*;
+use fanout::*;
+use headers::*;
+use http::StoredSignIn;
+use mock::*;
+use passthrough::*;
+use quota::*;
+use readiness::*;
+use reload::*;
+use request_log::*;
+use retry::*;
+use routes::*;
+use server::*;
+use state::*;
+use stream::*;
+use strict_tools::*;
+use tokens::*;
+use tool_trim::*;
+use translate::*;
+use types::*;
+use upstream::*;
+
+use anyhow::{Context, Result};
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    serve::ListenerExt,
+};
+use futures::stream::Stream;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::Infallible;
+use std::ops::ControlFlow;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::anthropic_oauth;
+use crate::auth::{AuthProvider, Credentials};
+use crate::codex_instructions::{CLAUDE_CODE_BRIDGE, get_codex_instructions};
+use crate::config::{
+    Config, ENV_CHAT_COMPLETIONS_URL, ENV_COMPACT_THRESHOLD, ENV_COMPLETIONS_URL,
+    ENV_CONTEXT_TOKENS, ENV_DEFAULT_HAIKU_MODEL, ENV_DEFAULT_OPUS_MODEL, ENV_DEFAULT_OUTPUT_TOKENS,
+    ENV_DEFAULT_SONNET_MODEL, ENV_HAIKU_SAMPLING, ENV_MAX_OUTPUT_TOKENS, ENV_MIN_OUTPUT_TOKENS,
+    ENV_MODEL, ENV_OPUS_SAMPLING, ENV_REASONING_SUMMARY, ENV_RESPONSES_URL, ENV_RETRY_ATTEMPTS,
+    ENV_RETRY_BASE_MS, ENV_RETRY_MAX_MS, ENV_SMALL_FAST_MODEL, ENV_SONNET_SAMPLING,
+    ENV_SSE_BATCH_MS, ENV_SSE_DEBUG, ENV_THINKING_HINT_BUDGET, ENV_TOOL_RESULT_MAX_KB,
+    HeaderPolicy, LoggingPolicy, wildcard_match,
+};
+use crate::lanes::{Lane, LanePermit, PriorityLimiter};
+use crate::local_models::{IdleUnloader, LeaseFile, LocalBackend};
+use crate::openai_oauth;
+use crate::platform;
+use crate::pricing::UsageTracker;
+use crate::provider_errors;
+
+/// Default port for the proxy server
+pub const PROXY_PORT: u16 = 4000;
+
+/// Default address the proxy server binds to
+pub const PROXY_BIND: &str = "127.0.0.1";
+
+/// The base URL that Claude Code uses to connect to a proxy on the default port
+pub const PROXY_ANTHROPIC_URL: &str = "http://localhost:4000/anthropic";
+
+/// Proxy log file name under the config directory
+pub const PROXY_LOG_FILE: &str = "proxy.log";
+
+/// Blob handling for the log sinks, set once from the config at startup
+static LOGGING: OnceLock<LoggingPolicy> = OnceLock::new();
+
+pub fn configure_logging(policy: LoggingPolicy) {
+    let _ = LOGGING.set(policy);
+}
+
+/// Where a log line comes from; each sink has its own blob handling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogSink {
+    Proxy,
+    SseDebug,
+}
+
+/// Append a line to the proxy log in the config directory.
+///
+/// Claude Code owns the terminal while the proxy runs, so nothing is printed.
+fn proxy_log(message: &str) {
+    log_to(LogSink::Proxy, message);
+}
+
+fn log_to(sink: LogSink, message: &str) {
+    use std::io::Write as _;
+
+    let Some(dir) = Config::config_dir() else {
+        return;
+    };
+    let policy = LOGGING.get().copied().unwrap_or_default();
+    let message = match sink {
+        LogSink::Proxy => policy.proxy_log.apply(message),
+        LogSink::SseDebug => policy.sse_debug.apply(message),
+    };
+    let message = policy.secrets.apply(&message);
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    if let Ok(mut file) = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(PROXY_LOG_FILE))
+    {
+        let _ = writeln!(file, "[{}] {}", timestamp, message);
+    }
+}
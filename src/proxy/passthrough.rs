@@ -0,0 +1,186 @@
+//! Anthropic passthrough: `/v1/messages` requests go to an Anthropic-compatible
+//! upstream byte for byte, so a profile that already speaks Anthropic can use the
+//! proxy for usage tracking and the request log alone.
+
+use super::*;
+
+/// Claude Code's credentials, which an Anthropic upstream accepts as they are. The
+/// header policy decides which of its other headers go along.
+const CREDENTIAL_HEADERS: [&str; 2] = ["authorization", "x-api-key"];
+
+/// Whether a target URL is an Anthropic Messages upstream: the Anthropic API itself,
+/// or an explicit `/messages` endpoint
+pub(crate) fn is_anthropic_url(url: &str) -> bool {
+    let trimmed = url.trim_end_matches('/');
+    trimmed.ends_with("/messages")
+        || url::Url::parse(trimmed)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+            .is_some_and(|host| host == "anthropic.com" || host.ends_with(".anthropic.com"))
+}
+
+/// Messages endpoint for a target URL
+pub(crate) fn anthropic_messages_url(target_url: &str) -> String {
+    let trimmed = target_url.trim_end_matches('/');
+    if trimmed.ends_with("/messages") {
+        trimmed.to_string()
+    } else {
+        format!("{}/messages", with_v1(trimmed))
+    }
+}
+
+/// Hand an upstream reply to Claude Code as it arrived, streamed or not, with its
+/// content type and the headers the policy relays
+fn relay_response(policy: &HeaderPolicy, upstream: reqwest::Response) -> Response {
+    let mut response = Response::builder().status(upstream.status());
+    if let Some(content_type) = upstream.headers().get(header::CONTENT_TYPE) {
+        response = response.header(header::CONTENT_TYPE, content_type);
+    }
+    for (name, value) in &relayed_headers(policy, upstream.headers()) {
+        if name != header::CONTENT_TYPE {
+            response = response.header(name, value);
+        }
+    }
+    response
+        .body(Body::from_stream(upstream.bytes_stream()))
+        .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+}
+
+/// Forward a `/v1/messages` body unchanged, with Claude Code's own auth and the
+/// headers the policy forwards, then record it like any other request
+pub(crate) async fn passthrough_messages(
+    state: Arc<ProxyState>,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    let started = std::time::Instant::now();
+    // Parsed only for the metrics and the lane; the body is sent as received
+    let request = serde_json::from_slice::<AnthropicRequest>(&body).ok();
+    let model = request
+        .as_ref()
+        .map(|request| request.model.clone())
+        .unwrap_or_default();
+    let lane = request.as_ref().map_or(Lane::Interactive, request_lane);
+    let permit = acquire_permit(&state, lane, &model).await;
+    let summary = RequestSummary {
+        model: model.clone(),
+        upstream_model: model,
+        api: UpstreamMode::Anthropic.label(),
+        stream: request.is_some_and(|request| request.stream.unwrap_or(false)),
+        started,
+    };
+
+    let response = forward_untranslated(&state, &headers, &state.messages_url, body).await;
+    deliver_response(&state, summary, permit, response).await
+}
+
+/// Forward a `/v1/messages/count_tokens` body to the upstream's own endpoint
+pub(crate) async fn passthrough_count_tokens(
+    state: Arc<ProxyState>,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    let url = format!("{}/count_tokens", state.messages_url);
+    forward_untranslated(&state, &headers, &url, body).await
+}
+
+/// POST `body` to `url` with Claude Code's auth and forwarded headers and relay the reply
+async fn forward_untranslated(
+    state: &ProxyState,
+    headers: &HeaderMap,
+    url: &str,
+    body: bytes::Bytes,
+) -> Response {
+    let mut builder = state
+        .http
+        .post(url)
+        .header(header::CONTENT_TYPE, "application/json");
+    for name in CREDENTIAL_HEADERS {
+        if let Some(value) = headers.get(name) {
+            builder = builder.header(name, value);
+        }
+    }
+    for (name, value) in &forwarded_headers(&state.header_policy, headers) {
+        if !state
+            .headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case(name.as_str()))
+        {
+            builder = builder.header(name, value);
+        }
+    }
+    for (name, value) in &state.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    let request = builder.body(body);
+    let result = send_with_retries(&state.retry, url, || {
+        // A body of bytes can always be cloned
+        let attempt = request.try_clone();
+        async move {
+            match attempt {
+                Some(attempt) => state
+                    .http
+                    .send(attempt)
+                    .await
+                    .map_err(UpstreamError::connect),
+                None => Err(UpstreamError::connect("request body cannot be resent")),
+            }
+        }
+    })
+    .await;
+    match result {
+        Ok(upstream) => relay_response(&state.header_policy, upstream),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Send an already Anthropic-shaped request (e.g. a compaction summary) to the
+/// passthrough upstream
+pub(crate) async fn handle_anthropic_request(
+    state: Arc<ProxyState>,
+    request: AnthropicRequest,
+    auth_header: Option<String>,
+) -> Response {
+    let result = send_with_retries(&state.retry, &state.messages_url, || {
+        send_json_request(
+            &state.http,
+            &state.messages_url,
+            &request,
+            auth_header.as_deref(),
+            &state.credentials,
+            &state.headers,
+        )
+    })
+    .await;
+    match result {
+        Ok(upstream) => relay_response(&state.header_policy, upstream),
+        Err(err) => err.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anthropic_targets_are_detected() {
+        assert!(is_anthropic_url("https://api.anthropic.com"));
+        assert!(is_anthropic_url(
+            "https://gateway.example/anthropic/v1/messages/"
+        ));
+        assert!(!is_anthropic_url("https://api.openai.com/v1"));
+        assert!(!is_anthropic_url("https://notanthropic.com/v1"));
+        assert_eq!(
+            anthropic_messages_url("https://api.anthropic.com/"),
+            "https://api.anthropic.com/v1/messages"
+        );
+        assert_eq!(
+            anthropic_messages_url("https://gateway.example/v1/messages"),
+            "https://gateway.example/v1/messages"
+        );
+        assert_eq!(
+            build_upstream_urls("https://api.anthropic.com").3,
+            UpstreamMode::Anthropic
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Codex subscription limits: the usage-limit headers the ChatGPT backend sends
+//! with every response are remembered, saved for the TUI, and used to pace
+//! requests as a window's quota runs out instead of running into hard errors.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::*;
+
+/// Latest Codex usage limits, under the config directory
+pub const CODEX_QUOTA_FILE: &str = "codex-quota.json";
+
+/// Used share of a window at which requests start being paced
+const PACE_FROM_PERCENT: f64 = 80.0;
+
+/// Delay added to each request as a window approaches 100% used
+const MAX_PACE_DELAY: Duration = Duration::from_secs(10);
+
+/// A request for an exhausted window waits for its reset only if it is this close
+const MAX_RESET_WAIT: Duration = Duration::from_secs(5 * 60);
+
+/// One usage-limit window, e.g. the 5-hour or the weekly limit
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateWindow {
+    pub used_percent: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_minutes: Option<u64>,
+    /// Unix seconds when the window resets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resets_at: Option<u64>,
+}
+
+impl RateWindow {
+    fn from_headers(headers: &reqwest::header::HeaderMap, prefix: &str, now: u64) -> Option<Self> {
+        let value = |name: &str| {
+            headers
+                .get(format!("x-codex-{}-{}", prefix, name))
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+        };
+        let used_percent = value("used-percent")?.parse::<f64>().ok()?;
+        let resets_at = value("reset-at")
+            .and_then(|v| v.parse::<u64>().ok())
+            .or_else(|| {
+                value("reset-after-seconds")
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .map(|secs| now + secs)
+            });
+        Some(Self {
+            used_percent,
+            window_minutes: value("window-minutes").and_then(|v| v.parse().ok()),
+            resets_at,
+        })
+    }
+
+    /// Share of the window left at `now`; a window past its reset is full again
+    pub fn remaining_percent(&self, now: u64) -> f64 {
+        if self.resets_at.is_some_and(|at| at <= now) {
+            return 100.0;
+        }
+        (100.0 - self.used_percent).clamp(0.0, 100.0)
+    }
+
+    /// How long a request should wait before using this window
+    fn pacing_delay(&self, now: u64) -> Duration {
+        if self.remaining_percent(now) > 100.0 - PACE_FROM_PERCENT {
+            return Duration::ZERO;
+        }
+        if self.used_percent >= 100.0 {
+            let until_reset = self
+                .resets_at
+                .map(|at| Duration::from_secs(at.saturating_sub(now)));
+            return match until_reset {
+                Some(wait) if wait <= MAX_RESET_WAIT => wait,
+                // Waiting hours helps nobody; let the upstream answer
+                _ => Duration::ZERO,
+            };
+        }
+        let pressure = (self.used_percent - PACE_FROM_PERCENT) / (100.0 - PACE_FROM_PERCENT);
+        MAX_PACE_DELAY.mul_f64(pressure.clamp(0.0, 1.0))
+    }
+
+    fn label(&self, fallback: &'static str) -> String {
+        match self.window_minutes {
+            Some(300) => "5h".to_string(),
+            Some(10080) => "weekly".to_string(),
+            Some(minutes) if minutes % 1440 == 0 => format!("{}d", minutes / 1440),
+            Some(minutes) if minutes % 60 == 0 => format!("{}h", minutes / 60),
+            Some(minutes) => format!("{}m", minutes),
+            None => fallback.to_string(),
+        }
+    }
+}
+
+/// `1h 20m`-style duration
+fn format_wait(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h {}m", secs / 3600, secs / 60 % 60),
+        _ => format!("{}d {}h", secs / 86400, secs / 3600 % 24),
+    }
+}
+
+/// Codex usage limits as of the last response that reported them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QuotaSnapshot {
+    /// Unix seconds of the response
+    pub observed_at: u64,
+    /// The short (5-hour) window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary: Option<RateWindow>,
+    /// The long (weekly) window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary: Option<RateWindow>,
+}
+
+impl QuotaSnapshot {
+    pub fn from_headers(headers: &reqwest::header::HeaderMap, now: u64) -> Option<Self> {
+        let primary = RateWindow::from_headers(headers, "primary", now);
+        let secondary = RateWindow::from_headers(headers, "secondary", now);
+        (primary.is_some() || secondary.is_some()).then_some(Self {
+            observed_at: now,
+            primary,
+            secondary,
+        })
+    }
+
+    fn windows(&self) -> impl Iterator<Item = (RateWindow, &'static str)> {
+        [(self.primary, "5h"), (self.secondary, "weekly")]
+            .into_iter()
+            .filter_map(|(window, fallback)| window.map(|w| (w, fallback)))
+    }
+
+    /// Longest wait any window asks for
+    pub fn pacing_delay(&self, now: u64) -> Duration {
+        self.windows()
+            .map(|(window, _)| window.pacing_delay(now))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// `5h: 38% left (resets in 1h 20m), weekly: 88% left (resets in 3d 2h)`
+    pub fn summary(&self, now: u64) -> String {
+        self.windows()
+            .map(|(window, fallback)| {
+                let remaining = window.remaining_percent(now);
+                let reset = match window.resets_at {
+                    Some(at) if at > now => format!(" (resets in {})", format_wait(at - now)),
+                    _ => String::new(),
+                };
+                format!(
+                    "{}: {:.0}% left{}",
+                    window.label(fallback),
+                    remaining,
+                    reset
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The saved snapshot, if a Codex session has reported one
+pub fn read_codex_quota() -> Option<QuotaSnapshot> {
+    let path = Config::config_dir()?.join(CODEX_QUOTA_FILE);
+    serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Usage limits seen during a Codex session
+#[derive(Debug)]
+pub struct CodexQuota {
+    latest: Mutex<Option<QuotaSnapshot>>,
+    /// Delay requests as a window runs out
+    pacing: bool,
+    path: Option<PathBuf>,
+}
+
+impl CodexQuota {
+    pub(crate) fn new(pacing: bool) -> Self {
+        Self {
+            latest: Mutex::new(read_codex_quota()),
+            pacing,
+            path: Config::config_dir().map(|dir| dir.join(CODEX_QUOTA_FILE)),
+        }
+    }
+
+    /// Remember the limits reported by an upstream response
+    pub(crate) fn observe(&self, headers: &reqwest::header::HeaderMap) {
+        let Some(snapshot) = QuotaSnapshot::from_headers(headers, unix_now()) else {
+            return;
+        };
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some(snapshot);
+        }
+        if let (Some(path), Ok(json)) = (&self.path, serde_json::to_string(&snapshot)) {
+            let _ = std::fs::write(path, json);
+        }
+    }
+
+    /// Wait as long as the latest limits ask before sending a request
+    pub(crate) async fn pace(&self) {
+        if !self.pacing {
+            return;
+        }
+        let now = unix_now();
+        let Some(snapshot) = self.latest.lock().ok().and_then(|latest| *latest) else {
+            return;
+        };
+        let delay = snapshot.pacing_delay(now);
+        if delay.is_zero() {
+            return;
+        }
+        proxy_log(&format!(
+            "Codex quota low ({}); pacing request by {:.1}s",
+            snapshot.summary(now),
+            delay.as_secs_f64()
+        ));
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codex_limits_are_read_and_paced_as_they_run_out() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in [
+            ("x-codex-primary-used-percent", "90"),
+            ("x-codex-primary-window-minutes", "300"),
+            ("x-codex-primary-reset-after-seconds", "4800"),
+            ("x-codex-secondary-used-percent", "12.5"),
+            ("x-codex-secondary-window-minutes", "10080"),
+            ("x-codex-secondary-reset-at", "1000000"),
+        ] {
+            headers.insert(name, value.parse().unwrap());
+        }
+        let now = 1000;
+        let snapshot = QuotaSnapshot::from_headers(&headers, now).unwrap();
+        assert_eq!(snapshot.primary.unwrap().resets_at, Some(5800));
+        assert_eq!(
+            snapshot.summary(now),
+            "5h: 10% left (resets in 1h 20m), weekly: 88% left (resets in 11d 13h)"
+        );
+        assert_eq!(snapshot.pacing_delay(now), Duration::from_secs(5));
+
+        let mut exhausted = snapshot;
+        exhausted.primary = Some(RateWindow {
+            used_percent: 100.0,
+            window_minutes: Some(300),
+            resets_at: Some(now + 120),
+        });
+        assert_eq!(exhausted.pacing_delay(now), Duration::from_secs(120));
+        assert_eq!(exhausted.pacing_delay(now + 3600), Duration::ZERO);
+        exhausted.primary.as_mut().unwrap().resets_at = Some(now + 3600);
+        assert_eq!(exhausted.pacing_delay(now), Duration::ZERO);
+
+        assert_eq!(
+            QuotaSnapshot::from_headers(&reqwest::header::HeaderMap::new(), now),
+            None
+        );
+    }
+}
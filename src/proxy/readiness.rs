@@ -0,0 +1,187 @@
+//! Holding requests while a local server is still starting or loading its model.
+//!
+//! Claude Code sends its first requests as soon as it starts, which is often before LM
+//! Studio (or Ollama, or `llama-server`) accepts connections or has loaded the model.
+//! Until the server's model list answers, requests wait in a queue instead of failing
+//! with connection errors; one probe runs at a time and every waiting request is let
+//! through once it passes. A server that is not ready within the profile's limit gets
+//! an Anthropic-style error saying so, rather than a bare 502.
+
+use super::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// How long requests wait for a local server unless `PROXY_READY_WAIT_SECS` says otherwise
+pub(crate) const DEFAULT_READY_WAIT: Duration = Duration::from_secs(120);
+
+/// Pause between readiness probes
+const PROBE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time one probe may take; a server that is busy loading can be slow to answer
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether a local server is ready for requests, and the queue of those waiting
+pub(crate) struct ReadinessGate {
+    backend: LocalBackend,
+    probe_url: url::Url,
+    max_wait: Duration,
+    ready: AtomicBool,
+    /// Held by the request probing; the others queue behind it
+    probing: tokio::sync::Mutex<()>,
+}
+
+impl ReadinessGate {
+    /// A gate for `target_url` when it is a local server and waiting is not turned off
+    pub(crate) fn new(target_url: &str, max_wait: Duration) -> Option<Self> {
+        if max_wait.is_zero() {
+            return None;
+        }
+        let backend = LocalBackend::detect(target_url)?;
+        Some(Self {
+            backend,
+            probe_url: backend.models_url(target_url).ok()?,
+            max_wait,
+            ready: AtomicBool::new(false),
+            probing: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// Whether a probe reply means the server takes requests. `llama-server` answers
+    /// 503 while it loads the model; any other reply means something is listening.
+    fn is_ready_status(status: StatusCode) -> bool {
+        status != StatusCode::SERVICE_UNAVAILABLE
+    }
+
+    async fn probe(&self, client: &reqwest::Client) -> bool {
+        client
+            .get(self.probe_url.clone())
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .is_ok_and(|response| Self::is_ready_status(response.status()))
+    }
+
+    /// Wait until the server is ready, or the error to give Claude Code when it does
+    /// not become ready in time
+    pub(crate) async fn wait(&self, client: &reqwest::Client) -> Result<(), Response> {
+        if self.ready.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let started = tokio::time::Instant::now();
+        let deadline = started + self.max_wait;
+        let Ok(_probing) = tokio::time::timeout_at(deadline, self.probing.lock()).await else {
+            return Err(self.not_ready_response());
+        };
+        // Another request may have seen the server come up while this one queued
+        if self.ready.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let mut logged = false;
+        loop {
+            if self.probe(client).await {
+                self.ready.store(true, Ordering::Release);
+                if logged {
+                    proxy_log(&format!(
+                        "{} ready after {:.1}s; releasing queued requests",
+                        self.backend.label(),
+                        started.elapsed().as_secs_f64()
+                    ));
+                }
+                return Ok(());
+            }
+            if !logged {
+                proxy_log(&format!(
+                    "{} at {} is not ready; holding requests for up to {}s",
+                    self.backend.label(),
+                    self.probe_url,
+                    self.max_wait.as_secs()
+                ));
+                logged = true;
+            }
+            if tokio::time::Instant::now() + PROBE_INTERVAL >= deadline {
+                proxy_log(&format!(
+                    "{} not ready after {}s; failing queued requests",
+                    self.backend.label(),
+                    self.max_wait.as_secs()
+                ));
+                return Err(self.not_ready_response());
+            }
+            tokio::time::sleep(PROBE_INTERVAL).await;
+        }
+    }
+
+    fn not_ready_response(&self) -> Response {
+        let message = format!(
+            "{} did not become ready within {}s ({} is unreachable or still loading the \
+             model). Start the server and load the model, or raise PROXY_READY_WAIT_SECS.",
+            self.backend.label(),
+            self.max_wait.as_secs(),
+            self.probe_url
+        );
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "type": "error",
+                "error": { "type": "api_error", "message": message }
+            })),
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn requests_wait_until_the_local_server_is_ready() {
+        // `llama-server` style: 503 while loading, then ready
+        let probes = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/v1/models",
+            get({
+                let probes = probes.clone();
+                move || async move {
+                    match probes.fetch_add(1, Ordering::SeqCst) {
+                        0 | 1 => StatusCode::SERVICE_UNAVAILABLE,
+                        _ => StatusCode::OK,
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let gate = Arc::new(ReadinessGate::new(&url, Duration::from_secs(10)).unwrap());
+        let (a, b) = tokio::join!(gate.wait(&client), gate.wait(&client));
+        assert!(a.is_ok() && b.is_ok());
+        assert_eq!(probes.load(Ordering::SeqCst), 3);
+        assert!(gate.wait(&client).await.is_ok());
+        assert_eq!(probes.load(Ordering::SeqCst), 3);
+
+        // Nothing listening: the wait runs out into an Anthropic error
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1", closed.local_addr().unwrap());
+        drop(closed);
+        let gate = ReadinessGate::new(&url, Duration::from_millis(600)).unwrap();
+        let response = gate.wait(&client).await.unwrap_err();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "api_error");
+        assert!(
+            body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("PROXY_READY_WAIT_SECS")
+        );
+
+        assert!(ReadinessGate::new(&url, Duration::ZERO).is_none());
+        assert!(ReadinessGate::new("https://api.openai.com/v1", DEFAULT_READY_WAIT).is_none());
+    }
+}
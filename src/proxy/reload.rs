@@ -0,0 +1,217 @@
+//! Live reload of a session proxy: `POST /admin/reload` prepares the profile's proxy
+//! settings again from `profiles.toml` (target URL, model map, headers and the rest)
+//! and builds a new state for them. New requests are served by the new state;
+//! requests in flight finish on the one they started with.
+//!
+//! The listen address stays as it is, and token usage keeps counting into the
+//! session's tracker so the cost printed on exit covers the whole session.
+//!
+//! Only processes on this machine may reload, and never a web page (see `access`).
+
+use super::*;
+use std::sync::RwLock;
+use tower::ServiceExt;
+
+/// Prepares the profile's proxy target and options again. Blocking.
+pub type ProxyReloader = Arc<dyn Fn() -> Result<(String, ProxyOptions)> + Send + Sync>;
+
+/// The state serving a session proxy, replaced on reload
+pub(crate) struct LiveProxy {
+    current: RwLock<(Arc<ProxyState>, Router)>,
+    reloader: Option<ProxyReloader>,
+    /// One reload at a time
+    reloading: tokio::sync::Mutex<()>,
+}
+
+impl LiveProxy {
+    pub(crate) fn new(state: Arc<ProxyState>, reloader: Option<ProxyReloader>) -> Arc<Self> {
+        let router = router(state.clone());
+        Arc::new(Self {
+            current: RwLock::new((state, router)),
+            reloader,
+            reloading: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    /// The state serving new requests
+    pub(crate) fn state(&self) -> Arc<ProxyState> {
+        let current = self.current.read().unwrap_or_else(|e| e.into_inner());
+        current.0.clone()
+    }
+
+    /// The router to serve: the state's own, with `/admin/reload` in front of it
+    /// when the launcher can prepare the profile again
+    pub(crate) fn router(self: &Arc<Self>) -> Router {
+        if self.reloader.is_none() {
+            let current = self.current.read().unwrap_or_else(|e| e.into_inner());
+            return current.1.clone();
+        }
+        Router::new()
+            .route("/admin/reload", post(reload_handler))
+            .route_layer(axum::middleware::from_fn(local_control_only))
+            .fallback(dispatch)
+            .with_state(self.clone())
+    }
+
+    /// Build a state from the profile as it is on disk now and swap it in
+    async fn reload(&self) -> Result<Arc<ProxyState>> {
+        let reloader = self.reloader.clone().context("This proxy cannot reload")?;
+        let _reloading = self.reloading.lock().await;
+        let (target_url, mut options) = tokio::task::spawn_blocking(move || reloader()).await??;
+        let old = self.state();
+        options.usage = old.usage.clone();
+        let state = build_state(target_url, options).await?;
+        if state.auth_provider == old.auth_provider {
+            // A token refreshed during the session is newer than the one signed in with
+            let refreshed = old.refreshed_token.lock().await.clone();
+            *state.refreshed_token.lock().await = refreshed;
+        }
+        if let Some(unloader) = &old.idle_unloader {
+            unloader.release_lease();
+        }
+        let router = router(state.clone());
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = (state.clone(), router);
+        proxy_log(&format!(
+            "Reloaded the proxy settings; now targeting {}",
+            state.target_url
+        ));
+        Ok(state)
+    }
+}
+
+async fn reload_handler(State(proxy): State<Arc<LiveProxy>>) -> Response {
+    match proxy.reload().await {
+        Ok(state) => Json(serde_json::json!({
+            "reloaded": true,
+            "target_url": state.target_url,
+            "api": state.upstream_mode.read().await.label(),
+        }))
+        .into_response(),
+        Err(e) => {
+            proxy_log(&format!("Reload failed, keeping the old settings: {:#}", e));
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": format!("{:#}", e) })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Hand a request to the current state's router
+async fn dispatch(
+    State(proxy): State<Arc<LiveProxy>>,
+    request: axum::extract::Request,
+) -> Response {
+    let router = {
+        let current = proxy.current.read().unwrap_or_else(|e| e.into_inner());
+        current.1.clone()
+    };
+    match router.oneshot(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::ConnectInfo;
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn reloading_swaps_in_the_settings_on_disk() {
+        let on_disk = Arc::new(Mutex::new(Some("first-model".to_string())));
+        let disk = on_disk.clone();
+        let reloader: ProxyReloader = Arc::new(move || {
+            let model = disk.lock().unwrap().clone().context("profile removed")?;
+            Ok((
+                "mock:".to_string(),
+                ProxyOptions {
+                    model_override: Some(model),
+                    ..Default::default()
+                },
+            ))
+        });
+        let (target_url, options) = reloader().unwrap();
+        let usage = options.usage.clone();
+        let state = build_state(target_url, options).await.unwrap();
+        let live = LiveProxy::new(state, Some(reloader));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = live
+            .router()
+            .into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let upstream_model = || async {
+            let response = client
+                .post(format!("{}/v1/messages", url))
+                .json(&json!({
+                    "model": "claude-sonnet-4-5",
+                    "max_tokens": 16,
+                    "messages": [{ "role": "user", "content": "hi" }]
+                }))
+                .send()
+                .await
+                .unwrap();
+            response.headers()[UPSTREAM_MODEL_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+        let reload = || async {
+            let response = client
+                .post(format!("{}/admin/reload", url))
+                .send()
+                .await
+                .unwrap();
+            (response.status(), response.json::<Value>().await.unwrap())
+        };
+
+        assert_eq!(upstream_model().await, "first-model");
+        *on_disk.lock().unwrap() = Some("second-model".to_string());
+        let (status, reply) = reload().await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(reply["target_url"], "mock:");
+        assert_eq!(upstream_model().await, "second-model");
+
+        // A failed reload keeps the settings in use
+        *on_disk.lock().unwrap() = None;
+        let (status, reply) = reload().await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(reply["error"], "profile removed");
+        assert_eq!(upstream_model().await, "second-model");
+
+        // Web pages and other machines cannot reload
+        *on_disk.lock().unwrap() = Some("third-model".to_string());
+        let response = client
+            .post(format!("{}/admin/reload", url))
+            .header(header::ORIGIN, "https://example.com")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let mut remote = axum::extract::Request::post("/admin/reload")
+            .body(Body::empty())
+            .unwrap();
+        remote
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([192, 168, 1, 20], 50000))));
+        let response = live.router().oneshot(remote).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(upstream_model().await, "second-model");
+
+        // Usage from before and after the reload lands in the session's tracker
+        let models: Vec<String> = usage
+            .snapshot()
+            .into_iter()
+            .map(|(model, _)| model)
+            .collect();
+        assert!(models.contains(&"first-model".to_string()));
+        assert!(models.contains(&"second-model".to_string()));
+    }
+}
@@ -0,0 +1,287 @@
+//! Optional request log: one JSON line per proxied request in `requests.jsonl`,
+//! read back by the TUI's log viewer.
+
+use std::collections::VecDeque;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use super::*;
+
+/// Request log file name under the config directory
+pub const REQUEST_LOG_FILE: &str = "requests.jsonl";
+
+/// Entries the log viewer keeps in memory; older ones scroll out
+const TAIL_CAPACITY: usize = 500;
+
+/// One proxied request, as recorded once its response has been sent in full
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestLogEntry {
+    /// Unix seconds when the request arrived
+    pub timestamp: u64,
+    pub profile: String,
+    /// Model Claude Code asked for
+    pub model: String,
+    /// Model sent upstream after overrides and routing
+    pub upstream_model: String,
+    /// Upstream API the request was translated for
+    pub api: String,
+    pub status: u16,
+    /// Milliseconds until the last byte of the response
+    pub latency_ms: u64,
+    pub stream: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u32>,
+}
+
+/// Appends entries for one profile's proxy session
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    profile: String,
+    path: PathBuf,
+}
+
+impl RequestLog {
+    /// `None` when the config directory cannot be determined
+    pub fn new(profile: &str) -> Option<Self> {
+        Some(Self {
+            profile: profile.to_string(),
+            path: Config::config_dir()?.join(REQUEST_LOG_FILE),
+        })
+    }
+
+    fn append(&self, entry: &RequestLogEntry) {
+        use std::io::Write as _;
+
+        let Ok(line) = serde_json::to_string(entry) else {
+            return;
+        };
+        if let Ok(mut file) = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+        {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+/// An entry waiting for its response to finish; written when dropped, so a
+/// stream Claude Code abandons halfway is still logged
+struct PendingEntry {
+    log: RequestLog,
+    entry: RequestLogEntry,
+    started: Instant,
+}
+
+impl Drop for PendingEntry {
+    fn drop(&mut self) {
+        self.entry.latency_ms = self.started.elapsed().as_millis() as u64;
+        self.log.append(&self.entry);
+    }
+}
+
+/// What is known about a request before it is sent upstream
+pub(crate) struct RequestSummary {
+    pub(crate) model: String,
+    pub(crate) upstream_model: String,
+    pub(crate) api: &'static str,
+    pub(crate) stream: bool,
+    pub(crate) started: Instant,
+}
+
+/// Record `response` in the request log once it has been sent in full
+pub(crate) async fn log_request(
+    log: RequestLog,
+    summary: RequestSummary,
+    response: Response,
+) -> Response {
+    use futures::StreamExt;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .saturating_sub(summary.started.elapsed().as_secs());
+    let entry = RequestLogEntry {
+        timestamp,
+        profile: log.profile.clone(),
+        model: summary.model,
+        upstream_model: summary.upstream_model,
+        api: summary.api.to_string(),
+        status: response.status().as_u16(),
+        latency_ms: 0,
+        stream: summary.stream,
+        input_tokens: None,
+        output_tokens: None,
+    };
+    let mut pending = PendingEntry {
+        log,
+        entry,
+        started: summary.started,
+    };
+
+    let is_sse = is_sse_response(&response);
+    let (parts, body) = response.into_parts();
+    if is_sse && parts.status.is_success() {
+        let stream = body.into_data_stream().map(move |chunk| {
+            // Capture the whole entry so it is written when the stream is dropped
+            let pending = &mut pending;
+            if let Ok(bytes) = &chunk {
+                let (input, output) = usage_from_sse_chunk(&String::from_utf8_lossy(bytes));
+                if input.is_some() {
+                    pending.entry.input_tokens = input;
+                }
+                if output.is_some() {
+                    pending.entry.output_tokens = output;
+                }
+            }
+            chunk
+        });
+        return Response::from_parts(parts, Body::from_stream(stream));
+    }
+
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::BAD_GATEWAY.into_response();
+    };
+    if parts.status.is_success()
+        && let Ok(value) = serde_json::from_slice::<Value>(&bytes)
+    {
+        let usage = AnthropicUsage::from_openai_usage_value(&value["usage"]);
+        pending.entry.input_tokens = Some(usage.total_input_tokens());
+        pending.entry.output_tokens = Some(usage.output_tokens);
+    }
+    drop(pending);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// The newest entries of the request log, following the file as it grows
+#[derive(Debug, Default)]
+pub struct LogTail {
+    entries: VecDeque<RequestLogEntry>,
+    /// Bytes of the file already read
+    offset: u64,
+    /// Unfinished last line, kept until its newline arrives
+    partial: String,
+}
+
+impl LogTail {
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &RequestLogEntry> + '_ {
+        self.entries.iter()
+    }
+
+    /// Read what was appended to `requests.jsonl` since the last call, returning
+    /// whether anything changed
+    pub fn poll(&mut self) -> bool {
+        match Config::config_dir() {
+            Some(dir) => self.poll_file(&dir.join(REQUEST_LOG_FILE)),
+            None => false,
+        }
+    }
+
+    fn poll_file(&mut self, path: &std::path::Path) -> bool {
+        let Ok(mut file) = std::fs::File::open(path) else {
+            return false;
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let mut changed = false;
+        if len < self.offset {
+            // Pruned or deleted since the last read; start over
+            *self = Self::default();
+            changed = true;
+        }
+        if len == self.offset || file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return changed;
+        }
+        let mut appended = Vec::new();
+        if file.read_to_end(&mut appended).is_err() {
+            return changed;
+        }
+        self.offset += appended.len() as u64;
+        self.partial.push_str(&String::from_utf8_lossy(&appended));
+
+        let complete = match self.partial.rfind('\n') {
+            Some(end) => self.partial.drain(..=end).collect::<String>(),
+            None => return changed,
+        };
+        for line in complete.lines() {
+            if let Ok(entry) = serde_json::from_str::<RequestLogEntry>(line) {
+                self.push(entry);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Add an entry, dropping the oldest once the tail is full
+    pub fn push(&mut self, entry: RequestLogEntry) {
+        if self.entries.len() == TAIL_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn streamed_requests_are_logged_with_usage_and_tailed() {
+        let dir = std::env::temp_dir().join(format!("request-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(REQUEST_LOG_FILE);
+        let _ = std::fs::remove_file(&path);
+        let log = RequestLog {
+            profile: "local".to_string(),
+            path: path.clone(),
+        };
+        let summary = || RequestSummary {
+            model: "claude-sonnet-4-5".to_string(),
+            upstream_model: "qwen3".to_string(),
+            api: "chat",
+            stream: true,
+            started: Instant::now(),
+        };
+
+        let events = concat!(
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12}}}\n\n",
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":7}}\n\n",
+        );
+        let response = sse_response(futures::stream::iter(vec![Ok::<_, Infallible>(
+            events.to_string(),
+        )]));
+        let response = log_request(log.clone(), summary(), response).await;
+        let mut tail = LogTail::default();
+        assert!(
+            !tail.poll_file(&path),
+            "nothing is logged before the body is sent"
+        );
+
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(tail.poll_file(&path));
+        let entry = tail.entries().next().unwrap().clone();
+        assert_eq!(entry.profile, "local");
+        assert_eq!(entry.upstream_model, "qwen3");
+        assert_eq!(
+            (entry.input_tokens, entry.output_tokens),
+            (Some(12), Some(7))
+        );
+
+        let failed = (StatusCode::TOO_MANY_REQUESTS, "slow down").into_response();
+        drop(log_request(log, summary(), failed).await);
+        assert!(tail.poll_file(&path));
+        assert!(!tail.poll_file(&path));
+        let statuses: Vec<u16> = tail.entries().map(|e| e.status).collect();
+        assert_eq!(statuses, vec![200, 429]);
+
+        std::fs::write(&path, "").unwrap();
+        assert!(tail.poll_file(&path));
+        assert_eq!(tail.entries().count(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,175 @@
+//! Retrying transient upstream failures: rate limits (429), overload and server
+//! errors (500, 502, 503, 504, 529) and failed connections are retried with
+//! exponential backoff and jitter, or after the upstream's `Retry-After`.
+//!
+//! A streamed request is retried the same way until its reply starts; once the
+//! first event has reached Claude Code the stream belongs to it.
+
+use super::*;
+use rand::Rng;
+
+/// Attempts per request, the first included, unless the profile says otherwise
+const DEFAULT_ATTEMPTS: u32 = 3;
+
+const DEFAULT_BASE_DELAY: Duration = Duration::from_millis(500);
+
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(20);
+
+/// Statuses worth another attempt; 529 is Anthropic's "overloaded"
+const RETRYABLE_STATUSES: [u16; 6] = [429, 500, 502, 503, 504, 529];
+
+/// How often and how patiently transient upstream failures are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per request, the first included; 1 turns retries off
+    pub attempts: u32,
+    /// Delay before the first retry, doubled for each further one
+    pub base_delay: Duration,
+    /// Longest wait before a retry. A `Retry-After` beyond it is not waited for;
+    /// the reply goes back to Claude Code as it is.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: DEFAULT_ATTEMPTS,
+            base_delay: DEFAULT_BASE_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_env(env: &HashMap<String, String>) -> Self {
+        let number = |key: &str| env.get(key).and_then(|v| v.trim().parse::<u64>().ok());
+        let default = Self::default();
+        Self {
+            attempts: number(ENV_RETRY_ATTEMPTS)
+                .map_or(default.attempts, |n| n.clamp(1, u32::MAX as u64) as u32),
+            base_delay: number(ENV_RETRY_BASE_MS).map_or(default.base_delay, Duration::from_millis),
+            max_delay: number(ENV_RETRY_MAX_MS).map_or(default.max_delay, Duration::from_millis),
+        }
+    }
+
+    /// Backoff before retry number `retry` (0 for the first): the doubled base delay,
+    /// capped, with up to half of it taken off at random so clients spread out
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0.0..=0.5);
+        delay.mul_f64(1.0 - jitter)
+    }
+
+    /// Wait before retry number `retry` of a reply with `headers`, or `None` when the
+    /// upstream asks for a longer wait than the policy allows
+    fn delay(&self, retry: u32, headers: Option<&reqwest::header::HeaderMap>) -> Option<Duration> {
+        match headers.and_then(retry_after) {
+            Some(wait) if wait > self.max_delay => None,
+            Some(wait) => Some(wait),
+            None => Some(self.backoff(retry)),
+        }
+    }
+}
+
+/// Wait the upstream asks for: OpenAI's `retry-after-ms`, or `Retry-After` in seconds
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let number = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|v| v.is_finite() && *v >= 0.0)
+    };
+    number("retry-after-ms")
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+        .or_else(|| number("retry-after").map(Duration::from_secs_f64))
+}
+
+/// Run `send` until it gets a reply that is not transient, or the policy gives up.
+/// The last reply (or error) is returned as it is.
+pub(crate) async fn send_with_retries<F, Fut>(
+    policy: &RetryPolicy,
+    url: &str,
+    mut send: F,
+) -> Result<reqwest::Response, UpstreamError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<reqwest::Response, UpstreamError>>,
+{
+    let mut retry = 0;
+    loop {
+        let result = send().await;
+        if retry + 1 >= policy.attempts {
+            return result;
+        }
+        let (reason, delay) = match &result {
+            Ok(response) if RETRYABLE_STATUSES.contains(&response.status().as_u16()) => (
+                response.status().to_string(),
+                policy.delay(retry, Some(response.headers())),
+            ),
+            Err(err) if err.is_connect_error() => {
+                ("connection failed".to_string(), policy.delay(retry, None))
+            }
+            _ => return result,
+        };
+        let Some(delay) = delay else {
+            return result;
+        };
+        proxy_log(&format!(
+            "{} from {}; retrying in {:.1}s (attempt {}/{})",
+            reason,
+            url,
+            delay.as_secs_f64(),
+            retry + 2,
+            policy.attempts
+        ));
+        tokio::time::sleep(delay).await;
+        retry += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_and_honor_retry_after() {
+        let policy = RetryPolicy {
+            attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+        for retry in 0..6 {
+            let delay = policy.delay(retry, None).unwrap();
+            let full = Duration::from_millis(100 * 2u64.pow(retry)).min(policy.max_delay);
+            assert!(delay <= full && delay >= full / 2, "{:?}", delay);
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("retry-after", "0.3".parse().unwrap());
+        assert_eq!(
+            policy.delay(3, Some(&headers)),
+            Some(Duration::from_millis(300))
+        );
+        headers.insert("retry-after-ms", "40".parse().unwrap());
+        assert_eq!(
+            policy.delay(0, Some(&headers)),
+            Some(Duration::from_millis(40))
+        );
+        // A wait beyond the cap is left to Claude Code
+        headers.insert("retry-after-ms", "60000".parse().unwrap());
+        assert_eq!(policy.delay(0, Some(&headers)), None);
+
+        let env = HashMap::from([
+            (ENV_RETRY_ATTEMPTS.to_string(), "0".to_string()),
+            (ENV_RETRY_BASE_MS.to_string(), "250".to_string()),
+        ]);
+        let policy = RetryPolicy::from_env(&env);
+        assert_eq!(policy.attempts, 1);
+        assert_eq!(policy.base_delay, Duration::from_millis(250));
+        assert_eq!(policy.max_delay, DEFAULT_MAX_DELAY);
+    }
+}
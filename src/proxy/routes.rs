@@ -0,0 +1,111 @@
+//! Per-model routing: requests whose model matches one of the profile's
+//! `[profiles.routes]` patterns go to that route's upstream with its own key and
+//! model name; everything else goes to the proxy target.
+//!
+//! Each route is served by a state of its own, built like the main one, so it gets
+//! endpoint probing, dialect handling and passthrough exactly as a target would.
+
+use super::*;
+
+/// A profile route with its key resolved by the launcher
+#[derive(Debug, Clone)]
+pub struct RouteOptions {
+    /// Requested model pattern; `*` and `?` allowed
+    pub pattern: String,
+    pub target_url: String,
+    /// Model sent upstream; the requested model when unset
+    pub model: Option<String>,
+    pub credentials: Credentials,
+}
+
+/// A route and the state serving it
+pub(crate) struct RoutedUpstream {
+    pub(crate) pattern: String,
+    pub(crate) state: Arc<ProxyState>,
+}
+
+/// Only the model is read to pick a route; the body is parsed again by the handler
+#[derive(Deserialize)]
+struct RequestedModel {
+    model: String,
+}
+
+impl ProxyOptions {
+    /// Options for a route's upstream: the session's usage tracker, request log,
+    /// request shaping and header policy, with the route's key and model. Settings
+    /// tied to the proxy target (its headers, endpoint overrides, compaction,
+    /// consensus, the mock script) are left out.
+    pub(crate) fn for_route(&self, route: &RouteOptions) -> ProxyOptions {
+        ProxyOptions {
+            listen: self.listen.clone(),
+            model_map: BTreeMap::new(),
+            model_override: route.model.clone(),
+            auxiliary_model: None,
+            credentials: route.credentials.clone(),
+            auth_provider: AuthProvider::StaticKey,
+            usage: self.usage.clone(),
+            token_limits: self.token_limits,
+            sampling: self.sampling.clone(),
+            thinking_hint: self.thinking_hint,
+            reasoning_summary: self.reasoning_summary,
+            idle_unload: self.idle_unload,
+            ready_wait: self.ready_wait,
+            chain_responses: self.chain_responses,
+            strict_tools: self.strict_tools,
+            tool_result_limit: self.tool_result_limit,
+            compaction: None,
+            sse: self.sse,
+            retry: self.retry,
+            headers: BTreeMap::new(),
+            header_policy: self.header_policy.clone(),
+            endpoints: EndpointOverrides::default(),
+            mock_script: Vec::new(),
+            max_concurrency: self.max_concurrency,
+            models: Vec::new(),
+            request_log: self.request_log.clone(),
+            capture: self.capture.clone(),
+            codex_pacing: self.codex_pacing,
+            consensus_model: None,
+            passthrough: false,
+            routes: Vec::new(),
+            fanout: Vec::new(),
+            reload: None,
+            http_backend: self.http_backend.clone(),
+            token_refresher: self.token_refresher.clone(),
+        }
+    }
+}
+
+/// The route for a requested model; the longest matching pattern wins
+pub(crate) fn find_route<'a>(
+    routes: &'a [RoutedUpstream],
+    model: &str,
+) -> Option<&'a RoutedUpstream> {
+    routes
+        .iter()
+        .filter(|route| wildcard_match(&route.pattern, model))
+        .max_by_key(|route| route.pattern.len())
+}
+
+/// State to serve a request body with: its route's, or `state` itself. Claude Code's
+/// key belongs to the proxy target, so it is dropped from routed requests.
+pub(crate) fn route_request(
+    state: Arc<ProxyState>,
+    headers: &mut HeaderMap,
+    body: &[u8],
+) -> Arc<ProxyState> {
+    if state.routes.is_empty() {
+        return state;
+    }
+    let Ok(requested) = serde_json::from_slice::<RequestedModel>(body) else {
+        return state;
+    };
+    match find_route(&state.routes, &requested.model) {
+        Some(route) => {
+            headers.remove(header::AUTHORIZATION);
+            headers.remove("x-api-key");
+            route.state.clone()
+        }
+        None => state,
+    }
+}
@@ -0,0 +1,1542 @@
+//! The axum server: endpoint probing, routing and the messages handler.
+
+use super::*;
+use std::sync::atomic::AtomicBool;
+
+/// Response header naming the upstream model that actually served a request
+pub(crate) const UPSTREAM_MODEL_HEADER: &str = "x-claude-profiler-upstream-model";
+
+/// Opening of the system prompt Claude Code gives Task sub-agents
+pub(crate) const SUBAGENT_PROMPT_MARKER: &str = "You are an agent for Claude Code";
+
+/// Per-request timeout when probing candidate upstream endpoints at startup
+const ENDPOINT_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Queue for a request when the concurrency limit is reached: sub-agent and
+/// auxiliary requests yield to the main conversation
+pub(crate) fn request_lane(request: &AnthropicRequest) -> Lane {
+    let is_subagent = system_prompt_text_opt(request.system.as_ref())
+        .is_some_and(|text| text.contains(SUBAGENT_PROMPT_MARKER));
+    if is_subagent || is_auxiliary_request(request) {
+        Lane::Background
+    } else {
+        Lane::Interactive
+    }
+}
+
+/// Detect if a request is an auxiliary request that should use a smaller/faster model
+pub(crate) fn is_auxiliary_request(request: &AnthropicRequest) -> bool {
+    auxiliary_class(request).is_some()
+}
+
+pub(crate) fn with_v1(base: &str) -> String {
+    let trimmed = base.trim_end_matches('/');
+    if trimmed.ends_with("/v1") {
+        trimmed.to_string()
+    } else {
+        format!("{}/v1", trimmed)
+    }
+}
+
+pub(crate) fn build_upstream_urls(target_url: &str) -> (String, String, String, UpstreamMode) {
+    let trimmed = target_url.trim_end_matches('/');
+    if is_mock_url(trimmed) {
+        return (
+            trimmed.to_string(),
+            trimmed.to_string(),
+            trimmed.to_string(),
+            UpstreamMode::Mock,
+        );
+    }
+    if is_anthropic_url(trimmed) {
+        return (
+            trimmed.to_string(),
+            trimmed.to_string(),
+            trimmed.to_string(),
+            UpstreamMode::Anthropic,
+        );
+    }
+    if is_bedrock_url(trimmed) {
+        return (
+            trimmed.to_string(),
+            trimmed.to_string(),
+            trimmed.to_string(),
+            UpstreamMode::Bedrock,
+        );
+    }
+    if trimmed.ends_with("/chat/completions") {
+        let base = trimmed.trim_end_matches("/chat/completions").to_string();
+        return (
+            format!("{}/responses", with_v1(&base)),
+            trimmed.to_string(),
+            format!("{}/completions", with_v1(&base)),
+            UpstreamMode::ChatCompletions,
+        );
+    }
+    if trimmed.ends_with("/completions") && !trimmed.ends_with("/chat/completions") {
+        let base = trimmed.trim_end_matches("/completions").to_string();
+        return (
+            format!("{}/responses", with_v1(&base)),
+            format!("{}/chat/completions", with_v1(&base)),
+            trimmed.to_string(),
+            UpstreamMode::Completions,
+        );
+    }
+    if trimmed.ends_with("/responses") {
+        let base = trimmed.trim_end_matches("/responses").to_string();
+        return (
+            trimmed.to_string(),
+            format!("{}/chat/completions", with_v1(&base)),
+            format!("{}/completions", with_v1(&base)),
+            UpstreamMode::Responses,
+        );
+    }
+
+    let base = trimmed.to_string();
+    (
+        format!("{}/responses", with_v1(&base)),
+        format!("{}/chat/completions", with_v1(&base)),
+        format!("{}/completions", with_v1(&base)),
+        UpstreamMode::Auto,
+    )
+}
+
+/// Endpoints to probe in Auto mode, in preference order. Overrides are taken as
+/// given; otherwise both the `/v1` and the bare path are tried when the target
+/// does not already end in `/v1`.
+fn endpoint_candidates(
+    target_url: &str,
+    overrides: &EndpointOverrides,
+) -> Vec<(UpstreamMode, String)> {
+    let base = target_url.trim_end_matches('/');
+    let families = [
+        (UpstreamMode::Responses, "responses", &overrides.responses),
+        (
+            UpstreamMode::ChatCompletions,
+            "chat/completions",
+            &overrides.chat_completions,
+        ),
+        (
+            UpstreamMode::Completions,
+            "completions",
+            &overrides.completions,
+        ),
+    ];
+
+    let mut candidates = Vec::new();
+    for (mode, path, override_url) in families {
+        if let Some(url) = override_url {
+            candidates.push((mode, url.clone()));
+            continue;
+        }
+        candidates.push((mode, format!("{}/{}", with_v1(base), path)));
+        if !base.ends_with("/v1") {
+            candidates.push((mode, format!("{}/{}", base, path)));
+        }
+    }
+    candidates
+}
+
+/// Whether a probe response shows the endpoint exists. An empty body is rejected
+/// by a real endpoint (400/422) but unknown routes answer 404/405/501.
+fn probe_found_endpoint(status: StatusCode) -> bool {
+    !matches!(
+        status,
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+    )
+}
+
+/// Probe every candidate concurrently and return the first that exists
+async fn probe_endpoints(
+    http: &UpstreamHttp,
+    candidates: Vec<(UpstreamMode, String)>,
+    credentials: &Credentials,
+    headers: &BTreeMap<String, String>,
+) -> Option<(UpstreamMode, String)> {
+    let empty_body = serde_json::json!({});
+    let empty_body = &empty_body;
+    let probes = candidates.iter().map(|(_, url)| async move {
+        let request = send_json_request(http, url, empty_body, None, credentials, headers);
+        match tokio::time::timeout(ENDPOINT_PROBE_TIMEOUT, request).await {
+            Ok(Ok(response)) => probe_found_endpoint(response.status()),
+            _ => false,
+        }
+    });
+    let found = futures::future::join_all(probes).await;
+    candidates
+        .into_iter()
+        .zip(found)
+        .find_map(|(candidate, found)| found.then_some(candidate))
+}
+
+/// Why the proxy is being shut down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    /// Claude Code exited on its own
+    Normal,
+    /// The session was ended by a signal; free local resources on the way out
+    Interrupted,
+}
+
+/// Resolve the upstream endpoints for `proxy_target_url` (probing them in Auto mode)
+/// and build the state shared by the request handlers
+pub(crate) async fn build_state(
+    proxy_target_url: String,
+    mut options: ProxyOptions,
+) -> Result<Arc<ProxyState>> {
+    let mut routes = Vec::new();
+    for route in std::mem::take(&mut options.routes) {
+        let route_options = options.for_route(&route);
+        let state = Box::pin(build_state(route.target_url, route_options))
+            .await
+            .with_context(|| format!("Failed to set up the route for {}", route.pattern))?;
+        routes.push(RoutedUpstream {
+            pattern: route.pattern,
+            state,
+        });
+    }
+    let mut fanout = Vec::new();
+    for upstream in std::mem::take(&mut options.fanout) {
+        let fanout_options = options.for_fanout(&upstream);
+        let state = Box::pin(build_state(upstream.target_url.clone(), fanout_options))
+            .await
+            .with_context(|| format!("Failed to set up the fan-out to {}", upstream.target_url))?;
+        fanout.push(state);
+    }
+
+    let (mut responses_url, mut chat_completions_url, mut completions_url, mut mode) =
+        build_upstream_urls(&proxy_target_url);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(300))
+        .build()?;
+    let http = UpstreamHttp::new(client, options.http_backend.clone());
+
+    if options.passthrough {
+        mode = UpstreamMode::Anthropic;
+    }
+    let endpoints = options.endpoints;
+    if mode == UpstreamMode::Auto {
+        let candidates = endpoint_candidates(&proxy_target_url, &endpoints);
+        match probe_endpoints(&http, candidates, &options.credentials, &options.headers).await {
+            Some((found_mode, url)) => {
+                proxy_log(&format!(
+                    "Endpoint probe selected {:?} at {}",
+                    found_mode, url
+                ));
+                match found_mode {
+                    UpstreamMode::Responses => responses_url = url,
+                    UpstreamMode::ChatCompletions => chat_completions_url = url,
+                    _ => completions_url = url,
+                }
+                mode = found_mode;
+            }
+            None => proxy_log("Endpoint probe was inconclusive; falling back per request"),
+        }
+    }
+    // Explicit overrides always win over the derived URLs
+    if let Some(url) = endpoints.responses {
+        responses_url = url;
+    }
+    if let Some(url) = endpoints.chat_completions {
+        chat_completions_url = url;
+    }
+    if let Some(url) = endpoints.completions {
+        completions_url = url;
+    }
+
+    let idle_unloader = options.idle_unload.and_then(|timeout| {
+        LocalBackend::detect(&proxy_target_url)
+            .filter(LocalBackend::can_unload)
+            .map(|backend| {
+                let unloader = IdleUnloader::new(backend, timeout);
+                Arc::new(match LeaseFile::default_file() {
+                    Some(file) => unloader.with_lease(file, &proxy_target_url),
+                    None => unloader,
+                })
+            })
+    });
+
+    let readiness = ReadinessGate::new(
+        &proxy_target_url,
+        options.ready_wait.unwrap_or(DEFAULT_READY_WAIT),
+    )
+    .map(Arc::new);
+
+    let state = Arc::new(ProxyState {
+        http,
+        target_url: proxy_target_url.trim_end_matches('/').to_string(),
+        responses_url: responses_url.clone(),
+        chat_dialect: ChatDialect::detect(&chat_completions_url),
+        tokenizer_missing: AtomicBool::new(false),
+        messages_url: anthropic_messages_url(&proxy_target_url),
+        chat_completions_url,
+        completions_url,
+        upstream_mode: tokio::sync::RwLock::new(mode),
+        model_map: options.model_map,
+        model_override: options.model_override,
+        auxiliary_model: options.auxiliary_model,
+        aux_promotion: AuxPromotion::default(),
+        credentials: options.credentials,
+        auth_provider: options.auth_provider,
+        refreshed_token: tokio::sync::Mutex::new(None),
+        token_refresher: options
+            .token_refresher
+            .unwrap_or_else(|| Arc::new(StoredSignIn)),
+        usage: options.usage,
+        token_limits: options.token_limits,
+        sampling: options.sampling,
+        thinking_hint: options.thinking_hint,
+        reasoning_summary: options.reasoning_summary,
+        idle_unloader,
+        readiness,
+        response_chains: options
+            .chain_responses
+            .then(|| Arc::new(ResponseChains::default())),
+        strict_tools: options.strict_tools,
+        tool_result_limit: options.tool_result_limit,
+        compaction: options.compaction,
+        sse: options.sse,
+        retry: options.retry,
+        headers: options.headers,
+        header_policy: options.header_policy,
+        mock: MockUpstream::new(options.mock_script),
+        limiter: options
+            .max_concurrency
+            .map(|max| Arc::new(PriorityLimiter::new(max))),
+        models: options.models,
+        request_log: options.request_log,
+        capture: options.capture,
+        codex_quota: is_chatgpt_codex_backend(&responses_url)
+            .then(|| Arc::new(CodexQuota::new(options.codex_pacing))),
+        consensus_model: options.consensus_model,
+        routes,
+        fanout: Fanout::new(fanout),
+    });
+
+    if let Some(unloader) = state.idle_unloader.clone() {
+        tokio::spawn(idle_unload_loop(state.clone(), unloader));
+    }
+    Ok(state)
+}
+
+pub(crate) fn router(state: Arc<ProxyState>) -> Router {
+    Router::new()
+        .route("/health", get(health_handler))
+        .route("/v1/messages", post(messages_handler))
+        .route("/anthropic/v1/messages", post(messages_handler))
+        .route("/v1/messages/count_tokens", post(count_tokens_handler))
+        .route(
+            "/anthropic/v1/messages/count_tokens",
+            post(count_tokens_handler),
+        )
+        .route("/v1/models", get(models_handler))
+        .route("/anthropic/v1/models", get(models_handler))
+        .route("/v1/models/{model_id}", get(model_handler))
+        .route("/anthropic/v1/models/{model_id}", get(model_handler))
+        .fallback(fallback_handler)
+        .with_state(state)
+}
+
+/// Start the proxy server with graceful shutdown support
+pub async fn start_server(
+    proxy_target_url: String,
+    mut options: ProxyOptions,
+    shutdown_rx: Option<tokio::sync::oneshot::Receiver<Shutdown>>,
+) -> Result<()> {
+    let addr = options.listen.socket();
+    let reloader = options.reload.take();
+    let live = LiveProxy::new(build_state(proxy_target_url, options).await?, reloader);
+    let app = live.router();
+
+    // Disable Nagle so each SSE event reaches Claude Code as soon as it is written
+    let listener = tokio::net::TcpListener::bind(&addr).await?.tap_io(|tcp| {
+        let _ = tcp.set_nodelay(true);
+    });
+
+    // `/admin/reload` checks where requests come from
+    let app = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+    if let Some(shutdown_rx) = shutdown_rx {
+        let (kind_tx, kind_rx) = tokio::sync::oneshot::channel();
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                let _ = kind_tx.send(shutdown_rx.await.unwrap_or(Shutdown::Normal));
+            })
+            .await?;
+        // A reload may have replaced the state the server started with
+        let state = live.state();
+        if kind_rx.await == Ok(Shutdown::Interrupted) {
+            unload_on_exit(&state).await;
+        } else if let Some(unloader) = &state.idle_unloader {
+            // Leaving the model loaded after a normal exit is deliberate
+            unloader.release_lease();
+        }
+    } else {
+        axum::serve(listener, app).await?;
+    }
+
+    Ok(())
+}
+
+/// Unload the local model of an interrupted session instead of leaving it resident
+async fn unload_on_exit(state: &ProxyState) {
+    let Some(unloader) = &state.idle_unloader else {
+        return;
+    };
+    let Some(model) = unloader.take_loaded() else {
+        return;
+    };
+    let backend = unloader.backend.label();
+    match unloader
+        .backend
+        .unload(&state.http.client, &state.target_url, &model)
+        .await
+    {
+        Ok(()) => {
+            unloader.unloaded();
+            proxy_log(&format!("Unloaded {} from {} on exit", model, backend))
+        }
+        Err(e) => proxy_log(&format!(
+            "Failed to unload {} from {}: {}",
+            model, backend, e
+        )),
+    }
+}
+
+/// Periodically unload the local model once it has been idle past the timeout
+async fn idle_unload_loop(state: Arc<ProxyState>, unloader: Arc<IdleUnloader>) {
+    let mut interval = tokio::time::interval(unloader.poll_interval());
+    loop {
+        interval.tick().await;
+        // Nothing serves this state any more (a daemon reload replaced it)
+        if Arc::strong_count(&state) == 1 {
+            break;
+        }
+        let Some(model) = unloader.take_expired(std::time::Instant::now()) else {
+            continue;
+        };
+        let backend = unloader.backend.label();
+        match unloader
+            .backend
+            .unload(&state.http.client, &state.target_url, &model)
+            .await
+        {
+            Ok(()) => {
+                unloader.unloaded();
+                proxy_log(&format!(
+                    "Unloaded {} from {} after {}s idle",
+                    model,
+                    backend,
+                    unloader.timeout.as_secs()
+                ))
+            }
+            Err(e) => proxy_log(&format!(
+                "Failed to unload {} from {}: {}",
+                model, backend, e
+            )),
+        }
+    }
+}
+
+/// Health check endpoint
+async fn health_handler() -> &'static str {
+    "OK"
+}
+
+/// Anthropic-style model list built from the profile's tier mappings
+fn models_list(models: &[ListedModel]) -> Value {
+    serde_json::json!({
+        "data": models.iter().map(ListedModel::to_json).collect::<Vec<_>>(),
+        "has_more": false,
+        "first_id": models.first().map(|m| m.id.as_str()),
+        "last_id": models.last().map(|m| m.id.as_str()),
+    })
+}
+
+async fn models_handler(State(state): State<Arc<ProxyState>>) -> Response {
+    Json(models_list(&state.models)).into_response()
+}
+
+async fn model_handler(
+    State(state): State<Arc<ProxyState>>,
+    Path(model_id): Path<String>,
+) -> Response {
+    match state.models.iter().find(|m| m.id == model_id) {
+        Some(model) => Json(model.to_json()).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "type": "error",
+                "error": { "type": "not_found_error", "message": format!("model: {}", model_id) }
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// Fallback handler for unmatched routes
+async fn fallback_handler(req: axum::extract::Request) -> Response {
+    let uri = req.uri().clone();
+
+    // Silently accept event logging requests (telemetry)
+    if uri.path().contains("event_logging") {
+        return StatusCode::OK.into_response();
+    }
+
+    (StatusCode::NOT_FOUND, format!("Not found: {}", uri)).into_response()
+}
+
+/// Main messages endpoint - handles Anthropic API requests
+pub(crate) async fn messages_handler(
+    State(state): State<Arc<ProxyState>>,
+    mut headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    let state = route_request(state, &mut headers, &body);
+    let (state, slot) = fan_out_request(state, &mut headers, &body);
+    let capture = state.capture.as_ref().map(|log| log.start(&headers, &body));
+    let response = with_capture(capture, handle_messages(state, headers, body)).await;
+    match slot {
+        Some(slot) => hold_permit(response, slot),
+        None => response,
+    }
+}
+
+async fn handle_messages(
+    state: Arc<ProxyState>,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    if let Some(readiness) = &state.readiness
+        && let Err(response) = readiness.wait(&state.http.client).await
+    {
+        return response;
+    }
+    if *state.upstream_mode.read().await == UpstreamMode::Anthropic {
+        return passthrough_messages(state, headers, body).await;
+    }
+    match Json::<AnthropicRequest>::from_bytes(&body) {
+        Ok(Json(request)) => translate_messages(state, headers, request).await,
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Translate a messages request for the upstream API and its reply back
+async fn translate_messages(
+    state: Arc<ProxyState>,
+    headers: HeaderMap,
+    mut request: AnthropicRequest,
+) -> Response {
+    let started = std::time::Instant::now();
+    let original_model = request.model.clone();
+    let (target_model, route) = select_target_model(&state, &request);
+    if let Some(route) = route
+        && target_model != original_model
+    {
+        proxy_log(&format!(
+            "Model {} -> {} ({})",
+            original_model, target_model, route
+        ));
+    }
+
+    if let Some(unloader) = &state.idle_unloader
+        && unloader.touch(&target_model)
+    {
+        proxy_log(&format!(
+            "Reloading {} on {}",
+            target_model,
+            unloader.backend.label()
+        ));
+    }
+
+    let requested_tokens = request.max_tokens;
+    request.max_tokens = state.token_limits.apply(requested_tokens);
+    if request.max_tokens != requested_tokens {
+        proxy_log(&format!(
+            "max_tokens {} -> {} for {}",
+            requested_tokens.map_or("unset".to_string(), |v| v.to_string()),
+            request
+                .max_tokens
+                .map_or("unset".to_string(), |v| v.to_string()),
+            target_model
+        ));
+    }
+    if let Some((budget, fitted)) = TokenLimits::fit_thinking(&mut request) {
+        proxy_log(&format!(
+            "Thinking budget {} -> {} to fit max_tokens for {}",
+            budget,
+            fitted.map_or("off".to_string(), |v| v.to_string()),
+            target_model
+        ));
+    }
+    state.sampling.apply(&mut request);
+    if let Some(budget) = state.thinking_hint.apply(&mut request) {
+        proxy_log(&format!(
+            "Thinking budget {} -> step-by-step hint for {}",
+            budget.map_or("unset".to_string(), |v| v.to_string()),
+            target_model
+        ));
+    }
+    if let Some(limit) = state.tool_result_limit {
+        for original in trim_tool_results(&mut request, limit) {
+            proxy_log(&format!(
+                "Trimmed a {} KB tool result to {} KB for {}",
+                original / 1024,
+                limit / 1024,
+                target_model
+            ));
+        }
+    }
+    let auth_header = extract_auth_header(&headers);
+    let mode = { *state.upstream_mode.read().await };
+    // Taken before compaction, so a summary request waits its turn like any other
+    let permit = acquire_permit(&state, request_lane(&request), &target_model).await;
+    if !is_auxiliary_request(&request) {
+        compact_history(
+            &state,
+            mode,
+            &mut request,
+            &target_model,
+            auth_header.clone(),
+        )
+        .await;
+    }
+    let strict_tools = state.strict_tools
+        && !matches!(mode, UpstreamMode::Bedrock | UpstreamMode::Mock)
+        && make_tools_strict(&mut request);
+
+    let aux_check = AuxCheck::new(&request).filter(|_| route == Some("auxiliary"));
+    let delivery = state.clone();
+    let summary = RequestSummary {
+        model: original_model.clone(),
+        upstream_model: target_model.clone(),
+        api: mode.label(),
+        stream: request.stream.unwrap_or(false),
+        started,
+    };
+
+    let consensus_model = state
+        .consensus_model
+        .clone()
+        .filter(|_| request_lane(&request) == Lane::Interactive);
+    let policy = state.header_policy.clone();
+    let response = with_client_headers(&policy, &headers, async {
+        match consensus_model {
+            Some(second) => {
+                consensus_request(state, mode, request, target_model, &second, auth_header).await
+            }
+            None => forward_with_empty_retry(state, mode, request, target_model, auth_header).await,
+        }
+    })
+    .await;
+
+    let response = if strict_tools {
+        strip_null_tool_input(response).await
+    } else {
+        response
+    };
+    let response = match aux_check {
+        Some(check) => {
+            let model = summary.upstream_model.clone();
+            check_auxiliary_reply(delivery.clone(), check, model, response).await
+        }
+        None => response,
+    };
+    deliver_response(&delivery, summary, permit, response).await
+}
+
+/// Wait for a concurrency slot when the profile limits requests in flight
+pub(crate) async fn acquire_permit(
+    state: &ProxyState,
+    lane: Lane,
+    model: &str,
+) -> Option<LanePermit> {
+    let limiter = state.limiter.clone()?;
+    let (permit, queued) = limiter.acquire(lane).await;
+    if queued {
+        proxy_log(&format!("Queued {} request for {}", lane.label(), model));
+    }
+    Some(permit)
+}
+
+/// Record usage and the request log, tag the upstream model and shape the stream
+/// on the way back to Claude Code, holding `permit` until the body is sent
+pub(crate) async fn deliver_response(
+    state: &ProxyState,
+    summary: RequestSummary,
+    permit: Option<LanePermit>,
+    response: Response,
+) -> Response {
+    let usage_model = summary.upstream_model.clone();
+    let response = track_usage(state.usage.clone(), usage_model.clone(), response).await;
+    let mut response = match state.request_log.clone() {
+        Some(log) => log_request(log, summary, response).await,
+        None => response,
+    };
+    if let Ok(value) = header::HeaderValue::from_str(&usage_model) {
+        response.headers_mut().insert(UPSTREAM_MODEL_HEADER, value);
+    }
+    let response = shape_sse(state.sse, response);
+    match permit {
+        Some(permit) => hold_permit(response, permit),
+        None => response,
+    }
+}
+
+/// Send a request to the upstream as `target_model` and translate the reply
+pub(crate) async fn forward_request(
+    state: Arc<ProxyState>,
+    mode: UpstreamMode,
+    request: AnthropicRequest,
+    target_model: String,
+    auth_header: Option<String>,
+) -> Response {
+    let original_model = request.model.clone();
+    let is_streaming = request.stream.unwrap_or(false);
+    let include_thinking = matches!(request.thinking, Some(ThinkingConfig::Enabled { .. }));
+    match mode {
+        UpstreamMode::Responses => {
+            let openai_request = anthropic_to_responses(&request, &target_model);
+            result_to_response(
+                handle_responses_request(
+                    state,
+                    openai_request,
+                    session_hint(&request),
+                    original_model,
+                    include_thinking,
+                    is_streaming,
+                    auth_header,
+                )
+                .await,
+            )
+        }
+        UpstreamMode::ChatCompletions => {
+            let openai_request = anthropic_to_chat(&request, &target_model);
+            result_to_response(
+                handle_chat_request(
+                    state,
+                    openai_request,
+                    original_model,
+                    include_thinking,
+                    is_streaming,
+                    auth_header,
+                )
+                .await,
+            )
+        }
+        UpstreamMode::Completions => {
+            let openai_request = anthropic_to_completions(&request, &target_model);
+            result_to_response(
+                handle_completions_request(
+                    state,
+                    openai_request,
+                    original_model,
+                    is_streaming,
+                    auth_header,
+                )
+                .await,
+            )
+        }
+        UpstreamMode::Bedrock => result_to_response(
+            handle_bedrock_request(
+                state,
+                request,
+                target_model,
+                original_model,
+                is_streaming,
+                auth_header,
+            )
+            .await,
+        ),
+        UpstreamMode::Mock => handle_mock_request(&state, &request, &original_model, is_streaming),
+        UpstreamMode::Anthropic => {
+            let mut request = request;
+            request.model = target_model;
+            handle_anthropic_request(state, request, auth_header).await
+        }
+        UpstreamMode::Auto => {
+            handle_auto_request(
+                state,
+                request,
+                target_model,
+                original_model,
+                is_streaming,
+                include_thinking,
+                auth_header,
+            )
+            .await
+        }
+    }
+}
+
+/// Keep the concurrency slot until the response body has been sent in full
+fn hold_permit<P: Send + 'static>(response: Response, permit: P) -> Response {
+    use futures::StreamExt;
+
+    let (parts, body) = response.into_parts();
+    let stream = body.into_data_stream().map(move |chunk| {
+        let _ = &permit;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{self, body_json, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn request_lane_sends_subagents_to_background() {
+        let mut req = base_request(vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Text("fix the bug".to_string()),
+        }]);
+        req.system = Some(SystemPrompt::Text(
+            "You are Claude Code, Anthropic's official CLI for Claude.".to_string(),
+        ));
+        assert_eq!(request_lane(&req), Lane::Interactive);
+
+        req.system = Some(SystemPrompt::Text(
+            "You are an agent for Claude Code, Anthropic's official CLI for Claude.".to_string(),
+        ));
+        assert_eq!(request_lane(&req), Lane::Background);
+
+        req.system = None;
+        req.max_tokens = Some(1);
+        assert_eq!(request_lane(&req), Lane::Background);
+    }
+
+    #[test]
+    fn models_list_reflects_tier_mappings() {
+        let env = HashMap::from([
+            (
+                ENV_DEFAULT_OPUS_MODEL.to_string(),
+                "gpt-5.2-codex-high".to_string(),
+            ),
+            (
+                ENV_DEFAULT_SONNET_MODEL.to_string(),
+                "gpt-5.2-codex".to_string(),
+            ),
+            (ENV_DEFAULT_HAIKU_MODEL.to_string(), " ".to_string()),
+            (ENV_MODEL.to_string(), "gpt-5.2-codex".to_string()),
+        ]);
+
+        let list = models_list(&ListedModel::from_env(&env));
+
+        assert_eq!(list["data"].as_array().map(Vec::len), Some(2));
+        assert_eq!(list["data"][0]["id"], "gpt-5.2-codex-high");
+        assert_eq!(list["data"][1]["display_name"], "Sonnet (gpt-5.2-codex)");
+        assert_eq!(list["first_id"], "gpt-5.2-codex-high");
+        assert_eq!(list["last_id"], "gpt-5.2-codex");
+        assert_eq!(list["has_more"], false);
+    }
+
+    #[test]
+    fn is_auxiliary_request_detects_patterns() {
+        let req = base_request(vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Text("hello".to_string()),
+        }]);
+        assert!(!is_auxiliary_request(&req));
+
+        let req = AnthropicRequest {
+            max_tokens: Some(1),
+            ..base_request(vec![AnthropicMessage {
+                role: "user".to_string(),
+                content: AnthropicContent::Text("hello".to_string()),
+            }])
+        };
+        assert!(is_auxiliary_request(&req));
+
+        let req = base_request(vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Text("[SUGGESTION MODE: ON]".to_string()),
+        }]);
+        assert!(is_auxiliary_request(&req));
+
+        let req = base_request(vec![AnthropicMessage {
+            role: "assistant".to_string(),
+            content: AnthropicContent::Text("{\"ok\":true}".to_string()),
+        }]);
+        assert!(is_auxiliary_request(&req));
+    }
+
+    #[test]
+    fn build_upstream_urls_detects_bedrock() {
+        let (_, _, _, mode) =
+            build_upstream_urls("https://bedrock-runtime.us-east-1.amazonaws.com/");
+        assert_eq!(mode, UpstreamMode::Bedrock);
+    }
+
+    #[test]
+    fn endpoint_candidates_try_bare_paths_and_overrides() {
+        let overrides = EndpointOverrides {
+            chat_completions: Some("https://gw.corp/ai/chat".to_string()),
+            ..Default::default()
+        };
+        let candidates = endpoint_candidates("https://gw.corp/ai/openai/", &overrides);
+        let urls: Vec<&str> = candidates.iter().map(|(_, url)| url.as_str()).collect();
+
+        assert_eq!(
+            urls,
+            vec![
+                "https://gw.corp/ai/openai/v1/responses",
+                "https://gw.corp/ai/openai/responses",
+                "https://gw.corp/ai/chat",
+                "https://gw.corp/ai/openai/v1/completions",
+                "https://gw.corp/ai/openai/completions",
+            ]
+        );
+        assert_eq!(candidates[2].0, UpstreamMode::ChatCompletions);
+        assert_eq!(
+            endpoint_candidates("http://localhost:1234/v1", &EndpointOverrides::default()).len(),
+            3
+        );
+        assert!(probe_found_endpoint(StatusCode::BAD_REQUEST));
+        assert!(probe_found_endpoint(StatusCode::UNAUTHORIZED));
+        assert!(!probe_found_endpoint(StatusCode::NOT_FOUND));
+    }
+
+    /// Stand-in for an OpenAI-compatible provider. Paths with queued replies exist
+    /// (an empty probe body gets 400), every other path answers 404.
+    struct FakeUpstream {
+        server: MockServer,
+    }
+
+    impl FakeUpstream {
+        async fn start() -> Self {
+            Self {
+                server: MockServer::start().await,
+            }
+        }
+
+        /// Queue one reply on `path`; queued replies are used in order
+        async fn reply(&self, path: &str, status: StatusCode, content_type: &str, body: &str) {
+            Mock::given(method("POST"))
+                .and(matchers::path(path))
+                .and(body_json(json!({})))
+                .respond_with(ResponseTemplate::new(StatusCode::BAD_REQUEST.as_u16()))
+                .with_priority(1)
+                .mount(&self.server)
+                .await;
+            Mock::given(method("POST"))
+                .and(matchers::path(path))
+                .respond_with(
+                    ResponseTemplate::new(status.as_u16())
+                        .set_body_raw(body.to_string(), content_type),
+                )
+                .up_to_n_times(1)
+                .mount(&self.server)
+                .await;
+        }
+
+        /// Path and body of every model request, leaving out the endpoint probes
+        async fn received(&self) -> Vec<(String, Value)> {
+            self.server
+                .received_requests()
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|request| request.method.as_str() == "POST")
+                .map(|request| {
+                    let body = serde_json::from_slice(&request.body).unwrap_or(Value::Null);
+                    (request.url.path().to_string(), body)
+                })
+                .filter(|(_, body)| *body != json!({}))
+                .collect()
+        }
+    }
+
+    /// Serve `app` on a free local port and return its base URL
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    /// Run the proxy against `fake` and return the proxy's base URL with its state
+    async fn serve_proxy(
+        fake: &FakeUpstream,
+        target_path: &str,
+        options: ProxyOptions,
+    ) -> (String, Arc<ProxyState>) {
+        let state = build_state(format!("{}{}", fake.server.uri(), target_path), options)
+            .await
+            .unwrap();
+        (serve(router(state.clone())).await, state)
+    }
+
+    fn messages_body(stream: bool) -> Value {
+        json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "stream": stream,
+            "system": "Be brief.",
+            "messages": [{ "role": "user", "content": "Hi" }],
+        })
+    }
+
+    #[tokio::test]
+    async fn messages_round_trip_through_a_chat_upstream() {
+        let fake = FakeUpstream::start().await;
+        fake.reply(
+            "/v1/chat/completions",
+            StatusCode::OK,
+            "application/json",
+            r#"{"id":"c1","choices":[{"message":{"role":"assistant","content":"Hello"},"finish_reason":"stop"}],"usage":{"prompt_tokens":12,"completion_tokens":3}}"#,
+        ).await;
+        let options = ProxyOptions {
+            model_override: Some("local-model".to_string()),
+            ..Default::default()
+        };
+        let (proxy, state) = serve_proxy(&fake, "/v1/chat/completions", options).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/v1/messages", proxy))
+            .json(&messages_body(false))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[UPSTREAM_MODEL_HEADER], "local-model");
+        let reply: Value = response.json().await.unwrap();
+        assert_eq!(reply["model"], "claude-sonnet-4-5");
+        assert_eq!(reply["content"][0]["text"], "Hello");
+        assert_eq!(reply["stop_reason"], "end_turn");
+
+        let received = fake.received().await;
+        assert_eq!(received.len(), 1);
+        let (_, sent) = &received[0];
+        assert_eq!(sent["model"], "local-model");
+        assert_eq!(sent["messages"][0]["role"], "system");
+        assert_eq!(sent["messages"][1]["content"], "Hi");
+        assert_eq!(state.usage.snapshot().len(), 1);
+    }
+
+    /// Answers every upstream request in-process with one chat completion
+    #[derive(Default)]
+    struct CannedBackend {
+        urls: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl HttpBackend for CannedBackend {
+        fn execute(
+            &self,
+            request: reqwest::Request,
+        ) -> futures::future::BoxFuture<'static, reqwest::Result<reqwest::Response>> {
+            self.urls.lock().unwrap().push(request.url().to_string());
+            let reply = axum::http::Response::builder()
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(
+                    r#"{"id":"c1","choices":[{"message":{"role":"assistant","content":"In-process"},"finish_reason":"stop"}]}"#,
+                )
+                .unwrap();
+            Box::pin(async move { Ok(reqwest::Response::from(reply)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn an_injected_backend_answers_in_place_of_the_network() {
+        let backend = Arc::new(CannedBackend::default());
+        let options = ProxyOptions {
+            http_backend: Some(backend.clone()),
+            ..Default::default()
+        };
+        let state = build_state(
+            "https://models.example/v1/chat/completions".to_string(),
+            options,
+        )
+        .await
+        .unwrap();
+        let proxy = serve(router(state)).await;
+
+        let reply: Value = reqwest::Client::new()
+            .post(format!("{}/v1/messages", proxy))
+            .json(&messages_body(false))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(reply["content"][0]["text"], "In-process");
+        assert_eq!(
+            *backend.urls.lock().unwrap(),
+            ["https://models.example/v1/chat/completions"]
+        );
+    }
+
+    /// Refuses the stale token with a 401 and answers the refreshed one (unless it
+    /// refuses every token). The stale requests wait until `stale_requests` of them
+    /// are in flight, so their 401s arrive together.
+    struct ExpiringTokenBackend {
+        accepts_fresh: bool,
+        stale_requests: Arc<tokio::sync::Barrier>,
+        authorizations: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl ExpiringTokenBackend {
+        fn new(accepts_fresh: bool, stale_requests: usize) -> Self {
+            Self {
+                accepts_fresh,
+                stale_requests: Arc::new(tokio::sync::Barrier::new(stale_requests)),
+                authorizations: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl HttpBackend for ExpiringTokenBackend {
+        fn execute(
+            &self,
+            request: reqwest::Request,
+        ) -> futures::future::BoxFuture<'static, reqwest::Result<reqwest::Response>> {
+            let authorization = request.headers()[header::AUTHORIZATION]
+                .to_str()
+                .unwrap()
+                .to_string();
+            self.authorizations
+                .lock()
+                .unwrap()
+                .push(authorization.clone());
+            let stale = authorization == "Bearer stale";
+            let accepted = !stale && self.accepts_fresh;
+            let stale_requests = self.stale_requests.clone();
+            Box::pin(async move {
+                if stale {
+                    stale_requests.wait().await;
+                }
+                let reply = if accepted {
+                    axum::http::Response::builder()
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(
+                            r#"{"id":"c1","choices":[{"message":{"role":"assistant","content":"Refreshed"},"finish_reason":"stop"}]}"#,
+                        )
+                } else {
+                    axum::http::Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(r#"{"error":{"message":"Token expired"}}"#)
+                };
+                Ok(reqwest::Response::from(reply.unwrap()))
+            })
+        }
+    }
+
+    /// Hands out the token `fresh`, noting the stale token of every refresh
+    #[derive(Default)]
+    struct CountingRefresher {
+        refreshed: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl TokenRefresher for CountingRefresher {
+        fn refresh(
+            &self,
+            _provider: AuthProvider,
+            stale: &str,
+        ) -> futures::future::BoxFuture<'static, Result<String>> {
+            self.refreshed.lock().unwrap().push(stale.to_string());
+            Box::pin(async { Ok("fresh".to_string()) })
+        }
+    }
+
+    async fn serve_oauth_proxy(
+        backend: Arc<ExpiringTokenBackend>,
+        refresher: Arc<CountingRefresher>,
+    ) -> String {
+        let options = ProxyOptions {
+            auth_provider: AuthProvider::OpenAiOAuth,
+            http_backend: Some(backend),
+            token_refresher: Some(refresher),
+            ..Default::default()
+        };
+        let state = build_state(
+            "https://models.example/v1/chat/completions".to_string(),
+            options,
+        )
+        .await
+        .unwrap();
+        serve(router(state)).await
+    }
+
+    async fn send_with_stale_token(proxy: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/v1/messages", proxy))
+            .header(header::AUTHORIZATION, "Bearer stale")
+            .json(&messages_body(false))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn concurrent_401s_share_one_oauth_refresh() {
+        let backend = Arc::new(ExpiringTokenBackend::new(true, 2));
+        let refresher = Arc::new(CountingRefresher::default());
+        let proxy = serve_oauth_proxy(backend.clone(), refresher.clone()).await;
+
+        let (first, second) =
+            tokio::join!(send_with_stale_token(&proxy), send_with_stale_token(&proxy));
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(second.status(), StatusCode::OK);
+        // One request refreshed; the other found the token already refreshed
+        assert_eq!(*refresher.refreshed.lock().unwrap(), ["stale"]);
+        assert_eq!(
+            *backend.authorizations.lock().unwrap(),
+            [
+                "Bearer stale",
+                "Bearer stale",
+                "Bearer fresh",
+                "Bearer fresh"
+            ]
+        );
+
+        // Later requests start out with the refreshed token
+        let third = send_with_stale_token(&proxy).await;
+        assert_eq!(third.status(), StatusCode::OK);
+        assert_eq!(backend.authorizations.lock().unwrap().len(), 5);
+        assert_eq!(refresher.refreshed.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_401_after_the_refresh_goes_back_to_the_client() {
+        let backend = Arc::new(ExpiringTokenBackend::new(false, 1));
+        let refresher = Arc::new(CountingRefresher::default());
+        let proxy = serve_oauth_proxy(backend.clone(), refresher.clone()).await;
+
+        let response = send_with_stale_token(&proxy).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(*refresher.refreshed.lock().unwrap(), ["stale"]);
+        assert_eq!(
+            *backend.authorizations.lock().unwrap(),
+            ["Bearer stale", "Bearer fresh"]
+        );
+    }
+
+    #[tokio::test]
+    async fn probed_upstream_streams_translated_events() {
+        let fake = FakeUpstream::start().await;
+        fake.reply(
+            "/v1/chat/completions",
+            StatusCode::OK,
+            "text/event-stream",
+            concat!(
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: [DONE]\n\n",
+            ),
+        ).await;
+        let (proxy, state) = serve_proxy(&fake, "", ProxyOptions::default()).await;
+        assert_eq!(
+            *state.upstream_mode.read().await,
+            UpstreamMode::ChatCompletions
+        );
+
+        let body = reqwest::Client::new()
+            .post(format!("{}/v1/messages", proxy))
+            .json(&messages_body(true))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.starts_with("event: message_start"));
+        assert!(body.contains("\"text\":\"Hel\""));
+        assert!(body.contains("\"text\":\"lo\""));
+        assert!(body.contains("event: message_stop"));
+        assert_eq!(fake.received().await[0].1["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn upstream_errors_reach_claude_code_with_their_status() {
+        let fake = FakeUpstream::start().await;
+        fake.reply(
+            "/v1/responses",
+            StatusCode::TOO_MANY_REQUESTS,
+            "application/json",
+            r#"{"error":{"message":"slow down"}}"#,
+        )
+        .await;
+        // Once retries run out
+        let options = ProxyOptions {
+            retry: RetryPolicy {
+                attempts: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (proxy, _) = serve_proxy(&fake, "/v1/responses", options).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{}/v1/messages", proxy))
+            .json(&messages_body(false))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.text().await.unwrap().contains("slow down"));
+    }
+
+    #[tokio::test]
+    async fn transient_upstream_errors_are_retried() {
+        let fake = FakeUpstream::start().await;
+        fake.reply(
+            "/v1/chat/completions",
+            StatusCode::SERVICE_UNAVAILABLE,
+            "application/json",
+            r#"{"error":{"message":"overloaded"}}"#,
+        )
+        .await;
+        fake.reply(
+            "/v1/chat/completions",
+            StatusCode::OK,
+            "application/json",
+            r#"{"id":"c1","choices":[{"message":{"role":"assistant","content":"Hi"},"finish_reason":"stop"}]}"#,
+        ).await;
+        let options = ProxyOptions {
+            retry: RetryPolicy {
+                attempts: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(10),
+            },
+            ..Default::default()
+        };
+        let (proxy, _) = serve_proxy(&fake, "/v1/chat/completions", options).await;
+
+        let reply: Value = reqwest::Client::new()
+            .post(format!("{}/v1/messages", proxy))
+            .json(&messages_body(false))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(reply["content"][0]["text"], "Hi");
+        assert_eq!(fake.received().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn consensus_merges_two_answers_with_the_auxiliary_model() {
+        let fake = FakeUpstream::start().await;
+        for text in [
+            "Use a mutex",
+            "Use a channel",
+            "Use a channel, or a mutex for shared state",
+        ] {
+            fake.reply(
+                "/v1/chat/completions",
+                StatusCode::OK,
+                "application/json",
+                &json!({
+                    "id": "c1",
+                    "choices": [{ "message": { "role": "assistant", "content": text }, "finish_reason": "stop" }],
+                    "usage": { "prompt_tokens": 10, "completion_tokens": 5 },
+                })
+                .to_string(),
+            ).await;
+        }
+        let options = ProxyOptions {
+            model_override: Some("local-model".to_string()),
+            auxiliary_model: Some("judge-model".to_string()),
+            consensus_model: Some("paid-model".to_string()),
+            ..Default::default()
+        };
+        let (proxy, state) = serve_proxy(&fake, "/v1/chat/completions", options).await;
+
+        let body = reqwest::Client::new()
+            .post(format!("{}/v1/messages", proxy))
+            .json(&messages_body(true))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.starts_with("event: message_start"));
+        assert!(body.contains("Use a channel, or a mutex for shared state"));
+
+        let received = fake.received().await;
+        let models: HashSet<&str> = received[..2]
+            .iter()
+            .map(|(_, sent)| sent["model"].as_str().unwrap())
+            .collect();
+        assert_eq!(models, HashSet::from(["local-model", "paid-model"]));
+        assert!(received[..2].iter().all(|(_, sent)| sent["stream"] != true));
+        let (_, judged) = &received[2];
+        assert_eq!(judged["model"], "judge-model");
+        let prompt = judged["messages"][0]["content"].as_str().unwrap();
+        assert!(prompt.contains("Use a mutex") && prompt.contains("Use a channel"));
+        assert_eq!(state.usage.snapshot().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn passthrough_forwards_requests_untranslated() {
+        let fake = FakeUpstream::start().await;
+        let reply = json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{ "type": "text", "text": "Hi" }],
+            "model": "claude-sonnet-4-5",
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 12, "output_tokens": 3 },
+        });
+        fake.reply(
+            "/v1/messages",
+            StatusCode::OK,
+            "application/json",
+            &reply.to_string(),
+        )
+        .await;
+        fake.reply(
+            "/v1/messages",
+            StatusCode::TOO_MANY_REQUESTS,
+            "application/json",
+            r#"{"type":"error","error":{"type":"rate_limit_error","message":"slow down"}}"#,
+        )
+        .await;
+        let options = ProxyOptions {
+            model_override: Some("ignored-model".to_string()),
+            retry: RetryPolicy {
+                attempts: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (proxy, state) = serve_proxy(&fake, "/v1/messages", options).await;
+
+        // Fields the proxy does not model, and the requested model, reach the upstream
+        let mut body = messages_body(false);
+        body["context_management"] = json!({ "edits": [] });
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/v1/messages", proxy))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.json::<Value>().await.unwrap(), reply);
+        assert_eq!(fake.received().await[0].1, body);
+        assert_eq!(state.usage.snapshot().len(), 1);
+
+        let response = client
+            .post(format!("{}/v1/messages", proxy))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.text().await.unwrap().contains("rate_limit_error"));
+    }
+
+    #[tokio::test]
+    async fn routed_models_go_to_their_own_upstream() {
+        let chat_reply = |text: &str| {
+            format!(
+                r#"{{"id":"c1","choices":[{{"message":{{"role":"assistant","content":"{}"}},"finish_reason":"stop"}}]}}"#,
+                text
+            )
+        };
+        let local = FakeUpstream::start().await;
+        local
+            .reply(
+                "/v1/chat/completions",
+                StatusCode::OK,
+                "application/json",
+                &chat_reply("From local"),
+            )
+            .await;
+        let local_url = local.server.uri();
+        let target = FakeUpstream::start().await;
+        target
+            .reply(
+                "/v1/chat/completions",
+                StatusCode::OK,
+                "application/json",
+                &chat_reply("From target"),
+            )
+            .await;
+        let options = ProxyOptions {
+            routes: vec![RouteOptions {
+                pattern: "claude-*haiku*".to_string(),
+                target_url: format!("{}/v1/chat/completions", local_url),
+                model: Some("qwen-small".to_string()),
+                credentials: Credentials::Bearer("local-key".to_string()),
+            }],
+            ..Default::default()
+        };
+        let (proxy, state) = serve_proxy(&target, "/v1/chat/completions", options).await;
+
+        let mut haiku = messages_body(false);
+        haiku["model"] = json!("claude-haiku-4-5");
+        for (body, expected) in [(haiku, "From local"), (messages_body(false), "From target")] {
+            let reply: Value = reqwest::Client::new()
+                .post(format!("{}/v1/messages", proxy))
+                .header("x-api-key", "target-key")
+                .json(&body)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(reply["content"][0]["text"], expected);
+        }
+
+        assert_eq!(local.received().await[0].1["model"], "qwen-small");
+        assert_eq!(target.received().await[0].1["model"], "claude-sonnet-4-5");
+        // Routes share the session's usage tracker
+        assert_eq!(state.usage.snapshot().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn mapped_models_take_precedence_over_the_override() {
+        let fake = FakeUpstream::start().await;
+        for _ in 0..3 {
+            fake.reply(
+                "/v1/chat/completions",
+                StatusCode::OK,
+                "application/json",
+                r#"{"id":"c1","choices":[{"message":{"role":"assistant","content":"Hi"},"finish_reason":"stop"}]}"#,
+            ).await;
+        }
+        let options = ProxyOptions {
+            model_map: BTreeMap::from([
+                ("claude-*".to_string(), "qwen2.5-coder-7b".to_string()),
+                (
+                    "claude-sonnet-4-*".to_string(),
+                    "qwen2.5-coder-32b".to_string(),
+                ),
+            ]),
+            model_override: Some("local-model".to_string()),
+            ..Default::default()
+        };
+        let (proxy, _state) = serve_proxy(&fake, "/v1/chat/completions", options).await;
+
+        for model in ["claude-sonnet-4-5", "claude-haiku-4-5", "gpt-4o"] {
+            let mut body = messages_body(false);
+            body["model"] = json!(model);
+            let response = reqwest::Client::new()
+                .post(format!("{}/v1/messages", proxy))
+                .json(&body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let sent: Vec<Value> = fake
+            .received()
+            .await
+            .into_iter()
+            .map(|(_, body)| body["model"].clone())
+            .collect();
+        assert_eq!(
+            sent,
+            ["qwen2.5-coder-32b", "qwen2.5-coder-7b", "local-model"]
+        );
+    }
+}
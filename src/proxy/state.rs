@@ -0,0 +1,791 @@
+//! Shared proxy state and the options the proxy is started with.
+
+use super::*;
+use std::sync::atomic::AtomicBool;
+
+/// `created_at` for synthesized `/v1/models` entries, which have no release date
+const LISTED_MODEL_CREATED_AT: &str = "1970-01-01T00:00:00Z";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UpstreamMode {
+    Auto,
+    Responses,
+    ChatCompletions,
+    Completions,
+    Bedrock,
+    Mock,
+    /// Anthropic Messages upstream; requests are forwarded untranslated
+    Anthropic,
+}
+
+impl UpstreamMode {
+    /// Short name for the request log
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            UpstreamMode::Auto => "auto",
+            UpstreamMode::Responses => "responses",
+            UpstreamMode::ChatCompletions => "chat",
+            UpstreamMode::Completions => "completions",
+            UpstreamMode::Bedrock => "bedrock",
+            UpstreamMode::Mock => "mock",
+            UpstreamMode::Anthropic => "anthropic",
+        }
+    }
+}
+
+/// Shared state for the proxy server
+pub struct ProxyState {
+    pub http: UpstreamHttp,
+    /// Target URL as configured in the profile (used as the Bedrock runtime base)
+    pub target_url: String,
+    pub responses_url: String,
+    pub chat_completions_url: String,
+    pub completions_url: String,
+    /// Server family behind `chat_completions_url`, for its streaming quirks
+    pub(crate) chat_dialect: ChatDialect,
+    /// Set once the upstream's `/tokenize` endpoint turned out not to exist, so token
+    /// counts go straight to the local estimate
+    pub(crate) tokenizer_missing: AtomicBool,
+    /// Messages endpoint for Anthropic passthrough
+    pub messages_url: String,
+    pub(crate) upstream_mode: tokio::sync::RwLock<UpstreamMode>,
+    /// Requested model pattern -> upstream model, from `[profiles.model_map]`
+    pub(crate) model_map: BTreeMap<String, String>,
+    /// Optional model override for main requests
+    pub model_override: Option<String>,
+    /// Optional auxiliary model for handling lightweight requests
+    /// (token counting, suggestions, etc.)
+    pub auxiliary_model: Option<String>,
+    /// Kinds of auxiliary request sent to the main model after the auxiliary model
+    /// kept failing them
+    pub(crate) aux_promotion: AuxPromotion,
+    /// Credentials resolved by the profile's auth provider
+    pub credentials: Credentials,
+    /// Provider the credentials came from (decides whether a 401 can be refreshed)
+    pub auth_provider: AuthProvider,
+    /// OAuth token obtained by a mid-session refresh; replaces the stale token
+    /// Claude Code keeps sending
+    pub(crate) refreshed_token: tokio::sync::Mutex<Option<String>>,
+    /// Where that token comes from
+    pub(crate) token_refresher: Arc<dyn TokenRefresher>,
+    /// Token usage per upstream model, priced by the launcher on exit
+    pub usage: Arc<UsageTracker>,
+    /// Per-profile bounds applied to `max_tokens`
+    pub token_limits: TokenLimits,
+    /// Per-tier temperature/top_p/top_k overrides
+    pub sampling: SamplingOverrides,
+    /// Thinking budgets replaced by a step-by-step hint
+    pub thinking_hint: ThinkingHint,
+    /// Reasoning summary asked of Responses upstreams, when the profile sets one
+    pub reasoning_summary: Option<ReasoningSummary>,
+    /// Unloads an idle local model, when the profile enables it
+    pub idle_unloader: Option<Arc<IdleUnloader>>,
+    /// Holds requests until a local upstream is ready
+    pub readiness: Option<Arc<ReadinessGate>>,
+    /// Response ids for `previous_response_id`, when the profile enables it
+    pub response_chains: Option<Arc<ResponseChains>>,
+    /// Send tools to OpenAI-family upstreams as strict function schemas
+    pub strict_tools: bool,
+    /// Longest tool result text sent upstream, in bytes
+    pub tool_result_limit: Option<usize>,
+    /// Summarizes older turns for small-context upstreams, when the profile enables it
+    pub compaction: Option<Compaction>,
+    /// Delivery tuning for streamed responses
+    pub sse: SseOptions,
+    /// Retries of transient upstream failures
+    pub retry: RetryPolicy,
+    /// Profile headers (e.g. `anthropic-beta`) added to every upstream request
+    pub headers: BTreeMap<String, String>,
+    /// Which headers pass between Claude Code and the upstream
+    pub header_policy: HeaderPolicy,
+    /// Canned responses for `mock://` targets
+    pub mock: MockUpstream,
+    /// Caps concurrent upstream requests, favouring interactive turns
+    pub limiter: Option<Arc<PriorityLimiter>>,
+    /// Models served from `/v1/models`
+    pub models: Vec<ListedModel>,
+    /// Records every request to `requests.jsonl`, when the profile enables it
+    pub request_log: Option<RequestLog>,
+    /// Saves request and response bodies for the inspector, when the profile enables it
+    pub capture: Option<CaptureLog>,
+    /// Usage limits reported by the Codex backend, for Codex targets
+    pub codex_quota: Option<Arc<CodexQuota>>,
+    /// Second model for consensus mode
+    pub consensus_model: Option<String>,
+    /// Upstreams serving the models matched by the profile's routes
+    pub(crate) routes: Vec<RoutedUpstream>,
+    /// Upstreams sharing the target's sub-agent conversations
+    pub(crate) fanout: Option<Fanout>,
+}
+
+/// Delivery tuning for SSE responses sent to Claude Code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SseOptions {
+    /// Coalesce events that arrive within this window into a single write
+    /// (smooths very chatty upstreams that send one token per event)
+    pub batch: Option<Duration>,
+    /// Log every write with its offset from the start of the stream
+    pub debug: bool,
+}
+
+impl SseOptions {
+    pub fn from_env(env: &HashMap<String, String>) -> Self {
+        Self {
+            batch: env
+                .get(ENV_SSE_BATCH_MS)
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+            debug: env
+                .get(ENV_SSE_DEBUG)
+                .is_some_and(|v| openai_oauth::is_truthy(v)),
+        }
+    }
+}
+
+/// Address a proxy session listens on, from the profile's `proxy_bind`/`proxy_port`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenAddr {
+    pub bind: String,
+    pub port: u16,
+}
+
+impl Default for ListenAddr {
+    fn default() -> Self {
+        Self {
+            bind: PROXY_BIND.to_string(),
+            port: PROXY_PORT,
+        }
+    }
+}
+
+impl ListenAddr {
+    pub fn new(bind: Option<&str>, port: Option<u16>) -> Self {
+        let defaults = Self::default();
+        Self {
+            bind: bind
+                .map(str::trim)
+                .filter(|b| !b.is_empty())
+                .map_or(defaults.bind, str::to_string),
+            port: port.unwrap_or(defaults.port),
+        }
+    }
+
+    /// `host` as it appears in an address, with IPv6 literals bracketed
+    fn host(host: &str) -> String {
+        if host.contains(':') && !host.starts_with('[') {
+            format!("[{}]", host)
+        } else {
+            host.to_string()
+        }
+    }
+
+    /// `bind:port`, as passed to the listener
+    pub fn socket(&self) -> String {
+        format!("{}:{}", Self::host(&self.bind), self.port)
+    }
+
+    /// Base URL for clients on this machine: loopback and wildcard binds are
+    /// reached through `localhost`, anything else through the bound address
+    pub fn base_url(&self) -> String {
+        let bind = self.bind.trim_start_matches('[').trim_end_matches(']');
+        let local = bind == "localhost"
+            || bind
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback() || ip.is_unspecified());
+        let host = if local {
+            "localhost".to_string()
+        } else {
+            Self::host(bind)
+        };
+        format!("http://{}:{}", host, self.port)
+    }
+
+    /// `ANTHROPIC_BASE_URL` for Claude Code
+    pub fn anthropic_url(&self) -> String {
+        format!("{}/anthropic", self.base_url())
+    }
+}
+
+/// Per-profile settings for a proxy session, resolved by the launcher
+#[derive(Default)]
+pub struct ProxyOptions {
+    /// Where the proxy listens
+    pub listen: ListenAddr,
+    /// Requested model pattern (`*` and `?` allowed) -> upstream model; takes
+    /// precedence over the override and the auxiliary model
+    pub model_map: BTreeMap<String, String>,
+    /// Optional model override for main requests
+    pub model_override: Option<String>,
+    /// Optional auxiliary model for lightweight requests
+    pub auxiliary_model: Option<String>,
+    pub credentials: Credentials,
+    pub auth_provider: AuthProvider,
+    pub usage: Arc<UsageTracker>,
+    pub token_limits: TokenLimits,
+    pub sampling: SamplingOverrides,
+    pub thinking_hint: ThinkingHint,
+    /// Reasoning summary asked of Responses upstreams; Codex defaults to `auto`
+    pub reasoning_summary: Option<ReasoningSummary>,
+    /// Unload the local model after this long without requests
+    pub idle_unload: Option<Duration>,
+    /// Longest wait for a local upstream to become ready; two minutes when unset,
+    /// zero to send requests straight away
+    pub ready_wait: Option<Duration>,
+    /// Send `previous_response_id` with only the new turn to Responses upstreams
+    pub chain_responses: bool,
+    /// Translate tool schemas to OpenAI strict mode
+    pub strict_tools: bool,
+    /// Cut tool result texts longer than this many bytes to their start and end
+    pub tool_result_limit: Option<usize>,
+    /// Summarize older turns once requests near the upstream's context window
+    pub compaction: Option<Compaction>,
+    pub sse: SseOptions,
+    pub retry: RetryPolicy,
+    /// Headers forwarded on every upstream request
+    pub headers: BTreeMap<String, String>,
+    /// Claude Code request headers forwarded upstream and upstream response headers
+    /// returned to it
+    pub header_policy: HeaderPolicy,
+    pub endpoints: EndpointOverrides,
+    /// Scripted turns for `mock://` targets
+    pub mock_script: Vec<MockTurn>,
+    /// Maximum upstream requests in flight; extra requests queue by lane
+    pub max_concurrency: Option<usize>,
+    pub models: Vec<ListedModel>,
+    /// Per-request log for the TUI's log viewer
+    pub request_log: Option<RequestLog>,
+    /// Full request and response bodies for the TUI's inspector
+    pub capture: Option<CaptureLog>,
+    /// Delay Codex requests as a usage-limit window runs out
+    pub codex_pacing: bool,
+    /// Second model answering main-conversation turns in consensus mode
+    pub consensus_model: Option<String>,
+    /// Forward requests untranslated to an Anthropic-compatible upstream
+    pub passthrough: bool,
+    /// Models sent to upstreams other than the proxy target
+    pub routes: Vec<RouteOptions>,
+    /// Upstreams the target's sub-agent conversations are spread over
+    pub fanout: Vec<FanoutUpstream>,
+    /// Prepares these options again for `/admin/reload`; without it the proxy has no
+    /// reload endpoint
+    pub reload: Option<ProxyReloader>,
+    /// Sends upstream requests in place of the HTTP client, for tests and embedders
+    pub http_backend: Option<Arc<dyn HttpBackend>>,
+    /// Refreshes OAuth tokens after a 401 in place of the stored sign-in
+    pub token_refresher: Option<Arc<dyn TokenRefresher>>,
+}
+
+/// A model Claude Code can select, as reported by `/v1/models`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedModel {
+    pub id: String,
+    pub display_name: String,
+}
+
+impl ListedModel {
+    /// The profile's tier mappings, strongest first, without duplicates
+    pub fn from_env(env: &HashMap<String, String>) -> Vec<Self> {
+        let tiers = [
+            (ENV_DEFAULT_OPUS_MODEL, "Opus"),
+            (ENV_DEFAULT_SONNET_MODEL, "Sonnet"),
+            (ENV_DEFAULT_HAIKU_MODEL, "Haiku"),
+            (ENV_MODEL, "Default"),
+            (ENV_SMALL_FAST_MODEL, "Small/fast"),
+        ];
+        let mut models: Vec<Self> = Vec::new();
+        for (key, tier) in tiers {
+            let Some(id) = env.get(key).map(|v| v.trim()).filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            if models.iter().any(|m| m.id == id) {
+                continue;
+            }
+            models.push(Self {
+                id: id.to_string(),
+                display_name: format!("{} ({})", tier, id),
+            });
+        }
+        models
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        serde_json::json!({
+            "type": "model",
+            "id": self.id,
+            "display_name": self.display_name,
+            "created_at": LISTED_MODEL_CREATED_AT,
+        })
+    }
+}
+
+/// Explicit upstream endpoints for servers the URL heuristics get wrong
+/// (gateways mounted under a subpath, or APIs served without `/v1`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointOverrides {
+    pub responses: Option<String>,
+    pub chat_completions: Option<String>,
+    pub completions: Option<String>,
+}
+
+impl EndpointOverrides {
+    pub fn from_env(env: &HashMap<String, String>) -> Self {
+        let url = |key: &str| {
+            env.get(key)
+                .map(|v| v.trim().trim_end_matches('/').to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Self {
+            responses: url(ENV_RESPONSES_URL),
+            chat_completions: url(ENV_CHAT_COMPLETIONS_URL),
+            completions: url(ENV_COMPLETIONS_URL),
+        }
+    }
+}
+
+/// Per-profile request shaping for `max_tokens`.
+///
+/// Local models often reject the large output budgets Claude Code asks for, so a
+/// profile can clamp the value into `[min, max]` and supply a default when the
+/// request omits it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenLimits {
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    pub default: Option<u32>,
+}
+
+impl TokenLimits {
+    pub fn from_env(env: &HashMap<String, String>) -> Self {
+        let parse = |key: &str| env.get(key).and_then(|v| v.trim().parse::<u32>().ok());
+        Self {
+            min: parse(ENV_MIN_OUTPUT_TOKENS),
+            max: parse(ENV_MAX_OUTPUT_TOKENS),
+            default: parse(ENV_DEFAULT_OUTPUT_TOKENS),
+        }
+    }
+
+    pub(crate) fn apply(&self, requested: Option<u32>) -> Option<u32> {
+        let mut value = requested.or(self.default)?;
+        if let Some(max) = self.max {
+            value = value.min(max);
+        }
+        if let Some(min) = self.min {
+            value = value.max(min);
+        }
+        Some(value)
+    }
+
+    /// Keep the thinking budget below `max_tokens`, as upstreams require once a
+    /// clamp has lowered it: the budget is halved to leave room for the answer, or
+    /// thinking is dropped when that would be under the smallest budget allowed.
+    /// Returns the old budget and the new one (`None` when dropped).
+    pub(crate) fn fit_thinking(request: &mut AnthropicRequest) -> Option<(u32, Option<u32>)> {
+        let max_tokens = request.max_tokens?;
+        let Some(ThinkingConfig::Enabled {
+            budget_tokens: Some(budget),
+        }) = request.thinking
+        else {
+            return None;
+        };
+        if budget < max_tokens {
+            return None;
+        }
+        let fitted = Some(max_tokens / 2).filter(|fitted| *fitted >= MIN_THINKING_BUDGET);
+        request.thinking = fitted.map(|budget| ThinkingConfig::Enabled {
+            budget_tokens: Some(budget),
+        });
+        Some((budget, fitted))
+    }
+}
+
+/// Smallest thinking budget upstreams accept
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// Appended to the system prompt when a thinking request is downscaled
+const THINKING_HINT: &str = "Think through the problem step by step before you answer, \
+then give a clear final answer.";
+
+/// Downscaling of thinking for models that cannot serve extended thinking. A
+/// request whose budget exceeds `max_budget` tokens (or that sets none) is sent
+/// without its thinking config, with a step-by-step hint in the system prompt
+/// instead. `0` converts every thinking request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThinkingHint {
+    pub max_budget: Option<u32>,
+}
+
+impl ThinkingHint {
+    pub fn from_env(env: &HashMap<String, String>) -> Self {
+        Self {
+            max_budget: env
+                .get(ENV_THINKING_HINT_BUDGET)
+                .and_then(|v| v.trim().parse::<u32>().ok()),
+        }
+    }
+
+    /// Convert the request's thinking into the hint when it is over budget. Returns
+    /// the budget that was replaced (`None` inside when the request set none).
+    pub(crate) fn apply(&self, request: &mut AnthropicRequest) -> Option<Option<u32>> {
+        let max_budget = self.max_budget?;
+        let Some(ThinkingConfig::Enabled { budget_tokens }) = request.thinking else {
+            return None;
+        };
+        if budget_tokens.is_some_and(|budget| budget <= max_budget) {
+            return None;
+        }
+        request.thinking = None;
+        request.system = Some(match request.system.take() {
+            None => SystemPrompt::Text(THINKING_HINT.to_string()),
+            Some(SystemPrompt::Text(text)) if text.trim().is_empty() => {
+                SystemPrompt::Text(THINKING_HINT.to_string())
+            }
+            Some(SystemPrompt::Text(text)) => {
+                SystemPrompt::Text(format!("{}\n\n{}", text, THINKING_HINT))
+            }
+            Some(SystemPrompt::Blocks(mut blocks)) => {
+                blocks.push(SystemBlock {
+                    block_type: "text".to_string(),
+                    text: THINKING_HINT.to_string(),
+                    cache_control: None,
+                });
+                SystemPrompt::Blocks(blocks)
+            }
+        });
+        Some(budget_tokens)
+    }
+}
+
+/// How much of its reasoning a Responses upstream summarizes back as thinking text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReasoningSummary {
+    #[default]
+    Auto,
+    Concise,
+    Detailed,
+    /// No summary; only the encrypted reasoning is returned
+    Off,
+}
+
+impl ReasoningSummary {
+    /// The profile's setting; unset or unrecognized values leave the default
+    pub fn from_env(env: &HashMap<String, String>) -> Option<Self> {
+        match env
+            .get(ENV_REASONING_SUMMARY)?
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "auto" => Some(ReasoningSummary::Auto),
+            "concise" => Some(ReasoningSummary::Concise),
+            "detailed" => Some(ReasoningSummary::Detailed),
+            "none" | "off" => Some(ReasoningSummary::Off),
+            _ => None,
+        }
+    }
+
+    fn param(self) -> Option<&'static str> {
+        match self {
+            ReasoningSummary::Auto => Some("auto"),
+            ReasoningSummary::Concise => Some("concise"),
+            ReasoningSummary::Detailed => Some("detailed"),
+            ReasoningSummary::Off => None,
+        }
+    }
+
+    /// Set the summary on a request's reasoning, adding reasoning when there is none
+    /// and a summary is wanted
+    pub(crate) fn apply(self, reasoning: &mut Option<ResponseReasoning>) {
+        match (self.param(), reasoning.as_mut()) {
+            (Some(summary), _) => {
+                reasoning
+                    .get_or_insert(ResponseReasoning {
+                        effort: None,
+                        summary: None,
+                    })
+                    .summary = Some(summary.to_string());
+            }
+            (None, Some(reasoning)) => reasoning.summary = None,
+            (None, None) => {}
+        }
+    }
+}
+
+/// Sampling parameters forced onto requests for one model tier
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Sampling {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+}
+
+impl Sampling {
+    /// Parse `temperature=0.6 top_p=0.95 top_k=20` (comma or space separated)
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut sampling = Self::default();
+        for part in spec
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+        {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got '{}'", part))?;
+            let invalid = || format!("invalid value for {}: '{}'", key, value);
+            match key.trim() {
+                "temperature" => {
+                    sampling.temperature = Some(value.parse().map_err(|_| invalid())?);
+                }
+                "top_p" => sampling.top_p = Some(value.parse().map_err(|_| invalid())?),
+                "top_k" => sampling.top_k = Some(value.parse().map_err(|_| invalid())?),
+                other => return Err(format!("unknown sampling parameter '{}'", other)),
+            }
+        }
+        Ok(sampling)
+    }
+
+    fn apply(&self, request: &mut AnthropicRequest) {
+        if let Some(temperature) = self.temperature {
+            request.temperature = Some(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            request.top_p = Some(top_p);
+        }
+        if let Some(top_k) = self.top_k {
+            request.top_k = Some(top_k);
+        }
+    }
+}
+
+/// One tier's sampling overrides and the upstream model the profile maps it to
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TierSampling {
+    /// Lowercase tier name found in Claude model ids (`opus`, `sonnet`, `haiku`)
+    tier: &'static str,
+    mapped_model: Option<String>,
+    sampling: Sampling,
+}
+
+/// Per-tier sampling overrides.
+///
+/// Local models often need very different temperatures from Claude's defaults. A
+/// request belongs to a tier when it asks for the model that tier is mapped to, or
+/// for a Claude model with the tier in its name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SamplingOverrides {
+    tiers: Vec<TierSampling>,
+}
+
+impl SamplingOverrides {
+    /// Invalid specs are ignored here; the profile editor rejects them on save
+    pub fn from_env(env: &HashMap<String, String>) -> Self {
+        let tiers = [
+            ("opus", ENV_OPUS_SAMPLING, ENV_DEFAULT_OPUS_MODEL),
+            ("sonnet", ENV_SONNET_SAMPLING, ENV_DEFAULT_SONNET_MODEL),
+            ("haiku", ENV_HAIKU_SAMPLING, ENV_DEFAULT_HAIKU_MODEL),
+        ];
+        let tiers = tiers
+            .into_iter()
+            .filter_map(|(tier, sampling_key, model_key)| {
+                let sampling = Sampling::parse(env.get(sampling_key)?).ok()?;
+                let mapped_model = env
+                    .get(model_key)
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty());
+                Some(TierSampling {
+                    tier,
+                    mapped_model,
+                    sampling,
+                })
+            })
+            .collect();
+        Self { tiers }
+    }
+
+    fn for_model(&self, model: &str) -> Option<&Sampling> {
+        self.tiers
+            .iter()
+            .find(|tier| tier.mapped_model.as_deref() == Some(model))
+            .or_else(|| {
+                let model = model.to_ascii_lowercase();
+                self.tiers.iter().find(|tier| model.contains(tier.tier))
+            })
+            .map(|tier| &tier.sampling)
+    }
+
+    pub(crate) fn apply(&self, request: &mut AnthropicRequest) {
+        if let Some(sampling) = self.for_model(&request.model) {
+            sampling.apply(request);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listen_addr_defaults_and_client_urls() {
+        let default = ListenAddr::new(None, None);
+        assert_eq!(default.socket(), "127.0.0.1:4000");
+        assert_eq!(default.anthropic_url(), PROXY_ANTHROPIC_URL);
+
+        let wildcard = ListenAddr::new(Some("0.0.0.0"), Some(4101));
+        assert_eq!(wildcard.socket(), "0.0.0.0:4101");
+        assert_eq!(wildcard.anthropic_url(), "http://localhost:4101/anthropic");
+
+        let lan = ListenAddr::new(Some("192.168.1.20"), Some(4102));
+        assert_eq!(lan.base_url(), "http://192.168.1.20:4102");
+
+        let v6 = ListenAddr::new(Some("::1"), None);
+        assert_eq!(v6.socket(), "[::1]:4000");
+        assert_eq!(v6.base_url(), "http://localhost:4000");
+        assert_eq!(ListenAddr::new(Some("  "), None).bind, PROXY_BIND);
+    }
+
+    #[test]
+    fn large_thinking_budgets_become_a_system_hint() {
+        let hint = ThinkingHint::from_env(&HashMap::from([(
+            ENV_THINKING_HINT_BUDGET.to_string(),
+            "4096".to_string(),
+        )]));
+        let thinking = |budget| {
+            let mut request = base_request(Vec::new());
+            request.system = Some(SystemPrompt::Text("Be brief.".to_string()));
+            request.thinking = Some(ThinkingConfig::Enabled {
+                budget_tokens: budget,
+            });
+            request
+        };
+
+        let mut small = thinking(Some(2048));
+        assert_eq!(hint.apply(&mut small), None);
+        assert!(small.thinking.is_some());
+
+        let mut large = thinking(Some(31999));
+        assert_eq!(hint.apply(&mut large), Some(Some(31999)));
+        assert!(large.thinking.is_none());
+        let system = system_prompt_text_opt(large.system.as_ref()).unwrap();
+        assert!(system.starts_with("Be brief.") && system.ends_with(THINKING_HINT));
+
+        let mut unset = thinking(None);
+        assert_eq!(hint.apply(&mut unset), Some(None));
+        assert_eq!(ThinkingHint::default().apply(&mut thinking(None)), None);
+    }
+
+    #[test]
+    fn sampling_overrides_match_tier_by_mapping_or_name() {
+        assert!(Sampling::parse("temperature=hot").is_err());
+        assert!(Sampling::parse("seed=1").is_err());
+
+        let overrides = SamplingOverrides::from_env(&HashMap::from([
+            (
+                ENV_DEFAULT_OPUS_MODEL.to_string(),
+                "qwen3-coder".to_string(),
+            ),
+            (
+                ENV_OPUS_SAMPLING.to_string(),
+                "temperature=0.7, top_p=0.8 top_k=20".to_string(),
+            ),
+            (
+                ENV_HAIKU_SAMPLING.to_string(),
+                "temperature=0.2".to_string(),
+            ),
+        ]));
+        let request = |model: &str| AnthropicRequest {
+            model: model.to_string(),
+            messages: vec![],
+            max_tokens: None,
+            temperature: Some(1.0),
+            top_p: None,
+            top_k: None,
+            stop_sequences: None,
+            stream: None,
+            system: None,
+            tools: None,
+            tool_choice: None,
+            thinking: None,
+            metadata: None,
+        };
+
+        let mut opus = request("qwen3-coder");
+        overrides.apply(&mut opus);
+        assert_eq!(
+            (opus.temperature, opus.top_p, opus.top_k),
+            (Some(0.7), Some(0.8), Some(20))
+        );
+
+        let mut haiku = request("claude-haiku-4-5");
+        overrides.apply(&mut haiku);
+        assert_eq!((haiku.temperature, haiku.top_p), (Some(0.2), None));
+
+        let mut sonnet = request("claude-sonnet-4-5");
+        overrides.apply(&mut sonnet);
+        assert_eq!(sonnet.temperature, Some(1.0));
+    }
+
+    #[test]
+    fn token_limits_clamp_and_default() {
+        let limits = TokenLimits::from_env(&HashMap::from([
+            (ENV_MIN_OUTPUT_TOKENS.to_string(), "256".to_string()),
+            (ENV_MAX_OUTPUT_TOKENS.to_string(), " 8192 ".to_string()),
+            (ENV_DEFAULT_OUTPUT_TOKENS.to_string(), "4096".to_string()),
+        ]));
+
+        assert_eq!(limits.apply(Some(32000)), Some(8192));
+        assert_eq!(limits.apply(Some(16)), Some(256));
+        assert_eq!(limits.apply(Some(1024)), Some(1024));
+        assert_eq!(limits.apply(None), Some(4096));
+        assert_eq!(TokenLimits::default().apply(None), None);
+
+        // The thinking budget has to stay below the clamped max_tokens
+        let mut request = base_request(Vec::new());
+        request.max_tokens = limits.apply(Some(32000));
+        request.thinking = Some(ThinkingConfig::Enabled {
+            budget_tokens: Some(16000),
+        });
+        assert_eq!(
+            TokenLimits::fit_thinking(&mut request),
+            Some((16000, Some(4096)))
+        );
+        assert_eq!(TokenLimits::fit_thinking(&mut request), None);
+        request.max_tokens = Some(1024);
+        assert_eq!(TokenLimits::fit_thinking(&mut request), Some((4096, None)));
+        assert!(request.thinking.is_none());
+    }
+
+    #[test]
+    fn reasoning_summary_is_set_or_removed() {
+        let env =
+            |value: &str| HashMap::from([(ENV_REASONING_SUMMARY.to_string(), value.to_string())]);
+        assert_eq!(
+            ReasoningSummary::from_env(&env(" Detailed ")),
+            Some(ReasoningSummary::Detailed)
+        );
+        assert_eq!(
+            ReasoningSummary::from_env(&env("none")),
+            Some(ReasoningSummary::Off)
+        );
+        assert_eq!(ReasoningSummary::from_env(&env("verbose")), None);
+        assert_eq!(ReasoningSummary::from_env(&HashMap::new()), None);
+
+        let mut reasoning = None;
+        ReasoningSummary::Concise.apply(&mut reasoning);
+        let set = reasoning.as_ref().unwrap();
+        assert_eq!(
+            (set.effort.as_deref(), set.summary.as_deref()),
+            (None, Some("concise"))
+        );
+
+        reasoning.as_mut().unwrap().effort = Some("high".to_string());
+        ReasoningSummary::Off.apply(&mut reasoning);
+        let cleared = reasoning.unwrap();
+        assert_eq!(
+            (cleared.effort.as_deref(), cleared.summary),
+            (Some("high"), None)
+        );
+
+        let mut reasoning = None;
+        ReasoningSummary::Off.apply(&mut reasoning);
+        assert!(reasoning.is_none());
+    }
+}
@@ -0,0 +1,1824 @@
+//! Conversion of upstream streams into Anthropic SSE events, plus SSE batching and
+//! usage tracking.
+
+use super::*;
+
+pub(crate) fn is_sse_response(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/event-stream"))
+}
+
+/// Apply micro-batching and latency logging to an SSE response body
+pub(crate) fn shape_sse(options: SseOptions, response: Response) -> Response {
+    if options == SseOptions::default() || !is_sse_response(&response) {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let stream = shape_sse_stream(body.into_data_stream(), options);
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+fn shape_sse_stream<E>(
+    data: impl Stream<Item = Result<bytes::Bytes, E>> + Send + 'static,
+    options: SseOptions,
+) -> impl Stream<Item = Result<bytes::Bytes, E>> + Send + 'static
+where
+    E: Send + 'static,
+{
+    use futures::StreamExt;
+
+    async_stream::stream! {
+        futures::pin_mut!(data);
+        let start = std::time::Instant::now();
+        let mut last = start;
+
+        while let Some(chunk) = data.next().await {
+            let Ok(first) = chunk else {
+                yield chunk;
+                break;
+            };
+
+            let mut bytes = first;
+            if let Some(window) = options.batch {
+                let deadline = tokio::time::Instant::now() + window;
+                let mut batch = bytes.to_vec();
+                while let Ok(Some(Ok(more))) = tokio::time::timeout_at(deadline, data.next()).await {
+                    batch.extend_from_slice(&more);
+                }
+                bytes = bytes::Bytes::from(batch);
+            }
+
+            if options.debug {
+                let now = std::time::Instant::now();
+                let text = String::from_utf8_lossy(&bytes);
+                let events: Vec<&str> = text
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("event: "))
+                    .collect();
+                log_to(
+                    LogSink::SseDebug,
+                    &format!(
+                        "[sse] +{}ms (+{}ms) {} bytes: {} {:?}",
+                        now.duration_since(start).as_millis(),
+                        now.duration_since(last).as_millis(),
+                        bytes.len(),
+                        events.join(","),
+                        text
+                    ),
+                );
+                last = now;
+            }
+
+            yield Ok(bytes);
+        }
+    }
+}
+
+/// Usage reported by an Anthropic SSE chunk as (input_tokens, output_tokens), with
+/// cached prompt tokens counted as input
+pub(crate) fn usage_from_sse_chunk(chunk: &str) -> (Option<u32>, Option<u32>) {
+    let mut input = None;
+    let mut output = None;
+    for line in chunk.lines() {
+        let Some(data) = line.strip_prefix("data: ") else {
+            continue;
+        };
+        let Ok(event) = serde_json::from_str::<Value>(data) else {
+            continue;
+        };
+        let tokens = |pointer: &str| event.pointer(pointer).and_then(lenient::count);
+        let prompt = |usage: Option<&Value>| {
+            let usage = usage.filter(|usage| usage.get("input_tokens").is_some())?;
+            Some(AnthropicUsage::from_openai_usage_value(usage).total_input_tokens())
+        };
+        match event.get("type").and_then(|t| t.as_str()) {
+            Some("message_start") => input = prompt(event.pointer("/message/usage")),
+            Some("message_delta") => {
+                input = prompt(event.get("usage")).or(input);
+                output = tokens("/usage/output_tokens");
+            }
+            _ => {}
+        }
+    }
+    (input, output)
+}
+
+/// Record token usage from a successful response on its way back to Claude Code
+pub(crate) async fn track_usage(
+    usage: Arc<UsageTracker>,
+    model: String,
+    response: Response,
+) -> Response {
+    use futures::StreamExt;
+
+    if !response.status().is_success() {
+        return response;
+    }
+    let is_sse = is_sse_response(&response);
+    let (parts, body) = response.into_parts();
+
+    if is_sse {
+        let mut input_tokens = 0;
+        let stream = body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                let (input, output) = usage_from_sse_chunk(&String::from_utf8_lossy(bytes));
+                if let Some(input) = input {
+                    input_tokens = input;
+                }
+                if let Some(output) = output {
+                    usage.record(&model, input_tokens, output);
+                }
+            }
+            chunk
+        });
+        return Response::from_parts(parts, Body::from_stream(stream));
+    }
+
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::BAD_GATEWAY.into_response();
+    };
+    if let Ok(value) = serde_json::from_slice::<Value>(&bytes) {
+        let usage_value = value.get("usage").cloned().unwrap_or(Value::Null);
+        let parsed = AnthropicUsage::from_openai_usage_value(&usage_value);
+        usage.record(&model, parsed.total_input_tokens(), parsed.output_tokens);
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+enum SseLine {
+    Done,
+    Json(Value),
+}
+
+fn parse_sse_line(line: &str) -> Option<SseLine> {
+    let line = line.trim_end_matches('\r');
+    if line.is_empty() || line.starts_with("event:") {
+        return None;
+    }
+    let data = line.strip_prefix("data:")?.trim_start();
+    if data == "[DONE]" {
+        return Some(SseLine::Done);
+    }
+    let event: Value = serde_json::from_str(data).ok()?;
+    Some(SseLine::Json(event))
+}
+
+/// Usage from a Chat Completions or Completions chunk; with `include_usage` it comes
+/// in a chunk of its own after the one with the finish reason
+fn record_chunk_usage(state: &mut StreamState, event: &Value) {
+    if let Some(usage) = event.get("usage").filter(|usage| usage.is_object())
+        && let Ok(usage) = serde_json::from_value::<ChatUsage>(usage.clone())
+    {
+        state.reported_usage = Some(AnthropicUsage::from_prompt_completion(
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            usage.cached_tokens(),
+        ));
+    }
+}
+
+/// The upstream said why it stopped: finish now if its usage is known, otherwise
+/// when the usage chunk, `[DONE]` or the end of the stream comes
+fn finish_reason_events(state: &mut StreamState, msg_id: &str, model: &str) -> Vec<String> {
+    if state.reported_usage.is_some() {
+        finish_stream_message(state, msg_id, model)
+    } else {
+        state.finish_pending = true;
+        Vec::new()
+    }
+}
+
+fn finish_stream_message(state: &mut StreamState, msg_id: &str, model: &str) -> Vec<String> {
+    let mut events = Vec::new();
+    if state.finished {
+        return events;
+    }
+    if let Some(start) = state.ensure_message_started(msg_id, model) {
+        events.push(start);
+    }
+    events.extend(state.finish_message());
+    events
+}
+
+fn text_delta_events(
+    state: &mut StreamState,
+    msg_id: &str,
+    model: &str,
+    content: &str,
+) -> Vec<String> {
+    let mut events = Vec::new();
+    if let Some(start) = state.ensure_message_started(msg_id, model) {
+        events.push(start);
+    }
+    if let Some(stop) = state.close_thinking_block() {
+        events.push(stop);
+    }
+    if let Some(start) = state.ensure_text_block_started() {
+        events.push(start);
+    }
+
+    state.output_tokens += 1;
+    let escaped = escape_json_string(content);
+    if let Some(index) = state.text_block_index {
+        events.push(event_text_delta(index, &escaped));
+    }
+    events
+}
+
+/// Stream refusal text as regular text and end the message as a refusal
+fn refusal_delta_events(
+    state: &mut StreamState,
+    msg_id: &str,
+    model: &str,
+    refusal: &str,
+) -> Vec<String> {
+    state.stop_reason = Some(REFUSAL_STOP_REASON);
+    text_delta_events(state, msg_id, model, refusal)
+}
+
+/// A content filter stopped the stream; explain it unless text was already shown
+fn content_filter_events(state: &mut StreamState, msg_id: &str, model: &str) -> Vec<String> {
+    let events = if state.text_block_index.is_none() {
+        text_delta_events(state, msg_id, model, CONTENT_FILTER_NOTICE)
+    } else {
+        Vec::new()
+    };
+    state.stop_reason = Some(REFUSAL_STOP_REASON);
+    events
+}
+
+fn thinking_delta_events(
+    state: &mut StreamState,
+    msg_id: &str,
+    model: &str,
+    content: &str,
+) -> Vec<String> {
+    let mut events = Vec::new();
+    if let Some(start) = state.ensure_message_started(msg_id, model) {
+        events.push(start);
+    }
+    if let Some(start) = state.ensure_thinking_block_started() {
+        events.push(start);
+    }
+    if state.thinking_block_open {
+        state.output_tokens += 1;
+        let escaped = escape_json_string(content);
+        if let Some(index) = state.thinking_block_index {
+            events.push(event_thinking_delta(index, &escaped));
+        }
+    }
+    events
+}
+
+/// Create an Anthropic-format SSE stream from OpenAI Responses stream
+pub(crate) fn create_anthropic_stream(
+    byte_stream: impl Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send + 'static,
+    model: String,
+    include_thinking: bool,
+) -> impl Stream<Item = Result<String, Infallible>> + Send + 'static {
+    use futures::StreamExt;
+
+    let mut buffer = String::new();
+    let mut state = StreamState::new();
+
+    async_stream::stream! {
+        let msg_id = format!("msg_{}", uuid_simple());
+        let model = model;
+
+        futures::pin_mut!(byte_stream);
+        let mut error = None;
+
+        while let Some(chunk_result) = byte_stream.next().await {
+            match chunk_result {
+                Ok(bytes) => {
+                    buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+                    // Process complete SSE lines
+                    while let Some(line) = drain_sse_line(&mut buffer) {
+                        let line = match parse_sse_line(&line) {
+                            Some(line) => line,
+                            None => continue,
+                        };
+
+                        match line {
+                            SseLine::Done => {
+                                for event in finish_stream_message(&mut state, &msg_id, &model) {
+                                    yield Ok(event);
+                                }
+                            }
+                            SseLine::Json(event) => {
+                                let event_type =
+                                    event.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+                                match event_type {
+                            "response.output_text.delta" => {
+                                if let Some(content) = event.get("delta").and_then(|d| d.as_str())
+                                    && !content.is_empty()
+                                {
+                                    for event in
+                                        text_delta_events(&mut state, &msg_id, &model, content)
+                                    {
+                                        yield Ok(event);
+                                    }
+                                }
+                            }
+                            "response.refusal.delta" => {
+                                if let Some(refusal) = event.get("delta").and_then(|d| d.as_str())
+                                    && !refusal.is_empty()
+                                {
+                                    for event in
+                                        refusal_delta_events(&mut state, &msg_id, &model, refusal)
+                                    {
+                                        yield Ok(event);
+                                    }
+                                }
+                            }
+                            "response.reasoning_summary_part.added"
+                                if include_thinking
+                                    // Summary parts are separate paragraphs
+                                    && event.get("summary_index").and_then(|i| i.as_u64())
+                                        > Some(0) =>
+                            {
+                                for event in
+                                    thinking_delta_events(&mut state, &msg_id, &model, "\n\n")
+                                {
+                                    yield Ok(event);
+                                }
+                            }
+                            "response.reasoning_text.delta"
+                            | "response.reasoning_summary_text.delta"
+                                if include_thinking =>
+                            {
+                                if let Some(reasoning) = event.get("delta").and_then(|d| d.as_str())
+                                    && !reasoning.is_empty()
+                                {
+                                    for event in thinking_delta_events(
+                                        &mut state,
+                                        &msg_id,
+                                        &model,
+                                        reasoning,
+                                    ) {
+                                        yield Ok(event);
+                                    }
+                                }
+                            }
+                            "response.output_item.added" => {
+                                if let Some(start) = state.ensure_message_started(&msg_id, &model) {
+                                    yield Ok(start);
+                                }
+
+                                if let Some(output_index) = output_index(&event)
+                                    && let Some(item) = event.get("item")
+                                {
+                                    let item_type =
+                                        item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                                    if item_type == "function_call" {
+                                        state.capture_tool_metadata(output_index, item);
+                                        if let Some(start) = state.ensure_tool_block_open(output_index)
+                                        {
+                                            yield Ok(start);
+                                        }
+                                        if let Some(arguments) =
+                                            item.get("arguments").and_then(|v| v.as_str())
+                                        {
+                                            state.complete_tool_args(output_index, arguments);
+                                        }
+                                        if let Some(delta) = state.flush_tool_args(output_index) {
+                                            yield Ok(delta);
+                                        }
+                                    }
+                                }
+                            }
+                            "response.function_call_arguments.delta" => {
+                                if let Some(start) = state.ensure_message_started(&msg_id, &model) {
+                                    yield Ok(start);
+                                }
+                                if let (Some(output_index), Some(delta)) = (
+                                    output_index(&event),
+                                    event.get("delta").and_then(|d| d.as_str()),
+                                )
+                                    && !delta.is_empty()
+                                {
+                                    state.append_tool_args(output_index, delta);
+                                    if let Some(delta) = state.flush_tool_args(output_index) {
+                                        yield Ok(delta);
+                                    }
+                                }
+                            }
+                            "response.function_call_arguments.done" => {
+                                if let Some(start) = state.ensure_message_started(&msg_id, &model) {
+                                    yield Ok(start);
+                                }
+                                if let Some(output_index) = output_index(&event) {
+                                    state.capture_tool_metadata(output_index, &event);
+                                    if let Some(start) = state.ensure_tool_block_open(output_index) {
+                                        yield Ok(start);
+                                    }
+                                    if let Some(arguments) =
+                                        event.get("arguments").and_then(|a| a.as_str())
+                                    {
+                                        state.complete_tool_args(output_index, arguments);
+                                    }
+                                    if let Some(delta) = state.flush_tool_args(output_index) {
+                                        yield Ok(delta);
+                                    }
+                                }
+                            }
+                            "response.output_item.done" => {
+                                if let (Some(output_index), Some(item)) = (output_index(&event), event.get("item")) {
+                                    let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                                    if item_type == "function_call" {
+                                        if let Some(arguments) = item.get("arguments").and_then(|a| a.as_str()) {
+                                            state.complete_tool_args(output_index, arguments);
+                                        }
+                                        for event in state.close_tool_block(output_index) {
+                                            yield Ok(event);
+                                        }
+                                    }
+                                }
+                            }
+                            "response.completed" | "response.failed" | "response.incomplete" => {
+                                if let Some(usage) = event.pointer("/response/usage").filter(|u| u.is_object()) {
+                                    state.reported_usage = Some(AnthropicUsage::from_openai_usage_value(usage));
+                                }
+                                let reason = event
+                                    .pointer("/response/incomplete_details/reason")
+                                    .and_then(|r| r.as_str());
+                                if reason == Some(CONTENT_FILTER_REASON) {
+                                    for event in content_filter_events(&mut state, &msg_id, &model) {
+                                        yield Ok(event);
+                                    }
+                                }
+                                for event in finish_stream_message(&mut state, &msg_id, &model) {
+                                    yield Ok(event);
+                                }
+                            }
+                            _ => {}
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        // Responses streams always end with `response.completed`; anything else was cut off
+        if !state.finished {
+            for event in interrupted_stream_events(&mut state, "Responses", error.as_ref()) {
+                yield Ok(event);
+            }
+        }
+    }
+}
+
+/// Create an Anthropic-format SSE stream from OpenAI Chat Completions stream
+pub(crate) fn create_anthropic_stream_from_chat(
+    byte_stream: impl Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send + 'static,
+    model: String,
+    dialect: ChatDialect,
+    include_thinking: bool,
+) -> impl Stream<Item = Result<String, Infallible>> + Send + 'static {
+    use futures::StreamExt;
+
+    let mut buffer = String::new();
+    let mut state = StreamState::new();
+    let mut adapter = DialectAdapter::new(dialect);
+
+    async_stream::stream! {
+        let msg_id = format!("msg_{}", uuid_simple());
+        let model = model;
+
+        futures::pin_mut!(byte_stream);
+        let mut error = None;
+
+        while let Some(chunk_result) = byte_stream.next().await {
+            match chunk_result {
+                Ok(bytes) => {
+                    buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+                    while let Some(line) = drain_sse_line(&mut buffer) {
+                        let line = match parse_sse_line(&line) {
+                            Some(line) => line,
+                            None => continue,
+                        };
+
+                        match line {
+                            SseLine::Done => {
+                                for event in finish_stream_message(&mut state, &msg_id, &model) {
+                                    yield Ok(event);
+                                }
+                            }
+                            SseLine::Json(mut event) => {
+                                adapter.adapt(&mut event);
+                                record_chunk_usage(&mut state, &event);
+                                if let Some(choices) = event.get("choices").and_then(|c| c.as_array()) {
+                                    for choice in choices {
+                                        if let Some(delta) = choice.get("delta") {
+                                            if include_thinking
+                                                && let Some(reasoning) = delta.get("reasoning_content").and_then(|r| r.as_str())
+                                            {
+                                                for event in thinking_delta_events(
+                                                    &mut state,
+                                                    &msg_id,
+                                                    &model,
+                                                    reasoning,
+                                                ) {
+                                                    yield Ok(event);
+                                                }
+                                            }
+
+                                            if let Some(refusal) = delta.get("refusal").and_then(|r| r.as_str())
+                                                && !refusal.is_empty()
+                                            {
+                                                for event in refusal_delta_events(
+                                                    &mut state,
+                                                    &msg_id,
+                                                    &model,
+                                                    refusal,
+                                                ) {
+                                                    yield Ok(event);
+                                                }
+                                            }
+
+                                            if let Some(content) = delta.get("content").and_then(|c| c.as_str())
+                                                && !content.is_empty()
+                                            {
+                                                for event in text_delta_events(
+                                                    &mut state,
+                                                    &msg_id,
+                                                    &model,
+                                                    content,
+                                                ) {
+                                                    yield Ok(event);
+                                                }
+                                            }
+
+                                            if let Some(tool_calls) = delta.get("tool_calls").and_then(|t| t.as_array()) {
+                                                for (tool_idx, tool_call) in tool_calls.iter().enumerate() {
+                                                    let output_index = tool_call
+                                                        .get("index")
+                                                        .and_then(|v| v.as_u64())
+                                                        .unwrap_or(tool_idx as u64) as u32;
+
+                                                    let call_id = tool_call.get("id").and_then(|v| v.as_str());
+                                                    let function = tool_call.get("function");
+                                                    let name = function.and_then(|f| f.get("name").and_then(|v| v.as_str()));
+                                                    let arguments = function.and_then(|f| f.get("arguments").and_then(|v| v.as_str()));
+
+                                                    if call_id.is_some() || name.is_some() {
+                                                        let mut map = serde_json::Map::new();
+                                                        if let Some(id) = call_id {
+                                                            map.insert("id".to_string(), Value::String(id.to_string()));
+                                                        }
+                                                        if let Some(name) = name {
+                                                            map.insert("name".to_string(), Value::String(name.to_string()));
+                                                        }
+                                                        if !map.is_empty() {
+                                                            state.capture_tool_metadata(output_index, &Value::Object(map));
+                                                        }
+                                                    }
+
+                                                    if let Some(start) = state.ensure_message_started(&msg_id, &model) {
+                                                        yield Ok(start);
+                                                    }
+                                                    if let Some(stop) = state.close_thinking_block() {
+                                                        yield Ok(stop);
+                                                    }
+                                                    if let Some(start) = state.ensure_tool_block_open(output_index) {
+                                                        yield Ok(start);
+                                                    }
+                                                    if let Some(args) = arguments {
+                                                        state.append_tool_args(output_index, args);
+                                                    }
+                                                    if let Some(delta) = state.flush_tool_args(output_index) {
+                                                        yield Ok(delta);
+                                                    }
+                                                }
+                                            }
+                                        }
+
+                                        if let Some(finish) = choice.get("finish_reason").and_then(|f| f.as_str())
+                                            && !finish.is_empty()
+                                        {
+                                            if finish == CONTENT_FILTER_REASON {
+                                                for event in content_filter_events(&mut state, &msg_id, &model) {
+                                                    yield Ok(event);
+                                                }
+                                            }
+                                            for event in finish_reason_events(&mut state, &msg_id, &model) {
+                                                yield Ok(event);
+                                            }
+                                        }
+                                    }
+                                }
+                                if state.finish_pending && state.reported_usage.is_some() {
+                                    for event in finish_stream_message(&mut state, &msg_id, &model) {
+                                        yield Ok(event);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        if let Some(error) = error {
+            for event in interrupted_stream_events(&mut state, "Chat", Some(&error)) {
+                yield Ok(event);
+            }
+        } else if !state.finished {
+            // Some servers close the stream without `[DONE]` or a finish reason
+            if !state.finish_pending {
+                proxy_log(&format!(
+                    "{} chat stream ended without [DONE]; finishing the message",
+                    adapter.dialect().label()
+                ));
+            }
+            for event in finish_stream_message(&mut state, &msg_id, &model) {
+                yield Ok(event);
+            }
+        }
+    }
+}
+
+/// Create an Anthropic-format SSE stream from OpenAI Completions stream
+pub(crate) fn create_anthropic_stream_from_completions(
+    byte_stream: impl Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send + 'static,
+    model: String,
+) -> impl Stream<Item = Result<String, Infallible>> + Send + 'static {
+    use futures::StreamExt;
+
+    let mut buffer = String::new();
+    let mut state = StreamState::new();
+
+    async_stream::stream! {
+        let msg_id = format!("msg_{}", uuid_simple());
+        let model = model;
+
+        futures::pin_mut!(byte_stream);
+        let mut error = None;
+
+        while let Some(chunk_result) = byte_stream.next().await {
+            match chunk_result {
+                Ok(bytes) => {
+                    buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+                    while let Some(line) = drain_sse_line(&mut buffer) {
+                        let line = match parse_sse_line(&line) {
+                            Some(line) => line,
+                            None => continue,
+                        };
+
+                        match line {
+                            SseLine::Done => {
+                                for event in finish_stream_message(&mut state, &msg_id, &model) {
+                                    yield Ok(event);
+                                }
+                            }
+                            SseLine::Json(event) => {
+                                record_chunk_usage(&mut state, &event);
+                                if let Some(choices) = event.get("choices").and_then(|c| c.as_array()) {
+                                    for choice in choices {
+                                        let text = choice
+                                            .get("text")
+                                            .and_then(|t| t.as_str())
+                                            .or_else(|| {
+                                                choice
+                                                    .get("delta")
+                                                    .and_then(|d| d.get("content"))
+                                                    .and_then(|t| t.as_str())
+                                            });
+
+                                        if let Some(content) = text
+                                            && !content.is_empty()
+                                        {
+                                            for event in text_delta_events(
+                                                &mut state,
+                                                &msg_id,
+                                                &model,
+                                                content,
+                                            ) {
+                                                yield Ok(event);
+                                            }
+                                        }
+
+                                        if let Some(finish) = choice.get("finish_reason").and_then(|f| f.as_str())
+                                            && !finish.is_empty()
+                                        {
+                                            if finish == CONTENT_FILTER_REASON {
+                                                for event in content_filter_events(&mut state, &msg_id, &model) {
+                                                    yield Ok(event);
+                                                }
+                                            }
+                                            for event in finish_reason_events(&mut state, &msg_id, &model) {
+                                                yield Ok(event);
+                                            }
+                                        }
+                                    }
+                                }
+                                if state.finish_pending && state.reported_usage.is_some() {
+                                    for event in finish_stream_message(&mut state, &msg_id, &model) {
+                                        yield Ok(event);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        if let Some(error) = error {
+            for event in interrupted_stream_events(&mut state, "Completions", Some(&error)) {
+                yield Ok(event);
+            }
+        } else {
+            for event in finish_stream_message(&mut state, &msg_id, &model) {
+                yield Ok(event);
+            }
+        }
+    }
+}
+
+/// Why a stream ended early, for the error event and the proxy log
+fn interruption_message(api: &str, error: Option<&reqwest::Error>) -> String {
+    let message = match error {
+        Some(e) => format!("{} stream from the upstream failed: {}", api, e),
+        None => format!(
+            "{} stream from the upstream ended before the message was complete",
+            api
+        ),
+    };
+    proxy_log(&message);
+    message
+}
+
+/// End a stream the upstream cut off: the open blocks are closed so Claude Code keeps
+/// what arrived, then an error event takes the place of `message_stop`, so the
+/// truncated turn is not mistaken for a finished one
+fn interrupted_stream_events(
+    state: &mut StreamState,
+    api: &str,
+    error: Option<&reqwest::Error>,
+) -> Vec<String> {
+    state.finished = true;
+    let mut events = state.close_open_blocks();
+    events.push(event_error(&interruption_message(api, error)));
+    events
+}
+
+fn event_error(message: &str) -> String {
+    format!(
+        "event: error\ndata: {{\"type\":\"error\",\"error\":{{\"type\":\"api_error\",\"message\":\"{}\"}}}}\n\n",
+        escape_json_string(message)
+    )
+}
+
+/// Error message carried by an event-stream exception frame, if this is one
+fn event_stream_exception(frame: &EventStreamFrame) -> Option<String> {
+    if frame.headers.get(":message-type").map(String::as_str) != Some("exception") {
+        return None;
+    }
+    let payload: Value = serde_json::from_slice(&frame.payload).unwrap_or(Value::Null);
+    let message = payload
+        .get("message")
+        .and_then(|m| m.as_str())
+        .unwrap_or("Bedrock stream error");
+    let kind = frame
+        .headers
+        .get(":exception-type")
+        .map(String::as_str)
+        .unwrap_or("exception");
+    Some(format!("{}: {}", kind, message))
+}
+
+/// Re-emit the Anthropic events wrapped in a Bedrock InvokeModel event stream as SSE
+pub(crate) fn create_anthropic_stream_from_bedrock_invoke(
+    byte_stream: impl Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send + 'static,
+    model: String,
+) -> impl Stream<Item = Result<String, Infallible>> + Send + 'static {
+    use base64::Engine as _;
+    use futures::StreamExt;
+
+    let mut decoder = EventStreamDecoder::default();
+    // Blocks started but not stopped, and whether `message_stop` came through
+    let mut open_blocks = std::collections::BTreeSet::new();
+    let mut stopped = false;
+
+    async_stream::stream! {
+        futures::pin_mut!(byte_stream);
+        let mut error = None;
+
+        while let Some(chunk_result) = byte_stream.next().await {
+            let bytes = match chunk_result {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            };
+            decoder.push(&bytes);
+
+            while let Some(frame) = decoder.next_frame() {
+                if let Some(message) = event_stream_exception(&frame) {
+                    yield Ok(event_error(&message));
+                    continue;
+                }
+                let Ok(chunk) = serde_json::from_slice::<Value>(&frame.payload) else {
+                    continue;
+                };
+                let Some(decoded) = chunk
+                    .get("bytes")
+                    .and_then(|b| b.as_str())
+                    .and_then(|b| base64::engine::general_purpose::STANDARD.decode(b).ok())
+                else {
+                    continue;
+                };
+                let Ok(mut event) = serde_json::from_slice::<Value>(&decoded) else {
+                    continue;
+                };
+                let event_type = event
+                    .get("type")
+                    .and_then(|t| t.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                if event_type == "message_start"
+                    && let Some(message) = event.get_mut("message")
+                {
+                    message["model"] = Value::String(model.clone());
+                }
+                let index = event.get("index").and_then(|i| i.as_u64());
+                match (event_type.as_str(), index) {
+                    ("content_block_start", Some(index)) => {
+                        open_blocks.insert(index);
+                    }
+                    ("content_block_stop", Some(index)) => {
+                        open_blocks.remove(&index);
+                    }
+                    ("message_stop", _) => stopped = true,
+                    _ => {}
+                }
+                yield Ok(format!("event: {}\ndata: {}\n\n", event_type, event));
+            }
+        }
+
+        if !stopped {
+            for index in open_blocks {
+                yield Ok(event_content_block_stop(index as usize));
+            }
+            yield Ok(event_error(&interruption_message("Bedrock", error.as_ref())));
+        }
+    }
+}
+
+/// Create an Anthropic-format SSE stream from a Bedrock ConverseStream response
+pub(crate) fn create_anthropic_stream_from_converse(
+    byte_stream: impl Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send + 'static,
+    model: String,
+) -> impl Stream<Item = Result<String, Infallible>> + Send + 'static {
+    use futures::StreamExt;
+
+    let mut decoder = EventStreamDecoder::default();
+    let mut state = StreamState::new();
+
+    async_stream::stream! {
+        let msg_id = format!("msg_{}", uuid_simple());
+        let model = model;
+
+        futures::pin_mut!(byte_stream);
+        let mut error = None;
+
+        while let Some(chunk_result) = byte_stream.next().await {
+            let bytes = match chunk_result {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            };
+            decoder.push(&bytes);
+
+            while let Some(frame) = decoder.next_frame() {
+                if let Some(message) = event_stream_exception(&frame) {
+                    yield Ok(event_error(&message));
+                    continue;
+                }
+                let event_type = frame.headers.get(":event-type").cloned().unwrap_or_default();
+                let Ok(event) = serde_json::from_slice::<Value>(&frame.payload) else {
+                    continue;
+                };
+                let block_index = event
+                    .get("contentBlockIndex")
+                    .and_then(|i| i.as_u64())
+                    .unwrap_or(0) as u32;
+
+                match event_type.as_str() {
+                    "messageStart" => {
+                        if let Some(start) = state.ensure_message_started(&msg_id, &model) {
+                            yield Ok(start);
+                        }
+                    }
+                    "contentBlockStart" => {
+                        if let Some(tool) = event.pointer("/start/toolUse") {
+                            let metadata = serde_json::json!({
+                                "id": tool.get("toolUseId"),
+                                "name": tool.get("name"),
+                            });
+                            state.capture_tool_metadata(block_index, &metadata);
+                            if let Some(start) = state.ensure_message_started(&msg_id, &model) {
+                                yield Ok(start);
+                            }
+                            if let Some(start) = state.ensure_tool_block_open(block_index) {
+                                yield Ok(start);
+                            }
+                        }
+                    }
+                    "contentBlockDelta" => {
+                        if let Some(text) = event.pointer("/delta/text").and_then(|t| t.as_str())
+                            && !text.is_empty()
+                        {
+                            for event in text_delta_events(&mut state, &msg_id, &model, text) {
+                                yield Ok(event);
+                            }
+                        } else if let Some(input) =
+                            event.pointer("/delta/toolUse/input").and_then(|i| i.as_str())
+                            && !input.is_empty()
+                            && state.tool_blocks_open.contains(&block_index)
+                        {
+                            let index = state.tool_block_index(block_index);
+                            yield Ok(event_tool_args_delta(index, &escape_json_string(input)));
+                        }
+                    }
+                    "contentBlockStop" => {
+                        if state.tool_blocks_open.remove(&block_index)
+                            && let Some(index) = state.tool_block_indices.get(&block_index)
+                        {
+                            yield Ok(event_content_block_stop(*index));
+                        }
+                    }
+                    "messageStop" => {
+                        for event in finish_stream_message(&mut state, &msg_id, &model) {
+                            yield Ok(event);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if !state.finished {
+            for event in interrupted_stream_events(&mut state, "Bedrock", error.as_ref()) {
+                yield Ok(event);
+            }
+        }
+    }
+}
+
+fn output_index(event: &Value) -> Option<u32> {
+    event
+        .get("output_index")
+        .and_then(|i| i.as_u64())
+        .map(|v| v as u32)
+}
+
+pub(crate) fn drain_sse_line(buffer: &mut String) -> Option<String> {
+    let newline = buffer.find('\n')?;
+    let line = buffer[..newline].to_string();
+    buffer.drain(..=newline);
+    Some(line)
+}
+
+pub(crate) fn event_content_block_stop(index: usize) -> String {
+    format!(
+        "event: content_block_stop\ndata: {{\"type\":\"content_block_stop\",\"index\":{}}}\n\n",
+        index
+    )
+}
+
+fn event_text_block_start(index: usize) -> String {
+    format!(
+        "event: content_block_start\ndata: {{\"type\":\"content_block_start\",\"index\":{},\"content_block\":{{\"type\":\"text\",\"text\":\"\"}}}}\n\n",
+        index
+    )
+}
+
+fn event_thinking_block_start(index: usize) -> String {
+    format!(
+        "event: content_block_start\ndata: {{\"type\":\"content_block_start\",\"index\":{},\"content_block\":{{\"type\":\"thinking\",\"thinking\":\"\"}}}}\n\n",
+        index
+    )
+}
+
+pub(crate) fn event_tool_block_start(index: usize, id: &str, name: &str) -> String {
+    format!(
+        "event: content_block_start\ndata: {{\"type\":\"content_block_start\",\"index\":{},\"content_block\":{{\"type\":\"tool_use\",\"id\":\"{}\",\"name\":\"{}\",\"input\":{{}}}}}}\n\n",
+        index, id, name
+    )
+}
+
+fn event_text_delta(index: usize, text: &str) -> String {
+    format!(
+        "event: content_block_delta\ndata: {{\"type\":\"content_block_delta\",\"index\":{},\"delta\":{{\"type\":\"text_delta\",\"text\":\"{}\"}}}}\n\n",
+        index, text
+    )
+}
+
+fn event_thinking_delta(index: usize, thinking: &str) -> String {
+    format!(
+        "event: content_block_delta\ndata: {{\"type\":\"content_block_delta\",\"index\":{},\"delta\":{{\"type\":\"thinking_delta\",\"thinking\":\"{}\"}}}}\n\n",
+        index, thinking
+    )
+}
+
+pub(crate) fn event_tool_args_delta(index: usize, args: &str) -> String {
+    format!(
+        "event: content_block_delta\ndata: {{\"type\":\"content_block_delta\",\"index\":{},\"delta\":{{\"type\":\"input_json_delta\",\"partial_json\":\"{}\"}}}}\n\n",
+        index, args
+    )
+}
+
+/// Closing usage: the upstream's own figures when it reported them (including
+/// cached prompt tokens), otherwise the streamed output count
+fn event_message_delta(
+    stop_reason: &str,
+    output_tokens: u32,
+    reported: Option<&AnthropicUsage>,
+) -> String {
+    let usage = reported
+        .and_then(|usage| serde_json::to_string(usage).ok())
+        .unwrap_or_else(|| format!("{{\"output_tokens\":{}}}", output_tokens));
+    format!(
+        "event: message_delta\ndata: {{\"type\":\"message_delta\",\"delta\":{{\"stop_reason\":\"{}\",\"stop_sequence\":null}},\"usage\":{}}}\n\n",
+        stop_reason, usage
+    )
+}
+
+pub(crate) fn event_message_stop() -> String {
+    "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n".to_string()
+}
+
+fn sse_event(name: &str, data: &Value) -> String {
+    format!("event: {}\ndata: {}\n\n", name, data)
+}
+
+/// Replay a complete response as the Anthropic SSE event sequence
+pub(crate) fn replay_sse_events(response: &AnthropicResponse) -> Vec<String> {
+    let mut events = vec![sse_event(
+        "message_start",
+        &serde_json::json!({
+            "type": "message_start",
+            "message": {
+                "id": response.id,
+                "type": "message",
+                "role": "assistant",
+                "content": [],
+                "model": response.model,
+                "stop_reason": null,
+                "stop_sequence": null,
+                "usage": { "input_tokens": response.usage.input_tokens, "output_tokens": 0 }
+            }
+        }),
+    )];
+
+    for (index, block) in response.content.iter().enumerate() {
+        let (start, delta) = match block {
+            ResponseContent::ToolUse { id, name, input } => (
+                serde_json::json!({ "type": "tool_use", "id": id, "name": name, "input": {} }),
+                serde_json::json!({ "type": "input_json_delta", "partial_json": input.to_string() }),
+            ),
+            ResponseContent::Text { text } => (
+                serde_json::json!({ "type": "text", "text": "" }),
+                serde_json::json!({ "type": "text_delta", "text": text }),
+            ),
+            _ => continue,
+        };
+        events.push(sse_event(
+            "content_block_start",
+            &serde_json::json!({ "type": "content_block_start", "index": index, "content_block": start }),
+        ));
+        events.push(sse_event(
+            "content_block_delta",
+            &serde_json::json!({ "type": "content_block_delta", "index": index, "delta": delta }),
+        ));
+        events.push(event_content_block_stop(index));
+    }
+
+    events.push(sse_event(
+        "message_delta",
+        &serde_json::json!({
+            "type": "message_delta",
+            "delta": { "stop_reason": response.stop_reason, "stop_sequence": null },
+            "usage": { "output_tokens": response.usage.output_tokens }
+        }),
+    ));
+    events.push(event_message_stop());
+    events
+}
+
+/// Send a complete response as JSON, or replayed as events to a streaming client
+pub(crate) fn replay_response(response: AnthropicResponse, is_streaming: bool) -> Response {
+    if !is_streaming {
+        return Json(response).into_response();
+    }
+    let events = replay_sse_events(&response);
+    sse_response(futures::stream::iter(
+        events.into_iter().map(Ok::<_, Infallible>),
+    ))
+}
+
+#[derive(Debug, Default)]
+struct StreamState {
+    message_started: bool,
+    /// `message_stop` was sent; later finish signals are ignored
+    finished: bool,
+    /// Overrides the default `end_turn` (e.g. a refusal)
+    stop_reason: Option<&'static str>,
+    input_tokens: u32,
+    output_tokens: u32,
+    /// Final usage from the upstream (Responses `response.completed`, or the usage
+    /// chunk of Chat Completions and Completions streams)
+    reported_usage: Option<AnthropicUsage>,
+    /// A finish reason came before the usage; the message ends once it arrives
+    finish_pending: bool,
+    next_block_index: usize,
+    thinking_block_index: Option<usize>,
+    thinking_block_open: bool,
+    text_block_index: Option<usize>,
+    text_block_open: bool,
+    tool_block_indices: HashMap<u32, usize>,
+    tool_blocks_open: HashSet<u32>,
+    tool_call_ids: HashMap<u32, String>,
+    tool_call_names: HashMap<u32, String>,
+    tool_args: HashMap<u32, ToolArgs>,
+}
+
+/// Arguments of one streamed tool call: the text received so far and how much of it
+/// has gone out as `input_json_delta`. Claude Code concatenates the fragments, so each
+/// byte must be sent exactly once, whether the upstream streams deltas, repeats the
+/// whole string when the call finishes, or both.
+#[derive(Debug, Default)]
+struct ToolArgs {
+    received: String,
+    emitted: usize,
+}
+
+impl ToolArgs {
+    fn append(&mut self, delta: &str) {
+        self.received.push_str(delta);
+    }
+
+    /// Reconcile with the complete arguments from an `added`/`done` event. Returns
+    /// false when they contradict what was already sent, which cannot be withdrawn.
+    fn complete(&mut self, arguments: &str) -> bool {
+        if self.received.starts_with(arguments) {
+            return true;
+        }
+        if !arguments.starts_with(&self.received[..self.emitted]) {
+            return false;
+        }
+        self.received = arguments.to_string();
+        true
+    }
+
+    /// Received text not sent yet
+    fn take_unsent(&mut self) -> Option<&str> {
+        if self.emitted == self.received.len() {
+            return None;
+        }
+        let start = std::mem::replace(&mut self.emitted, self.received.len());
+        Some(&self.received[start..])
+    }
+}
+
+impl StreamState {
+    fn new() -> Self {
+        Self {
+            input_tokens: 0,
+            ..Self::default()
+        }
+    }
+
+    fn ensure_message_started(&mut self, msg_id: &str, model: &str) -> Option<String> {
+        if self.message_started {
+            return None;
+        }
+        self.message_started = true;
+        Some(format!(
+            "event: message_start\ndata: {{\"type\":\"message_start\",\"message\":{{\"id\":\"{}\",\"type\":\"message\",\"role\":\"assistant\",\"content\":[],\"model\":\"{}\",\"stop_reason\":null,\"stop_sequence\":null,\"usage\":{{\"input_tokens\":{},\"output_tokens\":0}}}}}}\n\n",
+            msg_id, model, self.input_tokens
+        ))
+    }
+
+    fn ensure_text_block_started(&mut self) -> Option<String> {
+        if self.text_block_index.is_none() {
+            let index = self.next_block_index;
+            self.next_block_index += 1;
+            self.text_block_index = Some(index);
+            self.text_block_open = true;
+            return Some(event_text_block_start(index));
+        }
+        None
+    }
+
+    fn ensure_thinking_block_started(&mut self) -> Option<String> {
+        if self.thinking_block_index.is_none() {
+            let index = self.next_block_index;
+            self.next_block_index += 1;
+            self.thinking_block_index = Some(index);
+            self.thinking_block_open = true;
+            return Some(event_thinking_block_start(index));
+        }
+        None
+    }
+
+    fn close_thinking_block(&mut self) -> Option<String> {
+        if self.thinking_block_open {
+            self.thinking_block_open = false;
+            if let Some(index) = self.thinking_block_index {
+                return Some(event_content_block_stop(index));
+            }
+        }
+        None
+    }
+
+    fn close_text_block(&mut self) -> Option<String> {
+        if self.text_block_open {
+            self.text_block_open = false;
+            if let Some(index) = self.text_block_index {
+                return Some(event_content_block_stop(index));
+            }
+        }
+        None
+    }
+
+    fn close_open_tool_blocks(&mut self) -> Vec<String> {
+        let mut events = Vec::new();
+        let mut open: Vec<u32> = self.tool_blocks_open.iter().copied().collect();
+        open.sort_unstable();
+        for slot in open {
+            events.extend(self.close_tool_block(slot));
+        }
+        events
+    }
+
+    /// Send the rest of the block's arguments, then stop it
+    fn close_tool_block(&mut self, output_index: u32) -> Vec<String> {
+        let mut events = Vec::new();
+        if let Some(delta) = self.flush_tool_args(output_index) {
+            events.push(delta);
+        }
+        if self.tool_blocks_open.remove(&output_index)
+            && let Some(index) = self.tool_block_indices.get(&output_index)
+        {
+            events.push(event_content_block_stop(*index));
+        }
+        events
+    }
+
+    fn append_tool_args(&mut self, output_index: u32, delta: &str) {
+        self.tool_args
+            .entry(output_index)
+            .or_default()
+            .append(delta);
+    }
+
+    fn complete_tool_args(&mut self, output_index: u32, arguments: &str) {
+        if !arguments.is_empty()
+            && !self
+                .tool_args
+                .entry(output_index)
+                .or_default()
+                .complete(arguments)
+        {
+            proxy_log(&format!(
+                "Tool call {} finished with arguments that differ from the streamed ones; keeping the streamed arguments",
+                output_index
+            ));
+        }
+    }
+
+    /// `input_json_delta` with the arguments received but not sent yet, once the
+    /// tool block is open
+    fn flush_tool_args(&mut self, output_index: u32) -> Option<String> {
+        if !self.tool_blocks_open.contains(&output_index) {
+            return None;
+        }
+        let block_index = *self.tool_block_indices.get(&output_index)?;
+        let unsent = self.tool_args.get_mut(&output_index)?.take_unsent()?;
+        Some(event_tool_args_delta(
+            block_index,
+            &escape_json_string(unsent),
+        ))
+    }
+
+    /// Stop every content block still open
+    fn close_open_blocks(&mut self) -> Vec<String> {
+        let mut events = self.close_open_tool_blocks();
+        if let Some(stop) = self.close_text_block() {
+            events.push(stop);
+        }
+        if let Some(stop) = self.close_thinking_block() {
+            events.push(stop);
+        }
+        events
+    }
+
+    fn finish_message(&mut self) -> Vec<String> {
+        self.finished = true;
+        let mut events = self.close_open_blocks();
+        events.push(event_message_delta(
+            self.stop_reason.unwrap_or("end_turn"),
+            self.output_tokens,
+            self.reported_usage.as_ref(),
+        ));
+        events.push(event_message_stop());
+        events
+    }
+
+    fn tool_block_index(&mut self, output_index: u32) -> usize {
+        *self
+            .tool_block_indices
+            .entry(output_index)
+            .or_insert_with(|| {
+                let index = self.next_block_index;
+                self.next_block_index += 1;
+                index
+            })
+    }
+
+    fn capture_tool_metadata(&mut self, output_index: u32, item: &Value) {
+        // Prefer "id" (fc_xxx format) over "call_id" (call_xxx format) for OpenAI Codex compatibility
+        if let Some(id) = item
+            .get("id")
+            .and_then(|v| v.as_str())
+            .or_else(|| item.get("item_id").and_then(|v| v.as_str()))
+            .or_else(|| item.get("call_id").and_then(|v| v.as_str()))
+        {
+            self.tool_call_ids
+                .entry(output_index)
+                .or_insert_with(|| id.to_string());
+        }
+        if let Some(name) = item.get("name").and_then(|v| v.as_str()) {
+            self.tool_call_names
+                .entry(output_index)
+                .or_insert_with(|| name.to_string());
+        }
+    }
+
+    fn ensure_tool_block_open(&mut self, output_index: u32) -> Option<String> {
+        if self.tool_blocks_open.contains(&output_index) {
+            return None;
+        }
+        let (id, name) = match (
+            self.tool_call_ids.get(&output_index),
+            self.tool_call_names.get(&output_index),
+        ) {
+            (Some(id), Some(name)) => (id.to_string(), name.to_string()),
+            _ => return None,
+        };
+        let index = self.tool_block_index(output_index);
+        self.tool_blocks_open.insert(output_index);
+        Some(event_tool_block_start(index, &id, &name))
+    }
+}
+
+/// Simple UUID-like string generator
+pub(crate) fn uuid_simple() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{:x}{:x}", now.as_secs(), now.subsec_nanos())
+}
+
+/// Escape a string for JSON embedding
+pub(crate) fn escape_json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + s.len() / 4);
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                use std::fmt::Write;
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use futures::{StreamExt, stream};
+    use serde_json::json;
+
+    #[test]
+    fn escape_json_string_escapes_control_chars() {
+        let input = "a\"b\\c\n\r\t\u{0001}";
+        let escaped = escape_json_string(input);
+        assert_eq!(escaped, "a\\\"b\\\\c\\n\\r\\t\\u0001");
+    }
+
+    #[tokio::test]
+    async fn chat_stream_content_filter_finishes_with_refusal() {
+        let payload = concat!(
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"content_filter\"}]}\n\n",
+            "data: [DONE]\n\n"
+        );
+        let stream = create_anthropic_stream_from_chat(
+            stream::iter(vec![Ok(Bytes::from(payload))]),
+            "model".to_string(),
+            ChatDialect::OpenAi,
+            false,
+        );
+        let events: Vec<String> = stream.map(|r| r.unwrap()).collect().await;
+
+        assert!(events.iter().any(|e| e.contains("content filter blocked")));
+        assert!(
+            events
+                .iter()
+                .any(|e| e.contains("\"stop_reason\":\"refusal\""))
+        );
+        assert!(events.iter().any(|e| e.contains("message_stop")));
+    }
+
+    #[tokio::test]
+    async fn chat_stream_maps_reasoning_and_finishes_without_done() {
+        let payload = concat!(
+            "data:{\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":null,\"reasoning_content\":\"Hmm\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"Hi\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        );
+        let translate = |include_thinking, tail: &'static str| {
+            create_anthropic_stream_from_chat(
+                stream::iter(vec![Ok(Bytes::from(format!("{}{}", payload, tail)))]),
+                "model".to_string(),
+                ChatDialect::LlamaCpp,
+                include_thinking,
+            )
+            .map(|r| r.unwrap())
+            .collect::<Vec<String>>()
+        };
+
+        let events = translate(true, "").await;
+        let types: Vec<&str> = events
+            .iter()
+            .filter_map(|e| e.strip_prefix("event: ")?.split('\n').next())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "message_start",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "content_block_start",
+                "content_block_delta",
+                "content_block_stop",
+                "message_delta",
+                "message_stop",
+            ]
+        );
+        assert!(events[2].contains("\"thinking\":\"Hmm\""));
+
+        // A trailing [DONE] after the finish reason does not stop the message twice
+        let events = translate(false, "data: [DONE]\n\n").await;
+        assert!(!events.iter().any(|e| e.contains("thinking")));
+        assert_eq!(
+            events.iter().filter(|e| e.contains("message_stop")).count(),
+            1
+        );
+
+        // Without a finish reason or [DONE], the message is still finished
+        let events: Vec<String> = create_anthropic_stream_from_chat(
+            stream::iter(vec![Ok(Bytes::from(
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            ))]),
+            "model".to_string(),
+            ChatDialect::Ollama,
+            false,
+        )
+        .map(|r| r.unwrap())
+        .collect()
+        .await;
+        assert!(events.last().unwrap().contains("message_stop"));
+    }
+
+    #[tokio::test]
+    async fn streamed_usage_chunks_end_up_in_message_delta() {
+        let chat = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":1200,\"completion_tokens\":42,",
+            "\"prompt_tokens_details\":{\"cached_tokens\":1000}}}\n\n",
+            "data: [DONE]\n\n"
+        );
+        let events: Vec<String> = create_anthropic_stream_from_chat(
+            stream::iter(vec![Ok(Bytes::from(chat))]),
+            "model".to_string(),
+            ChatDialect::OpenAi,
+            false,
+        )
+        .map(|r| r.unwrap())
+        .collect()
+        .await;
+        let delta = events.iter().find(|e| e.contains("message_delta")).unwrap();
+        let data: Value = serde_json::from_str(delta.split("data: ").nth(1).unwrap()).unwrap();
+        assert_eq!(data["usage"]["input_tokens"], 200);
+        assert_eq!(data["usage"]["cache_read_input_tokens"], 1000);
+        assert_eq!(data["usage"]["output_tokens"], 42);
+        assert_eq!(
+            events.iter().filter(|e| e.contains("message_stop")).count(),
+            1
+        );
+
+        let completions = concat!(
+            "data: {\"choices\":[{\"text\":\"Hi\",\"finish_reason\":\"stop\"}],",
+            "\"usage\":{\"prompt_tokens\":30,\"completion_tokens\":2}}\n\n",
+        );
+        let events: Vec<String> = create_anthropic_stream_from_completions(
+            stream::iter(vec![Ok(Bytes::from(completions))]),
+            "model".to_string(),
+        )
+        .map(|r| r.unwrap())
+        .collect()
+        .await;
+        let delta = events.iter().find(|e| e.contains("message_delta")).unwrap();
+        assert!(delta.contains("\"input_tokens\":30"), "{}", delta);
+        assert!(delta.contains("\"output_tokens\":2"), "{}", delta);
+
+        // Streaming requests ask for the usage chunk
+        let mut request = base_request(Vec::new());
+        request.stream = Some(true);
+        let body = serde_json::to_value(anthropic_to_chat(&request, "model")).unwrap();
+        assert_eq!(body["stream_options"]["include_usage"], true);
+    }
+
+    #[tokio::test]
+    async fn create_anthropic_stream_emits_text_events() {
+        let payload = concat!(
+            "data: {\"type\":\"response.output_text.delta\",\"delta\":\"Hello\"}\n\n",
+            "data: [DONE]\n\n"
+        );
+        let stream = create_anthropic_stream(
+            stream::iter(vec![Ok(Bytes::from(payload))]),
+            "model".to_string(),
+            false,
+        );
+        let events: Vec<String> = stream.map(|r| r.unwrap()).collect().await;
+
+        assert!(events.iter().any(|e| e.contains("message_start")));
+        assert!(events.iter().any(|e| e.contains("\"type\":\"text_delta\"")));
+        assert!(events.iter().any(|e| e.contains("Hello")));
+        assert!(events.iter().any(|e| e.contains("message_stop")));
+    }
+
+    #[tokio::test]
+    async fn cut_off_streams_close_their_blocks_and_report_an_error() {
+        let payload = concat!(
+            "data: {\"type\":\"response.output_text.delta\",\"delta\":\"Hel\"}\n\n",
+            "data: {\"type\":\"response.output_item.added\",\"output_index\":1,\"item\":{\"type\":\"function_call\",\"call_id\":\"c1\",\"name\":\"Read\"}}\n\n",
+            "data: {\"type\":\"response.function_call_arguments.delta\",\"output_index\":1,\"delta\":\"{\\\"pa\"}\n\n",
+        );
+        let stream = create_anthropic_stream(
+            stream::iter(vec![Ok(Bytes::from(payload))]),
+            "model".to_string(),
+            false,
+        );
+        let events: Vec<String> = stream.map(|r| r.unwrap()).collect().await;
+
+        let starts = events
+            .iter()
+            .filter(|e| e.contains("content_block_start"))
+            .count();
+        let stops = events
+            .iter()
+            .filter(|e| e.contains("content_block_stop"))
+            .count();
+        assert_eq!((starts, stops), (2, 2));
+        assert!(events.last().unwrap().starts_with("event: error"));
+        assert!(!events.iter().any(|e| e.contains("message_stop")));
+
+        // Chat streams that fail mid-way are reported the same way
+        let failure = reqwest::Client::new()
+            .get("http://127.0.0.1:0")
+            .send()
+            .await
+            .unwrap_err();
+        let chunks = vec![
+            Ok(Bytes::from(
+                "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            )),
+            Err(failure),
+        ];
+        let stream = create_anthropic_stream_from_chat(
+            stream::iter(chunks),
+            "model".to_string(),
+            ChatDialect::OpenAi,
+            false,
+        );
+        let events: Vec<String> = stream.map(|r| r.unwrap()).collect().await;
+        assert!(events[events.len() - 2].contains("content_block_stop"));
+        assert!(
+            events
+                .last()
+                .unwrap()
+                .contains("Chat stream from the upstream failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn create_anthropic_stream_emits_tool_events() {
+        let payload = concat!(
+            "data: {\"type\":\"response.output_item.added\",\"output_index\":0,\"item\":{\"type\":\"function_call\",\"call_id\":\"call_1\",\"name\":\"tool\",\"arguments\":\"{\\\"x\\\":1\"}}\n\n",
+            "data: {\"type\":\"response.function_call_arguments.delta\",\"output_index\":0,\"delta\":\"}\"}\n\n",
+            "data: {\"type\":\"response.output_item.done\",\"output_index\":0,\"item\":{\"type\":\"function_call\"}}\n\n",
+            "data: [DONE]\n\n"
+        );
+        let stream = create_anthropic_stream(
+            stream::iter(vec![Ok(Bytes::from(payload))]),
+            "model".to_string(),
+            false,
+        );
+        let events: Vec<String> = stream.map(|r| r.unwrap()).collect().await;
+
+        assert!(events.iter().any(|e| e.contains("\"type\":\"tool_use\"")));
+        assert!(
+            events
+                .iter()
+                .any(|e| e.contains("\"type\":\"input_json_delta\""))
+        );
+        assert!(events.iter().any(|e| e.contains("content_block_stop")));
+    }
+
+    /// The `partial_json` fragments of a translated stream, concatenated, and how
+    /// many tool blocks it opened
+    fn streamed_tool_input(events: &[String]) -> (String, usize) {
+        let mut input = String::new();
+        let mut blocks = 0;
+        for data in events
+            .iter()
+            .flat_map(|e| e.lines())
+            .filter_map(|l| l.strip_prefix("data: "))
+        {
+            let event: Value = serde_json::from_str(data).unwrap();
+            if event.pointer("/content_block/type") == Some(&json!("tool_use")) {
+                blocks += 1;
+            }
+            if let Some(partial) = event
+                .pointer("/delta/partial_json")
+                .and_then(|p| p.as_str())
+            {
+                input.push_str(partial);
+            }
+        }
+        (input, blocks)
+    }
+
+    #[tokio::test]
+    async fn tool_arguments_are_streamed_exactly_once_however_they_are_split() {
+        let arguments = r#"{"command":"ls \"a b\"","description":"列出 ✓"}"#;
+        let boundaries: Vec<usize> = arguments
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([arguments.len()])
+            .collect();
+        let responses_event = |value: Value| format!("data: {}\n\n", value);
+        let added = |initial: &str| {
+            responses_event(json!({
+                "type": "response.output_item.added",
+                "output_index": 0,
+                "item": { "type": "function_call", "call_id": "call_1", "name": "Bash", "arguments": initial },
+            }))
+        };
+        let delta = |text: &str| {
+            responses_event(json!({
+                "type": "response.function_call_arguments.delta", "output_index": 0, "delta": text,
+            }))
+        };
+        let args_done = responses_event(json!({
+            "type": "response.function_call_arguments.done", "output_index": 0, "arguments": arguments,
+        }));
+        let item_done = responses_event(json!({
+            "type": "response.output_item.done",
+            "output_index": 0,
+            "item": { "type": "function_call", "arguments": arguments },
+        }));
+
+        for (a, &first) in boundaries.iter().enumerate() {
+            for &second in &boundaries[a..] {
+                let pieces = [
+                    &arguments[..first],
+                    &arguments[first..second],
+                    &arguments[second..],
+                ];
+                let deltas: String = pieces.iter().map(|p| delta(p)).collect();
+                let patterns = [
+                    // Deltas, then the whole string again when the call finishes
+                    format!("{}{}{}{}", added(""), deltas, args_done, item_done),
+                    // A prefix in `added`, the rest as deltas, stopped before the end
+                    format!("{}{}{}", added(pieces[0]), delta(pieces[1]), item_done),
+                    // Deltas that arrive before the call is announced
+                    format!(
+                        "{}{}{}{}",
+                        delta(pieces[0]),
+                        added(""),
+                        delta(pieces[1]),
+                        args_done
+                    ),
+                    // Only the final string
+                    format!("{}{}", added(""), item_done),
+                ];
+                for payload in patterns {
+                    let events: Vec<String> = create_anthropic_stream(
+                        stream::iter(vec![Ok(Bytes::from(payload + "data: [DONE]\n\n"))]),
+                        "model".to_string(),
+                        false,
+                    )
+                    .map(|r| r.unwrap())
+                    .collect()
+                    .await;
+                    assert_eq!(streamed_tool_input(&events), (arguments.to_string(), 1));
+                }
+
+                let chat_chunk = |call: Value| {
+                    format!(
+                        "data: {}\n\n",
+                        json!({ "choices": [{ "delta": { "tool_calls": [call] } }] })
+                    )
+                };
+                let mut payload = chat_chunk(json!({
+                    "index": 0, "id": "call_1", "function": { "name": "Bash", "arguments": pieces[0] },
+                }));
+                for piece in &pieces[1..] {
+                    payload +=
+                        &chat_chunk(json!({ "index": 0, "function": { "arguments": piece } }));
+                }
+                let events: Vec<String> = create_anthropic_stream_from_chat(
+                    stream::iter(vec![Ok(Bytes::from(payload + "data: [DONE]\n\n"))]),
+                    "model".to_string(),
+                    ChatDialect::OpenAi,
+                    false,
+                )
+                .map(|r| r.unwrap())
+                .collect()
+                .await;
+                assert_eq!(streamed_tool_input(&events), (arguments.to_string(), 1));
+            }
+        }
+    }
+
+    #[test]
+    fn usage_from_sse_chunk_reads_start_and_delta() {
+        let chunk = concat!(
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"output_tokens\":0}}}\n\n",
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":7}}\n\n"
+        );
+        assert_eq!(usage_from_sse_chunk(chunk), (Some(12), Some(7)));
+        assert_eq!(usage_from_sse_chunk("event: ping\n\n"), (None, None));
+    }
+
+    #[tokio::test]
+    async fn shape_sse_stream_batches_within_window() {
+        let chunks: Vec<Result<Bytes, Infallible>> = vec![
+            Ok(Bytes::from("event: a\ndata: {}\n\n")),
+            Ok(Bytes::from("event: b\ndata: {}\n\n")),
+        ];
+        let options = SseOptions {
+            batch: Some(Duration::from_millis(50)),
+            debug: false,
+        };
+        let out: Vec<Bytes> = shape_sse_stream(stream::iter(chunks), options)
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(out.len(), 1);
+        assert_eq!(
+            out[0],
+            Bytes::from("event: a\ndata: {}\n\nevent: b\ndata: {}\n\n")
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Strict tool schemas, and stripping the nulls they force back out of tool input.
+
+use super::*;
+
+/// Keywords OpenAI strict mode rejects; they only narrow what the model may send,
+/// so dropping them keeps every valid call valid
+const STRICT_UNSUPPORTED_KEYWORDS: [&str; 15] = [
+    "$schema",
+    "default",
+    "examples",
+    "minLength",
+    "maxLength",
+    "patternProperties",
+    "unevaluatedProperties",
+    "propertyNames",
+    "minProperties",
+    "maxProperties",
+    "unevaluatedItems",
+    "contains",
+    "minContains",
+    "maxContains",
+    "uniqueItems",
+];
+
+/// Allow `null` in place of a value, for optional properties strict mode forces
+/// into `required`
+fn nullable(mut schema: Value) -> Value {
+    let Some(map) = schema.as_object_mut() else {
+        return schema;
+    };
+    if let Some(Value::Array(values)) = map.get_mut("enum")
+        && !values.contains(&Value::Null)
+    {
+        values.push(Value::Null);
+    }
+    match map.get_mut("type") {
+        Some(Value::String(ty)) => {
+            let ty = std::mem::take(ty);
+            map.insert("type".to_string(), serde_json::json!([ty, "null"]));
+            schema
+        }
+        Some(Value::Array(types)) => {
+            if !types.contains(&Value::from("null")) {
+                types.push(Value::from("null"));
+            }
+            schema
+        }
+        _ => serde_json::json!({ "anyOf": [schema, { "type": "null" }] }),
+    }
+}
+
+fn strict_schema_map(map: &serde_json::Map<String, Value>) -> Option<Value> {
+    map.iter()
+        .map(|(key, schema)| Some((key.clone(), strict_schema(schema)?)))
+        .collect::<Option<serde_json::Map<_, _>>>()
+        .map(Value::Object)
+}
+
+/// Rewrite a JSON schema into the subset OpenAI accepts with `strict: true`.
+///
+/// Every object gets `additionalProperties: false` and lists all of its properties
+/// as required, with the optional ones made nullable. Returns `None` for schemas
+/// that cannot be expressed strictly (free-form objects), which are sent as-is.
+fn strict_schema(schema: &Value) -> Option<Value> {
+    let Value::Object(map) = schema else {
+        return Some(schema.clone());
+    };
+
+    let mut out = serde_json::Map::new();
+    for (key, value) in map {
+        if STRICT_UNSUPPORTED_KEYWORDS.contains(&key.as_str()) {
+            continue;
+        }
+        let value = match (key.as_str(), value) {
+            ("properties" | "$defs" | "definitions", Value::Object(inner)) => {
+                strict_schema_map(inner)?
+            }
+            ("items" | "not", _) => strict_schema(value)?,
+            ("additionalProperties", Value::Object(_)) => return None,
+            ("anyOf" | "allOf" | "oneOf" | "prefixItems", Value::Array(schemas)) => {
+                Value::Array(schemas.iter().map(strict_schema).collect::<Option<_>>()?)
+            }
+            _ => value.clone(),
+        };
+        // Strict mode only knows anyOf
+        let key = if key == "oneOf" {
+            "anyOf"
+        } else {
+            key.as_str()
+        };
+        out.insert(key.to_string(), value);
+    }
+
+    let is_object = match out.get("type") {
+        Some(Value::String(ty)) => ty == "object",
+        Some(Value::Array(types)) => types.contains(&Value::from("object")),
+        _ => false,
+    };
+    if is_object {
+        if out
+            .get("additionalProperties")
+            .is_some_and(|v| v != &Value::Bool(false))
+        {
+            return None;
+        }
+        let Some(Value::Object(properties)) = out.get_mut("properties") else {
+            return None;
+        };
+        let required: HashSet<String> = map
+            .get("required")
+            .and_then(|r| r.as_array())
+            .map(|r| {
+                r.iter()
+                    .filter_map(|v| v.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut names = Vec::with_capacity(properties.len());
+        for (name, property) in properties.iter_mut() {
+            if !required.contains(name) {
+                *property = nullable(std::mem::take(property));
+            }
+            names.push(Value::from(name.clone()));
+        }
+        out.insert("required".to_string(), Value::Array(names));
+        out.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+    Some(Value::Object(out))
+}
+
+/// Mark every tool whose schema can be made strict. Returns whether any was.
+pub(crate) fn make_tools_strict(request: &mut AnthropicRequest) -> bool {
+    let mut any = false;
+    for tool in request.tools.iter_mut().flatten() {
+        let Some(schema) = tool.get("input_schema").and_then(strict_schema) else {
+            continue;
+        };
+        if let Some(tool) = tool.as_object_mut() {
+            tool.insert("input_schema".to_string(), schema);
+            tool.insert("strict".to_string(), Value::Bool(true));
+            any = true;
+        }
+    }
+    any
+}
+
+/// Drop the `null`s strict mode makes the model send for omitted optional arguments,
+/// since Claude Code's tool validation rejects them
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+/// Rewrite one Anthropic SSE event, holding back tool argument deltas until their
+/// block stops so the whole input can be cleaned
+fn strip_null_tool_event(event: &str, pending: &mut HashMap<u64, String>) -> String {
+    let Some(data) = event.lines().find_map(|line| line.strip_prefix("data: ")) else {
+        return event.to_string();
+    };
+    let Ok(value) = serde_json::from_str::<Value>(data) else {
+        return event.to_string();
+    };
+    let Some(index) = value.get("index").and_then(|i| i.as_u64()) else {
+        return event.to_string();
+    };
+
+    match value.get("type").and_then(|t| t.as_str()) {
+        Some("content_block_delta") => {
+            match value
+                .pointer("/delta/partial_json")
+                .and_then(|p| p.as_str())
+            {
+                Some(partial) => {
+                    pending.entry(index).or_default().push_str(partial);
+                    String::new()
+                }
+                None => event.to_string(),
+            }
+        }
+        Some("content_block_stop") => {
+            let Some(args) = pending.remove(&index) else {
+                return event.to_string();
+            };
+            let args = match serde_json::from_str::<Value>(&args) {
+                Ok(mut input) => {
+                    strip_nulls(&mut input);
+                    input.to_string()
+                }
+                Err(_) => args,
+            };
+            event_tool_args_delta(index as usize, &escape_json_string(&args)) + event
+        }
+        _ => event.to_string(),
+    }
+}
+
+/// Clean tool inputs in a translated response, streamed or not
+pub(crate) async fn strip_null_tool_input(response: Response) -> Response {
+    use futures::StreamExt;
+
+    if !response.status().is_success() {
+        return response;
+    }
+    let is_sse = is_sse_response(&response);
+    let (parts, body) = response.into_parts();
+
+    if is_sse {
+        let data = body.into_data_stream();
+        let stream = async_stream::stream! {
+            futures::pin_mut!(data);
+            let mut buffer = String::new();
+            let mut pending = HashMap::new();
+            while let Some(chunk) = data.next().await {
+                let Ok(bytes) = chunk else {
+                    yield chunk;
+                    break;
+                };
+                buffer.push_str(&String::from_utf8_lossy(&bytes));
+                let mut out = String::new();
+                while let Some(end) = buffer.find("\n\n") {
+                    let event: String = buffer.drain(..end + 2).collect();
+                    out.push_str(&strip_null_tool_event(&event, &mut pending));
+                }
+                if !out.is_empty() {
+                    yield Ok(bytes::Bytes::from(out));
+                }
+            }
+            if !buffer.is_empty() {
+                yield Ok(bytes::Bytes::from(buffer));
+            }
+        };
+        return Response::from_parts(parts, Body::from_stream(stream));
+    }
+
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return StatusCode::BAD_GATEWAY.into_response();
+    };
+    let Ok(mut value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    if let Some(blocks) = value.get_mut("content").and_then(|c| c.as_array_mut()) {
+        for block in blocks {
+            if block.get("type").and_then(|t| t.as_str()) == Some("tool_use")
+                && let Some(input) = block.get_mut("input")
+            {
+                strip_nulls(input);
+            }
+        }
+    }
+    let mut parts = parts;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn strict_schema_requires_all_properties_and_nulls_optional_ones() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "file_path": {"type": "string", "minLength": 1},
+                "limit": {"type": "number"},
+                "mode": {"type": "string", "enum": ["a", "b"]},
+                "edits": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {"old": {"type": "string"}},
+                        "required": ["old"]
+                    }
+                }
+            },
+            "required": ["file_path"],
+            "additionalProperties": false
+        });
+
+        assert_eq!(
+            strict_schema(&schema).unwrap(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "file_path": {"type": "string"},
+                    "limit": {"type": ["number", "null"]},
+                    "mode": {"type": ["string", "null"], "enum": ["a", "b", null]},
+                    "edits": {
+                        "type": ["array", "null"],
+                        "items": {
+                            "type": "object",
+                            "properties": {"old": {"type": "string"}},
+                            "required": ["old"],
+                            "additionalProperties": false
+                        }
+                    }
+                },
+                "required": ["edits", "file_path", "limit", "mode"],
+                "additionalProperties": false
+            })
+        );
+
+        // Free-form maps cannot be strict; the tool is left alone
+        let free_form = json!({
+            "type": "object",
+            "properties": {"env": {"type": "object", "additionalProperties": {"type": "string"}}}
+        });
+        assert_eq!(strict_schema(&free_form), None);
+    }
+
+    #[test]
+    fn strict_tool_nulls_are_stripped_from_streamed_input() {
+        let mut pending = HashMap::new();
+        let events = [
+            event_tool_block_start(1, "call_1", "Read"),
+            event_tool_args_delta(1, &escape_json_string("{\"file_path\":\"a.rs\",")),
+            event_tool_args_delta(1, &escape_json_string("\"limit\":null}")),
+            event_content_block_stop(1),
+        ];
+        let out: String = events
+            .iter()
+            .map(|event| strip_null_tool_event(event, &mut pending))
+            .collect();
+
+        assert!(!out.contains("null"));
+        assert_eq!(out.matches("input_json_delta").count(), 1);
+        assert!(out.contains(r#"{\"file_path\":\"a.rs\"}"#));
+        assert!(out.ends_with(&event_content_block_stop(1)));
+    }
+}
@@ -0,0 +1,290 @@
+//! `/v1/messages/count_tokens`: Claude Code asks how large a request is without
+//! sending it. OpenAI-compatible upstreams have no such endpoint, so the count is
+//! estimated locally with the tiktoken encoding of the upstream model's family.
+//! vLLM and llama.cpp servers count the text with the model's own tokenizer on their
+//! `/tokenize` endpoint instead, and Anthropic upstreams are asked directly.
+
+use super::*;
+use std::sync::atomic::Ordering;
+use tiktoken_rs::CoreBPE;
+
+/// Role and separator tokens around each message, as OpenAI's chat format counts them
+const TOKENS_PER_MESSAGE: u32 = 3;
+
+/// An image of about 1.15 megapixels, the size Anthropic scales larger images down to
+const IMAGE_TOKENS: u32 = 1600;
+
+/// Byte-pair encoding used to estimate counts for a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenizerFamily {
+    /// GPT-4o and later OpenAI models
+    O200k,
+    /// Older OpenAI models, and the approximation for every other family
+    Cl100k,
+}
+
+impl TokenizerFamily {
+    pub(crate) fn for_model(model: &str) -> Self {
+        let model = model.to_ascii_lowercase();
+        let name = model.rsplit('/').next().unwrap_or(&model);
+        let o200k = [
+            "gpt-4o", "gpt-4.1", "gpt-4.5", "gpt-5", "o1", "o3", "o4", "codex",
+        ]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+            || name.contains("gpt-oss");
+        if o200k {
+            TokenizerFamily::O200k
+        } else {
+            TokenizerFamily::Cl100k
+        }
+    }
+
+    fn encoding(self) -> &'static CoreBPE {
+        match self {
+            TokenizerFamily::O200k => tiktoken_rs::o200k_base_singleton(),
+            TokenizerFamily::Cl100k => tiktoken_rs::cl100k_base_singleton(),
+        }
+    }
+}
+
+/// The texts of a request that a tokenizer sees (system prompt, messages and tool
+/// definitions), and the tokens counted without one: message framing and images
+fn request_texts(request: &AnthropicRequest) -> (Vec<String>, u32) {
+    let mut texts: Vec<String> = system_prompt_text_opt(request.system.as_ref())
+        .into_iter()
+        .collect();
+    let mut fixed = 0;
+    for message in &request.messages {
+        fixed += TOKENS_PER_MESSAGE;
+        match &message.content {
+            AnthropicContent::Text(text) => texts.push(text.clone()),
+            AnthropicContent::Blocks(blocks) => {
+                for block in blocks {
+                    match block {
+                        ContentBlock::Text { text, .. } => texts.push(text.clone()),
+                        ContentBlock::Image { .. } => fixed += IMAGE_TOKENS,
+                        ContentBlock::ToolUse { name, input, .. } => {
+                            texts.push(name.clone());
+                            texts.push(input.to_string());
+                        }
+                        ContentBlock::ToolResult { content, .. } => {
+                            texts.push(stringify_value(content))
+                        }
+                        ContentBlock::Thinking { thinking } => texts.push(thinking.clone()),
+                        ContentBlock::RedactedThinking { .. } => {}
+                    }
+                }
+            }
+        }
+    }
+    texts.extend(request.tools.iter().flatten().map(Value::to_string));
+    (texts, fixed)
+}
+
+/// Estimated input tokens of a request: system prompt, messages and tool definitions
+pub(crate) fn count_request_tokens(request: &AnthropicRequest, family: TokenizerFamily) -> u32 {
+    let encoding = family.encoding();
+    let (texts, fixed) = request_texts(request);
+    fixed
+        + texts
+            .iter()
+            .map(|text| encoding.encode_ordinary(text).len() as u32)
+            .sum::<u32>()
+}
+
+/// The upstream's `/tokenize` endpoint, for servers that have one and have not
+/// turned out to lack it
+fn tokenize_url(state: &ProxyState) -> Option<String> {
+    if !matches!(
+        state.chat_dialect,
+        ChatDialect::Vllm | ChatDialect::LlamaCpp
+    ) || state.tokenizer_missing.load(Ordering::Relaxed)
+    {
+        return None;
+    }
+    let url = url::Url::parse(&state.chat_completions_url).ok()?;
+    Some(url.join("/tokenize").ok()?.to_string())
+}
+
+/// `/tokenize` body for the server: vLLM takes the model and a prompt, llama.cpp
+/// the content alone
+fn tokenize_body(dialect: ChatDialect, model: &str, text: &str) -> Value {
+    match dialect {
+        ChatDialect::Vllm => serde_json::json!({
+            "model": model,
+            "prompt": text,
+            "add_special_tokens": false,
+        }),
+        _ => serde_json::json!({ "content": text }),
+    }
+}
+
+/// Tokens in a `/tokenize` reply: vLLM's `count`, or the length of the token list
+fn tokenize_count(reply: &Value) -> Option<u32> {
+    reply
+        .get("count")
+        .and_then(lenient::count)
+        .or_else(|| Some(reply.get("tokens")?.as_array()?.len() as u32))
+}
+
+/// Input tokens of a request counted by the upstream's own tokenizer
+async fn count_upstream_tokens(
+    state: &ProxyState,
+    url: &str,
+    auth_header: Option<&str>,
+    model: &str,
+    request: &AnthropicRequest,
+) -> Result<u32> {
+    let (texts, fixed) = request_texts(request);
+    let body = tokenize_body(state.chat_dialect, model, &texts.join("\n"));
+    let response = send_json_request(
+        &state.http,
+        url,
+        &body,
+        auth_header,
+        &state.credentials,
+        &state.headers,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("{}", e))?;
+    let status = response.status();
+    if matches!(
+        status,
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+    ) {
+        state.tokenizer_missing.store(true, Ordering::Relaxed);
+        anyhow::bail!("{} has no tokenizer endpoint ({})", url, status);
+    }
+    if !status.is_success() {
+        anyhow::bail!("{} answered {}", url, status);
+    }
+    let reply: Value = response.json().await?;
+    let count = tokenize_count(&reply).context("no token count in the reply")?;
+    Ok(fixed + count)
+}
+
+#[derive(Debug, Serialize)]
+struct CountTokensResponse {
+    input_tokens: u32,
+}
+
+/// Count a request's tokens for the model it would be sent upstream as
+pub(crate) async fn count_tokens_handler(
+    State(state): State<Arc<ProxyState>>,
+    mut headers: HeaderMap,
+    body: bytes::Bytes,
+) -> Response {
+    let state = route_request(state, &mut headers, &body);
+    if *state.upstream_mode.read().await == UpstreamMode::Anthropic {
+        return passthrough_count_tokens(state, headers, body).await;
+    }
+    let request = match Json::<AnthropicRequest>::from_bytes(&body) {
+        Ok(Json(request)) => request,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let model = select_target_model(&state, &request).0;
+    if let Some(url) = tokenize_url(&state) {
+        let auth_header = extract_auth_header(&headers);
+        match count_upstream_tokens(&state, &url, auth_header.as_deref(), &model, &request).await {
+            Ok(input_tokens) => return Json(CountTokensResponse { input_tokens }).into_response(),
+            Err(e) => proxy_log(&format!(
+                "Token count from {} failed, estimating locally: {:#}",
+                state.chat_dialect.label(),
+                e
+            )),
+        }
+    }
+    let family = TokenizerFamily::for_model(&model);
+    // Encoding a long conversation takes a while; keep it off the async workers
+    match tokio::task::spawn_blocking(move || count_request_tokens(&request, family)).await {
+        Ok(input_tokens) => Json(CountTokensResponse { input_tokens }).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn requests_are_counted_per_model_family() {
+        assert_eq!(
+            TokenizerFamily::for_model("openai/gpt-5.1-codex"),
+            TokenizerFamily::O200k
+        );
+        assert_eq!(
+            TokenizerFamily::for_model("o3-mini"),
+            TokenizerFamily::O200k
+        );
+        assert_eq!(
+            TokenizerFamily::for_model("glm-4.6"),
+            TokenizerFamily::Cl100k
+        );
+
+        let mut request: AnthropicRequest = serde_json::from_value(json!({
+            "model": "gpt-5",
+            "system": "You are a helpful assistant.",
+            "messages": [
+                { "role": "user", "content": "Hello world" },
+                { "role": "assistant", "content": [
+                    { "type": "tool_use", "id": "t1", "name": "Read", "input": { "path": "a.rs" } }
+                ]},
+                { "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "t1", "content": "fn main() {}" }
+                ]}
+            ]
+        }))
+        .unwrap();
+        let plain = count_request_tokens(&request, TokenizerFamily::O200k);
+        // "Hello world" is two tokens in both encodings
+        assert!(
+            plain > 2 + 3 * TOKENS_PER_MESSAGE && plain < 60,
+            "{}",
+            plain
+        );
+
+        request.tools = Some(vec![json!({
+            "name": "Read",
+            "description": "Read a file from disk",
+            "input_schema": { "type": "object", "properties": { "path": { "type": "string" } } }
+        })]);
+        assert!(count_request_tokens(&request, TokenizerFamily::O200k) > plain + 10);
+        assert!(count_request_tokens(&request, TokenizerFamily::Cl100k) > 0);
+    }
+
+    #[test]
+    fn upstream_tokenizers_are_asked_in_their_own_format() {
+        assert_eq!(
+            tokenize_body(ChatDialect::Vllm, "qwen3", "hi"),
+            json!({ "model": "qwen3", "prompt": "hi", "add_special_tokens": false })
+        );
+        assert_eq!(
+            tokenize_body(ChatDialect::LlamaCpp, "qwen3", "hi"),
+            json!({ "content": "hi" })
+        );
+        assert_eq!(
+            tokenize_count(&json!({ "count": 3, "tokens": [1, 2, 3], "max_model_len": 8192 })),
+            Some(3)
+        );
+        assert_eq!(tokenize_count(&json!({ "tokens": [1, 2] })), Some(2));
+        assert_eq!(tokenize_count(&json!({ "error": "no model" })), None);
+
+        // Framing and images are counted the same way either way
+        let request: AnthropicRequest = serde_json::from_value(json!({
+            "model": "qwen3",
+            "system": "Be brief.",
+            "messages": [{ "role": "user", "content": [
+                { "type": "text", "text": "What is this?" },
+                { "type": "image", "source": {
+                    "type": "base64", "media_type": "image/png", "data": "AAAA"
+                } }
+            ]}]
+        }))
+        .unwrap();
+        let (texts, fixed) = request_texts(&request);
+        assert_eq!(texts, ["Be brief.", "What is this?"]);
+        assert_eq!(fixed, TOKENS_PER_MESSAGE + IMAGE_TOKENS);
+    }
+}
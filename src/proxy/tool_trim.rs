@@ -0,0 +1,144 @@
+//! Trimming oversized tool results: with `PROXY_TOOL_RESULT_MAX_KB` set, the text of
+//! a single tool result beyond that size (a whole build log, a huge file) is cut to
+//! its start and end, where the command and the errors usually are, with a marker
+//! saying how much was left out.
+//!
+//! The cut depends only on the text, so the same history is trimmed the same way
+//! every turn and upstream prompt caches keep matching.
+
+use super::*;
+
+/// Share of the limit kept from the start of the text; the rest comes from the end
+const HEAD_SHARE: f64 = 0.4;
+
+/// Room left for the elision marker, so a trimmed text fits the limit and is not
+/// trimmed again
+const MARKER_RESERVE: usize = 96;
+
+/// The limit in bytes, from `PROXY_TOOL_RESULT_MAX_KB`; unset or 0 turns trimming off
+pub fn tool_result_limit(env: &HashMap<String, String>) -> Option<usize> {
+    env.get(ENV_TOOL_RESULT_MAX_KB)
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|kb| *kb > 0)
+        .map(|kb| kb * 1024)
+}
+
+/// `text` cut to at most `limit` bytes around an elision marker, breaking at lines
+/// where one is close; `None` when it already fits
+fn trim_text(text: &str, limit: usize) -> Option<String> {
+    if text.len() <= limit {
+        return None;
+    }
+    let kept = limit.saturating_sub(MARKER_RESERVE);
+    let head_budget = (kept as f64 * HEAD_SHARE) as usize;
+    let mut head_end = text.floor_char_boundary(head_budget);
+    if let Some(newline) = text[..head_end].rfind('\n')
+        && newline >= head_end / 2
+    {
+        head_end = newline + 1;
+    }
+    let mut tail_start = text.ceil_char_boundary(text.len() - (kept - head_budget));
+    if let Some(newline) = text[tail_start..].find('\n')
+        && newline < (text.len() - tail_start) / 2
+    {
+        tail_start += newline + 1;
+    }
+    Some(format!(
+        "{}\n[... {} bytes of tool output trimmed by the proxy ...]\n{}",
+        text[..head_end].trim_end_matches('\n'),
+        tail_start - head_end,
+        &text[tail_start..]
+    ))
+}
+
+/// Trim one tool result's content, a string or a list of blocks; returns the size
+/// of each text trimmed
+fn trim_content(content: &mut Value, limit: usize) -> Vec<usize> {
+    let texts: Vec<&mut Value> = match content {
+        Value::String(_) => vec![content],
+        Value::Array(blocks) => blocks
+            .iter_mut()
+            .filter(|block| block.get("type").and_then(Value::as_str) == Some("text"))
+            .filter_map(|block| block.get_mut("text"))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut trimmed = Vec::new();
+    for text in texts {
+        if let Some(original) = text.as_str()
+            && let Some(cut) = trim_text(original, limit)
+        {
+            trimmed.push(original.len());
+            *text = Value::String(cut);
+        }
+    }
+    trimmed
+}
+
+/// Trim every tool result in the request to `limit` bytes of text. Returns the
+/// original sizes of those trimmed in the newest message, the ones not seen before.
+pub(crate) fn trim_tool_results(request: &mut AnthropicRequest, limit: usize) -> Vec<usize> {
+    let last = request.messages.len().saturating_sub(1);
+    let mut newest = Vec::new();
+    for (index, message) in request.messages.iter_mut().enumerate() {
+        let AnthropicContent::Blocks(blocks) = &mut message.content else {
+            continue;
+        };
+        for block in blocks {
+            if let ContentBlock::ToolResult { content, .. } = block {
+                let trimmed = trim_content(content, limit);
+                if index == last {
+                    newest.extend(trimmed);
+                }
+            }
+        }
+    }
+    newest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn long_tool_results_keep_their_start_and_end() {
+        let log: String = (0..2000).map(|i| format!("línea {}\n", i)).collect();
+        let result = |content: Value| AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: "toolu_1".to_string(),
+                content,
+                is_error: None,
+                cache_control: None,
+            }]),
+        };
+        let mut request = base_request(vec![
+            result(json!(log)),
+            result(json!([
+                { "type": "text", "text": log },
+                { "type": "text", "text": "short" }
+            ])),
+        ]);
+
+        assert_eq!(trim_tool_results(&mut request, 1024), vec![log.len()]);
+        let AnthropicContent::Blocks(blocks) = &request.messages[1].content else {
+            unreachable!()
+        };
+        let ContentBlock::ToolResult { content, .. } = &blocks[0] else {
+            unreachable!()
+        };
+        let text = content[0]["text"].as_str().unwrap();
+        assert!(text.len() <= 1024, "{}", text.len());
+        assert!(text.starts_with("línea 0\nlínea 1\n"));
+        assert!(text.ends_with("línea 1998\nlínea 1999\n"));
+        assert!(text.contains("\n[... "));
+        assert!(text.contains(" bytes of tool output trimmed by the proxy ...]\nlínea "));
+        assert_eq!(content[1]["text"], "short");
+
+        // Trimming is stable, so resent history stays the same
+        let once = serde_json::to_string(&request.messages).unwrap();
+        assert!(trim_tool_results(&mut request, 1024).is_empty());
+        assert_eq!(serde_json::to_string(&request.messages).unwrap(), once);
+    }
+}
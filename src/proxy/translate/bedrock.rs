@@ -0,0 +1,518 @@
+//! AWS Bedrock translation: InvokeModel bodies, the Converse API and the binary
+//! event stream both return.
+
+use super::*;
+
+/// Header carrying comma-separated Anthropic beta feature flags
+const ANTHROPIC_BETA_HEADER: &str = "anthropic-beta";
+
+/// `anthropic_version` value Bedrock expects for Anthropic models
+const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+/// Bedrock requires max_tokens; used when Claude Code omits it
+const BEDROCK_DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Bedrock model IDs used when Claude Code sends an undated tier alias
+const BEDROCK_TIER_MODELS: [(&str, &str); 3] = [
+    ("haiku", "anthropic.claude-haiku-4-5-20251001-v1:0"),
+    ("sonnet", "anthropic.claude-sonnet-4-5-20250929-v1:0"),
+    ("opus", "anthropic.claude-opus-4-1-20250805-v1:0"),
+];
+
+pub(crate) fn is_bedrock_url(url: &str) -> bool {
+    url.contains("bedrock-runtime")
+}
+
+/// Map a Claude Code model name to a Bedrock model ID.
+///
+/// IDs that already look like Bedrock IDs (`anthropic.…`, `us.anthropic.…`,
+/// `meta.llama…`) pass through; dated Claude names get the `anthropic.` prefix and
+/// `-v1:0` suffix; bare tier aliases use [`BEDROCK_TIER_MODELS`].
+pub(crate) fn bedrock_model_id(model: &str) -> String {
+    if model.contains('.') {
+        return model.to_string();
+    }
+    let lower = model.to_ascii_lowercase();
+    let is_dated = lower
+        .rsplit('-')
+        .next()
+        .is_some_and(|tail| tail.len() == 8 && tail.bytes().all(|b| b.is_ascii_digit()));
+    if lower.starts_with("claude-") && is_dated {
+        return format!("anthropic.{}-v1:0", lower);
+    }
+    BEDROCK_TIER_MODELS
+        .iter()
+        .find(|(tier, _)| lower.contains(tier))
+        .map(|(_, id)| id.to_string())
+        .unwrap_or_else(|| model.to_string())
+}
+
+/// Anthropic models use InvokeModel with the native Messages body; everything else
+/// goes through the model-agnostic Converse API.
+pub(crate) fn bedrock_uses_converse(model_id: &str) -> bool {
+    !model_id.contains("anthropic.")
+}
+
+/// Build the Bedrock endpoint URL for a model and action (`invoke`, `converse-stream`, …)
+pub(crate) fn bedrock_endpoint(base: &str, model_id: &str, action: &str) -> String {
+    // Model IDs contain ':' which must be percent-encoded in the path for SigV4
+    let encoded: String = url::form_urlencoded::byte_serialize(model_id.as_bytes()).collect();
+    format!(
+        "{}/model/{}/{}",
+        base.trim_end_matches('/'),
+        encoded,
+        action
+    )
+}
+
+/// Beta features from a profile's `anthropic-beta` header (comma separated)
+pub(crate) fn beta_features(headers: &BTreeMap<String, String>) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(ANTHROPIC_BETA_HEADER))
+        .flat_map(|(_, value)| value.split(','))
+        .map(|feature| feature.trim().to_string())
+        .filter(|feature| !feature.is_empty())
+        .collect()
+}
+
+/// Native Anthropic body for Bedrock InvokeModel
+pub(crate) fn anthropic_to_bedrock_invoke(req: &AnthropicRequest, betas: &[String]) -> Value {
+    let mut body = serde_json::to_value(req).unwrap_or_else(|_| Value::Object(Default::default()));
+    if let Some(obj) = body.as_object_mut() {
+        obj.remove("model");
+        obj.remove("stream");
+        obj.insert(
+            "anthropic_version".to_string(),
+            Value::String(BEDROCK_ANTHROPIC_VERSION.to_string()),
+        );
+        obj.entry("max_tokens")
+            .or_insert_with(|| Value::from(BEDROCK_DEFAULT_MAX_TOKENS));
+        // InvokeModel takes beta flags in the body rather than as a header
+        if !betas.is_empty() {
+            obj.insert("anthropic_beta".to_string(), serde_json::json!(betas));
+        }
+    }
+    body
+}
+
+fn converse_content_block(block: &ContentBlock) -> Option<Value> {
+    match block {
+        ContentBlock::Text { text, .. } => Some(serde_json::json!({ "text": text })),
+        ContentBlock::Image { source } => {
+            let format = source
+                .media_type
+                .strip_prefix("image/")
+                .unwrap_or(&source.media_type);
+            Some(serde_json::json!({
+                "image": { "format": format, "source": { "bytes": source.data } }
+            }))
+        }
+        ContentBlock::ToolUse { id, name, input } => Some(serde_json::json!({
+            "toolUse": { "toolUseId": id, "name": name, "input": input }
+        })),
+        ContentBlock::ToolResult {
+            tool_use_id,
+            content,
+            is_error,
+            ..
+        } => {
+            let mut result = serde_json::json!({
+                "toolUseId": tool_use_id,
+                "content": [{ "text": stringify_value(content) }],
+            });
+            if *is_error == Some(true) {
+                result["status"] = Value::String("error".to_string());
+            }
+            Some(serde_json::json!({ "toolResult": result }))
+        }
+        ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => None,
+    }
+}
+
+/// Converse marks a cache breakpoint with a block of its own after the cached content
+fn converse_cache_point() -> Value {
+    serde_json::json!({ "cachePoint": { "type": "default" } })
+}
+
+fn map_tool_choice_for_converse(value: &Value) -> Option<Value> {
+    let ty = value
+        .get("type")
+        .and_then(|t| t.as_str())
+        .or_else(|| value.as_str())?;
+    match ty {
+        "auto" => Some(serde_json::json!({ "auto": {} })),
+        "any" => Some(serde_json::json!({ "any": {} })),
+        "tool" => {
+            let name = value.get("name")?.as_str()?;
+            Some(serde_json::json!({ "tool": { "name": name } }))
+        }
+        _ => None,
+    }
+}
+
+/// Convert an Anthropic request to a Bedrock Converse request body
+pub(crate) fn anthropic_to_converse(req: &AnthropicRequest) -> Value {
+    let messages: Vec<Value> = req
+        .messages
+        .iter()
+        .map(|msg| {
+            let content: Vec<Value> = match &msg.content {
+                AnthropicContent::Text(text) => vec![serde_json::json!({ "text": text })],
+                AnthropicContent::Blocks(blocks) => blocks
+                    .iter()
+                    .flat_map(|block| {
+                        let point = block.cache_control().map(|_| converse_cache_point());
+                        converse_content_block(block).into_iter().chain(point)
+                    })
+                    .collect(),
+            };
+            serde_json::json!({ "role": msg.role, "content": content })
+        })
+        .collect();
+
+    let mut inference = serde_json::Map::new();
+    inference.insert(
+        "maxTokens".to_string(),
+        Value::from(req.max_tokens.unwrap_or(BEDROCK_DEFAULT_MAX_TOKENS)),
+    );
+    if let Some(temperature) = req.temperature {
+        inference.insert("temperature".to_string(), Value::from(temperature));
+    }
+    if let Some(top_p) = req.top_p {
+        inference.insert("topP".to_string(), Value::from(top_p));
+    }
+    if let Some(stop) = &req.stop_sequences {
+        inference.insert("stopSequences".to_string(), Value::from(stop.clone()));
+    }
+
+    let mut body = serde_json::json!({
+        "messages": messages,
+        "inferenceConfig": inference,
+    });
+
+    if let Some(system) = system_prompt_text_opt(req.system.as_ref()) {
+        body["system"] = serde_json::json!([{ "text": system }]);
+        if system_has_cache_control(req.system.as_ref()) {
+            body["system"]
+                .as_array_mut()
+                .expect("system array")
+                .push(converse_cache_point());
+        }
+    }
+
+    if let Some(tools) = &req.tools {
+        let specs: Vec<Value> = tools
+            .iter()
+            .filter_map(|tool| {
+                let name = tool.get("name")?.as_str()?;
+                let mut spec = serde_json::json!({
+                    "name": name,
+                    "inputSchema": { "json": tool.get("input_schema").cloned().unwrap_or_else(|| serde_json::json!({"type": "object"})) },
+                });
+                if let Some(description) = tool.get("description").and_then(|d| d.as_str()) {
+                    spec["description"] = Value::String(description.to_string());
+                }
+                Some(serde_json::json!({ "toolSpec": spec }))
+            })
+            .collect();
+        if !specs.is_empty() {
+            let mut tool_config = serde_json::json!({ "tools": specs });
+            if let Some(choice) = req
+                .tool_choice
+                .as_ref()
+                .and_then(map_tool_choice_for_converse)
+            {
+                tool_config["toolChoice"] = choice;
+            }
+            body["toolConfig"] = tool_config;
+        }
+    }
+
+    body
+}
+
+fn converse_stop_reason(reason: &str) -> &'static str {
+    match reason {
+        "tool_use" => "tool_use",
+        "max_tokens" => "max_tokens",
+        "stop_sequence" => "stop_sequence",
+        _ => "end_turn",
+    }
+}
+
+/// Convert a Bedrock Converse response to an Anthropic response
+pub(crate) fn converse_to_anthropic(resp: &Value, original_model: &str) -> AnthropicResponse {
+    let mut content = Vec::new();
+    let blocks = resp
+        .pointer("/output/message/content")
+        .and_then(|c| c.as_array())
+        .cloned()
+        .unwrap_or_default();
+    for block in &blocks {
+        if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+            push_text_content(&mut content, text);
+        } else if let Some(tool) = block.get("toolUse") {
+            content.push(ResponseContent::ToolUse {
+                id: tool
+                    .get("toolUseId")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("tool")
+                    .to_string(),
+                name: tool
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                input: tool.get("input").cloned().unwrap_or(Value::Null),
+            });
+        }
+    }
+
+    let tokens = |pointer: &str| resp.pointer(pointer).and_then(lenient::count);
+    let usage = AnthropicUsage {
+        input_tokens: tokens("/usage/inputTokens").unwrap_or(0),
+        output_tokens: tokens("/usage/outputTokens").unwrap_or(0),
+        cache_creation_input_tokens: tokens("/usage/cacheWriteInputTokens"),
+        cache_read_input_tokens: tokens("/usage/cacheReadInputTokens"),
+    };
+
+    let mut anthropic = base_anthropic_response(&uuid_simple(), original_model, content, usage);
+    if let Some(reason) = resp.get("stopReason").and_then(|r| r.as_str()) {
+        anthropic.stop_reason = Some(converse_stop_reason(reason).to_string());
+    }
+    anthropic
+}
+
+/// A decoded frame from the `application/vnd.amazon.eventstream` encoding
+#[derive(Debug)]
+pub(crate) struct EventStreamFrame {
+    pub(crate) headers: HashMap<String, String>,
+    pub(crate) payload: Vec<u8>,
+}
+
+/// Incremental decoder for AWS event-stream framing.
+///
+/// Each frame is: total length (u32), headers length (u32), prelude CRC (u32),
+/// headers, payload, message CRC (u32). CRCs are not verified; TLS already protects
+/// the transport.
+#[derive(Debug, Default)]
+pub(crate) struct EventStreamDecoder {
+    buffer: Vec<u8>,
+}
+
+impl EventStreamDecoder {
+    pub(crate) fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn next_frame(&mut self) -> Option<EventStreamFrame> {
+        if self.buffer.len() < 12 {
+            return None;
+        }
+        let total_len = u32::from_be_bytes(self.buffer[0..4].try_into().ok()?) as usize;
+        let headers_len = u32::from_be_bytes(self.buffer[4..8].try_into().ok()?) as usize;
+        if total_len < 16 + headers_len {
+            // Corrupt frame: drop what we have rather than looping forever
+            self.buffer.clear();
+            return None;
+        }
+        if self.buffer.len() < total_len {
+            return None;
+        }
+        let frame: Vec<u8> = self.buffer.drain(..total_len).collect();
+        let headers = parse_event_stream_headers(&frame[12..12 + headers_len]);
+        let payload = frame[12 + headers_len..total_len - 4].to_vec();
+        Some(EventStreamFrame { headers, payload })
+    }
+}
+
+/// Parse event-stream headers, keeping only string-valued ones
+fn parse_event_stream_headers(mut bytes: &[u8]) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    while let Some((&name_len, rest)) = bytes.split_first() {
+        let name_len = name_len as usize;
+        if rest.len() < name_len + 1 {
+            break;
+        }
+        let name = String::from_utf8_lossy(&rest[..name_len]).to_string();
+        let value_type = rest[name_len];
+        let rest = &rest[name_len + 1..];
+        let (value, consumed) = match value_type {
+            0 | 1 => (None, 0),
+            2 => (None, 1),
+            3 => (None, 2),
+            4 => (None, 4),
+            5 | 8 => (None, 8),
+            9 => (None, 16),
+            6 | 7 => {
+                if rest.len() < 2 {
+                    break;
+                }
+                let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+                if rest.len() < 2 + len {
+                    break;
+                }
+                let value = (value_type == 7)
+                    .then(|| String::from_utf8_lossy(&rest[2..2 + len]).to_string());
+                (value, 2 + len)
+            }
+            _ => break,
+        };
+        if rest.len() < consumed {
+            break;
+        }
+        if let Some(value) = value {
+            headers.insert(name, value);
+        }
+        bytes = &rest[consumed..];
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use futures::{StreamExt, stream};
+    use serde_json::json;
+
+    fn event_stream_frame(event_type: &str, payload: &[u8]) -> Vec<u8> {
+        let mut headers = Vec::new();
+        for (name, value) in [(":event-type", event_type), (":message-type", "event")] {
+            headers.push(name.len() as u8);
+            headers.extend_from_slice(name.as_bytes());
+            headers.push(7);
+            headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            headers.extend_from_slice(value.as_bytes());
+        }
+        let total = 16 + headers.len() + payload.len();
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&(total as u32).to_be_bytes());
+        frame.extend_from_slice(&(headers.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&[0; 4]);
+        frame.extend_from_slice(&headers);
+        frame.extend_from_slice(payload);
+        frame.extend_from_slice(&[0; 4]);
+        frame
+    }
+
+    #[test]
+    fn bedrock_model_id_maps_claude_names() {
+        assert_eq!(
+            bedrock_model_id("claude-sonnet-4-5-20250929"),
+            "anthropic.claude-sonnet-4-5-20250929-v1:0"
+        );
+        assert_eq!(
+            bedrock_model_id("claude-haiku-latest"),
+            "anthropic.claude-haiku-4-5-20251001-v1:0"
+        );
+        assert_eq!(
+            bedrock_model_id("meta.llama3-70b-instruct-v1:0"),
+            "meta.llama3-70b-instruct-v1:0"
+        );
+        assert_eq!(
+            bedrock_endpoint(
+                "https://bedrock-runtime.us-east-1.amazonaws.com",
+                "anthropic.claude-v2:1",
+                "invoke"
+            ),
+            "https://bedrock-runtime.us-east-1.amazonaws.com/model/anthropic.claude-v2%3A1/invoke"
+        );
+    }
+
+    #[test]
+    fn anthropic_to_converse_maps_tool_results() {
+        let mut req = base_request(vec![AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: "tool_1".to_string(),
+                content: json!("boom"),
+                is_error: Some(true),
+                cache_control: None,
+            }]),
+        }]);
+        req.system = Some(SystemPrompt::Text("be brief".to_string()));
+        req.max_tokens = Some(256);
+
+        let body = anthropic_to_converse(&req);
+
+        assert_eq!(body["system"][0]["text"], "be brief");
+        assert_eq!(body["inferenceConfig"]["maxTokens"], 256);
+        let result = &body["messages"][0]["content"][0]["toolResult"];
+        assert_eq!(result["toolUseId"], "tool_1");
+        assert_eq!(result["status"], "error");
+    }
+
+    #[test]
+    fn bedrock_invoke_carries_profile_betas() {
+        let headers = BTreeMap::from([
+            (
+                "Anthropic-Beta".to_string(),
+                "interleaved-thinking-2025-05-14, token-efficient-tools-2025-02-19".to_string(),
+            ),
+            ("X-Team".to_string(), "core".to_string()),
+        ]);
+        let req = base_request(vec![]);
+
+        let body = anthropic_to_bedrock_invoke(&req, &beta_features(&headers));
+        assert_eq!(
+            body["anthropic_beta"],
+            json!([
+                "interleaved-thinking-2025-05-14",
+                "token-efficient-tools-2025-02-19"
+            ])
+        );
+        let body = anthropic_to_bedrock_invoke(&req, &[]);
+        assert!(body.get("anthropic_beta").is_none());
+    }
+
+    #[test]
+    fn event_stream_decoder_handles_split_frames() {
+        let frame = event_stream_frame("chunk", b"{\"a\":1}");
+        let mut decoder = EventStreamDecoder::default();
+        decoder.push(&frame[..10]);
+        assert!(decoder.next_frame().is_none());
+        decoder.push(&frame[10..]);
+
+        let decoded = decoder.next_frame().expect("frame");
+        assert_eq!(decoded.headers.get(":event-type").unwrap(), "chunk");
+        assert_eq!(decoded.payload, b"{\"a\":1}");
+        assert!(decoder.next_frame().is_none());
+    }
+
+    #[tokio::test]
+    async fn converse_stream_emits_text_and_tool_events() {
+        let mut payload = Vec::new();
+        for (event_type, body) in [
+            ("messageStart", r#"{"role":"assistant"}"#),
+            (
+                "contentBlockDelta",
+                r#"{"contentBlockIndex":0,"delta":{"text":"Hi"}}"#,
+            ),
+            (
+                "contentBlockStart",
+                r#"{"contentBlockIndex":1,"start":{"toolUse":{"toolUseId":"t1","name":"read"}}}"#,
+            ),
+            (
+                "contentBlockDelta",
+                r#"{"contentBlockIndex":1,"delta":{"toolUse":{"input":"{\"p\":1}"}}}"#,
+            ),
+            ("contentBlockStop", r#"{"contentBlockIndex":1}"#),
+            ("messageStop", r#"{"stopReason":"tool_use"}"#),
+        ] {
+            payload.extend(event_stream_frame(event_type, body.as_bytes()));
+        }
+        let stream = create_anthropic_stream_from_converse(
+            stream::iter(vec![Ok(Bytes::from(payload))]),
+            "model".to_string(),
+        );
+        let events: Vec<String> = stream.map(|r| r.unwrap()).collect().await;
+
+        assert!(events.iter().any(|e| e.contains("message_start")));
+        assert!(events.iter().any(|e| e.contains("Hi")));
+        assert!(events.iter().any(|e| e.contains("\"type\":\"tool_use\"")));
+        assert!(events.iter().any(|e| e.contains("input_json_delta")));
+        assert!(events.iter().any(|e| e.contains("message_stop")));
+    }
+}
@@ -0,0 +1,301 @@
+//! OpenAI Chat Completions conversion.
+
+use super::*;
+
+/// Convert Anthropic request to OpenAI Chat Completions request
+pub fn anthropic_to_chat(req: &AnthropicRequest, target_model: &str) -> ChatCompletionRequest {
+    let mut messages = Vec::new();
+
+    if let Some(system_text) = system_prompt_text_opt(req.system.as_ref()) {
+        messages.push(ChatMessage {
+            role: "system".to_string(),
+            content: Some(ChatMessageContent::Text(system_text)),
+            tool_calls: None,
+            tool_call_id: None,
+            refusal: None,
+        });
+    }
+
+    for msg in &req.messages {
+        convert_anthropic_message_to_chat(msg, &mut messages);
+    }
+
+    // Convert tools
+    let tools = req.tools.as_ref().and_then(|tools| {
+        let mapped: Vec<ChatTool> = tools
+            .iter()
+            .filter_map(|tool| {
+                let name = tool.get("name")?.as_str()?;
+                let description = tool.get("description").and_then(|d| d.as_str());
+                let input_schema = tool.get("input_schema").cloned();
+
+                Some(ChatTool {
+                    tool_type: "function".to_string(),
+                    function: ChatToolFunction {
+                        name: name.to_string(),
+                        description: description.map(String::from),
+                        parameters: input_schema,
+                        strict: tool.get("strict").and_then(|s| s.as_bool()),
+                    },
+                })
+            })
+            .collect();
+
+        (!mapped.is_empty()).then_some(mapped)
+    });
+
+    // Normalize model name for API (strip reasoning suffix)
+    let api_model = normalize_model_for_api(target_model);
+
+    ChatCompletionRequest {
+        model: api_model.to_string(),
+        messages,
+        max_tokens: req.max_tokens,
+        temperature: req.temperature,
+        top_p: req.top_p,
+        stream: req.stream,
+        tools,
+        tool_choice: req
+            .tool_choice
+            .as_ref()
+            .and_then(map_tool_choice_for_openai),
+        prompt_cache_key: prompt_cache_key(req),
+        stream_options: StreamOptions::for_stream(req.stream),
+    }
+}
+
+fn convert_anthropic_message_to_chat(msg: &AnthropicMessage, out: &mut Vec<ChatMessage>) {
+    match &msg.content {
+        AnthropicContent::Text(text) => out.push(ChatMessage {
+            role: msg.role.clone(),
+            content: Some(ChatMessageContent::Text(text.clone())),
+            tool_calls: None,
+            tool_call_id: None,
+            refusal: None,
+        }),
+        AnthropicContent::Blocks(blocks) => {
+            let mut parts: Vec<ChatContentPart> = Vec::new();
+            // Tool messages carry only text, so images returned by tools follow the
+            // tool messages in a user message
+            let mut tool_images: Vec<ChatContentPart> = Vec::new();
+
+            let flush_message =
+                |out: &mut Vec<ChatMessage>, role: &str, parts: &mut Vec<ChatContentPart>| {
+                    if !parts.is_empty() {
+                        let content = ChatMessageContent::Parts(std::mem::take(parts));
+                        out.push(ChatMessage {
+                            role: role.to_string(),
+                            content: Some(content),
+                            tool_calls: None,
+                            tool_call_id: None,
+                            refusal: None,
+                        });
+                    }
+                };
+
+            for block in blocks {
+                match block {
+                    ContentBlock::Text { text, .. } => {
+                        parts.push(ChatContentPart::Text { text: text.clone() });
+                    }
+                    ContentBlock::Image { source } => {
+                        if msg.role != "assistant" {
+                            let data_url =
+                                format!("data:{};base64,{}", source.media_type, source.data);
+                            parts.push(ChatContentPart::ImageUrl {
+                                image_url: ChatImageUrl { url: data_url },
+                            });
+                        }
+                    }
+                    ContentBlock::ToolUse { id, name, input } => {
+                        flush_message(out, &msg.role, &mut parts);
+                        out.push(ChatMessage {
+                            role: "assistant".to_string(),
+                            content: None,
+                            tool_calls: Some(vec![ChatToolCall {
+                                id: id.clone(),
+                                tool_type: "function".to_string(),
+                                function: ChatToolCallFunction {
+                                    name: name.clone(),
+                                    arguments: serde_json::to_string(input).unwrap_or_default(),
+                                },
+                            }]),
+                            tool_call_id: None,
+                            refusal: None,
+                        });
+                    }
+                    ContentBlock::ToolResult {
+                        tool_use_id,
+                        content,
+                        is_error,
+                        ..
+                    } => {
+                        flush_message(out, &msg.role, &mut parts);
+                        let content_str = tool_result_output(content, *is_error);
+                        let images: Vec<ChatContentPart> = tool_result_parts(content, *is_error)
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|part| match part {
+                                ToolResultPart::Image(url) => Some(ChatContentPart::ImageUrl {
+                                    image_url: ChatImageUrl { url },
+                                }),
+                                ToolResultPart::Text(_) => None,
+                            })
+                            .collect();
+                        if !images.is_empty() {
+                            tool_images.push(ChatContentPart::Text {
+                                text: format!("Images returned by tool call {}:", tool_use_id),
+                            });
+                            tool_images.extend(images);
+                        }
+                        out.push(ChatMessage {
+                            role: "tool".to_string(),
+                            content: Some(ChatMessageContent::Text(content_str)),
+                            tool_calls: None,
+                            tool_call_id: Some(tool_use_id.clone()),
+                            refusal: None,
+                        });
+                    }
+                    ContentBlock::Thinking { .. } => {}
+                    ContentBlock::RedactedThinking { .. } => {}
+                }
+            }
+
+            if !tool_images.is_empty() {
+                tool_images.append(&mut parts);
+                parts = tool_images;
+            }
+            flush_message(out, &msg.role, &mut parts);
+        }
+    }
+}
+
+/// Convert OpenAI Chat Completions response to Anthropic response
+pub fn chat_to_anthropic(resp: &ChatCompletionResponse, original_model: &str) -> AnthropicResponse {
+    let mut content = Vec::new();
+    let mut refused = false;
+
+    if let Some(choice) = resp.choices.first() {
+        if let Some(refusal) = &choice.message.refusal {
+            push_text_content(&mut content, refusal);
+            refused = true;
+        }
+        refused |= choice.finish_reason.as_deref() == Some(CONTENT_FILTER_REASON);
+
+        if let Some(message_content) = &choice.message.content {
+            match message_content {
+                ChatMessageContent::Text(text) => push_text_content(&mut content, text),
+                ChatMessageContent::Parts(parts) => {
+                    for part in parts {
+                        if let ChatContentPart::Text { text } = part {
+                            push_text_content(&mut content, text);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(tool_calls) = &choice.message.tool_calls {
+            for call in tool_calls {
+                push_tool_use(
+                    &mut content,
+                    &call.id,
+                    &call.function.name,
+                    &call.function.arguments,
+                );
+            }
+        }
+    }
+
+    let usage = usage_or_default(resp.usage.as_ref(), |u| {
+        AnthropicUsage::from_prompt_completion(
+            u.prompt_tokens,
+            u.completion_tokens,
+            u.cached_tokens(),
+        )
+    });
+
+    let mut anthropic = base_anthropic_response(&resp.id, original_model, content, usage);
+    if refused {
+        mark_refusal(&mut anthropic);
+    }
+    anthropic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn anthropic_to_chat_maps_system_and_tools() {
+        let req = AnthropicRequest {
+            model: "claude".to_string(),
+            messages: vec![AnthropicMessage {
+                role: "user".to_string(),
+                content: AnthropicContent::Text("hi".to_string()),
+            }],
+            max_tokens: Some(10),
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            stop_sequences: None,
+            stream: None,
+            system: Some(SystemPrompt::Text("sys".to_string())),
+            tools: Some(vec![json!({
+                "name": "tool1",
+                "description": "desc",
+                "input_schema": {"type": "object"}
+            })]),
+            tool_choice: None,
+            thinking: None,
+            metadata: None,
+        };
+
+        let mapped = anthropic_to_chat(&req, "target");
+        assert_eq!(mapped.model, "target");
+        assert_eq!(mapped.messages[0].role, "system");
+        match mapped.messages[0].content.as_ref().unwrap() {
+            ChatMessageContent::Text(text) => assert_eq!(text, "sys"),
+            _ => panic!("expected system text"),
+        }
+        let tools = mapped.tools.expect("tools mapped");
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].function.name, "tool1");
+    }
+
+    #[test]
+    fn chat_to_anthropic_maps_text_and_tool() {
+        let resp = ChatCompletionResponse {
+            id: "chat_1".to_string(),
+            choices: vec![ChatChoice {
+                message: ChatMessage {
+                    role: "assistant".to_string(),
+                    content: Some(ChatMessageContent::Text("hello".to_string())),
+                    tool_calls: Some(vec![ChatToolCall {
+                        id: "call_1".to_string(),
+                        tool_type: "function".to_string(),
+                        function: ChatToolCallFunction {
+                            name: "tool".to_string(),
+                            arguments: "{\"x\":1}".to_string(),
+                        },
+                    }]),
+                    tool_call_id: None,
+                    refusal: None,
+                },
+                finish_reason: Some("tool_calls".to_string()),
+            }],
+            usage: Some(ChatUsage {
+                prompt_tokens: 3,
+                completion_tokens: 5,
+                prompt_tokens_details: None,
+            }),
+        };
+
+        let mapped = chat_to_anthropic(&resp, "orig");
+        assert_eq!(mapped.model, "orig");
+        assert_eq!(mapped.usage.input_tokens, 3);
+        assert_eq!(mapped.usage.output_tokens, 5);
+        assert_eq!(mapped.content.len(), 2);
+    }
+}
@@ -0,0 +1,106 @@
+//! OpenAI legacy Completions conversion; messages are flattened into one prompt.
+
+use super::*;
+
+/// Convert Anthropic request to OpenAI legacy Completions request
+pub fn anthropic_to_completions(req: &AnthropicRequest, target_model: &str) -> CompletionsRequest {
+    let mut prompt = String::new();
+
+    if let Some(system_text) = system_prompt_text_opt(req.system.as_ref()) {
+        prompt.push_str("System: ");
+        prompt.push_str(&system_text);
+        prompt.push_str("\n\n");
+    }
+
+    for msg in &req.messages {
+        let content = flatten_anthropic_message_text(msg);
+        if !content.is_empty() {
+            let role = if msg.role.is_empty() {
+                "User"
+            } else if msg.role == "assistant" {
+                "Assistant"
+            } else {
+                msg.role.as_str()
+            };
+            prompt.push_str(role);
+            prompt.push_str(": ");
+            prompt.push_str(&content);
+            prompt.push_str("\n\n");
+        }
+    }
+
+    if let Some(last) = req.messages.last()
+        && last.role != "assistant"
+    {
+        prompt.push_str("Assistant: ");
+    }
+
+    // Normalize model name for API (strip reasoning suffix)
+    let api_model = normalize_model_for_api(target_model);
+
+    CompletionsRequest {
+        model: api_model.to_string(),
+        prompt,
+        max_tokens: req.max_tokens,
+        temperature: req.temperature,
+        top_p: req.top_p,
+        stop: req.stop_sequences.clone(),
+        stream: req.stream,
+        stream_options: StreamOptions::for_stream(req.stream),
+    }
+}
+
+pub(crate) fn flatten_anthropic_message_text(msg: &AnthropicMessage) -> String {
+    match &msg.content {
+        AnthropicContent::Text(text) => text.clone(),
+        AnthropicContent::Blocks(blocks) => {
+            let mut out = String::new();
+            for block in blocks {
+                match block {
+                    ContentBlock::Text { text, .. } => {
+                        if !out.is_empty() {
+                            out.push('\n');
+                        }
+                        out.push_str(text);
+                    }
+                    ContentBlock::ToolResult {
+                        content, is_error, ..
+                    } => {
+                        let content_str = tool_result_output(content, *is_error);
+                        if !content_str.is_empty() {
+                            if !out.is_empty() {
+                                out.push('\n');
+                            }
+                            out.push_str(&content_str);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            out
+        }
+    }
+}
+
+/// Convert OpenAI Completions response to Anthropic response
+pub fn completions_to_anthropic(
+    resp: &CompletionsResponse,
+    original_model: &str,
+) -> AnthropicResponse {
+    let mut content = Vec::new();
+    let mut refused = false;
+    if let Some(choice) = resp.choices.first() {
+        push_text_content(&mut content, &choice.text);
+        refused = choice.finish_reason.as_deref() == Some(CONTENT_FILTER_REASON);
+    }
+
+    let usage = usage_or_default(resp.usage.as_ref(), |u| {
+        AnthropicUsage::from_prompt_completion(u.prompt_tokens, u.completion_tokens, None)
+    });
+
+    let mut anthropic = base_anthropic_response(&resp.id, original_model, content, usage);
+    if refused {
+        mark_refusal(&mut anthropic);
+    }
+    anthropic
+}
@@ -0,0 +1,498 @@
+//! Conversion between Anthropic requests/responses and each upstream API, plus the
+//! helpers the converters share.
+
+mod bedrock;
+mod chat;
+mod completions;
+mod responses;
+
+pub(crate) use bedrock::*;
+pub use chat::*;
+pub use completions::*;
+pub use responses::*;
+
+use super::*;
+
+fn system_prompt_text(system: &SystemPrompt) -> String {
+    match system {
+        SystemPrompt::Text(text) => text.clone(),
+        SystemPrompt::Blocks(blocks) => blocks
+            .iter()
+            .map(|b| b.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+pub(crate) fn system_prompt_text_opt(system: Option<&SystemPrompt>) -> Option<String> {
+    let text = system.map(system_prompt_text)?;
+    if text.is_empty() { None } else { Some(text) }
+}
+
+pub(crate) fn system_has_cache_control(system: Option<&SystemPrompt>) -> bool {
+    match system {
+        Some(SystemPrompt::Blocks(blocks)) => {
+            blocks.iter().any(|block| block.cache_control.is_some())
+        }
+        _ => false,
+    }
+}
+
+/// Whether Claude Code marked any prompt-caching breakpoint in the request
+pub(crate) fn uses_prompt_cache(req: &AnthropicRequest) -> bool {
+    system_has_cache_control(req.system.as_ref())
+        || req.messages.iter().any(|msg| match &msg.content {
+            AnthropicContent::Blocks(blocks) => {
+                blocks.iter().any(|block| block.cache_control().is_some())
+            }
+            AnthropicContent::Text(_) => false,
+        })
+        || req
+            .tools
+            .iter()
+            .flatten()
+            .any(|tool| tool.get("cache_control").is_some())
+}
+
+/// OpenAI caches prompt prefixes automatically; a `prompt_cache_key` per session and
+/// system prompt keeps a conversation's requests on the same cache
+pub(crate) fn prompt_cache_key(req: &AnthropicRequest) -> Option<String> {
+    use std::hash::{Hash, Hasher};
+    if !uses_prompt_cache(req) {
+        return None;
+    }
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    session_hint(req).hash(&mut hasher);
+    system_prompt_text_opt(req.system.as_ref()).hash(&mut hasher);
+    Some(format!("claude-profiler-{:016x}", hasher.finish()))
+}
+
+pub(crate) fn stringify_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+/// Prefix marking a failed tool execution for upstreams without a structured error flag
+const TOOL_ERROR_PREFIX: &str = "ERROR: ";
+
+/// Stands in for an image an upstream cannot be sent
+pub(crate) const IMAGE_PLACEHOLDER: &str = "[image]";
+
+fn error_marked(output: String, is_error: Option<bool>) -> String {
+    if is_error == Some(true) && !output.starts_with(TOOL_ERROR_PREFIX) {
+        format!("{}{}", TOOL_ERROR_PREFIX, output)
+    } else {
+        output
+    }
+}
+
+/// Text sent upstream for a tool_result, marking failed executions so the model
+/// does not mistake an error message for normal output. Images become placeholders.
+pub(crate) fn tool_result_output(content: &Value, is_error: Option<bool>) -> String {
+    let output = match tool_result_parts(content, None) {
+        Some(parts) => parts
+            .iter()
+            .map(|part| match part {
+                ToolResultPart::Text(text) => text.as_str(),
+                ToolResultPart::Image(_) => IMAGE_PLACEHOLDER,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => stringify_value(content),
+    };
+    error_marked(output, is_error)
+}
+
+/// A piece of a tool_result that returned images
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ToolResultPart {
+    Text(String),
+    /// A data URL, or the image's own URL
+    Image(String),
+}
+
+/// URL of an Anthropic image source
+fn image_source_url(source: &Value) -> Option<String> {
+    match source.get("type")?.as_str()? {
+        "base64" => Some(format!(
+            "data:{};base64,{}",
+            source.get("media_type")?.as_str()?,
+            source.get("data")?.as_str()?
+        )),
+        "url" => source.get("url")?.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+/// The text and images of a tool_result in order, for upstreams that take images;
+/// `None` when it has no images, so it is sent as text
+pub(crate) fn tool_result_parts(
+    content: &Value,
+    is_error: Option<bool>,
+) -> Option<Vec<ToolResultPart>> {
+    let blocks = content.as_array()?;
+    let is_image = |block: &Value| block.get("type").and_then(Value::as_str) == Some("image");
+    if !blocks.iter().any(is_image) {
+        return None;
+    }
+    let mut parts: Vec<ToolResultPart> = blocks
+        .iter()
+        .map(|block| match block.get("type").and_then(Value::as_str) {
+            Some("text") => {
+                ToolResultPart::Text(block.get("text").map(stringify_value).unwrap_or_default())
+            }
+            Some("image") => block
+                .get("source")
+                .and_then(image_source_url)
+                .map(ToolResultPart::Image)
+                .unwrap_or_else(|| ToolResultPart::Text(IMAGE_PLACEHOLDER.to_string())),
+            _ => ToolResultPart::Text(stringify_value(block)),
+        })
+        .collect();
+    if is_error == Some(true) {
+        match parts.first_mut() {
+            Some(ToolResultPart::Text(text)) => {
+                *text = error_marked(std::mem::take(text), is_error)
+            }
+            _ => parts.insert(
+                0,
+                ToolResultPart::Text(TOOL_ERROR_PREFIX.trim_end().to_string()),
+            ),
+        }
+    }
+    Some(parts)
+}
+
+pub(crate) fn response_text_part_for_role(text: &str, role: &str) -> ResponseInputContentPart {
+    // Codex API requires different content types based on role:
+    // - Assistant messages must use `output_text`
+    // - User/developer messages must use `input_text`
+    if role.eq_ignore_ascii_case("assistant") {
+        ResponseInputContentPart::OutputText {
+            text: text.to_string(),
+        }
+    } else {
+        ResponseInputContentPart::InputText {
+            text: text.to_string(),
+        }
+    }
+}
+
+pub(crate) fn map_tool_choice_for_openai(value: &Value) -> Option<Value> {
+    if let Some(s) = value.as_str() {
+        let lower = s.trim().to_ascii_lowercase();
+        return match lower.as_str() {
+            "auto" => Some(Value::String("auto".to_string())),
+            "none" => Some(Value::String("none".to_string())),
+            "required" | "any" => Some(Value::String("required".to_string())),
+            _ => None,
+        };
+    }
+
+    let obj = value.as_object()?;
+    let ty = obj.get("type")?.as_str()?.to_ascii_lowercase();
+    match ty.as_str() {
+        "auto" => Some(Value::String("auto".to_string())),
+        "none" => Some(Value::String("none".to_string())),
+        "any" => Some(Value::String("required".to_string())),
+        "tool" => {
+            let name = obj.get("name")?.as_str()?;
+            Some(serde_json::json!({
+                "type": "function",
+                "function": { "name": name }
+            }))
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn base_anthropic_response(
+    response_id: &str,
+    model: &str,
+    content: Vec<ResponseContent>,
+    usage: AnthropicUsage,
+) -> AnthropicResponse {
+    AnthropicResponse {
+        id: match response_id {
+            "" => format!("msg_{}", uuid_simple()),
+            id => format!("msg_{}", id),
+        },
+        response_type: "message".to_string(),
+        role: "assistant".to_string(),
+        content,
+        model: model.to_string(),
+        stop_reason: Some("end_turn".to_string()),
+        stop_sequence: None,
+        usage,
+    }
+}
+
+/// Anthropic stop reason for refused or content-filtered output
+pub(crate) const REFUSAL_STOP_REASON: &str = "refusal";
+
+/// Shown when an upstream content filter cut a response without any explanation
+pub(crate) const CONTENT_FILTER_NOTICE: &str =
+    "[The upstream provider's content filter blocked this response.]";
+
+/// OpenAI finish/incomplete reason for filtered output
+pub(crate) const CONTENT_FILTER_REASON: &str = "content_filter";
+
+/// End the response as a refusal, making sure Claude Code has some text to show
+pub(crate) fn mark_refusal(response: &mut AnthropicResponse) {
+    if !response
+        .content
+        .iter()
+        .any(|block| matches!(block, ResponseContent::Text { .. }))
+    {
+        response.content.push(ResponseContent::Text {
+            text: CONTENT_FILTER_NOTICE.to_string(),
+        });
+    }
+    response.stop_reason = Some(REFUSAL_STOP_REASON.to_string());
+}
+
+pub(crate) fn usage_or_default<T>(
+    value: Option<T>,
+    map: impl FnOnce(T) -> AnthropicUsage,
+) -> AnthropicUsage {
+    value.map_or_else(AnthropicUsage::default, map)
+}
+
+pub(crate) fn push_text_content(content: &mut Vec<ResponseContent>, text: &str) {
+    if !text.is_empty() {
+        content.push(ResponseContent::Text {
+            text: text.to_string(),
+        });
+    }
+}
+
+pub(crate) fn push_tool_use(
+    content: &mut Vec<ResponseContent>,
+    id: &str,
+    name: &str,
+    arguments: &str,
+) {
+    // Some servers leave out the call id or send no arguments for parameterless tools
+    let id = match id {
+        "" => format!("toolu_{}_{}", uuid_simple(), content.len()),
+        id => id.to_string(),
+    };
+    let input: Value = match arguments.trim() {
+        "" => Value::Object(Default::default()),
+        arguments => {
+            serde_json::from_str(arguments).unwrap_or(Value::String(arguments.to_string()))
+        }
+    };
+    content.push(ResponseContent::ToolUse {
+        id,
+        name: name.to_string(),
+        input,
+    });
+}
+
+// ============================================================================
+// Reasoning Effort Helpers (Codex model suffix parsing)
+// ============================================================================
+
+/// Reasoning effort suffixes in order of specificity (longest first to avoid partial matches)
+const REASONING_SUFFIXES: [&str; 5] = ["-xhigh", "-high", "-medium", "-low", "-none"];
+
+/// Extract reasoning effort from model suffix (e.g., "gpt-5.1-codex-high" → Some("high"))
+pub(crate) fn parse_reasoning_effort(model: &str) -> Option<&'static str> {
+    for suffix in REASONING_SUFFIXES {
+        if model.ends_with(suffix) {
+            return Some(&suffix[1..]); // Strip the leading dash
+        }
+    }
+    None
+}
+
+/// Strip reasoning suffix to get base model name for API call
+/// (e.g., "gpt-5.1-codex-high" → "gpt-5.1-codex")
+pub(crate) fn normalize_model_for_api(model: &str) -> &str {
+    for suffix in REASONING_SUFFIXES {
+        if let Some(base) = model.strip_suffix(suffix) {
+            return base;
+        }
+    }
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn refusals_and_content_filters_end_the_turn() {
+        let resp: ChatCompletionResponse = serde_json::from_value(json!({
+            "id": "chat_1",
+            "choices": [{
+                "message": {"role": "assistant", "content": null, "refusal": "I can't help with that."},
+                "finish_reason": "stop"
+            }]
+        }))
+        .unwrap();
+        let mapped = chat_to_anthropic(&resp, "orig");
+        assert_eq!(mapped.stop_reason.as_deref(), Some("refusal"));
+        assert!(matches!(
+            &mapped.content[..],
+            [ResponseContent::Text { text }] if text == "I can't help with that."
+        ));
+
+        let resp = ResponsesResponse {
+            id: "resp_1".to_string(),
+            model: "gpt".to_string(),
+            output: vec![],
+            usage: None,
+            incomplete_details: Some(json!({"reason": "content_filter"})),
+        };
+        let mapped = responses_to_anthropic(&resp, "orig", false);
+        assert_eq!(mapped.stop_reason.as_deref(), Some("refusal"));
+        assert!(matches!(
+            &mapped.content[..],
+            [ResponseContent::Text { text }] if text == CONTENT_FILTER_NOTICE
+        ));
+    }
+
+    #[test]
+    fn tool_result_errors_are_marked_for_upstreams() {
+        let failed = |is_error| AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Blocks(vec![ContentBlock::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                content: json!("file not found"),
+                is_error,
+                cache_control: None,
+            }]),
+        };
+        let req = base_request(vec![failed(Some(true))]);
+
+        let responses = anthropic_to_responses(&req, "target");
+        match &responses.input[0] {
+            ResponseInputItem::FunctionCallOutput { output, .. } => {
+                assert_eq!(output.text(), "ERROR: file not found")
+            }
+            _ => panic!("expected function_call_output"),
+        }
+
+        let chat = anthropic_to_chat(&req, "target");
+        match chat.messages[0].content.as_ref().unwrap() {
+            ChatMessageContent::Text(text) => assert_eq!(text, "ERROR: file not found"),
+            _ => panic!("expected tool text"),
+        }
+
+        let completions = anthropic_to_completions(&req, "target");
+        assert!(completions.prompt.contains("ERROR: file not found"));
+
+        let ok = anthropic_to_chat(&base_request(vec![failed(None)]), "target");
+        match ok.messages[0].content.as_ref().unwrap() {
+            ChatMessageContent::Text(text) => assert_eq!(text, "file not found"),
+            _ => panic!("expected tool text"),
+        }
+    }
+
+    #[test]
+    fn cache_breakpoints_and_cached_usage_are_mapped() {
+        let mut req: AnthropicRequest = serde_json::from_value(json!({
+            "model": "claude",
+            "system": [{ "type": "text", "text": "You are Claude Code", "cache_control": { "type": "ephemeral" } }],
+            "messages": [{ "role": "user", "content": [{ "type": "text", "text": "hi" }] }],
+            "metadata": { "user_id": "session_1" }
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&req).unwrap()["system"][0]["cache_control"],
+            json!({ "type": "ephemeral" })
+        );
+        let key = anthropic_to_responses(&req, "gpt-5").prompt_cache_key;
+        assert!(key.is_some());
+        assert_eq!(anthropic_to_chat(&req, "gpt-5").prompt_cache_key, key);
+        req.system = None;
+        assert_eq!(anthropic_to_chat(&req, "gpt-5").prompt_cache_key, None);
+
+        let resp: ChatCompletionResponse = serde_json::from_value(json!({
+            "id": "chat_1",
+            "choices": [{ "message": { "role": "assistant", "content": "ok" }, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 1200, "completion_tokens": 5, "prompt_tokens_details": { "cached_tokens": 1024 } }
+        }))
+        .unwrap();
+        let usage = chat_to_anthropic(&resp, "orig").usage;
+        assert_eq!(usage.input_tokens, 176);
+        assert_eq!(usage.cache_read_input_tokens, Some(1024));
+        assert_eq!(usage.total_input_tokens(), 1200);
+
+        let usage = AnthropicUsage::from_openai_usage_value(&json!({
+            "input_tokens": 2000,
+            "input_tokens_details": { "cached_tokens": 0 },
+            "output_tokens": 9
+        }));
+        assert_eq!(
+            (usage.input_tokens, usage.cache_read_input_tokens),
+            (2000, None)
+        );
+        let usage = AnthropicUsage::from_openai_usage_value(&json!({
+            "input_tokens": 3, "cache_read_input_tokens": 500, "cache_creation_input_tokens": 40, "output_tokens": 9
+        }));
+        assert_eq!(usage.total_input_tokens(), 543);
+    }
+
+    #[test]
+    fn tool_result_images_are_sent_as_images() {
+        let screenshot = AnthropicMessage {
+            role: "user".to_string(),
+            content: AnthropicContent::Blocks(vec![
+                ContentBlock::ToolResult {
+                    tool_use_id: "call_1".to_string(),
+                    content: json!([
+                        { "type": "text", "text": "Screenshot taken" },
+                        { "type": "image", "source": {
+                            "type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="
+                        } }
+                    ]),
+                    is_error: None,
+                    cache_control: None,
+                },
+                ContentBlock::Text {
+                    text: "What do you see?".to_string(),
+                    cache_control: None,
+                },
+            ]),
+        };
+        let req = base_request(vec![screenshot]);
+        let data_url = "data:image/png;base64,iVBORw0KGgo=";
+
+        let responses = anthropic_to_responses(&req, "target");
+        let ResponseInputItem::FunctionCallOutput { output, .. } = &responses.input[0] else {
+            panic!("expected function_call_output");
+        };
+        assert_eq!(
+            serde_json::to_value(output).unwrap(),
+            json!([
+                { "type": "input_text", "text": "Screenshot taken" },
+                { "type": "input_image", "image_url": { "url": data_url } }
+            ])
+        );
+
+        // Tool messages are text only; the image follows them in a user message
+        let chat = serde_json::to_value(anthropic_to_chat(&req, "target")).unwrap();
+        assert_eq!(chat["messages"][0]["role"], "tool");
+        assert_eq!(chat["messages"][0]["content"], "Screenshot taken\n[image]");
+        assert_eq!(chat["messages"][1]["role"], "user");
+        assert_eq!(
+            chat["messages"][1]["content"][1]["image_url"]["url"],
+            data_url
+        );
+        assert_eq!(
+            chat["messages"][1]["content"][2]["text"],
+            "What do you see?"
+        );
+
+        // Upstreams without images get a placeholder instead of the encoded data
+        let completions = anthropic_to_completions(&req, "target");
+        assert!(completions.prompt.contains("Screenshot taken\n[image]"));
+        assert!(!completions.prompt.contains("iVBORw0KGgo="));
+    }
+}
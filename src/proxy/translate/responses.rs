@@ -0,0 +1,506 @@
+//! OpenAI Responses API conversion, including the input fixes the Codex backend needs.
+
+use super::*;
+
+fn normalize_call_id_for_codex(call_id: &str, call_id_map: &mut HashMap<String, String>) -> String {
+    if let Some(existing) = call_id_map.get(call_id) {
+        return existing.clone();
+    }
+    let mapped = if call_id.starts_with("fc_") {
+        call_id.to_string()
+    } else {
+        format!("fc_{}", call_id)
+    };
+    call_id_map.insert(call_id.to_string(), mapped.clone());
+    mapped
+}
+
+pub(crate) fn normalize_responses_input_for_codex(input: &mut [ResponseInputItem]) {
+    let mut call_id_map = HashMap::new();
+    for item in input.iter_mut() {
+        match item {
+            ResponseInputItem::Message { role, content } => {
+                let normalized_role = role.trim().to_ascii_lowercase();
+                if !normalized_role.is_empty() {
+                    *role = normalized_role;
+                }
+                let is_assistant = role == "assistant";
+                for part in content.iter_mut() {
+                    match part {
+                        ResponseInputContentPart::InputText { text } if is_assistant => {
+                            *part = ResponseInputContentPart::OutputText { text: text.clone() };
+                        }
+                        ResponseInputContentPart::OutputText { text } if !is_assistant => {
+                            *part = ResponseInputContentPart::InputText { text: text.clone() };
+                        }
+                        _ => {}
+                    }
+                }
+                if is_assistant {
+                    content.retain(|part| {
+                        !matches!(part, ResponseInputContentPart::InputImage { .. })
+                    });
+                }
+            }
+            ResponseInputItem::FunctionCall { id, call_id, .. } => {
+                let mapped = normalize_call_id_for_codex(call_id, &mut call_id_map);
+                *id = Some(mapped.clone());
+                *call_id = mapped;
+            }
+            ResponseInputItem::FunctionCallOutput { call_id, .. } => {
+                let mapped = normalize_call_id_for_codex(call_id, &mut call_id_map);
+                *call_id = mapped;
+            }
+        }
+    }
+}
+
+fn tool_output_to_message(call_id: &str, output: &ResponseToolOutput) -> ResponseInputItem {
+    let mut text = output.text();
+    if text.len() > 16000 {
+        text.truncate(16000);
+        text.push_str("\n...[truncated]");
+    }
+    ResponseInputItem::Message {
+        role: "assistant".to_string(),
+        content: vec![ResponseInputContentPart::OutputText {
+            text: format!("[Previous tool result; call_id={}]: {}", call_id, text),
+        }],
+    }
+}
+
+pub(crate) fn normalize_orphaned_tool_outputs_for_codex(input: &mut Vec<ResponseInputItem>) {
+    let mut call_ids = HashSet::new();
+    for item in input.iter() {
+        if let ResponseInputItem::FunctionCall { call_id, .. } = item {
+            call_ids.insert(call_id.clone());
+        }
+    }
+
+    let mut normalized = Vec::with_capacity(input.len());
+    for item in input.drain(..) {
+        match item {
+            ResponseInputItem::FunctionCallOutput { call_id, output } => {
+                if call_ids.contains(&call_id) {
+                    normalized.push(ResponseInputItem::FunctionCallOutput { call_id, output });
+                } else {
+                    normalized.push(tool_output_to_message(&call_id, &output));
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    *input = normalized;
+}
+
+/// Convert Anthropic request to OpenAI Responses request
+pub fn anthropic_to_responses(req: &AnthropicRequest, target_model: &str) -> ResponsesRequest {
+    let mut input = Vec::new();
+
+    // Convert messages
+    for msg in &req.messages {
+        input.extend(convert_anthropic_message(msg));
+    }
+
+    // Add system prompt as instructions if present (for non-Codex backends)
+    // Note: For Codex backend, this gets overridden in handle_responses_request
+    let instructions = system_prompt_text_opt(req.system.as_ref());
+
+    // Convert tools
+    let tools = req.tools.as_ref().and_then(|tools| {
+        let mapped: Vec<ResponseTool> = tools
+            .iter()
+            .filter_map(|tool| {
+                let name = tool.get("name")?.as_str()?;
+                let description = tool.get("description").and_then(|d| d.as_str());
+                let input_schema = tool.get("input_schema").cloned();
+
+                Some(ResponseTool {
+                    tool_type: "function".to_string(),
+                    name: name.to_string(),
+                    description: description.map(String::from),
+                    parameters: input_schema,
+                    strict: tool.get("strict").and_then(|s| s.as_bool()),
+                })
+            })
+            .collect();
+
+        (!mapped.is_empty()).then_some(mapped)
+    });
+
+    // Determine reasoning effort: model suffix takes precedence, then thinking config
+    let reasoning = if let Some(effort) = parse_reasoning_effort(target_model) {
+        // Model suffix specifies reasoning effort (e.g., gpt-5.1-codex-high)
+        Some(ResponseReasoning {
+            effort: Some(effort.to_string()),
+            summary: None,
+        })
+    } else {
+        // Fall back to thinking config mapping
+        match &req.thinking {
+            Some(ThinkingConfig::Enabled { budget_tokens }) => {
+                let effort = match budget_tokens {
+                    Some(budget) if *budget >= 4096 => "high",
+                    Some(budget) if *budget >= 1024 => "medium",
+                    Some(_) => "low",
+                    None => "medium",
+                };
+                Some(ResponseReasoning {
+                    effort: Some(effort.to_string()),
+                    summary: None,
+                })
+            }
+            _ => None,
+        }
+    };
+
+    // Normalize model name for API (strip reasoning suffix)
+    let api_model = normalize_model_for_api(target_model);
+
+    ResponsesRequest {
+        model: api_model.to_string(),
+        input,
+        instructions,
+        store: None,
+        max_output_tokens: req.max_tokens,
+        temperature: req.temperature,
+        top_p: req.top_p,
+        stream: req.stream,
+        tools,
+        tool_choice: req
+            .tool_choice
+            .as_ref()
+            .and_then(map_tool_choice_for_openai),
+        reasoning,
+        text: None,
+        include: None,
+        previous_response_id: None,
+        prompt_cache_key: prompt_cache_key(req),
+    }
+}
+
+/// Convert a single Anthropic message to OpenAI Responses input items
+fn convert_anthropic_message(msg: &AnthropicMessage) -> Vec<ResponseInputItem> {
+    match &msg.content {
+        AnthropicContent::Text(text) => vec![ResponseInputItem::Message {
+            role: msg.role.clone(),
+            content: vec![response_text_part_for_role(text, &msg.role)],
+        }],
+        AnthropicContent::Blocks(blocks) => {
+            let mut items = Vec::new();
+            let mut content_parts = Vec::new();
+
+            let flush_message =
+                |items: &mut Vec<ResponseInputItem>,
+                 content_parts: &mut Vec<ResponseInputContentPart>| {
+                    if !content_parts.is_empty() {
+                        let parts = std::mem::take(content_parts);
+                        items.push(ResponseInputItem::Message {
+                            role: msg.role.clone(),
+                            content: parts,
+                        });
+                    }
+                };
+
+            for block in blocks {
+                match block {
+                    ContentBlock::Text { text, .. } => {
+                        content_parts.push(response_text_part_for_role(text, &msg.role));
+                    }
+                    ContentBlock::Image { source } => {
+                        let data_url = format!("data:{};base64,{}", source.media_type, source.data);
+                        if msg.role != "assistant" {
+                            content_parts.push(ResponseInputContentPart::InputImage {
+                                image_url: ResponseImageUrl { url: data_url },
+                            });
+                        }
+                    }
+                    ContentBlock::ToolUse { id, name, input } => {
+                        flush_message(&mut items, &mut content_parts);
+                        items.push(ResponseInputItem::FunctionCall {
+                            id: None,
+                            call_id: id.clone(),
+                            name: name.clone(),
+                            arguments: serde_json::to_string(input).unwrap_or_default(),
+                        });
+                    }
+                    ContentBlock::ToolResult {
+                        tool_use_id,
+                        content,
+                        is_error,
+                        ..
+                    } => {
+                        flush_message(&mut items, &mut content_parts);
+                        let output = match tool_result_parts(content, *is_error) {
+                            Some(parts) => ResponseToolOutput::Parts(
+                                parts
+                                    .into_iter()
+                                    .map(|part| match part {
+                                        ToolResultPart::Text(text) => {
+                                            ResponseInputContentPart::InputText { text }
+                                        }
+                                        ToolResultPart::Image(url) => {
+                                            ResponseInputContentPart::InputImage {
+                                                image_url: ResponseImageUrl { url },
+                                            }
+                                        }
+                                    })
+                                    .collect(),
+                            ),
+                            None => {
+                                ResponseToolOutput::Text(tool_result_output(content, *is_error))
+                            }
+                        };
+                        items.push(ResponseInputItem::FunctionCallOutput {
+                            call_id: tool_use_id.clone(),
+                            output,
+                        });
+                    }
+                    ContentBlock::Thinking { .. } => {
+                        // Skip thinking blocks
+                    }
+                    ContentBlock::RedactedThinking { .. } => {
+                        // Skip redacted thinking blocks
+                    }
+                }
+            }
+
+            flush_message(&mut items, &mut content_parts);
+
+            items
+        }
+    }
+}
+
+/// Convert OpenAI Responses response to Anthropic response
+pub fn responses_to_anthropic(
+    resp: &ResponsesResponse,
+    original_model: &str,
+    include_thinking: bool,
+) -> AnthropicResponse {
+    let mut content = Vec::new();
+    let mut refused = resp
+        .incomplete_details
+        .as_ref()
+        .and_then(|details| details.get("reason"))
+        .and_then(|reason| reason.as_str())
+        == Some(CONTENT_FILTER_REASON);
+
+    for item in &resp.output {
+        let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+
+        if item_type == "message" {
+            let role = item.get("role").and_then(|r| r.as_str()).unwrap_or("");
+            if role != "assistant" {
+                continue;
+            }
+            if let Some(parts) = item.get("content").and_then(|c| c.as_array()) {
+                for part in parts {
+                    match part.get("type").and_then(|t| t.as_str()) {
+                        Some("output_text") => {
+                            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                                push_text_content(&mut content, text);
+                            }
+                        }
+                        Some("refusal") => {
+                            if let Some(text) = part.get("refusal").and_then(|t| t.as_str()) {
+                                push_text_content(&mut content, text);
+                            }
+                            refused = true;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        } else if item_type == "function_call" {
+            let name = item.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            let call_id = item
+                .get("call_id")
+                .and_then(|c| c.as_str())
+                .or_else(|| item.get("id").and_then(|c| c.as_str()))
+                .unwrap_or("call");
+            let arguments = item.get("arguments").and_then(|a| a.as_str()).unwrap_or("");
+            push_tool_use(&mut content, call_id, name, arguments);
+        } else if item_type == "reasoning"
+            && include_thinking
+            && let Some(thinking) = extract_reasoning_text(item)
+            && !thinking.is_empty()
+        {
+            content.push(ResponseContent::Thinking {
+                thinking,
+                signature: None,
+            });
+        }
+    }
+
+    let usage = usage_or_default(resp.usage.as_ref(), AnthropicUsage::from_openai_usage_value);
+
+    let mut anthropic = base_anthropic_response(&resp.id, original_model, content, usage);
+    if refused {
+        mark_refusal(&mut anthropic);
+    }
+    anthropic
+}
+
+fn extract_reasoning_text(item: &Value) -> Option<String> {
+    if let Some(parts) = item.get("content").and_then(|c| c.as_array()) {
+        let mut combined = String::new();
+        for part in parts {
+            let part_type = part.get("type").and_then(|t| t.as_str()).unwrap_or("");
+            if part_type == "reasoning_text"
+                && let Some(text) = part.get("text").and_then(|t| t.as_str())
+            {
+                combined.push_str(text);
+            }
+        }
+        if !combined.is_empty() {
+            return Some(combined);
+        }
+    }
+
+    if let Some(summary) = item.get("summary").and_then(|s| s.as_str())
+        && !summary.is_empty()
+    {
+        return Some(summary.to_string());
+    }
+
+    // Responses summaries are a list of `summary_text` parts, one per paragraph
+    let parts: Vec<&str> = item
+        .get("summary")
+        .and_then(|s| s.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+        .filter(|text| !text.is_empty())
+        .collect();
+    if !parts.is_empty() {
+        return Some(parts.join("\n\n"));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn anthropic_to_responses_maps_system_and_tools() {
+        let req = AnthropicRequest {
+            model: "claude".to_string(),
+            messages: vec![AnthropicMessage {
+                role: "user".to_string(),
+                content: AnthropicContent::Text("hi".to_string()),
+            }],
+            max_tokens: Some(10),
+            temperature: Some(0.5),
+            top_p: Some(0.9),
+            top_k: None,
+            stop_sequences: None,
+            stream: Some(false),
+            system: Some(SystemPrompt::Blocks(vec![
+                SystemBlock {
+                    block_type: "text".to_string(),
+                    text: "sys1".to_string(),
+                    cache_control: None,
+                },
+                SystemBlock {
+                    block_type: "text".to_string(),
+                    text: "sys2".to_string(),
+                    cache_control: None,
+                },
+            ])),
+            tools: Some(vec![json!({
+                "name": "tool1",
+                "description": "desc",
+                "input_schema": {"type": "object"}
+            })]),
+            tool_choice: Some(json!("auto")),
+            thinking: Some(ThinkingConfig::Enabled {
+                budget_tokens: Some(1500),
+            }),
+            metadata: None,
+        };
+
+        let mapped = anthropic_to_responses(&req, "target");
+        assert_eq!(mapped.model, "target");
+        assert_eq!(mapped.instructions.as_deref(), Some("sys1\nsys2"));
+        assert_eq!(mapped.max_output_tokens, Some(10));
+        assert_eq!(mapped.temperature, Some(0.5));
+        assert_eq!(mapped.top_p, Some(0.9));
+
+        let tools = mapped.tools.expect("tools mapped");
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].tool_type, "function");
+        assert_eq!(tools[0].name, "tool1");
+        assert_eq!(tools[0].description.as_deref(), Some("desc"));
+        assert!(tools[0].parameters.is_some());
+
+        let reasoning = mapped.reasoning.expect("reasoning mapped");
+        assert_eq!(reasoning.effort.as_deref(), Some("medium"));
+    }
+
+    #[test]
+    fn responses_to_anthropic_maps_text_and_tool() {
+        let resp = ResponsesResponse {
+            id: "resp_1".to_string(),
+            model: "gpt".to_string(),
+            output: vec![
+                json!({
+                    "type": "message",
+                    "role": "assistant",
+                    "content": [{"type": "output_text", "text": "hello"}]
+                }),
+                json!({
+                    "type": "function_call",
+                    "name": "tool",
+                    "call_id": "call_1",
+                    "arguments": "{\"x\":1}"
+                }),
+            ],
+            usage: Some(json!({"input_tokens": 3, "output_tokens": 5})),
+            incomplete_details: None,
+        };
+
+        let mapped = responses_to_anthropic(&resp, "orig", false);
+        assert_eq!(mapped.model, "orig");
+        assert_eq!(mapped.usage.input_tokens, 3);
+        assert_eq!(mapped.usage.output_tokens, 5);
+        assert_eq!(mapped.content.len(), 2);
+
+        match &mapped.content[0] {
+            ResponseContent::Text { text } => assert_eq!(text, "hello"),
+            _ => panic!("expected text content"),
+        }
+
+        match &mapped.content[1] {
+            ResponseContent::ToolUse { id, name, input } => {
+                assert_eq!(id, "call_1");
+                assert_eq!(name, "tool");
+                assert!(input.is_object());
+            }
+            _ => panic!("expected tool_use content"),
+        }
+    }
+
+    #[test]
+    fn extract_reasoning_text_prefers_content_then_summary() {
+        let item = json!({
+            "content": [{"type": "reasoning_text", "text": "thinking"}]
+        });
+        assert_eq!(extract_reasoning_text(&item).as_deref(), Some("thinking"));
+
+        let item = json!({"summary": "short"});
+        assert_eq!(extract_reasoning_text(&item).as_deref(), Some("short"));
+
+        let item = json!({"summary": [
+            {"type": "summary_text", "text": "First"},
+            {"type": "summary_text", "text": "Second"}
+        ]});
+        assert_eq!(
+            extract_reasoning_text(&item).as_deref(),
+            Some("First\n\nSecond")
+        );
+    }
+}
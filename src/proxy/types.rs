@@ -0,0 +1,600 @@
+//! Wire types for the Anthropic API and the OpenAI Responses, Chat Completions and
+//! legacy Completions APIs.
+
+use super::*;
+
+// ============================================================================
+// Anthropic API Types
+// ============================================================================
+
+/// Anthropic Messages API request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicRequest {
+    pub model: String,
+    pub messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemPrompt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+    /// Claude Code puts its session id in `user_id`; read for response chaining only
+    #[serde(default, skip_serializing)]
+    pub metadata: Option<Value>,
+}
+
+/// Anthropic extended thinking configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ThinkingConfig {
+    #[serde(rename = "enabled")]
+    Enabled { budget_tokens: Option<u32> },
+    #[serde(rename = "disabled")]
+    Disabled,
+}
+
+/// System prompt can be a string or array of content blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SystemPrompt {
+    Text(String),
+    Blocks(Vec<SystemBlock>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemBlock {
+    #[serde(rename = "type")]
+    pub block_type: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<Value>,
+}
+
+/// Anthropic message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicMessage {
+    pub role: String,
+    pub content: AnthropicContent,
+}
+
+/// Content can be a string or array of content blocks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AnthropicContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+/// Content block types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ContentBlock {
+    #[serde(rename = "text")]
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<Value>,
+    },
+    #[serde(rename = "image")]
+    Image { source: ImageSource },
+    #[serde(rename = "tool_use")]
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    #[serde(rename = "tool_result")]
+    ToolResult {
+        tool_use_id: String,
+        content: Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        is_error: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<Value>,
+    },
+    #[serde(rename = "thinking")]
+    Thinking { thinking: String },
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
+}
+
+impl ContentBlock {
+    /// Anthropic prompt-caching breakpoint set on the block, if any
+    pub(crate) fn cache_control(&self) -> Option<&Value> {
+        match self {
+            ContentBlock::Text { cache_control, .. }
+            | ContentBlock::ToolResult { cache_control, .. } => cache_control.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub media_type: String,
+    pub data: String,
+}
+
+/// Anthropic Messages API response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicResponse {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub response_type: String,
+    pub role: String,
+    pub content: Vec<ResponseContent>,
+    pub model: String,
+    pub stop_reason: Option<String>,
+    pub stop_sequence: Option<String>,
+    pub usage: AnthropicUsage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponseContent {
+    #[serde(rename = "text")]
+    Text { text: String },
+    #[serde(rename = "tool_use")]
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    #[serde(rename = "thinking")]
+    Thinking {
+        thinking: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signature: Option<String>,
+    },
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
+}
+
+/// Token usage. As in Anthropic's API, `input_tokens` excludes the prompt tokens
+/// read from or written to the cache.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnthropicUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u32>,
+}
+
+impl AnthropicUsage {
+    /// OpenAI counts cached tokens inside the prompt total
+    pub(crate) fn from_prompt_completion(
+        prompt_tokens: u32,
+        completion_tokens: u32,
+        cached_tokens: Option<u32>,
+    ) -> Self {
+        let cached = cached_tokens.filter(|&cached| cached > 0);
+        Self {
+            input_tokens: prompt_tokens.saturating_sub(cached.unwrap_or(0)),
+            output_tokens: completion_tokens,
+            cache_creation_input_tokens: None,
+            cache_read_input_tokens: cached,
+        }
+    }
+
+    /// Read a Responses API usage object (`input_tokens_details.cached_tokens`) or an
+    /// Anthropic one (`cache_*_input_tokens`)
+    pub(crate) fn from_openai_usage_value(value: &Value) -> Self {
+        let tokens = |pointer: &str| value.pointer(pointer).and_then(lenient::count);
+        let input_tokens = tokens("/input_tokens").unwrap_or(0);
+        let output_tokens = tokens("/output_tokens").unwrap_or(0);
+        if let Some(cached) = tokens("/input_tokens_details/cached_tokens") {
+            return Self::from_prompt_completion(input_tokens, output_tokens, Some(cached));
+        }
+        Self {
+            input_tokens,
+            output_tokens,
+            cache_creation_input_tokens: tokens("/cache_creation_input_tokens"),
+            cache_read_input_tokens: tokens("/cache_read_input_tokens"),
+        }
+    }
+
+    /// Every prompt token, cached or not
+    pub(crate) fn total_input_tokens(&self) -> u32 {
+        self.input_tokens
+            + self.cache_creation_input_tokens.unwrap_or(0)
+            + self.cache_read_input_tokens.unwrap_or(0)
+    }
+}
+
+// ============================================================================
+// OpenAI Responses API Types
+// ============================================================================
+
+/// OpenAI Responses request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponsesRequest {
+    pub model: String,
+    pub input: Vec<ResponseInputItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// ChatGPT Codex backend requires `store: false` (and it is safe for normal OpenAI Responses API usage).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ResponseTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ResponseReasoning>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<ResponseText>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    /// Continue from a stored response; `input` then holds only the new turn
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_response_id: Option<String>,
+    /// Groups requests sharing a prompt prefix so OpenAI routes them to the same cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_cache_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseReasoning {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseText {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbosity: Option<String>,
+}
+
+/// Responses input item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponseInputItem {
+    #[serde(rename = "message")]
+    Message {
+        role: String,
+        content: Vec<ResponseInputContentPart>,
+    },
+    #[serde(rename = "function_call")]
+    FunctionCall {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        #[serde(rename = "call_id")]
+        call_id: String,
+        name: String,
+        arguments: String,
+    },
+    #[serde(rename = "function_call_output")]
+    FunctionCallOutput {
+        #[serde(rename = "call_id")]
+        call_id: String,
+        output: ResponseToolOutput,
+    },
+}
+
+/// A function call's output: text, or parts when the tool returned images
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ResponseToolOutput {
+    Text(String),
+    Parts(Vec<ResponseInputContentPart>),
+}
+
+impl ResponseToolOutput {
+    /// The output as text, images marked by a placeholder
+    pub(crate) fn text(&self) -> String {
+        match self {
+            ResponseToolOutput::Text(text) => text.clone(),
+            ResponseToolOutput::Parts(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    ResponseInputContentPart::InputText { text }
+                    | ResponseInputContentPart::OutputText { text } => text.as_str(),
+                    ResponseInputContentPart::InputImage { .. } => IMAGE_PLACEHOLDER,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Responses input content part
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponseInputContentPart {
+    #[serde(rename = "input_text")]
+    InputText { text: String },
+    #[serde(rename = "output_text")]
+    OutputText { text: String },
+    #[serde(rename = "input_image")]
+    InputImage { image_url: ResponseImageUrl },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseImageUrl {
+    pub url: String,
+}
+
+/// Tool format for OpenAI Responses API
+/// Note: Codex API expects the flat structure with name/description/parameters at top level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseTool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+/// OpenAI Responses response (partial); off-spec fields are read leniently
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponsesResponse {
+    #[serde(default, deserialize_with = "lenient::string")]
+    pub id: String,
+    #[serde(default, deserialize_with = "lenient::string")]
+    pub model: String,
+    #[serde(default, deserialize_with = "lenient::list")]
+    pub output: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Value>,
+    /// Set when the response stopped early (`max_output_tokens`, `content_filter`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incomplete_details: Option<Value>,
+}
+
+// ============================================================================
+// OpenAI Chat Completions API Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ChatTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_cache_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+/// Asks Chat Completions and Completions streams for a final usage chunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+impl StreamOptions {
+    /// `include_usage` for streaming requests, so streamed replies report real token
+    /// counts
+    pub(crate) fn for_stream(stream: Option<bool>) -> Option<Self> {
+        (stream == Some(true)).then_some(Self {
+            include_usage: true,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChatMessageContent {
+    Text(String),
+    Parts(Vec<ChatContentPart>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ChatContentPart {
+    #[serde(rename = "text")]
+    Text { text: String },
+    #[serde(rename = "image_url")]
+    ImageUrl { image_url: ChatImageUrl },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatImageUrl {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    #[serde(default, deserialize_with = "lenient::string")]
+    pub role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<ChatMessageContent>,
+    #[serde(
+        default,
+        deserialize_with = "lenient::opt_list",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tool_calls: Option<Vec<ChatToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Refusal text returned in place of content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refusal: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatTool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub function: ChatToolFunction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatToolFunction {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatToolCall {
+    #[serde(default, deserialize_with = "lenient::string")]
+    pub id: String,
+    #[serde(rename = "type", default, deserialize_with = "lenient::string")]
+    pub tool_type: String,
+    pub function: ChatToolCallFunction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatToolCallFunction {
+    #[serde(default, deserialize_with = "lenient::string")]
+    pub name: String,
+    #[serde(default, deserialize_with = "lenient::arguments")]
+    pub arguments: String,
+}
+
+/// Chat Completions response; off-spec fields are read leniently
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatCompletionResponse {
+    #[serde(default, deserialize_with = "lenient::string")]
+    pub id: String,
+    #[serde(default, deserialize_with = "lenient::list")]
+    pub choices: Vec<ChatChoice>,
+    #[serde(default, deserialize_with = "lenient::opt")]
+    pub usage: Option<ChatUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatChoice {
+    pub message: ChatMessage,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatUsage {
+    #[serde(default, deserialize_with = "lenient::u32")]
+    pub prompt_tokens: u32,
+    #[serde(default, deserialize_with = "lenient::u32")]
+    pub completion_tokens: u32,
+    #[serde(default, deserialize_with = "lenient::opt")]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptTokensDetails {
+    #[serde(default, deserialize_with = "lenient::opt_u32")]
+    pub cached_tokens: Option<u32>,
+}
+
+impl ChatUsage {
+    pub(crate) fn cached_tokens(&self) -> Option<u32> {
+        self.prompt_tokens_details.as_ref()?.cached_tokens
+    }
+}
+
+// ============================================================================
+// OpenAI Completions API Types (legacy)
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionsRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+/// Completions response; off-spec fields are read leniently
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionsResponse {
+    #[serde(default, deserialize_with = "lenient::string")]
+    pub id: String,
+    #[serde(default, deserialize_with = "lenient::list")]
+    pub choices: Vec<CompletionChoice>,
+    #[serde(default, deserialize_with = "lenient::opt")]
+    pub usage: Option<CompletionUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionChoice {
+    #[serde(default, deserialize_with = "lenient::string")]
+    pub text: String,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionUsage {
+    #[serde(default, deserialize_with = "lenient::u32")]
+    pub prompt_tokens: u32,
+    #[serde(default, deserialize_with = "lenient::u32")]
+    pub completion_tokens: u32,
+}
+
+/// Minimal request with the given messages, for tests
+#[cfg(test)]
+pub(crate) fn base_request(messages: Vec<AnthropicMessage>) -> AnthropicRequest {
+    AnthropicRequest {
+        model: "claude".to_string(),
+        messages,
+        max_tokens: None,
+        temperature: None,
+        top_p: None,
+        top_k: None,
+        stop_sequences: None,
+        stream: None,
+        system: None,
+        tools: None,
+        tool_choice: None,
+        thinking: None,
+        metadata: None,
+    }
+}
@@ -0,0 +1,747 @@
+//! Sending requests upstream, with fallback, and the per-API request handlers.
+
+use super::*;
+
+/// AWS service name used when signing upstream requests with SigV4
+const AWS_SIGV4_SERVICE: &str = "bedrock";
+
+#[derive(Debug)]
+pub(crate) struct UpstreamError {
+    status: StatusCode,
+    body: String,
+}
+
+impl std::fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.status, self.body)
+    }
+}
+
+/// Start of the error body for requests that never reached the upstream
+const CONNECT_ERROR_PREFIX: &str = "Failed to connect to upstream";
+
+impl UpstreamError {
+    /// A request that could not be sent
+    pub(crate) fn connect(err: impl std::fmt::Display) -> Self {
+        Self {
+            status: StatusCode::BAD_GATEWAY,
+            body: format!("{}: {}", CONNECT_ERROR_PREFIX, err),
+        }
+    }
+
+    /// Whether the request never reached the upstream, so sending it again is safe
+    pub(crate) fn is_connect_error(&self) -> bool {
+        self.status == StatusCode::BAD_GATEWAY && self.body.starts_with(CONNECT_ERROR_PREFIX)
+    }
+
+    /// Pass the upstream failure to Claude Code, keeping a copy in the proxy log.
+    /// Failures the user can fix are replaced by an Anthropic error saying how.
+    pub(crate) fn into_response(self) -> Response {
+        let status = self.status.as_u16();
+        let message = provider_errors::describe(status, &self.body);
+        proxy_log(&format!("Upstream error {}", message));
+        if provider_errors::diagnose(status, &self.body).is_none() {
+            return (self.status, self.body).into_response();
+        }
+        let error_type = match self.status {
+            StatusCode::UNAUTHORIZED => "authentication_error",
+            StatusCode::FORBIDDEN => "permission_error",
+            StatusCode::NOT_FOUND => "not_found_error",
+            StatusCode::TOO_MANY_REQUESTS => "rate_limit_error",
+            _ => "invalid_request_error",
+        };
+        (
+            self.status,
+            Json(serde_json::json!({
+                "type": "error",
+                "error": { "type": error_type, "message": message }
+            })),
+        )
+            .into_response()
+    }
+}
+
+fn should_fallback(err: &UpstreamError) -> bool {
+    if matches!(
+        err.status,
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+    ) {
+        return true;
+    }
+    if err.status == StatusCode::BAD_REQUEST {
+        let body = err.body.to_ascii_lowercase();
+        return body.contains("not found")
+            || body.contains("unknown endpoint")
+            || body.contains("unsupported")
+            || body.contains("unrecognized");
+    }
+    false
+}
+
+/// The `[profiles.model_map]` entry for a requested model; the longest matching
+/// pattern wins
+fn mapped_model<'a>(map: &'a BTreeMap<String, String>, model: &str) -> Option<&'a String> {
+    map.iter()
+        .filter(|(pattern, _)| wildcard_match(pattern, model))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, mapped)| mapped)
+}
+
+/// Upstream model for a request, with the routing rule that chose it when that
+/// is not simply the requested model
+pub(crate) fn select_target_model(
+    state: &ProxyState,
+    request: &AnthropicRequest,
+) -> (String, Option<&'static str>) {
+    if let Some(mapped) = mapped_model(&state.model_map, &request.model) {
+        return (mapped.clone(), Some("model map"));
+    }
+    if let Some(class) = auxiliary_class(request)
+        && let Some(aux) = &state.auxiliary_model
+        && !state.aux_promotion.is_promoted(class)
+    {
+        return (aux.clone(), Some("auxiliary"));
+    }
+    match &state.model_override {
+        Some(model) => (model.clone(), Some("override")),
+        None => (request.model.clone(), None),
+    }
+}
+
+pub(crate) fn extract_auth_header(headers: &HeaderMap) -> Option<String> {
+    if let Some(value) = headers.get(header::AUTHORIZATION)
+        && let Ok(text) = value.to_str()
+        && !text.trim().is_empty()
+    {
+        return Some(text.to_string());
+    }
+
+    if let Some(value) = headers.get("x-api-key")
+        && let Ok(text) = value.to_str()
+    {
+        let trimmed = text.trim();
+        if !trimmed.is_empty() {
+            if trimmed.to_ascii_lowercase().starts_with("bearer ") {
+                return Some(trimmed.to_string());
+            }
+            return Some(format!("Bearer {}", trimmed));
+        }
+    }
+
+    None
+}
+
+pub(crate) fn is_chatgpt_codex_backend(url: &str) -> bool {
+    // Minimal heuristic: the Codex backend lives under chatgpt.com/backend-api/codex.
+    url.contains("://chatgpt.com/backend-api/codex/")
+}
+
+fn strip_bearer_prefix(auth: &str) -> Option<&str> {
+    let trimmed = auth.trim();
+    if trimmed.len() < 7 {
+        return None;
+    }
+    if trimmed[..6].eq_ignore_ascii_case("bearer") && trimmed.as_bytes()[6].is_ascii_whitespace() {
+        return Some(trimmed[7..].trim());
+    }
+    None
+}
+
+pub(crate) async fn send_json_request<T: Serialize>(
+    http: &UpstreamHttp,
+    url: &str,
+    body: &T,
+    auth_header: Option<&str>,
+    credentials: &Credentials,
+    extra_headers: &BTreeMap<String, String>,
+) -> Result<reqwest::Response, UpstreamError> {
+    let payload = serde_json::to_vec(body).map_err(|e| UpstreamError {
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+        body: format!("Failed to serialize upstream request: {}", e),
+    })?;
+
+    let mut builder = http.post(url).header("Content-Type", "application/json");
+    for (name, value) in &client_forwarded_headers() {
+        if !extra_headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case(name.as_str()))
+        {
+            builder = builder.header(name, value);
+        }
+    }
+    for (name, value) in extra_headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    match credentials {
+        Credentials::AwsSigV4(aws) => {
+            let parsed = url::Url::parse(url).map_err(|e| UpstreamError {
+                status: StatusCode::BAD_GATEWAY,
+                body: format!("Invalid upstream URL {}: {}", url, e),
+            })?;
+            for (name, value) in aws.sign(AWS_SIGV4_SERVICE, "POST", &parsed, &payload) {
+                builder = builder.header(name, value);
+            }
+        }
+        Credentials::Bearer(token) if auth_header.is_none() => {
+            builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        _ => {
+            if let Some(auth) = auth_header {
+                builder = builder.header(header::AUTHORIZATION, auth);
+            }
+        }
+    }
+
+    // ChatGPT Codex backend requires extra headers (Codex CLI parity).
+    if is_chatgpt_codex_backend(url) {
+        builder = builder
+            .header("accept", "text/event-stream")
+            .header("OpenAI-Beta", "responses=experimental")
+            .header("originator", "codex_cli_rs");
+
+        if let Some(auth) = auth_header
+            && let Some(token) = strip_bearer_prefix(auth)
+            && let Some(account_id) = openai_oauth::decode_chatgpt_account_id(token)
+        {
+            builder = builder.header("chatgpt-account-id", account_id);
+        }
+    }
+
+    note_upstream_request(url, &payload);
+    let response = http
+        .send(builder.body(payload))
+        .await
+        .map_err(UpstreamError::connect)?;
+    note_upstream_headers(response.headers());
+    Ok(tap_upstream_response(response))
+}
+
+/// Send a request upstream, paced by the Codex usage limits when they apply and
+/// retried when it fails for a transient reason
+async fn send_upstream<T: Serialize>(
+    state: &ProxyState,
+    url: &str,
+    body: &T,
+    auth_header: Option<&str>,
+) -> Result<reqwest::Response, UpstreamError> {
+    let quota = state
+        .codex_quota
+        .as_deref()
+        .filter(|_| is_chatgpt_codex_backend(url));
+    if let Some(quota) = quota {
+        quota.pace().await;
+    }
+    let response = send_with_retries(&state.retry, url, || {
+        send_with_refresh(state, url, body, auth_header)
+    })
+    .await?;
+    if let Some(quota) = quota {
+        quota.observe(response.headers());
+    }
+    Ok(response)
+}
+
+/// Send a request upstream. For OAuth profiles a 401 triggers a token refresh and
+/// one retry, and the refreshed token is used for the rest of the session.
+async fn send_with_refresh<T: Serialize>(
+    state: &ProxyState,
+    url: &str,
+    body: &T,
+    auth_header: Option<&str>,
+) -> Result<reqwest::Response, UpstreamError> {
+    let refreshed = state.refreshed_token.lock().await.clone();
+    let auth_header = refreshed
+        .map(|token| format!("Bearer {}", token))
+        .or_else(|| auth_header.map(str::to_string));
+
+    let response = send_json_request(
+        &state.http,
+        url,
+        body,
+        auth_header.as_deref(),
+        &state.credentials,
+        &state.headers,
+    )
+    .await?;
+    let provider = match state.auth_provider {
+        AuthProvider::OpenAiOAuth => "OpenAI",
+        AuthProvider::AnthropicOAuth => "Anthropic",
+        _ => return Ok(response),
+    };
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+
+    let stale = auth_header
+        .as_deref()
+        .and_then(strip_bearer_prefix)
+        .unwrap_or_default();
+    let token = {
+        // Hold the lock so concurrent 401s share a single refresh
+        let mut refreshed = state.refreshed_token.lock().await;
+        match refreshed.as_deref() {
+            Some(current) if current != stale => current.to_string(),
+            _ => match state
+                .token_refresher
+                .refresh(state.auth_provider, stale)
+                .await
+            {
+                Ok(token) => {
+                    proxy_log(&format!(
+                        "Upstream returned 401; refreshed {} OAuth token",
+                        provider
+                    ));
+                    *refreshed = Some(token.clone());
+                    token
+                }
+                Err(e) => {
+                    proxy_log(&format!(
+                        "Upstream returned 401; OAuth refresh failed: {}",
+                        e
+                    ));
+                    return Ok(response);
+                }
+            },
+        }
+    };
+
+    send_json_request(
+        &state.http,
+        url,
+        body,
+        Some(&format!("Bearer {}", token)),
+        &state.credentials,
+        &state.headers,
+    )
+    .await
+}
+
+async fn ensure_success(response: reqwest::Response) -> Result<reqwest::Response, UpstreamError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(UpstreamError { status, body })
+}
+
+/// Read a successful upstream body. The response types tolerate off-spec fields, so
+/// only a body that is not JSON at all, or has the wrong shape entirely, is an error.
+async fn parse_json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, UpstreamError> {
+    let text = response.text().await.map_err(|e| UpstreamError {
+        status: StatusCode::BAD_GATEWAY,
+        body: format!("Failed to read upstream response: {}", e),
+    })?;
+    parse_upstream_body(&text)
+}
+
+fn parse_upstream_body<T: DeserializeOwned>(text: &str) -> Result<T, UpstreamError> {
+    let value: Value = serde_json::from_str(text).map_err(|e| UpstreamError {
+        status: StatusCode::BAD_GATEWAY,
+        body: format!(
+            "Upstream returned a body that is not JSON ({}): {}",
+            e,
+            text.chars().take(200).collect::<String>()
+        ),
+    })?;
+    parse_upstream_value(value)
+}
+
+fn parse_upstream_value<T: DeserializeOwned>(value: Value) -> Result<T, UpstreamError> {
+    serde_json::from_value(value).map_err(|e| UpstreamError {
+        status: StatusCode::BAD_GATEWAY,
+        body: format!("Unexpected upstream response: {}", e),
+    })
+}
+
+pub(crate) fn sse_response(
+    stream: impl Stream<Item = Result<String, Infallible>> + Send + 'static,
+) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::CONNECTION, "keep-alive")
+        // Ask any intermediary (nginx, corporate proxies) not to buffer the stream
+        .header("X-Accel-Buffering", "no")
+        .body(Body::from_stream(stream))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+pub(crate) fn result_to_response(result: Result<Response, UpstreamError>) -> Response {
+    result.unwrap_or_else(|err| err.into_response())
+}
+
+async fn attempt_upstream(
+    state: &Arc<ProxyState>,
+    mode: UpstreamMode,
+    result: Result<Response, UpstreamError>,
+) -> Result<Response, UpstreamError> {
+    match result {
+        Ok(resp) => {
+            *state.upstream_mode.write().await = mode;
+            Ok(resp)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+async fn attempt_or_fallback(
+    state: &Arc<ProxyState>,
+    mode: UpstreamMode,
+    result: Result<Response, UpstreamError>,
+) -> Result<Option<Response>, Response> {
+    match attempt_upstream(state, mode, result).await {
+        Ok(resp) => Ok(Some(resp)),
+        Err(err) if should_fallback(&err) => Ok(None),
+        Err(err) => Err(err.into_response()),
+    }
+}
+
+fn handle_attempt_result(result: Result<Option<Response>, Response>) -> ControlFlow<Response, ()> {
+    match result {
+        Ok(Some(resp)) => ControlFlow::Break(resp),
+        Ok(None) => ControlFlow::Continue(()),
+        Err(resp) => ControlFlow::Break(resp),
+    }
+}
+
+pub(crate) async fn handle_responses_request(
+    state: Arc<ProxyState>,
+    mut request: ResponsesRequest,
+    session: Option<String>,
+    original_model: String,
+    include_thinking: bool,
+    is_streaming: bool,
+    auth_header: Option<String>,
+) -> Result<Response, UpstreamError> {
+    let is_codex_backend = is_chatgpt_codex_backend(&state.responses_url);
+    if is_codex_backend {
+        request.store = Some(false);
+        request.stream = Some(true);
+        request.include = Some(vec!["reasoning.encrypted_content".to_string()]);
+
+        // Fetch official Codex instructions from GitHub (required by Codex API)
+        match get_codex_instructions(&request.model).await {
+            Ok(instructions) => {
+                request.instructions = Some(instructions);
+            }
+            Err(e) => {
+                return Err(UpstreamError {
+                    status: StatusCode::INTERNAL_SERVER_ERROR,
+                    body: format!("Failed to fetch Codex instructions: {}", e),
+                });
+            }
+        }
+
+        // Add Claude Code bridge prompt as the developer message
+        let bridge_message = ResponseInputItem::Message {
+            role: "developer".to_string(),
+            content: vec![ResponseInputContentPart::InputText {
+                text: CLAUDE_CODE_BRIDGE.to_string(),
+            }],
+        };
+        request.input.insert(0, bridge_message);
+
+        normalize_responses_input_for_codex(&mut request.input);
+        normalize_orphaned_tool_outputs_for_codex(&mut request.input);
+
+        state
+            .reasoning_summary
+            .unwrap_or_default()
+            .apply(&mut request.reasoning);
+
+        let text = request.text.get_or_insert(ResponseText { verbosity: None });
+        if text.verbosity.is_none() {
+            text.verbosity = Some("medium".to_string());
+        }
+
+        // Remove unsupported parameters for Codex API
+        // The Codex API only supports: model, store, stream, instructions, input, tools, reasoning, text, include
+        request.max_output_tokens = None;
+        request.temperature = None;
+        request.top_p = None;
+        request.tool_choice = None;
+    }
+
+    // Other Responses upstreams get a summary only for requests that reason anyway
+    if !is_codex_backend
+        && request.reasoning.is_some()
+        && let Some(summary) = state.reasoning_summary
+    {
+        summary.apply(&mut request.reasoning);
+    }
+
+    // The Codex backend stores nothing (`store: false`), so it cannot chain
+    let chains = state.response_chains.clone().filter(|_| !is_codex_backend);
+    let mut chain = None;
+    if let Some(chains) = &chains {
+        let key = conversation_key(session.as_deref(), &request);
+        let full_input = request.input.clone();
+        request.store = Some(true);
+        if let Some(point) = chains.continue_from(key, &full_input) {
+            request.previous_response_id = Some(point.response_id);
+            request.input = full_input[point.new_items_from..].to_vec();
+        }
+        chain = Some((chains.clone(), key, full_input));
+    }
+
+    let response = send_upstream(
+        &state,
+        &state.responses_url,
+        &request,
+        auth_header.as_deref(),
+    )
+    .await?;
+
+    let response = match ensure_success(response).await {
+        // The stored response may have expired or been evicted upstream
+        Err(err) if request.previous_response_id.is_some() && err.status.is_client_error() => {
+            let (chains, key, full_input) = chain.as_ref().expect("chained request");
+            proxy_log(&format!(
+                "previous_response_id rejected ({}); resending full history",
+                err.status
+            ));
+            chains.forget(*key);
+            request.previous_response_id = None;
+            request.input = full_input.clone();
+            let response = send_upstream(
+                &state,
+                &state.responses_url,
+                &request,
+                auth_header.as_deref(),
+            )
+            .await?;
+            ensure_success(response).await?
+        }
+        result => result?,
+    };
+
+    if is_streaming {
+        let byte_stream = record_stream_response_id(response.bytes_stream(), chain);
+        let stream = create_anthropic_stream(byte_stream, original_model, include_thinking);
+        return Ok(sse_response(stream));
+    }
+
+    // The ChatGPT Codex backend can return SSE even when stream=false.
+    // When that happens, extract the final `response` object from the SSE and treat it as JSON.
+    let openai_resp = match response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(ct) if ct.to_ascii_lowercase().contains("text/event-stream") => {
+            let full = response.text().await.unwrap_or_default();
+            let mut final_response: Option<Value> = None;
+            for line in full.lines() {
+                let Some(data) = line.strip_prefix("data: ") else {
+                    continue;
+                };
+                if data == "[DONE]" {
+                    continue;
+                }
+                let Ok(event) = serde_json::from_str::<Value>(data) else {
+                    continue;
+                };
+                let event_type = event.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                if event_type == "response.done" || event_type == "response.completed" {
+                    final_response = event.get("response").cloned();
+                }
+            }
+            let final_response = final_response.ok_or_else(|| UpstreamError {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                body: "Could not find final response in SSE stream".to_string(),
+            })?;
+            parse_upstream_value::<ResponsesResponse>(final_response)?
+        }
+        _ => parse_json::<ResponsesResponse>(response).await?,
+    };
+
+    if let Some((chains, key, full_input)) = chain {
+        chains.record(key, &full_input, openai_resp.id.clone());
+    }
+
+    let anthropic_resp = responses_to_anthropic(&openai_resp, &original_model, include_thinking);
+    Ok(Json(anthropic_resp).into_response())
+}
+
+pub(crate) async fn handle_chat_request(
+    state: Arc<ProxyState>,
+    request: ChatCompletionRequest,
+    original_model: String,
+    include_thinking: bool,
+    is_streaming: bool,
+    auth_header: Option<String>,
+) -> Result<Response, UpstreamError> {
+    let response = send_upstream(
+        &state,
+        &state.chat_completions_url,
+        &request,
+        auth_header.as_deref(),
+    )
+    .await?;
+
+    let response = ensure_success(response).await?;
+    if is_streaming {
+        let byte_stream = response.bytes_stream();
+        let stream = create_anthropic_stream_from_chat(
+            byte_stream,
+            original_model,
+            state.chat_dialect,
+            include_thinking,
+        );
+        return Ok(sse_response(stream));
+    }
+    let openai_resp = parse_json::<ChatCompletionResponse>(response).await?;
+
+    let anthropic_resp = chat_to_anthropic(&openai_resp, &original_model);
+    Ok(Json(anthropic_resp).into_response())
+}
+
+pub(crate) async fn handle_completions_request(
+    state: Arc<ProxyState>,
+    request: CompletionsRequest,
+    original_model: String,
+    is_streaming: bool,
+    auth_header: Option<String>,
+) -> Result<Response, UpstreamError> {
+    let response = send_upstream(
+        &state,
+        &state.completions_url,
+        &request,
+        auth_header.as_deref(),
+    )
+    .await?;
+
+    let response = ensure_success(response).await?;
+    if is_streaming {
+        let byte_stream = response.bytes_stream();
+        let stream = create_anthropic_stream_from_completions(byte_stream, original_model);
+        return Ok(sse_response(stream));
+    }
+    let openai_resp = parse_json::<CompletionsResponse>(response).await?;
+
+    let anthropic_resp = completions_to_anthropic(&openai_resp, &original_model);
+    Ok(Json(anthropic_resp).into_response())
+}
+
+pub(crate) async fn handle_auto_request(
+    state: Arc<ProxyState>,
+    request: AnthropicRequest,
+    target_model: String,
+    original_model: String,
+    is_streaming: bool,
+    include_thinking: bool,
+    auth_header: Option<String>,
+) -> Response {
+    let response_request = anthropic_to_responses(&request, &target_model);
+    if let ControlFlow::Break(resp) = handle_attempt_result(
+        attempt_or_fallback(
+            &state,
+            UpstreamMode::Responses,
+            handle_responses_request(
+                state.clone(),
+                response_request,
+                session_hint(&request),
+                original_model.clone(),
+                include_thinking,
+                is_streaming,
+                auth_header.clone(),
+            )
+            .await,
+        )
+        .await,
+    ) {
+        return resp;
+    }
+
+    let chat_request = anthropic_to_chat(&request, &target_model);
+    if let ControlFlow::Break(resp) = handle_attempt_result(
+        attempt_or_fallback(
+            &state,
+            UpstreamMode::ChatCompletions,
+            handle_chat_request(
+                state.clone(),
+                chat_request,
+                original_model.clone(),
+                include_thinking,
+                is_streaming,
+                auth_header.clone(),
+            )
+            .await,
+        )
+        .await,
+    ) {
+        return resp;
+    }
+
+    let completion_request = anthropic_to_completions(&request, &target_model);
+    result_to_response(
+        attempt_upstream(
+            &state,
+            UpstreamMode::Completions,
+            handle_completions_request(
+                state.clone(),
+                completion_request,
+                original_model,
+                is_streaming,
+                auth_header,
+            )
+            .await,
+        )
+        .await,
+    )
+}
+
+pub(crate) async fn handle_bedrock_request(
+    state: Arc<ProxyState>,
+    request: AnthropicRequest,
+    target_model: String,
+    original_model: String,
+    is_streaming: bool,
+    auth_header: Option<String>,
+) -> Result<Response, UpstreamError> {
+    let model_id = bedrock_model_id(&target_model);
+
+    if bedrock_uses_converse(&model_id) {
+        let body = anthropic_to_converse(&request);
+        let action = if is_streaming {
+            "converse-stream"
+        } else {
+            "converse"
+        };
+        let url = bedrock_endpoint(&state.target_url, &model_id, action);
+        let response = send_upstream(&state, &url, &body, auth_header.as_deref()).await?;
+
+        let response = ensure_success(response).await?;
+        if is_streaming {
+            let byte_stream = response.bytes_stream();
+            let stream = create_anthropic_stream_from_converse(byte_stream, original_model);
+            return Ok(sse_response(stream));
+        }
+        let converse_resp = parse_json::<Value>(response).await?;
+        let anthropic_resp = converse_to_anthropic(&converse_resp, &original_model);
+        return Ok(Json(anthropic_resp).into_response());
+    }
+
+    let body = anthropic_to_bedrock_invoke(&request, &beta_features(&state.headers));
+    let action = if is_streaming {
+        "invoke-with-response-stream"
+    } else {
+        "invoke"
+    };
+    let url = bedrock_endpoint(&state.target_url, &model_id, action);
+    let response = send_upstream(&state, &url, &body, auth_header.as_deref()).await?;
+
+    let response = ensure_success(response).await?;
+    if is_streaming {
+        let byte_stream = response.bytes_stream();
+        let stream = create_anthropic_stream_from_bedrock_invoke(byte_stream, original_model);
+        return Ok(sse_response(stream));
+    }
+
+    // InvokeModel already returns an Anthropic Messages response
+    let mut anthropic_resp = parse_json::<Value>(response).await?;
+    anthropic_resp["model"] = Value::String(original_model);
+    Ok(Json(anthropic_resp).into_response())
+}
@@ -0,0 +1,440 @@
+//! JSON Schema for `profiles.toml`, and checking the file against it on load.
+//!
+//! `claude-profiler schema` prints the schema so editors (Taplo, VS Code's Even Better
+//! TOML) can complete keys and flag mistakes while typing. The same schema is applied
+//! when the config is loaded: misspelled keys and values of the wrong type are reported
+//! as `profiles.toml:LINE:COLUMN: message`, one line each, instead of being dropped on
+//! the next save or failing somewhere deep inside the parser.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::ops::Range;
+use toml_edit::{Item, TableLike};
+
+use crate::config::Config;
+
+/// The schema of `profiles.toml`
+pub fn config_schema() -> Value {
+    let mut schema =
+        serde_json::to_value(schemars::schema_for!(Config)).expect("a generated schema serializes");
+    schema["title"] = "claude-profiler profiles.toml".into();
+    schema
+}
+
+/// `claude-profiler schema`: print the schema to stdout
+pub fn run_command(args: &[String]) -> Result<()> {
+    if !args.is_empty() {
+        anyhow::bail!("Usage: claude-profiler schema");
+    }
+    println!("{}", serde_json::to_string_pretty(&config_schema())?);
+    Ok(())
+}
+
+/// A problem at a place in a TOML file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based line
+    pub line: usize,
+    /// 1-based column, in characters
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// `message` at byte offset `span.start` of `contents`; the start of the file
+    /// when the parser gave no span
+    pub fn at(contents: &str, span: Option<Range<usize>>, message: impl Into<String>) -> Self {
+        let offset = span.map_or(0, |span| span.start.min(contents.len()));
+        let before = &contents[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message: message.into(),
+        }
+    }
+}
+
+/// Problems with `contents` as a `profiles.toml`, in file order; empty when it
+/// matches the schema
+pub fn check_config(contents: &str) -> Vec<Diagnostic> {
+    let document = match toml_edit::Document::parse(contents) {
+        Ok(document) => document,
+        Err(e) => return vec![Diagnostic::at(contents, e.span(), e.message().trim())],
+    };
+    let schema = config_schema();
+    let mut checker = Checker {
+        contents,
+        root: &schema,
+        diagnostics: Vec::new(),
+    };
+    let root = Item::Table(document.as_table().clone());
+    checker.check(&root, None, &schema, "");
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics.dedup();
+    diagnostics
+}
+
+/// Walks a parsed document alongside the schema. Covers the keywords schemars emits
+/// for the config types: `$ref`, `type`, `properties`, `additionalProperties`,
+/// `required`, `items`, `enum`/`const`, `oneOf`/`anyOf` and `minimum`/`maximum`.
+struct Checker<'a> {
+    contents: &'a str,
+    root: &'a Value,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, span: Option<Range<usize>>, message: String) {
+        self.diagnostics
+            .push(Diagnostic::at(self.contents, span, message));
+    }
+
+    fn resolve(&self, mut schema: &'a Value) -> &'a Value {
+        while let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.root.pointer(pointer))
+            {
+                Some(target) => schema = target,
+                None => break,
+            }
+        }
+        schema
+    }
+
+    /// Check `item` (found at `span`, named `path` in messages) against `schema`
+    fn check(&mut self, item: &Item, span: Option<Range<usize>>, schema: &'a Value, path: &str) {
+        let schema = self.resolve(schema);
+        let span = item.span().or(span);
+        if schema == &Value::Bool(false) {
+            self.report(span, format!("`{}` is not allowed here", path));
+            return;
+        }
+        if !schema.is_object() {
+            return;
+        }
+
+        if let Some(alternatives) = schema
+            .get("oneOf")
+            .or_else(|| schema.get("anyOf"))
+            .and_then(Value::as_array)
+        {
+            return self.check_alternatives(item, span, alternatives, path);
+        }
+
+        let found = type_of(item);
+        if let Some(expected) = schema.get("type") {
+            let allowed: Vec<&str> = match expected {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            let matches = allowed
+                .iter()
+                .any(|name| *name == found || (*name == "number" && found == "integer"));
+            if !allowed.is_empty() && !matches {
+                let expected: Vec<&str> = allowed.into_iter().filter(|n| *n != "null").collect();
+                self.report(
+                    span,
+                    format!(
+                        "{}expected {}, found {}",
+                        label(path),
+                        expected.join(" or "),
+                        found
+                    ),
+                );
+                return;
+            }
+        }
+
+        let allowed = constants(schema);
+        if !allowed.is_empty() {
+            let value = item.as_value().map(to_json);
+            if !value.is_some_and(|value| allowed.contains(&value)) {
+                self.report(
+                    span,
+                    format!("{}expected {}", label(path), one_of(&allowed)),
+                );
+            }
+            return;
+        }
+
+        if let Some(number) = item
+            .as_value()
+            .and_then(|v| v.as_float().or(v.as_integer().map(|i| i as f64)))
+        {
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+                && number < minimum
+            {
+                self.report(
+                    span.clone(),
+                    format!("{}must be at least {}", label(path), minimum),
+                );
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64)
+                && number > maximum
+            {
+                self.report(
+                    span.clone(),
+                    format!("{}must be at most {}", label(path), maximum),
+                );
+            }
+        }
+
+        if let Some(table) = item.as_table_like() {
+            self.check_table(table, span, schema, path);
+        } else if let Some(items) = schema.get("items") {
+            for (element, element_span) in elements(item) {
+                self.check(&element, element_span, items, path);
+            }
+        }
+    }
+
+    fn check_table(
+        &mut self,
+        table: &dyn TableLike,
+        span: Option<Range<usize>>,
+        schema: &'a Value,
+        path: &str,
+    ) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+        for (name, value) in table.iter() {
+            let key_span = table.get_key_value(name).and_then(|(key, _)| key.span());
+            let child_path = if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", path, name)
+            };
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => self.check(value, key_span, property, &child_path),
+                None if additional == Some(&Value::Bool(false)) => {
+                    let message = match path {
+                        "" => format!("unknown key `{}`", name),
+                        _ => format!("unknown key `{}` in `{}`", name, path),
+                    };
+                    self.report(key_span, message);
+                }
+                None => {
+                    if let Some(additional) = additional {
+                        self.check(value, key_span, additional, &child_path);
+                    }
+                }
+            }
+        }
+        for name in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !table.contains_key(name) {
+                let message = match path {
+                    "" => format!("missing key `{}`", name),
+                    _ => format!("missing key `{}` in `{}`", name, path),
+                };
+                self.report(span.clone(), message);
+            }
+        }
+    }
+
+    /// `oneOf`/`anyOf`: fine when any alternative matches. A choice between fixed
+    /// values is reported as such; otherwise the closest alternative's problems are
+    fn check_alternatives(
+        &mut self,
+        item: &Item,
+        span: Option<Range<usize>>,
+        alternatives: &'a [Value],
+        path: &str,
+    ) {
+        let mut best: Option<Vec<Diagnostic>> = None;
+        for alternative in alternatives {
+            let mut nested = Checker {
+                contents: self.contents,
+                root: self.root,
+                diagnostics: Vec::new(),
+            };
+            nested.check(item, span.clone(), alternative, path);
+            if nested.diagnostics.is_empty() {
+                return;
+            }
+            if best
+                .as_ref()
+                .is_none_or(|best| nested.diagnostics.len() < best.len())
+            {
+                best = Some(nested.diagnostics);
+            }
+        }
+
+        let allowed: Vec<Vec<Value>> = alternatives
+            .iter()
+            .map(|alternative| constants(self.resolve(alternative)))
+            .collect();
+        if allowed.iter().all(|values| !values.is_empty()) {
+            let allowed: Vec<Value> = allowed.into_iter().flatten().collect();
+            self.report(
+                span,
+                format!("{}expected {}", label(path), one_of(&allowed)),
+            );
+        } else {
+            self.diagnostics.extend(best.unwrap_or_default());
+        }
+    }
+}
+
+/// `path: ` for messages about a value, so they say which key is wrong
+fn label(path: &str) -> String {
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("`{}`: ", path)
+    }
+}
+
+/// The fixed values a schema allows (`const` or `enum`), if it has any
+fn constants(schema: &Value) -> Vec<Value> {
+    if let Some(value) = schema.get("const") {
+        return vec![value.clone()];
+    }
+    schema
+        .get("enum")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn one_of(values: &[Value]) -> String {
+    let values: Vec<String> = values.iter().map(Value::to_string).collect();
+    match values.as_slice() {
+        [only] => only.clone(),
+        _ => format!("one of {}", values.join(", ")),
+    }
+}
+
+/// The JSON Schema type name of a TOML item
+fn type_of(item: &Item) -> &'static str {
+    use toml_edit::Value as Toml;
+
+    match item {
+        Item::None => "nothing",
+        Item::Table(_) | Item::Value(Toml::InlineTable(_)) => "object",
+        Item::ArrayOfTables(_) | Item::Value(Toml::Array(_)) => "array",
+        Item::Value(Toml::String(_)) => "string",
+        Item::Value(Toml::Integer(_)) => "integer",
+        Item::Value(Toml::Float(_)) => "number",
+        Item::Value(Toml::Boolean(_)) => "boolean",
+        Item::Value(Toml::Datetime(_)) => "datetime",
+    }
+}
+
+/// The elements of an array or array of tables, with their positions
+fn elements(item: &Item) -> Vec<(Item, Option<Range<usize>>)> {
+    match item {
+        Item::ArrayOfTables(tables) => tables
+            .iter()
+            .map(|table| (Item::Table(table.clone()), table.span()))
+            .collect(),
+        Item::Value(toml_edit::Value::Array(array)) => array
+            .iter()
+            .map(|value| (Item::Value(value.clone()), value.span()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A scalar TOML value as JSON, for comparing against `const`/`enum`
+fn to_json(value: &toml_edit::Value) -> Value {
+    use toml_edit::Value as Toml;
+
+    match value {
+        Toml::String(s) => Value::from(s.value().as_str()),
+        Toml::Integer(i) => Value::from(*i.value()),
+        Toml::Float(f) => Value::from(*f.value()),
+        Toml::Boolean(b) => Value::from(*b.value()),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_problems_are_reported_where_they_are() {
+        let schema = config_schema();
+        let profile = &schema["$defs"]["Profile"];
+        assert_eq!(profile["required"], serde_json::json!(["name"]));
+        assert!(profile["properties"]["proxy_port"].is_object());
+        assert_eq!(profile["additionalProperties"], false);
+
+        let valid = r#"
+default_profile = "work"
+list_view = "compact"
+
+[[profiles]]
+name = "work"
+proxy_port = 4001
+env = { ANTHROPIC_MODEL = "claude-sonnet-4" }
+
+[retention]
+log_max_age_days = 7
+"#;
+        assert_eq!(check_config(valid), []);
+        let default = toml::to_string_pretty(&Config::create_default()).unwrap();
+        assert_eq!(check_config(&default), []);
+        let config: Config = toml::from_str(valid).unwrap();
+        assert_eq!(config.profiles[0].proxy_port, Some(4001));
+
+        let invalid = r#"list_view = "wide"
+
+[[profiles]]
+name = "work"
+descripton = "typo"
+proxy_port = 70000
+
+[[profiles]]
+description = "no name"
+env = { ANTHROPIC_MODEL = 4 }
+
+[cost_ceiling]
+enabled = "yes"
+"#;
+        let found: Vec<(usize, usize, String)> = check_config(invalid)
+            .into_iter()
+            .map(|d| (d.line, d.column, d.message))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    1,
+                    13,
+                    "`list_view`: expected one of \"detailed\", \"compact\"".to_string()
+                ),
+                (5, 1, "unknown key `descripton` in `profiles`".to_string()),
+                (
+                    6,
+                    14,
+                    "`profiles.proxy_port`: must be at most 65535".to_string()
+                ),
+                (8, 1, "missing key `name` in `profiles`".to_string()),
+                (
+                    10,
+                    27,
+                    "`profiles.env.ANTHROPIC_MODEL`: expected string, found integer".to_string()
+                ),
+                (
+                    13,
+                    11,
+                    "`cost_ceiling.enabled`: expected boolean, found string".to_string()
+                ),
+            ]
+        );
+
+        let broken = check_config("[[profiles]]\nname = \"work\nx = 1\n");
+        assert_eq!((broken.len(), broken[0].line), (1, 2));
+    }
+}
@@ -0,0 +1,120 @@
+//! API keys kept in the OS keychain (macOS Keychain, Windows Credential Manager,
+//! Secret Service on Linux) instead of `profiles.toml`.
+//!
+//! A profile value written as `keychain:<entry>` names an entry under the
+//! `claude-profiler` service. The launcher swaps in the secret just before starting
+//! Claude Code, so the config file only ever holds the reference.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+use crate::config::{KEYCHAIN_PREFIX, keychain_reference};
+
+/// Keychain service the entries are stored under
+const KEYCHAIN_SERVICE: &str = "claude-profiler";
+
+/// Somewhere secrets can be stored and read back by entry name
+pub trait SecretStore {
+    fn get(&self, entry: &str) -> Result<String>;
+    fn set(&self, entry: &str, secret: &str) -> Result<()>;
+}
+
+/// The OS keychain
+pub struct Keychain;
+
+impl Keychain {
+    fn entry(entry: &str) -> Result<keyring::Entry> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, entry)
+            .with_context(|| format!("Invalid keychain entry '{}'", entry))
+    }
+
+    /// Remove `entry`; an entry that does not exist is not an error
+    pub fn delete(&self, entry: &str) -> Result<()> {
+        match Self::entry(entry)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Failed to delete keychain entry '{}'", entry))
+            }
+        }
+    }
+}
+
+impl SecretStore for Keychain {
+    fn get(&self, entry: &str) -> Result<String> {
+        Self::entry(entry)?
+            .get_password()
+            .with_context(|| format!("Failed to read keychain entry '{}'", entry))
+    }
+
+    fn set(&self, entry: &str, secret: &str) -> Result<()> {
+        Self::entry(entry)?
+            .set_password(secret)
+            .with_context(|| format!("Failed to write keychain entry '{}'", entry))
+    }
+}
+
+/// Move `secret` into `store` under `entry` and return the reference to save in
+/// its place
+pub fn store_secret(store: &dyn SecretStore, entry: &str, secret: &str) -> Result<String> {
+    store.set(entry, secret)?;
+    Ok(format!("{}{}", KEYCHAIN_PREFIX, entry))
+}
+
+/// Replace every `keychain:<entry>` value in `env` with the stored secret
+pub fn resolve_keychain_references(
+    env: &mut HashMap<String, String>,
+    store: &dyn SecretStore,
+) -> Result<()> {
+    for (key, value) in env.iter_mut() {
+        if let Some(entry) = keychain_reference(value).map(str::to_string) {
+            *value = store
+                .get(&entry)
+                .with_context(|| format!("{} refers to {}{}", key, KEYCHAIN_PREFIX, entry))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ENV_AUTH_TOKEN;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct MemoryStore(RefCell<HashMap<String, String>>);
+
+    impl SecretStore for MemoryStore {
+        fn get(&self, entry: &str) -> Result<String> {
+            self.0.borrow().get(entry).cloned().context("No such entry")
+        }
+
+        fn set(&self, entry: &str, secret: &str) -> Result<()> {
+            self.0
+                .borrow_mut()
+                .insert(entry.to_string(), secret.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn keychain_references_are_stored_and_resolved() {
+        let store = MemoryStore::default();
+        let reference = store_secret(&store, "OpenRouter", "sk-or-secret").unwrap();
+        assert_eq!(reference, "keychain:OpenRouter");
+
+        let mut env = HashMap::from([
+            (ENV_AUTH_TOKEN.to_string(), reference),
+            ("API_TIMEOUT_MS".to_string(), "600000".to_string()),
+        ]);
+        resolve_keychain_references(&mut env, &store).unwrap();
+        assert_eq!(env[ENV_AUTH_TOKEN], "sk-or-secret");
+        assert_eq!(env["API_TIMEOUT_MS"], "600000");
+
+        let mut missing =
+            HashMap::from([(ENV_AUTH_TOKEN.to_string(), "keychain:gone".to_string())]);
+        let error = resolve_keychain_references(&mut missing, &store).unwrap_err();
+        assert!(error.to_string().contains("keychain:gone"));
+        assert_eq!(keychain_reference("keychain:"), None);
+    }
+}
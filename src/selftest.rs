@@ -0,0 +1,413 @@
+//! `claude-profiler proxy-test <profile>`: exercise the proxy against a profile's upstream.
+//!
+//! Starts the proxy exactly as a launch would, sends one Anthropic request per
+//! capability Claude Code relies on and checks the translated response has the shape
+//! Claude Code expects. Content is not judged, only structure, so any model passes
+//! as long as the translation is sound.
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, ENV_AUTH_TOKEN};
+use crate::launcher;
+use crate::proxy::Shutdown;
+
+/// Model sent when the profile maps none (the proxy may still override it)
+const FALLBACK_MODEL: &str = "claude-sonnet-4-5";
+
+/// Upstreams can be slow to load a model on the first request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// 1x1 PNG for the image check
+const TEST_IMAGE_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8DwHwAFBQIAX8jx0gAAAABJRU5ErkJggg==";
+
+/// One capability check: a request body and a validator for the raw response body
+struct Check {
+    name: &'static str,
+    stream: bool,
+    body: fn(&str) -> Value,
+    /// `Ok(detail)` when the response is well formed
+    validate: fn(&str) -> Result<String, String>,
+}
+
+const CHECKS: [Check; 5] = [
+    Check {
+        name: "text",
+        stream: false,
+        body: text_request,
+        validate: validate_text,
+    },
+    Check {
+        name: "tools",
+        stream: false,
+        body: tools_request,
+        validate: validate_tools,
+    },
+    Check {
+        name: "streaming",
+        stream: true,
+        body: streaming_request,
+        validate: validate_stream,
+    },
+    Check {
+        name: "images",
+        stream: false,
+        body: image_request,
+        validate: validate_text,
+    },
+    Check {
+        name: "thinking",
+        stream: false,
+        body: thinking_request,
+        validate: validate_thinking,
+    },
+];
+
+fn user_message(model: &str, content: Value) -> Value {
+    json!({
+        "model": model,
+        "max_tokens": 256,
+        "messages": [{"role": "user", "content": content}],
+    })
+}
+
+fn text_request(model: &str) -> Value {
+    user_message(model, json!("Reply with the single word: pong"))
+}
+
+fn tools_request(model: &str) -> Value {
+    let mut body = user_message(model, json!("What is the weather in Paris? Use the tool."));
+    body["tools"] = json!([{
+        "name": "get_weather",
+        "description": "Current weather for a city",
+        "input_schema": {
+            "type": "object",
+            "properties": {"city": {"type": "string"}},
+            "required": ["city"],
+        },
+    }]);
+    body["tool_choice"] = json!({"type": "tool", "name": "get_weather"});
+    body
+}
+
+fn streaming_request(model: &str) -> Value {
+    let mut body = text_request(model);
+    body["stream"] = json!(true);
+    body
+}
+
+fn image_request(model: &str) -> Value {
+    user_message(
+        model,
+        json!([
+            {
+                "type": "image",
+                "source": {"type": "base64", "media_type": "image/png", "data": TEST_IMAGE_PNG},
+            },
+            {"type": "text", "text": "Describe this image in one word."},
+        ]),
+    )
+}
+
+fn thinking_request(model: &str) -> Value {
+    let mut body = user_message(model, json!("What is 17 * 23? Answer with the number."));
+    body["max_tokens"] = json!(4096);
+    body["thinking"] = json!({"type": "enabled", "budget_tokens": 1024});
+    body
+}
+
+/// A non-streaming Messages response with its content blocks
+fn parse_message(body: &str) -> Result<(Value, Vec<Value>), String> {
+    let message: Value =
+        serde_json::from_str(body).map_err(|e| format!("response is not JSON: {}", e))?;
+    if message.get("type").and_then(Value::as_str) != Some("message") {
+        return Err(format!("unexpected response: {}", truncate(body)));
+    }
+    if message.get("role").and_then(Value::as_str) != Some("assistant") {
+        return Err("role is not assistant".to_string());
+    }
+    if message
+        .pointer("/usage/input_tokens")
+        .and_then(Value::as_u64)
+        .is_none()
+    {
+        return Err("usage.input_tokens missing".to_string());
+    }
+    let content = message
+        .get("content")
+        .and_then(Value::as_array)
+        .cloned()
+        .ok_or("content is not an array")?;
+    Ok((message, content))
+}
+
+fn blocks_of<'a>(content: &'a [Value], kind: &'a str) -> impl Iterator<Item = &'a Value> {
+    content
+        .iter()
+        .filter(move |block| block.get("type").and_then(Value::as_str) == Some(kind))
+}
+
+fn validate_text(body: &str) -> Result<String, String> {
+    let (message, content) = parse_message(body)?;
+    let text: String = blocks_of(&content, "text")
+        .filter_map(|block| block.get("text").and_then(Value::as_str))
+        .collect();
+    if text.trim().is_empty() {
+        return Err("no text in response".to_string());
+    }
+    let stop_reason = message
+        .get("stop_reason")
+        .and_then(Value::as_str)
+        .ok_or("stop_reason missing")?;
+    Ok(format!("stop_reason {}", stop_reason))
+}
+
+fn validate_tools(body: &str) -> Result<String, String> {
+    let (_, content) = parse_message(body)?;
+    let call = blocks_of(&content, "tool_use")
+        .next()
+        .ok_or("no tool_use block")?;
+    if call
+        .get("id")
+        .and_then(Value::as_str)
+        .is_none_or(str::is_empty)
+    {
+        return Err("tool_use has no id".to_string());
+    }
+    if call.get("name").and_then(Value::as_str) != Some("get_weather") {
+        return Err(format!("unexpected tool name: {}", call["name"]));
+    }
+    if !call.get("input").is_some_and(Value::is_object) {
+        return Err("tool_use input is not an object".to_string());
+    }
+    Ok(format!("input {}", call["input"]))
+}
+
+/// Events must arrive in Messages API order with at least one text delta
+fn validate_stream(body: &str) -> Result<String, String> {
+    let events: Vec<Value> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str(data.trim()).ok())
+        .collect();
+    let kinds: Vec<&str> = events
+        .iter()
+        .filter_map(|event| event.get("type").and_then(Value::as_str))
+        .filter(|kind| *kind != "ping")
+        .collect();
+
+    if kinds.first() != Some(&"message_start") {
+        return Err(format!("first event is {:?}", kinds.first()));
+    }
+    if kinds.last() != Some(&"message_stop") {
+        return Err(format!("last event is {:?}", kinds.last()));
+    }
+    let position = |kind: &str| kinds.iter().position(|k| *k == kind);
+    let delta = position("content_block_delta").ok_or("no content_block_delta")?;
+    let message_delta = position("message_delta").ok_or("no message_delta")?;
+    if position("content_block_start").is_none_or(|start| start > delta) {
+        return Err("content_block_delta before content_block_start".to_string());
+    }
+    if message_delta < delta {
+        return Err("message_delta before content".to_string());
+    }
+    let has_text = events
+        .iter()
+        .any(|event| event.pointer("/delta/type").and_then(Value::as_str) == Some("text_delta"));
+    if !has_text {
+        return Err("no text_delta".to_string());
+    }
+    Ok(format!("{} events", kinds.len()))
+}
+
+/// Thinking blocks are optional (not every upstream reasons), but must be well formed
+fn validate_thinking(body: &str) -> Result<String, String> {
+    validate_text(body)?;
+    let (_, content) = parse_message(body)?;
+    let thinking: Vec<&Value> = blocks_of(&content, "thinking").collect();
+    if thinking
+        .iter()
+        .any(|block| block.get("thinking").and_then(Value::as_str).is_none())
+    {
+        return Err("thinking block without text".to_string());
+    }
+    if thinking.is_empty() {
+        Ok("accepted, no thinking returned".to_string())
+    } else {
+        Ok(format!("{} thinking blocks", thinking.len()))
+    }
+}
+
+fn truncate(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    match line.char_indices().nth(120) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+async fn run_check(
+    client: &reqwest::Client,
+    anthropic_url: &str,
+    check: &Check,
+    model: &str,
+    token: Option<&str>,
+) -> Result<String, String> {
+    let url = format!("{}/v1/messages", anthropic_url);
+    let mut request = client
+        .post(url)
+        .header("anthropic-version", "2023-06-01")
+        .json(&(check.body)(model));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    let is_sse = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/event-stream"));
+    let body = response.text().await.map_err(|e| e.to_string())?;
+
+    if !status.is_success() {
+        return Err(crate::provider_errors::describe(status.as_u16(), &body));
+    }
+    if check.stream && !is_sse {
+        return Err("response is not an event stream".to_string());
+    }
+    (check.validate)(&body)
+}
+
+/// `claude-profiler proxy-test <profile>`
+pub fn run_command(config: &Config, args: &[String]) -> Result<()> {
+    let [name] = args else {
+        anyhow::bail!("Usage: claude-profiler proxy-test <profile>");
+    };
+    let profile = config
+        .profiles
+        .iter()
+        .find(|p| &p.name == name)
+        .with_context(|| format!("No profile named '{}'", name))?;
+
+    let prepared = launcher::prepare_profile(profile)?;
+    let Some((target, options)) = prepared.proxy else {
+        anyhow::bail!(
+            "Profile '{}' does not use the proxy (no PROXY_TARGET_URL)",
+            name
+        );
+    };
+    let model = profile
+        .primary_model()
+        .unwrap_or(FALLBACK_MODEL)
+        .to_string();
+    let token = prepared.env.get(ENV_AUTH_TOKEN).cloned();
+
+    println!("Testing '{}' -> {}", profile.name, target);
+    let proxy = launcher::start_proxy(target, options)?;
+    let url = proxy.anthropic_url();
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to build HTTP client")?;
+    let rt = tokio::runtime::Runtime::new()?;
+    let mut failed = 0;
+
+    println!();
+    println!(
+        "  {:<10} {:<6} {:>7}  Detail",
+        "Capability", "Result", "Time"
+    );
+    for check in &CHECKS {
+        let start = Instant::now();
+        let result = rt.block_on(run_check(&client, &url, check, &model, token.as_deref()));
+        let elapsed = format!("{:.1}s", start.elapsed().as_secs_f32());
+        let (label, detail) = match result {
+            Ok(detail) => ("PASS", detail),
+            Err(detail) => {
+                failed += 1;
+                ("FAIL", detail)
+            }
+        };
+        println!(
+            "  {:<10} {:<6} {:>7}  {}",
+            check.name, label, elapsed, detail
+        );
+    }
+    println!();
+
+    proxy.stop(Shutdown::Normal);
+    if failed > 0 {
+        anyhow::bail!(
+            "{} of {} capabilities failed; see proxy.log for upstream errors",
+            failed,
+            CHECKS.len()
+        );
+    }
+    println!("All {} capabilities passed", CHECKS.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validators_accept_well_formed_responses() {
+        let text = json!({
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "pong"}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 5, "output_tokens": 1},
+        });
+        assert_eq!(
+            validate_text(&text.to_string()).unwrap(),
+            "stop_reason end_turn"
+        );
+        assert!(validate_thinking(&text.to_string()).is_ok());
+        assert_eq!(
+            validate_tools(&text.to_string()).unwrap_err(),
+            "no tool_use block"
+        );
+
+        let stream = [
+            r#"data: {"type":"message_start"}"#,
+            r#"data: {"type":"content_block_start"}"#,
+            r#"data: {"type":"content_block_delta","delta":{"type":"text_delta","text":"pong"}}"#,
+            r#"data: {"type":"content_block_stop"}"#,
+            r#"data: {"type":"message_delta"}"#,
+            r#"data: {"type":"message_stop"}"#,
+        ]
+        .join("\n\n");
+        assert_eq!(validate_stream(&stream).unwrap(), "6 events");
+        assert!(validate_stream(&stream.replace("message_stop", "ping")).is_err());
+    }
+
+    #[test]
+    fn validators_reject_malformed_responses() {
+        assert!(validate_text("Not found").is_err());
+        let no_usage = json!({
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "pong"}],
+        });
+        assert_eq!(
+            validate_text(&no_usage.to_string()).unwrap_err(),
+            "usage.input_tokens missing"
+        );
+
+        let tool = json!({
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "tool_use", "id": "t1", "name": "get_weather", "input": "Paris"}],
+            "usage": {"input_tokens": 5},
+        });
+        assert_eq!(
+            validate_tools(&tool.to_string()).unwrap_err(),
+            "tool_use input is not an object"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Signal handling while Claude Code runs as a child process.
+//!
+//! Ctrl+C reaches the whole foreground process group, so Claude Code sees it itself;
+//! the launcher ignores SIGINT and SIGQUIT meanwhile (as `system(3)` does) instead of
+//! dying and orphaning the proxy. SIGTERM and SIGHUP are recorded so the launcher can
+//! forward them to the child and clean up. Dispositions and terminal modes are put back
+//! when the guard is dropped.
+
+use std::process::Child;
+
+#[cfg(unix)]
+mod imp {
+    use std::process::Child;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// Last forwardable signal received, or 0
+    static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn record(signal: libc::c_int) {
+        RECEIVED.store(signal, Ordering::SeqCst);
+    }
+
+    pub struct Guard {
+        previous: Vec<(libc::c_int, libc::sigaction)>,
+        termios: Option<libc::termios>,
+    }
+
+    impl Guard {
+        pub fn install() -> Self {
+            RECEIVED.store(0, Ordering::SeqCst);
+            let handlers = [
+                (libc::SIGINT, libc::SIG_IGN),
+                (libc::SIGQUIT, libc::SIG_IGN),
+                (
+                    libc::SIGTERM,
+                    record as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                ),
+                (
+                    libc::SIGHUP,
+                    record as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                ),
+            ];
+            let mut previous = Vec::new();
+            // SAFETY: plain sigaction/tcgetattr calls on zero-initialised C structs;
+            // the handler only stores to an atomic, which is async-signal-safe.
+            unsafe {
+                for (signal, handler) in handlers {
+                    let mut action: libc::sigaction = std::mem::zeroed();
+                    action.sa_sigaction = handler;
+                    action.sa_flags = libc::SA_RESTART;
+                    libc::sigemptyset(&mut action.sa_mask);
+                    let mut old: libc::sigaction = std::mem::zeroed();
+                    if libc::sigaction(signal, &action, &mut old) == 0 {
+                        previous.push((signal, old));
+                    }
+                }
+                let mut termios: libc::termios = std::mem::zeroed();
+                let termios =
+                    (libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0).then_some(termios);
+                Self { previous, termios }
+            }
+        }
+
+        pub fn take(&self) -> Option<i32> {
+            match RECEIVED.swap(0, Ordering::SeqCst) {
+                0 => None,
+                signal => Some(signal),
+            }
+        }
+    }
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            // SAFETY: restores exactly what `install` saved
+            unsafe {
+                if let Some(termios) = &self.termios {
+                    libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+                }
+                for (signal, old) in &self.previous {
+                    libc::sigaction(*signal, old, std::ptr::null_mut());
+                }
+            }
+        }
+    }
+
+    pub fn forward(child: &Child, signal: i32) {
+        // SAFETY: kill(2) on our own child's pid
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, signal);
+        }
+    }
+
+    pub fn terminate(child: &Child) {
+        forward(child, libc::SIGTERM);
+    }
+
+    pub fn name(signal: i32) -> &'static str {
+        match signal {
+            libc::SIGTERM => "SIGTERM",
+            libc::SIGHUP => "SIGHUP",
+            _ => "signal",
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::process::Child;
+
+    /// Windows delivers Ctrl+C to every process on the console; nothing to forward
+    pub struct Guard;
+
+    impl Guard {
+        pub fn install() -> Self {
+            Guard
+        }
+
+        pub fn take(&self) -> Option<i32> {
+            None
+        }
+    }
+
+    pub fn forward(_child: &Child, _signal: i32) {}
+
+    pub fn terminate(_child: &Child) {}
+
+    pub fn name(_signal: i32) -> &'static str {
+        "signal"
+    }
+}
+
+/// Signal handling for the lifetime of one child process
+pub struct SessionSignals(imp::Guard);
+
+impl SessionSignals {
+    pub fn install() -> Self {
+        Self(imp::Guard::install())
+    }
+
+    /// Forward a SIGTERM/SIGHUP received since the last call to `child`, returning
+    /// its name
+    pub fn forward_pending(&self, child: &Child) -> Option<&'static str> {
+        let signal = self.0.take()?;
+        imp::forward(child, signal);
+        Some(imp::name(signal))
+    }
+
+    /// Ask `child` to exit, as if the session had been stopped from its terminal
+    pub fn terminate(&self, child: &Child) {
+        imp::terminate(child);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Command;
+
+    #[test]
+    fn sigterm_is_forwarded_to_the_child() {
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let signals = SessionSignals::install();
+        assert_eq!(signals.forward_pending(&child), None);
+
+        // SAFETY: the guard has replaced the default SIGTERM action
+        unsafe {
+            libc::raise(libc::SIGTERM);
+        }
+        assert_eq!(signals.forward_pending(&child), Some("SIGTERM"));
+        drop(signals);
+
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+}
@@ -0,0 +1,284 @@
+//! Multi-line text editor state for the profile description field.
+//!
+//! Lines are wrapped by display width (so CJK and other wide characters take two
+//! columns) and the same wrapping drives both rendering and cursor placement.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::cell::Cell;
+use unicode_width::UnicodeWidthChar;
+
+/// One wrapped row: chars `start..end` of logical line `line`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VisualRow {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextArea {
+    lines: Vec<Vec<char>>,
+    /// (line, char index within the line)
+    cursor: (usize, usize),
+    /// Wrap width from the last render, used for Up/Down between wrapped rows
+    width: Cell<u16>,
+}
+
+impl Default for TextArea {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+fn text_width(chars: &[char]) -> usize {
+    chars.iter().map(|&c| char_width(c)).sum()
+}
+
+impl TextArea {
+    /// Editor holding `text`, with the cursor at the end
+    pub fn new(text: &str) -> Self {
+        let lines: Vec<Vec<char>> = text.split('\n').map(|l| l.chars().collect()).collect();
+        let last = lines.len() - 1;
+        let cursor = (last, lines[last].len());
+        Self {
+            lines,
+            cursor,
+            width: Cell::new(0),
+        }
+    }
+
+    pub fn value(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn line_text(&self, row: &VisualRow) -> String {
+        self.lines[row.line][row.start..row.end].iter().collect()
+    }
+
+    pub fn set_width(&self, width: u16) {
+        self.width.set(width);
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        if c == '\n' {
+            self.insert_newline();
+            return;
+        }
+        let (line, col) = self.cursor;
+        self.lines[line].insert(col, c);
+        self.cursor.1 += 1;
+    }
+
+    /// Insert pasted or IME-composed text; `\r\n` and `\r` count as newlines
+    pub fn insert_str(&mut self, text: &str) {
+        for c in text.replace("\r\n", "\n").replace('\r', "\n").chars() {
+            self.insert_char(c);
+        }
+    }
+
+    pub fn insert_newline(&mut self) {
+        let (line, col) = self.cursor;
+        let rest = self.lines[line].split_off(col);
+        self.lines.insert(line + 1, rest);
+        self.cursor = (line + 1, 0);
+    }
+
+    fn backspace(&mut self) {
+        let (line, col) = self.cursor;
+        if col > 0 {
+            self.lines[line].remove(col - 1);
+            self.cursor.1 -= 1;
+        } else if line > 0 {
+            let rest = self.lines.remove(line);
+            let prev_len = self.lines[line - 1].len();
+            self.lines[line - 1].extend(rest);
+            self.cursor = (line - 1, prev_len);
+        }
+    }
+
+    fn delete(&mut self) {
+        let (line, col) = self.cursor;
+        if col < self.lines[line].len() {
+            self.lines[line].remove(col);
+        } else if line + 1 < self.lines.len() {
+            let next = self.lines.remove(line + 1);
+            self.lines[line].extend(next);
+        }
+    }
+
+    fn move_left(&mut self) {
+        let (line, col) = self.cursor;
+        if col > 0 {
+            self.cursor.1 -= 1;
+        } else if line > 0 {
+            self.cursor = (line - 1, self.lines[line - 1].len());
+        }
+    }
+
+    fn move_right(&mut self) {
+        let (line, col) = self.cursor;
+        if col < self.lines[line].len() {
+            self.cursor.1 += 1;
+        } else if line + 1 < self.lines.len() {
+            self.cursor = (line + 1, 0);
+        }
+    }
+
+    /// Apply an editing key. Returns false for keys the editor does not use.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.insert_char(c)
+            }
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left => self.move_left(),
+            KeyCode::Right => self.move_right(),
+            KeyCode::Home => self.cursor.1 = 0,
+            KeyCode::End => self.cursor.1 = self.lines[self.cursor.0].len(),
+            KeyCode::Char('a') if ctrl => self.cursor.1 = 0,
+            KeyCode::Char('e') if ctrl => self.cursor.1 = self.lines[self.cursor.0].len(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Wrap every line to `width` columns, breaking after whitespace where possible
+    pub fn rows(&self, width: u16) -> Vec<VisualRow> {
+        let width = (width as usize).max(1);
+        let mut rows = Vec::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            let mut start = 0;
+            loop {
+                let mut end = start;
+                let mut used = 0;
+                let mut last_break = None;
+                while end < line.len() {
+                    let w = char_width(line[end]);
+                    if used + w > width && end > start {
+                        break;
+                    }
+                    used += w;
+                    end += 1;
+                    if line[end - 1].is_whitespace() {
+                        last_break = Some(end);
+                    }
+                }
+                if end < line.len()
+                    && let Some(at) = last_break
+                {
+                    end = at;
+                }
+                rows.push(VisualRow {
+                    line: index,
+                    start,
+                    end,
+                });
+                if end >= line.len() {
+                    break;
+                }
+                start = end;
+            }
+        }
+        rows
+    }
+
+    /// Wrapped row holding the cursor; a cursor at a wrap point belongs to the next row
+    fn cursor_row(&self, rows: &[VisualRow]) -> usize {
+        let (line, col) = self.cursor;
+        rows.iter()
+            .rposition(|row| row.line == line && row.start <= col)
+            .unwrap_or(0)
+    }
+
+    /// Cursor as (column, row) in the wrapped text
+    pub fn cursor_position(&self, width: u16) -> (u16, u16) {
+        let rows = self.rows(width);
+        let index = self.cursor_row(&rows);
+        let row = rows[index];
+        let x = text_width(&self.lines[row.line][row.start..self.cursor.1]);
+        (x.min(width.saturating_sub(1) as usize) as u16, index as u16)
+    }
+
+    /// Move to the wrapped row above or below, keeping the column where possible.
+    /// Returns false at the first/last row so the caller can move focus instead.
+    pub fn move_vertical(&mut self, delta: isize) -> bool {
+        let rows = self.rows(self.width.get());
+        let current = self.cursor_row(&rows);
+        let Some(target) = current
+            .checked_add_signed(delta)
+            .filter(|&target| target < rows.len())
+        else {
+            return false;
+        };
+
+        let row = rows[current];
+        let x = text_width(&self.lines[row.line][row.start..self.cursor.1]);
+        let target = rows[target];
+        let line = &self.lines[target.line];
+        let mut col = target.start;
+        let mut used = 0;
+        // Stop before a wrap point so the cursor stays on the target row
+        let last = if target.end < line.len() {
+            target.end - 1
+        } else {
+            target.end
+        };
+        while col < last && used + char_width(line[col]) <= x {
+            used += char_width(line[col]);
+            col += 1;
+        }
+        self.cursor = (target.line, col);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_characters_wrap_and_place_the_cursor_by_width() {
+        let mut text = TextArea::new("日本語のテキスト");
+        let rows = text.rows(6);
+        assert_eq!(
+            rows.iter()
+                .map(|row| text.line_text(row))
+                .collect::<Vec<_>>(),
+            vec!["日本語", "のテキ", "スト"]
+        );
+        assert_eq!(text.cursor_position(6), (4, 2));
+
+        text.set_width(6);
+        assert!(text.move_vertical(-1));
+        assert_eq!(text.cursor_position(6), (4, 1));
+        assert!(text.move_vertical(-1));
+        assert!(!text.move_vertical(-1));
+        assert_eq!(text.cursor_position(6), (4, 0));
+    }
+
+    #[test]
+    fn newlines_split_and_backspace_joins_lines() {
+        let mut text = TextArea::new("first words here");
+        assert_eq!(text.rows(12).len(), 2);
+
+        text.insert_str("\r\nsecond");
+        assert_eq!(text.value(), "first words here\nsecond");
+        assert_eq!(text.cursor_position(40), (6, 1));
+
+        for _ in 0.."second".len() + 1 {
+            text.handle_key(KeyEvent::from(KeyCode::Backspace));
+        }
+        assert_eq!(text.value(), "first words here");
+        assert!(!text.handle_key(KeyEvent::from(KeyCode::Up)));
+    }
+}
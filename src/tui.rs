@@ -1,23 +1,58 @@
 use std::io::{Stdout, stdout};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use anyhow::Result;
 use crossterm::{
-    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    event::{
+        DisableBracketedPaste, EnableBracketedPaste, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    terminal::{
+        EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+        supports_keyboard_enhancement,
+    },
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
 
 pub type Tui = Terminal<CrosstermBackend<Stdout>>;
 
-/// Initialize the terminal for TUI rendering
+/// Whether the terminal answered the keyboard enhancement query, asked on the first
+/// `init` only since terminals that ignore it make the query wait
+static KEYBOARD_ENHANCEMENT: OnceLock<bool> = OnceLock::new();
+
+/// What `init` managed to turn on, so `restore` turns off only that
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+static BRACKETED_PASTE: AtomicBool = AtomicBool::new(false);
+static KEYBOARD_FLAGS: AtomicBool = AtomicBool::new(false);
+
+/// Initialize the terminal for TUI rendering. Features the terminal lacks (keyboard
+/// enhancement, bracketed paste, the alternate screen, truecolor) are done without
+/// rather than failing.
 pub fn init() -> Result<Tui> {
     enable_raw_mode()?;
-    execute!(
-        stdout(),
-        EnterAlternateScreen,
-        PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
-    )?;
+    let keyboard_enhancement =
+        *KEYBOARD_ENHANCEMENT.get_or_init(|| supports_keyboard_enhancement().unwrap_or(false));
+    let mut out = stdout();
+    ALTERNATE_SCREEN.store(
+        execute!(out, EnterAlternateScreen).is_ok(),
+        Ordering::Relaxed,
+    );
+    BRACKETED_PASTE.store(
+        execute!(out, EnableBracketedPaste).is_ok(),
+        Ordering::Relaxed,
+    );
+    KEYBOARD_FLAGS.store(
+        keyboard_enhancement
+            && execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )
+            .is_ok(),
+        Ordering::Relaxed,
+    );
+    crate::display::set_truecolor(crate::display::detect_truecolor());
     let backend = CrosstermBackend::new(stdout());
     let terminal = Terminal::new(backend)?;
     Ok(terminal)
@@ -25,16 +60,27 @@ pub fn init() -> Result<Tui> {
 
 /// Restore terminal to normal state
 pub fn restore() -> Result<()> {
-    execute!(stdout(), PopKeyboardEnhancementFlags, LeaveAlternateScreen)?;
+    let mut out = stdout();
+    if KEYBOARD_FLAGS.swap(false, Ordering::Relaxed) {
+        let _ = execute!(out, PopKeyboardEnhancementFlags);
+    }
+    if BRACKETED_PASTE.swap(false, Ordering::Relaxed) {
+        let _ = execute!(out, DisableBracketedPaste);
+    }
+    if ALTERNATE_SCREEN.swap(false, Ordering::Relaxed) {
+        execute!(out, LeaveAlternateScreen)?;
+    }
     disable_raw_mode()?;
     Ok(())
 }
 
-/// Install a panic hook that restores the terminal before printing the panic
+/// Install a panic hook that restores the terminal before printing the panic, then
+/// unloads the local models the proxy loaded
 pub fn install_panic_hook() {
     let original_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
         let _ = restore();
         original_hook(panic_info);
+        crate::local_models::unload_after_panic();
     }));
 }
@@ -0,0 +1,131 @@
+//! Two profiles' environment variables side by side, with the differences
+//! highlighted: changed values in yellow, variables only one profile sets in green.
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+use super::shown_value;
+use crate::app::App;
+use crate::config::EnvComparison;
+
+/// One side of a row: `KEY = "value"`, or the key dimmed when this profile does not
+/// set it
+fn side_line(app: &App, row: &EnvComparison, value: Option<&str>) -> Line<'static> {
+    let color = match (row.left, row.right) {
+        _ if !row.differs() => Color::Gray,
+        (Some(_), Some(_)) => Color::Yellow,
+        _ => Color::Green,
+    };
+    match value {
+        Some(value) => Line::from(vec![
+            Span::styled(row.key.to_string(), Style::default().fg(color)),
+            Span::raw(" = "),
+            Span::styled(
+                format!(
+                    "\"{}\"",
+                    shown_value(row.key, value, app.config.logging.secrets)
+                ),
+                Style::default().fg(color),
+            ),
+        ]),
+        None => Line::from(Span::styled(
+            format!("{} (not set)", row.key),
+            Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC),
+        )),
+    }
+}
+
+pub fn render_compare(
+    frame: &mut Frame,
+    app: &App,
+    area: Rect,
+    scroll: u16,
+    only_differences: bool,
+) {
+    frame.render_widget(Clear, area);
+    let Some((left, right)) = app.compared_profiles() else {
+        return;
+    };
+
+    let rows = left.env_compare(right);
+    let differing = rows.iter().filter(|row| row.differs()).count();
+    let shown: Vec<&EnvComparison> = rows
+        .iter()
+        .filter(|row| !only_differences || row.differs())
+        .collect();
+    let scroll = scroll.min(shown.len().saturating_sub(1) as u16);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .split(area);
+    let panes = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 2); 2])
+        .split(chunks[0]);
+
+    let sides = [(panes[0], &left.name, true), (panes[1], &right.name, false)];
+    for (pane, name, is_left) in sides {
+        let lines: Vec<Line> = if shown.is_empty() {
+            vec![Line::from(Span::styled(
+                if only_differences {
+                    "No differences"
+                } else {
+                    "No environment variables"
+                },
+                Style::default().fg(Color::DarkGray),
+            ))]
+        } else {
+            shown
+                .iter()
+                .map(|row| {
+                    let value = if is_left { row.left } else { row.right };
+                    side_line(app, row, value)
+                })
+                .collect()
+        };
+        frame.render_widget(
+            Paragraph::new(lines).scroll((scroll, 0)).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" {} ", name))
+                    .style(Style::default().bg(Color::Black)),
+            ),
+            pane,
+        );
+    }
+
+    let summary = format!(
+        " {} of {} variables differ{}",
+        differing,
+        rows.len(),
+        if only_differences {
+            " (showing differences only)"
+        } else {
+            ""
+        }
+    );
+    let help = Line::from(vec![
+        Span::styled(summary, Style::default().fg(Color::Gray)),
+        Span::styled("   ↑/↓", Style::default().fg(Color::Cyan)),
+        Span::styled(
+            " Right-hand profile  ",
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::styled("d", Style::default().fg(Color::Cyan)),
+        Span::styled(" Differences only  ", Style::default().fg(Color::DarkGray)),
+        Span::styled("Esc", Style::default().fg(Color::Cyan)),
+        Span::styled(" Close", Style::default().fg(Color::DarkGray)),
+    ]);
+    frame.render_widget(
+        Paragraph::new(help).style(Style::default().bg(Color::Black)),
+        chunks[1],
+    );
+}
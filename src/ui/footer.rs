@@ -0,0 +1,280 @@
+//! Footer with the key hints for the current mode, cut to fit the terminal width.
+
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::{
+    App, AppMode, EDIT_FIELD_API_KEY, EDIT_FIELD_DESCRIPTION, EDIT_FIELD_ENV, EDIT_FIELD_HAIKU,
+    EDIT_FIELD_OPUS, EDIT_FIELD_SONNET,
+};
+
+/// Gap between two hints
+const SEPARATOR: &str = "  ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hint {
+    key: &'static str,
+    label: &'static str,
+}
+
+const fn hint(key: &'static str, label: &'static str) -> Hint {
+    Hint { key, label }
+}
+
+impl Hint {
+    /// Columns taken by `[key] label`
+    fn width(&self) -> usize {
+        self.key.width() + self.label.width() + 3
+    }
+}
+
+/// Shown last when `?` opens the help popup
+const HELP: Hint = hint("?", "Help");
+/// Replaces the hints that did not fit when `?` opens the help popup
+const MORE: Hint = hint("?", "More");
+/// Marks dropped hints in modes without a help popup
+const ELLIPSIS: &str = "…";
+
+/// Hints for the current mode, most important first
+fn mode_hints(app: &App) -> Vec<Hint> {
+    match app.mode {
+        AppMode::Normal => vec![
+            hint("^/v", "Navigate"),
+            hint("Enter", "Launch"),
+            hint("e", "Edit"),
+            hint("n", "New"),
+            hint("N", "From shell"),
+            hint("c", "Duplicate"),
+            hint("q", "Quit"),
+            hint("i", "Notes"),
+            hint("w", "Workspace"),
+            hint("v", "View"),
+            hint("o", "Console"),
+            hint("l", "Logs"),
+            hint("I", "Inspector"),
+            hint("/", "Filter"),
+            hint("h", "Health"),
+            hint("u", "Usage"),
+            hint("C", "Compare"),
+            hint("t", "Tour"),
+            hint("d", "Delete"),
+            if app.is_selected_profile_codex() {
+                hint("r", "Reset OAuth")
+            } else {
+                hint("r", "Reset")
+            },
+            hint("R", "Reset All"),
+        ],
+        AppMode::Help => vec![hint("Any key", "Close")],
+        AppMode::TagFilter => vec![
+            hint("Type", "Tags"),
+            hint("Enter", "Apply"),
+            hint("Esc", "Clear"),
+        ],
+        AppMode::Notes { .. } => vec![
+            hint("Esc", "Close"),
+            hint("^/v", "Scroll"),
+            hint("PgUp/PgDn", "Page"),
+        ],
+        AppMode::Logs { .. } => vec![
+            hint("Esc", "Close"),
+            hint("^/v", "Scroll"),
+            hint("f", "Filter"),
+            hint("End", "Follow"),
+            hint("i", "Inspector"),
+        ],
+        AppMode::Inspector => vec![
+            hint("Esc", "Close"),
+            hint("^/v", "Select"),
+            hint("r", "Replay"),
+            hint("PgUp/PgDn", "Scroll"),
+        ],
+        AppMode::Usage => vec![hint("Esc", "Close")],
+        AppMode::Compare { .. } => vec![
+            hint("Esc", "Close"),
+            hint("^/v", "Other profile"),
+            hint("d", "Differences only"),
+            hint("PgUp/PgDn", "Scroll"),
+        ],
+        AppMode::ConfirmCost => vec![hint("y", "Launch"), hint("n", "Cancel")],
+        AppMode::ConfirmResetOAuth => vec![hint("y", "Sign out"), hint("n", "Cancel")],
+        AppMode::ImportDetected => vec![hint("y", "Create"), hint("n", "Skip")],
+        AppMode::UnloadLeftoverModels => vec![hint("y", "Unload"), hint("n", "Keep loaded")],
+        AppMode::TrustWorkspace => vec![hint("y", "Trust"), hint("n", "Ignore settings")],
+        AppMode::Tutorial { .. } => vec![
+            hint("Enter", "Next"),
+            hint("<-", "Back"),
+            hint("Esc", "Skip"),
+        ],
+        AppMode::EditProfile { focused_field, .. } => {
+            let is_model_field = matches!(
+                focused_field,
+                EDIT_FIELD_HAIKU | EDIT_FIELD_SONNET | EDIT_FIELD_OPUS
+            );
+            if is_model_field && app.has_model_picker() {
+                return vec![
+                    hint("Enter", "Pick Model"),
+                    hint("Esc", "Cancel"),
+                    hint("Tab", "Switch"),
+                ];
+            }
+            let mut hints = vec![
+                hint("Enter", "Save"),
+                hint("Esc", "Cancel"),
+                hint("Tab", "Switch"),
+            ];
+            match focused_field {
+                EDIT_FIELD_DESCRIPTION => hints.push(hint("Alt+Enter", "Newline")),
+                EDIT_FIELD_API_KEY => {
+                    hints.push(if app.reveal_api_key {
+                        hint("Ctrl+G", "Hide")
+                    } else {
+                        hint("Ctrl+G", "Reveal")
+                    });
+                    hints.push(hint("Ctrl+K", "Keychain"));
+                }
+                EDIT_FIELD_ENV => {
+                    hints.push(hint("Ctrl+N", "Add Variable"));
+                    hints.push(hint("Ctrl+D", "Remove"));
+                }
+                _ => {}
+            }
+            hints.push(if app.show_advanced {
+                hint("Ctrl+A", "Hide Advanced")
+            } else {
+                hint("Ctrl+A", "Advanced")
+            });
+            hints
+        }
+        AppMode::ModelPicker { .. } => vec![
+            hint("Enter", "Select"),
+            hint("Esc", "Cancel"),
+            hint("^/v", "Navigate"),
+            hint("Type", "Filter"),
+            hint("Ctrl+R", "Refresh"),
+        ],
+    }
+}
+
+/// Columns taken by `hints` joined with separators, plus an optional trailing marker
+fn line_width(hints: &[Hint], tail: Option<usize>) -> usize {
+    let widths: Vec<usize> = hints.iter().map(Hint::width).chain(tail).collect();
+    widths.iter().sum::<usize>() + SEPARATOR.len() * widths.len().saturating_sub(1)
+}
+
+/// Leading hints that fit in `width` columns, and whether any were dropped. With
+/// `help`, room is kept for a trailing `[?] Help` / `[?] More`.
+fn fit_hints(hints: &[Hint], width: usize, help: bool) -> (&[Hint], bool) {
+    if line_width(hints, help.then(|| HELP.width())) <= width {
+        return (hints, false);
+    }
+    let tail = if help { MORE.width() } else { ELLIPSIS.width() };
+    let mut count = hints.len();
+    while count > 0 && line_width(&hints[..count], Some(tail)) > width {
+        count -= 1;
+    }
+    (&hints[..count], true)
+}
+
+fn hint_spans(hint: &Hint) -> [Span<'static>; 3] {
+    [
+        Span::styled("[", Style::default().fg(Color::DarkGray)),
+        Span::styled(hint.key, Style::default().fg(Color::Cyan)),
+        Span::styled(
+            format!("] {}", hint.label),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ]
+}
+
+fn hint_line(app: &App, width: usize) -> Line<'static> {
+    let hints = mode_hints(app);
+    let help = app.mode == AppMode::Normal;
+    let (shown, elided) = fit_hints(&hints, width, help);
+
+    let mut spans = Vec::new();
+    for (i, hint) in shown.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(SEPARATOR));
+        }
+        spans.extend(hint_spans(hint));
+    }
+    let tail = match (help, elided) {
+        (true, false) => Some(hint_spans(&HELP).to_vec()),
+        (true, true) => Some(hint_spans(&MORE).to_vec()),
+        (false, true) => Some(vec![Span::styled(
+            ELLIPSIS,
+            Style::default().fg(Color::DarkGray),
+        )]),
+        (false, false) => None,
+    };
+    if let Some(tail) = tail {
+        if !shown.is_empty() {
+            spans.push(Span::raw(SEPARATOR));
+        }
+        spans.extend(tail);
+    }
+    Line::from(spans)
+}
+
+pub fn render_footer(frame: &mut Frame, area: Rect, app: &App) {
+    let footer_text = if let Some(ref msg) = app.status_message {
+        let msg_lower = msg.to_ascii_lowercase();
+        let is_error = msg_lower.contains("failed") || msg_lower.contains("error");
+        let (label, color) = if is_error {
+            ("Error: ", Color::Red)
+        } else {
+            ("Success: ", Color::Green)
+        };
+        Line::from(vec![
+            Span::styled(label, Style::default().fg(color)),
+            Span::raw(msg.clone()),
+            Span::raw(" (press any key to clear)"),
+        ])
+    } else {
+        hint_line(app, area.width as usize)
+    };
+
+    let footer = Paragraph::new(footer_text).block(Block::default().borders(Borders::TOP));
+    frame.render_widget(footer, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(line: &Line) -> String {
+        line.spans
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn hints_elide_to_the_width_behind_a_help_fallback() {
+        let mut app = App::new(crate::config::Config::create_default());
+        let full = text(&hint_line(&app, 500));
+        assert!(full.starts_with("[^/v] Navigate  [Enter] Launch"));
+        assert!(full.ends_with("[R] Reset All  [?] Help"));
+
+        let narrow = text(&hint_line(&app, 40));
+        assert_eq!(narrow, "[^/v] Navigate  [Enter] Launch  [?] More");
+        assert!(narrow.width() <= 40);
+        assert_eq!(text(&hint_line(&app, 5)), "[?] More");
+
+        app.mode = AppMode::EditProfile {
+            focused_field: EDIT_FIELD_DESCRIPTION,
+            is_creating: true,
+        };
+        let edit = text(&hint_line(&app, 500));
+        assert!(edit.contains("[Alt+Enter] Newline"));
+        assert!(!edit.contains("[?]"));
+        assert_eq!(text(&hint_line(&app, 30)), "[Enter] Save  [Esc] Cancel  …");
+    }
+}
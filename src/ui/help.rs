@@ -48,6 +48,123 @@ pub fn render_help_popup(frame: &mut Frame, area: Rect) {
             ),
             Span::raw("Toggle this help"),
         ]),
+        Line::from(vec![
+            Span::styled(
+                "  t  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Replay the guided tour"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  w  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Use selected profile for this directory"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  v  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Toggle compact list view"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  i  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Read the selected profile's notes"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  o  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Open the provider's API key page"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  l  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Show the proxy request log"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  I  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Inspect captured requests and replay them"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  u  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Show token usage and cost per profile, model and day"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  C  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Compare the env of two profiles side by side"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  c  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Duplicate the selected profile and edit the copy"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  N  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("New profile from the ANTHROPIC_*/CLAUDE_* variables of this shell"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  /  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Filter profiles by tag (Esc clears)"),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  h  ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("Check every profile's upstream again"),
+        ]),
         Line::from(vec![
             Span::styled(
                 "  e  ",
@@ -82,7 +199,7 @@ pub fn render_help_popup(frame: &mut Frame, area: Rect) {
                     .fg(Color::Cyan)
                     .add_modifier(Modifier::BOLD),
             ),
-            Span::raw("Reset selected profile to defaults"),
+            Span::raw("Reset selected profile to defaults (Codex: sign out of ChatGPT)"),
         ]),
         Line::from(vec![
             Span::styled(
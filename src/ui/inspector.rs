@@ -0,0 +1,189 @@
+//! Request inspector: captured requests on top, and the selected one's bodies side by
+//! side below, as Claude Code sent it, as sent upstream and as the upstream answered.
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+
+use super::logs::clock;
+use crate::app::App;
+use crate::proxy::Capture;
+
+/// Capture rows shown above the bodies
+const LIST_ROWS: u16 = 8;
+
+/// A body as indented JSON when it is JSON, as received otherwise (event streams)
+fn pretty(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| serde_json::to_string_pretty(&value).ok())
+        .unwrap_or_else(|| body.to_string())
+}
+
+fn status_color(status: u16) -> Color {
+    if (200..300).contains(&status) {
+        Color::Green
+    } else {
+        Color::Red
+    }
+}
+
+fn capture_line(capture: &Capture) -> Line<'static> {
+    let mut spans = vec![
+        Span::styled(
+            format!("{}  ", clock(capture.timestamp)),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::styled(
+            format!("{:<14} ", capture.profile),
+            Style::default().fg(Color::Cyan),
+        ),
+        Span::styled(
+            format!("{:>3} ", capture.status),
+            Style::default().fg(status_color(capture.status)),
+        ),
+        Span::raw(format!(
+            "{} -> {}",
+            capture.model().unwrap_or_else(|| "?".to_string()),
+            capture.upstream_model().unwrap_or_else(|| "-".to_string())
+        )),
+    ];
+    if capture.replay_of.is_some() {
+        spans.push(Span::styled(
+            "  (replay)",
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    Line::from(spans)
+}
+
+fn body_pane(frame: &mut Frame, area: Rect, title: String, body: String, scroll: u16) {
+    let pane = Paragraph::new(body)
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(pane, area);
+}
+
+pub fn render_inspector(frame: &mut Frame, app: &App, area: Rect) {
+    frame.render_widget(Clear, area);
+    let inspector = &app.inspector;
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(LIST_ROWS + 2),
+            Constraint::Min(4),
+            Constraint::Length(1),
+        ])
+        .split(area);
+
+    let rows = LIST_ROWS as usize;
+    let first = inspector.selected.saturating_sub(rows - 1);
+    let list: Vec<Line> = if inspector.captures.is_empty() {
+        vec![Line::from(Span::styled(
+            "No requests captured yet. Set PROXY_REQUEST_CAPTURE=1 in a proxied profile's env.",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        inspector
+            .captures
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(index, capture)| {
+                let line = capture_line(capture);
+                if index == inspector.selected {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            })
+            .collect()
+    };
+    frame.render_widget(
+        Paragraph::new(list).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    " Inspector ({} captured) ",
+                    inspector.captures.len()
+                ))
+                .style(Style::default().bg(Color::Black)),
+        ),
+        chunks[0],
+    );
+
+    if let Some(capture) = inspector.selected() {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 3); 3])
+            .split(chunks[1]);
+        body_pane(
+            frame,
+            panes[0],
+            " Claude Code request ".to_string(),
+            pretty(&capture.request),
+            inspector.scroll,
+        );
+        body_pane(
+            frame,
+            panes[1],
+            format!(
+                " Upstream request: {} ",
+                capture.upstream_url.as_deref().unwrap_or("not sent")
+            ),
+            capture
+                .upstream_request
+                .as_deref()
+                .map(pretty)
+                .unwrap_or_default(),
+            inspector.scroll,
+        );
+        body_pane(
+            frame,
+            panes[2],
+            format!(
+                " Upstream response: {} ",
+                capture
+                    .upstream_status
+                    .map_or("none".to_string(), |status| status.to_string())
+            ),
+            format!(
+                "{}\n\n--- returned to Claude Code (HTTP {}) ---\n{}",
+                pretty(&capture.upstream_response),
+                capture.status,
+                pretty(&capture.response)
+            ),
+            inspector.scroll,
+        );
+    }
+
+    let mut help = vec![
+        Span::styled(" ↑/↓", Style::default().fg(Color::Cyan)),
+        Span::styled(" Select  ", Style::default().fg(Color::DarkGray)),
+        Span::styled("PgUp/PgDn", Style::default().fg(Color::Cyan)),
+        Span::styled(" Scroll  ", Style::default().fg(Color::DarkGray)),
+        Span::styled("r", Style::default().fg(Color::Cyan)),
+        Span::styled(" Replay  ", Style::default().fg(Color::DarkGray)),
+        Span::styled("Esc", Style::default().fg(Color::Cyan)),
+        Span::styled(" Close  ", Style::default().fg(Color::DarkGray)),
+    ];
+    if let Some(status) = &inspector.status {
+        let color = if inspector.is_replaying() {
+            Color::Yellow
+        } else {
+            Color::Gray
+        };
+        help.push(Span::styled(status.clone(), Style::default().fg(color)));
+    }
+    frame.render_widget(
+        Paragraph::new(Line::from(help)).style(Style::default().bg(Color::Black)),
+        chunks[2],
+    );
+}
@@ -0,0 +1,113 @@
+//! Request log viewer: one row per proxied request, newest at the bottom.
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+use crate::app::App;
+use crate::proxy::RequestLogEntry;
+
+/// `hh:mm:ss` (UTC) of a unix timestamp
+pub(super) fn clock(timestamp: u64) -> String {
+    let secs = timestamp % 86400;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn tokens(value: Option<u32>) -> String {
+    value.map_or("-".to_string(), |v| v.to_string())
+}
+
+fn entry_line(entry: &RequestLogEntry) -> Line<'static> {
+    let status_color = if (200..300).contains(&entry.status) {
+        Color::Green
+    } else {
+        Color::Red
+    };
+    Line::from(vec![
+        Span::styled(
+            format!("{}  ", clock(entry.timestamp)),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::styled(
+            format!("{:<14} ", entry.profile),
+            Style::default().fg(Color::Cyan),
+        ),
+        Span::styled(
+            format!("{:>3} ", entry.status),
+            Style::default().fg(status_color),
+        ),
+        Span::raw(format!("{:>7}ms ", entry.latency_ms)),
+        Span::styled(
+            format!(
+                "{:>7} in {:>6} out  ",
+                tokens(entry.input_tokens),
+                tokens(entry.output_tokens)
+            ),
+            Style::default().fg(Color::Gray),
+        ),
+        Span::raw(format!(
+            "{} -> {} ({}{})",
+            entry.model,
+            entry.upstream_model,
+            entry.api,
+            if entry.stream { ", stream" } else { "" }
+        )),
+    ])
+}
+
+pub fn render_logs(frame: &mut Frame, app: &App, area: Rect, back: usize) {
+    frame.render_widget(Clear, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(area);
+
+    let entries = app.visible_log_entries();
+    let rows = chunks[0].height.saturating_sub(2) as usize;
+    let end = entries.len().saturating_sub(back);
+    let text: Vec<Line> = if entries.is_empty() {
+        vec![Line::from(Span::styled(
+            "No requests logged yet. Set PROXY_REQUEST_LOG=1 in a proxied profile's env.",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        entries[end.saturating_sub(rows)..end]
+            .iter()
+            .map(|entry| entry_line(entry))
+            .collect()
+    };
+
+    let scope = app.log_filter.as_deref().unwrap_or("all profiles");
+    let position = if back == 0 {
+        "following".to_string()
+    } else {
+        format!("{} newer below", back)
+    };
+    let log = Paragraph::new(text).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Request log: {} ({}) ", scope, position))
+            .style(Style::default().bg(Color::Black)),
+    );
+    frame.render_widget(log, chunks[0]);
+
+    let help = Line::from(vec![
+        Span::styled(" ↑/↓ PgUp/PgDn", Style::default().fg(Color::Cyan)),
+        Span::styled(" Scroll  ", Style::default().fg(Color::DarkGray)),
+        Span::styled("End", Style::default().fg(Color::Cyan)),
+        Span::styled(" Follow  ", Style::default().fg(Color::DarkGray)),
+        Span::styled("f", Style::default().fg(Color::Cyan)),
+        Span::styled(" Filter  ", Style::default().fg(Color::DarkGray)),
+        Span::styled("Esc", Style::default().fg(Color::Cyan)),
+        Span::styled(" Close", Style::default().fg(Color::DarkGray)),
+    ]);
+    frame.render_widget(
+        Paragraph::new(help).style(Style::default().bg(Color::Black)),
+        chunks[1],
+    );
+}
@@ -1,26 +1,49 @@
+mod compare;
+mod footer;
 mod help;
+mod inspector;
+mod logs;
 mod profile_list;
+mod tutorial;
+mod usage;
 
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
 };
 use std::borrow::Cow;
 
 use crate::app::{
-    App, AppMode, EDIT_FIELD_API_KEY, EDIT_FIELD_DESCRIPTION, EDIT_FIELD_HAIKU, EDIT_FIELD_NAME,
-    EDIT_FIELD_OPUS, EDIT_FIELD_PROXY_URL, EDIT_FIELD_SONNET, EDIT_FIELD_URL,
+    App, AppMode, EDIT_FIELD_API_KEY, EDIT_FIELD_DESCRIPTION, EDIT_FIELD_ENV, EDIT_FIELD_HAIKU,
+    EDIT_FIELD_HAIKU_SAMPLING, EDIT_FIELD_NAME, EDIT_FIELD_OPUS, EDIT_FIELD_OPUS_SAMPLING,
+    EDIT_FIELD_PROXY_URL, EDIT_FIELD_SONNET, EDIT_FIELD_SONNET_SAMPLING, EDIT_FIELD_URL,
 };
 use crate::config::{
-    ENV_AUTH_TOKEN, ENV_BASE_URL, ENV_DEFAULT_HAIKU_MODEL, ENV_DEFAULT_OPUS_MODEL,
-    ENV_DEFAULT_SONNET_MODEL, ENV_PROXY_TARGET_URL,
+    Date, ENV_AUTH_TOKEN, ENV_BASE_URL, ENV_DEFAULT_HAIKU_MODEL, ENV_DEFAULT_OPUS_MODEL,
+    ENV_DEFAULT_SONNET_MODEL, ENV_HAIKU_SAMPLING, ENV_OPUS_SAMPLING, ENV_PROXY_TARGET_URL,
+    ENV_SONNET_SAMPLING, KeyExpiry, keychain_reference,
 };
+use crate::health::Health;
+use crate::proxy;
+use crate::redact::{SecretMask, is_sensitive_key};
 
+pub use compare::render_compare;
+pub use footer::render_footer;
 pub use help::render_help_popup;
+pub use inspector::render_inspector;
+pub use logs::render_logs;
 pub use profile_list::render_profile_list;
+pub use tutorial::{TutorialAreas, render_tutorial};
+pub use usage::render_usage;
+
+/// Description rows shown before the field scrolls
+const DESCRIPTION_MAX_ROWS: usize = 6;
+
+/// Env editor rows shown before it scrolls
+const ENV_EDITOR_MAX_ROWS: usize = 6;
 
 /// Main UI rendering function
 pub fn render(frame: &mut Frame, app: &mut App) {
@@ -42,6 +65,86 @@ pub fn render(frame: &mut Frame, app: &mut App) {
     render_details(frame, app, chunks[4]);
     render_footer(frame, chunks[5], app);
 
+    // Overlay the guided tour, keeping its popup above the region it highlights
+    if let AppMode::Tutorial { step } = app.mode {
+        let areas = TutorialAreas {
+            profile_list: chunks[3],
+            details: chunks[4],
+            footer: chunks[5],
+        };
+        let top = Rect {
+            height: chunks[2].y.saturating_sub(frame.area().y),
+            ..frame.area()
+        };
+        let popup = centered_rect(60, 60, top);
+        render_tutorial(frame, step, &areas, popup);
+    }
+
+    // Overlay the first-run offer for detected API keys
+    if app.mode == AppMode::ImportDetected {
+        let area = centered_rect(60, 50, frame.area());
+        render_import_detected(frame, app, area);
+    }
+
+    // Overlay the offer to unload models a crashed run left loaded
+    if app.mode == AppMode::UnloadLeftoverModels {
+        let area = centered_rect(60, 40, frame.area());
+        render_leftover_models(frame, app, area);
+    }
+
+    // Overlay the trust prompt for a new or changed pin file
+    if app.mode == AppMode::TrustWorkspace {
+        let area = centered_rect(60, 50, frame.area());
+        render_trust_workspace(frame, app, area);
+    }
+
+    // Overlay the cost confirmation for an expensive launch
+    if app.mode == AppMode::ConfirmCost {
+        let area = centered_rect(60, 40, frame.area());
+        render_confirm_cost(frame, app, area);
+    }
+
+    // Overlay the sign-out confirmation for Codex profiles
+    if app.mode == AppMode::ConfirmResetOAuth {
+        let area = centered_rect(60, 30, frame.area());
+        render_confirm_reset_oauth(frame, area);
+    }
+
+    // Overlay profile notes
+    if let AppMode::Notes { scroll } = app.mode {
+        let area = centered_rect(70, 70, frame.area());
+        render_notes(frame, app, area, scroll);
+    }
+
+    // Overlay the request log viewer
+    if let AppMode::Logs { back } = app.mode {
+        let area = centered_rect(90, 80, frame.area());
+        render_logs(frame, app, area, back);
+    }
+
+    // Overlay the request inspector
+    if app.mode == AppMode::Inspector {
+        let area = centered_rect(95, 90, frame.area());
+        render_inspector(frame, app, area);
+    }
+
+    // Overlay the usage dashboard
+    if app.mode == AppMode::Usage {
+        let area = centered_rect(90, 85, frame.area());
+        render_usage(frame, app, area);
+    }
+
+    // Overlay the profile comparison
+    if let AppMode::Compare {
+        scroll,
+        only_differences,
+        ..
+    } = app.mode
+    {
+        let area = centered_rect(95, 85, frame.area());
+        render_compare(frame, app, area, scroll, only_differences);
+    }
+
     // Overlay help if in help mode
     if app.mode == AppMode::Help {
         let area = centered_rect(60, 50, frame.area());
@@ -69,6 +172,9 @@ pub fn render(frame: &mut Frame, app: &mut App) {
         let picker_area = centered_rect(50, 60, frame.area());
         render_model_picker(frame, app, picker_area);
     }
+
+    // High contrast and ASCII-only rendering apply to whatever was drawn
+    crate::display::adjust_buffer(frame.buffer_mut());
 }
 
 fn title_height_for_width(_w: u16, _h: u16) -> u16 {
@@ -139,33 +245,170 @@ fn render_title(frame: &mut Frame, area: Rect) {
 
 fn render_details(frame: &mut Frame, app: &App, area: Rect) {
     let content = if let Some(profile) = app.current_profile() {
+        let mut lines = Vec::new();
+        if let Some((dir, workspace_profile)) = app.workspace_profile() {
+            let hint = if workspace_profile == profile.name {
+                " (Enter to launch)"
+            } else {
+                ""
+            };
+            lines.push(Line::from(vec![
+                Span::styled("Workspace ", Style::default().fg(Color::Cyan)),
+                Span::styled(dir.to_string(), Style::default().fg(Color::DarkGray)),
+                Span::raw(" -> "),
+                Span::styled(
+                    workspace_profile.to_string(),
+                    Style::default().fg(Color::Green),
+                ),
+                Span::styled(hint, Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+        if let Some(pinned) = &app.workspace_pin
+            && !pinned.allows(&profile.name)
+        {
+            lines.push(Line::from(vec![
+                Span::styled("Pinned ", Style::default().fg(Color::Cyan)),
+                Span::styled(
+                    format!("not allowed by {}", pinned.path.display()),
+                    Style::default().fg(Color::Red),
+                ),
+            ]));
+        }
+        if let Some(result) = app.health.status(profile) {
+            let (status, color) = match &result.health {
+                Health::Up { latency } => (
+                    format!("reachable ({} ms)", latency.as_millis()),
+                    Color::Green,
+                ),
+                Health::Down(reason) => (format!("unreachable: {}", reason), Color::Red),
+                Health::Checking => ("checking...".to_string(), Color::Yellow),
+            };
+            let mut line = Line::from(vec![
+                Span::styled("Health ", Style::default().fg(Color::Cyan)),
+                Span::styled(status, Style::default().fg(color)),
+            ]);
+            if result.health != Health::Checking {
+                line.push_span(Span::styled(
+                    format!(
+                        " ({}s ago, h to recheck)",
+                        result.checked_at.elapsed().as_secs()
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            lines.push(line);
+        }
+        if !profile.tags.is_empty() {
+            lines.push(Line::from(vec![
+                Span::styled("Tags ", Style::default().fg(Color::Cyan)),
+                Span::raw(profile.tags.join(", ")),
+            ]));
+        }
+        if let Some(date) = profile.key_expires_on {
+            let expiry = profile.key_expiry(Date::today());
+            let text = match &expiry {
+                Some(KeyExpiry::Expired { .. }) => format!("{} (expired, rotate the key)", date),
+                Some(expiry) => format!("{} ({})", date, expiry.label()),
+                None => date.to_string(),
+            };
+            let style = match &expiry {
+                Some(expiry) => Style::default().fg(profile_list::expiry_color(expiry)),
+                None => Style::default(),
+            };
+            lines.push(Line::from(vec![
+                Span::styled("Key expires ", Style::default().fg(Color::Cyan)),
+                Span::styled(text, style),
+            ]));
+        }
+        if let Some(first_line) = profile.notes.lines().find(|l| !l.trim().is_empty()) {
+            lines.push(Line::from(vec![
+                Span::styled("Notes ", Style::default().fg(Color::Cyan)),
+                Span::raw(first_line.trim().to_string()),
+                Span::styled(" (i to read)", Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+        for (name, value) in &profile.headers {
+            lines.push(Line::from(vec![
+                Span::styled("Header ", Style::default().fg(Color::Cyan)),
+                Span::raw(format!(
+                    "{}: {}",
+                    name,
+                    shown_value(name, value, app.config.logging.secrets)
+                )),
+            ]));
+        }
+        for (pattern, model) in &profile.model_map {
+            lines.push(Line::from(vec![
+                Span::styled("Model ", Style::default().fg(Color::Cyan)),
+                Span::raw(format!("{} -> {}", pattern, model)),
+            ]));
+        }
+        for (pattern, route) in &profile.routes {
+            let model = route.model.as_deref().unwrap_or("same model");
+            lines.push(Line::from(vec![
+                Span::styled("Route ", Style::default().fg(Color::Cyan)),
+                Span::raw(format!("{} -> {} ({})", pattern, route.url, model)),
+            ]));
+        }
+        for upstream in &profile.fanout {
+            let model = upstream.model.as_deref().unwrap_or("same model");
+            lines.push(Line::from(vec![
+                Span::styled("Fan-out ", Style::default().fg(Color::Cyan)),
+                Span::raw(format!("sub-agents -> {} ({})", upstream.url, model)),
+            ]));
+        }
+        for (name, server) in &profile.mcp {
+            let via = server.url.as_deref().or(server.command.as_deref());
+            lines.push(Line::from(vec![
+                Span::styled("MCP ", Style::default().fg(Color::Cyan)),
+                Span::raw(format!("{} ({})", name, via.unwrap_or("incomplete"))),
+            ]));
+        }
+        if let Some(target) = profile.proxy_target_url() {
+            lines.push(Line::from(vec![
+                Span::styled("Proxy ", Style::default().fg(Color::Cyan)),
+                Span::styled(
+                    proxy::ListenAddr::new(profile.proxy_bind.as_deref(), profile.proxy_port)
+                        .anthropic_url(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(" -> "),
+                Span::styled(target.to_string(), Style::default().fg(Color::Green)),
+            ]));
+        }
+        if app.is_selected_profile_codex()
+            && let Some(quota) = &app.codex_quota
+        {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            lines.push(Line::from(vec![
+                Span::styled("Codex quota ", Style::default().fg(Color::Cyan)),
+                Span::raw(quota.summary(now)),
+            ]));
+        }
         if profile.env.is_empty() {
-            vec![Line::from(Span::styled(
+            lines.push(Line::from(Span::styled(
                 "No environment variables (uses existing environment)",
                 Style::default().fg(Color::DarkGray),
-            ))]
+            )));
         } else {
             let mut env_items: Vec<(&String, &String)> = profile.env.iter().collect();
-            env_items.sort_by(|(a, _), (b, _)| a.cmp(b));
-            env_items
-                .into_iter()
-                .map(|(key, value)| {
-                    let display_value = if is_sensitive_key(key) {
-                        mask_value(value)
-                    } else {
-                        value.to_string()
-                    };
-                    Line::from(vec![
-                        Span::styled(key.as_str(), Style::default().fg(Color::Yellow)),
-                        Span::raw(" = "),
-                        Span::styled(
-                            format!("\"{}\"", display_value),
-                            Style::default().fg(Color::Green),
-                        ),
-                    ])
-                })
-                .collect()
+            env_items.sort_by_key(|(key, _)| *key);
+            lines.extend(env_items.into_iter().map(|(key, value)| {
+                let display_value = shown_value(key, value, app.config.logging.secrets);
+                Line::from(vec![
+                    Span::styled(key.as_str(), Style::default().fg(Color::Yellow)),
+                    Span::raw(" = "),
+                    Span::styled(
+                        format!("\"{}\"", display_value),
+                        Style::default().fg(Color::Green),
+                    ),
+                ])
+            }));
         }
+        lines
     } else {
         vec![Line::from("No profile selected")]
     };
@@ -178,54 +421,269 @@ fn render_details(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(details, area);
 }
 
-fn render_footer(frame: &mut Frame, area: Rect, app: &App) {
-    let footer_text = if let Some(ref msg) = app.status_message {
-        let msg_lower = msg.to_ascii_lowercase();
-        let is_error = msg_lower.contains("failed") || msg_lower.contains("error");
-        let (label, color) = if is_error {
-            ("Error: ", Color::Red)
-        } else {
-            ("Success: ", Color::Green)
-        };
+fn render_notes(frame: &mut Frame, app: &App, area: Rect, scroll: u16) {
+    frame.render_widget(Clear, area);
+
+    let Some(profile) = app.current_profile() else {
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(area);
+
+    let text: Vec<Line> = if profile.notes.trim().is_empty() {
+        vec![Line::from(Span::styled(
+            "No notes yet. Add a `notes` field to this profile in profiles.toml.",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        profile.notes.lines().map(Line::from).collect()
+    };
+
+    let notes = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Notes: {} ", profile.name))
+                .style(Style::default().bg(Color::Black)),
+        )
+        .style(Style::default().bg(Color::Black));
+    frame.render_widget(notes, chunks[0]);
+
+    let help = Line::from(vec![
+        Span::styled(" ↑/↓ PgUp/PgDn", Style::default().fg(Color::Cyan)),
+        Span::styled(" Scroll  ", Style::default().fg(Color::DarkGray)),
+        Span::styled("Esc", Style::default().fg(Color::Cyan)),
+        Span::styled(" Close", Style::default().fg(Color::DarkGray)),
+    ]);
+    frame.render_widget(
+        Paragraph::new(help).style(Style::default().bg(Color::Black)),
+        chunks[1],
+    );
+}
+
+fn render_import_detected(frame: &mut Frame, app: &App, area: Rect) {
+    frame.render_widget(Clear, area);
+
+    let mut text = vec![
+        Line::from("API keys found in your environment:"),
+        Line::from(""),
+    ];
+    for (var, profile) in &app.detected_profiles {
+        text.push(Line::from(vec![
+            Span::styled(format!("  {:<20}", var), Style::default().fg(Color::Cyan)),
+            Span::raw(" -> "),
+            Span::styled(
+                profile.name.as_str(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        ]));
+    }
+    text.extend([
+        Line::from(""),
+        Line::from(
+            "Create a ready-to-use profile for each? The profiles read the key from the \
+             environment at launch; it is not written to profiles.toml.",
+        ),
+        Line::from(""),
         Line::from(vec![
-            Span::styled(label, Style::default().fg(color)),
-            Span::raw(msg),
-            Span::raw(" (press any key to clear)"),
-        ])
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(" Create  "),
+            Span::styled("n", Style::default().fg(Color::Cyan)),
+            Span::raw(" Skip"),
+        ]),
+    ]);
+
+    let popup = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Set up profiles ")
+            .style(Style::default().bg(Color::Black)),
+    );
+    frame.render_widget(popup, area);
+}
+
+fn render_leftover_models(frame: &mut Frame, app: &App, area: Rect) {
+    frame.render_widget(Clear, area);
+
+    let mut text = vec![
+        Line::from("An earlier claude-profiler run ended without unloading:"),
+        Line::from(""),
+    ];
+    for lease in &app.leftover_models {
+        text.push(Line::from(vec![
+            Span::styled(
+                format!("  {}", lease.model),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" on "),
+            Span::styled(lease.backend.label(), Style::default().fg(Color::Cyan)),
+            Span::styled(
+                format!(" ({})", lease.target_url),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+    }
+    text.extend([
+        Line::from(""),
+        Line::from("Unload them now to free their memory?"),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(" Unload  "),
+            Span::styled("n", Style::default().fg(Color::Cyan)),
+            Span::raw(" Keep loaded"),
+        ]),
+    ]);
+
+    let popup = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Leftover models ")
+            .style(Style::default().bg(Color::Black)),
+    );
+    frame.render_widget(popup, area);
+}
+
+fn render_trust_workspace(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(pinned) = &app.workspace_pin else {
+        return;
+    };
+    frame.render_widget(Clear, area);
+
+    let allowed = if pinned.pin.profiles.is_empty() {
+        "any profile".to_string()
     } else {
+        pinned.pin.profiles.join(", ")
+    };
+    let mut text = vec![
+        Line::from(Span::styled(
+            pinned.path.display().to_string(),
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Allowed  ", Style::default().fg(Color::Cyan)),
+            Span::raw(allowed),
+        ]),
+    ];
+    let mut settings: Vec<_> = pinned.pin.env.iter().collect();
+    settings.sort();
+    for (name, value) in settings {
+        text.push(Line::from(vec![
+            Span::styled("Sets     ", Style::default().fg(Color::Cyan)),
+            Span::raw(format!(
+                "{}={}",
+                name,
+                shown_value(name, value, app.config.logging.secrets)
+            )),
+        ]));
+    }
+    text.extend([
+        Line::from(""),
+        Line::from(
+            "Trust this project file? Its profile pin applies either way; its settings \
+             are only added to launches once trusted.",
+        ),
+        Line::from(""),
         Line::from(vec![
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
-            Span::styled("^/v", Style::default().fg(Color::Cyan)),
-            Span::styled("] Navigate  ", Style::default().fg(Color::DarkGray)),
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
-            Span::styled("Enter", Style::default().fg(Color::Cyan)),
-            Span::styled("] Launch  ", Style::default().fg(Color::DarkGray)),
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
-            Span::styled("?", Style::default().fg(Color::Cyan)),
-            Span::styled("] Help  ", Style::default().fg(Color::DarkGray)),
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
-            Span::styled("e", Style::default().fg(Color::Cyan)),
-            Span::styled("] Edit  ", Style::default().fg(Color::DarkGray)),
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(" Trust  "),
             Span::styled("n", Style::default().fg(Color::Cyan)),
-            Span::styled("] New  ", Style::default().fg(Color::DarkGray)),
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
-            Span::styled("d", Style::default().fg(Color::Cyan)),
-            Span::styled("] Delete  ", Style::default().fg(Color::DarkGray)),
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
-            Span::styled("r", Style::default().fg(Color::Cyan)),
-            Span::styled("] Reset  ", Style::default().fg(Color::DarkGray)),
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
-            Span::styled("R", Style::default().fg(Color::Cyan)),
-            Span::styled("] Reset All  ", Style::default().fg(Color::DarkGray)),
-            Span::styled("[", Style::default().fg(Color::DarkGray)),
-            Span::styled("q", Style::default().fg(Color::Cyan)),
-            Span::styled("] Quit", Style::default().fg(Color::DarkGray)),
-        ])
+            Span::raw(" Ignore settings"),
+        ]),
+    ]);
+
+    let popup = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Project profile pin ")
+            .style(Style::default().bg(Color::Black)),
+    );
+    frame.render_widget(popup, area);
+}
+
+fn render_confirm_cost(frame: &mut Frame, app: &App, area: Rect) {
+    let Some((profile, estimate)) = &app.pending_launch else {
+        return;
     };
+    frame.render_widget(Clear, area);
 
-    let footer = Paragraph::new(footer_text).block(Block::default().borders(Borders::TOP));
-    frame.render_widget(footer, area);
+    let last = &estimate.last_session;
+    let text = vec![
+        Line::from(vec![
+            Span::styled("Model         ", Style::default().fg(Color::Cyan)),
+            Span::raw(format!(
+                "{} (${:.2} in / ${:.2} out per million tokens)",
+                estimate.model, estimate.price.input, estimate.price.output
+            )),
+        ]),
+        Line::from(vec![
+            Span::styled("Last session  ", Style::default().fg(Color::Cyan)),
+            Span::raw(format!(
+                "~${:.2} for {} requests, {} in / {} out tokens",
+                estimate.last_session_usd, last.requests, last.input_tokens, last.output_tokens
+            )),
+        ]),
+        Line::from(vec![
+            Span::styled("Projected     ", Style::default().fg(Color::Cyan)),
+            Span::styled(
+                format!("~${:.2} for a session like it", estimate.projected_usd),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(""),
+        Line::from(format!("Launch '{}' anyway?", profile.name)),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(" Launch  "),
+            Span::styled("n", Style::default().fg(Color::Cyan)),
+            Span::raw(" Cancel"),
+        ]),
+    ];
+
+    let popup = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Expensive launch ")
+            .style(Style::default().bg(Color::Black)),
+    );
+    frame.render_widget(popup, area);
+}
+
+fn render_confirm_reset_oauth(frame: &mut Frame, area: Rect) {
+    frame.render_widget(Clear, area);
+
+    let status = if crate::openai_oauth::has_stored_tokens() {
+        "Signed in to ChatGPT. Clearing the stored tokens signs out every Codex profile."
+    } else {
+        "No ChatGPT sign-in is stored."
+    };
+    let text = vec![
+        Line::from(status),
+        Line::from(""),
+        Line::from("The next Codex launch opens the browser to sign in again."),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(" Sign out  "),
+            Span::styled("n", Style::default().fg(Color::Cyan)),
+            Span::raw(" Cancel"),
+        ]),
+    ];
+
+    let popup = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Reset OAuth ")
+            .style(Style::default().bg(Color::Black)),
+    );
+    frame.render_widget(popup, area);
 }
 
 fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field: usize) {
@@ -249,23 +707,34 @@ fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field:
     });
 
     let desc_width = inner_area.width.saturating_sub(2);
-    let desc_lines = estimate_line_count(app.description_input.value(), desc_width);
-    let desc_height = (desc_lines + 2).max(3);
+    app.description_input.set_width(desc_width);
+    let desc_rows = app.description_input.rows(desc_width);
+    let desc_height = desc_rows.len().clamp(1, DESCRIPTION_MAX_ROWS) as u16 + 2;
+    let env_rows = app.env_editor.rows();
+    let env_height = env_rows.len().clamp(1, ENV_EDITOR_MAX_ROWS) as u16 + 2;
 
+    let mut constraints = vec![
+        Constraint::Length(3),           // Name
+        Constraint::Length(desc_height), // Description
+        Constraint::Length(3),           // API Key
+        Constraint::Length(3),           // URL
+        Constraint::Length(3),           // Proxy Target URL
+        Constraint::Length(3),           // Haiku
+        Constraint::Length(3),           // Sonnet
+        Constraint::Length(3),           // Opus
+        Constraint::Length(env_height),  // Other variables
+    ];
+    if app.show_advanced {
+        constraints.extend([
+            Constraint::Length(3), // Haiku sampling
+            Constraint::Length(3), // Sonnet sampling
+            Constraint::Length(3), // Opus sampling
+        ]);
+    }
+    constraints.push(Constraint::Min(0)); // Spacer
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3), // Name
-            Constraint::Length(desc_height), // Description
-            Constraint::Length(3), // API Key
-            Constraint::Length(3), // URL
-            Constraint::Length(3), // Proxy Target URL
-            Constraint::Length(3), // Haiku
-            Constraint::Length(3), // Sonnet
-            Constraint::Length(3), // Opus
-            Constraint::Min(1),    // Spacer
-            Constraint::Length(1), // Help
-        ])
+        .constraints(constraints)
         .split(inner_area);
 
     render_edit_field(
@@ -274,22 +743,30 @@ fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field:
         "Profile Name",
         app.name_input.value(),
         focused_field == EDIT_FIELD_NAME,
-        false,
     );
-    render_edit_field(
-        frame,
+    let (desc_cursor_x, desc_cursor_y) = app.description_input.cursor_position(desc_width);
+    let desc_scroll = desc_cursor_y.saturating_sub(DESCRIPTION_MAX_ROWS as u16 - 1);
+    let desc_lines: Vec<Line> = desc_rows
+        .iter()
+        .map(|row| Line::from(app.description_input.line_text(row)))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(desc_lines)
+            .scroll((desc_scroll, 0))
+            .block(edit_field_block(
+                "Description",
+                focused_field == EDIT_FIELD_DESCRIPTION,
+            )),
         chunks[1],
-        "Description",
-        app.description_input.value(),
-        focused_field == EDIT_FIELD_DESCRIPTION,
-        true,
     );
 
-    let api_key_value: Cow<'_, str> = if app.reveal_api_key {
-        Cow::Borrowed(app.api_key_input.value())
-    } else {
-        Cow::Owned("*".repeat(app.api_key_input.value().len()))
-    };
+    // A keychain reference is not a secret, so it is always shown
+    let api_key_value: Cow<'_, str> =
+        if app.reveal_api_key || keychain_reference(app.api_key_input.value()).is_some() {
+            Cow::Borrowed(app.api_key_input.value())
+        } else {
+            Cow::Owned("*".repeat(app.api_key_input.value().len()))
+        };
 
     render_edit_field(
         frame,
@@ -297,7 +774,6 @@ fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field:
         ENV_AUTH_TOKEN,
         api_key_value.as_ref(),
         focused_field == EDIT_FIELD_API_KEY,
-        false,
     );
     render_edit_field(
         frame,
@@ -305,7 +781,6 @@ fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field:
         ENV_BASE_URL,
         app.url_input.value(),
         focused_field == EDIT_FIELD_URL,
-        false,
     );
     render_edit_field(
         frame,
@@ -313,7 +788,6 @@ fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field:
         ENV_PROXY_TARGET_URL,
         app.proxy_url_input.value(),
         focused_field == EDIT_FIELD_PROXY_URL,
-        false,
     );
     render_edit_field(
         frame,
@@ -321,7 +795,6 @@ fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field:
         ENV_DEFAULT_HAIKU_MODEL,
         app.haiku_model_input.value(),
         focused_field == EDIT_FIELD_HAIKU,
-        false,
     );
     render_edit_field(
         frame,
@@ -329,7 +802,6 @@ fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field:
         ENV_DEFAULT_SONNET_MODEL,
         app.sonnet_model_input.value(),
         focused_field == EDIT_FIELD_SONNET,
-        false,
     );
     render_edit_field(
         frame,
@@ -337,60 +809,117 @@ fn render_edit_profile(frame: &mut Frame, app: &App, area: Rect, focused_field:
         ENV_DEFAULT_OPUS_MODEL,
         app.opus_model_input.value(),
         focused_field == EDIT_FIELD_OPUS,
-        false,
     );
 
-    let is_model_field = matches!(
-        focused_field,
-        EDIT_FIELD_HAIKU | EDIT_FIELD_SONNET | EDIT_FIELD_OPUS
-    );
-    let show_model_picker_hint = is_model_field && app.is_codex_profile() && !app.codex_models.is_empty();
-
-    let help_text = if show_model_picker_hint {
-        Line::from(vec![
-            Span::styled("Tab", Style::default().fg(Color::Cyan)),
-            Span::raw(" Switch  "),
-            Span::styled("Enter", Style::default().fg(Color::Green)),
-            Span::raw(" Pick Model  "),
-            Span::styled("Esc", Style::default().fg(Color::Cyan)),
-            Span::raw(" Cancel"),
-        ])
+    // Every other variable, one `KEY=VALUE` row each
+    let env_focused = focused_field == EDIT_FIELD_ENV;
+    let env_selected = app.env_editor.selected();
+    let env_scroll = env_selected.saturating_sub(ENV_EDITOR_MAX_ROWS - 1);
+    let env_lines: Vec<Line> = if env_rows.is_empty() {
+        vec![Line::from(Span::styled(
+            "None; type KEY=VALUE to add one",
+            Style::default().fg(Color::DarkGray),
+        ))]
     } else {
-        Line::from(vec![
-            Span::styled("Tab", Style::default().fg(Color::Cyan)),
-            Span::raw(" Switch  "),
-            Span::styled("Ctrl+G", Style::default().fg(Color::Cyan)),
-            Span::raw(" Toggle Reveal  "),
-            Span::styled("Enter", Style::default().fg(Color::Cyan)),
-            Span::raw(" Save  "),
-            Span::styled("Esc", Style::default().fg(Color::Cyan)),
-            Span::raw(" Cancel"),
-        ])
-    };
-    frame.render_widget(Paragraph::new(help_text), chunks[9]);
-
-    // Calculate wrapped cursor for description if focused
-    let (desc_cursor_x, desc_cursor_y) = if focused_field == EDIT_FIELD_DESCRIPTION {
-        calculate_wrapped_cursor(
-            app.description_input.value(),
-            app.description_input.visual_cursor(),
-            chunks[1].width.saturating_sub(2),
-        )
-    } else {
-        (app.description_input.visual_cursor() as u16, 0)
+        env_rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                if env_focused && index == env_selected {
+                    return Line::from(Span::styled(
+                        row.value(),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ));
+                }
+                let text = match row.value().split_once('=') {
+                    Some((key, value)) if !app.reveal_api_key => format!(
+                        "{}={}",
+                        key,
+                        shown_value(key.trim(), value, app.config.logging.secrets)
+                    ),
+                    _ => row.value().to_string(),
+                };
+                Line::from(Span::styled(text, Style::default().fg(Color::Gray)))
+            })
+            .collect()
     };
+    frame.render_widget(
+        Paragraph::new(env_lines)
+            .scroll((env_scroll as u16, 0))
+            .block(edit_field_block(
+                &format!("Other variables ({})", env_rows.len()),
+                env_focused,
+            )),
+        chunks[EDIT_FIELD_ENV],
+    );
+
+    // Advanced: per-tier sampling, e.g. `temperature=0.7 top_p=0.8`
+    if app.show_advanced {
+        let sampling_fields = [
+            (
+                EDIT_FIELD_HAIKU_SAMPLING,
+                ENV_HAIKU_SAMPLING,
+                &app.haiku_sampling_input,
+            ),
+            (
+                EDIT_FIELD_SONNET_SAMPLING,
+                ENV_SONNET_SAMPLING,
+                &app.sonnet_sampling_input,
+            ),
+            (
+                EDIT_FIELD_OPUS_SAMPLING,
+                ENV_OPUS_SAMPLING,
+                &app.opus_sampling_input,
+            ),
+        ];
+        for (field, key, input) in sampling_fields {
+            render_edit_field(
+                frame,
+                chunks[field],
+                key,
+                input.value(),
+                focused_field == field,
+            );
+        }
+    }
 
     // Set cursor
-    let cursor_positions = [
+    let mut cursor_positions = vec![
         (chunks[0], app.name_input.visual_cursor() as u16, 0),
-        (chunks[1], desc_cursor_x, desc_cursor_y),
+        (chunks[1], desc_cursor_x, desc_cursor_y - desc_scroll),
         (chunks[2], app.api_key_input.visual_cursor() as u16, 0),
         (chunks[3], app.url_input.visual_cursor() as u16, 0),
         (chunks[4], app.proxy_url_input.visual_cursor() as u16, 0),
         (chunks[5], app.haiku_model_input.visual_cursor() as u16, 0),
         (chunks[6], app.sonnet_model_input.visual_cursor() as u16, 0),
         (chunks[7], app.opus_model_input.visual_cursor() as u16, 0),
+        (
+            chunks[8],
+            env_rows
+                .get(env_selected)
+                .map_or(0, |row| row.visual_cursor() as u16),
+            (env_selected - env_scroll) as u16,
+        ),
     ];
+    if app.show_advanced {
+        cursor_positions.extend([
+            (
+                chunks[9],
+                app.haiku_sampling_input.visual_cursor() as u16,
+                0,
+            ),
+            (
+                chunks[10],
+                app.sonnet_sampling_input.visual_cursor() as u16,
+                0,
+            ),
+            (
+                chunks[11],
+                app.opus_sampling_input.visual_cursor() as u16,
+                0,
+            ),
+        ]);
+    }
     if let Some((chunk, cursor_x, cursor_y)) = cursor_positions.get(focused_field) {
         frame.set_cursor_position((chunk.x + *cursor_x + 1, chunk.y + 1 + *cursor_y));
     }
@@ -423,39 +952,28 @@ fn field_border_style(focused: bool) -> Style {
     }
 }
 
-fn render_edit_field(
-    frame: &mut Frame,
-    area: Rect,
-    title: &str,
-    value: &str,
-    focused: bool,
-    multiline: bool,
-) {
+fn edit_field_block(title: &str, focused: bool) -> Block<'_> {
     let title_line = Line::from(vec![Span::raw(" "), Span::raw(title), Span::raw(" ")]);
-    let mut paragraph = Paragraph::new(value).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title(title_line)
-            .border_style(field_border_style(focused)),
-    );
-
-    if multiline {
-        paragraph = paragraph.wrap(Wrap { trim: true });
-    }
-
-    frame.render_widget(paragraph, area);
+    Block::default()
+        .borders(Borders::ALL)
+        .title(title_line)
+        .border_style(field_border_style(focused))
 }
 
-fn is_sensitive_key(key: &str) -> bool {
-    let upper = key.to_ascii_uppercase();
-    upper.contains("TOKEN") || upper.contains("KEY") || upper.contains("SECRET")
+fn render_edit_field(frame: &mut Frame, area: Rect, title: &str, value: &str, focused: bool) {
+    frame.render_widget(
+        Paragraph::new(value).block(edit_field_block(title, focused)),
+        area,
+    );
 }
 
-fn mask_value(value: &str) -> String {
-    if value.len() > 8 {
-        format!("{}...{}", &value[..4], &value[value.len() - 4..])
+/// A setting as shown on screen, masked when it holds a credential; keychain
+/// references are not secret themselves
+fn shown_value(key: &str, value: &str, mask: SecretMask) -> String {
+    if is_sensitive_key(key) && keychain_reference(value).is_none() {
+        mask.mask(value)
     } else {
-        "****".to_string()
+        value.to_string()
     }
 }
 
@@ -476,130 +994,64 @@ fn render_model_picker(frame: &mut Frame, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Min(3), // Model list
-            Constraint::Length(1), // Help text
+            Constraint::Length(1), // Filter
+            Constraint::Min(3),    // Model list
         ])
         .split(inner_area);
 
-    // Render model list
-    let models: Vec<Line> = app
-        .codex_models
-        .iter()
-        .enumerate()
-        .map(|(i, model)| {
-            let is_selected = i == app.model_picker_index;
-            let prefix = if is_selected { "▸ " } else { "  " };
-            let style = if is_selected {
-                Style::default().fg(Color::Cyan)
-            } else {
-                Style::default()
-            };
-            Line::from(Span::styled(format!("{}{}", prefix, model), style))
-        })
-        .collect();
-
-    let list = Paragraph::new(models).block(Block::default());
-    frame.render_widget(list, chunks[0]);
-
-    // Help text
-    let help_text = Line::from(vec![
-        Span::styled("↑/↓", Style::default().fg(Color::Cyan)),
-        Span::raw(" Navigate  "),
-        Span::styled("Enter", Style::default().fg(Color::Cyan)),
-        Span::raw(" Select  "),
-        Span::styled("Esc", Style::default().fg(Color::Cyan)),
-        Span::raw(" Cancel"),
+    let filter_line = Line::from(vec![
+        Span::styled("Filter: ", Style::default().fg(Color::DarkGray)),
+        Span::raw(app.model_picker_query.as_str()),
     ]);
-    frame.render_widget(Paragraph::new(help_text), chunks[1]);
-}
-
-/// A word chunk with its position and length information for text wrapping
-struct WordChunk {
-    word_start: usize,
-    word_len: usize,
-    chunk_len: usize, // word + trailing spaces
-}
-
-/// Iterate through text producing word chunks for wrapping calculation.
-/// This matches ratatui's simple wrapping logic (split by whitespace).
-fn iter_word_chunks(chars: &[char]) -> impl Iterator<Item = WordChunk> + '_ {
-    let mut i = 0;
-    std::iter::from_fn(move || {
-        if i >= chars.len() {
-            return None;
-        }
-        let word_start = i;
-        // Find word end
-        while i < chars.len() && !chars[i].is_whitespace() {
-            i += 1;
-        }
-        let word_len = i - word_start;
-        // Find trailing spaces
-        while i < chars.len() && chars[i].is_whitespace() {
-            i += 1;
-        }
-        let chunk_len = i - word_start;
-        Some(WordChunk { word_start, word_len, chunk_len })
-    })
-}
-
-/// Calculate the cursor position (col, row) for wrapped text.
-fn calculate_wrapped_cursor(text: &str, cursor_idx: usize, max_width: u16) -> (u16, u16) {
-    if max_width == 0 {
-        return (0, 0);
-    }
-    let max_width = max_width as usize;
-    let chars: Vec<char> = text.chars().collect();
-
-    if cursor_idx == 0 || chars.is_empty() {
-        return (0, 0);
-    }
-
-    let mut col = 0;
-    let mut current_row = 0;
+    frame.render_widget(Paragraph::new(filter_line), chunks[0]);
+    frame.set_cursor_position((
+        chunks[0].x + "Filter: ".len() as u16 + app.model_picker_query.chars().count() as u16,
+        chunks[0].y,
+    ));
 
-    for chunk in iter_word_chunks(&chars) {
-        // Wrap to next line if word doesn't fit
-        if col > 0 && col + chunk.word_len > max_width {
-            current_row += 1;
-            col = 0;
+    // Render model list, grouped by family
+    let filtered = app.filtered_models();
+    let mut models: Vec<Line> = Vec::new();
+    let mut selected_line = 0;
+    let mut current_group = String::new();
+    for (i, model) in filtered.iter().enumerate() {
+        let group = app.picker_group_label(model);
+        if group != current_group {
+            models.push(Line::from(Span::styled(
+                group.clone(),
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+            )));
+            current_group = group;
         }
-        // Check if cursor is in this chunk
-        let chunk_end = chunk.word_start + chunk.chunk_len;
-        if cursor_idx >= chunk.word_start && cursor_idx <= chunk_end {
-            let offset = cursor_idx - chunk.word_start;
-            return ((col + offset) as u16, current_row);
+        let is_selected = i == app.model_picker_index;
+        if is_selected {
+            selected_line = models.len();
         }
-        col += chunk.chunk_len;
-    }
-
-    // Cursor at the very end
-    if cursor_idx == chars.len() {
-        return (col as u16, current_row);
-    }
-    (0, 0)
-}
-
-fn estimate_line_count(text: &str, max_width: u16) -> u16 {
-    if max_width == 0 {
-        return 1;
+        let prefix = if is_selected { "▸ " } else { "  " };
+        let style = if is_selected {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        };
+        models.push(Line::from(Span::styled(
+            format!("{}{}", prefix, model),
+            style,
+        )));
     }
-    let chars: Vec<char> = text.chars().collect();
-    if chars.is_empty() {
-        return 1;
+    if filtered.is_empty() {
+        models.push(Line::from(Span::styled(
+            "No models match",
+            Style::default().fg(Color::DarkGray),
+        )));
     }
 
-    let mut lines = 1;
-    let mut col = 0;
-
-    for chunk in iter_word_chunks(&chars) {
-        if col > 0 && col + chunk.word_len > max_width as usize {
-            lines += 1;
-            col = 0;
-        }
-        col += chunk.chunk_len;
-    }
-    lines
+    // Keep the highlighted model visible
+    let visible = chunks[1].height as usize;
+    let scroll = (selected_line + 1).saturating_sub(visible) as u16;
+    let list = Paragraph::new(models)
+        .block(Block::default())
+        .scroll((scroll, 0));
+    frame.render_widget(list, chunks[1]);
 }
-
-
@@ -3,23 +3,225 @@ use ratatui::{
     layout::Rect,
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem},
+    widgets::{Block, Borders, List, ListItem, ListState},
 };
+use std::collections::HashMap;
 
-use crate::app::App;
+use crate::app::{App, AppMode};
+use crate::config::{Config, Date, KeyExpiry, ListView, Profile, Provider};
+use crate::health::{Health, HealthChecks};
+
+/// Widest name column in the compact view before names are truncated
+const COMPACT_NAME_WIDTH: usize = 24;
+
+fn provider_color(provider: Provider) -> Color {
+    match provider {
+        Provider::Anthropic => Color::Rgb(217, 119, 87),
+        Provider::ZAi => Color::Blue,
+        Provider::MiniMax => Color::Magenta,
+        Provider::OpenRouter => Color::Rgb(130, 110, 255),
+        Provider::Codex => Color::LightGreen,
+        Provider::OpenAi => Color::Green,
+        Provider::Bedrock => Color::Yellow,
+        Provider::Local => Color::Cyan,
+        Provider::Mock => Color::LightMagenta,
+        Provider::Custom => Color::Gray,
+    }
+}
 
 pub fn render_profile_list(frame: &mut Frame, app: &mut App, area: Rect) {
+    let visible = app.visible_profiles();
+    let items = match app.config.list_view {
+        ListView::Detailed => {
+            detailed_items(&app.config, &visible, &app.last_upstream, &app.health, area)
+        }
+        ListView::Compact => compact_items(&app.config, &visible, &app.health),
+    };
+
+    let title = if app.mode == AppMode::TagFilter {
+        format!("Profiles  tags: {}_", app.tag_filter)
+    } else if !app.tag_filter.is_empty() {
+        format!(
+            "Profiles  tags: {} ({} of {}, / to change)",
+            app.tag_filter,
+            visible.len(),
+            app.config.profiles.len()
+        )
+    } else {
+        "Profiles".to_string()
+    };
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::TOP).title(title))
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+
+    // The list state indexes all profiles; the widget only sees the visible ones
+    let mut state = ListState::default()
+        .with_offset(app.list_state.offset())
+        .with_selected(
+            app.list_state
+                .selected()
+                .and_then(|i| visible.iter().position(|&v| v == i)),
+        );
+    frame.render_stateful_widget(list, area, &mut state);
+    *app.list_state.offset_mut() = state.offset();
+}
+
+/// ` #work #local` after a profile's name
+fn tag_span(profile: &Profile) -> Option<Span<'static>> {
+    (!profile.tags.is_empty()).then(|| {
+        let tags: Vec<String> = profile.tags.iter().map(|tag| format!("#{}", tag)).collect();
+        Span::styled(
+            format!("  {}", tags.join(" ")),
+            Style::default().fg(Color::DarkGray),
+        )
+    })
+}
+
+/// Yellow for a key about to expire, red for one that has
+pub(super) fn expiry_color(expiry: &KeyExpiry) -> Color {
+    match expiry {
+        KeyExpiry::Soon { .. } => Color::Yellow,
+        KeyExpiry::Expired { .. } => Color::Red,
+    }
+}
+
+/// `  key expires in 3 days` after a profile's name, while its key needs rotating
+fn expiry_span(profile: &Profile, today: Date) -> Option<Span<'static>> {
+    profile.key_expiry(today).map(|expiry| {
+        Span::styled(
+            format!("  {}", expiry.label()),
+            Style::default().fg(expiry_color(&expiry)),
+        )
+    })
+}
+
+/// Dot before a profile's name: green when its upstream answered, red when it did
+/// not, yellow while checking, blank without a result
+fn health_span(health: &HealthChecks, profile: &Profile) -> Span<'static> {
+    let color = match health.status(profile).map(|result| &result.health) {
+        Some(Health::Up { .. }) => Color::Green,
+        Some(Health::Down(_)) => Color::Red,
+        Some(Health::Checking) => Color::Yellow,
+        None => return Span::raw("  "),
+    };
+    Span::styled("● ", Style::default().fg(color))
+}
+
+/// `3d ago`-style age of a launch timestamp
+fn format_last_used(launched: Option<u64>, now: u64) -> String {
+    let Some(launched) = launched else {
+        return "never".to_string();
+    };
+    let age = now.saturating_sub(launched);
+    match age {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", age / 60),
+        3600..86400 => format!("{}h ago", age / 3600),
+        _ => format!("{}d ago", age / 86400),
+    }
+}
+
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return format!("{:<width$}", text, width = width);
+    }
+    let truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    format!("{}~", truncated)
+}
+
+/// One row per profile: name, provider, model, last used
+fn compact_items(
+    config: &Config,
+    visible: &[usize],
+    health: &HealthChecks,
+) -> Vec<ListItem<'static>> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let today = Date::today();
+    let name_width = visible
+        .iter()
+        .map(|&i| config.profiles[i].name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(COMPACT_NAME_WIDTH);
+
+    visible
+        .iter()
+        .map(|&i| {
+            let profile = &config.profiles[i];
+            let provider = profile.provider();
+            let last_used = format_last_used(config.last_used.get(&profile.name).copied(), now);
+            let mut line = Line::from(vec![
+                health_span(health, profile),
+                Span::styled(
+                    format!("{}  ", fit(&profile.name, name_width)),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!("{:<12}", provider.label()),
+                    Style::default().fg(provider_color(provider)),
+                ),
+                Span::styled(
+                    format!("{}  ", fit(profile.primary_model().unwrap_or("-"), 28)),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::styled(last_used, Style::default().fg(Color::DarkGray)),
+            ]);
+            if let Some(tags) = tag_span(profile) {
+                line.push_span(tags);
+            }
+            if let Some(expiry) = expiry_span(profile, today) {
+                line.push_span(expiry);
+            }
+            ListItem::new(line)
+        })
+        .collect()
+}
+
+/// Name and provider with the wrapped description below
+fn detailed_items<'a>(
+    config: &'a Config,
+    visible: &[usize],
+    last_upstream: &HashMap<String, String>,
+    health: &HealthChecks,
+    area: Rect,
+) -> Vec<ListItem<'a>> {
     let list_width = area.width.saturating_sub(4) as usize; // -2 for borders/padding, extra safety
+    let today = Date::today();
 
-    let items: Vec<ListItem> = app
-        .config
-        .profiles
+    visible
         .iter()
-        .map(|profile| {
-            let name_line = Line::from(Span::styled(
-                &profile.name,
-                Style::default().add_modifier(Modifier::BOLD),
-            ));
+        .map(|&i| {
+            let profile = &config.profiles[i];
+            let provider = profile.provider();
+            let mut name_line = Line::from(vec![
+                health_span(health, profile),
+                Span::styled(
+                    format!("[{}] ", provider.label()),
+                    Style::default().fg(provider_color(provider)),
+                ),
+                Span::styled(&profile.name, Style::default().add_modifier(Modifier::BOLD)),
+            ]);
+            if let Some(tags) = tag_span(profile) {
+                name_line.push_span(tags);
+            }
+            if let Some(expiry) = expiry_span(profile, today) {
+                name_line.push_span(expiry);
+            }
+            if let Some(source) = config.included_source(profile) {
+                name_line.push_span(Span::styled(
+                    format!("  ({})", source),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
 
             let mut lines = vec![name_line];
 
@@ -28,14 +230,12 @@ pub fn render_profile_list(frame: &mut Frame, app: &mut App, area: Rect) {
             let mut current_line = String::new();
 
             for word in words {
-                if current_line.len() + word.len() + 1 > list_width {
-                    if !current_line.is_empty() {
-                        lines.push(Line::from(Span::styled(
-                            current_line.clone(),
-                            Style::default().fg(Color::Gray),
-                        )));
-                        current_line.clear();
-                    }
+                if current_line.len() + word.len() + 1 > list_width && !current_line.is_empty() {
+                    lines.push(Line::from(Span::styled(
+                        current_line.clone(),
+                        Style::default().fg(Color::Gray),
+                    )));
+                    current_line.clear();
                 }
                 if !current_line.is_empty() {
                     current_line.push(' ');
@@ -49,19 +249,15 @@ pub fn render_profile_list(frame: &mut Frame, app: &mut App, area: Rect) {
                 )));
             }
 
+            if let Some(upstream) = last_upstream.get(&profile.name) {
+                lines.push(Line::from(Span::styled(
+                    format!("upstream: {}", upstream),
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
+
             lines.push(Line::from("")); // Spacer
             ListItem::new(lines)
         })
-        .collect();
-
-    let list = List::new(items)
-        .block(Block::default().borders(Borders::TOP).title("Profiles"))
-        .highlight_style(
-            Style::default()
-                .bg(Color::DarkGray)
-                .add_modifier(Modifier::BOLD),
-        )
-        .highlight_symbol(">> ");
-
-    frame.render_stateful_widget(list, area, &mut app.list_state);
+        .collect()
 }
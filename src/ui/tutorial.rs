@@ -0,0 +1,82 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+
+use crate::app::{TUTORIAL_STEPS, TutorialRegion};
+
+/// Areas of the main screen a tutorial step can point at
+pub struct TutorialAreas {
+    pub profile_list: Rect,
+    pub details: Rect,
+    pub footer: Rect,
+}
+
+pub fn render_tutorial(frame: &mut Frame, step: usize, areas: &TutorialAreas, popup: Rect) {
+    let Some(current) = TUTORIAL_STEPS.get(step) else {
+        return;
+    };
+    let highlight = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+
+    // Outline the region this step talks about
+    match current.region {
+        TutorialRegion::ProfileList | TutorialRegion::Details => {
+            let area = if current.region == TutorialRegion::ProfileList {
+                areas.profile_list
+            } else {
+                areas.details
+            };
+            frame.render_widget(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(highlight),
+                area,
+            );
+        }
+        // The footer is too short for a border; recolor it instead
+        TutorialRegion::Footer => frame.buffer_mut().set_style(areas.footer, highlight),
+    }
+
+    frame.render_widget(Clear, popup);
+
+    let text = vec![
+        Line::from(""),
+        Line::from(Span::raw(current.body)),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("[", Style::default().fg(Color::DarkGray)),
+            Span::styled("Enter/→", Style::default().fg(Color::Cyan)),
+            Span::styled("] Next  ", Style::default().fg(Color::DarkGray)),
+            Span::styled("[", Style::default().fg(Color::DarkGray)),
+            Span::styled("←", Style::default().fg(Color::Cyan)),
+            Span::styled("] Back  ", Style::default().fg(Color::DarkGray)),
+            Span::styled("[", Style::default().fg(Color::DarkGray)),
+            Span::styled("Esc", Style::default().fg(Color::Cyan)),
+            Span::styled("] Skip tour", Style::default().fg(Color::DarkGray)),
+        ]),
+    ];
+
+    let title = format!(
+        " Tour {}/{}: {} ",
+        step + 1,
+        TUTORIAL_STEPS.len(),
+        current.title
+    );
+    let tour = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(highlight)
+                .title(title)
+                .style(Style::default().bg(Color::Black)),
+        )
+        .style(Style::default().bg(Color::Black));
+
+    frame.render_widget(tour, popup);
+}
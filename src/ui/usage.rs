@@ -0,0 +1,222 @@
+//! Usage dashboard: tokens per day as a bar chart, lifetime totals per profile and
+//! per upstream model, and launch patterns when local analytics are on.
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Clear, Paragraph, Row, Table},
+};
+
+use crate::analytics::{Analytics, ENV_NO_ANALYTICS, WEEKDAYS};
+use crate::app::App;
+use crate::usage::Counters;
+
+/// Days shown in the chart
+const CHART_DAYS: u64 = 14;
+
+/// `1234567` as `1.2M`
+fn compact(value: u64) -> String {
+    match value {
+        0..1_000 => value.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", value as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1}M", value as f64 / 1e6),
+        _ => format!("{:.1}B", value as f64 / 1e9),
+    }
+}
+
+/// Counts as a row of block characters scaled to the largest
+fn sparkline(counts: &[u64]) -> String {
+    const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = counts.iter().copied().max().unwrap_or_default().max(1);
+    counts
+        .iter()
+        .map(|&count| match count {
+            0 => '·',
+            _ => LEVELS[(count * 7 / max) as usize],
+        })
+        .collect()
+}
+
+/// `work ×20, local ×12`
+fn top_list(counts: &[(&str, u64)]) -> String {
+    counts
+        .iter()
+        .map(|(name, count)| format!("{} ×{}", name, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn patterns_lines(app: &App) -> Vec<Line<'static>> {
+    let label = |text: &str| Span::styled(format!("{:<9}", text), Style::default().fg(Color::Cyan));
+    let dim = |text: String| Line::from(Span::styled(text, Style::default().fg(Color::DarkGray)));
+    let Some(analytics) = &app.analytics else {
+        return vec![dim(if app.config.analytics.enabled {
+            format!("Off: {} is set.", ENV_NO_ANALYTICS)
+        } else {
+            "Off. Set `enabled = true` under [analytics] in profiles.toml to count launches \
+             (kept on this machine only)."
+                .to_string()
+        })];
+    };
+    if analytics.launches == 0 {
+        return vec![dim("No launches counted yet.".to_string())];
+    }
+
+    let mut summary = format!("{} launches", analytics.launches);
+    if let Some(hour) = Analytics::busiest(&analytics.hours) {
+        summary.push_str(&format!(", busiest at {:02}:00 UTC", hour));
+    }
+    if let Some(day) = Analytics::busiest(&analytics.weekdays) {
+        summary.push_str(&format!(", mostly on {}", WEEKDAYS[day]));
+    }
+    let days = WEEKDAYS
+        .iter()
+        .zip(analytics.weekdays)
+        .map(|(day, count)| format!("{} {}", day, count))
+        .collect::<Vec<_>>()
+        .join("  ");
+    vec![
+        Line::from(vec![label("Launches"), Span::raw(summary)]),
+        Line::from(vec![
+            label("Hours"),
+            Span::raw(format!("00 {} 23", sparkline(&analytics.hours))),
+        ]),
+        Line::from(vec![label("Days"), Span::raw(days)]),
+        Line::from(vec![
+            label("Profiles"),
+            Span::raw(top_list(&Analytics::top(&analytics.profiles, 3))),
+        ]),
+        Line::from(vec![
+            label("Models"),
+            Span::raw(top_list(&Analytics::top(&analytics.models, 3))),
+        ]),
+    ]
+}
+
+fn totals_table<'a>(title: &'a str, rows: &[(String, Counters)]) -> Table<'a> {
+    let header = Row::new(["", "Requests", "In", "Out", "Cost"]).style(
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    );
+    let rows = rows.iter().map(|(name, counters)| {
+        Row::new([
+            name.clone(),
+            counters.requests.to_string(),
+            compact(counters.input_tokens),
+            compact(counters.output_tokens),
+            format!("${:.2}", counters.cost_usd),
+        ])
+    });
+    Table::new(
+        rows,
+        [
+            Constraint::Min(12),
+            Constraint::Length(8),
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Length(9),
+        ],
+    )
+    .header(header)
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} ", title)),
+    )
+}
+
+pub fn render_usage(frame: &mut Frame, app: &App, area: Rect) {
+    frame.render_widget(Clear, area);
+
+    let total = app.usage.total();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            " Usage: {} requests, {} in / {} out tokens, ~${:.2} ",
+            total.requests,
+            compact(total.input_tokens),
+            compact(total.output_tokens),
+            total.cost_usd
+        ))
+        .style(Style::default().bg(Color::Black));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(40),
+            Constraint::Min(4),
+            Constraint::Length(7),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    if total.requests == 0 {
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                "No usage recorded yet. Token usage is counted for sessions that go through the proxy.",
+                Style::default().fg(Color::DarkGray),
+            )),
+            chunks[0],
+        );
+    } else {
+        let today = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let days = app.usage.by_day(today, CHART_DAYS);
+        let bars: Vec<Bar> = days
+            .iter()
+            .map(|(day, counters)| {
+                Bar::default()
+                    .value(counters.tokens())
+                    .text_value(compact(counters.tokens()))
+                    .label(Line::from(day[5..].to_string()))
+            })
+            .collect();
+        let bar_width = (chunks[0].width.saturating_sub(2) / CHART_DAYS as u16)
+            .saturating_sub(1)
+            .max(1);
+        let chart = BarChart::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" Tokens per day (UTC, last {} days) ", CHART_DAYS)),
+            )
+            .data(BarGroup::new(bars))
+            .bar_width(bar_width)
+            .bar_gap(1)
+            .bar_style(Style::default().fg(Color::Cyan))
+            .value_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+        frame.render_widget(chart, chunks[0]);
+    }
+
+    let tables = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[1]);
+    frame.render_widget(
+        totals_table("By profile", &app.usage.by_profile()),
+        tables[0],
+    );
+    frame.render_widget(totals_table("By model", &app.usage.by_model()), tables[1]);
+
+    frame.render_widget(
+        Paragraph::new(patterns_lines(app)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Usage patterns (local only) "),
+        ),
+        chunks[2],
+    );
+
+    let help = Line::from(vec![
+        Span::styled(" Esc", Style::default().fg(Color::Cyan)),
+        Span::styled(" Close", Style::default().fg(Color::DarkGray)),
+    ]);
+    frame.render_widget(Paragraph::new(help), chunks[3]);
+}
@@ -0,0 +1,246 @@
+//! Lifetime token counters behind the usage dashboard.
+//!
+//! Every proxied session adds its usage to `usage.json`, keyed by day, profile and
+//! upstream model. Unlike the session history, the counters are never pruned by the
+//! retention policy, so the dashboard keeps its totals. When the file does not exist
+//! yet it is started from whatever history there is.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::auth::civil_from_days;
+use crate::config::Config;
+use crate::pricing::{self, HistoryEntry};
+
+pub const USAGE_FILE: &str = "usage.json";
+
+/// Token usage summed over any number of sessions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Counters {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Estimated cost of the models that had a known price
+    pub cost_usd: f64,
+}
+
+impl Counters {
+    pub fn tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    fn add(&mut self, other: &Counters) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+/// Usage counters by day (`YYYY-MM-DD`, UTC), profile and upstream model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageCounters {
+    #[serde(default)]
+    pub days: BTreeMap<String, BTreeMap<String, BTreeMap<String, Counters>>>,
+}
+
+/// `YYYY-MM-DD` (UTC) of a unix timestamp
+pub fn utc_day(timestamp: u64) -> String {
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn usage_file() -> Option<PathBuf> {
+    Config::config_dir().map(|dir| dir.join(USAGE_FILE))
+}
+
+impl UsageCounters {
+    /// The saved counters, or counters rebuilt from the usage history
+    pub fn load() -> Self {
+        let saved = usage_file()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok());
+        saved.unwrap_or_else(|| {
+            let mut counters = Self::default();
+            for entry in pricing::read_history() {
+                counters.add_session(&entry);
+            }
+            counters
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = usage_file().context("Could not determine config directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn add_session(&mut self, entry: &HistoryEntry) {
+        let models = self
+            .days
+            .entry(utc_day(entry.timestamp))
+            .or_default()
+            .entry(entry.profile.clone())
+            .or_default();
+        for model in &entry.models {
+            models
+                .entry(model.model.clone())
+                .or_default()
+                .add(&Counters {
+                    requests: model.requests,
+                    input_tokens: model.input_tokens,
+                    output_tokens: model.output_tokens,
+                    cost_usd: model.cost_usd.unwrap_or_default(),
+                });
+        }
+    }
+
+    /// Every counter with its day, profile and model
+    fn rows(&self) -> impl Iterator<Item = (&str, &str, &str, &Counters)> {
+        self.days.iter().flat_map(|(day, profiles)| {
+            profiles.iter().flat_map(move |(profile, models)| {
+                models.iter().map(move |(model, counters)| {
+                    (day.as_str(), profile.as_str(), model.as_str(), counters)
+                })
+            })
+        })
+    }
+
+    fn totals_by<'a>(
+        &'a self,
+        key: impl Fn(&'a str, &'a str) -> &'a str,
+    ) -> Vec<(String, Counters)> {
+        let mut totals: BTreeMap<&str, Counters> = BTreeMap::new();
+        for (_, profile, model, counters) in self.rows() {
+            totals.entry(key(profile, model)).or_default().add(counters);
+        }
+        let mut totals: Vec<(String, Counters)> = totals
+            .into_iter()
+            .map(|(name, counters)| (name.to_string(), counters))
+            .collect();
+        totals.sort_by(|a, b| b.1.tokens().cmp(&a.1.tokens()).then(a.0.cmp(&b.0)));
+        totals
+    }
+
+    /// Lifetime totals per profile, most tokens first
+    pub fn by_profile(&self) -> Vec<(String, Counters)> {
+        self.totals_by(|profile, _| profile)
+    }
+
+    /// Lifetime totals per upstream model, most tokens first
+    pub fn by_model(&self) -> Vec<(String, Counters)> {
+        self.totals_by(|_, model| model)
+    }
+
+    /// Totals for each of the `count` days up to `today`, oldest first, including
+    /// days without usage
+    pub fn by_day(&self, today: u64, count: u64) -> Vec<(String, Counters)> {
+        (0..count)
+            .rev()
+            .map(|back| {
+                let day = utc_day(today.saturating_sub(back * 86_400));
+                let mut total = Counters::default();
+                for counters in self
+                    .days
+                    .get(&day)
+                    .into_iter()
+                    .flat_map(|profiles| profiles.values().flat_map(|models| models.values()))
+                {
+                    total.add(counters);
+                }
+                (day, total)
+            })
+            .collect()
+    }
+
+    pub fn total(&self) -> Counters {
+        let mut total = Counters::default();
+        for (_, _, _, counters) in self.rows() {
+            total.add(counters);
+        }
+        total
+    }
+}
+
+/// Add a finished session to `usage.json`. Call before the session is appended to
+/// the history, which a missing file is rebuilt from.
+pub fn record_session(entry: &HistoryEntry) -> Result<()> {
+    if entry.models.is_empty() {
+        return Ok(());
+    }
+    let mut counters = UsageCounters::load();
+    counters.add_session(entry);
+    counters.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::HistoryModel;
+
+    fn session(
+        timestamp: u64,
+        profile: &str,
+        model: &str,
+        input: u64,
+        output: u64,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            profile: profile.to_string(),
+            models: vec![HistoryModel {
+                model: model.to_string(),
+                requests: 1,
+                input_tokens: input,
+                output_tokens: output,
+                cost_usd: Some(0.5),
+            }],
+            project: None,
+        }
+    }
+
+    #[test]
+    fn sessions_are_counted_per_profile_model_and_day() {
+        // 2025-03-01 12:00 UTC
+        let day = 1_740_830_400;
+        let mut counters = UsageCounters::default();
+        counters.add_session(&session(day - 86_400, "OpenRouter", "gpt-5", 100, 10));
+        counters.add_session(&session(day, "OpenRouter", "gpt-5", 200, 20));
+        counters.add_session(&session(day, "Local", "qwen3-coder", 1_000, 100));
+
+        assert_eq!(utc_day(day), "2025-03-01");
+        assert_eq!(
+            counters.by_profile()[0],
+            (
+                "Local".to_string(),
+                Counters {
+                    requests: 1,
+                    input_tokens: 1_000,
+                    output_tokens: 100,
+                    cost_usd: 0.5,
+                }
+            )
+        );
+        assert_eq!(counters.by_model()[1].0, "gpt-5");
+        assert_eq!(counters.by_model()[1].1.tokens(), 330);
+
+        let days = counters.by_day(day, 3);
+        let tokens: Vec<u64> = days.iter().map(|(_, c)| c.tokens()).collect();
+        assert_eq!(days[0].0, "2025-02-27");
+        assert_eq!(tokens, vec![0, 110, 1_320]);
+        assert_eq!(counters.total().requests, 3);
+
+        let saved = serde_json::to_string(&counters).unwrap();
+        assert_eq!(
+            serde_json::from_str::<UsageCounters>(&saved).unwrap(),
+            counters
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! `.claude-profiler.toml` in a project directory pins the profiles that may be
+//! launched there, so a client repository is not accidentally opened with a personal
+//! provider. It may also carry environment settings for the project.
+//!
+//! The allow-list only ever narrows the choice and is always enforced. The settings
+//! are applied once the user has trusted the file; editing it asks again.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::auth::sha256_hex;
+use crate::config::{Config, Profile, wildcard_match};
+
+pub const PIN_FILE_NAME: &str = ".claude-profiler.toml";
+
+/// Contents of a pin file
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspacePin {
+    /// Profile names allowed in the project; `*` and `?` may be used. Empty allows all.
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    /// Profile to preselect, otherwise the first allowed one
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Environment added to every launch from the project, once trusted
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+/// A pin file found for the launch directory
+#[derive(Debug, Clone)]
+pub struct PinnedWorkspace {
+    pub path: PathBuf,
+    pub pin: WorkspacePin,
+    /// SHA-256 of the file, recorded when it is trusted
+    fingerprint: String,
+}
+
+impl PinnedWorkspace {
+    /// The pin file in `dir` or its nearest ancestor that has one
+    pub fn find(dir: &Path) -> Result<Option<Self>> {
+        let Some(path) = dir
+            .ancestors()
+            .map(|ancestor| ancestor.join(PIN_FILE_NAME))
+            .find(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(path, &content).map(Some)
+    }
+
+    fn parse(path: PathBuf, content: &str) -> Result<Self> {
+        let pin = toml::from_str(content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Self {
+            path,
+            pin,
+            fingerprint: sha256_hex(content.as_bytes()),
+        })
+    }
+
+    fn key(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        self.pin.profiles.is_empty()
+            || self
+                .pin
+                .profiles
+                .iter()
+                .any(|pattern| wildcard_match(pattern, name))
+    }
+
+    /// Index of the profile to preselect: the pinned default, else the first allowed
+    pub fn preferred_profile(&self, profiles: &[Profile]) -> Option<usize> {
+        self.pin
+            .default
+            .as_ref()
+            .and_then(|name| profiles.iter().position(|p| &p.name == name))
+            .filter(|&index| self.allows(&profiles[index].name))
+            .or_else(|| profiles.iter().position(|p| self.allows(&p.name)))
+    }
+
+    /// Whether this exact file content has been trusted
+    pub fn is_trusted(&self, config: &Config) -> bool {
+        config.trusted_workspaces.get(&self.key()) == Some(&self.fingerprint)
+    }
+
+    pub fn trust(&self, config: &mut Config) {
+        config
+            .trusted_workspaces
+            .insert(self.key(), self.fingerprint.clone());
+    }
+
+    /// Add the project's settings to a profile about to be launched
+    pub fn apply(&self, profile: &mut Profile) {
+        profile.env.extend(self.pin.env.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_restrict_profiles_and_need_trust_for_settings() {
+        let pinned = PinnedWorkspace::parse(
+            PathBuf::from("/work/client/.claude-profiler.toml"),
+            "profiles = [\"Client *\", \"zai\"]\ndefault = \"zai\"\n[env]\nAPI_TIMEOUT_MS = \"900000\"\n",
+        )
+        .unwrap();
+        assert!(pinned.allows("Client OpenAI"));
+        assert!(pinned.allows("zai"));
+        assert!(!pinned.allows("Personal OpenRouter"));
+
+        let mut config = Config::create_default();
+        let default = config.profiles.iter().position(|p| p.name == "zai");
+        assert_eq!(pinned.preferred_profile(&config.profiles), default);
+
+        assert!(!pinned.is_trusted(&config));
+        pinned.trust(&mut config);
+        assert!(pinned.is_trusted(&config));
+        let edited = PinnedWorkspace::parse(
+            pinned.path.clone(),
+            "profiles = [\"*\"]\n[env]\nANTHROPIC_BASE_URL = \"https://elsewhere\"\n",
+        )
+        .unwrap();
+        assert!(!edited.is_trusted(&config));
+
+        let mut profile = config.profiles[0].clone();
+        pinned.apply(&mut profile);
+        assert_eq!(profile.env["API_TIMEOUT_MS"], "900000");
+
+        assert!(PinnedWorkspace::parse(pinned.path.clone(), "profile = \"typo\"").is_err());
+    }
+}